@@ -9,8 +9,8 @@ use serde_json::{json, Map, Value};
 
 use stratisd::{
     engine::{
-        EncryptionInfo, KeyDescription, Name, PoolIdentifier, PoolUuid, UnlockMethod,
-        CLEVIS_TANG_TRUST_URL,
+        EncryptionConfig, EncryptionInfo, KeyDescription, Name, Pbkdf, PoolIdentifier, PoolUuid,
+        UnlockMethod, CLEVIS_TANG_TRUST_URL,
     },
     jsonrpc::client::{filesystem, key, pool, report},
     stratis::{StratisError, VERSION},
@@ -64,44 +64,41 @@ fn parse_args() -> Command<'static> {
                             .long("--prompt")
                             .takes_value(false)
                             .requires("unlock_method"),
-                    ),
-                Command::new("stop")
-                    .arg(Arg::new("id").required(true))
-                    .arg(Arg::new("name").long("--name").takes_value(false)),
-                Command::new("create")
-                    .arg(Arg::new("name").required(true))
-                    .arg(
-                        Arg::new("blockdevs")
-                            .multiple_occurrences(true)
-                            .required(true),
                     )
-                    .arg(Arg::new("key_desc").long("--key-desc").takes_value(true))
                     .arg(
-                        Arg::new("clevis")
-                            .long("--clevis")
-                            .takes_value(true)
-                            .possible_values(["nbde", "tang", "tpm2"])
-                            .requires_if("nbde", "tang_args")
-                            .requires_if("tang", "tang_args"),
-                    )
-                    .arg(
-                        Arg::new("tang_url")
-                            .long("--tang-url")
+                        Arg::new("keyfile_path")
+                            .long("--keyfile-path")
                             .takes_value(true)
-                            .required_if_eq("clevis", "nbde")
-                            .required_if_eq("clevis", "tang"),
+                            .requires("unlock_method")
+                            .conflicts_with("prompt"),
                     )
                     .arg(
-                        Arg::new("thumbprint")
-                            .long("--thumbprint")
-                            .takes_value(true),
-                    )
-                    .arg(Arg::new("trust_url").long("--trust-url").takes_value(false))
+                        Arg::new("allow_missing")
+                            .long("--allow-missing")
+                            .takes_value(false),
+                    ),
+                Command::new("stop")
+                    .arg(Arg::new("id").required(true))
+                    .arg(Arg::new("name").long("--name").takes_value(false)),
+                encryption_config_args(encryption_args(
+                    Command::new("create")
+                        .arg(Arg::new("name").required(true))
+                        .arg(
+                            Arg::new("blockdevs")
+                                .multiple_occurrences(true)
+                                .required(true),
+                        ),
+                )),
+                encryption_args(Command::new("encrypt").arg(Arg::new("name").required(true)))
                     .group(
-                        ArgGroup::new("tang_args")
-                            .arg("thumbprint")
-                            .arg("trust_url"),
+                        ArgGroup::new("encryption")
+                            .arg("key_desc")
+                            .arg("clevis")
+                            .multiple(true)
+                            .required(true),
                     ),
+                Command::new("rotate-volume-key").arg(Arg::new("name").required(true)),
+                Command::new("reencryption-progress").arg(Arg::new("uuid").required(true)),
                 Command::new("init-cache")
                     .arg(Arg::new("name").required(true))
                     .arg(
@@ -126,6 +123,12 @@ fn parse_args() -> Command<'static> {
                             .multiple_occurrences(true)
                             .required(true),
                     ),
+                Command::new("remove-cache").arg(Arg::new("name").required(true)),
+                Command::new("replace-blockdev")
+                    .arg(Arg::new("name").required(true))
+                    .arg(Arg::new("old").required(true))
+                    .arg(Arg::new("new").required(true)),
+                Command::new("replace-progress").arg(Arg::new("uuid").required(true)),
                 Command::new("destroy").arg(Arg::new("name").required(true)),
                 Command::new("is-encrypted")
                     .arg(Arg::new("name").long("--name").takes_value(false))
@@ -154,11 +157,138 @@ fn parse_args() -> Command<'static> {
                     .arg(Arg::new("pool_name").required(true))
                     .arg(Arg::new("fs_name").required(true))
                     .arg(Arg::new("new_fs_name").required(true)),
+                Command::new("revert")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").required(true))
+                    .arg(Arg::new("snapshot_name").required(true)),
+                Command::new("send")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").required(true))
+                    .arg(Arg::new("parent").long("--parent").takes_value(true))
+                    .arg(Arg::new("file").long("--file").takes_value(true)),
+                Command::new("receive")
+                    .arg(Arg::new("pool_name").required(true))
+                    .arg(Arg::new("fs_name").required(true))
+                    .arg(
+                        Arg::new("incremental")
+                            .long("--incremental")
+                            .takes_value(false),
+                    )
+                    .arg(Arg::new("file").long("--file").takes_value(true)),
             ]),
             Command::new("report"),
         ])
 }
 
+fn encryption_args(command: Command<'static>) -> Command<'static> {
+    command
+        .arg(Arg::new("key_desc").long("--key-desc").takes_value(true))
+        .arg(
+            Arg::new("clevis")
+                .long("--clevis")
+                .takes_value(true)
+                .possible_values(["nbde", "tang", "tpm2"])
+                .requires_if("nbde", "tang_args")
+                .requires_if("tang", "tang_args"),
+        )
+        .arg(
+            Arg::new("tang_url")
+                .long("--tang-url")
+                .takes_value(true)
+                .required_if_eq("clevis", "nbde")
+                .required_if_eq("clevis", "tang"),
+        )
+        .arg(
+            Arg::new("thumbprint")
+                .long("--thumbprint")
+                .takes_value(true),
+        )
+        .arg(Arg::new("trust_url").long("--trust-url").takes_value(false))
+        .group(
+            ArgGroup::new("tang_args")
+                .arg("thumbprint")
+                .arg("trust_url"),
+        )
+}
+
+fn encryption_config_args(command: Command<'static>) -> Command<'static> {
+    command
+        .arg(Arg::new("cipher").long("--cipher").takes_value(true))
+        .arg(Arg::new("key_size").long("--key-size").takes_value(true))
+        .arg(
+            Arg::new("pbkdf")
+                .long("--pbkdf")
+                .takes_value(true)
+                .possible_values(["pbkdf2", "argon2i", "argon2id"]),
+        )
+        .arg(
+            Arg::new("pbkdf_memory")
+                .long("--pbkdf-memory")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("pbkdf_iterations")
+                .long("--pbkdf-iterations")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("sector_size")
+                .long("--sector-size")
+                .takes_value(true),
+        )
+}
+
+fn get_encryption_config_from_args(
+    args: &ArgMatches,
+) -> Result<Option<EncryptionConfig>, Box<dyn Error>> {
+    fn parse_u32(args: &ArgMatches, name: &str) -> Result<Option<u32>, Box<dyn Error>> {
+        Ok(args.value_of(name).map(|s| s.parse::<u32>()).transpose()?)
+    }
+
+    let config = EncryptionConfig {
+        cipher: args.value_of("cipher").map(|s| s.to_owned()),
+        key_size: parse_u32(args, "key_size")?,
+        pbkdf: args.value_of("pbkdf").map(Pbkdf::try_from).transpose()?,
+        pbkdf_memory: parse_u32(args, "pbkdf_memory")?,
+        pbkdf_iterations: parse_u32(args, "pbkdf_iterations")?,
+        sector_size: parse_u32(args, "sector_size")?,
+    };
+    Ok(if config == EncryptionConfig::default() {
+        None
+    } else {
+        Some(config)
+    })
+}
+
+fn get_encryption_info_from_args(
+    args: &ArgMatches,
+) -> Result<Option<EncryptionInfo>, StratisError> {
+    let key_description = match args.value_of("key_desc").map(|s| s.to_owned()) {
+        Some(string) => Some(KeyDescription::try_from(string)?),
+        None => None,
+    };
+    let pin = args.value_of("clevis");
+    let clevis_info = match pin {
+        Some("nbde" | "tang") => {
+            let mut json = Map::new();
+            json.insert(
+                "url".to_string(),
+                Value::from(args.value_of("tang_url").expect("Required")),
+            );
+            if args.is_present("trust_url") {
+                json.insert(CLEVIS_TANG_TRUST_URL.to_string(), Value::from(true));
+            } else if let Some(thp) = args.value_of("thumbprint") {
+                json.insert("thp".to_string(), Value::from(thp));
+            }
+            pin.map(|p| (p.to_string(), Value::from(json)))
+        }
+        Some("tpm2") => Some(("tpm2".to_string(), json!({}))),
+        Some(_) => unreachable!("Validated by parser"),
+        None => None,
+    };
+    Ok(EncryptionInfo::from_options((key_description, clevis_info)))
+}
+
 fn get_paths_from_args(args: &ArgMatches) -> Vec<PathBuf> {
     args.values_of("blockdevs")
         .expect("required")
@@ -210,7 +340,20 @@ fn main() -> Result<(), String> {
                         "--prompt and an unlock_method of clevis are mutally exclusive".to_string(),
                     )));
                 }
-                pool::pool_start(id, unlock_method, prompt)?;
+                let keyfile_path = args.value_of("keyfile_path");
+                if keyfile_path.is_some() && unlock_method == Some(UnlockMethod::Clevis) {
+                    return Err(Box::new(StratisError::Msg(
+                        "--keyfile-path and an unlock_method of clevis are mutually exclusive"
+                            .to_string(),
+                    )));
+                }
+                pool::pool_start(
+                    id,
+                    unlock_method,
+                    args.is_present("allow_missing"),
+                    prompt,
+                    keyfile_path,
+                )?;
                 Ok(())
             } else if let Some(args) = subcommand.subcommand_matches("stop") {
                 let id = if args.is_present("name") {
@@ -226,35 +369,27 @@ fn main() -> Result<(), String> {
                 Ok(())
             } else if let Some(args) = subcommand.subcommand_matches("create") {
                 let paths = get_paths_from_args(args);
-                let key_description = match args.value_of("key_desc").map(|s| s.to_owned()) {
-                    Some(string) => Some(KeyDescription::try_from(string)?),
-                    None => None,
-                };
-                let pin = args.value_of("clevis");
-                let clevis_info = match pin {
-                    Some("nbde" | "tang") => {
-                        let mut json = Map::new();
-                        json.insert(
-                            "url".to_string(),
-                            Value::from(args.value_of("tang_url").expect("Required")),
-                        );
-                        if args.is_present("trust_url") {
-                            json.insert(CLEVIS_TANG_TRUST_URL.to_string(), Value::from(true));
-                        } else if let Some(thp) = args.value_of("thumbprint") {
-                            json.insert("thp".to_string(), Value::from(thp));
-                        }
-                        pin.map(|p| (p.to_string(), Value::from(json)))
-                    }
-                    Some("tpm2") => Some(("tpm2".to_string(), json!({}))),
-                    Some(_) => unreachable!("Validated by parser"),
-                    None => None,
-                };
                 pool::pool_create(
                     args.value_of("name").expect("required").to_string(),
                     paths,
-                    EncryptionInfo::from_options((key_description, clevis_info)),
+                    get_encryption_info_from_args(args)?,
+                    get_encryption_config_from_args(args)?,
                 )?;
                 Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("encrypt") {
+                pool::pool_encrypt(
+                    args.value_of("name").expect("required").to_string(),
+                    get_encryption_info_from_args(args)?
+                        .expect("at least one encryption method is required by the parser"),
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("rotate-volume-key") {
+                pool::pool_rotate_volume_key(args.value_of("name").expect("required").to_string())?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("reencryption-progress") {
+                let uuid = PoolUuid::parse_str(args.value_of("uuid").expect("required"))?;
+                println!("{}", pool::pool_reencryption_progress(uuid)?);
+                Ok(())
             } else if let Some(args) = subcommand.subcommand_matches("destroy") {
                 pool::pool_destroy(args.value_of("name").expect("required").to_string())?;
                 Ok(())
@@ -276,6 +411,20 @@ fn main() -> Result<(), String> {
                 let paths = get_paths_from_args(args);
                 pool::pool_add_cache(args.value_of("name").expect("required").to_string(), paths)?;
                 Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("remove-cache") {
+                pool::pool_remove_cache(args.value_of("name").expect("required").to_string())?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("replace-blockdev") {
+                pool::pool_replace_blockdev(
+                    args.value_of("name").expect("required").to_string(),
+                    PathBuf::from(args.value_of("old").expect("required")),
+                    PathBuf::from(args.value_of("new").expect("required")),
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("replace-progress") {
+                let uuid = PoolUuid::parse_str(args.value_of("uuid").expect("required"))?;
+                println!("{}", pool::pool_replace_progress(uuid)?);
+                Ok(())
             } else if let Some(args) = subcommand.subcommand_matches("is-encrypted") {
                 let id = if args.is_present("name") {
                     PoolIdentifier::Name(Name::new(
@@ -360,6 +509,31 @@ fn main() -> Result<(), String> {
                     args.value_of("new_fs_name").expect("required").to_string(),
                 )?;
                 Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("revert") {
+                filesystem::filesystem_revert(
+                    args.value_of("pool_name").expect("required").to_string(),
+                    args.value_of("fs_name").expect("required").to_string(),
+                    args.value_of("snapshot_name")
+                        .expect("required")
+                        .to_string(),
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("send") {
+                filesystem::filesystem_send(
+                    args.value_of("pool_name").expect("required").to_string(),
+                    args.value_of("fs_name").expect("required").to_string(),
+                    args.value_of("parent").map(|p| p.to_string()),
+                    args.value_of("file"),
+                )?;
+                Ok(())
+            } else if let Some(args) = subcommand.subcommand_matches("receive") {
+                filesystem::filesystem_receive(
+                    args.value_of("pool_name").expect("required").to_string(),
+                    args.value_of("fs_name").expect("required").to_string(),
+                    args.is_present("incremental"),
+                    args.value_of("file"),
+                )?;
+                Ok(())
             } else {
                 filesystem::filesystem_list()?;
                 Ok(())
@@ -9,6 +9,7 @@ use std::{
     io::{Read, Write},
     os::unix::io::AsRawFd,
     str::FromStr,
+    time::Duration,
 };
 
 use clap::{Arg, Command};
@@ -19,7 +20,7 @@ use nix::{
     unistd::getpid,
 };
 
-use stratisd::stratis::{run, StratisError, StratisResult, VERSION};
+use stratisd::stratis::{parse_fstrim_interval, run, StratisError, StratisResult, VERSION};
 
 const STRATISD_PID_PATH: &str = "/run/stratisd.pid";
 const STRATISD_MIN_PID_PATH: &str = "/run/stratisd-min.pid";
@@ -40,6 +41,14 @@ fn parse_args() -> Command<'static> {
                 .takes_value(false)
                 .help("Enables sim engine."),
         )
+        .arg(
+            Arg::new("fstrim_interval")
+                .forbid_empty_values(true)
+                .long("--fstrim-interval")
+                .value_name("HOURS")
+                .value_parser(parse_fstrim_interval)
+                .help("Periodically discards the unused blocks of all mounted filesystems, with the given number of hours between runs."),
+        )
 }
 
 /// To ensure only one instance of stratisd runs at a time, acquire an
@@ -124,7 +133,10 @@ fn main() -> Result<(), String> {
         }
         builder.init();
 
-        run(args.is_present("sim"))?;
+        run(
+            args.is_present("sim"),
+            args.get_one::<Duration>("fstrim_interval").copied(),
+        )?;
         Ok(())
     }
 
@@ -16,7 +16,9 @@ use serde_json::{json, Value};
 
 use devicemapper::{Bytes, Sectors};
 
-use stratisd::engine::{crypt_metadata_size, ThinPoolSizeParams, BDA};
+use stratisd::engine::{
+    crypt_metadata_size, ThinPoolSizeParams, BDA, DATA_BLOCK_SIZE, DEFAULT_EXTEND_POLICY,
+};
 
 #[cfg(feature = "systemd_compat")]
 use crate::generators::{stratis_clevis_setup_generator, stratis_setup_generator};
@@ -220,7 +222,11 @@ fn predict_pool_usage(
     let total_size: Sectors = device_sizes.iter().cloned().sum();
     let non_metadata_size: Sectors = stratis_avail_sizes.iter().cloned().sum();
 
-    let size_params = ThinPoolSizeParams::new(non_metadata_size)?;
+    let size_params = ThinPoolSizeParams::new(
+        non_metadata_size,
+        DATA_BLOCK_SIZE,
+        DEFAULT_EXTEND_POLICY.extend_size,
+    )?;
     let total_non_data = 2usize * size_params.meta_size() + size_params.mdv_size();
 
     let avail_size = (non_metadata_size)
@@ -9,6 +9,7 @@ use std::{
     os::unix::io::AsRawFd,
     process::exit,
     str::FromStr,
+    time::Duration,
 };
 
 use clap::{Arg, Command};
@@ -19,7 +20,7 @@ use nix::{
     unistd::getpid,
 };
 
-use stratisd::stratis::{run, StratisError, StratisResult, VERSION};
+use stratisd::stratis::{parse_fstrim_interval, run, StratisError, StratisResult, VERSION};
 
 const STRATISD_PID_PATH: &str = "/run/stratisd.pid";
 const STRATISD_MIN_PID_PATH: &str = "/run/stratisd-min.pid";
@@ -116,6 +117,14 @@ fn main() {
                 .possible_values(["trace", "debug", "info", "warn", "error"])
                 .help("Sets level for generation of log messages."),
         )
+        .arg(
+            Arg::new("fstrim-interval")
+                .forbid_empty_values(true)
+                .long("fstrim-interval")
+                .value_name("HOURS")
+                .value_parser(parse_fstrim_interval)
+                .help("Periodically discards the unused blocks of all mounted filesystems, with the given number of hours between runs."),
+        )
         .get_matches();
 
     // Using a let-expression here so that the scope of the lock file
@@ -127,7 +136,10 @@ fn main() {
             Err(err) => Err(err),
             Ok(_) => {
                 initialize_log(matches.value_of("log-level"));
-                run(matches.is_present("sim"))
+                run(
+                    matches.is_present("sim"),
+                    matches.get_one::<Duration>("fstrim-interval").copied(),
+                )
             }
         }
     };
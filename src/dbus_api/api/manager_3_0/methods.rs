@@ -21,8 +21,8 @@ use crate::{
     },
     engine::{
         CreateAction, DeleteAction, EncryptionInfo, Engine, EngineAction, KeyActions,
-        KeyDescription, MappingCreateAction, MappingDeleteAction, Pool, PoolIdentifier, PoolUuid,
-        UnlockMethod,
+        KeyDescription, MappingCreateAction, MappingDeleteAction, Pool, PoolCreateOptions,
+        PoolIdentifier, PoolUuid, Redundancy, UnlockMethod,
     },
     stratis::StratisError,
 };
@@ -198,9 +198,11 @@ where
         }
     };
 
-    let msg = match handle_action!(block_on(
-        dbus_context.engine.unlock_pool(pool_uuid, unlock_method)
-    )) {
+    let msg = match handle_action!(block_on(dbus_context.engine.unlock_pool(
+        pool_uuid,
+        unlock_method,
+        None
+    ))) {
         Ok(unlock_action) => match unlock_action.changed() {
             Some(vec) => {
                 let str_uuids: Vec<_> = vec.into_iter().map(|u| uuid_to_string!(u)).collect();
@@ -269,16 +271,14 @@ where
     let default_return: (bool, (dbus::Path<'static>, Vec<dbus::Path<'static>>)) =
         (false, (dbus::Path::default(), Vec::new()));
 
-    match tuple_to_option(redundancy_tuple) {
-        None | Some(0) => {}
-        Some(n) => {
-            return Ok(vec![return_message.append3(
-                default_return,
-                1u16,
-                format!("code {n} does not correspond to any redundancy"),
-            )]);
+    let redundancy = match tuple_to_option(redundancy_tuple).map(Redundancy::try_from) {
+        None => Redundancy::None,
+        Some(Ok(redundancy)) => redundancy,
+        Some(Err(e)) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
         }
-    }
+    };
 
     let key_desc = match key_desc_tuple.and_then(tuple_to_option) {
         Some(kds) => match KeyDescription::try_from(kds) {
@@ -306,7 +306,11 @@ where
     let create_result = handle_action!(block_on(dbus_context.engine.create_pool(
         name,
         &devs.map(Path::new).collect::<Vec<&Path>>(),
-        EncryptionInfo::from_options((key_desc, clevis_info)).as_ref(),
+        PoolCreateOptions {
+            redundancy,
+            encryption_info: EncryptionInfo::from_options((key_desc, clevis_info)).as_ref(),
+            ..Default::default()
+        },
     )));
     match create_result {
         Ok(pool_uuid_action) => match pool_uuid_action {
@@ -59,11 +59,12 @@ where
         }
     };
 
-    let ret = match handle_action!(block_on(
-        dbus_context
-            .engine
-            .start_pool(PoolIdentifier::Uuid(pool_uuid), unlock_method)
-    )) {
+    let ret = match handle_action!(block_on(dbus_context.engine.start_pool(
+        PoolIdentifier::Uuid(pool_uuid),
+        unlock_method,
+        None,
+        false
+    ))) {
         Ok(StartAction::Started(_)) => {
             let guard = match block_on(
                 dbus_context
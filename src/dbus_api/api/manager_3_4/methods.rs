@@ -66,9 +66,12 @@ where
         }
     };
 
-    let ret = match handle_action!(block_on(
-        dbus_context.engine.start_pool(id.clone(), unlock_method)
-    )) {
+    let ret = match handle_action!(block_on(dbus_context.engine.start_pool(
+        id.clone(),
+        unlock_method,
+        None,
+        false
+    ))) {
         Ok(StartAction::Started(_)) => {
             let guard = match block_on(dbus_context.engine.get_pool(id.clone())) {
                 Some(g) => g,
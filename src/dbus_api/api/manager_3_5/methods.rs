@@ -15,7 +15,10 @@ use crate::{
         types::{DbusErrorEnum, TData, OK_STRING},
         util::{engine_to_dbus_err_tuple, get_next_arg, tuple_to_option},
     },
-    engine::{CreateAction, EncryptionInfo, Engine, KeyDescription, Pool, PoolIdentifier},
+    engine::{
+        CreateAction, EncryptionInfo, Engine, KeyDescription, Pool, PoolCreateOptions,
+        PoolIdentifier,
+    },
     stratis::StratisError,
 };
 
@@ -67,7 +70,10 @@ where
     let create_result = handle_action!(block_on(dbus_context.engine.create_pool(
         name,
         &devs.map(Path::new).collect::<Vec<&Path>>(),
-        EncryptionInfo::from_options((key_desc, clevis_info)).as_ref(),
+        PoolCreateOptions {
+            encryption_info: EncryptionInfo::from_options((key_desc, clevis_info)).as_ref(),
+            ..Default::default()
+        },
     )));
     match create_result {
         Ok(pool_uuid_action) => match pool_uuid_action {
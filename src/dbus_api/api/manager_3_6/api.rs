@@ -0,0 +1,110 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus_tree::{Factory, MTSync, Method};
+
+use crate::{
+    dbus_api::{
+        api::manager_3_6::methods::{create_pool, start_pool},
+        types::TData,
+    },
+    engine::Engine,
+};
+
+pub fn create_pool_method<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Method<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.method("CreatePool", (), create_pool)
+        .in_arg(("name", "s"))
+        .in_arg(("devices", "as"))
+        // Optional key description of key in the kernel keyring
+        // b: true if the pool should be encrypted and able to be
+        // unlocked with a passphrase associated with this key description.
+        // s: key description
+        //
+        // Rust representation: (bool, String)
+        .in_arg(("key_desc", "(bs)"))
+        // Optional Clevis information for binding on initialization.
+        // b: true if the pool should be encrypted and able to be unlocked
+        // using Clevis.
+        // s: pin name
+        // s: JSON config for Clevis use
+        //
+        // Rust representation: (bool, (String, String))
+        .in_arg(("clevis_info", "(b(ss))"))
+        // Optional redundancy of the data tier of the pool.
+        // b: true if the redundancy is specified
+        // q: redundancy code; 0 for none, 1 for raid1
+        //
+        // Rust representation: (bool, u16)
+        .in_arg(("redundancy", "(bq)"))
+        // true if each data device should be protected by dm-integrity
+        .in_arg(("integrity", "b"))
+        // Optional settings of the thin pool of the pool, each in bytes.
+        // (bs): data block size; a multiple of 64 KiB
+        // (bs): free space in the thin pool data device below which the
+        // device is extended
+        // (bs): amount by which the thin pool data device is extended
+        //
+        // Rust representation: ((bool, &str), (bool, &str), (bool, &str))
+        .in_arg(("thin_pool_config", "((bs)(bs)(bs))"))
+        // Optional settings of the encryption of the pool; only valid if
+        // the pool is encrypted.
+        // (bs): cipher, e.g. "aes-xts-plain64"
+        // (bu): size of the volume key in bits
+        // (bs): PBKDF of the keyslot of the passphrase in the kernel keyring:
+        // "pbkdf2", "argon2i", or "argon2id"
+        // (bu): memory cost of the PBKDF in KiB; Argon2 only
+        // (bu): iteration cost of the PBKDF
+        // (bu): encryption sector size in bytes
+        //
+        // Rust representation: ((bool, &str), (bool, u32), (bool, &str),
+        // (bool, u32), (bool, u32), (bool, u32))
+        .in_arg(("encryption_config", "((bs)(bu)(bs)(bu)(bu)(bu))"))
+        // In order from left to right:
+        // b: true if a pool was created and object paths were returned
+        // o: Object path for Pool
+        // a(o): Array of object paths for block devices
+        //
+        // Rust representation: (bool, (dbus::Path, Vec<dbus::Path>))
+        .out_arg(("result", "(b(oao))"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn start_pool_method<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Method<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.method("StartPool", (), start_pool)
+        .in_arg(("id", "s"))
+        .in_arg(("id_type", "s"))
+        .in_arg(("unlock_method", "(bs)"))
+        // true if the pool may be started even if some data devices on
+        // which no data is allocated are missing
+        .in_arg(("allow_missing", "b"))
+        // Optional file descriptor from which the passphrase is read to
+        // unlock the pool with the keyring unlock method. The passphrase is
+        // used directly and is not added to the kernel keyring.
+        // b: true if the passphrase is provided
+        // h: file descriptor
+        //
+        // Rust representation: (bool, OwnedFd)
+        .in_arg(("key_fd", "(bh)"))
+        // In order from left to right:
+        // b: true if the pool was newly started
+        // o: pool path
+        // oa: block device paths
+        // oa: filesystem paths
+        //
+        // Rust representation: bool
+        .out_arg(("result", "(b(oaoao))"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
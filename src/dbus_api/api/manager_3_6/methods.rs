@@ -0,0 +1,334 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::path::Path;
+
+use dbus::{
+    arg::{Array, OwnedFd},
+    Message,
+};
+use dbus_tree::{MTSync, MethodInfo, MethodResult};
+use futures::executor::block_on;
+
+use devicemapper::Sectors;
+
+use crate::{
+    dbus_api::{
+        blockdev::create_dbus_blockdev,
+        filesystem::create_dbus_filesystem,
+        pool::{create_dbus_pool, prop_conv::prop_to_sectors},
+        types::{DbusErrorEnum, TData, OK_STRING},
+        util::{engine_to_dbus_err_tuple, get_next_arg, tuple_to_option},
+    },
+    engine::{
+        CreateAction, EncryptionConfig, EncryptionInfo, Engine, KeyDescription, Name, Pbkdf, Pool,
+        PoolCreateOptions, PoolIdentifier, PoolUuid, Redundancy, StartAction, ThinPoolConfig,
+        UnlockMethod,
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+type EncryptionParams = (Option<(bool, String)>, Option<(bool, (String, String))>);
+
+type ThinPoolConfigParams<'a> = ((bool, &'a str), (bool, &'a str), (bool, &'a str));
+
+type EncryptionConfigParams<'a> = (
+    (bool, &'a str),
+    (bool, u32),
+    (bool, &'a str),
+    (bool, u32),
+    (bool, u32),
+    (bool, u32),
+);
+
+/// Convert the optional data block size, low water mark, and extension size
+/// of the thin pool, each given in bytes, to a thin pool configuration.
+fn tuple_to_thin_pool_config(
+    (data_block_size, low_water, extend_size): ThinPoolConfigParams<'_>,
+) -> Result<ThinPoolConfig, String> {
+    fn parse(size_opt: (bool, &str), desc: &str) -> Result<Option<Sectors>, String> {
+        tuple_to_option(size_opt)
+            .map(|size| prop_to_sectors(size, desc))
+            .transpose()
+    }
+
+    Ok(ThinPoolConfig {
+        data_block_size: parse(data_block_size, "data block size")?,
+        low_water: parse(low_water, "low water mark")?,
+        extend_size: parse(extend_size, "extension size")?,
+    })
+}
+
+/// Convert the optional cipher, key size, PBKDF, PBKDF memory and iteration
+/// costs, and sector size to an encryption configuration. Returns None if no
+/// setting is specified.
+fn tuple_to_encryption_config(
+    (cipher, key_size, pbkdf, pbkdf_memory, pbkdf_iterations, sector_size): EncryptionConfigParams<
+        '_,
+    >,
+) -> StratisResult<Option<EncryptionConfig>> {
+    let config = EncryptionConfig {
+        cipher: tuple_to_option(cipher).map(|c| c.to_string()),
+        key_size: tuple_to_option(key_size),
+        pbkdf: tuple_to_option(pbkdf).map(Pbkdf::try_from).transpose()?,
+        pbkdf_memory: tuple_to_option(pbkdf_memory),
+        pbkdf_iterations: tuple_to_option(pbkdf_iterations),
+        sector_size: tuple_to_option(sector_size),
+    };
+    Ok(if config == EncryptionConfig::default() {
+        None
+    } else {
+        Some(config)
+    })
+}
+
+pub fn create_pool<E>(m: &MethodInfo<'_, MTSync<TData<E>>, TData<E>>) -> MethodResult
+where
+    E: 'static + Engine,
+{
+    let base_path = m.path.get_name();
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let name: &str = get_next_arg(&mut iter, 0)?;
+    let devs: Array<'_, &str, _> = get_next_arg(&mut iter, 1)?;
+    let (key_desc_tuple, clevis_tuple): EncryptionParams = (
+        Some(get_next_arg(&mut iter, 2)?),
+        Some(get_next_arg(&mut iter, 3)?),
+    );
+    let redundancy_tuple: (bool, u16) = get_next_arg(&mut iter, 4)?;
+    let integrity: bool = get_next_arg(&mut iter, 5)?;
+    let thin_pool_tuple: ThinPoolConfigParams<'_> = get_next_arg(&mut iter, 6)?;
+    let encryption_config_tuple: EncryptionConfigParams<'_> = get_next_arg(&mut iter, 7)?;
+
+    let return_message = message.method_return();
+
+    let default_return: (bool, (dbus::Path<'static>, Vec<dbus::Path<'static>>)) =
+        (false, (dbus::Path::default(), Vec::new()));
+
+    let redundancy = match tuple_to_option(redundancy_tuple).map(Redundancy::try_from) {
+        None => Redundancy::None,
+        Some(Ok(redundancy)) => redundancy,
+        Some(Err(e)) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let thin_pool_config = match tuple_to_thin_pool_config(thin_pool_tuple) {
+        Ok(config) => config,
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&StratisError::Msg(e));
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let encryption_config = match tuple_to_encryption_config(encryption_config_tuple) {
+        Ok(config) => config,
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let key_desc = match key_desc_tuple.and_then(tuple_to_option) {
+        Some(kds) => match KeyDescription::try_from(kds) {
+            Ok(kd) => Some(kd),
+            Err(e) => {
+                let (rc, rs) = engine_to_dbus_err_tuple(&e);
+                return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            }
+        },
+        None => None,
+    };
+
+    let clevis_info = match clevis_tuple.and_then(tuple_to_option) {
+        Some((pin, json_string)) => match serde_json::from_str(json_string.as_str()) {
+            Ok(j) => Some((pin, j)),
+            Err(e) => {
+                let (rc, rs) = engine_to_dbus_err_tuple(&StratisError::Serde(e));
+                return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            }
+        },
+        None => None,
+    };
+
+    let dbus_context = m.tree.get_data();
+    let create_result = handle_action!(block_on(dbus_context.engine.create_pool(
+        name,
+        &devs.map(Path::new).collect::<Vec<&Path>>(),
+        PoolCreateOptions {
+            redundancy,
+            integrity,
+            encryption_info: EncryptionInfo::from_options((key_desc, clevis_info)).as_ref(),
+            encryption_config: encryption_config.as_ref(),
+            thin_pool_config: Some(&thin_pool_config),
+        },
+    )));
+    match create_result {
+        Ok(pool_uuid_action) => match pool_uuid_action {
+            CreateAction::Created(uuid) => {
+                let guard = match block_on(dbus_context.engine.get_pool(PoolIdentifier::Uuid(uuid)))
+                {
+                    Some(g) => g,
+                    None => {
+                        let (rc, rs) = engine_to_dbus_err_tuple(&StratisError::Msg(
+                            format!("Pool with UUID {uuid} was successfully started but appears to have been removed before it could be exposed on the D-Bus")
+                        ));
+                        return Ok(vec![return_message.append3(default_return, rc, rs)]);
+                    }
+                };
+
+                let (pool_name, pool_uuid, pool) = guard.as_tuple();
+                let pool_path =
+                    create_dbus_pool(dbus_context, base_path.clone(), &pool_name, pool_uuid, pool);
+                let mut bd_paths = Vec::new();
+                for (bd_uuid, tier, bd) in pool.blockdevs() {
+                    bd_paths.push(create_dbus_blockdev(
+                        dbus_context,
+                        pool_path.clone(),
+                        bd_uuid,
+                        tier,
+                        bd,
+                    ));
+                }
+
+                Ok(vec![return_message.append3(
+                    (true, (pool_path, bd_paths)),
+                    DbusErrorEnum::OK as u16,
+                    OK_STRING.to_string(),
+                )])
+            }
+            CreateAction::Identity => Ok(vec![return_message.append3(
+                default_return,
+                DbusErrorEnum::OK as u16,
+                OK_STRING.to_string(),
+            )]),
+        },
+        Err(x) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&x);
+            Ok(vec![return_message.append3(default_return, rc, rs)])
+        }
+    }
+}
+
+pub fn start_pool<E>(m: &MethodInfo<'_, MTSync<TData<E>>, TData<E>>) -> MethodResult
+where
+    E: 'static + Engine,
+{
+    let base_path = m.path.get_name();
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let dbus_context = m.tree.get_data();
+    let default_return: (
+        bool,
+        (
+            dbus::Path<'static>,
+            Vec<dbus::Path<'static>>,
+            Vec<dbus::Path<'static>>,
+        ),
+    ) = (false, (dbus::Path::default(), Vec::new(), Vec::new()));
+    let return_message = message.method_return();
+
+    let id_str: &str = get_next_arg(&mut iter, 0)?;
+    let id = {
+        let id_type_str: &str = get_next_arg(&mut iter, 1)?;
+        match id_type_str {
+            "uuid" => match PoolUuid::parse_str(id_str) {
+                Ok(u) => PoolIdentifier::Uuid(u),
+                Err(e) => {
+                    let (rc, rs) = engine_to_dbus_err_tuple(&e);
+                    return Ok(vec![return_message.append3(default_return, rc, rs)]);
+                }
+            },
+            "name" => PoolIdentifier::Name(Name::new(id_str.to_string())),
+            _ => {
+                let (rc, rs) = engine_to_dbus_err_tuple(&StratisError::Msg(format!(
+                    "ID type {id_type_str} not recognized"
+                )));
+                return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            }
+        }
+    };
+    let unlock_method = {
+        let unlock_method_tup: (bool, &str) = get_next_arg(&mut iter, 2)?;
+        match tuple_to_option(unlock_method_tup) {
+            Some(unlock_method_str) => match UnlockMethod::try_from(unlock_method_str) {
+                Ok(um) => Some(um),
+                Err(e) => {
+                    let (rc, rs) = engine_to_dbus_err_tuple(&e);
+                    return Ok(vec![return_message.append3(default_return, rc, rs)]);
+                }
+            },
+            None => None,
+        }
+    };
+    let allow_missing: bool = get_next_arg(&mut iter, 3)?;
+    let passphrase_fd = {
+        let key_fd_tup: (bool, OwnedFd) = get_next_arg(&mut iter, 4)?;
+        tuple_to_option(key_fd_tup).map(|fd| fd.into_fd())
+    };
+
+    let ret = match handle_action!(block_on(dbus_context.engine.start_pool(
+        id.clone(),
+        unlock_method,
+        passphrase_fd,
+        allow_missing
+    ))) {
+        Ok(StartAction::Started(_)) => {
+            let guard = match block_on(dbus_context.engine.get_pool(id.clone())) {
+                Some(g) => g,
+                None => {
+                    let (rc, rs) = engine_to_dbus_err_tuple(&StratisError::Msg(
+                        format!("Pool with {id:?} was successfully started but appears to have been removed before it could be exposed on the D-Bus")
+                    ));
+                    return Ok(vec![return_message.append3(default_return, rc, rs)]);
+                }
+            };
+
+            let (pool_name, pool_uuid, pool) = guard.as_tuple();
+            let pool_path =
+                create_dbus_pool(dbus_context, base_path.clone(), &pool_name, pool_uuid, pool);
+            let mut bd_paths = Vec::new();
+            for (bd_uuid, tier, bd) in pool.blockdevs() {
+                bd_paths.push(create_dbus_blockdev(
+                    dbus_context,
+                    pool_path.clone(),
+                    bd_uuid,
+                    tier,
+                    bd,
+                ));
+            }
+            let mut fs_paths = Vec::new();
+            for (name, fs_uuid, fs) in pool.filesystems() {
+                fs_paths.push(create_dbus_filesystem(
+                    dbus_context,
+                    pool_path.clone(),
+                    &pool_name,
+                    &name,
+                    fs_uuid,
+                    fs,
+                ));
+            }
+
+            if pool.is_encrypted() {
+                dbus_context.push_locked_pools(block_on(dbus_context.engine.locked_pools()));
+            }
+            dbus_context.push_stopped_pools(block_on(dbus_context.engine.stopped_pools()));
+
+            (true, (pool_path, bd_paths, fs_paths))
+        }
+        Ok(StartAction::Identity) => default_return,
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    Ok(vec![return_message.append3(
+        ret,
+        DbusErrorEnum::OK as u16,
+        OK_STRING.to_string(),
+    )])
+}
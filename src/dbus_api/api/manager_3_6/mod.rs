@@ -0,0 +1,4 @@
+mod api;
+mod methods;
+
+pub use api::{create_pool_method, start_pool_method};
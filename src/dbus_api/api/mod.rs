@@ -16,6 +16,7 @@ mod manager_3_0;
 mod manager_3_2;
 mod manager_3_4;
 mod manager_3_5;
+mod manager_3_6;
 pub mod prop_conv;
 mod report_3_0;
 mod shared;
@@ -126,6 +127,20 @@ where
                 .add_p(manager_3_0::version_property(&f))
                 .add_p(manager_3_2::stopped_pools_property(&f)),
         )
+        .add(
+            f.interface(consts::MANAGER_INTERFACE_NAME_3_6, ())
+                .add_m(manager_3_6::create_pool_method(&f))
+                .add_m(manager_3_0::set_key_method(&f))
+                .add_m(manager_3_0::unset_key_method(&f))
+                .add_m(manager_3_0::list_keys_method(&f))
+                .add_m(manager_3_0::destroy_pool_method(&f))
+                .add_m(manager_3_0::engine_state_report_method(&f))
+                .add_m(manager_3_6::start_pool_method(&f))
+                .add_m(manager_3_2::stop_pool_method(&f))
+                .add_m(manager_3_2::refresh_state_method(&f))
+                .add_p(manager_3_0::version_property(&f))
+                .add_p(manager_3_2::stopped_pools_property(&f)),
+        )
         .add(
             f.interface(consts::REPORT_INTERFACE_NAME_3_0, ())
                 .add_m(report_3_0::get_report_method(&f)),
@@ -149,6 +164,10 @@ where
         .add(
             f.interface(consts::REPORT_INTERFACE_NAME_3_5, ())
                 .add_m(report_3_0::get_report_method(&f)),
+        )
+        .add(
+            f.interface(consts::REPORT_INTERFACE_NAME_3_6, ())
+                .add_m(report_3_0::get_report_method(&f)),
         );
 
     let path = obj_path.get_name().to_owned();
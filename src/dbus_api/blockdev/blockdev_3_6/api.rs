@@ -0,0 +1,42 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus_tree::{Access, EmitsChangedSignal, Factory, MTSync, Property};
+
+use crate::{
+    dbus_api::{
+        blockdev::blockdev_3_6::props::{
+            get_blockdev_integrity_mismatches, get_blockdev_tags, set_blockdev_tags,
+        },
+        consts,
+        types::TData,
+    },
+    engine::Engine,
+};
+
+pub fn integrity_mismatches_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.property::<(bool, u64), _>(consts::BLOCKDEV_INTEGRITY_MISMATCHES_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_blockdev_integrity_mismatches)
+}
+
+pub fn tags_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.property::<Vec<&str>, _>(consts::BLOCKDEV_TAGS_PROP, ())
+        .access(Access::ReadWrite)
+        .emits_changed(EmitsChangedSignal::True)
+        .auto_emit_on_set(false)
+        .on_get(get_blockdev_tags)
+        .on_set(set_blockdev_tags)
+}
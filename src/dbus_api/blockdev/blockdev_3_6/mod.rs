@@ -0,0 +1,4 @@
+mod api;
+mod props;
+
+pub use api::{integrity_mismatches_property, tags_property};
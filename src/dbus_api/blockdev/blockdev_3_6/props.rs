@@ -0,0 +1,67 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus::arg::{Iter, IterAppend};
+use dbus_tree::{MTSync, MethodErr, PropInfo};
+
+use crate::{
+    dbus_api::{
+        blockdev::shared::{
+            self, get_blockdev_property, set_pool_level_blockdev_property_to_display,
+        },
+        consts,
+        types::TData,
+    },
+    engine::{Engine, PropChangeAction},
+};
+
+/// Get the number of integrity mismatches detected on the block device
+/// represented by the object path.
+pub fn get_blockdev_integrity_mismatches<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: Engine,
+{
+    get_blockdev_property(i, p, |_, p| {
+        shared::blockdev_integrity_mismatches_prop::<E>(p)
+    })
+}
+
+pub fn get_blockdev_tags<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: Engine,
+{
+    get_blockdev_property(i, p, |_, p| Ok(shared::blockdev_tags_prop::<E>(p)))
+}
+
+pub fn set_blockdev_tags<E>(
+    i: &mut Iter<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: Engine,
+{
+    let tags: Vec<String> = i
+        .get()
+        .ok_or_else(|| MethodErr::failed("A list of tags is required as argument to set them"))?;
+    let res =
+        set_pool_level_blockdev_property_to_display(p, consts::BLOCKDEV_TAGS_PROP, |n, p, uuid| {
+            shared::set_blockdev_tags_prop::<E>(p, n, uuid, &tags)
+        });
+    match res {
+        Ok(PropChangeAction::NewValue(v)) => {
+            p.tree
+                .get_data()
+                .push_blockdev_tags_change(p.path.get_name(), v);
+            Ok(())
+        }
+        Ok(PropChangeAction::Identity) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
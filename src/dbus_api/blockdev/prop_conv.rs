@@ -12,6 +12,13 @@ pub fn blockdev_new_size_to_prop(new_size: Option<Sectors>) -> (bool, String) {
     option_to_tuple(new_size.map(|s| (*s.bytes()).to_string()), String::new())
 }
 
+/// Generate D-Bus representation of block device integrity mismatches
+/// property.
+#[inline]
+pub fn blockdev_integrity_mismatches_to_prop(mismatches: Option<u64>) -> (bool, u64) {
+    option_to_tuple(mismatches, 0)
+}
+
 /// Generate D-Bus representation of block device user info property.
 #[inline]
 pub fn blockdev_user_info_to_prop(user_info: Option<String>) -> (bool, String) {
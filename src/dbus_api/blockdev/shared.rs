@@ -10,7 +10,7 @@ use crate::{
     dbus_api::{blockdev::prop_conv, types::TData, util::option_to_tuple},
     engine::{
         BlockDev, BlockDevTier, DevUuid, Engine, Name, Pool, PoolIdentifier, PropChangeAction,
-        ToDisplay,
+        RenameAction, ToDisplay,
     },
 };
 
@@ -201,6 +201,40 @@ where
     }
 }
 
+/// Generate D-Bus representation of tags property.
+#[inline]
+pub fn blockdev_tags_prop<E>(dev: &<E::Pool as Pool>::BlockDev) -> Vec<String>
+where
+    E: Engine,
+{
+    dev.tags().to_vec()
+}
+
+/// Set the tags of a blockdev. The new value is the sorted, deduplicated
+/// list of tags that the blockdev has after the change.
+pub fn set_blockdev_tags_prop<E>(
+    pool: &mut E::Pool,
+    pool_name: &Name,
+    dev_uuid: DevUuid,
+    tags: &[String],
+) -> Result<PropChangeAction<Vec<String>>, String>
+where
+    E: Engine,
+{
+    match pool
+        .set_blockdev_tags(pool_name, dev_uuid, tags)
+        .map_err(|e| e.to_string())?
+    {
+        RenameAction::Renamed(_) => Ok(PropChangeAction::NewValue(
+            pool.get_blockdev(dev_uuid)
+                .map(|(_, bd)| bd.tags().to_vec())
+                .unwrap_or_default(),
+        )),
+        RenameAction::Identity => Ok(PropChangeAction::Identity),
+        RenameAction::NoSource => Err(format!("Blockdev with UUID {dev_uuid} not found")),
+    }
+}
+
 /// Generate D-Bus representation of initialization time property.
 #[inline]
 pub fn blockdev_init_time_prop<E>(dev: &<E::Pool as Pool>::BlockDev) -> u64
@@ -242,3 +276,17 @@ where
 {
     prop_conv::blockdev_new_size_to_prop(dev.new_size())
 }
+
+/// Generate D-Bus representation of the number of integrity mismatches
+/// detected on the block device.
+#[inline]
+pub fn blockdev_integrity_mismatches_prop<E>(
+    dev: &<E::Pool as Pool>::BlockDev,
+) -> Result<(bool, u64), String>
+where
+    E: Engine,
+{
+    dev.integrity_mismatches()
+        .map(prop_conv::blockdev_integrity_mismatches_to_prop)
+        .map_err(|e| e.to_string())
+}
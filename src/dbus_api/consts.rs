@@ -13,12 +13,14 @@ pub const MANAGER_INTERFACE_NAME_3_2: &str = "org.storage.stratis3.Manager.r2";
 pub const MANAGER_INTERFACE_NAME_3_3: &str = "org.storage.stratis3.Manager.r3";
 pub const MANAGER_INTERFACE_NAME_3_4: &str = "org.storage.stratis3.Manager.r4";
 pub const MANAGER_INTERFACE_NAME_3_5: &str = "org.storage.stratis3.Manager.r5";
+pub const MANAGER_INTERFACE_NAME_3_6: &str = "org.storage.stratis3.Manager.r6";
 pub const REPORT_INTERFACE_NAME_3_0: &str = "org.storage.stratis3.Report.r0";
 pub const REPORT_INTERFACE_NAME_3_1: &str = "org.storage.stratis3.Report.r1";
 pub const REPORT_INTERFACE_NAME_3_2: &str = "org.storage.stratis3.Report.r2";
 pub const REPORT_INTERFACE_NAME_3_3: &str = "org.storage.stratis3.Report.r3";
 pub const REPORT_INTERFACE_NAME_3_4: &str = "org.storage.stratis3.Report.r4";
 pub const REPORT_INTERFACE_NAME_3_5: &str = "org.storage.stratis3.Report.r5";
+pub const REPORT_INTERFACE_NAME_3_6: &str = "org.storage.stratis3.Report.r6";
 
 pub const LOCKED_POOLS_PROP: &str = "LockedPools";
 pub const STOPPED_POOLS_PROP: &str = "StoppedPools";
@@ -29,6 +31,7 @@ pub const POOL_INTERFACE_NAME_3_2: &str = "org.storage.stratis3.pool.r2";
 pub const POOL_INTERFACE_NAME_3_3: &str = "org.storage.stratis3.pool.r3";
 pub const POOL_INTERFACE_NAME_3_4: &str = "org.storage.stratis3.pool.r4";
 pub const POOL_INTERFACE_NAME_3_5: &str = "org.storage.stratis3.pool.r5";
+pub const POOL_INTERFACE_NAME_3_6: &str = "org.storage.stratis3.pool.r6";
 pub const POOL_NAME_PROP: &str = "Name";
 pub const POOL_UUID_PROP: &str = "Uuid";
 pub const POOL_HAS_CACHE_PROP: &str = "HasCache";
@@ -42,6 +45,17 @@ pub const POOL_ALLOC_SIZE_PROP: &str = "AllocatedSize";
 pub const POOL_FS_LIMIT_PROP: &str = "FsLimit";
 pub const POOL_OVERPROV_PROP: &str = "Overprovisioning";
 pub const POOL_NO_ALLOCABLE_SPACE_PROP: &str = "NoAllocSpace";
+pub const POOL_REPLACE_PROGRESS_PROP: &str = "ReplaceProgress";
+pub const POOL_REENCRYPTION_PROGRESS_PROP: &str = "ReencryptionProgress";
+pub const POOL_CACHE_CONFIG_PROP: &str = "CacheConfig";
+pub const POOL_CACHE_STATS_PROP: &str = "CacheStats";
+pub const POOL_ALLOCATION_CONFIG_PROP: &str = "AllocationConfig";
+pub const POOL_DISCARD_PASSDOWN_PROP: &str = "DiscardPassdown";
+pub const POOL_FS_EXTEND_CONFIG_PROP: &str = "FsExtendConfig";
+pub const POOL_DATA_BLOCK_SIZE_PROP: &str = "DataBlockSize";
+pub const POOL_THIN_POOL_EXTEND_POLICY_PROP: &str = "ThinPoolExtendPolicy";
+pub const POOL_ENCRYPTION_CONFIG_PROP: &str = "EncryptionConfig";
+pub const POOL_PASSPHRASE_BINDINGS_PROP: &str = "PassphraseBindings";
 
 pub const FILESYSTEM_INTERFACE_NAME_3_0: &str = "org.storage.stratis3.filesystem.r0";
 pub const FILESYSTEM_INTERFACE_NAME_3_1: &str = "org.storage.stratis3.filesystem.r1";
@@ -49,6 +63,7 @@ pub const FILESYSTEM_INTERFACE_NAME_3_2: &str = "org.storage.stratis3.filesystem
 pub const FILESYSTEM_INTERFACE_NAME_3_3: &str = "org.storage.stratis3.filesystem.r3";
 pub const FILESYSTEM_INTERFACE_NAME_3_4: &str = "org.storage.stratis3.filesystem.r4";
 pub const FILESYSTEM_INTERFACE_NAME_3_5: &str = "org.storage.stratis3.filesystem.r5";
+pub const FILESYSTEM_INTERFACE_NAME_3_6: &str = "org.storage.stratis3.filesystem.r6";
 pub const FILESYSTEM_NAME_PROP: &str = "Name";
 pub const FILESYSTEM_UUID_PROP: &str = "Uuid";
 pub const FILESYSTEM_USED_PROP: &str = "Used";
@@ -56,6 +71,14 @@ pub const FILESYSTEM_DEVNODE_PROP: &str = "Devnode";
 pub const FILESYSTEM_POOL_PROP: &str = "Pool";
 pub const FILESYSTEM_CREATED_PROP: &str = "Created";
 pub const FILESYSTEM_SIZE_PROP: &str = "Size";
+pub const FILESYSTEM_ORIGIN_PROP: &str = "Origin";
+pub const FILESYSTEM_SNAPSHOT_POLICIES_PROP: &str = "SnapshotPolicies";
+pub const FILESYSTEM_SIZE_LIMIT_PROP: &str = "SizeLimit";
+pub const FILESYSTEM_EXTEND_CONFIG_PROP: &str = "ExtendConfig";
+pub const FILESYSTEM_FS_TYPE_PROP: &str = "FsType";
+pub const FILESYSTEM_MOUNT_OPTIONS_PROP: &str = "MountOptions";
+pub const FILESYSTEM_USED_EXCLUSIVE_PROP: &str = "UsedExclusive";
+pub const FILESYSTEM_USED_SHARED_PROP: &str = "UsedShared";
 
 pub const BLOCKDEV_INTERFACE_NAME_3_0: &str = "org.storage.stratis3.blockdev.r0";
 pub const BLOCKDEV_INTERFACE_NAME_3_1: &str = "org.storage.stratis3.blockdev.r1";
@@ -63,6 +86,7 @@ pub const BLOCKDEV_INTERFACE_NAME_3_2: &str = "org.storage.stratis3.blockdev.r2"
 pub const BLOCKDEV_INTERFACE_NAME_3_3: &str = "org.storage.stratis3.blockdev.r3";
 pub const BLOCKDEV_INTERFACE_NAME_3_4: &str = "org.storage.stratis3.blockdev.r4";
 pub const BLOCKDEV_INTERFACE_NAME_3_5: &str = "org.storage.stratis3.blockdev.r5";
+pub const BLOCKDEV_INTERFACE_NAME_3_6: &str = "org.storage.stratis3.blockdev.r6";
 pub const BLOCKDEV_DEVNODE_PROP: &str = "Devnode";
 pub const BLOCKDEV_HARDWARE_INFO_PROP: &str = "HardwareInfo";
 pub const BLOCKDEV_USER_INFO_PROP: &str = "UserInfo";
@@ -72,6 +96,8 @@ pub const BLOCKDEV_UUID_PROP: &str = "Uuid";
 pub const BLOCKDEV_TIER_PROP: &str = "Tier";
 pub const BLOCKDEV_PHYSICAL_PATH_PROP: &str = "PhysicalPath";
 pub const BLOCKDEV_NEW_SIZE_PROP: &str = "NewPhysicalSize";
+pub const BLOCKDEV_INTEGRITY_MISMATCHES_PROP: &str = "IntegrityMismatches";
+pub const BLOCKDEV_TAGS_PROP: &str = "Tags";
 
 pub const BLOCKDEV_TOTAL_SIZE_PROP: &str = "TotalPhysicalSize";
 
@@ -84,6 +110,7 @@ pub fn standard_pool_interfaces() -> Vec<String> {
         POOL_INTERFACE_NAME_3_3,
         POOL_INTERFACE_NAME_3_4,
         POOL_INTERFACE_NAME_3_5,
+        POOL_INTERFACE_NAME_3_6,
     ]
     .iter()
     .map(|s| (*s).to_string())
@@ -99,6 +126,7 @@ pub fn standard_filesystem_interfaces() -> Vec<String> {
         FILESYSTEM_INTERFACE_NAME_3_3,
         FILESYSTEM_INTERFACE_NAME_3_4,
         FILESYSTEM_INTERFACE_NAME_3_5,
+        FILESYSTEM_INTERFACE_NAME_3_6,
     ]
     .iter()
     .map(|s| (*s).to_string())
@@ -114,6 +142,7 @@ pub fn standard_blockdev_interfaces() -> Vec<String> {
         BLOCKDEV_INTERFACE_NAME_3_3,
         BLOCKDEV_INTERFACE_NAME_3_4,
         BLOCKDEV_INTERFACE_NAME_3_5,
+        BLOCKDEV_INTERFACE_NAME_3_6,
     ]
     .iter()
     .map(|s| (*s).to_string())
@@ -7,32 +7,12 @@ use dbus_tree::{MTSync, MethodErr, PropInfo};
 
 use crate::{
     dbus_api::{
-        filesystem::shared::{self, filesystem_operation},
+        filesystem::shared::{self, get_filesystem_property},
         types::TData,
     },
-    engine::{Engine, Name, Pool},
+    engine::Engine,
 };
 
-/// Get a filesystem property and place it on the D-Bus. The property is
-/// found by means of the getter method which takes a reference to a
-/// Filesystem and obtains the property from the filesystem.
-fn get_filesystem_property<F, R, E>(
-    i: &mut IterAppend<'_>,
-    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
-    getter: F,
-) -> Result<(), MethodErr>
-where
-    F: Fn((Name, Name, &<E::Pool as Pool>::Filesystem)) -> Result<R, String>,
-    R: dbus::arg::Append,
-    E: Engine,
-{
-    i.append(
-        filesystem_operation(p.tree, p.path.get_name(), getter)
-            .map_err(|ref e| MethodErr::failed(e))?,
-    );
-    Ok(())
-}
-
 /// Get the devnode for an object path.
 pub fn get_filesystem_devnode<E>(
     i: &mut IterAppend<'_>,
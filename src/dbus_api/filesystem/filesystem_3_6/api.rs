@@ -0,0 +1,132 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus_tree::{Access, EmitsChangedSignal, Factory, MTSync, Property};
+
+use crate::{
+    dbus_api::{
+        consts,
+        filesystem::filesystem_3_6::props::{
+            get_filesystem_extend_config, get_filesystem_fs_type, get_filesystem_mount_options,
+            get_filesystem_origin, get_filesystem_size_limit, get_filesystem_snapshot_policies,
+            get_filesystem_used_exclusive, get_filesystem_used_shared,
+            set_filesystem_extend_config, set_filesystem_size_limit,
+            set_filesystem_snapshot_policies,
+        },
+        types::TData,
+    },
+    engine::Engine,
+};
+
+pub fn origin_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.property::<(bool, &str), _>(consts::FILESYSTEM_ORIGIN_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_filesystem_origin)
+}
+
+pub fn fs_type_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.property::<&str, _>(consts::FILESYSTEM_FS_TYPE_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_filesystem_fs_type)
+}
+
+pub fn mount_options_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.property::<(bool, &str), _>(consts::FILESYSTEM_MOUNT_OPTIONS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_filesystem_mount_options)
+}
+
+pub fn used_exclusive_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.property::<(bool, &str), _>(consts::FILESYSTEM_USED_EXCLUSIVE_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_filesystem_used_exclusive)
+}
+
+pub fn used_shared_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.property::<(bool, &str), _>(consts::FILESYSTEM_USED_SHARED_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::True)
+        .on_get(get_filesystem_used_shared)
+}
+
+pub fn snapshot_policies_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    // s: Name of the policy
+    // t: Interval between snapshots in seconds
+    // (bt): Number of snapshots to keep, if limited
+    // (bt): Maximum age of snapshots to keep in seconds, if limited
+    f.property::<Vec<(&str, u64, (bool, u64), (bool, u64))>, _>(
+        consts::FILESYSTEM_SNAPSHOT_POLICIES_PROP,
+        (),
+    )
+    .access(Access::ReadWrite)
+    .emits_changed(EmitsChangedSignal::True)
+    .auto_emit_on_set(false)
+    .on_get(get_filesystem_snapshot_policies)
+    .on_set(set_filesystem_snapshot_policies)
+}
+
+pub fn size_limit_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.property::<(bool, &str), _>(consts::FILESYSTEM_SIZE_LIMIT_PROP, ())
+        .access(Access::ReadWrite)
+        .emits_changed(EmitsChangedSignal::True)
+        .auto_emit_on_set(false)
+        .on_get(get_filesystem_size_limit)
+        .on_set(set_filesystem_size_limit)
+}
+
+pub fn extend_config_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    // b: Whether the filesystem has its own configuration; if not, the
+    // configuration of its pool applies
+    // (bybs): The configuration, as in the FsExtendConfig pool property
+    f.property::<(bool, (bool, u8, bool, &str)), _>(consts::FILESYSTEM_EXTEND_CONFIG_PROP, ())
+        .access(Access::ReadWrite)
+        .emits_changed(EmitsChangedSignal::True)
+        .auto_emit_on_set(false)
+        .on_get(get_filesystem_extend_config)
+        .on_set(set_filesystem_extend_config)
+}
@@ -0,0 +1,7 @@
+mod api;
+mod props;
+
+pub use api::{
+    extend_config_property, fs_type_property, mount_options_property, origin_property,
+    size_limit_property, snapshot_policies_property, used_exclusive_property, used_shared_property,
+};
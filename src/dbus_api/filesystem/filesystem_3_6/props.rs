@@ -0,0 +1,209 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus::arg::{Iter, IterAppend};
+use dbus_tree::{MTSync, MethodErr, PropInfo};
+use devicemapper::Bytes;
+
+use crate::{
+    dbus_api::{
+        consts,
+        filesystem::{
+            prop_conv::{
+                prop_to_fs_extend_config, prop_to_fs_snapshot_policies, FsExtendConfigProp,
+                SnapshotPolicyProp,
+            },
+            shared::{
+                self, get_filesystem_property, set_pool_level_filesystem_property_to_display,
+            },
+        },
+        types::TData,
+        util::tuple_to_option,
+    },
+    engine::{Engine, PropChangeAction},
+};
+
+/// Get the UUID of the filesystem this filesystem is a snapshot of.
+pub fn get_filesystem_origin<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: Engine,
+{
+    get_filesystem_property(i, p, |(_, _, fs)| Ok(shared::fs_origin_prop(fs)))
+}
+
+/// Get the type of the filesystem.
+pub fn get_filesystem_fs_type<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: Engine,
+{
+    get_filesystem_property(i, p, |(_, _, fs)| Ok(shared::fs_type_prop(fs)))
+}
+
+/// Get the options with which the filesystem is recommended to be mounted.
+pub fn get_filesystem_mount_options<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: Engine,
+{
+    get_filesystem_property(i, p, |(_, _, fs)| Ok(shared::fs_mount_options_prop(fs)))
+}
+
+/// Get the space that is mapped only by this filesystem.
+pub fn get_filesystem_used_exclusive<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: Engine,
+{
+    get_filesystem_property(i, p, |(_, _, fs)| Ok(shared::fs_used_exclusive_prop(fs)))
+}
+
+/// Get the space that this filesystem shares with other filesystems.
+pub fn get_filesystem_used_shared<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: Engine,
+{
+    get_filesystem_property(i, p, |(_, _, fs)| Ok(shared::fs_used_shared_prop(fs)))
+}
+
+/// Get the policies according to which snapshots of the filesystem are taken.
+pub fn get_filesystem_snapshot_policies<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: Engine,
+{
+    get_filesystem_property(i, p, |(_, _, fs)| Ok(shared::fs_snapshot_policies_prop(fs)))
+}
+
+pub fn set_filesystem_snapshot_policies<E>(
+    i: &mut Iter<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: Engine,
+{
+    let policies: Vec<SnapshotPolicyProp> = i.get().ok_or_else(|| {
+        MethodErr::failed("A list of snapshot policies is required as argument to set them")
+    })?;
+    let policies = prop_to_fs_snapshot_policies(policies);
+    let res = set_pool_level_filesystem_property_to_display(
+        p,
+        consts::FILESYSTEM_SNAPSHOT_POLICIES_PROP,
+        |n, p, uuid| shared::set_fs_snapshot_policies_prop::<E>(p, n, uuid, &policies),
+    );
+    match res {
+        Ok(PropChangeAction::NewValue(v)) => {
+            p.tree
+                .get_data()
+                .push_filesystem_snapshot_policies_change(p.path.get_name(), v);
+            Ok(())
+        }
+        Ok(PropChangeAction::Identity) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Get the maximum size to which the filesystem may be extended.
+pub fn get_filesystem_size_limit<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: Engine,
+{
+    get_filesystem_property(i, p, |(_, _, fs)| Ok(shared::fs_size_limit_prop(fs)))
+}
+
+pub fn set_filesystem_size_limit<E>(
+    i: &mut Iter<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: Engine,
+{
+    let size_limit_opt: (bool, String) = i.get().ok_or_else(|| {
+        MethodErr::failed("An optional size limit is required as argument to set it")
+    })?;
+    let size_limit = tuple_to_option(size_limit_opt)
+        .map(|val| {
+            val.parse::<u128>().map(Bytes).map_err(|_| {
+                MethodErr::failed(&format!(
+                    "Could not parse filesystem size limit string {val} to integer value"
+                ))
+            })
+        })
+        .transpose()?;
+    let res = set_pool_level_filesystem_property_to_display(
+        p,
+        consts::FILESYSTEM_SIZE_LIMIT_PROP,
+        |n, p, uuid| shared::set_fs_size_limit_prop::<E>(p, n, uuid, size_limit),
+    );
+    match res {
+        Ok(PropChangeAction::NewValue(v)) => {
+            p.tree
+                .get_data()
+                .push_filesystem_size_limit_change(p.path.get_name(), v);
+            Ok(())
+        }
+        Ok(PropChangeAction::Identity) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Get the configuration of the automatic extension of the filesystem, if it
+/// overrides that of its pool.
+pub fn get_filesystem_extend_config<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: Engine,
+{
+    get_filesystem_property(i, p, |(_, _, fs)| Ok(shared::fs_extend_config_prop(fs)))
+}
+
+pub fn set_filesystem_extend_config<E>(
+    i: &mut Iter<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: Engine,
+{
+    let config_opt: (bool, FsExtendConfigProp) = i.get().ok_or_else(|| {
+        MethodErr::failed("An optional extension configuration is required as argument to set it")
+    })?;
+    let config = tuple_to_option(config_opt)
+        .map(prop_to_fs_extend_config)
+        .transpose()
+        .map_err(|e| MethodErr::failed(&e))?;
+    let res = set_pool_level_filesystem_property_to_display(
+        p,
+        consts::FILESYSTEM_EXTEND_CONFIG_PROP,
+        |n, p, uuid| shared::set_fs_extend_config_prop::<E>(p, n, uuid, config),
+    );
+    match res {
+        Ok(PropChangeAction::NewValue(v)) => {
+            p.tree
+                .get_data()
+                .push_filesystem_extend_config_change(p.path.get_name(), v);
+            Ok(())
+        }
+        Ok(PropChangeAction::Identity) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
@@ -14,6 +14,7 @@ use crate::{
 };
 
 mod filesystem_3_0;
+mod filesystem_3_6;
 pub mod prop_conv;
 mod shared;
 
@@ -103,6 +104,25 @@ where
                 .add_p(filesystem_3_0::created_property(&f))
                 .add_p(filesystem_3_0::size_property(&f))
                 .add_p(filesystem_3_0::used_property(&f)),
+        )
+        .add(
+            f.interface(consts::FILESYSTEM_INTERFACE_NAME_3_6, ())
+                .add_m(filesystem_3_0::rename_method(&f))
+                .add_p(filesystem_3_0::devnode_property(&f))
+                .add_p(filesystem_3_0::name_property(&f))
+                .add_p(filesystem_3_0::pool_property(&f))
+                .add_p(filesystem_3_0::uuid_property(&f))
+                .add_p(filesystem_3_0::created_property(&f))
+                .add_p(filesystem_3_0::size_property(&f))
+                .add_p(filesystem_3_0::used_property(&f))
+                .add_p(filesystem_3_6::origin_property(&f))
+                .add_p(filesystem_3_6::snapshot_policies_property(&f))
+                .add_p(filesystem_3_6::size_limit_property(&f))
+                .add_p(filesystem_3_6::extend_config_property(&f))
+                .add_p(filesystem_3_6::fs_type_property(&f))
+                .add_p(filesystem_3_6::mount_options_property(&f))
+                .add_p(filesystem_3_6::used_exclusive_property(&f))
+                .add_p(filesystem_3_6::used_shared_property(&f)),
         );
 
     let path = object_path.get_name().to_owned();
@@ -172,10 +192,27 @@ where
             consts::FILESYSTEM_NAME_PROP => shared::fs_name_prop(fs_name),
             consts::FILESYSTEM_UUID_PROP => uuid_to_string!(fs_uuid),
             consts::FILESYSTEM_DEVNODE_PROP => shared::fs_devnode_prop::<E>(fs, pool_name, fs_name),
-            consts::FILESYSTEM_POOL_PROP => parent,
+            consts::FILESYSTEM_POOL_PROP => parent.clone(),
             consts::FILESYSTEM_CREATED_PROP => shared::fs_created_prop::<E>(fs),
             consts::FILESYSTEM_SIZE_PROP => shared::fs_size_prop(fs),
             consts::FILESYSTEM_USED_PROP => shared::fs_used_prop::<E>(fs)
+        },
+        consts::FILESYSTEM_INTERFACE_NAME_3_6 => {
+            consts::FILESYSTEM_NAME_PROP => shared::fs_name_prop(fs_name),
+            consts::FILESYSTEM_UUID_PROP => uuid_to_string!(fs_uuid),
+            consts::FILESYSTEM_DEVNODE_PROP => shared::fs_devnode_prop::<E>(fs, pool_name, fs_name),
+            consts::FILESYSTEM_POOL_PROP => parent,
+            consts::FILESYSTEM_CREATED_PROP => shared::fs_created_prop::<E>(fs),
+            consts::FILESYSTEM_SIZE_PROP => shared::fs_size_prop(fs),
+            consts::FILESYSTEM_USED_PROP => shared::fs_used_prop::<E>(fs),
+            consts::FILESYSTEM_ORIGIN_PROP => shared::fs_origin_prop(fs),
+            consts::FILESYSTEM_SNAPSHOT_POLICIES_PROP => shared::fs_snapshot_policies_prop(fs),
+            consts::FILESYSTEM_SIZE_LIMIT_PROP => shared::fs_size_limit_prop(fs),
+            consts::FILESYSTEM_EXTEND_CONFIG_PROP => shared::fs_extend_config_prop(fs),
+            consts::FILESYSTEM_FS_TYPE_PROP => shared::fs_type_prop(fs),
+            consts::FILESYSTEM_MOUNT_OPTIONS_PROP => shared::fs_mount_options_prop(fs),
+            consts::FILESYSTEM_USED_EXCLUSIVE_PROP => shared::fs_used_exclusive_prop(fs),
+            consts::FILESYSTEM_USED_SHARED_PROP => shared::fs_used_shared_prop(fs)
         }
     }
 }
@@ -4,7 +4,23 @@
 
 use devicemapper::Bytes;
 
-use crate::dbus_api::util::option_to_tuple;
+use crate::{
+    dbus_api::util::{option_to_tuple, tuple_to_option},
+    engine::{
+        FilesystemType, FilesystemUuid, FsExtendConfig, FsExtendIncrement, SnapshotPolicy,
+        SpaceUsage,
+    },
+};
+
+/// D-Bus representation of a snapshot policy: its name, interval in seconds,
+/// and optional count of snapshots to keep and maximum age in seconds.
+pub type SnapshotPolicyProp = (String, u64, (bool, u64), (bool, u64));
+
+/// D-Bus representation of a filesystem extension configuration: whether
+/// extension is enabled, the percentage of the filesystem that must be used
+/// before it is extended, whether the increment is a percentage of the size
+/// of the filesystem, and the increment as a percentage or a number of bytes.
+pub type FsExtendConfigProp = (bool, u8, bool, String);
 
 /// Generate D-Bus representation of filesystem size property.
 #[inline]
@@ -17,3 +33,118 @@ pub fn fs_size_to_prop(size: Bytes) -> String {
 pub fn fs_used_to_prop(used: Option<Bytes>) -> (bool, String) {
     option_to_tuple(used.map(|u| (*u).to_string()), String::new())
 }
+
+/// Generate D-Bus representation of exclusively used space property.
+#[inline]
+pub fn fs_used_exclusive_to_prop(space_usage: Option<SpaceUsage>) -> (bool, String) {
+    option_to_tuple(
+        space_usage.map(|u| (*u.exclusive).to_string()),
+        String::new(),
+    )
+}
+
+/// Generate D-Bus representation of shared used space property.
+#[inline]
+pub fn fs_used_shared_to_prop(space_usage: Option<SpaceUsage>) -> (bool, String) {
+    option_to_tuple(space_usage.map(|u| (*u.shared).to_string()), String::new())
+}
+
+/// Generate D-Bus representation of origin property.
+#[inline]
+pub fn fs_origin_to_prop(origin: Option<FilesystemUuid>) -> (bool, String) {
+    option_to_tuple(origin.map(|u| uuid_to_string!(u)), String::new())
+}
+
+/// Generate D-Bus representation of filesystem type property.
+#[inline]
+pub fn fs_type_to_prop(fs_type: FilesystemType) -> String {
+    fs_type.to_string()
+}
+
+/// Generate D-Bus representation of mount options property.
+#[inline]
+pub fn fs_mount_options_to_prop(mount_options: Option<&str>) -> (bool, String) {
+    option_to_tuple(mount_options.map(|o| o.to_string()), String::new())
+}
+
+/// Generate D-Bus representation of size limit property.
+#[inline]
+pub fn fs_size_limit_to_prop(size_limit: Option<Bytes>) -> (bool, String) {
+    option_to_tuple(size_limit.map(|l| (*l).to_string()), String::new())
+}
+
+/// Generate D-Bus representation of snapshot policies property.
+#[inline]
+pub fn fs_snapshot_policies_to_prop(policies: &[SnapshotPolicy]) -> Vec<SnapshotPolicyProp> {
+    policies
+        .iter()
+        .map(|p| {
+            (
+                p.name.clone(),
+                p.interval,
+                option_to_tuple(p.keep_count, 0),
+                option_to_tuple(p.keep_age, 0),
+            )
+        })
+        .collect()
+}
+
+/// Convert the D-Bus representation of snapshot policies to policies.
+#[inline]
+pub fn prop_to_fs_snapshot_policies(policies: Vec<SnapshotPolicyProp>) -> Vec<SnapshotPolicy> {
+    policies
+        .into_iter()
+        .map(|(name, interval, keep_count, keep_age)| SnapshotPolicy {
+            name,
+            interval,
+            keep_count: tuple_to_option(keep_count),
+            keep_age: tuple_to_option(keep_age),
+        })
+        .collect()
+}
+
+/// Generate D-Bus representation of a filesystem extension configuration.
+pub fn fs_extend_config_to_prop(config: FsExtendConfig) -> FsExtendConfigProp {
+    let (is_percent, increment) = match config.increment {
+        FsExtendIncrement::Percent(percent) => (true, percent.to_string()),
+        FsExtendIncrement::Absolute(amount) => (false, (*amount.bytes()).to_string()),
+    };
+    (config.enabled, config.threshold, is_percent, increment)
+}
+
+/// Generate D-Bus representation of the extension configuration property of
+/// a filesystem, which is unset if the filesystem uses the configuration of
+/// its pool.
+pub fn fs_extend_config_opt_to_prop(config: Option<FsExtendConfig>) -> (bool, FsExtendConfigProp) {
+    option_to_tuple(
+        config.map(fs_extend_config_to_prop),
+        (false, 0, false, String::new()),
+    )
+}
+
+/// Convert the D-Bus representation of a filesystem extension configuration
+/// to a configuration.
+pub fn prop_to_fs_extend_config(
+    (enabled, threshold, is_percent, increment): FsExtendConfigProp,
+) -> Result<FsExtendConfig, String> {
+    let increment = if is_percent {
+        FsExtendIncrement::Percent(increment.parse::<u64>().map_err(|_| {
+            format!("Could not parse extension increment string {increment} to a percentage")
+        })?)
+    } else {
+        let amount = Bytes(increment.parse::<u128>().map_err(|_| {
+            format!("Could not parse extension increment string {increment} to integer value")
+        })?);
+        if amount.sectors().bytes() != amount {
+            return Err(format!(
+                "Extension increment {amount} must be a multiple of the sector size"
+            ));
+        }
+        FsExtendIncrement::Absolute(amount.sectors())
+    };
+    Ok(FsExtendConfig {
+        enabled,
+        threshold,
+        increment,
+    })
+}
@@ -3,13 +3,21 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use chrono::SecondsFormat;
-use dbus::Path;
-use dbus_tree::{MTSync, Tree};
+use dbus::{arg::IterAppend, Path};
+use dbus_tree::{MTSync, MethodErr, PropInfo, Tree};
 use futures::executor::block_on;
 
+use devicemapper::Bytes;
+
 use crate::{
-    dbus_api::{filesystem::prop_conv, types::TData},
-    engine::{Engine, Filesystem, Name, Pool, PoolIdentifier},
+    dbus_api::{
+        filesystem::prop_conv::{self, FsExtendConfigProp, SnapshotPolicyProp},
+        types::TData,
+    },
+    engine::{
+        Engine, Filesystem, FilesystemUuid, FsExtendConfig, Name, Pool, PoolIdentifier,
+        PropChangeAction, RenameAction, SnapshotPolicy, ToDisplay,
+    },
 };
 
 /// Get execute a given closure providing a filesystem object and return
@@ -62,6 +70,93 @@ where
     closure((pool_name, fs_name, fs))
 }
 
+/// Perform a set operation on a filesystem that requires a pool level API
+/// for a given D-Bus implicit argument that is a filesystem.
+pub fn filesystem_pool_level_set_operation<F, R, E>(
+    tree: &Tree<MTSync<TData<E>>, TData<E>>,
+    object_path: &Path<'static>,
+    closure: F,
+) -> Result<R, String>
+where
+    F: Fn(&Name, &mut E::Pool, FilesystemUuid) -> Result<R, String>,
+    E: Engine,
+{
+    let dbus_context = tree.get_data();
+
+    let filesystem_path = tree
+        .get(object_path)
+        .expect("tree must contain implicit argument");
+
+    let filesystem_data = filesystem_path
+        .get_data()
+        .as_ref()
+        .ok_or_else(|| format!("no data for object path {object_path}"))?;
+    let filesystem_uuid = typed_uuid_string_err!(filesystem_data.uuid; Fs);
+
+    let pool_path = tree
+        .get(&filesystem_data.parent)
+        .ok_or_else(|| format!("no path for parent object path {}", &filesystem_data.parent))?;
+
+    let pool_uuid = typed_uuid_string_err!(
+        pool_path
+            .get_data()
+            .as_ref()
+            .ok_or_else(|| format!("no data for object path {object_path}"))?
+            .uuid;
+        Pool
+    );
+
+    let mut guard = block_on(
+        dbus_context
+            .engine
+            .get_mut_pool(PoolIdentifier::Uuid(pool_uuid)),
+    )
+    .ok_or_else(|| format!("no pool corresponding to uuid {}", &pool_uuid))?;
+    let (name, _, pool) = guard.as_mut_tuple();
+    closure(&name, pool, filesystem_uuid)
+}
+
+/// Get a filesystem property and place it on the D-Bus. The property is
+/// found by means of the getter method which takes a reference to a
+/// Filesystem and obtains the property from the filesystem.
+pub fn get_filesystem_property<F, R, E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+    getter: F,
+) -> Result<(), MethodErr>
+where
+    F: Fn((Name, Name, &<E::Pool as Pool>::Filesystem)) -> Result<R, String>,
+    R: dbus::arg::Append,
+    E: Engine,
+{
+    i.append(
+        filesystem_operation(p.tree, p.path.get_name(), getter)
+            .map_err(|ref e| MethodErr::failed(e))?,
+    );
+    Ok(())
+}
+
+/// Set a filesystem property that requires a pool level API. The property
+/// is set by means of the setter method which takes a mutable reference to
+/// the pool and the UUID of the filesystem.
+pub fn set_pool_level_filesystem_property_to_display<F, R, E>(
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+    prop_name: &str,
+    setter: F,
+) -> Result<R, MethodErr>
+where
+    F: Fn(&Name, &mut E::Pool, FilesystemUuid) -> Result<R, String>,
+    E: Engine,
+    R: ToDisplay,
+{
+    info!("Setting property {}", prop_name);
+    let res = filesystem_pool_level_set_operation(p.tree, p.path.get_name(), setter)
+        .map_err(|ref e| MethodErr::failed(e));
+    let res_display = res.as_ref().map(|o| o.to_display());
+    let _ = handle_action!(res_display);
+    res
+}
+
 /// Generate D-Bus representation of name property.
 #[inline]
 pub fn fs_name_prop(name: &Name) -> String {
@@ -104,3 +199,103 @@ where
 {
     prop_conv::fs_used_to_prop(fs.used().ok())
 }
+
+/// Generate D-Bus representation of exclusively used space property.
+pub fn fs_used_exclusive_prop(fs: &dyn Filesystem) -> (bool, String) {
+    prop_conv::fs_used_exclusive_to_prop(fs.space_usage())
+}
+
+/// Generate D-Bus representation of shared used space property.
+pub fn fs_used_shared_prop(fs: &dyn Filesystem) -> (bool, String) {
+    prop_conv::fs_used_shared_to_prop(fs.space_usage())
+}
+
+/// Generate D-Bus representation of origin property.
+pub fn fs_origin_prop(fs: &dyn Filesystem) -> (bool, String) {
+    prop_conv::fs_origin_to_prop(fs.origin())
+}
+
+/// Generate D-Bus representation of filesystem type property.
+pub fn fs_type_prop(fs: &dyn Filesystem) -> String {
+    prop_conv::fs_type_to_prop(fs.fs_type())
+}
+
+/// Generate D-Bus representation of mount options property.
+pub fn fs_mount_options_prop(fs: &dyn Filesystem) -> (bool, String) {
+    prop_conv::fs_mount_options_to_prop(fs.mount_options())
+}
+
+/// Generate D-Bus representation of snapshot policies property.
+pub fn fs_snapshot_policies_prop(fs: &dyn Filesystem) -> Vec<SnapshotPolicyProp> {
+    prop_conv::fs_snapshot_policies_to_prop(fs.snapshot_policies())
+}
+
+/// Set the snapshot policies of a filesystem.
+pub fn set_fs_snapshot_policies_prop<E>(
+    pool: &mut E::Pool,
+    pool_name: &Name,
+    fs_uuid: FilesystemUuid,
+    policies: &[SnapshotPolicy],
+) -> Result<PropChangeAction<Vec<SnapshotPolicy>>, String>
+where
+    E: Engine,
+{
+    match pool
+        .set_snapshot_policies(pool_name, fs_uuid, policies)
+        .map_err(|e| e.to_string())?
+    {
+        RenameAction::Renamed(_) => Ok(PropChangeAction::NewValue(policies.to_vec())),
+        RenameAction::Identity => Ok(PropChangeAction::Identity),
+        RenameAction::NoSource => Err(format!("Filesystem with UUID {fs_uuid} not found")),
+    }
+}
+
+/// Generate D-Bus representation of size limit property.
+pub fn fs_size_limit_prop(fs: &dyn Filesystem) -> (bool, String) {
+    prop_conv::fs_size_limit_to_prop(fs.size_limit().map(|l| l.bytes()))
+}
+
+/// Set the size limit of a filesystem.
+pub fn set_fs_size_limit_prop<E>(
+    pool: &mut E::Pool,
+    pool_name: &Name,
+    fs_uuid: FilesystemUuid,
+    size_limit: Option<Bytes>,
+) -> Result<PropChangeAction<Option<Bytes>>, String>
+where
+    E: Engine,
+{
+    match pool
+        .set_fs_size_limit(pool_name, fs_uuid, size_limit)
+        .map_err(|e| e.to_string())?
+    {
+        RenameAction::Renamed(_) => Ok(PropChangeAction::NewValue(size_limit)),
+        RenameAction::Identity => Ok(PropChangeAction::Identity),
+        RenameAction::NoSource => Err(format!("Filesystem with UUID {fs_uuid} not found")),
+    }
+}
+
+/// Generate D-Bus representation of extension configuration property.
+pub fn fs_extend_config_prop(fs: &dyn Filesystem) -> (bool, FsExtendConfigProp) {
+    prop_conv::fs_extend_config_opt_to_prop(fs.extend_config())
+}
+
+/// Set the extension configuration of a filesystem.
+pub fn set_fs_extend_config_prop<E>(
+    pool: &mut E::Pool,
+    pool_name: &Name,
+    fs_uuid: FilesystemUuid,
+    config: Option<FsExtendConfig>,
+) -> Result<PropChangeAction<Option<FsExtendConfig>>, String>
+where
+    E: Engine,
+{
+    match pool
+        .set_fs_extend_config(pool_name, fs_uuid, config)
+        .map_err(|e| e.to_string())?
+    {
+        RenameAction::Renamed(_) => Ok(PropChangeAction::NewValue(config)),
+        RenameAction::Identity => Ok(PropChangeAction::Identity),
+        RenameAction::NoSource => Err(format!("Filesystem with UUID {fs_uuid} not found")),
+    }
+}
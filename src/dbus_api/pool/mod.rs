@@ -17,9 +17,12 @@ mod pool_3_0;
 mod pool_3_1;
 mod pool_3_3;
 mod pool_3_5;
+mod pool_3_6;
 pub mod prop_conv;
 mod shared;
 
+pub use shared::push_unset_origins;
+
 pub fn create_dbus_pool<'a, E>(
     dbus_context: &DbusContext<E>,
     parent: dbus::Path<'static>,
@@ -213,6 +216,54 @@ where
                 .add_p(pool_3_1::fs_limit_property(&f))
                 .add_p(pool_3_1::enable_overprov_property(&f))
                 .add_p(pool_3_1::no_alloc_space_property(&f)),
+        )
+        .add(
+            f.interface(consts::POOL_INTERFACE_NAME_3_6, ())
+                .add_m(pool_3_6::create_filesystems_method(&f))
+                .add_m(pool_3_0::destroy_filesystems_method(&f))
+                .add_m(pool_3_0::snapshot_filesystem_method(&f))
+                .add_m(pool_3_0::add_blockdevs_method(&f))
+                .add_m(pool_3_0::bind_clevis_method(&f))
+                .add_m(pool_3_0::unbind_clevis_method(&f))
+                .add_m(pool_3_6::init_cache_method(&f))
+                .add_m(pool_3_0::add_cachedevs_method(&f))
+                .add_m(pool_3_0::bind_keyring_method(&f))
+                .add_m(pool_3_0::unbind_keyring_method(&f))
+                .add_m(pool_3_0::rebind_keyring_method(&f))
+                .add_m(pool_3_0::rebind_clevis_method(&f))
+                .add_m(pool_3_0::rename_method(&f))
+                .add_m(pool_3_3::grow_physical_device_method(&f))
+                .add_m(pool_3_6::replace_blockdev_method(&f))
+                .add_m(pool_3_6::remove_cache_method(&f))
+                .add_m(pool_3_6::revert_filesystem_method(&f))
+                .add_m(pool_3_6::encrypt_pool_method(&f))
+                .add_m(pool_3_6::rotate_volume_key_method(&f))
+                .add_m(pool_3_6::add_passphrase_binding_method(&f))
+                .add_m(pool_3_6::remove_passphrase_binding_method(&f))
+                .add_p(pool_3_0::name_property(&f))
+                .add_p(pool_3_0::uuid_property(&f))
+                .add_p(pool_3_0::encrypted_property(&f))
+                .add_p(pool_3_0::avail_actions_property(&f))
+                .add_p(pool_3_0::key_desc_property(&f))
+                .add_p(pool_3_0::clevis_info_property(&f))
+                .add_p(pool_3_0::has_cache_property(&f))
+                .add_p(pool_3_0::alloc_size_property(&f))
+                .add_p(pool_3_0::used_size_property(&f))
+                .add_p(pool_3_0::total_size_property(&f))
+                .add_p(pool_3_1::fs_limit_property(&f))
+                .add_p(pool_3_1::enable_overprov_property(&f))
+                .add_p(pool_3_1::no_alloc_space_property(&f))
+                .add_p(pool_3_6::replace_progress_property(&f))
+                .add_p(pool_3_6::reencryption_progress_property(&f))
+                .add_p(pool_3_6::cache_config_property(&f))
+                .add_p(pool_3_6::cache_stats_property(&f))
+                .add_p(pool_3_6::allocation_config_property(&f))
+                .add_p(pool_3_6::discard_passdown_property(&f))
+                .add_p(pool_3_6::fs_extend_config_property(&f))
+                .add_p(pool_3_6::data_block_size_property(&f))
+                .add_p(pool_3_6::thin_pool_extend_policy_property(&f))
+                .add_p(pool_3_6::encryption_config_property(&f))
+                .add_p(pool_3_6::passphrase_bindings_property(&f)),
         );
 
     let path = object_path.get_name().to_owned();
@@ -317,6 +368,29 @@ where
             consts::POOL_FS_LIMIT_PROP => shared::pool_fs_limit::<E>(pool),
             consts::POOL_OVERPROV_PROP => shared::pool_overprov_enabled::<E>(pool),
             consts::POOL_NO_ALLOCABLE_SPACE_PROP => shared::pool_no_alloc_space::<E>(pool)
+        },
+        consts::POOL_INTERFACE_NAME_3_6 => {
+            consts::POOL_NAME_PROP => shared::pool_name_prop(pool_name),
+            consts::POOL_UUID_PROP => uuid_to_string!(pool_uuid),
+            consts::POOL_ENCRYPTED_PROP => shared::pool_enc_prop::<E>(pool),
+            consts::POOL_AVAIL_ACTIONS_PROP => shared::pool_avail_actions_prop::<E>(pool),
+            consts::POOL_KEY_DESC_PROP => shared::pool_key_desc_prop::<E>(pool),
+            consts::POOL_CLEVIS_INFO_PROP => shared::pool_clevis_info_prop::<E>(pool),
+            consts::POOL_HAS_CACHE_PROP => shared::pool_has_cache_prop::<E>(pool),
+            consts::POOL_ALLOC_SIZE_PROP => shared::pool_allocated_size::<E>(pool),
+            consts::POOL_TOTAL_USED_PROP => shared::pool_used_size::<E>(pool),
+            consts::POOL_TOTAL_SIZE_PROP => shared::pool_total_size::<E>(pool),
+            consts::POOL_FS_LIMIT_PROP => shared::pool_fs_limit::<E>(pool),
+            consts::POOL_OVERPROV_PROP => shared::pool_overprov_enabled::<E>(pool),
+            consts::POOL_NO_ALLOCABLE_SPACE_PROP => shared::pool_no_alloc_space::<E>(pool),
+            consts::POOL_CACHE_CONFIG_PROP => shared::pool_cache_config::<E>(pool),
+            consts::POOL_ALLOCATION_CONFIG_PROP => shared::pool_allocation_config::<E>(pool),
+            consts::POOL_DISCARD_PASSDOWN_PROP => shared::pool_discard_passdown::<E>(pool),
+            consts::POOL_FS_EXTEND_CONFIG_PROP => shared::pool_fs_extend_config::<E>(pool),
+            consts::POOL_DATA_BLOCK_SIZE_PROP => shared::pool_data_block_size::<E>(pool),
+            consts::POOL_THIN_POOL_EXTEND_POLICY_PROP => shared::pool_thin_pool_extend_policy::<E>(pool),
+            consts::POOL_ENCRYPTION_CONFIG_PROP => shared::pool_encryption_config::<E>(pool),
+            consts::POOL_PASSPHRASE_BINDINGS_PROP => shared::pool_passphrase_bindings::<E>(pool)
         }
     }
 }
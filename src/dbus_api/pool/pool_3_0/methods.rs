@@ -9,15 +9,16 @@ use dbus_tree::{MTSync, MethodInfo, MethodResult};
 use futures::executor::block_on;
 use serde_json::Value;
 
-use devicemapper::Bytes;
-
 use crate::{
     dbus_api::{
         consts::filesystem_interface_list,
         filesystem::create_dbus_filesystem,
-        pool::shared::{add_blockdevs, BlockDevOp},
+        pool::shared::{
+            add_blockdevs, create_filesystems_from_specs, push_unset_origins, snapshots_of,
+            BlockDevOp,
+        },
         types::{DbusErrorEnum, TData, OK_STRING},
-        util::{engine_to_dbus_err_tuple, get_next_arg, tuple_to_option},
+        util::{engine_to_dbus_err_tuple, get_next_arg},
     },
     engine::{
         CreateAction, DeleteAction, Engine, EngineAction, FilesystemUuid, KeyDescription, Name,
@@ -34,101 +35,13 @@ where
     let mut iter = message.iter_init();
 
     let filesystems: Array<'_, (&str, (bool, &str)), _> = get_next_arg(&mut iter, 0)?;
-    let dbus_context = m.tree.get_data();
-
-    let object_path = m.path.get_name();
-    let return_message = message.method_return();
-    let default_return: (bool, Vec<(dbus::Path<'_>, &str)>) = (false, Vec::new());
-
-    if filesystems.count() > 1 {
-        let error_message = "only 1 filesystem per request allowed";
-        let (rc, rs) = (DbusErrorEnum::ERROR as u16, error_message);
-        return Ok(vec![return_message.append3(default_return, rc, rs)]);
-    }
-
-    let pool_path = m
-        .tree
-        .get(object_path)
-        .expect("implicit argument must be in tree");
-    let pool_uuid = typed_uuid!(
-        get_data!(pool_path; default_return; return_message).uuid;
-        Pool;
-        default_return;
-        return_message
-    );
-
-    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
-    let (pool_name, _, pool) = guard.as_mut_tuple();
 
-    let filesystem_specs = match filesystems
-        .map(|(name, size_opt)| {
-            tuple_to_option(size_opt)
-                .map(|val| {
-                    val.parse::<u128>().map_err(|_| {
-                        format!("Could not parse filesystem size string {val} to integer value")
-                    })
-                })
-                .transpose()
-                .map(|size_opt| (name, size_opt.map(Bytes)))
-        })
-        .collect::<Result<Vec<(&str, Option<Bytes>)>, String>>()
-    {
-        Ok(val) => val,
-        Err(err) => {
-            let (rc, rs) = (DbusErrorEnum::ERROR as u16, err);
-            return Ok(vec![return_message.append3(default_return, rc, rs)]);
-        }
-    };
-
-    let result = handle_action!(
-        pool.create_filesystems(&pool_name, pool_uuid, &filesystem_specs),
-        dbus_context,
-        pool_path.get_name()
-    );
-
-    let infos = match result {
-        Ok(created_set) => created_set.changed(),
-        Err(err) => {
-            let (rc, rs) = engine_to_dbus_err_tuple(&err);
-            return Ok(vec![return_message.append3(default_return, rc, rs)]);
-        }
-    };
-
-    let return_value = match infos {
-        Some(ref newly_created_filesystems) => {
-            let v = newly_created_filesystems
-                .iter()
-                .map(|&(name, uuid, _)| {
-                    let filesystem = pool
-                        .get_filesystem(uuid)
-                        .expect("just inserted by create_filesystems")
-                        .1;
-                    // FIXME: To avoid this expect, modify create_filesystem
-                    // so that it returns a mutable reference to the
-                    // filesystem created.
-                    (
-                        create_dbus_filesystem(
-                            dbus_context,
-                            object_path.clone(),
-                            &pool_name,
-                            &Name::new(name.to_string()),
-                            uuid,
-                            filesystem,
-                        ),
-                        name,
-                    )
-                })
-                .collect::<Vec<_>>();
-            (true, v)
-        }
-        None => default_return,
-    };
-
-    Ok(vec![return_message.append3(
-        return_value,
-        DbusErrorEnum::OK as u16,
-        OK_STRING.to_string(),
-    )])
+    create_filesystems_from_specs(
+        m,
+        filesystems
+            .map(|(name, size_opt)| (name, size_opt, (false, ""), (false, ""), HashMap::new()))
+            .collect(),
+    )
 }
 
 pub fn destroy_filesystems<E>(m: &MethodInfo<'_, MTSync<TData<E>>, TData<E>>) -> MethodResult
@@ -171,11 +84,11 @@ where
         }
     }
 
+    let fs_uuids = filesystem_map.keys().cloned().collect::<Vec<_>>();
+    let snapshots = snapshots_of::<E>(pool, &fs_uuids);
+
     let result = handle_action!(
-        pool.destroy_filesystems(
-            &pool_name,
-            &filesystem_map.keys().cloned().collect::<Vec<_>>(),
-        ),
+        pool.destroy_filesystems(&pool_name, &fs_uuids),
         dbus_context,
         pool_path.get_name()
     );
@@ -190,6 +103,14 @@ where
                         .expect("'uuids' is a subset of filesystem_map.keys()");
                     dbus_context.push_remove(op, filesystem_interface_list());
                 }
+                push_unset_origins(
+                    m.tree,
+                    pool_path.get_name(),
+                    &snapshots
+                        .into_iter()
+                        .filter(|uuid| !changed_uuids.contains(uuid))
+                        .collect::<Vec<_>>(),
+                );
                 changed_uuids
                     .iter()
                     .map(|uuid| uuid_to_string!(uuid))
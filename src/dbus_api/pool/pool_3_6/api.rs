@@ -0,0 +1,372 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus_tree::{Access, EmitsChangedSignal, Factory, MTSync, Method, Property};
+
+use crate::{
+    dbus_api::{
+        consts,
+        pool::{
+            pool_3_6::{
+                methods::{
+                    add_passphrase_binding, create_filesystems, encrypt_pool, init_cache,
+                    remove_cache, remove_passphrase_binding, replace_blockdev, revert_filesystem,
+                    rotate_volume_key,
+                },
+                props::{
+                    get_pool_allocation_config, get_pool_cache_config, get_pool_cache_stats,
+                    get_pool_data_block_size, get_pool_discard_passdown,
+                    get_pool_encryption_config, get_pool_fs_extend_config,
+                    get_pool_passphrase_bindings, get_pool_reencryption_progress,
+                    get_pool_replace_progress, get_pool_thin_pool_extend_policy,
+                    set_pool_allocation_config, set_pool_cache_config, set_pool_discard_passdown,
+                    set_pool_fs_extend_config, set_pool_thin_pool_extend_policy,
+                },
+            },
+            prop_conv::EncryptionConfigProp,
+        },
+        types::TData,
+    },
+    engine::Engine,
+};
+
+pub fn create_filesystems_method<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Method<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.method("CreateFilesystems", (), create_filesystems)
+        // s: Name of the filesystem
+        // (bs): Optional size of the filesystem in bytes
+        // (bs): Optional size limit of the filesystem in bytes
+        // (bs): Optional type of the filesystem, "xfs" or "ext4"; XFS if not
+        // specified
+        // a{ss}: Options with which to create the filesystem: any of
+        // "reflink", "bigtime", "inode_size", and "label", which are passed
+        // to mkfs, and "mount_options", the recommended mount options
+        .in_arg(("specs", "a(s(bs)(bs)(bs)a{ss})"))
+        // b: true if filesystems were created
+        // a(os): Array of tuples with object paths and names
+        //
+        // Rust representation: (bool, Vec<(dbus::Path, String)>)
+        .out_arg(("results", "(ba(os))"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn init_cache_method<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Method<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.method("InitCache", (), init_cache)
+        .in_arg(("devices", "as"))
+        // s: Cache mode: "writethrough", "writeback", or "passthrough"
+        // s: Cache policy, e.g. "smq"
+        // a(ss): Cache policy tunables as key/value pairs
+        .in_arg(("cache_config", "(ssa(ss))"))
+        // b: Indicates if any cache devices were added
+        // ao: Array of object paths of created cache devices
+        //
+        // Rust representation: (bool, Vec<dbus::path>)
+        .out_arg(("results", "(bao)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn replace_blockdev_method<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Method<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.method("ReplaceBlockdev", (), replace_blockdev)
+        // o: Object path of the blockdev to replace
+        .in_arg(("old", "o"))
+        // s: Device node of the replacement device
+        .in_arg(("new", "s"))
+        // b: true if the blockdev was replaced
+        // o: Object path of the new blockdev
+        //
+        // Rust representation: (bool, dbus::Path)
+        .out_arg(("result", "(bo)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn remove_cache_method<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Method<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.method("RemoveCache", (), remove_cache)
+        // b: true if the cache was removed
+        // ao: Object paths of the removed cache blockdevs
+        //
+        // Rust representation: (bool, Vec<dbus::Path>)
+        .out_arg(("results", "(bao)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn revert_filesystem_method<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Method<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.method("RevertFilesystem", (), revert_filesystem)
+        // o: Object path of the filesystem to revert
+        .in_arg(("origin", "o"))
+        // o: Object path of the snapshot to revert to
+        .in_arg(("snapshot", "o"))
+        // b: true if the filesystem was reverted
+        // o: Object path of the removed snapshot
+        //
+        // Rust representation: (bool, dbus::Path)
+        .out_arg(("result", "(bo)"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn encrypt_pool_method<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Method<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.method("EncryptPool", (), encrypt_pool)
+        // Optional key description of key in the kernel keyring
+        // b: true if the pool should be able to be unlocked with a
+        // passphrase associated with this key description.
+        // s: key description
+        //
+        // Rust representation: (bool, String)
+        .in_arg(("key_desc", "(bs)"))
+        // Optional Clevis information for binding
+        // b: true if the pool should be able to be unlocked using Clevis.
+        // s: pin name
+        // s: JSON config for Clevis use
+        //
+        // Rust representation: (bool, (String, String))
+        .in_arg(("clevis_info", "(b(ss))"))
+        // b: true if the pool was encrypted
+        .out_arg(("result", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn rotate_volume_key_method<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Method<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.method("RotateVolumeKey", (), rotate_volume_key)
+        // b: true if the rotation of the volume keys was begun
+        .out_arg(("result", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn add_passphrase_binding_method<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Method<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.method("AddPassphraseBinding", (), add_passphrase_binding)
+        // s: Name of the binding, e.g. "recovery"
+        .in_arg(("name", "s"))
+        .in_arg(("key_desc", "s"))
+        // b: Indicates if the passphrase binding was added
+        //
+        // Rust representation: bool
+        .out_arg(("results", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn remove_passphrase_binding_method<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Method<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.method("RemovePassphraseBinding", (), remove_passphrase_binding)
+        .in_arg(("name", "s"))
+        // b: Indicates if the passphrase binding was removed
+        //
+        // Rust representation: bool
+        .out_arg(("results", "b"))
+        .out_arg(("return_code", "q"))
+        .out_arg(("return_string", "s"))
+}
+
+pub fn replace_progress_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.property::<(bool, (&str, &str, &str, &str)), _>(consts::POOL_REPLACE_PROGRESS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_replace_progress)
+}
+
+pub fn reencryption_progress_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.property::<(bool, (&str, &str)), _>(consts::POOL_REENCRYPTION_PROGRESS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_reencryption_progress)
+}
+
+pub fn cache_config_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.property::<(bool, (&str, &str, Vec<(&str, &str)>)), _>(consts::POOL_CACHE_CONFIG_PROP, ())
+        .access(Access::ReadWrite)
+        .emits_changed(EmitsChangedSignal::True)
+        .auto_emit_on_set(false)
+        .on_get(get_pool_cache_config)
+        .on_set(set_pool_cache_config)
+}
+
+pub fn cache_stats_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.property::<(bool, (u64, u64, u64, u64, u64)), _>(consts::POOL_CACHE_STATS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_cache_stats)
+}
+
+pub fn allocation_config_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    // s: Allocation policy: "fill-first", "spread", or "prefer-tagged"
+    // a(ss): Preferred blockdev tag for each allocation purpose, where the
+    // purpose is one of "thin-data", "thin-meta", or "mdv"
+    f.property::<(&str, Vec<(&str, &str)>), _>(consts::POOL_ALLOCATION_CONFIG_PROP, ())
+        .access(Access::ReadWrite)
+        .emits_changed(EmitsChangedSignal::True)
+        .auto_emit_on_set(false)
+        .on_get(get_pool_allocation_config)
+        .on_set(set_pool_allocation_config)
+}
+
+pub fn discard_passdown_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.property::<bool, _>(consts::POOL_DISCARD_PASSDOWN_PROP, ())
+        .access(Access::ReadWrite)
+        .emits_changed(EmitsChangedSignal::True)
+        .auto_emit_on_set(false)
+        .on_get(get_pool_discard_passdown)
+        .on_set(set_pool_discard_passdown)
+}
+
+pub fn fs_extend_config_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    // b: Whether filesystems are extended automatically
+    // y: Percentage of a filesystem that must be used before it is extended
+    // b: Whether the increment is a percentage of the size of the filesystem
+    // s: The increment as a percentage or a number of bytes
+    f.property::<(bool, u8, bool, &str), _>(consts::POOL_FS_EXTEND_CONFIG_PROP, ())
+        .access(Access::ReadWrite)
+        .emits_changed(EmitsChangedSignal::True)
+        .auto_emit_on_set(false)
+        .on_get(get_pool_fs_extend_config)
+        .on_set(set_pool_fs_extend_config)
+}
+
+pub fn data_block_size_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    f.property::<&str, _>(consts::POOL_DATA_BLOCK_SIZE_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::Const)
+        .on_get(get_pool_data_block_size)
+}
+
+pub fn thin_pool_extend_policy_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    // s: The amount of free space in bytes below which the thin pool data
+    // device is extended
+    // s: The number of bytes by which the thin pool data device is extended
+    f.property::<(&str, &str), _>(consts::POOL_THIN_POOL_EXTEND_POLICY_PROP, ())
+        .access(Access::ReadWrite)
+        .emits_changed(EmitsChangedSignal::True)
+        .auto_emit_on_set(false)
+        .on_get(get_pool_thin_pool_extend_policy)
+        .on_set(set_pool_thin_pool_extend_policy)
+}
+
+pub fn encryption_config_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    // b: false if the pool is not encrypted
+    // b: false if the devices of the pool do not all have the same settings
+    // (bs): cipher, e.g. "aes-xts-plain64"
+    // (bu): size of the volume key in bits
+    // (bs): PBKDF of the keyslot of the passphrase in the kernel keyring:
+    // "pbkdf2", "argon2i", or "argon2id"
+    // (bu): memory cost of the PBKDF in KiB
+    // (bu): iteration cost of the PBKDF
+    // (bu): encryption sector size in bytes
+    f.property::<(bool, (bool, EncryptionConfigProp)), _>(consts::POOL_ENCRYPTION_CONFIG_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_encryption_config)
+}
+
+pub fn passphrase_bindings_property<E>(
+    f: &Factory<MTSync<TData<E>>, TData<E>>,
+) -> Property<MTSync<TData<E>>, TData<E>>
+where
+    E: 'static + Engine,
+{
+    // b: false if the pool is not encrypted
+    // b: false if the devices of the pool do not all have the same bindings
+    // a(ss): name and key description of each named passphrase binding
+    f.property::<(bool, (bool, Vec<(&str, &str)>)), _>(consts::POOL_PASSPHRASE_BINDINGS_PROP, ())
+        .access(Access::Read)
+        .emits_changed(EmitsChangedSignal::False)
+        .on_get(get_pool_passphrase_bindings)
+}
@@ -0,0 +1,537 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::{collections::HashMap, path::Path};
+
+use dbus::{arg::Array, Message};
+use dbus_tree::{MTSync, MethodInfo, MethodResult};
+
+use crate::{
+    dbus_api::{
+        blockdev::create_dbus_blockdev,
+        consts::{blockdev_interface_list, filesystem_interface_list},
+        pool::shared::{
+            add_blockdevs, create_filesystems_from_specs, push_unset_origins, snapshots_of,
+            BlockDevOp,
+        },
+        types::{DbusErrorEnum, TData, OK_STRING},
+        util::{engine_to_dbus_err_tuple, get_next_arg, tuple_to_option},
+    },
+    engine::{
+        BlockDevTier, CacheConfig, CacheMode, CreateAction, DeleteAction, EncryptionInfo, Engine,
+        EngineAction, KeyDescription, Pool, StratisUuid,
+    },
+    stratis::StratisError,
+};
+
+pub fn create_filesystems<E>(m: &MethodInfo<'_, MTSync<TData<E>>, TData<E>>) -> MethodResult
+where
+    E: 'static + Engine,
+{
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let filesystems: Array<
+        '_,
+        (
+            &str,
+            (bool, &str),
+            (bool, &str),
+            (bool, &str),
+            HashMap<&str, &str>,
+        ),
+        _,
+    > = get_next_arg(&mut iter, 0)?;
+
+    create_filesystems_from_specs(m, filesystems.collect())
+}
+
+pub fn init_cache<E>(m: &MethodInfo<'_, MTSync<TData<E>>, TData<E>>) -> MethodResult
+where
+    E: 'static + Engine,
+{
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let _: Array<'_, &str, _> = get_next_arg(&mut iter, 0)?;
+    let (mode, policy, policy_args): (String, String, Vec<(String, String)>) =
+        get_next_arg(&mut iter, 1)?;
+
+    let mode = match CacheMode::try_from(mode.as_str()) {
+        Ok(mode) => mode,
+        Err(err) => {
+            let default_return: (bool, Vec<dbus::Path<'_>>) = (false, Vec::new());
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return Ok(vec![message.method_return().append3(
+                default_return,
+                rc,
+                rs,
+            )]);
+        }
+    };
+
+    add_blockdevs(
+        m,
+        BlockDevOp::InitCacheWithConfig(CacheConfig {
+            mode,
+            policy,
+            policy_args,
+        }),
+    )
+}
+
+pub fn replace_blockdev<E>(m: &MethodInfo<'_, MTSync<TData<E>>, TData<E>>) -> MethodResult
+where
+    E: 'static + Engine,
+{
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let old_path: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+    let new: &str = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = (false, dbus::Path::default());
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let old_uuid = match m.tree.get(&old_path) {
+        Some(op) => typed_uuid!(
+            get_data!(op; default_return; return_message).uuid;
+            Dev;
+            default_return;
+            return_message
+        ),
+        None => {
+            let message = format!("no blockdev with object path {old_path}");
+            return Ok(vec![return_message.append3(
+                default_return,
+                DbusErrorEnum::ERROR as u16,
+                message,
+            )]);
+        }
+    };
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let result = handle_action!(
+        pool.replace_blockdev(&pool_name, pool_uuid, old_uuid, Path::new(new)),
+        dbus_context,
+        pool_path.get_name()
+    );
+
+    let msg = match result.map(|action| action.changed()) {
+        Ok(Some(new_uuid)) => {
+            dbus_context.push_remove(&old_path, blockdev_interface_list());
+            let new_path = create_dbus_blockdev(
+                dbus_context,
+                object_path.clone(),
+                new_uuid,
+                BlockDevTier::Data,
+                pool.get_blockdev(new_uuid)
+                    .expect("just inserted by replace_blockdev")
+                    .1,
+            );
+            return_message.append3(
+                (true, new_path),
+                DbusErrorEnum::OK as u16,
+                OK_STRING.to_string(),
+            )
+        }
+        Ok(None) => return_message.append3(
+            default_return,
+            DbusErrorEnum::OK as u16,
+            OK_STRING.to_string(),
+        ),
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+
+    Ok(vec![msg])
+}
+
+pub fn remove_cache<E>(m: &MethodInfo<'_, MTSync<TData<E>>, TData<E>>) -> MethodResult
+where
+    E: 'static + Engine,
+{
+    let message: &Message = m.msg;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return: (bool, Vec<dbus::Path<'static>>) = (false, Vec::new());
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let result = handle_action!(
+        pool.remove_cache(pool_uuid, &pool_name),
+        dbus_context,
+        pool_path.get_name()
+    );
+
+    let msg = match result.map(|action| action.changed()) {
+        Ok(Some(uuids)) => {
+            let blockdev_paths = m
+                .tree
+                .iter()
+                .filter_map(|opath| {
+                    opath
+                        .get_data()
+                        .as_ref()
+                        .and_then(|op_cxt| match op_cxt.uuid {
+                            StratisUuid::Dev(uuid)
+                                if op_cxt.parent == *object_path && uuids.contains(&uuid) =>
+                            {
+                                Some(opath.get_name().clone())
+                            }
+                            _ => None,
+                        })
+                })
+                .collect::<Vec<_>>();
+            for path in blockdev_paths.iter() {
+                dbus_context.push_remove(path, blockdev_interface_list());
+            }
+            dbus_context.push_pool_cache_change(pool_path.get_name(), false);
+            dbus_context.push_pool_cache_config_change(pool_path.get_name(), None);
+            return_message.append3(
+                (true, blockdev_paths),
+                DbusErrorEnum::OK as u16,
+                OK_STRING.to_string(),
+            )
+        }
+        Ok(None) => return_message.append3(
+            default_return,
+            DbusErrorEnum::OK as u16,
+            OK_STRING.to_string(),
+        ),
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+
+    Ok(vec![msg])
+}
+
+pub fn revert_filesystem<E>(m: &MethodInfo<'_, MTSync<TData<E>>, TData<E>>) -> MethodResult
+where
+    E: 'static + Engine,
+{
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let origin: dbus::Path<'static> = get_next_arg(&mut iter, 0)?;
+    let snapshot: dbus::Path<'static> = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = (false, dbus::Path::default());
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut fs_uuids = Vec::new();
+    for path in [&origin, &snapshot] {
+        match m.tree.get(path) {
+            Some(op) => fs_uuids.push(typed_uuid!(
+                get_data!(op; default_return; return_message).uuid;
+                Fs;
+                default_return;
+                return_message
+            )),
+            None => {
+                let message = format!("no data for object path {path}");
+                let (rc, rs) = (DbusErrorEnum::ERROR as u16, message);
+                return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            }
+        }
+    }
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let snapshots = snapshots_of::<E>(pool, &fs_uuids[1..]);
+
+    let result = handle_action!(
+        pool.revert_filesystem(&pool_name, pool_uuid, fs_uuids[0], fs_uuids[1]),
+        dbus_context,
+        pool_path.get_name()
+    );
+
+    let msg = match result.map(|action| action.changed()) {
+        Ok(Some(_)) => {
+            dbus_context.push_remove(&snapshot, filesystem_interface_list());
+            push_unset_origins(m.tree, pool_path.get_name(), &snapshots);
+            return_message.append3(
+                (true, snapshot),
+                DbusErrorEnum::OK as u16,
+                OK_STRING.to_string(),
+            )
+        }
+        Ok(None) => return_message.append3(
+            default_return,
+            DbusErrorEnum::OK as u16,
+            OK_STRING.to_string(),
+        ),
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+
+    Ok(vec![msg])
+}
+
+pub fn encrypt_pool<E>(m: &MethodInfo<'_, MTSync<TData<E>>, TData<E>>) -> MethodResult
+where
+    E: 'static + Engine,
+{
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+
+    let key_desc_tuple: (bool, String) = get_next_arg(&mut iter, 0)?;
+    let clevis_tuple: (bool, (String, String)) = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let key_desc = match tuple_to_option(key_desc_tuple).map(KeyDescription::try_from) {
+        Some(Ok(kd)) => Some(kd),
+        Some(Err(e)) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+        None => None,
+    };
+
+    let clevis_info = match tuple_to_option(clevis_tuple) {
+        Some((pin, json_string)) => match serde_json::from_str(json_string.as_str()) {
+            Ok(j) => Some((pin, j)),
+            Err(e) => {
+                let (rc, rs) = engine_to_dbus_err_tuple(&StratisError::Serde(e));
+                return Ok(vec![return_message.append3(default_return, rc, rs)]);
+            }
+        },
+        None => None,
+    };
+
+    let encryption_info = match EncryptionInfo::from_options((key_desc, clevis_info)) {
+        Some(ei) => ei,
+        None => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&StratisError::Msg(
+                "Either a key description or Clevis information must be specified to encrypt a pool"
+                    .to_string(),
+            ));
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let msg = match handle_action!(
+        pool.encrypt_pool(&pool_name, pool_uuid, &encryption_info),
+        dbus_context,
+        pool_path.get_name()
+    ) {
+        Ok(CreateAction::Identity) => {
+            return_message.append3(false, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Ok(CreateAction::Created(_)) => {
+            dbus_context.push_pool_key_desc_change(pool_path.get_name(), pool.encryption_info());
+            dbus_context.push_pool_clevis_info_change(pool_path.get_name(), pool.encryption_info());
+            return_message.append3(true, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
+
+pub fn rotate_volume_key<E>(m: &MethodInfo<'_, MTSync<TData<E>>, TData<E>>) -> MethodResult
+where
+    E: 'static + Engine,
+{
+    let message: &Message = m.msg;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut pool = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+
+    let msg = match handle_action!(
+        pool.rotate_volume_key(pool_uuid),
+        dbus_context,
+        pool_path.get_name()
+    ) {
+        Ok(_) => return_message.append3(true, DbusErrorEnum::OK as u16, OK_STRING.to_string()),
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
+
+pub fn add_passphrase_binding<E>(m: &MethodInfo<'_, MTSync<TData<E>>, TData<E>>) -> MethodResult
+where
+    E: 'static + Engine,
+{
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let name: String = get_next_arg(&mut iter, 0)?;
+    let key_desc_str: String = get_next_arg(&mut iter, 1)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let key_desc = match KeyDescription::try_from(key_desc_str) {
+        Ok(kd) => kd,
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut pool = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+
+    let msg = match handle_action!(
+        pool.add_passphrase_binding(&name, &key_desc),
+        dbus_context,
+        pool_path.get_name()
+    ) {
+        Ok(CreateAction::Identity) => {
+            return_message.append3(false, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Ok(CreateAction::Created(_)) => {
+            return_message.append3(true, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
+
+pub fn remove_passphrase_binding<E>(m: &MethodInfo<'_, MTSync<TData<E>>, TData<E>>) -> MethodResult
+where
+    E: 'static + Engine,
+{
+    let message: &Message = m.msg;
+    let mut iter = message.iter_init();
+    let name: String = get_next_arg(&mut iter, 0)?;
+
+    let dbus_context = m.tree.get_data();
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return = false;
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut pool = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+
+    let msg = match handle_action!(
+        pool.remove_passphrase_binding(&name),
+        dbus_context,
+        pool_path.get_name()
+    ) {
+        Ok(DeleteAction::Identity) => {
+            return_message.append3(false, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Ok(DeleteAction::Deleted(_)) => {
+            return_message.append3(true, DbusErrorEnum::OK as u16, OK_STRING.to_string())
+        }
+        Err(e) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&e);
+            return_message.append3(default_return, rc, rs)
+        }
+    };
+    Ok(vec![msg])
+}
@@ -0,0 +1,13 @@
+mod api;
+mod methods;
+mod props;
+
+pub use api::{
+    add_passphrase_binding_method, allocation_config_property, cache_config_property,
+    cache_stats_property, create_filesystems_method, data_block_size_property,
+    discard_passdown_property, encrypt_pool_method, encryption_config_property,
+    fs_extend_config_property, init_cache_method, passphrase_bindings_property,
+    reencryption_progress_property, remove_cache_method, remove_passphrase_binding_method,
+    replace_blockdev_method, replace_progress_property, revert_filesystem_method,
+    rotate_volume_key_method, thin_pool_extend_policy_property,
+};
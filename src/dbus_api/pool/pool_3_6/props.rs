@@ -0,0 +1,314 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use dbus::arg::{Iter, IterAppend};
+use dbus_tree::{MTSync, MethodErr, PropInfo};
+
+use crate::{
+    dbus_api::{
+        consts,
+        filesystem::prop_conv::FsExtendConfigProp,
+        pool::{
+            prop_conv::{reencryption_progress_to_prop, replace_progress_to_prop},
+            shared::{self, get_pool_property, set_pool_property},
+        },
+        types::TData,
+    },
+    engine::{Engine, PropChangeAction, StratisUuid},
+};
+
+/// Get the progress of a blockdev replacement in the pool. The pool is
+/// locked for the duration of the replacement, so the progress is obtained
+/// from the engine without accessing the pool.
+pub fn get_pool_replace_progress<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    let pool_uuid = match p.path.get_data().as_ref().map(|data| &data.uuid) {
+        Some(StratisUuid::Pool(uuid)) => *uuid,
+        _ => {
+            return Err(MethodErr::failed(&format!(
+                "no pool UUID for object path {}",
+                p.path.get_name()
+            )))
+        }
+    };
+    i.append(replace_progress_to_prop(
+        p.tree.get_data().engine.replace_progress(pool_uuid),
+    ));
+    Ok(())
+}
+
+/// Get the progress of a volume key rotation in the pool. The data is
+/// reencrypted in the background, so the progress is obtained from the
+/// engine without accessing the pool.
+pub fn get_pool_reencryption_progress<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    let pool_uuid = match p.path.get_data().as_ref().map(|data| &data.uuid) {
+        Some(StratisUuid::Pool(uuid)) => *uuid,
+        _ => {
+            return Err(MethodErr::failed(&format!(
+                "no pool UUID for object path {}",
+                p.path.get_name()
+            )))
+        }
+    };
+    i.append(reencryption_progress_to_prop(
+        p.tree.get_data().engine.reencryption_progress(pool_uuid),
+    ));
+    Ok(())
+}
+
+pub fn get_pool_cache_config<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_cache_config::<E>(pool))
+    })
+}
+
+pub fn set_pool_cache_config<E>(
+    i: &mut Iter<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    let (has_config, config): (bool, (String, String, Vec<(String, String)>)) =
+        i.get().ok_or_else(|| {
+            MethodErr::failed(
+                "A cache mode and policy are required to change the cache configuration",
+            )
+        })?;
+    if !has_config {
+        return Err(MethodErr::failed(
+            "The cache configuration can not be unset; remove the cache instead",
+        ));
+    }
+    let res = set_pool_property(p, consts::POOL_CACHE_CONFIG_PROP, |(name, _, pool)| {
+        shared::set_pool_cache_config::<E>(&name, pool, config.clone())
+    });
+    match res {
+        Ok(PropChangeAction::NewValue(v)) => {
+            p.tree
+                .get_data()
+                .push_pool_cache_config_change(p.path.get_name(), Some(v));
+            Ok(())
+        }
+        Ok(PropChangeAction::Identity) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn get_pool_allocation_config<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_allocation_config::<E>(pool))
+    })
+}
+
+pub fn set_pool_allocation_config<E>(
+    i: &mut Iter<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    let config: (String, Vec<(String, String)>) = i.get().ok_or_else(|| {
+        MethodErr::failed("An allocation policy is required to change the allocation configuration")
+    })?;
+    let res = set_pool_property(p, consts::POOL_ALLOCATION_CONFIG_PROP, |(name, _, pool)| {
+        shared::set_pool_allocation_config::<E>(&name, pool, config.clone())
+    });
+    match res {
+        Ok(PropChangeAction::NewValue(v)) => {
+            p.tree
+                .get_data()
+                .push_pool_allocation_config_change(p.path.get_name(), v);
+            Ok(())
+        }
+        Ok(PropChangeAction::Identity) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn get_pool_discard_passdown<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_discard_passdown::<E>(pool))
+    })
+}
+
+pub fn set_pool_discard_passdown<E>(
+    i: &mut Iter<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    let enabled: bool = i.get().ok_or_else(|| {
+        MethodErr::failed("Discard passdown changes require a boolean as an argument")
+    })?;
+    let res = set_pool_property(p, consts::POOL_DISCARD_PASSDOWN_PROP, |(name, _, pool)| {
+        shared::set_pool_discard_passdown::<E>(&name, pool, enabled)
+    });
+    match res {
+        Ok(PropChangeAction::NewValue(v)) => {
+            p.tree
+                .get_data()
+                .push_pool_discard_passdown_change(p.path.get_name(), v);
+            Ok(())
+        }
+        Ok(PropChangeAction::Identity) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn get_pool_cache_stats<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    get_pool_property(i, p, |(_, _, pool)| shared::pool_cache_stats::<E>(pool))
+}
+
+pub fn get_pool_fs_extend_config<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_fs_extend_config::<E>(pool))
+    })
+}
+
+pub fn set_pool_fs_extend_config<E>(
+    i: &mut Iter<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    let config: FsExtendConfigProp = i.get().ok_or_else(|| {
+        MethodErr::failed(
+            "A filesystem extension configuration is required to change the configuration",
+        )
+    })?;
+    let res = set_pool_property(p, consts::POOL_FS_EXTEND_CONFIG_PROP, |(name, _, pool)| {
+        shared::set_pool_fs_extend_config::<E>(&name, pool, config.clone())
+    });
+    match res {
+        Ok(PropChangeAction::NewValue(v)) => {
+            p.tree
+                .get_data()
+                .push_pool_fs_extend_config_change(p.path.get_name(), v);
+            Ok(())
+        }
+        Ok(PropChangeAction::Identity) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn get_pool_data_block_size<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_data_block_size::<E>(pool))
+    })
+}
+
+pub fn get_pool_encryption_config<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_encryption_config::<E>(pool))
+    })
+}
+
+pub fn get_pool_passphrase_bindings<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_passphrase_bindings::<E>(pool))
+    })
+}
+
+pub fn get_pool_thin_pool_extend_policy<E>(
+    i: &mut IterAppend<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    get_pool_property(i, p, |(_, _, pool)| {
+        Ok(shared::pool_thin_pool_extend_policy::<E>(pool))
+    })
+}
+
+pub fn set_pool_thin_pool_extend_policy<E>(
+    i: &mut Iter<'_>,
+    p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
+) -> Result<(), MethodErr>
+where
+    E: 'static + Engine,
+{
+    let policy: (String, String) = i.get().ok_or_else(|| {
+        MethodErr::failed("A thin pool extension policy is required to change the policy")
+    })?;
+    let res = set_pool_property(
+        p,
+        consts::POOL_THIN_POOL_EXTEND_POLICY_PROP,
+        |(name, _, pool)| {
+            shared::set_pool_thin_pool_extend_policy::<E>(&name, pool, policy.clone())
+        },
+    );
+    match res {
+        Ok(PropChangeAction::NewValue(v)) => {
+            p.tree
+                .get_data()
+                .push_pool_thin_pool_extend_policy_change(p.path.get_name(), v);
+            Ok(())
+        }
+        Ok(PropChangeAction::Identity) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
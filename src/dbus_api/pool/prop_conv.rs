@@ -2,11 +2,15 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use devicemapper::Bytes;
+use devicemapper::{Bytes, Sectors};
 
 use crate::{
     dbus_api::util::option_to_tuple,
-    engine::{ActionAvailability, PoolEncryptionInfo},
+    engine::{
+        ActionAvailability, AllocationConfig, CacheConfig, CacheStats, EncryptionConfig,
+        MaybeInconsistent, PassphraseBindings, PoolEncryptionInfo, ReencryptionProgress,
+        ReplaceProgress, ThinPoolExtendPolicy,
+    },
     stratis::StratisResult,
 };
 
@@ -52,6 +56,71 @@ pub fn clevis_info_to_prop(ei: Option<PoolEncryptionInfo>) -> (bool, (bool, (Str
     )
 }
 
+/// D-Bus representation of the encryption settings of a pool: the cipher,
+/// the key size in bits, the PBKDF, the PBKDF memory cost in KiB, the PBKDF
+/// iteration cost, and the encryption sector size in bytes, each of which
+/// may be unknown.
+pub type EncryptionConfigProp = (
+    (bool, String),
+    (bool, u32),
+    (bool, String),
+    (bool, u32),
+    (bool, u32),
+    (bool, u32),
+);
+
+/// Generate D-Bus representation of the named passphrase bindings of a pool.
+pub fn passphrase_bindings_to_prop(
+    bindings: Option<MaybeInconsistent<PassphraseBindings>>,
+) -> (bool, (bool, Vec<(String, String)>)) {
+    match bindings {
+        None => (false, (false, Vec::new())),
+        Some(MaybeInconsistent::Yes) => (true, (false, Vec::new())),
+        Some(MaybeInconsistent::No(bindings)) => (
+            true,
+            (
+                true,
+                bindings
+                    .into_iter()
+                    .map(|(name, kd)| (name, kd.as_application_str().to_string()))
+                    .collect(),
+            ),
+        ),
+    }
+}
+
+/// Generate D-Bus representation of the encryption settings of a pool.
+pub fn encryption_config_to_prop(
+    config: Option<MaybeInconsistent<EncryptionConfig>>,
+) -> (bool, (bool, EncryptionConfigProp)) {
+    let default = (
+        (false, String::new()),
+        (false, 0),
+        (false, String::new()),
+        (false, 0),
+        (false, 0),
+        (false, 0),
+    );
+    match config {
+        None => (false, (false, default)),
+        Some(MaybeInconsistent::Yes) => (true, (false, default)),
+        Some(MaybeInconsistent::No(config)) => (
+            true,
+            (
+                true,
+                (
+                    option_to_tuple(config.cipher, String::new()),
+                    option_to_tuple(config.key_size, 0),
+                    option_to_tuple(config.pbkdf.map(|p| p.to_string()), String::new()),
+                    option_to_tuple(config.pbkdf_memory, 0),
+                    option_to_tuple(config.pbkdf_iterations, 0),
+                    option_to_tuple(config.sector_size, 0),
+                ),
+            ),
+        ),
+    }
+}
+
 /// Generate D-Bus representation of pool state property.
 #[inline]
 pub fn avail_actions_to_prop(avail_actions: ActionAvailability) -> String {
@@ -74,3 +143,115 @@ pub fn pool_alloc_to_prop(alloc: Bytes) -> String {
 pub fn pool_used_to_prop(used: Option<Bytes>) -> (bool, String) {
     option_to_tuple(used.map(|u| (*u).to_string()), String::new())
 }
+
+/// Generate D-Bus representation of the progress of a blockdev replacement.
+pub fn replace_progress_to_prop(
+    progress: Option<ReplaceProgress>,
+) -> (bool, (String, String, String, String)) {
+    option_to_tuple(
+        progress.map(|p| {
+            (
+                uuid_to_string!(p.old),
+                uuid_to_string!(p.new),
+                (*p.copied.bytes()).to_string(),
+                (*p.total.bytes()).to_string(),
+            )
+        }),
+        (String::new(), String::new(), String::new(), String::new()),
+    )
+}
+
+/// Generate D-Bus representation of the progress of a volume key rotation.
+pub fn reencryption_progress_to_prop(
+    progress: Option<ReencryptionProgress>,
+) -> (bool, (String, String)) {
+    option_to_tuple(
+        progress.map(|p| {
+            (
+                (*p.reencrypted.bytes()).to_string(),
+                (*p.total.bytes()).to_string(),
+            )
+        }),
+        (String::new(), String::new()),
+    )
+}
+
+/// Generate D-Bus representation of the mode and policy of the cache.
+pub fn cache_config_to_prop(
+    config: Option<CacheConfig>,
+) -> (bool, (String, String, Vec<(String, String)>)) {
+    option_to_tuple(
+        config.map(|c| (c.mode.to_string(), c.policy, c.policy_args)),
+        (String::new(), String::new(), Vec::new()),
+    )
+}
+
+/// Generate D-Bus representation of the allocation policy of the data tier
+/// and the blockdev tags preferred for each allocation purpose.
+pub fn allocation_config_to_prop(config: AllocationConfig) -> (String, Vec<(String, String)>) {
+    (
+        config.policy.to_string(),
+        config
+            .tags
+            .into_iter()
+            .map(|(purpose, tag)| (purpose.to_string(), tag))
+            .collect(),
+    )
+}
+
+/// Generate D-Bus representation of the hit, miss, and dirty block counters
+/// of the cache.
+pub fn cache_stats_to_prop(stats: Option<CacheStats>) -> (bool, (u64, u64, u64, u64, u64)) {
+    option_to_tuple(
+        stats.map(|s| {
+            (
+                s.read_hits,
+                s.read_misses,
+                s.write_hits,
+                s.write_misses,
+                s.dirty,
+            )
+        }),
+        (0, 0, 0, 0, 0),
+    )
+}
+
+/// Generate D-Bus representation of the data block size of the thin pool in
+/// bytes.
+pub fn data_block_size_to_prop(size: Sectors) -> String {
+    (*size.bytes()).to_string()
+}
+
+/// Generate D-Bus representation of the thin pool extension policy: the low
+/// water mark and the extension size, both in bytes.
+pub fn thin_pool_extend_policy_to_prop(policy: ThinPoolExtendPolicy) -> (String, String) {
+    (
+        (*policy.low_water.bytes()).to_string(),
+        (*policy.extend_size.bytes()).to_string(),
+    )
+}
+
+/// Convert a number of bytes, as received over D-Bus, to a number of sectors.
+pub fn prop_to_sectors(size: &str, desc: &str) -> Result<Sectors, String> {
+    let amount = Bytes(
+        size.parse::<u128>()
+            .map_err(|_| format!("Could not parse {desc} string {size} to integer value"))?,
+    );
+    if amount.sectors().bytes() != amount {
+        return Err(format!(
+            "The {desc} {amount} must be a multiple of the sector size"
+        ));
+    }
+    Ok(amount.sectors())
+}
+
+/// Convert the D-Bus representation of a thin pool extension policy to a
+/// policy.
+pub fn prop_to_thin_pool_extend_policy(
+    (low_water, extend_size): (String, String),
+) -> Result<ThinPoolExtendPolicy, String> {
+    Ok(ThinPoolExtendPolicy {
+        low_water: prop_to_sectors(&low_water, "low water mark")?,
+        extend_size: prop_to_sectors(&extend_size, "extension size")?,
+    })
+}
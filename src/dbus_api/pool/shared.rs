@@ -2,7 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::{fmt::Display, path::Path};
+use std::{collections::HashMap, fmt::Display, path::Path};
 
 use dbus::{
     arg::{Array, IterAppend},
@@ -11,21 +11,31 @@ use dbus::{
 use dbus_tree::{MTSync, MethodErr, MethodInfo, MethodResult, PropInfo, Tree};
 use futures::executor::block_on;
 
+use devicemapper::Bytes;
+
 use crate::{
     dbus_api::{
         blockdev::create_dbus_blockdev,
-        pool::prop_conv,
+        filesystem::{
+            create_dbus_filesystem,
+            prop_conv::{fs_extend_config_to_prop, prop_to_fs_extend_config, FsExtendConfigProp},
+        },
+        pool::prop_conv::{self, EncryptionConfigProp},
         types::{DbusErrorEnum, TData, OK_STRING},
-        util::{engine_to_dbus_err_tuple, get_next_arg},
+        util::{engine_to_dbus_err_tuple, get_next_arg, tuple_to_option},
     },
     engine::{
-        BlockDevTier, Engine, EngineAction, Name, Pool, PoolIdentifier, PoolUuid, PropChangeAction,
+        AllocationConfig, AllocationPolicy, AllocationPurpose, BlockDevTier, CacheConfig,
+        CacheMode, Engine, EngineAction, Filesystem, FilesystemSpec, FilesystemType,
+        FilesystemUuid, FsCreateOptions, FsExtendConfig, Name, Pool, PoolIdentifier, PoolUuid,
+        PropChangeAction, StratisUuid, ThinPoolExtendPolicy,
     },
 };
 
 pub enum BlockDevOp {
     InitCache,
     InitCacheWithEnc,
+    InitCacheWithConfig(CacheConfig),
     AddCache,
     AddData,
 }
@@ -143,20 +153,44 @@ where
     let result = match op {
         BlockDevOp::InitCache => {
             let res = handle_action!(
-                pool.init_cache(pool_uuid, &pool_name, &blockdevs, false),
+                pool.init_cache(
+                    pool_uuid,
+                    &pool_name,
+                    &blockdevs,
+                    &CacheConfig::default(),
+                    false
+                ),
                 dbus_context,
                 pool_path.get_name()
             );
             dbus_context.push_pool_cache_change(pool_path.get_name(), true);
+            dbus_context.push_pool_cache_config_change(pool_path.get_name(), pool.cache_config());
             res
         }
         BlockDevOp::InitCacheWithEnc => {
             let res = handle_action!(
-                pool.init_cache(pool_uuid, &pool_name, &blockdevs, true),
+                pool.init_cache(
+                    pool_uuid,
+                    &pool_name,
+                    &blockdevs,
+                    &CacheConfig::default(),
+                    true
+                ),
                 dbus_context,
                 pool_path.get_name()
             );
             dbus_context.push_pool_cache_change(pool_path.get_name(), true);
+            dbus_context.push_pool_cache_config_change(pool_path.get_name(), pool.cache_config());
+            res
+        }
+        BlockDevOp::InitCacheWithConfig(ref cache_config) => {
+            let res = handle_action!(
+                pool.init_cache(pool_uuid, &pool_name, &blockdevs, cache_config, true),
+                dbus_context,
+                pool_path.get_name()
+            );
+            dbus_context.push_pool_cache_change(pool_path.get_name(), true);
+            dbus_context.push_pool_cache_config_change(pool_path.get_name(), pool.cache_config());
             res
         }
         BlockDevOp::AddCache => {
@@ -220,6 +254,172 @@ where
 /// Get a pool property and place it on the D-Bus. The property is
 /// found by means of the getter method which takes a reference to a
 /// Pool and obtains the property from the pool.
+/// Create the filesystems specified by name, optional size, and optional size
+/// limit in the pool that is the implicit argument of the method call.
+pub fn create_filesystems_from_specs<E>(
+    m: &MethodInfo<'_, MTSync<TData<E>>, TData<E>>,
+    filesystems: Vec<(
+        &str,
+        (bool, &str),
+        (bool, &str),
+        (bool, &str),
+        HashMap<&str, &str>,
+    )>,
+) -> MethodResult
+where
+    E: 'static + Engine,
+{
+    let message: &Message = m.msg;
+    let dbus_context = m.tree.get_data();
+
+    let object_path = m.path.get_name();
+    let return_message = message.method_return();
+    let default_return: (bool, Vec<(dbus::Path<'_>, &str)>) = (false, Vec::new());
+
+    if filesystems.len() > 1 {
+        let error_message = "only 1 filesystem per request allowed";
+        let (rc, rs) = (DbusErrorEnum::ERROR as u16, error_message);
+        return Ok(vec![return_message.append3(default_return, rc, rs)]);
+    }
+
+    let pool_path = m
+        .tree
+        .get(object_path)
+        .expect("implicit argument must be in tree");
+    let pool_uuid = typed_uuid!(
+        get_data!(pool_path; default_return; return_message).uuid;
+        Pool;
+        default_return;
+        return_message
+    );
+
+    let mut guard = get_mut_pool!(dbus_context.engine; pool_uuid; default_return; return_message);
+    let (pool_name, _, pool) = guard.as_mut_tuple();
+
+    let filesystem_specs = match filesystems
+        .into_iter()
+        .map(|(name, size_opt, size_limit_opt, fs_type_opt, options)| {
+            let size = parse_size(size_opt, "size")?;
+            let size_limit = parse_size(size_limit_opt, "size limit")?;
+            let fs_type = tuple_to_option(fs_type_opt)
+                .map(FilesystemType::try_from)
+                .transpose()
+                .map_err(|e| e.to_string())?;
+            let options = parse_fs_create_options(options)?;
+            Ok(FilesystemSpec {
+                name,
+                size,
+                size_limit,
+                fs_type,
+                options,
+            })
+        })
+        .collect::<Result<Vec<FilesystemSpec<'_>>, String>>()
+    {
+        Ok(val) => val,
+        Err(err) => {
+            let (rc, rs) = (DbusErrorEnum::ERROR as u16, err);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let result = handle_action!(
+        pool.create_filesystems(&pool_name, pool_uuid, &filesystem_specs),
+        dbus_context,
+        pool_path.get_name()
+    );
+
+    let infos = match result {
+        Ok(created_set) => created_set.changed(),
+        Err(err) => {
+            let (rc, rs) = engine_to_dbus_err_tuple(&err);
+            return Ok(vec![return_message.append3(default_return, rc, rs)]);
+        }
+    };
+
+    let return_value = match infos {
+        Some(ref newly_created_filesystems) => {
+            let v = newly_created_filesystems
+                .iter()
+                .map(|&(name, uuid, _)| {
+                    let filesystem = pool
+                        .get_filesystem(uuid)
+                        .expect("just inserted by create_filesystems")
+                        .1;
+                    // FIXME: To avoid this expect, modify create_filesystem
+                    // so that it returns a mutable reference to the
+                    // filesystem created.
+                    (
+                        create_dbus_filesystem(
+                            dbus_context,
+                            object_path.clone(),
+                            &pool_name,
+                            &Name::new(name.to_string()),
+                            uuid,
+                            filesystem,
+                        ),
+                        name,
+                    )
+                })
+                .collect::<Vec<_>>();
+            (true, v)
+        }
+        None => default_return,
+    };
+
+    Ok(vec![return_message.append3(
+        return_value,
+        DbusErrorEnum::OK as u16,
+        OK_STRING.to_string(),
+    )])
+}
+
+/// Parse an optional size string, as received over D-Bus, into a number of
+/// bytes.
+fn parse_size(size_opt: (bool, &str), desc: &str) -> Result<Option<Bytes>, String> {
+    tuple_to_option(size_opt)
+        .map(|val| {
+            val.parse::<u128>().map(Bytes).map_err(|_| {
+                format!("Could not parse filesystem {desc} string {val} to integer value")
+            })
+        })
+        .transpose()
+}
+
+/// Parse the options with which a filesystem is to be created, as received
+/// over D-Bus. Returns an error if any option is not recognized or its value
+/// can not be parsed.
+fn parse_fs_create_options(
+    options: HashMap<&str, &str>,
+) -> Result<Option<FsCreateOptions>, String> {
+    fn parse_bool(key: &str, val: &str) -> Result<bool, String> {
+        val.parse::<bool>().map_err(|_| {
+            format!("Could not parse filesystem option {key} value {val} to boolean value")
+        })
+    }
+
+    if options.is_empty() {
+        return Ok(None);
+    }
+
+    let mut fs_options = FsCreateOptions::default();
+    for (key, val) in options {
+        match key {
+            "reflink" => fs_options.mkfs.reflink = Some(parse_bool(key, val)?),
+            "bigtime" => fs_options.mkfs.bigtime = Some(parse_bool(key, val)?),
+            "inode_size" => {
+                fs_options.mkfs.inode_size = Some(val.parse::<u64>().map_err(|_| {
+                    format!("Could not parse filesystem option {key} value {val} to integer value")
+                })?)
+            }
+            "label" => fs_options.mkfs.label = Some(val.to_string()),
+            "mount_options" => fs_options.mount_options = Some(val.to_string()),
+            _ => return Err(format!("Unrecognized filesystem option {key}")),
+        }
+    }
+    Ok(Some(fs_options))
+}
+
 pub fn get_pool_property<F, R, E>(
     i: &mut IterAppend<'_>,
     p: &PropInfo<'_, MTSync<TData<E>>, TData<E>>,
@@ -293,6 +493,24 @@ where
     prop_conv::clevis_info_to_prop(pool.encryption_info())
 }
 
+/// Generate D-Bus representation of the encryption settings of a pool.
+#[inline]
+pub fn pool_encryption_config<E>(pool: &E::Pool) -> (bool, (bool, EncryptionConfigProp))
+where
+    E: 'static + Engine,
+{
+    prop_conv::encryption_config_to_prop(pool.encryption_config())
+}
+
+/// Generate D-Bus representation of the named passphrase bindings of a pool.
+#[inline]
+pub fn pool_passphrase_bindings<E>(pool: &E::Pool) -> (bool, (bool, Vec<(String, String)>))
+where
+    E: 'static + Engine,
+{
+    prop_conv::passphrase_bindings_to_prop(pool.passphrase_bindings())
+}
+
 /// Generate D-Bus representation of a boolean indicating whether the pool
 /// has a cache.
 #[inline]
@@ -387,6 +605,96 @@ where
     }
 }
 
+/// Generate a D-Bus representation of whether the pool passes discards down
+/// to its devices.
+#[inline]
+pub fn pool_discard_passdown<E>(pool: &E::Pool) -> bool
+where
+    E: 'static + Engine,
+{
+    pool.discard_passdown()
+}
+
+/// Enable or disable passing discards down to the devices of a pool.
+pub fn set_pool_discard_passdown<E>(
+    name: &Name,
+    pool: &mut E::Pool,
+    enabled: bool,
+) -> Result<PropChangeAction<bool>, String>
+where
+    E: 'static + Engine,
+{
+    match pool.set_discard_passdown(name, enabled) {
+        Ok(true) => Ok(PropChangeAction::NewValue(enabled)),
+        Ok(false) => Ok(PropChangeAction::Identity),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Generate a D-Bus representation of the default filesystem extension
+/// configuration of the pool.
+#[inline]
+pub fn pool_fs_extend_config<E>(pool: &E::Pool) -> FsExtendConfigProp
+where
+    E: 'static + Engine,
+{
+    fs_extend_config_to_prop(pool.default_fs_extend_config())
+}
+
+/// Set the default filesystem extension configuration of a pool.
+pub fn set_pool_fs_extend_config<E>(
+    name: &Name,
+    pool: &mut E::Pool,
+    config: FsExtendConfigProp,
+) -> Result<PropChangeAction<FsExtendConfig>, String>
+where
+    E: 'static + Engine,
+{
+    let config = prop_to_fs_extend_config(config)?;
+    match pool.set_default_fs_extend_config(name, config) {
+        Ok(true) => Ok(PropChangeAction::NewValue(config)),
+        Ok(false) => Ok(PropChangeAction::Identity),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Generate a D-Bus representation of the data block size of the thin pool of
+/// the pool.
+#[inline]
+pub fn pool_data_block_size<E>(pool: &E::Pool) -> String
+where
+    E: 'static + Engine,
+{
+    prop_conv::data_block_size_to_prop(pool.data_block_size())
+}
+
+/// Generate a D-Bus representation of the thin pool extension policy of the
+/// pool.
+#[inline]
+pub fn pool_thin_pool_extend_policy<E>(pool: &E::Pool) -> (String, String)
+where
+    E: 'static + Engine,
+{
+    prop_conv::thin_pool_extend_policy_to_prop(pool.thin_pool_extend_policy())
+}
+
+/// Set the thin pool extension policy of a pool.
+pub fn set_pool_thin_pool_extend_policy<E>(
+    name: &Name,
+    pool: &mut E::Pool,
+    policy: (String, String),
+) -> Result<PropChangeAction<ThinPoolExtendPolicy>, String>
+where
+    E: 'static + Engine,
+{
+    let policy = prop_conv::prop_to_thin_pool_extend_policy(policy)?;
+    match pool.set_thin_pool_extend_policy(name, policy) {
+        Ok(true) => Ok(PropChangeAction::NewValue(policy)),
+        Ok(false) => Ok(PropChangeAction::Identity),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 /// Generate a D-Bus representation of whether the pool has remaining space to
 /// allocate or not.
 #[inline]
@@ -396,3 +704,118 @@ where
 {
     pool.out_of_alloc_space()
 }
+
+/// Generate a D-Bus representation of the mode and policy of the cache.
+#[inline]
+pub fn pool_cache_config<E>(pool: &E::Pool) -> (bool, (String, String, Vec<(String, String)>))
+where
+    E: 'static + Engine,
+{
+    prop_conv::cache_config_to_prop(pool.cache_config())
+}
+
+/// Set the mode and policy of the cache of a pool.
+pub fn set_pool_cache_config<E>(
+    name: &Name,
+    pool: &mut E::Pool,
+    (mode, policy, policy_args): (String, String, Vec<(String, String)>),
+) -> Result<PropChangeAction<CacheConfig>, String>
+where
+    E: 'static + Engine,
+{
+    let config = CacheConfig {
+        mode: CacheMode::try_from(mode.as_str()).map_err(|e| e.to_string())?,
+        policy,
+        policy_args,
+    };
+    match pool.set_cache_config(name, config.clone()) {
+        Ok(true) => Ok(PropChangeAction::NewValue(config)),
+        Ok(false) => Ok(PropChangeAction::Identity),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Generate a D-Bus representation of the allocation policy of the data tier.
+#[inline]
+pub fn pool_allocation_config<E>(pool: &E::Pool) -> (String, Vec<(String, String)>)
+where
+    E: 'static + Engine,
+{
+    prop_conv::allocation_config_to_prop(pool.allocation_config())
+}
+
+/// Set the allocation policy of the data tier of a pool.
+pub fn set_pool_allocation_config<E>(
+    name: &Name,
+    pool: &mut E::Pool,
+    (policy, tags): (String, Vec<(String, String)>),
+) -> Result<PropChangeAction<AllocationConfig>, String>
+where
+    E: 'static + Engine,
+{
+    let config = AllocationConfig {
+        policy: AllocationPolicy::try_from(policy.as_str()).map_err(|e| e.to_string())?,
+        tags: tags
+            .into_iter()
+            .map(|(purpose, tag)| {
+                AllocationPurpose::try_from(purpose.as_str())
+                    .map(|purpose| (purpose, tag))
+                    .map_err(|e| e.to_string())
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+    match pool.set_allocation_config(name, config.clone()) {
+        Ok(true) => Ok(PropChangeAction::NewValue(config)),
+        Ok(false) => Ok(PropChangeAction::Identity),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Generate a D-Bus representation of the hit, miss, and dirty block counters
+/// of the cache.
+#[inline]
+pub fn pool_cache_stats<E>(pool: &E::Pool) -> Result<(bool, (u64, u64, u64, u64, u64)), String>
+where
+    E: 'static + Engine,
+{
+    pool.cache_stats()
+        .map(prop_conv::cache_stats_to_prop)
+        .map_err(|e| e.to_string())
+}
+
+/// Get the UUIDs of the filesystems in the pool that are snapshots of any of
+/// the given filesystems.
+pub fn snapshots_of<E>(pool: &E::Pool, origins: &[FilesystemUuid]) -> Vec<FilesystemUuid>
+where
+    E: Engine,
+{
+    pool.filesystems()
+        .into_iter()
+        .filter_map(|(_, uuid, fs)| {
+            fs.origin()
+                .filter(|origin| origins.contains(origin))
+                .map(|_| uuid)
+        })
+        .collect()
+}
+
+/// Send a signal indicating that the filesystems with the given UUIDs in the
+/// pool with object path pool_path no longer have an origin.
+pub fn push_unset_origins<E>(
+    tree: &Tree<MTSync<TData<E>>, TData<E>>,
+    pool_path: &dbus::Path<'static>,
+    fs_uuids: &[FilesystemUuid],
+) where
+    E: Engine,
+{
+    let dbus_context = tree.get_data();
+    for opath in tree.iter() {
+        if let Some(op_cxt) = opath.get_data() {
+            if let StratisUuid::Fs(uuid) = op_cxt.uuid {
+                if op_cxt.parent == *pool_path && fs_uuids.contains(&uuid) {
+                    dbus_context.push_filesystem_origin_change(opath.get_name(), None);
+                }
+            }
+        }
+    }
+}
@@ -18,6 +18,7 @@ use dbus::{
     Path,
 };
 use dbus_tree::{MTSync, ObjectPath};
+use futures::executor::block_on;
 use tokio::sync::{broadcast::Receiver, mpsc::UnboundedReceiver};
 
 use devicemapper::{Bytes, Sectors};
@@ -27,11 +28,20 @@ use crate::{
         api::prop_conv::{locked_pools_to_prop, stopped_pools_to_prop},
         blockdev::prop_conv::{blockdev_new_size_to_prop, blockdev_user_info_to_prop},
         consts,
-        filesystem::prop_conv::{fs_size_to_prop, fs_used_to_prop},
+        filesystem::{
+            create_dbus_filesystem,
+            prop_conv::{
+                fs_extend_config_opt_to_prop, fs_extend_config_to_prop, fs_origin_to_prop,
+                fs_size_limit_to_prop, fs_size_to_prop, fs_snapshot_policies_to_prop,
+                fs_used_exclusive_to_prop, fs_used_shared_to_prop, fs_used_to_prop,
+            },
+        },
         pool::prop_conv::{
-            avail_actions_to_prop, clevis_info_to_prop, key_desc_to_prop, pool_alloc_to_prop,
-            pool_size_to_prop, pool_used_to_prop,
+            allocation_config_to_prop, avail_actions_to_prop, cache_config_to_prop,
+            clevis_info_to_prop, key_desc_to_prop, pool_alloc_to_prop, pool_size_to_prop,
+            pool_used_to_prop, thin_pool_extend_policy_to_prop,
         },
+        pool::push_unset_origins,
         types::{
             DbusAction, InterfacesAddedThreadSafe, InterfacesRemoved, LockableTree, SignalChange,
             TData, TreeReadLock, TreeWriteLock,
@@ -39,8 +49,10 @@ use crate::{
         util::{poll_exit_and_future, thread_safe_to_dbus_sendable},
     },
     engine::{
-        ActionAvailability, DevUuid, Engine, FilesystemUuid, LockedPoolsInfo, PoolEncryptionInfo,
-        PoolUuid, StoppedPoolsInfo, StratisUuid,
+        ActionAvailability, AllocationConfig, CacheConfig, DevUuid, Engine, FilesystemUuid,
+        FsExtendConfig, LockedPoolsInfo, Pool, PoolEncryptionInfo, PoolIdentifier, PoolUuid,
+        ScheduledSnapshots, SnapshotPolicy, SpaceUsage, StoppedPoolsInfo, StratisUuid,
+        ThinPoolExtendPolicy,
     },
     stratis::{StratisError, StratisResult},
 };
@@ -58,7 +70,7 @@ pub struct DbusTreeHandler<E> {
 
 impl<E> DbusTreeHandler<E>
 where
-    E: Engine,
+    E: 'static + Engine,
 {
     pub fn new(
         tree: LockableTree<E>,
@@ -182,6 +194,11 @@ where
                         vec![consts::FILESYSTEM_DEVNODE_PROP.into()],
                         consts::FILESYSTEM_NAME_PROP.to_string() =>
                         Variant(new_name.box_clone())
+                    },
+                    consts::FILESYSTEM_INTERFACE_NAME_3_6 => {
+                        vec![consts::FILESYSTEM_DEVNODE_PROP.into()],
+                        consts::FILESYSTEM_NAME_PROP.to_string() =>
+                        Variant(new_name.box_clone())
                     }
                 },
             )
@@ -191,6 +208,87 @@ where
         }
     }
 
+    /// Handle a change of the origin of a filesystem in the engine.
+    fn handle_fs_origin_change(&self, item: Path<'static>, origin: Option<FilesystemUuid>) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &item,
+            prop_hashmap!(
+                consts::FILESYSTEM_INTERFACE_NAME_3_6 => {
+                    Vec::new(),
+                    consts::FILESYSTEM_ORIGIN_PROP.to_string() =>
+                    box_variant!(fs_origin_to_prop(origin))
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating filesystem origin change: {}",
+                e
+            );
+        }
+    }
+
+    /// Handle a change of the snapshot policies of a filesystem in the engine.
+    fn handle_fs_snapshot_policies_change(
+        &self,
+        item: Path<'static>,
+        policies: Vec<SnapshotPolicy>,
+    ) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &item,
+            prop_hashmap!(
+                consts::FILESYSTEM_INTERFACE_NAME_3_6 => {
+                    Vec::new(),
+                    consts::FILESYSTEM_SNAPSHOT_POLICIES_PROP.to_string() =>
+                    box_variant!(fs_snapshot_policies_to_prop(&policies))
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating filesystem snapshot policies change: {}",
+                e
+            );
+        }
+    }
+
+    /// Handle a change of the size limit of a filesystem in the engine.
+    fn handle_fs_size_limit_change(&self, item: Path<'static>, size_limit: Option<Bytes>) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &item,
+            prop_hashmap!(
+                consts::FILESYSTEM_INTERFACE_NAME_3_6 => {
+                    Vec::new(),
+                    consts::FILESYSTEM_SIZE_LIMIT_PROP.to_string() =>
+                    box_variant!(fs_size_limit_to_prop(size_limit))
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating filesystem size limit change: {}",
+                e
+            );
+        }
+    }
+
+    /// Handle a change of the extension configuration of a filesystem in the
+    /// engine.
+    fn handle_fs_extend_config_change(&self, item: Path<'static>, config: Option<FsExtendConfig>) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &item,
+            prop_hashmap!(
+                consts::FILESYSTEM_INTERFACE_NAME_3_6 => {
+                    Vec::new(),
+                    consts::FILESYSTEM_EXTEND_CONFIG_PROP.to_string() =>
+                    box_variant!(fs_extend_config_opt_to_prop(config))
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating filesystem extension configuration change: {}",
+                e
+            );
+        }
+    }
+
     /// Handle a pool name change in the engine.
     fn handle_pool_name_change(
         &self,
@@ -231,6 +329,11 @@ where
                         Vec::new(),
                         consts::POOL_NAME_PROP.to_string() =>
                         Variant(new_name.box_clone())
+                    },
+                    consts::POOL_INTERFACE_NAME_3_6 => {
+                        Vec::new(),
+                        consts::POOL_NAME_PROP.to_string() =>
+                        Variant(new_name.box_clone())
                     }
                 },
             )
@@ -272,6 +375,9 @@ where
                             },
                             consts::FILESYSTEM_INTERFACE_NAME_3_5 => {
                                 vec![consts::FILESYSTEM_DEVNODE_PROP.into()]
+                            },
+                            consts::FILESYSTEM_INTERFACE_NAME_3_6 => {
+                                vec![consts::FILESYSTEM_DEVNODE_PROP.into()]
                             }
                         },
                     )
@@ -320,6 +426,11 @@ where
                         box_variant!(avail_prop.clone())
                     },
                     consts::POOL_INTERFACE_NAME_3_5 => {
+                        Vec::new(),
+                        consts::POOL_AVAIL_ACTIONS_PROP.to_string() =>
+                        box_variant!(avail_prop.clone())
+                    },
+                    consts::POOL_INTERFACE_NAME_3_6 => {
                         Vec::new(),
                         consts::POOL_AVAIL_ACTIONS_PROP.to_string() =>
                         box_variant!(avail_prop)
@@ -365,6 +476,11 @@ where
                         box_variant!(kd_prop.clone())
                     },
                     consts::POOL_INTERFACE_NAME_3_5 => {
+                        Vec::new(),
+                        consts::POOL_KEY_DESC_PROP.to_string() =>
+                        box_variant!(kd_prop.clone())
+                    },
+                    consts::POOL_INTERFACE_NAME_3_6 => {
                         Vec::new(),
                         consts::POOL_KEY_DESC_PROP.to_string() =>
                         box_variant!(kd_prop)
@@ -410,6 +526,11 @@ where
                         box_variant!(ci_prop.clone())
                     },
                     consts::POOL_INTERFACE_NAME_3_5 => {
+                        Vec::new(),
+                        consts::POOL_CLEVIS_INFO_PROP.to_string() =>
+                        box_variant!(ci_prop.clone())
+                    },
+                    consts::POOL_INTERFACE_NAME_3_6 => {
                         Vec::new(),
                         consts::POOL_CLEVIS_INFO_PROP.to_string() =>
                         box_variant!(ci_prop)
@@ -451,6 +572,10 @@ where
                     consts::POOL_INTERFACE_NAME_3_5 => {
                         Vec::new(),
                         consts::POOL_HAS_CACHE_PROP.to_string() => box_variant!(b)
+                    },
+                    consts::POOL_INTERFACE_NAME_3_6 => {
+                        Vec::new(),
+                        consts::POOL_HAS_CACHE_PROP.to_string() => box_variant!(b)
                     }
                 },
             )
@@ -509,6 +634,11 @@ where
                         Vec::new(),
                         consts::STOPPED_POOLS_PROP.to_string() =>
                         box_variant!(stopped_pools_to_prop(&stopped_pools))
+                    },
+                    consts::MANAGER_INTERFACE_NAME_3_6 => {
+                        Vec::new(),
+                        consts::STOPPED_POOLS_PROP.to_string() =>
+                        box_variant!(stopped_pools_to_prop(&stopped_pools))
                     }
                 },
             )
@@ -526,6 +656,7 @@ where
         uuid: FilesystemUuid,
         new_used: SignalChange<Option<Bytes>>,
         new_size: SignalChange<Bytes>,
+        new_space_usage: SignalChange<Option<SpaceUsage>>,
     ) {
         handle_background_change!(
             self,
@@ -580,6 +711,20 @@ where
                 consts::FILESYSTEM_SIZE_PROP.to_string(),
                 fs_size_to_prop,
                 new_size
+            },
+            consts::FILESYSTEM_INTERFACE_NAME_3_6 => {
+                consts::FILESYSTEM_USED_PROP.to_string(),
+                fs_used_to_prop,
+                new_used,
+                consts::FILESYSTEM_SIZE_PROP.to_string(),
+                fs_size_to_prop,
+                new_size,
+                consts::FILESYSTEM_USED_EXCLUSIVE_PROP.to_string(),
+                fs_used_exclusive_to_prop,
+                new_space_usage,
+                consts::FILESYSTEM_USED_SHARED_PROP.to_string(),
+                fs_used_shared_to_prop,
+                new_space_usage
             }
         );
     }
@@ -662,6 +807,17 @@ where
                 consts::POOL_NO_ALLOCABLE_SPACE_PROP.to_string(),
                 |x| x,
                 new_no_space
+            },
+            consts::POOL_INTERFACE_NAME_3_6 => {
+                consts::POOL_TOTAL_USED_PROP.to_string(),
+                pool_used_to_prop,
+                new_used,
+                consts::POOL_ALLOC_SIZE_PROP.to_string(),
+                pool_alloc_to_prop,
+                new_alloc,
+                consts::POOL_NO_ALLOCABLE_SPACE_PROP.to_string(),
+                |x| x,
+                new_no_space
             }
         );
     }
@@ -700,6 +856,11 @@ where
                     Vec::new(),
                     consts::POOL_TOTAL_SIZE_PROP.to_string() =>
                     box_variant!(pool_size_to_prop(new_size))
+                },
+                consts::POOL_INTERFACE_NAME_3_6 => {
+                    Vec::new(),
+                    consts::POOL_TOTAL_SIZE_PROP.to_string() =>
+                    box_variant!(pool_size_to_prop(new_size))
                 }
             },
         ) {
@@ -739,6 +900,11 @@ where
                     Vec::new(),
                     consts::POOL_FS_LIMIT_PROP.to_string() =>
                     box_variant!(new_fs_limit)
+                },
+                consts::POOL_INTERFACE_NAME_3_6 => {
+                    Vec::new(),
+                    consts::POOL_FS_LIMIT_PROP.to_string() =>
+                    box_variant!(new_fs_limit)
                 }
             ),
         ) {
@@ -776,6 +942,11 @@ where
                     box_variant!(user_info_prop.clone())
                 },
                 consts::POOL_INTERFACE_NAME_3_5 => {
+                    Vec::new(),
+                    consts::BLOCKDEV_USER_INFO_PROP.to_string() =>
+                    box_variant!(user_info_prop.clone())
+                },
+                consts::POOL_INTERFACE_NAME_3_6 => {
                     Vec::new(),
                     consts::BLOCKDEV_USER_INFO_PROP.to_string() =>
                     box_variant!(user_info_prop)
@@ -789,6 +960,206 @@ where
         }
     }
 
+    /// Send a signal indicating that the blockdev tags have changed.
+    fn handle_blockdev_tags_change(&self, path: Path<'static>, new_tags: Vec<String>) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &path,
+            prop_hashmap!(
+                consts::BLOCKDEV_INTERFACE_NAME_3_6 => {
+                    Vec::new(),
+                    consts::BLOCKDEV_TAGS_PROP.to_string() =>
+                    box_variant!(new_tags)
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating blockdev tags change: {}",
+                e
+            );
+        }
+    }
+
+    /// Add the filesystems created by snapshot policies in a pool to the
+    /// D-Bus tree and remove those destroyed by them.
+    fn handle_scheduled_snapshots(
+        &self,
+        read_lock: TreeReadLock<E>,
+        pool_uuid: PoolUuid,
+        scheduled: ScheduledSnapshots,
+    ) {
+        let dbus_context = read_lock.get_data().clone();
+        let mut pool_path = None;
+        let mut removed = Vec::new();
+        for opath in read_lock.iter() {
+            if let Some(op_cxt) = opath.get_data() {
+                match op_cxt.uuid {
+                    StratisUuid::Pool(uuid) if uuid == pool_uuid => {
+                        pool_path = Some(opath.get_name().clone());
+                    }
+                    StratisUuid::Fs(uuid) if scheduled.destroyed.contains(&uuid) => {
+                        removed.push(opath.get_name().clone());
+                    }
+                    _ => (),
+                }
+            }
+        }
+        let pool_path = match pool_path {
+            Some(path) => path,
+            None => {
+                warn!(
+                    "Pool with UUID {} in which scheduled snapshots were taken could not be found in the D-Bus tree",
+                    pool_uuid
+                );
+                return;
+            }
+        };
+        for path in removed {
+            dbus_context.push_remove(&path, consts::filesystem_interface_list());
+        }
+        push_unset_origins(&read_lock, &pool_path, &scheduled.origins_unset);
+        drop(read_lock);
+
+        if scheduled.created.is_empty() {
+            return;
+        }
+        match block_on(
+            dbus_context
+                .engine
+                .get_pool(PoolIdentifier::Uuid(pool_uuid)),
+        ) {
+            Some(guard) => {
+                let (pool_name, _, pool) = guard.as_tuple();
+                for uuid in scheduled.created {
+                    if let Some((fs_name, fs)) = pool.get_filesystem(uuid) {
+                        create_dbus_filesystem(
+                            &dbus_context,
+                            pool_path.clone(),
+                            &pool_name,
+                            &fs_name,
+                            uuid,
+                            fs,
+                        );
+                    }
+                }
+            }
+            None => warn!(
+                "Pool with UUID {} in which scheduled snapshots were taken could not be found",
+                pool_uuid
+            ),
+        }
+    }
+
+    /// Send a signal indicating that the pool allocation policy or tags have
+    /// changed.
+    fn handle_pool_allocation_config_change(
+        &self,
+        path: Path<'static>,
+        new_config: AllocationConfig,
+    ) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &path,
+            prop_hashmap!(
+                consts::POOL_INTERFACE_NAME_3_6 => {
+                    Vec::new(),
+                    consts::POOL_ALLOCATION_CONFIG_PROP.to_string() =>
+                    box_variant!(allocation_config_to_prop(new_config))
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating pool allocation configuration change: {}",
+                e
+            );
+        }
+    }
+
+    /// Send a signal indicating that the pool discard passdown setting has
+    /// changed.
+    fn handle_pool_discard_passdown_change(&self, path: Path<'static>, enabled: bool) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &path,
+            prop_hashmap!(
+                consts::POOL_INTERFACE_NAME_3_6 => {
+                    Vec::new(),
+                    consts::POOL_DISCARD_PASSDOWN_PROP.to_string() =>
+                    box_variant!(enabled)
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating pool discard passdown change: {}",
+                e
+            );
+        }
+    }
+
+    /// Send a signal indicating that the default filesystem extension
+    /// configuration of the pool has changed.
+    fn handle_pool_fs_extend_config_change(&self, path: Path<'static>, config: FsExtendConfig) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &path,
+            prop_hashmap!(
+                consts::POOL_INTERFACE_NAME_3_6 => {
+                    Vec::new(),
+                    consts::POOL_FS_EXTEND_CONFIG_PROP.to_string() =>
+                    box_variant!(fs_extend_config_to_prop(config))
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating pool filesystem extension configuration change: {}",
+                e
+            );
+        }
+    }
+
+    /// Send a signal indicating that the thin pool extension policy of the
+    /// pool has changed.
+    fn handle_pool_thin_pool_extend_policy_change(
+        &self,
+        path: Path<'static>,
+        policy: ThinPoolExtendPolicy,
+    ) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &path,
+            prop_hashmap!(
+                consts::POOL_INTERFACE_NAME_3_6 => {
+                    Vec::new(),
+                    consts::POOL_THIN_POOL_EXTEND_POLICY_PROP.to_string() =>
+                    box_variant!(thin_pool_extend_policy_to_prop(policy))
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating pool thin pool extension policy change: {}",
+                e
+            );
+        }
+    }
+
+    /// Send a signal indicating that the pool cache mode or policy has changed.
+    fn handle_pool_cache_config_change(
+        &self,
+        path: Path<'static>,
+        new_config: Option<CacheConfig>,
+    ) {
+        if let Err(e) = self.property_changed_invalidated_signal(
+            &path,
+            prop_hashmap!(
+                consts::POOL_INTERFACE_NAME_3_6 => {
+                    Vec::new(),
+                    consts::POOL_CACHE_CONFIG_PROP.to_string() =>
+                    box_variant!(cache_config_to_prop(new_config))
+                }
+            ),
+        ) {
+            warn!(
+                "Failed to send a signal over D-Bus indicating pool cache configuration change: {}",
+                e
+            );
+        }
+    }
+
     /// Send a signal indicating that the pool overprovisioning mode has changed.
     fn handle_pool_overprov_mode_change(&self, path: Path<'static>, new_mode: bool) {
         if let Err(e) = self.property_changed_invalidated_signal(
@@ -818,6 +1189,11 @@ where
                     Vec::new(),
                     consts::POOL_OVERPROV_PROP.to_string() =>
                     box_variant!(new_mode)
+                },
+                consts::POOL_INTERFACE_NAME_3_6 => {
+                    Vec::new(),
+                    consts::POOL_OVERPROV_PROP.to_string() =>
+                    box_variant!(new_mode)
                 }
             ),
         ) {
@@ -921,6 +1297,20 @@ where
                 consts::POOL_NO_ALLOCABLE_SPACE_PROP.to_string(),
                 |x| x,
                 new_no_space
+            },
+            consts::POOL_INTERFACE_NAME_3_6 => {
+                consts::POOL_TOTAL_USED_PROP.to_string(),
+                pool_used_to_prop,
+                new_used,
+                consts::POOL_ALLOC_SIZE_PROP.to_string(),
+                pool_alloc_to_prop,
+                new_alloc,
+                consts::POOL_TOTAL_SIZE_PROP.to_string(),
+                pool_size_to_prop,
+                new_size,
+                consts::POOL_NO_ALLOCABLE_SPACE_PROP.to_string(),
+                |x| x,
+                new_no_space
             }
         );
     }
@@ -951,6 +1341,11 @@ where
                 consts::BLOCKDEV_NEW_SIZE_PROP.to_string(),
                 blockdev_new_size_to_prop,
                 new_size
+            },
+            consts::BLOCKDEV_INTERFACE_NAME_3_6 => {
+                consts::BLOCKDEV_NEW_SIZE_PROP.to_string(),
+                blockdev_new_size_to_prop,
+                new_size
             }
         )
     }
@@ -983,6 +1378,22 @@ where
                 self.handle_fs_name_change(item, new_name);
                 Ok(true)
             }
+            DbusAction::FsOriginChange(item, origin) => {
+                self.handle_fs_origin_change(item, origin);
+                Ok(true)
+            }
+            DbusAction::FsSnapshotPoliciesChange(item, policies) => {
+                self.handle_fs_snapshot_policies_change(item, policies);
+                Ok(true)
+            }
+            DbusAction::FsSizeLimitChange(item, size_limit) => {
+                self.handle_fs_size_limit_change(item, size_limit);
+                Ok(true)
+            }
+            DbusAction::FsExtendConfigChange(item, config) => {
+                self.handle_fs_extend_config_change(item, config);
+                Ok(true)
+            }
             DbusAction::PoolNameChange(item, new_name) => {
                 if let Some(read_lock) =
                     poll_exit_and_future(self.should_exit.recv(), self.tree.read())?
@@ -1021,6 +1432,26 @@ where
                 self.handle_pool_overprov_mode_change(path, new_mode);
                 Ok(true)
             }
+            DbusAction::PoolCacheConfigChange(path, new_config) => {
+                self.handle_pool_cache_config_change(path, new_config);
+                Ok(true)
+            }
+            DbusAction::PoolAllocationConfigChange(path, new_config) => {
+                self.handle_pool_allocation_config_change(path, new_config);
+                Ok(true)
+            }
+            DbusAction::PoolFsExtendConfigChange(path, config) => {
+                self.handle_pool_fs_extend_config_change(path, config);
+                Ok(true)
+            }
+            DbusAction::PoolThinPoolExtendPolicyChange(path, policy) => {
+                self.handle_pool_thin_pool_extend_policy_change(path, policy);
+                Ok(true)
+            }
+            DbusAction::PoolDiscardPassdownChange(path, enabled) => {
+                self.handle_pool_discard_passdown_change(path, enabled);
+                Ok(true)
+            }
             DbusAction::LockedPoolsChange(pools) => {
                 self.handle_locked_pools_change(pools);
                 Ok(true)
@@ -1033,6 +1464,20 @@ where
                 self.handle_blockdev_user_info_change(path, new_user_info);
                 Ok(true)
             }
+            DbusAction::BlockdevTagsChange(path, new_tags) => {
+                self.handle_blockdev_tags_change(path, new_tags);
+                Ok(true)
+            }
+            DbusAction::ScheduledSnapshots(pool_uuid, scheduled) => {
+                if let Some(read_lock) =
+                    poll_exit_and_future(self.should_exit.recv(), self.tree.read())?
+                {
+                    self.handle_scheduled_snapshots(read_lock, pool_uuid, scheduled);
+                    Ok(true)
+                } else {
+                    Ok(false)
+                }
+            }
             DbusAction::PoolForegroundChange(item, new_used, new_alloc, new_size, new_no_space) => {
                 self.handle_pool_foreground_change(
                     item,
@@ -1043,13 +1488,14 @@ where
                 );
                 Ok(true)
             }
-            DbusAction::FsBackgroundChange(uuid, new_used, new_size) => {
+            DbusAction::FsBackgroundChange(uuid, new_used, new_size, new_space_usage) => {
                 background_arm! {
                     self,
                     uuid,
                     handle_fs_background_change,
                     new_used,
-                    new_size
+                    new_size,
+                    new_space_usage
                 }
             }
             DbusAction::PoolBackgroundChange(uuid, new_used, new_alloc, new_no_space) => {
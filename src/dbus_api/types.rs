@@ -28,10 +28,11 @@ use devicemapper::{Bytes, Sectors};
 use crate::{
     dbus_api::{connection::DbusConnectionHandler, tree::DbusTreeHandler, udev::DbusUdevHandler},
     engine::{
-        total_allocated, total_used, ActionAvailability, DevUuid, Diff, Engine, ExclusiveGuard,
-        FilesystemUuid, Lockable, LockedPoolsInfo, PoolDiff, PoolEncryptionInfo, PoolUuid,
-        SharedGuard, StoppedPoolsInfo, StratBlockDevDiff, StratFilesystemDiff, StratPoolDiff,
-        StratisUuid, ThinPoolDiff,
+        total_allocated, total_used, ActionAvailability, AllocationConfig, CacheConfig, DevUuid,
+        Diff, Engine, ExclusiveGuard, FilesystemUuid, FsExtendConfig, Lockable, LockedPoolsInfo,
+        PoolDiff, PoolEncryptionInfo, PoolUuid, ScheduledSnapshots, SharedGuard, SnapshotPolicy,
+        SpaceUsage, StoppedPoolsInfo, StratBlockDevDiff, StratFilesystemDiff, StratPoolDiff,
+        StratisUuid, ThinPoolDiff, ThinPoolExtendPolicy,
     },
 };
 
@@ -105,6 +106,10 @@ pub enum DbusAction<E> {
     ),
     Remove(Path<'static>, InterfacesRemoved),
     FsNameChange(Path<'static>, String),
+    FsOriginChange(Path<'static>, Option<FilesystemUuid>),
+    FsSnapshotPoliciesChange(Path<'static>, Vec<SnapshotPolicy>),
+    FsSizeLimitChange(Path<'static>, Option<Bytes>),
+    FsExtendConfigChange(Path<'static>, Option<FsExtendConfig>),
     PoolNameChange(Path<'static>, String),
     PoolAvailActions(Path<'static>, ActionAvailability),
     PoolKeyDescChange(Path<'static>, Option<PoolEncryptionInfo>),
@@ -113,14 +118,22 @@ pub enum DbusAction<E> {
     PoolSizeChange(Path<'static>, Bytes),
     PoolFsLimitChange(Path<'static>, u64),
     PoolOverprovModeChange(Path<'static>, bool),
+    PoolCacheConfigChange(Path<'static>, Option<CacheConfig>),
+    PoolAllocationConfigChange(Path<'static>, AllocationConfig),
+    PoolDiscardPassdownChange(Path<'static>, bool),
+    PoolFsExtendConfigChange(Path<'static>, FsExtendConfig),
+    PoolThinPoolExtendPolicyChange(Path<'static>, ThinPoolExtendPolicy),
     LockedPoolsChange(LockedPoolsInfo),
     StoppedPoolsChange(StoppedPoolsInfo),
     BlockdevUserInfoChange(Path<'static>, Option<String>),
+    BlockdevTagsChange(Path<'static>, Vec<String>),
+    ScheduledSnapshots(PoolUuid, ScheduledSnapshots),
 
     FsBackgroundChange(
         FilesystemUuid,
         SignalChange<Option<Bytes>>,
         SignalChange<Bytes>,
+        SignalChange<Option<SpaceUsage>>,
     ),
     PoolBackgroundChange(
         PoolUuid,
@@ -175,12 +188,17 @@ where
         diffs
             .into_iter()
             .map(|(uuid, diff)| {
-                let StratFilesystemDiff { size, used } = diff;
+                let StratFilesystemDiff {
+                    size,
+                    used,
+                    space_usage,
+                } = diff;
 
                 DbusAction::FsBackgroundChange(
                     uuid,
                     SignalChange::from(used),
                     SignalChange::from(size),
+                    SignalChange::from(space_usage),
                 )
             })
             .collect()
@@ -310,6 +328,79 @@ where
         }
     }
 
+    /// Send changed signal for Origin property.
+    pub fn push_filesystem_origin_change(
+        &self,
+        item: &Path<'static>,
+        origin: Option<FilesystemUuid>,
+    ) {
+        if let Err(e) = self
+            .sender
+            .send(DbusAction::FsOriginChange(item.clone(), origin))
+        {
+            warn!(
+                "D-Bus filesystem origin change event could not be sent to the processing thread; \
+                no signal will be sent out for filesystem with path {}: {}",
+                item, e,
+            )
+        }
+    }
+
+    /// Send changed signal for changed filesystem snapshot policies property.
+    pub fn push_filesystem_snapshot_policies_change(
+        &self,
+        path: &Path<'static>,
+        policies: Vec<SnapshotPolicy>,
+    ) {
+        if let Err(e) = self
+            .sender
+            .send(DbusAction::FsSnapshotPoliciesChange(path.clone(), policies))
+        {
+            warn!(
+                "D-Bus filesystem snapshot policies change event could not be sent to the processing thread; \
+                no signal will be sent out for filesystem with path {}: {}",
+                path, e,
+            )
+        }
+    }
+
+    /// Send changed signal for changed filesystem size limit property.
+    pub fn push_filesystem_size_limit_change(
+        &self,
+        path: &Path<'static>,
+        size_limit: Option<Bytes>,
+    ) {
+        if let Err(e) = self
+            .sender
+            .send(DbusAction::FsSizeLimitChange(path.clone(), size_limit))
+        {
+            warn!(
+                "D-Bus filesystem size limit change event could not be sent to the processing thread; \
+                no signal will be sent out for filesystem with path {}: {}",
+                path, e,
+            )
+        }
+    }
+
+    /// Send changed signal for changed filesystem extension configuration
+    /// property.
+    pub fn push_filesystem_extend_config_change(
+        &self,
+        path: &Path<'static>,
+        config: Option<FsExtendConfig>,
+    ) {
+        if let Err(e) = self
+            .sender
+            .send(DbusAction::FsExtendConfigChange(path.clone(), config))
+        {
+            warn!(
+                "D-Bus filesystem extension configuration change event could not be sent to the processing thread; \
+                no signal will be sent out for filesystem with path {}: {}",
+                path, e,
+            )
+        }
+    }
+
     /// Send changed signal for KeyDesc property.
     pub fn push_pool_key_desc_change(&self, item: &Path<'static>, ei: Option<PoolEncryptionInfo>) {
         if let Err(e) = self
@@ -409,6 +500,83 @@ where
         }
     }
 
+    /// Send changed signal for pool CacheConfig property.
+    pub fn push_pool_cache_config_change(
+        &self,
+        item: &Path<'static>,
+        new_config: Option<CacheConfig>,
+    ) {
+        if let Err(e) = self
+            .sender
+            .send(DbusAction::PoolCacheConfigChange(item.clone(), new_config))
+        {
+            warn!(
+                "D-Bus pool cache configuration change event could not be sent to the processing thread; no signal will be sent out for the cache configuration change of pool with path {}: {}",
+                item, e,
+            )
+        }
+    }
+
+    /// Send changed signal for pool AllocationConfig property.
+    pub fn push_pool_allocation_config_change(
+        &self,
+        item: &Path<'static>,
+        new_config: AllocationConfig,
+    ) {
+        if let Err(e) = self.sender.send(DbusAction::PoolAllocationConfigChange(
+            item.clone(),
+            new_config,
+        )) {
+            warn!(
+                "D-Bus pool allocation configuration change event could not be sent to the processing thread; no signal will be sent out for the allocation configuration change of pool with path {}: {}",
+                item, e,
+            )
+        }
+    }
+
+    /// Send changed signal for pool DiscardPassdown property.
+    pub fn push_pool_discard_passdown_change(&self, item: &Path<'static>, enabled: bool) {
+        if let Err(e) = self
+            .sender
+            .send(DbusAction::PoolDiscardPassdownChange(item.clone(), enabled))
+        {
+            warn!(
+                "D-Bus pool discard passdown change event could not be sent to the processing thread; no signal will be sent out for the discard passdown setting of pool with path {}: {}",
+                item, e,
+            )
+        }
+    }
+
+    /// Send changed signal for pool FsExtendConfig property.
+    pub fn push_pool_fs_extend_config_change(&self, item: &Path<'static>, config: FsExtendConfig) {
+        if let Err(e) = self
+            .sender
+            .send(DbusAction::PoolFsExtendConfigChange(item.clone(), config))
+        {
+            warn!(
+                "D-Bus pool filesystem extension configuration change event could not be sent to the processing thread; no signal will be sent out for the filesystem extension configuration of pool with path {}: {}",
+                item, e,
+            )
+        }
+    }
+
+    /// Send changed signal for pool ThinPoolExtendPolicy property.
+    pub fn push_pool_thin_pool_extend_policy_change(
+        &self,
+        item: &Path<'static>,
+        policy: ThinPoolExtendPolicy,
+    ) {
+        if let Err(e) = self.sender.send(DbusAction::PoolThinPoolExtendPolicyChange(
+            item.clone(),
+            policy,
+        )) {
+            warn!(
+                "D-Bus pool thin pool extension policy change event could not be sent to the processing thread; no signal will be sent out for the thin pool extension policy of pool with path {}: {}",
+                item, e,
+            )
+        }
+    }
+
     /// Send changed signal for pool available actions state.
     pub fn push_pool_avail_actions(&self, item: &Path<'static>, avail_actions: ActionAvailability) {
         if let Err(e) = self
@@ -460,6 +628,19 @@ where
             )
         }
     }
+
+    /// Send changed signal for changed blockdev tags property.
+    pub fn push_blockdev_tags_change(&self, path: &Path<'static>, tags: Vec<String>) {
+        if let Err(e) = self
+            .sender
+            .send(DbusAction::BlockdevTagsChange(path.clone(), tags))
+        {
+            warn!(
+                "Block device tags change event could not be sent to the processing thread; no signal will be sent out for the block device tags change: {}",
+                e,
+            )
+        }
+    }
 }
 
 #[derive(Debug)]
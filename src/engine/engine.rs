@@ -5,6 +5,7 @@
 use std::{
     collections::{HashMap, HashSet},
     fmt::Debug,
+    fs::File,
     os::unix::io::RawFd,
     path::{Path, PathBuf},
 };
@@ -23,12 +24,16 @@ use crate::{
             SomeLockWriteGuard,
         },
         types::{
-            ActionAvailability, BlockDevTier, Clevis, CreateAction, DeleteAction, DevUuid,
-            EncryptionInfo, FilesystemUuid, GrowAction, Key, KeyDescription, LockedPoolsInfo,
-            MappingCreateAction, MappingDeleteAction, Name, PoolDiff, PoolEncryptionInfo,
-            PoolIdentifier, PoolUuid, RegenAction, RenameAction, ReportType, SetCreateAction,
-            SetDeleteAction, SetUnlockAction, StartAction, StopAction, StoppedPoolsInfo,
-            StratFilesystemDiff, UdevEngineEvent, UnlockMethod,
+            ActionAvailability, AllocationConfig, BlockDevTier, CacheConfig, CacheStats, Clevis,
+            CreateAction, DeleteAction, DevUuid, Encryption, EncryptionConfig, EncryptionInfo,
+            FilesystemSpec, FilesystemType, FilesystemUuid, FsExtendConfig, GrowAction, Key,
+            KeyDescription, LockedPoolsInfo, MappingCreateAction, MappingDeleteAction,
+            MaybeInconsistent, Name, PassphraseBindings, PoolCreateOptions, PoolDiff,
+            PoolEncryptionInfo, PoolIdentifier, PoolUuid, Redundancy, ReencryptionProgress,
+            RegenAction, RenameAction, ReplaceProgress, ReportType, ScheduledSnapshots,
+            SetCreateAction, SetDeleteAction, SetUnlockAction, SnapshotPolicy, SpaceUsage,
+            StartAction, StopAction, StoppedPoolsInfo, StratFilesystemDiff, ThinPoolExtendPolicy,
+            UdevEngineEvent, UnlockMethod,
         },
     },
     stratis::StratisResult,
@@ -93,6 +98,36 @@ pub trait Filesystem: Debug {
 
     /// Get the size of the filesystem in bytes.
     fn size(&self) -> Bytes;
+
+    /// The size beyond which the filesystem is not extended automatically,
+    /// if any.
+    fn size_limit(&self) -> Option<Sectors>;
+
+    /// The configuration of the automatic extension of this filesystem, if it
+    /// overrides the default configuration of its pool.
+    fn extend_config(&self) -> Option<FsExtendConfig>;
+
+    /// The UUID of the filesystem this filesystem is a snapshot of, if any.
+    fn origin(&self) -> Option<FilesystemUuid>;
+
+    /// The policies according to which snapshots of this filesystem are
+    /// taken automatically.
+    fn snapshot_policies(&self) -> &[SnapshotPolicy];
+
+    /// The name of the snapshot policy that created this filesystem, if it
+    /// is a scheduled snapshot.
+    fn scheduled_by(&self) -> Option<&str>;
+
+    /// The type of the filesystem on the thin device.
+    fn fs_type(&self) -> FilesystemType;
+
+    /// The options with which the filesystem is recommended to be mounted,
+    /// if any.
+    fn mount_options(&self) -> Option<&str>;
+
+    /// The mapped, exclusive, and shared space of the filesystem's thin
+    /// device, as most recently calculated, if available.
+    fn space_usage(&self) -> Option<SpaceUsage>;
 }
 
 pub trait BlockDev: Debug {
@@ -123,6 +158,14 @@ pub trait BlockDev: Debug {
     /// If internally the new size is None, the block device size is equal to that
     /// registered in the BDA.
     fn new_size(&self) -> Option<Sectors>;
+
+    /// The user-defined tags of the block device, in sorted order.
+    fn tags(&self) -> &[String];
+
+    /// The number of integrity mismatches detected on the block device since
+    /// it was set up, or None if the block device is not protected by
+    /// dm-integrity.
+    fn integrity_mismatches(&self) -> StratisResult<Option<u64>>;
 }
 
 pub trait Pool: Debug + Send + Sync {
@@ -145,15 +188,46 @@ pub trait Pool: Debug + Send + Sync {
     /// can only be initialized once and if an attempt is made to initialize it
     /// twice with different sets of block devices, the user should be notified
     /// of their error.
+    ///
+    /// The cache is created with the mode and policy specified by
+    /// cache_config. If the cache has already been initialized, cache_config
+    /// is ignored.
     fn init_cache(
         &mut self,
         pool_uuid: PoolUuid,
         pool_name: &str,
         blockdevs: &[&Path],
+        cache_config: &CacheConfig,
         supports_encrypted: bool,
     ) -> StratisResult<SetCreateAction<DevUuid>>;
 
-    /// Creates the filesystems specified by specs.
+    /// Remove the cache from the pool. All dirty blocks in the cache are
+    /// written back to the data tier before the cache is removed, and the
+    /// cache blockdevs are then wiped.
+    /// Returns a list of the UUIDs of the cache blockdevs that were removed;
+    /// the list is empty if the pool has no cache.
+    fn remove_cache(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+    ) -> StratisResult<SetDeleteAction<DevUuid>>;
+
+    /// The mode and policy of the cache, or None if the pool has no cache.
+    fn cache_config(&self) -> Option<CacheConfig>;
+
+    /// Change the mode and policy of the cache.
+    /// Returns true if the mode or the policy changed.
+    /// Returns an error if the pool has no cache.
+    fn set_cache_config(&mut self, pool_name: &str, config: CacheConfig) -> StratisResult<bool>;
+
+    /// The current hit, miss, and dirty block counters of the cache, or None
+    /// if the pool has no cache.
+    fn cache_stats(&self) -> StratisResult<Option<CacheStats>>;
+
+    /// Creates the filesystems specified by specs. Each spec gives the name
+    /// of the filesystem and, optionally, its size, size limit, type, and
+    /// creation options. If no type is specified, an XFS filesystem is
+    /// created.
     /// Returns a list of the names of filesystems actually created.
     /// Returns an error if any of the specified names are already in use
     /// for filesystems in this pool. If the same name is passed multiple
@@ -162,7 +236,7 @@ pub trait Pool: Debug + Send + Sync {
         &mut self,
         pool_name: &str,
         pool_uuid: PoolUuid,
-        specs: &[(&'b str, Option<Bytes>)],
+        specs: &[FilesystemSpec<'b>],
     ) -> StratisResult<SetCreateAction<(&'b str, FilesystemUuid, Sectors)>>;
 
     /// Adds blockdevs specified by paths to pool.
@@ -179,6 +253,42 @@ pub trait Pool: Debug + Send + Sync {
         tier: BlockDevTier,
     ) -> StratisResult<SetCreateAction<DevUuid>>;
 
+    /// Remove the blockdevs specified by UUID from the given tier of the pool.
+    /// For the data tier, any data stored on the blockdevs is first moved to
+    /// the remaining blockdevs in the tier. For the cache tier, the cache is
+    /// flushed and rebuilt from the remaining blockdevs. The removed blockdevs
+    /// are then wiped.
+    /// Returns a list of the UUIDs of the blockdevs actually removed; UUIDs
+    /// that do not belong to any blockdev in the pool are ignored.
+    /// Returns an error if a blockdev belongs to a different tier than the
+    /// one specified, if removing the blockdevs would leave the tier empty,
+    /// or if the remaining blockdevs do not have sufficient unallocated space
+    /// to hold the data moved off the removed blockdevs. Also returns an
+    /// error if a cache blockdev holds the cache metadata.
+    fn remove_blockdevs(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        devices: &[DevUuid],
+        tier: BlockDevTier,
+    ) -> StratisResult<SetDeleteAction<DevUuid>>;
+
+    /// Replace the data blockdev specified by old with the device specified
+    /// by new. The new device is initialized with the same encryption
+    /// settings as the pool, all data stored on the old blockdev is moved to
+    /// it, and the old blockdev is then removed from the pool and wiped.
+    /// Returns the UUID of the new blockdev.
+    /// Returns an error if old is not a data blockdev in the pool, if new
+    /// is already in use, or if new is too small to hold the data stored on
+    /// old.
+    fn replace_blockdev(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        old: DevUuid,
+        new: &Path,
+    ) -> StratisResult<CreateAction<DevUuid>>;
+
     /// Bind all devices in the given pool for automated unlocking
     /// using clevis.
     fn bind_clevis(
@@ -204,6 +314,41 @@ pub trait Pool: Debug + Send + Sync {
     /// Regenerate the Clevis bindings associated with a pool.
     fn rebind_clevis(&mut self) -> StratisResult<RegenAction>;
 
+    /// Bind all devices in the given pool to an additional passphrase in the
+    /// kernel keyring under the given name. Every named binding has its own
+    /// keyslot, so the pool can be unlocked with any of them, and each one
+    /// can be removed without affecting the others.
+    fn add_passphrase_binding(
+        &mut self,
+        name: &str,
+        key_desc: &KeyDescription,
+    ) -> StratisResult<CreateAction<Key>>;
+
+    /// Unbind all devices in the given pool from the passphrase bound under
+    /// the given name.
+    fn remove_passphrase_binding(&mut self, name: &str) -> StratisResult<DeleteAction<Key>>;
+
+    /// Encrypt the data devices of an unencrypted pool in place, binding
+    /// them to the unlocking mechanisms in encryption_info. The data is
+    /// encrypted in the background, and the pool remains usable while it is
+    /// encrypted; the progress is available through
+    /// Engine::reencryption_progress(). The last sectors of each data
+    /// device, which hold the LUKS2 header once the data has been shifted,
+    /// must not be allocated.
+    fn encrypt_pool(
+        &mut self,
+        name: &Name,
+        pool_uuid: PoolUuid,
+        encryption_info: &EncryptionInfo,
+    ) -> StratisResult<CreateAction<Encryption>>;
+
+    /// Generate new volume keys for all data devices of an encrypted pool and
+    /// begin reencrypting the data with them in the background. The progress
+    /// of the reencryption is available through
+    /// Engine::reencryption_progress(). An interrupted reencryption is resumed
+    /// when the pool is started again.
+    fn rotate_volume_key(&mut self, pool_uuid: PoolUuid) -> StratisResult<RegenAction>;
+
     /// Ensures that all designated filesystems are gone from pool.
     /// Returns a list of the filesystems found, and actually destroyed.
     /// This list will be a subset of the uuids passed in fs_uuids.
@@ -226,6 +371,79 @@ pub trait Pool: Debug + Send + Sync {
         new_name: &str,
     ) -> StratisResult<RenameAction<FilesystemUuid>>;
 
+    /// Set the size beyond which the filesystem is not extended
+    /// automatically; None removes the limit.
+    /// Returns an error if the limit is less than the size of the filesystem.
+    fn set_fs_size_limit(
+        &mut self,
+        pool_name: &str,
+        uuid: FilesystemUuid,
+        size_limit: Option<Bytes>,
+    ) -> StratisResult<RenameAction<FilesystemUuid>>;
+
+    /// Set the configuration of the automatic extension of the filesystem;
+    /// None makes the filesystem use the default configuration of the pool.
+    fn set_fs_extend_config(
+        &mut self,
+        pool_name: &str,
+        uuid: FilesystemUuid,
+        config: Option<FsExtendConfig>,
+    ) -> StratisResult<RenameAction<FilesystemUuid>>;
+
+    /// Set the policies according to which snapshots of the filesystem are
+    /// taken automatically.
+    fn set_snapshot_policies(
+        &mut self,
+        pool_name: &str,
+        uuid: FilesystemUuid,
+        policies: &[SnapshotPolicy],
+    ) -> StratisResult<RenameAction<FilesystemUuid>>;
+
+    /// Revert the origin filesystem to one of its snapshots.
+    /// The origin keeps its UUID and name, but its data is replaced by the
+    /// snapshot's data. The snapshot and the origin's previous data are
+    /// destroyed. Both filesystems must be unmounted.
+    fn revert_filesystem(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        origin: FilesystemUuid,
+        snapshot: FilesystemUuid,
+    ) -> StratisResult<DeleteAction<FilesystemUuid>>;
+
+    /// Write the contents of the filesystem to the file as a stream of
+    /// blocks. If parent is specified, only the blocks in which the
+    /// filesystem differs from the parent, usually an earlier snapshot of
+    /// the same filesystem, are written. The filesystem must be unmounted.
+    /// Returns the number of bytes of filesystem data written.
+    fn send_filesystem(
+        &self,
+        fs_uuid: FilesystemUuid,
+        parent: Option<FilesystemUuid>,
+        stream: File,
+    ) -> StratisResult<Bytes>;
+
+    /// Create a filesystem with the given name from a complete stream read
+    /// from the file.
+    fn receive_filesystem(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        fs_name: &str,
+        stream: File,
+    ) -> StratisResult<FilesystemUuid>;
+
+    /// Apply an incremental stream read from the file to the filesystem,
+    /// which must be unmounted and must be the parent the stream was
+    /// generated from or the filesystem that received the parent. Returns the number of bytes of
+    /// filesystem data written.
+    fn receive_filesystem_delta(
+        &mut self,
+        pool_name: &str,
+        fs_uuid: FilesystemUuid,
+        stream: File,
+    ) -> StratisResult<Bytes>;
+
     /// Snapshot filesystem
     /// Create a CoW snapshot of the origin
     fn snapshot_filesystem(
@@ -293,15 +511,52 @@ pub trait Pool: Debug + Send + Sync {
         user_info: Option<&str>,
     ) -> StratisResult<RenameAction<DevUuid>>;
 
+    /// Set the tags of the blockdev specified by the uuid. Duplicate tags are
+    /// ignored.
+    fn set_blockdev_tags(
+        &mut self,
+        pool_name: &str,
+        uuid: DevUuid,
+        tags: &[String],
+    ) -> StratisResult<RenameAction<DevUuid>>;
+
+    /// The policy by which space is allocated from the data tier, with the
+    /// tags of the blockdevs preferred for each purpose.
+    fn allocation_config(&self) -> AllocationConfig;
+
+    /// Change the policy by which space is allocated from the data tier.
+    /// Returns true if the configuration changed.
+    fn set_allocation_config(
+        &mut self,
+        pool_name: &str,
+        config: AllocationConfig,
+    ) -> StratisResult<bool>;
+
     /// true if the pool has a cache, otherwise false
     fn has_cache(&self) -> bool;
 
+    /// The redundancy with which the data tier of the pool stores its data.
+    fn redundancy(&self) -> Redundancy;
+
+    /// Whether the data devices of the pool are protected by dm-integrity.
+    fn has_integrity(&self) -> bool;
+
     /// Determine if the pool's data is encrypted
     fn is_encrypted(&self) -> bool;
 
     /// Get all encryption information for this pool.
     fn encryption_info(&self) -> Option<PoolEncryptionInfo>;
 
+    /// Get the settings of the encryption of the devices of this pool, as
+    /// recorded in their LUKS2 headers. The settings that can not be
+    /// determined, such as the PBKDF of a pool with no binding to a
+    /// passphrase in the kernel keyring, are None.
+    fn encryption_config(&self) -> Option<MaybeInconsistent<EncryptionConfig>>;
+
+    /// Get the named passphrase bindings of the devices of this pool. These
+    /// are in addition to the keyring binding in the encryption info.
+    fn passphrase_bindings(&self) -> Option<MaybeInconsistent<PassphraseBindings>>;
+
     /// Get the pool state for the given pool. The state indicates which actions
     /// will be disabled or enabled. Disabled actions are triggered by failures
     /// caught by stratisd.
@@ -327,6 +582,42 @@ pub trait Pool: Debug + Send + Sync {
     /// Returns a boolean indicating whether the pool is out of allocation space.
     fn out_of_alloc_space(&self) -> bool;
 
+    /// Check whether discards issued to the filesystems are passed down to
+    /// the devices beneath the pool.
+    fn discard_passdown(&self) -> bool;
+
+    /// Either enable or disable passing discards down to the devices beneath
+    /// the pool. Returns true if the setting changed.
+    fn set_discard_passdown(&mut self, pool_name: &str, enabled: bool) -> StratisResult<bool>;
+
+    /// The configuration of the automatic extension of the filesystems in
+    /// the pool that do not have a configuration of their own.
+    fn default_fs_extend_config(&self) -> FsExtendConfig;
+
+    /// Set the default configuration of the automatic extension of the
+    /// filesystems in the pool. Returns true if the configuration changed.
+    fn set_default_fs_extend_config(
+        &mut self,
+        pool_name: &str,
+        config: FsExtendConfig,
+    ) -> StratisResult<bool>;
+
+    /// The size of the blocks in which the thin pool of the pool allocates
+    /// data. This is chosen when the pool is created and can not be changed.
+    fn data_block_size(&self) -> Sectors;
+
+    /// The policy according to which the thin pool data device of the pool
+    /// is extended.
+    fn thin_pool_extend_policy(&self) -> ThinPoolExtendPolicy;
+
+    /// Set the policy according to which the thin pool data device of the
+    /// pool is extended. Returns true if the policy changed.
+    fn set_thin_pool_extend_policy(
+        &mut self,
+        pool_name: &str,
+        policy: ThinPoolExtendPolicy,
+    ) -> StratisResult<bool>;
+
     /// Grow either a specified device or all devices in a pool if the underlying
     /// physical device or devices have changed in size.
     fn grow_physical(
@@ -349,13 +640,18 @@ pub trait Engine: Debug + Report + Send + Sync {
     /// Key handling type associated with this engine type.
     type KeyActions: KeyActions;
 
-    /// Create a Stratis pool.
+    /// Create a Stratis pool. The data tier of the pool stores its data
+    /// with the redundancy specified in options. The thin pool of the pool is
+    /// set up according to its thin_pool_config; defaults are used for any
+    /// settings not specified. Likewise, if the pool is encrypted, the LUKS2
+    /// headers of its devices are formatted according to its
+    /// encryption_config.
     /// Returns the UUID of the newly created pool.
     async fn create_pool(
         &self,
         name: &str,
         blockdev_paths: &[&Path],
-        encryption_info: Option<&EncryptionInfo>,
+        options: PoolCreateOptions<'_>,
     ) -> StratisResult<CreateAction<PoolUuid>>;
 
     /// Handle a libudev event.
@@ -386,10 +682,15 @@ pub trait Engine: Debug + Report + Send + Sync {
     /// in the unlocked state. If some devices are able to be unlocked
     /// and some fail, an error is returned as all devices should be able to
     /// be unlocked if the necessary key is in the keyring.
+    ///
+    /// If passphrase_fd is specified, the passphrase is read from it and used
+    /// to unlock the devices directly; it is not added to the kernel keyring.
+    /// This is only valid with the keyring unlock method.
     async fn unlock_pool(
         &self,
         uuid: PoolUuid,
         unlock_method: UnlockMethod,
+        passphrase_fd: Option<RawFd>,
     ) -> StratisResult<SetUnlockAction<DevUuid>>;
 
     /// Find the pool designated by name or UUID.
@@ -432,6 +733,21 @@ pub trait Engine: Debug + Report + Send + Sync {
         pools: Option<&HashSet<PoolUuid>>,
     ) -> HashMap<FilesystemUuid, StratFilesystemDiff>;
 
+    /// Discard the unused blocks of all mounted filesystems, so that the
+    /// space they occupy is returned to their pools. Return the number of
+    /// bytes discarded from each filesystem, or the error encountered.
+    async fn trim_filesystems(&self) -> HashMap<FilesystemUuid, StratisResult<Bytes>>;
+
+    /// Recalculate the mapped, exclusive, and shared space of all filesystems
+    /// and return the filesystems whose space usage changed.
+    async fn update_space_usage(&self) -> HashMap<FilesystemUuid, StratFilesystemDiff>;
+
+    /// Take the snapshots that are due according to the snapshot policies of
+    /// all filesystems and destroy the scheduled snapshots that are no longer
+    /// to be kept. Return the filesystems created and destroyed in each pool,
+    /// or the error that prevented applying the policies of a pool.
+    async fn run_snapshot_policies(&self) -> HashMap<PoolUuid, StratisResult<ScheduledSnapshots>>;
+
     /// Get the handler for kernel keyring operations.
     async fn get_key_handler(&self) -> SharedGuard<OwnedRwLockReadGuard<Self::KeyActions>>;
 
@@ -440,10 +756,21 @@ pub trait Engine: Debug + Report + Send + Sync {
 
     /// Start and set up a pool, creating all necessary devicemapper devices to
     /// perform IO operations and start monitoring for events.
+    ///
+    /// If allow_missing is true, the pool may be started in a degraded state
+    /// even if some of its data devices are missing, provided that no data
+    /// is allocated on the missing devices. Further allocation is then
+    /// disabled for the pool.
+    ///
+    /// If passphrase_fd is specified, the passphrase is read from it and used
+    /// to unlock the pool without adding it to the kernel keyring. This is
+    /// only valid with the keyring unlock method.
     async fn start_pool(
         &self,
         pool_id: PoolIdentifier<PoolUuid>,
         unlock_method: Option<UnlockMethod>,
+        passphrase_fd: Option<RawFd>,
+        allow_missing: bool,
     ) -> StratisResult<StartAction<PoolUuid>>;
 
     /// Stop and tear down a pool, storing the information for it to be started
@@ -453,6 +780,16 @@ pub trait Engine: Debug + Report + Send + Sync {
     /// Refresh the state of all pools and liminal devices.
     async fn refresh_state(&self) -> StratisResult<()>;
 
+    /// Get the progress of the blockdev replacement currently in progress in
+    /// the pool with the given UUID, if any. This method does not require
+    /// access to the pool, which is locked while the replacement is executing.
+    fn replace_progress(&self, pool_uuid: PoolUuid) -> Option<ReplaceProgress>;
+
+    /// Get the progress of the volume key rotation currently in progress in
+    /// the pool with the given UUID, if any. The data is reencrypted in the
+    /// background without holding the lock on the pool.
+    fn reencryption_progress(&self, pool_uuid: PoolUuid) -> Option<ReencryptionProgress>;
+
     /// Return true if this engine is the simulator engine, otherwise false.
     fn is_sim(&self) -> bool;
 }
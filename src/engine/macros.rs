@@ -132,6 +132,20 @@ macro_rules! set_blockdev_user_info {
     };
 }
 
+macro_rules! set_blockdev_tags {
+    ($s:ident; $tags:ident) => {{
+        let mut tags = $tags.to_vec();
+        tags.sort();
+        tags.dedup();
+        if $s.tags != tags {
+            $s.tags = tags;
+            true
+        } else {
+            false
+        }
+    }};
+}
+
 macro_rules! device_list_check_num {
     ($vec:ident, ($is_one:tt, $is_many:tt)) => {{
         let joined_string = $vec.join(", ");
@@ -9,17 +9,22 @@ pub use self::{
     strat_engine::{
         crypt_metadata_size, get_dm, get_dm_init, set_up_crypt_logging, unshare_mount_namespace,
         StaticHeader, StaticHeaderResult, StratEngine, StratKeyActions, ThinPoolSizeParams, BDA,
-        CLEVIS_TANG_TRUST_URL,
+        CLEVIS_TANG_TRUST_URL, DATA_BLOCK_SIZE, DEFAULT_EXTEND_POLICY,
     },
     structures::{ExclusiveGuard, SharedGuard, Table},
     types::{
-        ActionAvailability, BlockDevTier, ClevisInfo, CreateAction, DeleteAction, DevUuid, Diff,
-        EncryptionInfo, EngineAction, FilesystemUuid, GrowAction, KeyDescription, Lockable,
-        LockedPoolInfo, LockedPoolsInfo, MappingCreateAction, MappingDeleteAction,
-        MaybeInconsistent, Name, PoolDiff, PoolEncryptionInfo, PoolIdentifier, PoolUuid,
-        PropChangeAction, RenameAction, ReportType, SetCreateAction, SetDeleteAction, StartAction,
-        StopAction, StoppedPoolInfo, StoppedPoolsInfo, StratBlockDevDiff, StratFilesystemDiff,
-        StratPoolDiff, StratisUuid, ThinPoolDiff, ToDisplay, UdevEngineEvent, UnlockMethod,
+        ActionAvailability, AllocationConfig, AllocationPolicy, AllocationPurpose, BlockDevTier,
+        CacheConfig, CacheMode, CacheStats, ClevisInfo, CreateAction, DeleteAction, DevUuid, Diff,
+        EncryptionConfig, EncryptionInfo, EngineAction, FilesystemSpec, FilesystemType,
+        FilesystemUuid, FsCreateOptions, FsExtendConfig, FsExtendIncrement, GrowAction,
+        KeyDescription, Lockable, LockedPoolInfo, LockedPoolsInfo, MappingCreateAction,
+        MappingDeleteAction, MaybeInconsistent, MkfsOptions, Name, PassphraseBindings, Pbkdf,
+        PoolCreateOptions, PoolDiff, PoolEncryptionInfo, PoolIdentifier, PoolUuid,
+        PropChangeAction, Redundancy, ReencryptionProgress, RenameAction, ReplaceProgress,
+        ReportType, ScheduledSnapshots, SetCreateAction, SetDeleteAction, SnapshotPolicy,
+        SpaceUsage, StartAction, StopAction, StoppedPoolInfo, StoppedPoolsInfo, StratBlockDevDiff,
+        StratFilesystemDiff, StratPoolDiff, StratisUuid, ThinPoolConfig, ThinPoolDiff,
+        ThinPoolExtendPolicy, ToDisplay, UdevEngineEvent, UnlockMethod,
     },
 };
 
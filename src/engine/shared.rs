@@ -11,17 +11,22 @@ use std::{
 };
 
 use chrono::{DateTime, LocalResult, TimeZone, Utc};
+use libcryptsetup_rs::SafeMemHandle;
 use nix::poll::{poll, PollFd, PollFlags};
 use regex::Regex;
+use serde_json::Value;
 
 use devicemapper::{Bytes, Sectors, IEC, SECTOR_SIZE};
 
 use crate::{
     engine::{
-        engine::{BlockDev, Pool, MAX_STRATIS_PASS_SIZE},
+        engine::{BlockDev, Filesystem, Pool, MAX_STRATIS_PASS_SIZE},
         types::{
-            BlockDevTier, CreateAction, DevUuid, Diff, EncryptionInfo, MaybeInconsistent, Name,
-            PoolEncryptionInfo, PoolUuid, SetCreateAction,
+            AllocationConfig, BlockDevTier, CreateAction, DevUuid, Diff, EncryptionConfig,
+            EncryptionInfo, FilesystemSpec, FilesystemType, FsCreateOptions, FsExtendConfig,
+            FsExtendIncrement, MaybeInconsistent, Name, PassphraseBindings, Pbkdf,
+            PoolEncryptionInfo, PoolUuid, Redundancy, SetCreateAction, SizedKeyMemory,
+            SnapshotPolicy, ThinPoolConfig, ThinPoolExtendPolicy, UnlockMethod,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -39,6 +44,25 @@ const MAX_THIN_DEV_SIZE: Sectors = Sectors(16 * IEC::Pi); // 8 EiB
 // https://www.spinics.net/lists/linux-xfs/msg59453.html
 const MIN_THIN_DEV_SIZE: Sectors = Sectors(IEC::Mi); // 512 MiB
 
+// Inode sizes accepted by mkfs.xfs and mkfs.ext4, the latter for the default
+// block size of 4 KiB.
+const MIN_XFS_INODE_SIZE: u64 = 256;
+const MAX_XFS_INODE_SIZE: u64 = 2048;
+const MIN_EXT4_INODE_SIZE: u64 = 128;
+const MAX_EXT4_INODE_SIZE: u64 = 4096;
+
+// Maximum label lengths in bytes, as stored in the superblock.
+const MAX_XFS_LABEL_LEN: usize = 12;
+const MAX_EXT4_LABEL_LEN: usize = 16;
+
+const MAX_MOUNT_OPTIONS_LEN: usize = 256;
+
+// Limits of the PBKDF costs accepted by libcryptsetup; memory in KiB.
+const PBKDF2_MIN_ITERATIONS: u32 = 1000;
+const ARGON2_MIN_ITERATIONS: u32 = 4;
+const ARGON2_MIN_MEMORY: u32 = 32;
+const ARGON2_MAX_MEMORY: u32 = 4 * 1024 * 1024;
+
 /// Called when the name of a requested pool coincides with the name of an
 /// existing pool. Returns an error if the specifications of the requested
 /// pool differ from the specifications of the existing pool, otherwise
@@ -47,10 +71,26 @@ pub fn create_pool_idempotent_or_err<P>(
     pool: &P,
     pool_name: &Name,
     blockdev_paths: &[&Path],
+    redundancy: Redundancy,
+    integrity: bool,
 ) -> StratisResult<CreateAction<PoolUuid>>
 where
     P: Pool,
 {
+    if pool.redundancy() != redundancy {
+        return Err(StratisError::Msg(format!(
+            "The existing pool named {pool_name} has redundancy {}; the input requests redundancy {redundancy}",
+            pool.redundancy()
+        )));
+    }
+
+    if pool.has_integrity() != integrity {
+        return Err(StratisError::Msg(format!(
+            "The existing pool named {pool_name} {} integrity protection of its data devices; the input requests the opposite",
+            if pool.has_integrity() { "has" } else { "does not have" }
+        )));
+    }
+
     let input_devices: HashSet<PathBuf, RandomState> =
         blockdev_paths.iter().map(|p| p.to_path_buf()).collect();
 
@@ -134,6 +174,32 @@ pub fn set_key_shared(key_fd: RawFd, memory: &mut [u8]) -> StratisResult<usize>
     Ok(bytes_read)
 }
 
+/// Read the passphrase passed through a file descriptor to unlock a pool.
+/// The passphrase is only held in memory for the duration of the unlock and
+/// is never added to a keyring. It is only valid with the keyring unlock
+/// method. The file descriptor is always consumed.
+pub fn read_unlock_passphrase(
+    unlock_method: Option<UnlockMethod>,
+    passphrase_fd: Option<RawFd>,
+) -> StratisResult<Option<SizedKeyMemory>> {
+    let fd = match passphrase_fd {
+        Some(fd) => fd,
+        None => return Ok(None),
+    };
+
+    let mut memory = SafeMemHandle::alloc(MAX_STRATIS_PASS_SIZE)?;
+    let bytes = set_key_shared(fd, memory.as_mut())?;
+
+    if unlock_method == Some(UnlockMethod::Keyring) {
+        Ok(Some(SizedKeyMemory::new(memory, bytes)))
+    } else {
+        Err(StratisError::Msg(
+            "A passphrase may only be provided to unlock a pool with the keyring unlock method"
+                .to_string(),
+        ))
+    }
+}
+
 /// Validate a str for use as a Pool or Filesystem name.
 pub fn validate_name(name: &str) -> StratisResult<()> {
     if name.contains('\u{0}') {
@@ -184,6 +250,239 @@ pub fn validate_name(name: &str) -> StratisResult<()> {
     Ok(())
 }
 
+/// Validate strs for use as blockdev tags. A tag may not be empty and may not
+/// contain whitespace or control characters.
+pub fn validate_blockdev_tags(tags: &[String]) -> StratisResult<()> {
+    for tag in tags {
+        if tag.is_empty() {
+            return Err(StratisError::Msg(
+                "A blockdev tag may not be empty".to_string(),
+            ));
+        }
+        if tag.len() > 255 {
+            return Err(StratisError::Msg(format!(
+                "Tag has more than 255 bytes: {tag}"
+            )));
+        }
+        if tag.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(StratisError::Msg(format!(
+                "Tag contains whitespace or control characters: {tag}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Validate an allocation configuration. Its tags must be valid blockdev
+/// tags, and at most one tag may be specified for each purpose.
+pub fn validate_allocation_config(config: &AllocationConfig) -> StratisResult<()> {
+    validate_blockdev_tags(
+        &config
+            .tags
+            .iter()
+            .map(|(_, tag)| tag.to_owned())
+            .collect::<Vec<_>>(),
+    )?;
+    let purposes = config
+        .tags
+        .iter()
+        .map(|(purpose, _)| *purpose)
+        .collect::<HashSet<_>>();
+    if purposes.len() != config.tags.len() {
+        return Err(StratisError::Msg(
+            "At most one tag may be specified for each allocation purpose".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Validate the snapshot policies of a filesystem. Policy names must be
+/// distinct, non-empty, and free of whitespace, since they become part of
+/// snapshot names. Intervals must be positive and at least one snapshot
+/// must be kept if the count is limited.
+pub fn validate_snapshot_policies(policies: &[SnapshotPolicy]) -> StratisResult<()> {
+    for policy in policies {
+        if policy.name.is_empty()
+            || policy
+                .name
+                .chars()
+                .any(|c| c.is_whitespace() || c.is_control() || c == '/')
+        {
+            return Err(StratisError::Msg(format!(
+                "Snapshot policy name \"{}\" must be non-empty and may not contain whitespace, control characters or '/'",
+                policy.name
+            )));
+        }
+        if policy.interval == 0 {
+            return Err(StratisError::Msg(format!(
+                "Snapshot policy {} must have a positive interval",
+                policy.name
+            )));
+        }
+        if policy.keep_count == Some(0) {
+            return Err(StratisError::Msg(format!(
+                "Snapshot policy {} must keep at least one snapshot",
+                policy.name
+            )));
+        }
+    }
+    let names = policies
+        .iter()
+        .map(|policy| policy.name.as_str())
+        .collect::<HashSet<_>>();
+    if names.len() != policies.len() {
+        return Err(StratisError::Msg(
+            "The names of the snapshot policies of a filesystem must be distinct".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Verify that a filesystem extension configuration is sensible. The
+/// threshold must be a percentage strictly between 0 and 100 and the
+/// increment must be positive.
+pub fn validate_fs_extend_config(config: &FsExtendConfig) -> StratisResult<()> {
+    if config.threshold == 0 || config.threshold >= 100 {
+        return Err(StratisError::Msg(format!(
+            "Filesystem extension threshold must be between 1% and 99%, but is {}%",
+            config.threshold
+        )));
+    }
+    match config.increment {
+        FsExtendIncrement::Absolute(Sectors(0)) | FsExtendIncrement::Percent(0) => Err(
+            StratisError::Msg("Filesystem extension increment must be positive".to_string()),
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Verify that the data block size of a thin pool is one that devicemapper
+/// supports: a multiple of 64 KiB between 64 KiB and 1 GiB.
+pub fn validate_data_block_size(data_block_size: Sectors) -> StratisResult<()> {
+    let min_size = Bytes::from(64 * IEC::Ki).sectors();
+    let max_size = Bytes::from(IEC::Gi).sectors();
+    if data_block_size < min_size || data_block_size > max_size || *data_block_size % *min_size != 0
+    {
+        return Err(StratisError::Msg(format!(
+            "Thin pool data block size {data_block_size} must be a multiple of {min_size} between {min_size} and {max_size}"
+        )));
+    }
+    Ok(())
+}
+
+/// Verify that a thin pool extension policy is sensible for a thin pool with
+/// the given data block size. The low water mark and the extension size must
+/// both be at least one data block; they are rounded down to whole data blocks
+/// when used.
+pub fn validate_thin_pool_extend_policy(
+    policy: &ThinPoolExtendPolicy,
+    data_block_size: Sectors,
+) -> StratisResult<()> {
+    for (desc, size) in [
+        ("low water mark", policy.low_water),
+        ("extension size", policy.extend_size),
+    ] {
+        if size < data_block_size {
+            return Err(StratisError::Msg(format!(
+                "Thin pool {desc} {size} must be at least the data block size {data_block_size}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Fill in the settings not specified in a thin pool configuration from the
+/// defaults and validate the result. Returns the data block size and the
+/// extension policy of the thin pool.
+pub fn thin_pool_settings(
+    config: Option<&ThinPoolConfig>,
+    default_data_block_size: Sectors,
+    default_policy: ThinPoolExtendPolicy,
+) -> StratisResult<(Sectors, ThinPoolExtendPolicy)> {
+    let config = config.copied().unwrap_or_default();
+    let data_block_size = config.data_block_size.unwrap_or(default_data_block_size);
+    let policy = ThinPoolExtendPolicy {
+        low_water: config.low_water.unwrap_or(default_policy.low_water),
+        extend_size: config.extend_size.unwrap_or(default_policy.extend_size),
+    };
+    validate_data_block_size(data_block_size)?;
+    validate_thin_pool_extend_policy(&policy, data_block_size)?;
+    Ok((data_block_size, policy))
+}
+
+/// Verify that the settings of an encryption configuration are within the
+/// limits of LUKS2 and dm-crypt. Whether the kernel supports the cipher with
+/// the given key size can only be determined by the engine.
+pub fn validate_encryption_config(config: &EncryptionConfig) -> StratisResult<()> {
+    config.cipher_and_mode()?;
+
+    if let Some(key_size) = config.key_size {
+        if key_size == 0 || key_size % 8 != 0 {
+            return Err(StratisError::Msg(format!(
+                "Key size {key_size} must be a positive number of bits divisible by 8"
+            )));
+        }
+    }
+
+    if let Some(sector_size) = config.sector_size {
+        if !(512..=4096).contains(&sector_size) || !sector_size.is_power_of_two() {
+            return Err(StratisError::Msg(format!(
+                "Encryption sector size {sector_size} must be a power of two between 512 and 4096 bytes"
+            )));
+        }
+    }
+
+    match config.pbkdf {
+        Some(Pbkdf::Pbkdf2) => {
+            if config.pbkdf_memory.is_some() {
+                return Err(StratisError::Msg(
+                    "A memory cost can only be specified for the Argon2 PBKDFs".to_string(),
+                ));
+            }
+            if let Some(iterations) = config.pbkdf_iterations {
+                if iterations < PBKDF2_MIN_ITERATIONS {
+                    return Err(StratisError::Msg(format!(
+                        "PBKDF2 iterations {iterations} must be at least {PBKDF2_MIN_ITERATIONS}"
+                    )));
+                }
+            }
+        }
+        // The default PBKDF of LUKS2 is Argon2 unless the host is in FIPS
+        // mode; libcryptsetup rejects an Argon2 memory cost otherwise.
+        Some(Pbkdf::Argon2i) | Some(Pbkdf::Argon2id) | None => {
+            if let Some(memory) = config.pbkdf_memory {
+                if !(ARGON2_MIN_MEMORY..=ARGON2_MAX_MEMORY).contains(&memory) {
+                    return Err(StratisError::Msg(format!(
+                        "Argon2 memory cost {memory} KiB must be between {ARGON2_MIN_MEMORY} and {ARGON2_MAX_MEMORY} KiB"
+                    )));
+                }
+            }
+            if let (Some(_), Some(iterations)) = (config.pbkdf, config.pbkdf_iterations) {
+                if iterations < ARGON2_MIN_ITERATIONS {
+                    return Err(StratisError::Msg(format!(
+                        "Argon2 iterations {iterations} must be at least {ARGON2_MIN_ITERATIONS}"
+                    )));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Verify that the number of data devices specified is compatible with the
+/// redundancy of the data tier. A mirrored data tier requires its devices
+/// in pairs, one for each leg of the mirror.
+pub fn validate_redundancy(redundancy: Redundancy, num_devices: usize) -> StratisResult<()> {
+    match redundancy {
+        Redundancy::None => Ok(()),
+        Redundancy::Raid1 if num_devices % 2 == 0 => Ok(()),
+        Redundancy::Raid1 => Err(StratisError::Msg(format!(
+            "A data tier with redundancy {redundancy} requires devices in pairs but {num_devices} devices were specified"
+        ))),
+    }
+}
+
 /// Verify that all paths are absolute.
 pub fn validate_paths(paths: &[&Path]) -> StratisResult<()> {
     let non_absolute_paths: Vec<&Path> = paths
@@ -205,13 +504,29 @@ pub fn validate_paths(paths: &[&Path]) -> StratisResult<()> {
     }
 }
 
+/// The parameters of a filesystem to be created, validated and with defaults
+/// filled in for any values that its FilesystemSpec left unspecified.
+#[derive(Debug)]
+pub struct FilesystemParams {
+    pub size: Sectors,
+    pub size_limit: Option<Sectors>,
+    pub fs_type: FilesystemType,
+    pub options: FsCreateOptions,
+}
+
+/// Validate the specifications of filesystems to be created, filling in
+/// default values for any that are not specified.
 pub fn validate_filesystem_size_specs<'a>(
-    specs: &[(&'a str, Option<Bytes>)],
-) -> StratisResult<HashMap<&'a str, Sectors>> {
+    specs: &[FilesystemSpec<'a>],
+) -> StratisResult<HashMap<&'a str, FilesystemParams>> {
     specs
         .iter()
-        .map(|&(name, size_opt)| {
-            size_opt
+        .map(|spec| {
+            let name = spec.name;
+            let fs_type = spec.fs_type.unwrap_or_default();
+            let options = spec.options.clone().unwrap_or_default();
+            validate_fs_create_options(name, fs_type, &options)?;
+            spec.size
                 .map(|size| {
                     let size_sectors = size.sectors();
                     if size_sectors.bytes() != size {
@@ -232,9 +547,108 @@ pub fn validate_filesystem_size_specs<'a>(
                 })
                 .transpose()
                 .map(|size_opt| size_opt.unwrap_or(DEFAULT_THIN_DEV_SIZE))
-                .map(|size| (name, size))
+                .and_then(|size| {
+                    validate_fs_size_limit(name, size, spec.size_limit).map(|size_limit| {
+                        (
+                            name,
+                            FilesystemParams {
+                                size,
+                                size_limit,
+                                fs_type,
+                                options,
+                            },
+                        )
+                    })
+                })
+        })
+        .collect::<StratisResult<HashMap<_, _>>>()
+}
+
+/// Validate the options with which a filesystem of the given type is to be
+/// created. Options that the filesystem type does not support are rejected,
+/// as are labels and mount options that could not safely be written to a
+/// udev environment variable or to /etc/fstab.
+pub fn validate_fs_create_options(
+    name: &str,
+    fs_type: FilesystemType,
+    options: &FsCreateOptions,
+) -> StratisResult<()> {
+    let mkfs = &options.mkfs;
+    if fs_type != FilesystemType::Xfs && (mkfs.reflink.is_some() || mkfs.bigtime.is_some()) {
+        return Err(StratisError::Msg(format!(
+            "The reflink and bigtime options are not supported for filesystem {name} of type {fs_type}"
+        )));
+    }
+    if let Some(inode_size) = mkfs.inode_size {
+        let (min, max) = match fs_type {
+            FilesystemType::Xfs => (MIN_XFS_INODE_SIZE, MAX_XFS_INODE_SIZE),
+            FilesystemType::Ext4 => (MIN_EXT4_INODE_SIZE, MAX_EXT4_INODE_SIZE),
+        };
+        if !inode_size.is_power_of_two() || inode_size < min || inode_size > max {
+            return Err(StratisError::Msg(format!(
+                "Requested inode size {inode_size} of filesystem {name} must be a power of 2 between {min} and {max}"
+            )));
+        }
+    }
+    if let Some(ref label) = mkfs.label {
+        let max = match fs_type {
+            FilesystemType::Xfs => MAX_XFS_LABEL_LEN,
+            FilesystemType::Ext4 => MAX_EXT4_LABEL_LEN,
+        };
+        if label.is_empty() || label.len() > max {
+            return Err(StratisError::Msg(format!(
+                "Requested label of filesystem {name} must be between 1 and {max} bytes long"
+            )));
+        }
+        if label.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(StratisError::Msg(format!(
+                "Requested label of filesystem {name} may not contain whitespace or control characters"
+            )));
+        }
+    }
+    if let Some(ref mount_options) = options.mount_options {
+        if mount_options.is_empty() || mount_options.len() > MAX_MOUNT_OPTIONS_LEN {
+            return Err(StratisError::Msg(format!(
+                "Recommended mount options of filesystem {name} must be between 1 and {MAX_MOUNT_OPTIONS_LEN} bytes long"
+            )));
+        }
+        if mount_options.split(',').any(|opt| {
+            opt.is_empty()
+                || !opt
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "=_-.:/@+".contains(c))
+        }) {
+            return Err(StratisError::Msg(format!(
+                "Recommended mount options of filesystem {name} must be a comma-separated list of options consisting of alphanumeric characters and any of \"=_-.:/@+\""
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Validate the size limit of a filesystem of the given size and convert it
+/// to sectors. The limit may not be less than the size of the filesystem.
+pub fn validate_fs_size_limit(
+    name: &str,
+    size: Sectors,
+    size_limit: Option<Bytes>,
+) -> StratisResult<Option<Sectors>> {
+    size_limit
+        .map(|limit| {
+            let limit_sectors = limit.sectors();
+            if limit_sectors.bytes() != limit {
+                Err(StratisError::Msg(format!(
+                    "Requested size limit of filesystem {name} must be divisible by {SECTOR_SIZE}"
+                )))
+            } else if limit_sectors < size {
+                Err(StratisError::Msg(format!(
+                    "Requested size limit of filesystem {name} is {limit_sectors} which is less than its size: {size}"
+                )))
+            } else {
+                Ok(limit_sectors)
+            }
         })
-        .collect::<StratisResult<HashMap<_, Sectors>>>()
+        .transpose()
 }
 
 /// Gather a collection of information from block devices that may or may not
@@ -274,6 +688,42 @@ where
     gather(len, iterator, PoolEncryptionInfo::from)
 }
 
+/// Gather the encryption configuration from across multiple block devices.
+pub fn gather_encryption_config<'a, I>(
+    len: usize,
+    iterator: I,
+) -> StratisResult<Option<MaybeInconsistent<EncryptionConfig>>>
+where
+    I: Iterator<Item = Option<&'a EncryptionConfig>>,
+{
+    gather(len, iterator, |mut configs| {
+        let first_config = configs.pop().expect("!configs.is_empty()");
+        if configs.into_iter().all(|config| config == first_config) {
+            MaybeInconsistent::No(first_config.clone())
+        } else {
+            MaybeInconsistent::Yes
+        }
+    })
+}
+
+/// Gather the named passphrase bindings from across multiple block devices.
+pub fn gather_passphrase_bindings<'a, I>(
+    len: usize,
+    iterator: I,
+) -> StratisResult<Option<MaybeInconsistent<PassphraseBindings>>>
+where
+    I: Iterator<Item = Option<&'a PassphraseBindings>>,
+{
+    gather(len, iterator, |mut bindings| {
+        let first_bindings = bindings.pop().expect("!bindings.is_empty()");
+        if bindings.into_iter().all(|b| b == first_bindings) {
+            MaybeInconsistent::No(first_bindings.clone())
+        } else {
+            MaybeInconsistent::Yes
+        }
+    })
+}
+
 /// Gather the pool name information from across multiple block devices.
 pub fn gather_pool_name<'a, I>(
     len: usize,
@@ -300,6 +750,49 @@ where
     })
 }
 
+/// Generate the space usage report for the given pools. The filesystems of
+/// each pool are listed in descending order of exclusively used space, i.e.,
+/// of the space that destroying each would free. Filesystems whose space
+/// usage has not yet been calculated are listed last.
+pub fn space_usage_report<'a, P, I>(pools: I) -> Value
+where
+    P: Pool + 'a,
+    I: Iterator<Item = (&'a Name, &'a PoolUuid, &'a P)>,
+{
+    json!({
+        "pools": pools.map(|(pool_name, pool_uuid, pool)| {
+            let mut filesystems = pool.filesystems();
+            filesystems.sort_by_key(|(_, _, fs)| {
+                std::cmp::Reverse(fs.space_usage().map(|u| u.exclusive))
+            });
+            json!({
+                "name": pool_name.to_string(),
+                "uuid": pool_uuid.to_string(),
+                "filesystems": filesystems.into_iter().map(|(fs_name, fs_uuid, fs)| {
+                    let mut json = json!({
+                        "name": fs_name.to_string(),
+                        "uuid": fs_uuid.to_string(),
+                    });
+                    if let Value::Object(ref mut map) = json {
+                        if let Some(origin) = fs.origin() {
+                            map.insert("origin".to_string(), Value::from(origin.to_string()));
+                        }
+                        if let Some(usage) = fs.space_usage() {
+                            map.insert("mapped".to_string(), Value::from(usage.mapped.to_string()));
+                            map.insert(
+                                "exclusive".to_string(),
+                                Value::from(usage.exclusive.to_string()),
+                            );
+                            map.insert("shared".to_string(), Value::from(usage.shared.to_string()));
+                        }
+                    }
+                    json
+                }).collect::<Vec<_>>(),
+            })
+        }).collect::<Vec<_>>(),
+    })
+}
+
 /// Calculate the total used diff from a diff of the thin pool usage and metadata size.
 pub fn total_used(used: &Diff<Option<Bytes>>, metadata_size: &Diff<Bytes>) -> Diff<Option<Bytes>> {
     let changed = matches!(
@@ -349,6 +842,8 @@ pub fn now_to_timestamp() -> DateTime<Utc> {
 
 #[cfg(test)]
 mod tests {
+    use crate::engine::types::MkfsOptions;
+
     use super::*;
 
     #[test]
@@ -387,4 +882,226 @@ mod tests {
         assert_matches!(validate_name("ユニコード"), Ok(_));
         assert_matches!(validate_name("ユニコード?"), Err(_));
     }
+
+    #[test]
+    fn test_validate_fs_create_options() {
+        let mkfs = |reflink, bigtime, inode_size, label: Option<&str>| FsCreateOptions {
+            mkfs: MkfsOptions {
+                reflink,
+                bigtime,
+                inode_size,
+                label: label.map(|l| l.to_string()),
+            },
+            mount_options: None,
+        };
+        let mount = |mount_options: &str| FsCreateOptions {
+            mkfs: MkfsOptions::default(),
+            mount_options: Some(mount_options.to_string()),
+        };
+        let xfs = FilesystemType::Xfs;
+        let ext4 = FilesystemType::Ext4;
+
+        assert_matches!(
+            validate_fs_create_options("fs", xfs, &FsCreateOptions::default()),
+            Ok(_)
+        );
+        assert_matches!(
+            validate_fs_create_options(
+                "fs",
+                xfs,
+                &mkfs(Some(true), Some(true), Some(512), Some("db"))
+            ),
+            Ok(_)
+        );
+        assert_matches!(
+            validate_fs_create_options("fs", ext4, &mkfs(Some(true), None, None, None)),
+            Err(_)
+        );
+        assert_matches!(
+            validate_fs_create_options("fs", ext4, &mkfs(None, Some(false), None, None)),
+            Err(_)
+        );
+        assert_matches!(
+            validate_fs_create_options("fs", ext4, &mkfs(None, None, Some(128), None)),
+            Ok(_)
+        );
+        assert_matches!(
+            validate_fs_create_options("fs", xfs, &mkfs(None, None, Some(128), None)),
+            Err(_)
+        );
+        assert_matches!(
+            validate_fs_create_options("fs", xfs, &mkfs(None, None, Some(384), None)),
+            Err(_)
+        );
+        assert_matches!(
+            validate_fs_create_options("fs", xfs, &mkfs(None, None, None, Some(""))),
+            Err(_)
+        );
+        assert_matches!(
+            validate_fs_create_options("fs", xfs, &mkfs(None, None, None, Some("thirteen_char"))),
+            Err(_)
+        );
+        assert_matches!(
+            validate_fs_create_options("fs", ext4, &mkfs(None, None, None, Some("thirteen_char"))),
+            Ok(_)
+        );
+        assert_matches!(
+            validate_fs_create_options("fs", xfs, &mkfs(None, None, None, Some("a b"))),
+            Err(_)
+        );
+
+        assert_matches!(
+            validate_fs_create_options(
+                "fs",
+                xfs,
+                &mount("noatime,logbsize=256k,context=system_u:object_r:fs_t:s0")
+            ),
+            Ok(_)
+        );
+        assert_matches!(validate_fs_create_options("fs", xfs, &mount("")), Err(_));
+        assert_matches!(
+            validate_fs_create_options("fs", xfs, &mount("noatime,")),
+            Err(_)
+        );
+        assert_matches!(
+            validate_fs_create_options("fs", xfs, &mount("noatime nodev")),
+            Err(_)
+        );
+        assert_matches!(
+            validate_fs_create_options("fs", xfs, &mount("noatime\"")),
+            Err(_)
+        );
+        assert_matches!(
+            validate_fs_create_options("fs", xfs, &mount(&"a".repeat(257))),
+            Err(_)
+        );
+    }
+
+    #[test]
+    fn test_validate_encryption_config() {
+        let config = |cipher: Option<&str>, key_size, pbkdf, pbkdf_memory, pbkdf_iterations| {
+            EncryptionConfig {
+                cipher: cipher.map(|c| c.to_string()),
+                key_size,
+                pbkdf,
+                pbkdf_memory,
+                pbkdf_iterations,
+                sector_size: None,
+            }
+        };
+
+        assert_matches!(
+            validate_encryption_config(&EncryptionConfig::default()),
+            Ok(_)
+        );
+        assert_matches!(
+            validate_encryption_config(&config(
+                Some("aes-xts-plain64"),
+                Some(512),
+                None,
+                None,
+                None
+            )),
+            Ok(_)
+        );
+        assert_matches!(
+            validate_encryption_config(&config(Some("aes"), None, None, None, None)),
+            Err(_)
+        );
+        assert_matches!(
+            validate_encryption_config(&config(Some("-xts-plain64"), None, None, None, None)),
+            Err(_)
+        );
+        assert_matches!(
+            validate_encryption_config(&config(None, Some(260), None, None, None)),
+            Err(_)
+        );
+        assert_matches!(
+            validate_encryption_config(&config(
+                None,
+                None,
+                Some(Pbkdf::Pbkdf2),
+                None,
+                Some(100_000)
+            )),
+            Ok(_)
+        );
+        assert_matches!(
+            validate_encryption_config(&config(None, None, Some(Pbkdf::Pbkdf2), Some(65536), None)),
+            Err(_)
+        );
+        assert_matches!(
+            validate_encryption_config(&config(None, None, Some(Pbkdf::Pbkdf2), None, Some(10))),
+            Err(_)
+        );
+        assert_matches!(
+            validate_encryption_config(&config(
+                None,
+                None,
+                Some(Pbkdf::Argon2id),
+                Some(65536),
+                Some(4)
+            )),
+            Ok(_)
+        );
+        assert_matches!(
+            validate_encryption_config(&config(None, None, None, Some(16), None)),
+            Err(_)
+        );
+        assert_matches!(
+            validate_encryption_config(&config(None, None, Some(Pbkdf::Argon2i), None, Some(2))),
+            Err(_)
+        );
+
+        for (sector_size, valid) in [(512, true), (4096, true), (1000, false), (8192, false)] {
+            assert_eq!(
+                validate_encryption_config(&EncryptionConfig {
+                    sector_size: Some(sector_size),
+                    ..Default::default()
+                })
+                .is_ok(),
+                valid
+            );
+        }
+    }
+
+    #[test]
+    /// A passphrase is read from the file descriptor only for the keyring
+    /// unlock method.
+    fn test_read_unlock_passphrase() {
+        use nix::unistd::{close, pipe, write};
+
+        fn passphrase_fd(passphrase: &[u8]) -> RawFd {
+            let (read_end, write_end) = pipe().unwrap();
+            write(write_end, passphrase).unwrap();
+            close(write_end).unwrap();
+            read_end
+        }
+
+        assert_matches!(
+            read_unlock_passphrase(Some(UnlockMethod::Keyring), None),
+            Ok(None)
+        );
+        assert_eq!(
+            read_unlock_passphrase(
+                Some(UnlockMethod::Keyring),
+                Some(passphrase_fd(b"passphrase"))
+            )
+            .unwrap()
+            .unwrap()
+            .as_ref(),
+            b"passphrase"
+        );
+        assert_matches!(
+            read_unlock_passphrase(
+                Some(UnlockMethod::Clevis),
+                Some(passphrase_fd(b"passphrase"))
+            ),
+            Err(_)
+        );
+        assert_matches!(
+            read_unlock_passphrase(None, Some(passphrase_fd(b"passphrase"))),
+            Err(_)
+        );
+    }
 }
@@ -9,10 +9,13 @@ use serde_json::{Map, Value};
 
 use devicemapper::{Bytes, Sectors, IEC};
 
-use crate::engine::{
-    engine::BlockDev,
-    shared::now_to_timestamp,
-    types::{DevUuid, EncryptionInfo, KeyDescription},
+use crate::{
+    engine::{
+        engine::BlockDev,
+        shared::now_to_timestamp,
+        types::{DevUuid, EncryptionInfo, KeyDescription},
+    },
+    stratis::StratisResult,
 };
 
 #[derive(Debug)]
@@ -23,6 +26,8 @@ pub struct SimDev {
     hardware_info: Option<String>,
     initialization_time: DateTime<Utc>,
     encryption_info: Option<EncryptionInfo>,
+    integrity: bool,
+    tags: Vec<String>,
 }
 
 impl SimDev {
@@ -64,11 +69,23 @@ impl BlockDev for SimDev {
     fn new_size(&self) -> Option<Sectors> {
         None
     }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn integrity_mismatches(&self) -> StratisResult<Option<u64>> {
+        Ok(if self.integrity { Some(0) } else { None })
+    }
 }
 
 impl SimDev {
     /// Generates a new device from any devnode.
-    pub fn new(devnode: &Path, encryption_info: Option<&EncryptionInfo>) -> (DevUuid, SimDev) {
+    pub fn new(
+        devnode: &Path,
+        integrity: bool,
+        encryption_info: Option<&EncryptionInfo>,
+    ) -> (DevUuid, SimDev) {
         (
             DevUuid::new_v4(),
             SimDev {
@@ -77,6 +94,8 @@ impl SimDev {
                 hardware_info: None,
                 initialization_time: now_to_timestamp(),
                 encryption_info: encryption_info.cloned(),
+                integrity,
+                tags: Vec::new(),
             },
         )
     }
@@ -88,6 +107,12 @@ impl SimDev {
         set_blockdev_user_info!(self; user_info)
     }
 
+    /// Set the tags on this blockdev.
+    /// Returns true if the tags were changed, otherwise false.
+    pub fn set_tags(&mut self, tags: &[String]) -> bool {
+        set_blockdev_tags!(self; tags)
+    }
+
     /// Set the clevis info for a block device.
     pub fn set_clevis_info(&mut self, pin: &str, config: &Value) {
         self.encryption_info = self
@@ -114,6 +139,11 @@ impl SimDev {
         self.encryption_info = self.encryption_info.take().map(|ei| ei.unset_key_desc())
     }
 
+    /// Set the encryption information for an unencrypted block device.
+    pub fn set_encryption_info(&mut self, encryption_info: &EncryptionInfo) {
+        self.encryption_info = Some(encryption_info.clone());
+    }
+
     /// Get encryption information for this block device.
     pub fn encryption_info(&self) -> Option<&EncryptionInfo> {
         self.encryption_info.as_ref()
@@ -128,6 +158,8 @@ impl<'a> Into<Value> for &'a SimDev {
             Value::from(self.devnode.display().to_string()),
         );
         json.insert("size".to_string(), Value::from(self.size().to_string()));
+        json.insert("integrity".to_string(), Value::from(self.integrity));
+        json.insert("tags".to_string(), Value::from(self.tags.clone()));
         if let Some(EncryptionInfo::Both(kd, (pin, config))) = self.encryption_info.as_ref() {
             json.insert(
                 "key_description".to_string(),
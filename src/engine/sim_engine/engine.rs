@@ -4,6 +4,7 @@
 
 use std::{
     collections::{hash_map::RandomState, HashMap, HashSet},
+    os::unix::io::RawFd,
     path::Path,
     sync::Arc,
 };
@@ -13,18 +14,24 @@ use futures::executor::block_on;
 use serde_json::{json, Value};
 use tokio::sync::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
 
+use devicemapper::Bytes;
+
 use crate::{
     engine::{
         engine::{Engine, HandleEvents, Pool, Report},
-        shared::{create_pool_idempotent_or_err, validate_name, validate_paths},
+        shared::{
+            create_pool_idempotent_or_err, read_unlock_passphrase, space_usage_report,
+            validate_encryption_config, validate_name, validate_paths, validate_redundancy,
+        },
         sim_engine::{keys::SimKeyActions, pool::SimPool},
         structures::{
             AllLockReadGuard, AllLockWriteGuard, AllOrSomeLock, ExclusiveGuard, Lockable,
             SharedGuard, SomeLockReadGuard, SomeLockWriteGuard, Table,
         },
         types::{
-            CreateAction, DeleteAction, DevUuid, EncryptionInfo, FilesystemUuid, LockedPoolsInfo,
-            Name, PoolDevice, PoolDiff, PoolIdentifier, PoolUuid, RenameAction, ReportType,
+            CreateAction, DeleteAction, DevUuid, FilesystemUuid, LockedPoolsInfo, Name,
+            PoolCreateOptions, PoolDevice, PoolDiff, PoolIdentifier, PoolUuid,
+            ReencryptionProgress, RenameAction, ReplaceProgress, ReportType, ScheduledSnapshots,
             SetUnlockAction, StartAction, StopAction, StoppedPoolInfo, StoppedPoolsInfo,
             StratFilesystemDiff, UdevEngineEvent, UnlockMethod,
         },
@@ -115,6 +122,7 @@ impl Report for SimEngine {
                     .collect::<Vec<_>>()
                 })
             }
+            ReportType::SpaceUsage => space_usage_report(block_on(self.pools.read_all()).iter()),
         }
     }
 }
@@ -128,13 +136,31 @@ impl Engine for SimEngine {
         &self,
         name: &str,
         blockdev_paths: &[&Path],
-        encryption_info: Option<&EncryptionInfo>,
+        options: PoolCreateOptions<'_>,
     ) -> StratisResult<CreateAction<PoolUuid>> {
+        let PoolCreateOptions {
+            redundancy,
+            integrity,
+            encryption_info,
+            encryption_config,
+            thin_pool_config,
+        } = options;
+
         validate_name(name)?;
         let name = Name::new(name.to_owned());
 
         validate_paths(blockdev_paths)?;
 
+        if let Some(config) = encryption_config {
+            if encryption_info.is_none() {
+                return Err(StratisError::Msg(
+                    "Encryption settings were specified for a pool that is not encrypted"
+                        .to_string(),
+                ));
+            }
+            validate_encryption_config(config)?;
+        }
+
         if let Some(key_desc) = encryption_info.and_then(|ei| ei.key_description()) {
             if !self.key_handler.read().await.contains_key(key_desc) {
                 return Err(StratisError::Msg(format!(
@@ -146,7 +172,9 @@ impl Engine for SimEngine {
 
         let guard = self.pools.read(PoolIdentifier::Name(name.clone())).await;
         match guard.as_ref().map(|g| g.as_tuple()) {
-            Some((_, _, pool)) => create_pool_idempotent_or_err(pool, &name, blockdev_paths),
+            Some((_, _, pool)) => {
+                create_pool_idempotent_or_err(pool, &name, blockdev_paths, redundancy, integrity)
+            }
             None => {
                 if blockdev_paths.is_empty() {
                     Err(StratisError::Msg(
@@ -155,8 +183,16 @@ impl Engine for SimEngine {
                 } else {
                     let device_set: HashSet<_, RandomState> = HashSet::from_iter(blockdev_paths);
                     let devices = device_set.into_iter().cloned().collect::<Vec<_>>();
+                    validate_redundancy(redundancy, devices.len())?;
 
-                    let (pool_uuid, pool) = SimPool::new(&devices, encryption_info);
+                    let (pool_uuid, pool) = SimPool::new(
+                        &devices,
+                        redundancy,
+                        integrity,
+                        encryption_info,
+                        encryption_config,
+                        thin_pool_config,
+                    )?;
 
                     self.pools.write_all().await.insert(
                         Name::new(name.to_owned()),
@@ -214,8 +250,10 @@ impl Engine for SimEngine {
     async fn unlock_pool(
         &self,
         _pool_uuid: PoolUuid,
-        _unlock_method: UnlockMethod,
+        unlock_method: UnlockMethod,
+        passphrase_fd: Option<RawFd>,
     ) -> StratisResult<SetUnlockAction<DevUuid>> {
+        read_unlock_passphrase(Some(unlock_method), passphrase_fd)?;
         Ok(SetUnlockAction::empty())
     }
 
@@ -285,6 +323,18 @@ impl Engine for SimEngine {
         HashMap::default()
     }
 
+    async fn trim_filesystems(&self) -> HashMap<FilesystemUuid, StratisResult<Bytes>> {
+        HashMap::default()
+    }
+
+    async fn update_space_usage(&self) -> HashMap<FilesystemUuid, StratFilesystemDiff> {
+        HashMap::default()
+    }
+
+    async fn run_snapshot_policies(&self) -> HashMap<PoolUuid, StratisResult<ScheduledSnapshots>> {
+        HashMap::default()
+    }
+
     async fn get_key_handler(&self) -> SharedGuard<OwnedRwLockReadGuard<Self::KeyActions>> {
         self.key_handler.read().await
     }
@@ -297,7 +347,11 @@ impl Engine for SimEngine {
         &self,
         id: PoolIdentifier<PoolUuid>,
         unlock_method: Option<UnlockMethod>,
+        passphrase_fd: Option<RawFd>,
+        _: bool,
     ) -> StratisResult<StartAction<PoolUuid>> {
+        read_unlock_passphrase(unlock_method, passphrase_fd)?;
+
         if let Some(guard) = self.pools.read(id.clone()).await {
             let (_, pool_uuid, pool) = guard.as_tuple();
             if pool.is_encrypted() && unlock_method.is_none() {
@@ -367,6 +421,14 @@ impl Engine for SimEngine {
         Ok(())
     }
 
+    fn replace_progress(&self, _pool_uuid: PoolUuid) -> Option<ReplaceProgress> {
+        None
+    }
+
+    fn reencryption_progress(&self, _pool_uuid: PoolUuid) -> Option<ReencryptionProgress> {
+        None
+    }
+
     fn is_sim(&self) -> bool {
         true
     }
@@ -376,7 +438,7 @@ impl Engine for SimEngine {
 mod tests {
     use crate::engine::{
         engine::{Engine, Pool},
-        types::{EngineAction, RenameAction},
+        types::{EngineAction, FilesystemSpec, Redundancy, RenameAction},
     };
 
     use super::*;
@@ -403,7 +465,7 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             "name",
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
@@ -415,10 +477,14 @@ mod tests {
     /// Destroying a pool with devices should succeed
     fn destroy_pool_w_devices() {
         let engine = SimEngine::default();
-        let uuid = test_async!(engine.create_pool("name", strs_to_paths!(["/s/d"]), None))
-            .unwrap()
-            .changed()
-            .unwrap();
+        let uuid = test_async!(engine.create_pool(
+            "name",
+            strs_to_paths!(["/s/d"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
         assert!(test_async!(engine.destroy_pool(uuid)).is_ok());
     }
 
@@ -427,13 +493,17 @@ mod tests {
     fn destroy_pool_w_filesystem() {
         let engine = SimEngine::default();
         let pool_name = "pool_name";
-        let uuid = test_async!(engine.create_pool(pool_name, strs_to_paths!(["/s/d"]), None))
-            .unwrap()
-            .changed()
-            .unwrap();
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/s/d"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
         {
             let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
-            pool.create_filesystems(pool_name, uuid, &[("test", None)])
+            pool.create_filesystems(pool_name, uuid, &[FilesystemSpec::new("test")])
                 .unwrap();
         }
         assert!(test_async!(engine.destroy_pool(uuid)).is_err());
@@ -446,9 +516,9 @@ mod tests {
         let name = "name";
         let engine = SimEngine::default();
         let devices = strs_to_paths!(["/s/d"]);
-        test_async!(engine.create_pool(name, devices, None)).unwrap();
+        test_async!(engine.create_pool(name, devices, PoolCreateOptions::default())).unwrap();
         assert_matches!(
-            test_async!(engine.create_pool(name, devices, None)),
+            test_async!(engine.create_pool(name, devices, PoolCreateOptions::default())),
             Ok(CreateAction::Identity)
         );
     }
@@ -458,30 +528,89 @@ mod tests {
     fn create_pool_name_collision_different_args() {
         let name = "name";
         let engine = SimEngine::default();
-        test_async!(engine.create_pool(name, strs_to_paths!(["/s/d"]), None)).unwrap();
+        test_async!(engine.create_pool(
+            name,
+            strs_to_paths!(["/s/d"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap();
         assert!(test_async!(engine.create_pool(
             name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .is_err());
     }
 
+    #[test]
+    /// Creating a mirrored pool requires devices in pairs, and creating a
+    /// pool with the name of an existing pool but a different redundancy
+    /// should fail.
+    fn create_pool_raid1() {
+        let engine = SimEngine::default();
+        assert!(test_async!(engine.create_pool(
+            "name",
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            PoolCreateOptions {
+                redundancy: Redundancy::Raid1,
+                ..Default::default()
+            }
+        ))
+        .is_err());
+
+        let devices = strs_to_paths!(["/dev/one", "/dev/two"]);
+        let uuid = test_async!(engine.create_pool(
+            "name",
+            devices,
+            PoolCreateOptions {
+                redundancy: Redundancy::Raid1,
+                ..Default::default()
+            }
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        assert_eq!(
+            test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid)))
+                .unwrap()
+                .redundancy(),
+            Redundancy::Raid1
+        );
+        assert_matches!(
+            test_async!(engine.create_pool(
+                "name",
+                devices,
+                PoolCreateOptions {
+                    redundancy: Redundancy::Raid1,
+                    ..Default::default()
+                }
+            )),
+            Ok(CreateAction::Identity)
+        );
+        assert!(
+            test_async!(engine.create_pool("name", devices, PoolCreateOptions::default())).is_err()
+        );
+    }
+
     #[test]
     /// Creating a pool with duplicate devices should succeed
     fn create_pool_duplicate_devices() {
         let path = "/s/d";
         let engine = SimEngine::default();
         assert_matches!(
-            test_async!(engine.create_pool("name", strs_to_paths!([path, path]), None))
-                .unwrap()
-                .changed()
-                .map(
-                    |uuid| test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid)))
-                        .unwrap()
-                        .blockdevs()
-                        .len()
-                ),
+            test_async!(engine.create_pool(
+                "name",
+                strs_to_paths!([path, path]),
+                PoolCreateOptions::default()
+            ))
+            .unwrap()
+            .changed()
+            .map(
+                |uuid| test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid)))
+                    .unwrap()
+                    .blockdevs()
+                    .len()
+            ),
             Some(1)
         );
     }
@@ -504,7 +633,7 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
@@ -522,7 +651,7 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             "old_name",
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
@@ -541,7 +670,7 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             "old_name",
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
@@ -549,7 +678,7 @@ mod tests {
         test_async!(engine.create_pool(
             new_name,
             strs_to_paths!(["/dev/four", "/dev/five", "/dev/six"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap();
         assert!(test_async!(engine.rename_pool(uuid, new_name)).is_err());
@@ -563,7 +692,7 @@ mod tests {
         test_async!(engine.create_pool(
             new_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap();
         assert_matches!(
@@ -9,27 +9,95 @@ use serde_json::{Map, Value};
 
 use devicemapper::{Bytes, Sectors};
 
-use crate::{engine::Filesystem, stratis::StratisResult};
+use crate::{
+    engine::{
+        Filesystem, FilesystemType, FilesystemUuid, FsExtendConfig, SnapshotPolicy, SpaceUsage,
+    },
+    stratis::StratisResult,
+};
 
 #[derive(Debug)]
 pub struct SimFilesystem {
     rand: u32,
     created: DateTime<Utc>,
     size: Sectors,
+    size_limit: Option<Sectors>,
+    fs_type: FilesystemType,
+    mount_options: Option<String>,
+    extend_config: Option<FsExtendConfig>,
+    origin: Option<FilesystemUuid>,
+    snapshot_policies: Vec<SnapshotPolicy>,
 }
 
 impl SimFilesystem {
-    pub fn new(size: Sectors) -> SimFilesystem {
+    pub fn new(
+        size: Sectors,
+        size_limit: Option<Sectors>,
+        fs_type: FilesystemType,
+        mount_options: Option<String>,
+        origin: Option<FilesystemUuid>,
+    ) -> SimFilesystem {
         SimFilesystem {
             rand: rand::random::<u32>(),
             created: Utc::now(),
             size,
+            size_limit,
+            fs_type,
+            mount_options,
+            extend_config: None,
+            origin,
+            snapshot_policies: Vec::new(),
         }
     }
 
     pub fn size(&self) -> Sectors {
         self.size
     }
+
+    /// Take over the contents of the given snapshot.
+    pub fn revert(&mut self, snapshot: &SimFilesystem) {
+        self.size = snapshot.size;
+        self.fs_type = snapshot.fs_type;
+    }
+
+    /// Set the size limit of this filesystem. Returns true if the limit
+    /// changed.
+    pub fn set_size_limit(&mut self, size_limit: Option<Sectors>) -> bool {
+        if self.size_limit == size_limit {
+            false
+        } else {
+            self.size_limit = size_limit;
+            true
+        }
+    }
+
+    /// Set the configuration of the automatic extension of this filesystem.
+    /// Returns true if the configuration changed.
+    pub fn set_extend_config(&mut self, config: Option<FsExtendConfig>) -> bool {
+        if self.extend_config == config {
+            false
+        } else {
+            self.extend_config = config;
+            true
+        }
+    }
+
+    /// Forget the origin of this filesystem. Returns true if the filesystem
+    /// had an origin.
+    pub fn unset_origin(&mut self) -> bool {
+        self.origin.take().is_some()
+    }
+
+    /// Set the snapshot policies of this filesystem. Returns true if the
+    /// policies changed.
+    pub fn set_snapshot_policies(&mut self, policies: &[SnapshotPolicy]) -> bool {
+        if self.snapshot_policies == policies {
+            false
+        } else {
+            self.snapshot_policies = policies.to_vec();
+            true
+        }
+    }
 }
 
 impl Filesystem for SimFilesystem {
@@ -54,12 +122,50 @@ impl Filesystem for SimFilesystem {
     fn size(&self) -> Bytes {
         self.size.bytes()
     }
+
+    fn size_limit(&self) -> Option<Sectors> {
+        self.size_limit
+    }
+
+    fn extend_config(&self) -> Option<FsExtendConfig> {
+        self.extend_config
+    }
+
+    fn origin(&self) -> Option<FilesystemUuid> {
+        self.origin
+    }
+
+    fn snapshot_policies(&self) -> &[SnapshotPolicy] {
+        &self.snapshot_policies
+    }
+
+    fn scheduled_by(&self) -> Option<&str> {
+        None
+    }
+
+    fn fs_type(&self) -> FilesystemType {
+        self.fs_type
+    }
+
+    fn mount_options(&self) -> Option<&str> {
+        self.mount_options.as_deref()
+    }
+
+    fn space_usage(&self) -> Option<SpaceUsage> {
+        let used = (self.size / 2u64).bytes();
+        Some(SpaceUsage {
+            mapped: used,
+            exclusive: used,
+            shared: Bytes(0),
+        })
+    }
 }
 
 impl<'a> Into<Value> for &'a SimFilesystem {
     fn into(self) -> Value {
         let mut json = Map::new();
         json.insert("size".to_string(), Value::from(self.size().to_string()));
+        json.insert("fs_type".to_string(), Value::from(self.fs_type.to_string()));
         json.insert(
             "used".to_string(),
             Value::from(
@@ -68,6 +174,27 @@ impl<'a> Into<Value> for &'a SimFilesystem {
                     .unwrap_or_else(|_| "Unavailable".to_string()),
             ),
         );
+        if let Some(size_limit) = self.size_limit {
+            json.insert(
+                "size_limit".to_string(),
+                Value::from(size_limit.to_string()),
+            );
+        }
+        if let Some(extend_config) = self.extend_config {
+            json.insert(
+                "extend_config".to_string(),
+                Value::from(extend_config.to_string()),
+            );
+        }
+        if let Some(origin) = self.origin {
+            json.insert("origin".to_string(), Value::from(origin.to_string()));
+        }
+        if let Some(ref mount_options) = self.mount_options {
+            json.insert(
+                "mount_options".to_string(),
+                Value::from(mount_options.to_string()),
+            );
+        }
         Value::from(json)
     }
 }
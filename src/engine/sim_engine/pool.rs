@@ -4,6 +4,8 @@
 
 use std::{
     collections::{hash_map::RandomState, HashMap, HashSet},
+    fs::File,
+    io,
     path::Path,
     vec::Vec,
 };
@@ -14,46 +16,127 @@ use devicemapper::{Bytes, Sectors, IEC};
 
 use crate::{
     engine::{
-        engine::Pool,
+        engine::{Filesystem, Pool},
         shared::{
-            gather_encryption_info, init_cache_idempotent_or_err, validate_filesystem_size_specs,
-            validate_name, validate_paths,
+            gather_encryption_info, init_cache_idempotent_or_err, thin_pool_settings,
+            validate_allocation_config, validate_blockdev_tags, validate_filesystem_size_specs,
+            validate_fs_extend_config, validate_fs_size_limit, validate_name, validate_paths,
+            validate_redundancy, validate_snapshot_policies, validate_thin_pool_extend_policy,
         },
         sim_engine::{blockdev::SimDev, filesystem::SimFilesystem},
         structures::Table,
         types::{
-            ActionAvailability, BlockDevTier, Clevis, CreateAction, DeleteAction, DevUuid,
-            EncryptionInfo, FilesystemUuid, GrowAction, Key, KeyDescription, Name,
-            PoolEncryptionInfo, PoolUuid, RegenAction, RenameAction, SetCreateAction,
-            SetDeleteAction,
+            ActionAvailability, AllocationConfig, BlockDevTier, CacheConfig, CacheStats, Clevis,
+            CreateAction, DeleteAction, DevUuid, Encryption, EncryptionConfig, EncryptionInfo,
+            FilesystemSpec, FilesystemUuid, FsExtendConfig, GrowAction, Key, KeyDescription,
+            MaybeInconsistent, Name, PassphraseBindings, Pbkdf, PoolEncryptionInfo, PoolUuid,
+            Redundancy, RegenAction, RenameAction, SetCreateAction, SetDeleteAction,
+            SnapshotPolicy, ThinPoolConfig, ThinPoolExtendPolicy,
         },
     },
     stratis::{StratisError, StratisResult},
 };
 
+// 1 MiB
+const DEFAULT_DATA_BLOCK_SIZE: Sectors = Sectors(2 * IEC::Ki);
+
+const DEFAULT_EXTEND_POLICY: ThinPoolExtendPolicy = ThinPoolExtendPolicy {
+    // 15 GiB
+    low_water: Sectors(30 * IEC::Mi),
+    // 50 GiB
+    extend_size: Sectors(100 * IEC::Mi),
+};
+
+const DEFAULT_CIPHER: &str = "aes-xts-plain64";
+// bits
+const DEFAULT_KEY_SIZE: u32 = 512;
+const DEFAULT_PBKDF: Pbkdf = Pbkdf::Argon2id;
+// KiB
+const DEFAULT_PBKDF_MEMORY: u32 = 1024 * 1024;
+const DEFAULT_ARGON2_ITERATIONS: u32 = 4;
+const DEFAULT_PBKDF2_ITERATIONS: u32 = 1000;
+const DEFAULT_SECTOR_SIZE: u32 = 512;
+
+/// Fill in the defaults for the encryption settings that were not
+/// specified, as a real pool would report them after creation.
+fn effective_encryption_config(config: Option<&EncryptionConfig>) -> EncryptionConfig {
+    let config = config.cloned().unwrap_or_default();
+    let pbkdf = config.pbkdf.unwrap_or(DEFAULT_PBKDF);
+    let (pbkdf_memory, pbkdf_iterations) = match pbkdf {
+        Pbkdf::Pbkdf2 => (
+            None,
+            config.pbkdf_iterations.unwrap_or(DEFAULT_PBKDF2_ITERATIONS),
+        ),
+        Pbkdf::Argon2i | Pbkdf::Argon2id => (
+            Some(config.pbkdf_memory.unwrap_or(DEFAULT_PBKDF_MEMORY)),
+            config.pbkdf_iterations.unwrap_or(DEFAULT_ARGON2_ITERATIONS),
+        ),
+    };
+    EncryptionConfig {
+        cipher: Some(config.cipher.unwrap_or_else(|| DEFAULT_CIPHER.to_string())),
+        key_size: Some(config.key_size.unwrap_or(DEFAULT_KEY_SIZE)),
+        pbkdf: Some(pbkdf),
+        pbkdf_memory,
+        pbkdf_iterations: Some(pbkdf_iterations),
+        sector_size: Some(config.sector_size.unwrap_or(DEFAULT_SECTOR_SIZE)),
+    }
+}
+
 #[derive(Debug)]
 pub struct SimPool {
     block_devs: HashMap<DevUuid, SimDev>,
     cache_devs: HashMap<DevUuid, SimDev>,
+    cache_config: CacheConfig,
+    redundancy: Redundancy,
+    integrity: bool,
+    allocation_config: AllocationConfig,
     filesystems: Table<FilesystemUuid, SimFilesystem>,
     fs_limit: u64,
     enable_overprov: bool,
+    discard_passdown: bool,
+    fs_extend_config: FsExtendConfig,
+    data_block_size: Sectors,
+    extend_policy: ThinPoolExtendPolicy,
+    encryption_config: Option<EncryptionConfig>,
+    passphrase_bindings: Option<PassphraseBindings>,
 }
 
 impl SimPool {
-    pub fn new(paths: &[&Path], enc_info: Option<&EncryptionInfo>) -> (PoolUuid, SimPool) {
+    pub fn new(
+        paths: &[&Path],
+        redundancy: Redundancy,
+        integrity: bool,
+        enc_info: Option<&EncryptionInfo>,
+        enc_config: Option<&EncryptionConfig>,
+        thin_pool_config: Option<&ThinPoolConfig>,
+    ) -> StratisResult<(PoolUuid, SimPool)> {
+        let (data_block_size, extend_policy) = thin_pool_settings(
+            thin_pool_config,
+            DEFAULT_DATA_BLOCK_SIZE,
+            DEFAULT_EXTEND_POLICY,
+        )?;
         let devices: HashSet<_, RandomState> = HashSet::from_iter(paths);
-        let device_pairs = devices.iter().map(|p| SimDev::new(p, enc_info));
-        (
+        let device_pairs = devices.iter().map(|p| SimDev::new(p, integrity, enc_info));
+        Ok((
             PoolUuid::new_v4(),
             SimPool {
                 block_devs: device_pairs.collect(),
                 cache_devs: HashMap::new(),
+                cache_config: CacheConfig::default(),
+                redundancy,
+                integrity,
+                allocation_config: AllocationConfig::default(),
                 filesystems: Table::default(),
                 fs_limit: 10,
                 enable_overprov: true,
+                discard_passdown: false,
+                fs_extend_config: FsExtendConfig::default(),
+                data_block_size,
+                extend_policy,
+                encryption_config: enc_info.map(|_| effective_encryption_config(enc_config)),
+                passphrase_bindings: enc_info.map(|_| PassphraseBindings::new()),
             },
-        )
+        ))
     }
 
     pub fn has_filesystems(&self) -> bool {
@@ -121,6 +204,16 @@ impl SimPool {
             Ok(())
         }
     }
+
+    /// Forget the origin of any filesystem that is a snapshot of one of the
+    /// removed filesystems.
+    fn unset_origins(&mut self, removed: &[FilesystemUuid]) {
+        for (_, _, fs) in self.filesystems.iter_mut() {
+            if fs.origin().map(|o| removed.contains(&o)).unwrap_or(false) {
+                fs.unset_origin();
+            }
+        }
+    }
 }
 
 // Precondition: SimDev::into() always returns a value that matches Value::Object(_).
@@ -188,6 +281,7 @@ impl Pool for SimPool {
         _pool_uuid: PoolUuid,
         _pool_name: &str,
         blockdevs: &[&Path],
+        cache_config: &CacheConfig,
         supports_encrypted: bool,
     ) -> StratisResult<SetCreateAction<DevUuid>> {
         validate_paths(blockdevs)?;
@@ -203,9 +297,13 @@ impl Pool for SimPool {
                     "At least one blockdev path is required to initialize a cache.".to_string(),
                 ));
             }
-            let blockdev_pairs: Vec<_> = blockdevs.iter().map(|p| SimDev::new(p, None)).collect();
+            let blockdev_pairs: Vec<_> = blockdevs
+                .iter()
+                .map(|p| SimDev::new(p, false, None))
+                .collect();
             let blockdev_uuids: Vec<_> = blockdev_pairs.iter().map(|(uuid, _)| *uuid).collect();
             self.cache_devs.extend(blockdev_pairs);
+            self.cache_config = cache_config.clone();
             Ok(SetCreateAction::new(blockdev_uuids))
         } else {
             init_cache_idempotent_or_err(
@@ -215,29 +313,83 @@ impl Pool for SimPool {
         }
     }
 
+    fn remove_cache(
+        &mut self,
+        _pool_uuid: PoolUuid,
+        _pool_name: &str,
+    ) -> StratisResult<SetDeleteAction<DevUuid>> {
+        Ok(SetDeleteAction::new(
+            self.cache_devs.drain().map(|(uuid, _)| uuid).collect(),
+        ))
+    }
+
+    fn cache_config(&self) -> Option<CacheConfig> {
+        if self.has_cache() {
+            Some(self.cache_config.clone())
+        } else {
+            None
+        }
+    }
+
+    fn set_cache_config(&mut self, _pool_name: &str, config: CacheConfig) -> StratisResult<bool> {
+        if !self.has_cache() {
+            return Err(StratisError::Msg(
+                "The pool has no cache, so the cache mode and policy can not be set".to_string(),
+            ));
+        }
+
+        if self.cache_config == config {
+            Ok(false)
+        } else {
+            self.cache_config = config;
+            Ok(true)
+        }
+    }
+
+    fn cache_stats(&self) -> StratisResult<Option<CacheStats>> {
+        if self.has_cache() {
+            Ok(Some(CacheStats {
+                read_hits: 0,
+                read_misses: 0,
+                write_hits: 0,
+                write_misses: 0,
+                dirty: 0,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
     fn create_filesystems<'b>(
         &mut self,
         _pool_name: &str,
         _pool_uuid: PoolUuid,
-        specs: &[(&'b str, Option<Bytes>)],
+        specs: &[FilesystemSpec<'b>],
     ) -> StratisResult<SetCreateAction<(&'b str, FilesystemUuid, Sectors)>> {
         self.check_fs_limit(specs.len())?;
 
         let spec_map = validate_filesystem_size_specs(specs)?;
 
-        spec_map.iter().fold(Ok(()), |res, (name, size)| {
+        spec_map.iter().fold(Ok(()), |res, (name, params)| {
             res.and_then(|()| validate_name(name))
                 .and_then(|()| {
                     if let Some((_, fs)) = self.filesystems.get_by_name(name) {
-                        if fs.size() == *size {
-                            Ok(())
-                        } else {
+                        if fs.size() != params.size {
                             Err(StratisError::Msg(format!(
                                 "Size {} of filesystem {} to be created conflicts with size {} for existing filesystem",
-                                size,
+                                params.size,
                                 name,
                                 fs.size()
                             )))
+                        } else if fs.fs_type() != params.fs_type {
+                            Err(StratisError::Msg(format!(
+                                "Type {} of filesystem {} to be created conflicts with type {} for existing filesystem",
+                                params.fs_type,
+                                name,
+                                fs.fs_type()
+                            )))
+                        } else {
+                            Ok(())
                         }
                     } else {
                         Ok(())
@@ -246,13 +398,19 @@ impl Pool for SimPool {
         })?;
 
         let mut result = Vec::new();
-        for (name, size) in spec_map {
+        for (name, params) in spec_map {
             if !self.filesystems.contains_name(name) {
                 let uuid = FilesystemUuid::new_v4();
-                let new_filesystem = SimFilesystem::new(size);
+                let new_filesystem = SimFilesystem::new(
+                    params.size,
+                    params.size_limit,
+                    params.fs_type,
+                    params.options.mount_options,
+                    None,
+                );
                 self.filesystems
                     .insert(Name::new((name).to_owned()), uuid, new_filesystem);
-                result.push((name, uuid, size));
+                result.push((name, uuid, params.size));
             }
         }
 
@@ -291,18 +449,17 @@ impl Pool for SimPool {
 
         let filtered_device_pairs: Vec<_> = devices
             .iter()
-            .map(|p| {
-                SimDev::new(
-                    p,
-                    match tier {
-                        BlockDevTier::Data => encryption_info.as_ref(),
-                        BlockDevTier::Cache => None,
-                    },
-                )
+            .map(|p| match tier {
+                BlockDevTier::Data => SimDev::new(p, self.integrity, encryption_info.as_ref()),
+                BlockDevTier::Cache => SimDev::new(p, false, None),
             })
             .filter(|(_, sd)| !filter.contains(&sd.devnode()))
             .collect();
 
+        if tier == BlockDevTier::Data {
+            validate_redundancy(self.redundancy, filtered_device_pairs.len())?;
+        }
+
         let ret_uuids = filtered_device_pairs
             .iter()
             .map(|&(uuid, _)| uuid)
@@ -317,6 +474,97 @@ impl Pool for SimPool {
         Ok(SetCreateAction::new(ret_uuids))
     }
 
+    fn remove_blockdevs(
+        &mut self,
+        pool_uuid: PoolUuid,
+        _pool_name: &str,
+        devices: &[DevUuid],
+        tier: BlockDevTier,
+    ) -> StratisResult<SetDeleteAction<DevUuid>> {
+        let (devs, other_devs) = match tier {
+            BlockDevTier::Data => (&mut self.block_devs, &self.cache_devs),
+            BlockDevTier::Cache => (&mut self.cache_devs, &self.block_devs),
+        };
+
+        if let Some(uuid) = devices.iter().find(|u| other_devs.contains_key(u)) {
+            return Err(StratisError::Msg(format!(
+                "Device with UUID {uuid} belongs to a different tier of pool with UUID {pool_uuid} than the one specified"
+            )));
+        }
+
+        if tier == BlockDevTier::Data && self.redundancy != Redundancy::None {
+            return Err(StratisError::Msg(format!(
+                "Removing devices is not supported for pools with a mirrored data tier; the data tier of pool with UUID {pool_uuid} has redundancy {}",
+                self.redundancy
+            )));
+        }
+
+        let to_remove = devices
+            .iter()
+            .filter(|u| devs.contains_key(u))
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        if to_remove.is_empty() {
+            return Ok(SetDeleteAction::empty());
+        }
+
+        if to_remove.len() == devs.len() {
+            return Err(StratisError::Msg(format!(
+                "Removing the requested devices would leave no devices in the tier of pool with UUID {pool_uuid}"
+            )));
+        }
+
+        for uuid in to_remove.iter() {
+            devs.remove(uuid);
+        }
+        Ok(SetDeleteAction::new(to_remove.into_iter().collect()))
+    }
+
+    fn replace_blockdev(
+        &mut self,
+        _pool_name: &str,
+        pool_uuid: PoolUuid,
+        old: DevUuid,
+        new: &Path,
+    ) -> StratisResult<CreateAction<DevUuid>> {
+        validate_paths(&[new])?;
+
+        if self.cache_devs.contains_key(&old) {
+            return Err(StratisError::Msg(
+                "Replacing devices in the cache tier is not supported".to_string(),
+            ));
+        }
+        if !self.block_devs.contains_key(&old) {
+            return Err(StratisError::Msg(format!(
+                "No device with UUID {old} found in pool with UUID {pool_uuid}"
+            )));
+        }
+        if self.redundancy != Redundancy::None {
+            return Err(StratisError::Msg(format!(
+                "Replacing devices is not supported for pools with a mirrored data tier; the data tier of pool with UUID {pool_uuid} has redundancy {}",
+                self.redundancy
+            )));
+        }
+        if self
+            .block_devs
+            .values()
+            .chain(self.cache_devs.values())
+            .any(|bd| bd.devnode() == new)
+        {
+            return Err(StratisError::Msg(format!(
+                "Device {} is already in use by pool with UUID {pool_uuid}",
+                new.display()
+            )));
+        }
+
+        let encryption_info = pool_enc_to_enc!(self.encryption_info());
+        let (new_uuid, new_dev) = SimDev::new(new, self.integrity, encryption_info.as_ref());
+        self.block_devs.remove(&old);
+        self.block_devs.insert(new_uuid, new_dev);
+        Ok(CreateAction::Created(new_uuid))
+    }
+
     fn bind_clevis(
         &mut self,
         pin: &str,
@@ -478,6 +726,97 @@ impl Pool for SimPool {
         }
     }
 
+    fn add_passphrase_binding(
+        &mut self,
+        name: &str,
+        key_desc: &KeyDescription,
+    ) -> StratisResult<CreateAction<Key>> {
+        validate_name(name)?;
+        let bindings = self.passphrase_bindings.as_mut().ok_or_else(|| {
+            StratisError::Msg("Requested pool does not appear to be encrypted".to_string())
+        })?;
+
+        match bindings.get(name) {
+            Some(kd) if kd == key_desc => Ok(CreateAction::Identity),
+            Some(kd) => Err(StratisError::Msg(format!(
+                "This pool is already bound under name {} with key description {}; \
+                    this differs from the requested key description {}",
+                name,
+                kd.as_application_str(),
+                key_desc.as_application_str(),
+            ))),
+            None => {
+                bindings.insert(name.to_string(), key_desc.clone());
+                Ok(CreateAction::Created(Key))
+            }
+        }
+    }
+
+    fn remove_passphrase_binding(&mut self, name: &str) -> StratisResult<DeleteAction<Key>> {
+        let bindings = self.passphrase_bindings.as_mut().ok_or_else(|| {
+            StratisError::Msg("Requested pool does not appear to be encrypted".to_string())
+        })?;
+
+        Ok(if bindings.remove(name).is_some() {
+            DeleteAction::Deleted(Key)
+        } else {
+            DeleteAction::Identity
+        })
+    }
+
+    fn encrypt_pool(
+        &mut self,
+        _name: &Name,
+        _pool_uuid: PoolUuid,
+        encryption_info: &EncryptionInfo,
+    ) -> StratisResult<CreateAction<Encryption>> {
+        if let Some(current) = pool_enc_to_enc!(self.encryption_info()) {
+            return if &current == encryption_info {
+                Ok(CreateAction::Identity)
+            } else {
+                Err(StratisError::Msg(
+                    "Pool is already encrypted with a different configuration than the one requested"
+                        .to_string(),
+                ))
+            };
+        }
+
+        if !self.cache_devs.is_empty() {
+            return Err(StratisError::Msg(
+                "Encrypting a pool with a cache is not supported".to_string(),
+            ));
+        }
+        if self.redundancy != Redundancy::None {
+            return Err(StratisError::Msg(
+                "Encrypting a pool with a mirrored data tier is not supported".to_string(),
+            ));
+        }
+        if self.integrity {
+            return Err(StratisError::Msg(
+                "Encrypting a pool with integrity protection is not supported".to_string(),
+            ));
+        }
+
+        self.block_devs
+            .iter_mut()
+            .for_each(|(_, bd)| bd.set_encryption_info(encryption_info));
+        self.encryption_config = Some(effective_encryption_config(None));
+        self.passphrase_bindings = Some(PassphraseBindings::new());
+        Ok(CreateAction::Created(Encryption))
+    }
+
+    // The sim engine does not store volume keys, so the rotation completes
+    // immediately.
+    fn rotate_volume_key(&mut self, _pool_uuid: PoolUuid) -> StratisResult<RegenAction> {
+        if self.encryption_info().is_none() {
+            Err(StratisError::Msg(
+                "Requested pool does not appear to be encrypted".to_string(),
+            ))
+        } else {
+            Ok(RegenAction)
+        }
+    }
+
     fn destroy_filesystems(
         &mut self,
         _pool_name: &str,
@@ -489,6 +828,7 @@ impl Pool for SimPool {
                 removed.push(uuid);
             }
         }
+        self.unset_origins(&removed);
         Ok(SetDeleteAction::new(removed))
     }
 
@@ -539,7 +879,15 @@ impl Pool for SimPool {
                         return Ok(CreateAction::Identity);
                     }
                 }
-                SimFilesystem::new(filesystem.size())
+                let mut snapshot = SimFilesystem::new(
+                    filesystem.size(),
+                    filesystem.size_limit(),
+                    filesystem.fs_type(),
+                    filesystem.mount_options().map(|o| o.to_owned()),
+                    Some(origin_uuid),
+                );
+                snapshot.set_extend_config(filesystem.extend_config());
+                snapshot
             }
             None => {
                 return Err(StratisError::Msg(origin_uuid.to_string()));
@@ -558,6 +906,148 @@ impl Pool for SimPool {
         )))
     }
 
+    fn set_fs_size_limit(
+        &mut self,
+        _pool_name: &str,
+        uuid: FilesystemUuid,
+        size_limit: Option<Bytes>,
+    ) -> StratisResult<RenameAction<FilesystemUuid>> {
+        match self.filesystems.get_mut_by_uuid(uuid) {
+            Some((name, fs)) => {
+                let size_limit = validate_fs_size_limit(&name, fs.size(), size_limit)?;
+                if fs.set_size_limit(size_limit) {
+                    Ok(RenameAction::Renamed(uuid))
+                } else {
+                    Ok(RenameAction::Identity)
+                }
+            }
+            None => Ok(RenameAction::NoSource),
+        }
+    }
+
+    fn set_fs_extend_config(
+        &mut self,
+        _pool_name: &str,
+        uuid: FilesystemUuid,
+        config: Option<FsExtendConfig>,
+    ) -> StratisResult<RenameAction<FilesystemUuid>> {
+        if let Some(ref config) = config {
+            validate_fs_extend_config(config)?;
+        }
+        match self.filesystems.get_mut_by_uuid(uuid) {
+            Some((_, fs)) => {
+                if fs.set_extend_config(config) {
+                    Ok(RenameAction::Renamed(uuid))
+                } else {
+                    Ok(RenameAction::Identity)
+                }
+            }
+            None => Ok(RenameAction::NoSource),
+        }
+    }
+
+    fn set_snapshot_policies(
+        &mut self,
+        _pool_name: &str,
+        uuid: FilesystemUuid,
+        policies: &[SnapshotPolicy],
+    ) -> StratisResult<RenameAction<FilesystemUuid>> {
+        validate_snapshot_policies(policies)?;
+        match self.filesystems.get_mut_by_uuid(uuid) {
+            Some((_, fs)) => {
+                if fs.set_snapshot_policies(policies) {
+                    Ok(RenameAction::Renamed(uuid))
+                } else {
+                    Ok(RenameAction::Identity)
+                }
+            }
+            None => Ok(RenameAction::NoSource),
+        }
+    }
+
+    fn revert_filesystem(
+        &mut self,
+        _pool_name: &str,
+        _pool_uuid: PoolUuid,
+        origin: FilesystemUuid,
+        snapshot: FilesystemUuid,
+    ) -> StratisResult<DeleteAction<FilesystemUuid>> {
+        if origin == snapshot {
+            return Err(StratisError::Msg(
+                "A filesystem can not be reverted to itself".into(),
+            ));
+        }
+        if self.filesystems.get_by_uuid(origin).is_none() {
+            return Err(StratisError::Msg(format!(
+                "Filesystem with UUID {origin} could not be found"
+            )));
+        }
+
+        let (_, snapshot_fs) = self.filesystems.remove_by_uuid(snapshot).ok_or_else(|| {
+            StratisError::Msg(format!(
+                "Filesystem with UUID {snapshot} could not be found"
+            ))
+        })?;
+        self.filesystems
+            .get_mut_by_uuid(origin)
+            .expect("checked above")
+            .1
+            .revert(&snapshot_fs);
+        self.unset_origins(&[snapshot]);
+        Ok(DeleteAction::Deleted(snapshot))
+    }
+
+    fn send_filesystem(
+        &self,
+        fs_uuid: FilesystemUuid,
+        parent: Option<FilesystemUuid>,
+        _stream: File,
+    ) -> StratisResult<Bytes> {
+        for uuid in std::iter::once(fs_uuid).chain(parent) {
+            if self.filesystems.get_by_uuid(uuid).is_none() {
+                return Err(StratisError::Msg(format!(
+                    "Filesystem with UUID {uuid} not found"
+                )));
+            }
+        }
+        Ok(Bytes(0))
+    }
+
+    fn receive_filesystem(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        fs_name: &str,
+        mut stream: File,
+    ) -> StratisResult<FilesystemUuid> {
+        io::copy(&mut stream, &mut io::sink())?;
+        if self.filesystems.contains_name(fs_name) {
+            return Err(StratisError::Msg(format!(
+                "Filesystem {fs_name} already exists"
+            )));
+        }
+        self.create_filesystems(pool_name, pool_uuid, &[FilesystemSpec::new(fs_name)])?
+            .changed()
+            .and_then(|mut created| created.pop())
+            .map(|(_, uuid, _)| uuid)
+            .ok_or_else(|| StratisError::Msg(format!("Filesystem {fs_name} was not created")))
+    }
+
+    fn receive_filesystem_delta(
+        &mut self,
+        _pool_name: &str,
+        fs_uuid: FilesystemUuid,
+        mut stream: File,
+    ) -> StratisResult<Bytes> {
+        io::copy(&mut stream, &mut io::sink())?;
+        if self.filesystems.get_by_uuid(fs_uuid).is_none() {
+            return Err(StratisError::Msg(format!(
+                "Filesystem with UUID {fs_uuid} not found"
+            )));
+        }
+        Ok(Bytes(0))
+    }
+
     fn total_physical_size(&self) -> Sectors {
         // We choose to make our pools very big, and we can change that
         // if it is inconvenient.
@@ -646,10 +1136,55 @@ impl Pool for SimPool {
         ))
     }
 
+    fn set_blockdev_tags(
+        &mut self,
+        _pool_name: &str,
+        uuid: DevUuid,
+        tags: &[String],
+    ) -> StratisResult<RenameAction<DevUuid>> {
+        validate_blockdev_tags(tags)?;
+        Ok(self.get_mut_blockdev_internal(uuid).map_or_else(
+            || RenameAction::NoSource,
+            |(_, b)| {
+                if b.set_tags(tags) {
+                    RenameAction::Renamed(uuid)
+                } else {
+                    RenameAction::Identity
+                }
+            },
+        ))
+    }
+
+    fn allocation_config(&self) -> AllocationConfig {
+        self.allocation_config.clone()
+    }
+
+    fn set_allocation_config(
+        &mut self,
+        _pool_name: &str,
+        config: AllocationConfig,
+    ) -> StratisResult<bool> {
+        validate_allocation_config(&config)?;
+        if self.allocation_config == config {
+            Ok(false)
+        } else {
+            self.allocation_config = config;
+            Ok(true)
+        }
+    }
+
     fn has_cache(&self) -> bool {
         !self.cache_devs.is_empty()
     }
 
+    fn redundancy(&self) -> Redundancy {
+        self.redundancy
+    }
+
+    fn has_integrity(&self) -> bool {
+        self.integrity
+    }
+
     fn is_encrypted(&self) -> bool {
         self.datadevs_encrypted()
     }
@@ -658,6 +1193,14 @@ impl Pool for SimPool {
         self.encryption_info()
     }
 
+    fn encryption_config(&self) -> Option<MaybeInconsistent<EncryptionConfig>> {
+        self.encryption_config.clone().map(MaybeInconsistent::No)
+    }
+
+    fn passphrase_bindings(&self) -> Option<MaybeInconsistent<PassphraseBindings>> {
+        self.passphrase_bindings.clone().map(MaybeInconsistent::No)
+    }
+
     fn avail_actions(&self) -> ActionAvailability {
         ActionAvailability::Full
     }
@@ -690,6 +1233,50 @@ impl Pool for SimPool {
         false
     }
 
+    fn discard_passdown(&self) -> bool {
+        self.discard_passdown
+    }
+
+    fn set_discard_passdown(&mut self, _pool_name: &str, enabled: bool) -> StratisResult<bool> {
+        let changed = self.discard_passdown != enabled;
+        self.discard_passdown = enabled;
+        Ok(changed)
+    }
+
+    fn default_fs_extend_config(&self) -> FsExtendConfig {
+        self.fs_extend_config
+    }
+
+    fn set_default_fs_extend_config(
+        &mut self,
+        _pool_name: &str,
+        config: FsExtendConfig,
+    ) -> StratisResult<bool> {
+        validate_fs_extend_config(&config)?;
+        let changed = self.fs_extend_config != config;
+        self.fs_extend_config = config;
+        Ok(changed)
+    }
+
+    fn data_block_size(&self) -> Sectors {
+        self.data_block_size
+    }
+
+    fn thin_pool_extend_policy(&self) -> ThinPoolExtendPolicy {
+        self.extend_policy
+    }
+
+    fn set_thin_pool_extend_policy(
+        &mut self,
+        _pool_name: &str,
+        policy: ThinPoolExtendPolicy,
+    ) -> StratisResult<bool> {
+        validate_thin_pool_extend_policy(&policy, self.data_block_size)?;
+        let changed = self.extend_policy != policy;
+        self.extend_policy = policy;
+        Ok(changed)
+    }
+
     fn grow_physical(
         &mut self,
         _: &Name,
@@ -706,8 +1293,12 @@ mod tests {
     use std::path::Path;
 
     use crate::engine::{
+        engine::BlockDev,
         sim_engine::SimEngine,
-        types::{EngineAction, PoolIdentifier},
+        types::{
+            AllocationPolicy, AllocationPurpose, CacheMode, EngineAction, FilesystemType,
+            FsCreateOptions, FsExtendIncrement, MkfsOptions, PoolCreateOptions, PoolIdentifier,
+        },
         Engine,
     };
 
@@ -721,7 +1312,7 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
@@ -741,14 +1332,14 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
         .unwrap();
         let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
         let infos = pool
-            .create_filesystems(pool_name, uuid, &[("old_name", None)])
+            .create_filesystems(pool_name, uuid, &[FilesystemSpec::new("old_name")])
             .unwrap()
             .changed()
             .unwrap();
@@ -769,14 +1360,18 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
         .unwrap();
         let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
         let results = pool
-            .create_filesystems(pool_name, uuid, &[(old_name, None), (new_name, None)])
+            .create_filesystems(
+                pool_name,
+                uuid,
+                &[FilesystemSpec::new(old_name), FilesystemSpec::new(new_name)],
+            )
             .unwrap()
             .changed()
             .unwrap();
@@ -796,7 +1391,7 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
@@ -816,7 +1411,7 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
@@ -836,7 +1431,7 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
@@ -856,14 +1451,14 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
         .unwrap();
         let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
         let fs_results = pool
-            .create_filesystems(pool_name, uuid, &[("fs_name", None)])
+            .create_filesystems(pool_name, uuid, &[FilesystemSpec::new("fs_name")])
             .unwrap()
             .changed()
             .unwrap();
@@ -875,39 +1470,484 @@ mod tests {
     }
 
     #[test]
-    /// Creating an empty list of filesystems should succeed, always
-    fn create_fs_none() {
+    /// Reverting a filesystem to a snapshot removes the snapshot and keeps
+    /// the origin.
+    fn revert_fs_snapshot() {
         let engine = SimEngine::default();
         let pool_name = "pool_name";
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
         .unwrap();
         let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
-        let fs = pool.create_filesystems(pool_name, uuid, &[]).unwrap();
-        assert!(!fs.is_changed())
-    }
-
-    #[test]
-    /// Creating a non-empty list of filesystems always succeeds.
-    fn create_fs_some() {
-        let engine = SimEngine::default();
-        let pool_name = "pool_name";
-        let uuid = test_async!(engine.create_pool(
-            pool_name,
-            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+        let fs_uuid = pool
+            .create_filesystems(pool_name, uuid, &[FilesystemSpec::new("fs_name")])
+            .unwrap()
+            .changed()
+            .unwrap()[0]
+            .1;
+        let snapshot_uuid = match pool
+            .snapshot_filesystem(pool_name, uuid, fs_uuid, "snapshot_name")
+            .unwrap()
+        {
+            CreateAction::Created((snapshot_uuid, _)) => snapshot_uuid,
+            CreateAction::Identity => panic!("snapshot should have been created"),
+        };
+        assert_matches!(
+            pool.revert_filesystem(pool_name, uuid, fs_uuid, fs_uuid),
+            Err(_)
+        );
+        assert_eq!(
+            pool.revert_filesystem(pool_name, uuid, fs_uuid, snapshot_uuid)
+                .unwrap(),
+            DeleteAction::Deleted(snapshot_uuid)
+        );
+        assert!(pool.get_filesystem(snapshot_uuid).is_none());
+        assert!(pool.get_filesystem(fs_uuid).is_some());
+        assert_matches!(
+            pool.revert_filesystem(pool_name, uuid, fs_uuid, snapshot_uuid),
+            Err(_)
+        );
+    }
+
+    #[test]
+    /// Receiving a filesystem creates it, unless a filesystem with the same
+    /// name already exists; sending or receiving into an unknown filesystem
+    /// fails.
+    fn send_receive_fs() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let fs_uuid = pool
+            .create_filesystems(pool_name, uuid, &[FilesystemSpec::new("fs_name")])
+            .unwrap()
+            .changed()
+            .unwrap()[0]
+            .1;
+        let stream = || tempfile::tempfile().unwrap();
+
+        assert_eq!(
+            pool.send_filesystem(fs_uuid, None, stream()).unwrap(),
+            Bytes(0)
+        );
+        assert_matches!(
+            pool.send_filesystem(fs_uuid, Some(FilesystemUuid::new_v4()), stream()),
+            Err(_)
+        );
+
+        let received_uuid = pool
+            .receive_filesystem(pool_name, uuid, "received", stream())
+            .unwrap();
+        assert!(pool.get_filesystem(received_uuid).is_some());
+        assert_matches!(
+            pool.receive_filesystem(pool_name, uuid, "received", stream()),
+            Err(_)
+        );
+
+        assert!(pool
+            .receive_filesystem_delta(pool_name, received_uuid, stream())
+            .is_ok());
+        assert_matches!(
+            pool.receive_filesystem_delta(pool_name, FilesystemUuid::new_v4(), stream()),
+            Err(_)
+        );
+    }
+
+    #[test]
+    /// A snapshot records its origin until the origin is destroyed.
+    fn snapshot_origin() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let fs_uuid = pool
+            .create_filesystems(pool_name, uuid, &[FilesystemSpec::new("fs_name")])
+            .unwrap()
+            .changed()
+            .unwrap()[0]
+            .1;
+        assert_eq!(pool.get_filesystem(fs_uuid).unwrap().1.origin(), None);
+        let snapshot_uuid = match pool
+            .snapshot_filesystem(pool_name, uuid, fs_uuid, "snapshot_name")
+            .unwrap()
+        {
+            CreateAction::Created((snapshot_uuid, _)) => snapshot_uuid,
+            CreateAction::Identity => panic!("snapshot should have been created"),
+        };
+        assert_eq!(
+            pool.get_filesystem(snapshot_uuid).unwrap().1.origin(),
+            Some(fs_uuid)
+        );
+        pool.destroy_filesystems(pool_name, &[fs_uuid]).unwrap();
+        assert_eq!(pool.get_filesystem(snapshot_uuid).unwrap().1.origin(), None);
+    }
+
+    #[test]
+    /// Setting snapshot policies validates them and reports whether they
+    /// changed.
+    fn set_snapshot_policies() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let fs_uuid = pool
+            .create_filesystems(pool_name, uuid, &[FilesystemSpec::new("fs_name")])
+            .unwrap()
+            .changed()
+            .unwrap()[0]
+            .1;
+        let policies = [
+            SnapshotPolicy {
+                name: "hourly".to_string(),
+                interval: 3600,
+                keep_count: Some(24),
+                keep_age: None,
+            },
+            SnapshotPolicy {
+                name: "daily".to_string(),
+                interval: 86400,
+                keep_count: None,
+                keep_age: Some(7 * 86400),
+            },
+        ];
+        assert_matches!(
+            pool.set_snapshot_policies(pool_name, fs_uuid, &policies),
+            Ok(RenameAction::Renamed(_))
+        );
+        assert_eq!(
+            pool.get_filesystem(fs_uuid).unwrap().1.snapshot_policies(),
+            &policies
+        );
+        assert_matches!(
+            pool.set_snapshot_policies(pool_name, fs_uuid, &policies),
+            Ok(RenameAction::Identity)
+        );
+        assert_matches!(
+            pool.set_snapshot_policies(pool_name, FilesystemUuid::new_v4(), &policies),
+            Ok(RenameAction::NoSource)
+        );
+
+        let mut invalid = policies.to_vec();
+        invalid[1].name = "hourly".to_string();
+        assert!(pool
+            .set_snapshot_policies(pool_name, fs_uuid, &invalid)
+            .is_err());
+        let mut invalid = policies.to_vec();
+        invalid[0].interval = 0;
+        assert!(pool
+            .set_snapshot_policies(pool_name, fs_uuid, &invalid)
+            .is_err());
+        let mut invalid = policies.to_vec();
+        invalid[0].keep_count = Some(0);
+        assert!(pool
+            .set_snapshot_policies(pool_name, fs_uuid, &invalid)
+            .is_err());
+
+        assert_matches!(
+            pool.set_snapshot_policies(pool_name, fs_uuid, &[]),
+            Ok(RenameAction::Renamed(_))
+        );
+        assert!(pool
+            .get_filesystem(fs_uuid)
+            .unwrap()
+            .1
+            .snapshot_policies()
+            .is_empty());
+    }
+
+    #[test]
+    /// Test setting the size limit of a filesystem.
+    fn set_fs_size_limit() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let fs_uuid = pool
+            .create_filesystems(
+                pool_name,
+                uuid,
+                &[FilesystemSpec {
+                    name: "fs_name",
+                    size: Some(Bytes::from(IEC::Gi)),
+                    ..Default::default()
+                }],
+            )
+            .unwrap()
+            .changed()
+            .unwrap()[0]
+            .1;
+        assert_eq!(pool.get_filesystem(fs_uuid).unwrap().1.size_limit(), None);
+
+        assert!(pool
+            .set_fs_size_limit(pool_name, fs_uuid, Some(Bytes::from(IEC::Mi)))
+            .is_err());
+        assert!(pool
+            .set_fs_size_limit(pool_name, fs_uuid, Some(Bytes::from(2 * IEC::Gi + 1)))
+            .is_err());
+
+        assert_matches!(
+            pool.set_fs_size_limit(pool_name, fs_uuid, Some(Bytes::from(2 * IEC::Gi))),
+            Ok(RenameAction::Renamed(_))
+        );
+        assert_eq!(
+            pool.get_filesystem(fs_uuid).unwrap().1.size_limit(),
+            Some(Bytes::from(2 * IEC::Gi).sectors())
+        );
+        assert_matches!(
+            pool.set_fs_size_limit(pool_name, fs_uuid, Some(Bytes::from(2 * IEC::Gi))),
+            Ok(RenameAction::Identity)
+        );
+        assert_matches!(
+            pool.set_fs_size_limit(pool_name, FilesystemUuid::new_v4(), None),
+            Ok(RenameAction::NoSource)
+        );
+        assert_matches!(
+            pool.set_fs_size_limit(pool_name, fs_uuid, None),
+            Ok(RenameAction::Renamed(_))
+        );
+        assert_eq!(pool.get_filesystem(fs_uuid).unwrap().1.size_limit(), None);
+    }
+
+    #[test]
+    /// Test setting the default extension configuration of a pool and the
+    /// extension configuration of a filesystem.
+    fn set_fs_extend_config() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let fs_uuid = pool
+            .create_filesystems(pool_name, uuid, &[FilesystemSpec::new("fs_name")])
+            .unwrap()
+            .changed()
+            .unwrap()[0]
+            .1;
+
+        let config = FsExtendConfig {
+            enabled: true,
+            threshold: 80,
+            increment: FsExtendIncrement::Percent(25),
+        };
+        assert_eq!(pool.default_fs_extend_config(), FsExtendConfig::default());
+        assert!(pool
+            .set_default_fs_extend_config(pool_name, config)
+            .unwrap());
+        assert!(!pool
+            .set_default_fs_extend_config(pool_name, config)
+            .unwrap());
+        assert_eq!(pool.default_fs_extend_config(), config);
+        assert!(pool
+            .set_default_fs_extend_config(
+                pool_name,
+                FsExtendConfig {
+                    threshold: 0,
+                    ..config
+                }
+            )
+            .is_err());
+        assert!(pool
+            .set_default_fs_extend_config(
+                pool_name,
+                FsExtendConfig {
+                    increment: FsExtendIncrement::Absolute(Sectors(0)),
+                    ..config
+                }
+            )
+            .is_err());
+
+        assert_eq!(
+            pool.get_filesystem(fs_uuid).unwrap().1.extend_config(),
+            None
+        );
+        let disabled = FsExtendConfig {
+            enabled: false,
+            ..config
+        };
+        assert_matches!(
+            pool.set_fs_extend_config(pool_name, fs_uuid, Some(disabled)),
+            Ok(RenameAction::Renamed(_))
+        );
+        assert_matches!(
+            pool.set_fs_extend_config(pool_name, fs_uuid, Some(disabled)),
+            Ok(RenameAction::Identity)
+        );
+        assert_eq!(
+            pool.get_filesystem(fs_uuid).unwrap().1.extend_config(),
+            Some(disabled)
+        );
+        assert!(pool
+            .set_fs_extend_config(
+                pool_name,
+                fs_uuid,
+                Some(FsExtendConfig {
+                    increment: FsExtendIncrement::Percent(0),
+                    ..config
+                })
+            )
+            .is_err());
+        assert_matches!(
+            pool.set_fs_extend_config(pool_name, FilesystemUuid::new_v4(), None),
+            Ok(RenameAction::NoSource)
+        );
+        assert_matches!(
+            pool.set_fs_extend_config(pool_name, fs_uuid, None),
+            Ok(RenameAction::Renamed(_))
+        );
+        assert_eq!(
+            pool.get_filesystem(fs_uuid).unwrap().1.extend_config(),
+            None
+        );
+    }
+
+    #[test]
+    /// Test creating a pool with a thin pool configuration and changing the
+    /// thin pool extension policy of the pool.
+    fn set_thin_pool_extend_policy() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let paths = strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]);
+        let config = ThinPoolConfig {
+            data_block_size: Some(Bytes::from(256 * IEC::Ki).sectors()),
+            low_water: Some(Bytes::from(IEC::Gi).sectors()),
+            extend_size: None,
+        };
+
+        assert!(test_async!(engine.create_pool(
+            pool_name,
+            paths,
+            PoolCreateOptions {
+                thin_pool_config: Some(&ThinPoolConfig {
+                    data_block_size: Some(Bytes::from(100 * IEC::Ki).sectors()),
+                    ..config
+                }),
+                ..Default::default()
+            }
+        ))
+        .is_err());
+        assert!(test_async!(engine.create_pool(
+            pool_name,
+            paths,
+            PoolCreateOptions {
+                thin_pool_config: Some(&ThinPoolConfig {
+                    low_water: Some(Bytes::from(64 * IEC::Ki).sectors()),
+                    ..config
+                }),
+                ..Default::default()
+            }
+        ))
+        .is_err());
+
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            paths,
+            PoolCreateOptions {
+                thin_pool_config: Some(&config),
+                ..Default::default()
+            }
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+
+        assert_eq!(pool.data_block_size(), Bytes::from(256 * IEC::Ki).sectors());
+        let policy = pool.thin_pool_extend_policy();
+        assert_eq!(policy.low_water, Bytes::from(IEC::Gi).sectors());
+        assert_eq!(policy.extend_size, DEFAULT_EXTEND_POLICY.extend_size);
+
+        let policy = ThinPoolExtendPolicy {
+            low_water: Bytes::from(2 * IEC::Gi).sectors(),
+            extend_size: Bytes::from(4 * IEC::Gi).sectors(),
+        };
+        assert!(pool.set_thin_pool_extend_policy(pool_name, policy).unwrap());
+        assert!(!pool.set_thin_pool_extend_policy(pool_name, policy).unwrap());
+        assert_eq!(pool.thin_pool_extend_policy(), policy);
+        assert!(pool
+            .set_thin_pool_extend_policy(
+                pool_name,
+                ThinPoolExtendPolicy {
+                    extend_size: Sectors(0),
+                    ..policy
+                }
+            )
+            .is_err());
+        assert_eq!(pool.thin_pool_extend_policy(), policy);
+    }
+
+    #[test]
+    /// Creating an empty list of filesystems should succeed, always
+    fn create_fs_none() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let fs = pool.create_filesystems(pool_name, uuid, &[]).unwrap();
+        assert!(!fs.is_changed())
+    }
+
+    #[test]
+    /// Creating a non-empty list of filesystems always succeeds.
+    fn create_fs_some() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
         .unwrap();
         let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
         assert!(match pool
-            .create_filesystems(pool_name, uuid, &[("name", None)])
+            .create_filesystems(pool_name, uuid, &[FilesystemSpec::new("name")])
             .ok()
             .and_then(|fs| fs.changed())
         {
@@ -925,16 +1965,16 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
         .unwrap();
         let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
-        pool.create_filesystems(pool_name, uuid, &[(fs_name, None)])
+        pool.create_filesystems(pool_name, uuid, &[FilesystemSpec::new(fs_name)])
             .unwrap();
         let set_create_action = pool
-            .create_filesystems(pool_name, uuid, &[(fs_name, None)])
+            .create_filesystems(pool_name, uuid, &[FilesystemSpec::new(fs_name)])
             .unwrap();
         assert!(!set_create_action.is_changed());
     }
@@ -948,14 +1988,18 @@ mod tests {
         let uuid = test_async!(engine.create_pool(
             pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
         .unwrap();
         let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
         assert!(match pool
-            .create_filesystems(pool_name, uuid, &[(fs_name, None), (fs_name, None)])
+            .create_filesystems(
+                pool_name,
+                uuid,
+                &[FilesystemSpec::new(fs_name), FilesystemSpec::new(fs_name)]
+            )
             .ok()
             .and_then(|fs| fs.changed())
         {
@@ -965,27 +2009,701 @@ mod tests {
     }
 
     #[test]
-    /// Adding a list of devices to an empty pool should yield list.
-    fn add_device_empty() {
+    /// Creating an ext4 filesystem records its type, which its snapshots
+    /// inherit, and conflicts with a request for an XFS filesystem of the
+    /// same name.
+    fn create_fs_ext4() {
+        let fs_name = "fs_name";
         let engine = SimEngine::default();
+        let pool_name = "pool_name";
         let uuid = test_async!(engine.create_pool(
-            "pool_name",
+            pool_name,
             strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
-            None,
+            PoolCreateOptions::default()
         ))
         .unwrap()
         .changed()
         .unwrap();
-        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
-        let (pool_name, _, pool) = guard.as_mut_tuple();
-        let devices = [Path::new("/s/a"), Path::new("/s/b")];
-        assert!(match pool
-            .add_blockdevs(uuid, &pool_name, &devices, BlockDevTier::Data)
-            .ok()
-            .and_then(|c| c.changed())
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let fs_uuid = pool
+            .create_filesystems(
+                pool_name,
+                uuid,
+                &[FilesystemSpec {
+                    name: fs_name,
+                    fs_type: Some(FilesystemType::Ext4),
+                    ..Default::default()
+                }],
+            )
+            .unwrap()
+            .changed()
+            .unwrap()[0]
+            .1;
+        assert_eq!(
+            pool.get_filesystem(fs_uuid).unwrap().1.fs_type(),
+            FilesystemType::Ext4
+        );
+
+        assert!(pool
+            .create_filesystems(pool_name, uuid, &[FilesystemSpec::new(fs_name)])
+            .is_err());
+        assert!(!pool
+            .create_filesystems(
+                pool_name,
+                uuid,
+                &[FilesystemSpec {
+                    name: fs_name,
+                    fs_type: Some(FilesystemType::Ext4),
+                    ..Default::default()
+                }],
+            )
+            .unwrap()
+            .is_changed());
+
+        let snapshot_uuid = match pool
+            .snapshot_filesystem(pool_name, uuid, fs_uuid, "snapshot_name")
+            .unwrap()
         {
-            Some(devs) => devs.len() == devices.len(),
-            _ => false,
-        });
+            CreateAction::Created((snapshot_uuid, _)) => snapshot_uuid,
+            CreateAction::Identity => panic!("snapshot should have been created"),
+        };
+        assert_eq!(
+            pool.get_filesystem(snapshot_uuid).unwrap().1.fs_type(),
+            FilesystemType::Ext4
+        );
+    }
+
+    #[test]
+    /// Creating a filesystem with options records its recommended mount
+    /// options, which its snapshots inherit; options the filesystem type does
+    /// not support are rejected.
+    fn create_fs_options() {
+        let engine = SimEngine::default();
+        let pool_name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            pool_name,
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+
+        let options = FsCreateOptions {
+            mkfs: MkfsOptions {
+                reflink: Some(true),
+                bigtime: None,
+                inode_size: Some(512),
+                label: Some("database".to_string()),
+            },
+            mount_options: Some("noatime,logbsize=256k".to_string()),
+        };
+        assert!(pool
+            .create_filesystems(
+                pool_name,
+                uuid,
+                &[FilesystemSpec {
+                    name: "ext4_fs",
+                    fs_type: Some(FilesystemType::Ext4),
+                    options: Some(options.clone()),
+                    ..Default::default()
+                }],
+            )
+            .is_err());
+
+        let fs_uuid = pool
+            .create_filesystems(
+                pool_name,
+                uuid,
+                &[FilesystemSpec {
+                    name: "fs_name",
+                    options: Some(options),
+                    ..Default::default()
+                }],
+            )
+            .unwrap()
+            .changed()
+            .unwrap()[0]
+            .1;
+        assert_eq!(
+            pool.get_filesystem(fs_uuid).unwrap().1.mount_options(),
+            Some("noatime,logbsize=256k")
+        );
+
+        let snapshot_uuid = match pool
+            .snapshot_filesystem(pool_name, uuid, fs_uuid, "snapshot_name")
+            .unwrap()
+        {
+            CreateAction::Created((snapshot_uuid, _)) => snapshot_uuid,
+            CreateAction::Identity => panic!("snapshot should have been created"),
+        };
+        assert_eq!(
+            pool.get_filesystem(snapshot_uuid)
+                .unwrap()
+                .1
+                .mount_options(),
+            Some("noatime,logbsize=256k")
+        );
+    }
+
+    #[test]
+    /// Adding a list of devices to an empty pool should yield list.
+    fn add_device_empty() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+        let devices = [Path::new("/s/a"), Path::new("/s/b")];
+        assert!(match pool
+            .add_blockdevs(uuid, &pool_name, &devices, BlockDevTier::Data)
+            .ok()
+            .and_then(|c| c.changed())
+        {
+            Some(devs) => devs.len() == devices.len(),
+            _ => false,
+        });
+    }
+
+    #[test]
+    /// Data devices are added to a mirrored pool only in pairs, and can not
+    /// be removed or replaced.
+    fn raid1_data_devices() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two"]),
+            PoolCreateOptions {
+                redundancy: Redundancy::Raid1,
+                ..Default::default()
+            }
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+
+        assert!(pool
+            .add_blockdevs(
+                uuid,
+                &pool_name,
+                strs_to_paths!(["/dev/three"]),
+                BlockDevTier::Data
+            )
+            .is_err());
+        assert_eq!(
+            pool.add_blockdevs(
+                uuid,
+                &pool_name,
+                strs_to_paths!(["/dev/three", "/dev/four"]),
+                BlockDevTier::Data
+            )
+            .unwrap()
+            .changed()
+            .map(|uuids| uuids.len()),
+            Some(2)
+        );
+
+        let dev_uuids = pool
+            .blockdevs()
+            .iter()
+            .map(|(u, _, _)| *u)
+            .collect::<Vec<_>>();
+        assert!(pool
+            .remove_blockdevs(uuid, &pool_name, &dev_uuids[..2], BlockDevTier::Data)
+            .is_err());
+        assert!(pool
+            .replace_blockdev(&pool_name, uuid, dev_uuids[0], Path::new("/dev/five"))
+            .is_err());
+        assert_eq!(pool.blockdevs().len(), 4);
+    }
+
+    #[test]
+    /// Data devices added to a pool with integrity protection are also
+    /// protected, while cache devices are not.
+    fn integrity_data_devices() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two"]),
+            PoolCreateOptions {
+                integrity: true,
+                ..Default::default()
+            }
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+        assert!(pool.has_integrity());
+
+        pool.add_blockdevs(
+            uuid,
+            &pool_name,
+            strs_to_paths!(["/dev/three"]),
+            BlockDevTier::Data,
+        )
+        .unwrap();
+        pool.init_cache(
+            uuid,
+            &pool_name,
+            strs_to_paths!(["/dev/four"]),
+            &CacheConfig::default(),
+            true,
+        )
+        .unwrap();
+
+        for (_, tier, bd) in pool.blockdevs() {
+            assert_eq!(
+                bd.integrity_mismatches().unwrap(),
+                match tier {
+                    BlockDevTier::Data => Some(0),
+                    BlockDevTier::Cache => None,
+                }
+            );
+        }
+    }
+
+    #[test]
+    /// Removing devices removes only the requested devices and refuses to
+    /// remove every device in a tier.
+    fn remove_device() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two", "/dev/three"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+        let dev_uuids = pool
+            .blockdevs()
+            .iter()
+            .map(|(u, _, _)| *u)
+            .collect::<Vec<_>>();
+
+        assert!(pool
+            .remove_blockdevs(uuid, &pool_name, &dev_uuids, BlockDevTier::Data)
+            .is_err());
+        assert_matches!(
+            pool.remove_blockdevs(uuid, &pool_name, &[DevUuid::new_v4()], BlockDevTier::Data)
+                .map(|a| a.is_changed()),
+            Ok(false)
+        );
+        assert_eq!(
+            pool.remove_blockdevs(uuid, &pool_name, &dev_uuids[..1], BlockDevTier::Data)
+                .unwrap()
+                .changed(),
+            Some(vec![dev_uuids[0]])
+        );
+        assert_eq!(pool.blockdevs().len(), 2);
+
+        let cache_uuids = pool
+            .init_cache(
+                uuid,
+                &pool_name,
+                strs_to_paths!(["/dev/four", "/dev/five"]),
+                &CacheConfig::default(),
+                false,
+            )
+            .unwrap()
+            .changed()
+            .unwrap();
+        assert!(pool
+            .remove_blockdevs(uuid, &pool_name, &cache_uuids[..1], BlockDevTier::Data)
+            .is_err());
+        assert!(pool
+            .remove_blockdevs(uuid, &pool_name, &cache_uuids, BlockDevTier::Cache)
+            .is_err());
+        assert_eq!(
+            pool.remove_blockdevs(uuid, &pool_name, &cache_uuids[..1], BlockDevTier::Cache)
+                .unwrap()
+                .changed(),
+            Some(vec![cache_uuids[0]])
+        );
+        assert_eq!(pool.blockdevs().len(), 3);
+    }
+
+    #[test]
+    /// Replacing a data device swaps it for a new device, and replacing a
+    /// device that is not in the pool is an error.
+    fn replace_device() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+        let (old_uuid, _, _) = pool.blockdevs()[0];
+
+        assert!(pool
+            .replace_blockdev(&pool_name, uuid, DevUuid::new_v4(), Path::new("/dev/three"))
+            .is_err());
+        assert!(pool
+            .replace_blockdev(&pool_name, uuid, old_uuid, Path::new("/dev/two"))
+            .is_err());
+
+        let new_uuid = pool
+            .replace_blockdev(&pool_name, uuid, old_uuid, Path::new("/dev/three"))
+            .unwrap()
+            .changed()
+            .unwrap();
+        assert!(pool.get_blockdev(old_uuid).is_none());
+        assert_eq!(
+            pool.get_blockdev(new_uuid).unwrap().1.devnode(),
+            Path::new("/dev/three")
+        );
+        assert_eq!(pool.blockdevs().len(), 2);
+    }
+
+    #[test]
+    /// Removing the cache removes all cache devices, and removing the cache
+    /// of a pool without a cache changes nothing.
+    fn remove_cache() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+
+        assert_matches!(
+            pool.remove_cache(uuid, &pool_name).map(|a| a.is_changed()),
+            Ok(false)
+        );
+
+        let cache_uuids = pool
+            .init_cache(
+                uuid,
+                &pool_name,
+                strs_to_paths!(["/dev/three"]),
+                &CacheConfig::default(),
+                false,
+            )
+            .unwrap()
+            .changed()
+            .unwrap();
+        assert!(pool.has_cache());
+
+        assert_eq!(
+            pool.remove_cache(uuid, &pool_name).unwrap().changed(),
+            Some(cache_uuids)
+        );
+        assert!(!pool.has_cache());
+        assert_eq!(pool.blockdevs().len(), 2);
+    }
+
+    #[test]
+    /// Verify that the cache mode and policy chosen at initialization can be
+    /// changed and are reported correctly.
+    fn cache_config() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+
+        assert_eq!(pool.cache_config(), None);
+        assert_matches!(pool.cache_stats(), Ok(None));
+        assert_matches!(
+            pool.set_cache_config(&pool_name, CacheConfig::default()),
+            Err(_)
+        );
+
+        let writeback = CacheConfig {
+            mode: CacheMode::Writeback,
+            policy: "smq".to_string(),
+            policy_args: vec![("migration_threshold".to_string(), "4096".to_string())],
+        };
+        pool.init_cache(
+            uuid,
+            &pool_name,
+            strs_to_paths!(["/dev/three"]),
+            &writeback,
+            false,
+        )
+        .unwrap();
+        assert_eq!(pool.cache_config(), Some(writeback.clone()));
+        assert_matches!(pool.cache_stats(), Ok(Some(_)));
+
+        assert_matches!(pool.set_cache_config(&pool_name, writeback), Ok(false));
+        assert_matches!(
+            pool.set_cache_config(&pool_name, CacheConfig::default()),
+            Ok(true)
+        );
+        assert_eq!(pool.cache_config(), Some(CacheConfig::default()));
+    }
+
+    #[test]
+    /// Blockdev tags are sorted and deduplicated, and an allocation
+    /// configuration may name each purpose only once.
+    fn allocation_config() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+
+        let dev_uuid = pool.blockdevs()[0].0;
+        let tags = vec!["ssd".to_string(), "fast".to_string(), "ssd".to_string()];
+        assert_matches!(
+            pool.set_blockdev_tags(&pool_name, dev_uuid, &tags),
+            Ok(RenameAction::Renamed(_))
+        );
+        assert_eq!(
+            pool.get_blockdev(dev_uuid).unwrap().1.tags(),
+            &["fast".to_string(), "ssd".to_string()]
+        );
+        assert_matches!(
+            pool.set_blockdev_tags(&pool_name, dev_uuid, &tags),
+            Ok(RenameAction::Identity)
+        );
+        assert_matches!(
+            pool.set_blockdev_tags(&pool_name, dev_uuid, &["not valid".to_string()]),
+            Err(_)
+        );
+
+        assert_eq!(pool.allocation_config(), AllocationConfig::default());
+        let config = AllocationConfig {
+            policy: AllocationPolicy::PreferTagged,
+            tags: vec![(AllocationPurpose::ThinMeta, "fast".to_string())],
+        };
+        assert_matches!(
+            pool.set_allocation_config(&pool_name, config.clone()),
+            Ok(true)
+        );
+        assert_matches!(
+            pool.set_allocation_config(&pool_name, config.clone()),
+            Ok(false)
+        );
+        assert_eq!(pool.allocation_config(), config);
+        assert_matches!(
+            pool.set_allocation_config(
+                &pool_name,
+                AllocationConfig {
+                    policy: AllocationPolicy::PreferTagged,
+                    tags: vec![
+                        (AllocationPurpose::ThinMeta, "fast".to_string()),
+                        (AllocationPurpose::ThinMeta, "ssd".to_string()),
+                    ],
+                }
+            ),
+            Err(_)
+        );
+    }
+
+    #[test]
+    /// Encrypting an unencrypted pool sets the requested encryption info on
+    /// all of its block devices; encrypting it again with the same
+    /// configuration is a no-op, with a different one an error.
+    fn encrypt_pool() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+
+        assert!(!pool.is_encrypted());
+
+        let key_desc = KeyDescription::try_from("test-key".to_string()).unwrap();
+        let encryption_info = EncryptionInfo::KeyDesc(key_desc);
+        assert_matches!(
+            pool.encrypt_pool(&pool_name, uuid, &encryption_info),
+            Ok(CreateAction::Created(_))
+        );
+        assert!(pool.is_encrypted());
+        assert!(pool
+            .blockdevs()
+            .iter()
+            .all(|(_, _, bd)| bd.encryption_info() == Some(&encryption_info)));
+
+        assert_matches!(
+            pool.encrypt_pool(&pool_name, uuid, &encryption_info),
+            Ok(CreateAction::Identity)
+        );
+        assert_matches!(
+            pool.encrypt_pool(
+                &pool_name,
+                uuid,
+                &EncryptionInfo::ClevisInfo(("tang".to_string(), Value::Null))
+            ),
+            Err(_)
+        );
+    }
+
+    #[test]
+    /// Test that the volume key can only be rotated on an encrypted pool.
+    fn rotate_volume_key() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+
+        assert_matches!(pool.rotate_volume_key(uuid), Err(_));
+
+        let key_desc = KeyDescription::try_from("test-key".to_string()).unwrap();
+        pool.encrypt_pool(&pool_name, uuid, &EncryptionInfo::KeyDesc(key_desc))
+            .unwrap();
+        assert_matches!(pool.rotate_volume_key(uuid), Ok(RegenAction));
+        assert_eq!(engine.reencryption_progress(uuid), None);
+    }
+
+    #[test]
+    /// Test that an encrypted pool reports the encryption settings with which
+    /// it was created, with the defaults filled in, and that the settings are
+    /// rejected for an unencrypted pool.
+    fn encryption_config() {
+        let engine = SimEngine::default();
+        let config = EncryptionConfig {
+            cipher: Some("aes-xts-plain64".to_string()),
+            key_size: Some(256),
+            pbkdf: Some(Pbkdf::Pbkdf2),
+            pbkdf_memory: None,
+            pbkdf_iterations: Some(100_000),
+            sector_size: None,
+        };
+        assert_matches!(
+            test_async!(engine.create_pool(
+                "pool_name",
+                strs_to_paths!(["/dev/one"]),
+                PoolCreateOptions {
+                    encryption_config: Some(&config),
+                    ..Default::default()
+                }
+            )),
+            Err(_)
+        );
+
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one", "/dev/two"]),
+            PoolCreateOptions {
+                encryption_info: Some(&EncryptionInfo::ClevisInfo((
+                    "tpm2".to_string(),
+                    Value::Object(Map::new())
+                ))),
+                encryption_config: Some(&config),
+                ..Default::default()
+            }
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let guard = test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (_, _, pool) = guard.as_tuple();
+
+        assert_eq!(
+            pool.encryption_config(),
+            Some(MaybeInconsistent::No(EncryptionConfig {
+                sector_size: Some(DEFAULT_SECTOR_SIZE),
+                ..config
+            }))
+        );
+    }
+
+    #[test]
+    /// Test that named passphrase bindings can be added and removed
+    /// independently of each other on an encrypted pool only.
+    fn passphrase_bindings() {
+        let engine = SimEngine::default();
+        let uuid = test_async!(engine.create_pool(
+            "pool_name",
+            strs_to_paths!(["/dev/one"]),
+            PoolCreateOptions::default()
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        let (pool_name, _, pool) = guard.as_mut_tuple();
+
+        let recovery = KeyDescription::try_from("recovery-key".to_string()).unwrap();
+        let operator = KeyDescription::try_from("operator-key".to_string()).unwrap();
+        assert_eq!(pool.passphrase_bindings(), None);
+        assert_matches!(pool.add_passphrase_binding("recovery", &recovery), Err(_));
+
+        pool.encrypt_pool(&pool_name, uuid, &EncryptionInfo::KeyDesc(recovery.clone()))
+            .unwrap();
+        assert_eq!(
+            pool.passphrase_bindings(),
+            Some(MaybeInconsistent::No(PassphraseBindings::new()))
+        );
+
+        assert_matches!(
+            pool.add_passphrase_binding("recovery", &recovery),
+            Ok(CreateAction::Created(Key))
+        );
+        assert_matches!(
+            pool.add_passphrase_binding("operator", &operator),
+            Ok(CreateAction::Created(Key))
+        );
+        assert_matches!(
+            pool.add_passphrase_binding("operator", &operator),
+            Ok(CreateAction::Identity)
+        );
+        assert_matches!(pool.add_passphrase_binding("operator", &recovery), Err(_));
+
+        assert_matches!(
+            pool.remove_passphrase_binding("operator"),
+            Ok(DeleteAction::Deleted(Key))
+        );
+        assert_matches!(
+            pool.remove_passphrase_binding("operator"),
+            Ok(DeleteAction::Identity)
+        );
+        assert_eq!(
+            pool.passphrase_bindings(),
+            Some(MaybeInconsistent::No(PassphraseBindings::from([(
+                "recovery".to_string(),
+                recovery
+            )])))
+        );
     }
 }
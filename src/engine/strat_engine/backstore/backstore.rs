@@ -4,25 +4,41 @@
 
 // Code to handle the backing store of a pool.
 
-use std::{cmp, collections::HashMap, fs, path::PathBuf};
+use std::{
+    cmp,
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
 
 use chrono::{DateTime, Utc};
+use libc::c_uint;
 use serde_json::Value;
 use tempfile::TempDir;
 
-use devicemapper::{CacheDev, Device, DmDevice, LinearDev, Sectors};
+use devicemapper::{
+    device_exists, Bytes, CacheDev, CacheDevStatus, CacheDevWorkingStatus, DevId, Device, DmDevice,
+    DmFlags, DmName, DmOptions, LinearDev, LinearDevTargetParams, Sectors, TargetLine, TargetTable,
+};
 
 use crate::{
     engine::{
-        shared::gather_encryption_info,
+        shared::{gather_encryption_info, gather_passphrase_bindings, validate_redundancy},
         strat_engine::{
             backstore::{
-                blockdev::StratBlockDev,
+                blockdev::{BlockDevEncryption, StratBlockDev},
                 blockdevmgr::BlockDevMgr,
                 cache_tier::CacheTier,
-                crypt::{back_up_luks_header, interpret_clevis_config, restore_luks_header},
+                crypt::{
+                    back_up_luks_header, interpret_clevis_config, restore_luks_header, CryptHandle,
+                },
                 data_tier::DataTier,
                 devices::UnownedDevices,
+                migration::Migration,
+                mirror::{LegTables, MirrorDev},
                 shared::BlockSizeSummary,
                 transaction::RequestTransaction,
             },
@@ -35,8 +51,10 @@ use crate::{
             writing::wipe_sectors,
         },
         types::{
-            ActionAvailability, BlockDevTier, DevUuid, EncryptionInfo, KeyDescription, Name,
-            PoolEncryptionInfo, PoolUuid,
+            ActionAvailability, AllocationConfig, AllocationPurpose, BlockDevTier, CacheConfig,
+            CacheMode, CacheStats, DevUuid, EncryptionConfig, EncryptionInfo, KeyDescription,
+            MaybeInconsistent, Name, PassphraseBindings, PoolCreateOptions, PoolEncryptionInfo,
+            PoolUuid, Redundancy, ReencryptionProgress, ReplaceProgress,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -46,6 +64,139 @@ use crate::{
 /// typical size.
 const CACHE_BLOCK_SIZE: Sectors = Sectors(2048); // 1024 KiB
 
+/// The time after which writing back the dirty blocks of a cache is given up
+/// if no dirty block has been written back.
+const CACHE_CLEAN_TIMEOUT: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    static ref REPLACEMENTS_IN_PROGRESS: Mutex<HashMap<PoolUuid, ReplaceProgress>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Get the progress of the block device replacement currently in progress
+/// in the pool with the given UUID, if any.
+pub fn replace_progress(pool_uuid: PoolUuid) -> Option<ReplaceProgress> {
+    REPLACEMENTS_IN_PROGRESS
+        .lock()
+        .expect("Should not panic")
+        .get(&pool_uuid)
+        .cloned()
+}
+
+lazy_static! {
+    static ref REENCRYPTIONS_IN_PROGRESS: Mutex<HashMap<PoolUuid, ReencryptionProgress>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Get the progress of the volume key rotation currently in progress in the
+/// pool with the given UUID, if any.
+pub fn reencryption_progress(pool_uuid: PoolUuid) -> Option<ReencryptionProgress> {
+    REENCRYPTIONS_IN_PROGRESS
+        .lock()
+        .expect("Should not panic")
+        .get(&pool_uuid)
+        .cloned()
+}
+
+/// The reencryption of the data of a device in the background.
+enum Reencryption {
+    /// The rotation of the volume key of the device.
+    VolumeKeyRotation(CryptHandle),
+    /// The encryption in place of the device, which was initialized with
+    /// the given temporary keyslot.
+    Encryption(CryptHandle, c_uint),
+}
+
+impl Reencryption {
+    fn handle(&self) -> &CryptHandle {
+        match self {
+            Reencryption::VolumeKeyRotation(handle) => handle,
+            Reencryption::Encryption(handle, _) => handle,
+        }
+    }
+}
+
+/// The encryption in place of the data devices of a pool, begun by
+/// Backstore::encrypt(), whose data has not yet been encrypted.
+pub struct PoolEncryption {
+    pool_uuid: PoolUuid,
+    reencryptions: Vec<Reencryption>,
+}
+
+impl PoolEncryption {
+    /// Encrypt the data of the devices in the background; the progress is
+    /// available through reencryption_progress() while the data is
+    /// encrypted.
+    pub fn spawn(self) -> StratisResult<()> {
+        spawn_reencryption(self.pool_uuid, self.reencryptions)
+    }
+}
+
+/// Reencrypt the data of the given devices in a background thread, one
+/// device after the other. The pool is not locked while the data is
+/// reencrypted, so the progress is recorded separately.
+fn spawn_reencryption(pool_uuid: PoolUuid, reencryptions: Vec<Reencryption>) -> StratisResult<()> {
+    let sizes = reencryptions
+        .iter()
+        .map(|reencryption| reencryption.handle().logical_device_size())
+        .collect::<StratisResult<Vec<_>>>()?;
+
+    {
+        let mut in_progress = REENCRYPTIONS_IN_PROGRESS.lock().expect("Should not panic");
+        if in_progress.contains_key(&pool_uuid) {
+            return Err(StratisError::Msg(format!(
+                "A reencryption is already in progress for pool with UUID {pool_uuid}"
+            )));
+        }
+        in_progress.insert(
+            pool_uuid,
+            ReencryptionProgress {
+                reencrypted: Sectors(0),
+                total: sizes.iter().cloned().sum(),
+            },
+        );
+    }
+
+    thread::spawn(move || {
+        let mut done = Sectors(0);
+        for (reencryption, size) in reencryptions.into_iter().zip(sizes) {
+            let progress = move |reencrypted: Bytes, _: Bytes| {
+                if let Some(progress) = REENCRYPTIONS_IN_PROGRESS
+                    .lock()
+                    .expect("Should not panic")
+                    .get_mut(&pool_uuid)
+                {
+                    progress.reencrypted = done + reencrypted.sectors();
+                }
+            };
+            let res = match reencryption {
+                Reencryption::VolumeKeyRotation(ref handle) => {
+                    handle.resume_volume_key_rotation(progress)
+                }
+                Reencryption::Encryption(ref handle, keyslot) => {
+                    handle.resume_encryption(keyslot, progress)
+                }
+            };
+            if let Err(e) = res {
+                warn!(
+                    "Failed to reencrypt device {} in pool with UUID {}; the reencryption is resumed when a volume key rotation is requested or the pool is started again: {}",
+                    reencryption.handle().luks2_device_path().display(),
+                    pool_uuid,
+                    e
+                );
+            }
+            done += size;
+        }
+        REENCRYPTIONS_IN_PROGRESS
+            .lock()
+            .expect("Should not panic")
+            .remove(&pool_uuid);
+        info!("Reencryption of pool with UUID {} finished", pool_uuid);
+    });
+
+    Ok(())
+}
+
 /// Make a DM cache device. If the cache device is being made new,
 /// take extra steps to make it clean.
 fn make_cache(
@@ -80,7 +231,10 @@ fn make_cache(
     )?;
 
     let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::Cache);
-    Ok(CacheDev::setup(
+    if device_exists(get_dm(), &dm_name)? {
+        load_default_cache_table(&dm_name)?;
+    }
+    let mut cache_device = CacheDev::setup(
         get_dm(),
         &dm_name,
         Some(&dm_uuid),
@@ -88,7 +242,279 @@ fn make_cache(
         cache,
         origin,
         CACHE_BLOCK_SIZE,
-    )?)
+    )?;
+
+    // The cache device is always set up with the default mode and policy.
+    let table = cache_table(&cache_device, &cache_tier.config);
+    if table != cache_device.table().to_raw_table() {
+        load_cache_table(&mut cache_device, &table)?;
+    }
+
+    Ok(cache_device)
+}
+
+/// Get the table of the DM cache device with the mode and policy specified
+/// by config.
+fn cache_table(cache: &CacheDev, config: &CacheConfig) -> Vec<(u64, u64, String, String)> {
+    let mut table = cache.table().clone();
+    let params = &mut table.table.params;
+    params
+        .feature_args
+        .retain(|arg| CacheMode::try_from(arg.as_str()).is_err());
+    params.feature_args.insert(config.mode.to_string());
+    params.policy = config.policy.clone();
+    params.policy_args = config.policy_args.iter().cloned().collect();
+    table.to_raw_table()
+}
+
+/// Load the given table into the DM cache device and resume it, making the
+/// new table active.
+///
+/// The table is loaded directly, so CacheDev::table() continues to report the
+/// default mode and policy; changes to the sub-devices of the cache device
+/// must go through set_cache_sub_table, which accounts for this.
+fn load_cache_table(
+    cache: &mut CacheDev,
+    table: &[(u64, u64, String, String)],
+) -> StratisResult<()> {
+    get_dm().table_load(&DevId::Name(cache.name()), table, DmOptions::default())?;
+    cache.resume(get_dm())?;
+    Ok(())
+}
+
+/// Load the default mode and policy into an existing DM cache device, if it
+/// has a different mode or policy arguments. CacheDev::setup() only accepts
+/// an existing device whose table has the default mode and no policy
+/// arguments, but the device may have been loaded with the mode and policy
+/// of its configuration by an earlier instance of stratisd.
+fn load_default_cache_table(dm_name: &DmName) -> StratisResult<()> {
+    let id = DevId::Name(dm_name);
+    let (_, current) = get_dm().table_status(
+        &id,
+        DmOptions::default().set_flags(DmFlags::DM_STATUS_TABLE),
+    )?;
+    // The parameters of a cache target are the metadata, cache and origin
+    // devices and the cache block size, followed by the feature arguments,
+    // the policy and the policy arguments.
+    let default = current
+        .iter()
+        .map(|(start, length, target_type, params)| {
+            let devs_and_block_size = params.split_whitespace().take(4).collect::<Vec<_>>();
+            (
+                *start,
+                *length,
+                target_type.clone(),
+                format!(
+                    "{} 1 {} default 0",
+                    devs_and_block_size.join(" "),
+                    CacheMode::Writethrough
+                ),
+            )
+        })
+        .collect::<Vec<_>>();
+    if default != current {
+        get_dm().table_load(&id, &default, DmOptions::default())?;
+        get_dm().device_suspend(&id, DmOptions::default())?;
+    }
+    Ok(())
+}
+
+/// The sub-devices of the DM cache device.
+#[derive(Clone, Copy, Debug)]
+enum CacheSubDev {
+    Origin,
+    Cache,
+    Meta,
+}
+
+/// Set the table of a sub-device of the DM cache device and resume the cache
+/// device with the mode and policy specified by config.
+///
+/// The CacheDev methods which set the table of a sub-device also load the
+/// table that CacheDev tracks, which has the default mode and policy. That
+/// table must never become active, so the table with the configured mode and
+/// policy is loaded in its place before the cache device is resumed. If that
+/// fails, the inactive table is cleared, so that resuming the cache device
+/// keeps its previous table.
+fn set_cache_sub_table(
+    cache: &mut CacheDev,
+    sub_dev: CacheSubDev,
+    table: Vec<TargetLine<LinearDevTargetParams>>,
+    config: &CacheConfig,
+) -> StratisResult<()> {
+    match sub_dev {
+        CacheSubDev::Origin => cache.set_origin_table(get_dm(), table)?,
+        CacheSubDev::Cache => cache.set_cache_table(get_dm(), table)?,
+        CacheSubDev::Meta => cache.set_meta_table(get_dm(), table)?,
+    }
+
+    if let Err(causal_error) = load_cache_table(cache, &cache_table(cache, config)) {
+        if let Err(rollback_error) = get_dm().table_clear(&DevId::Name(cache.name())) {
+            return Err(StratisError::NoActionRollbackError {
+                causal_error: Box::new(causal_error),
+                rollback_error: Box::new(StratisError::from(rollback_error)),
+            });
+        }
+        return Err(causal_error);
+    }
+
+    Ok(())
+}
+
+/// Get the status of a working DM cache device.
+fn cache_status(cache: &CacheDev) -> StratisResult<Box<CacheDevWorkingStatus>> {
+    match cache.status(get_dm(), DmOptions::default())? {
+        CacheDevStatus::Working(status) => Ok(status),
+        CacheDevStatus::Error => Err(StratisError::Msg(
+            "The status of the cache device could not be obtained".to_string(),
+        )),
+        CacheDevStatus::Fail => Err(StratisError::Msg(
+            "The cache device is in a failed state".to_string(),
+        )),
+    }
+}
+
+/// Switch the DM cache device to writethrough mode, so that no further
+/// blocks become dirty, and to the cleaner policy, and wait until all dirty
+/// blocks have been written back to the origin device. If the cache can not
+/// be cleaned, or no dirty block is written back for CACHE_CLEAN_TIMEOUT,
+/// the cache device is switched back to the mode and policy specified by
+/// config.
+fn clean_cache(cache: &mut CacheDev, config: &CacheConfig) -> StratisResult<()> {
+    fn wait_for_clean(cache: &CacheDev) -> StratisResult<()> {
+        let mut dirty = cache_status(cache)?.performance.dirty;
+        let mut last_progress = Instant::now();
+        while dirty != 0 {
+            if last_progress.elapsed() > CACHE_CLEAN_TIMEOUT {
+                return Err(StratisError::Msg(format!(
+                    "No dirty block of the cache was written back in {} seconds; {} dirty blocks remain",
+                    CACHE_CLEAN_TIMEOUT.as_secs(),
+                    dirty
+                )));
+            }
+            thread::sleep(Duration::from_millis(100));
+            let remaining = cache_status(cache)?.performance.dirty;
+            if remaining < dirty {
+                last_progress = Instant::now();
+            }
+            dirty = remaining;
+        }
+        Ok(())
+    }
+
+    let cleaner = cache_table(
+        cache,
+        &CacheConfig {
+            mode: CacheMode::Writethrough,
+            policy: "cleaner".to_string(),
+            policy_args: Vec::new(),
+        },
+    );
+    load_cache_table(cache, &cleaner)?;
+
+    if let Err(causal_error) = wait_for_clean(cache) {
+        if let Err(rollback_error) = load_cache_table(cache, &cache_table(cache, config)) {
+            return Err(StratisError::NoActionRollbackError {
+                causal_error: Box::new(causal_error),
+                rollback_error: Box::new(rollback_error),
+            });
+        }
+        return Err(causal_error);
+    }
+
+    Ok(())
+}
+
+/// Write back all dirty blocks in the DM cache device and then invalidate
+/// every block in the cache, so that the cache device no longer depends on any
+/// data stored on the cache sub-device. The cache device is left in
+/// passthrough mode with the cleaner policy. If the cache can not be
+/// invalidated, it is switched back to the mode and policy specified by
+/// config.
+fn invalidate_cache(cache: &mut CacheDev, config: &CacheConfig) -> StratisResult<()> {
+    fn invalidate(cache: &mut CacheDev, table: &[(u64, u64, String, String)]) -> StratisResult<()> {
+        load_cache_table(cache, table)?;
+        let total_cache = cache_status(cache)?.usage.total_cache;
+        get_dm().target_msg(
+            &DevId::Name(cache.name()),
+            None,
+            &format!("invalidate_cblocks 0-{}", *total_cache),
+        )?;
+        Ok(())
+    }
+
+    clean_cache(cache, config)?;
+
+    // Blocks can only be invalidated while the cache is in passthrough mode.
+    let passthrough = cache_table(
+        cache,
+        &CacheConfig {
+            mode: CacheMode::Passthrough,
+            policy: "cleaner".to_string(),
+            policy_args: Vec::new(),
+        },
+    );
+
+    if let Err(causal_error) = invalidate(cache, &passthrough) {
+        if let Err(rollback_error) = load_cache_table(cache, &cache_table(cache, config)) {
+            return Err(StratisError::NoActionRollbackError {
+                causal_error: Box::new(causal_error),
+                rollback_error: Box::new(rollback_error),
+            });
+        }
+        return Err(causal_error);
+    }
+
+    Ok(())
+}
+
+/// Get the table of the origin sub-device of the cap device. If the data
+/// tier is mirrored, the origin maps the whole of the DM raid device,
+/// otherwise it maps the segments allocated from the data tier directly.
+fn origin_table(
+    data_tier: &DataTier,
+    mirror: Option<&MirrorDev>,
+) -> Vec<TargetLine<LinearDevTargetParams>> {
+    match mirror {
+        Some(mirror) => mirror.linear_table(),
+        None => data_tier.segments.map_to_dm(),
+    }
+}
+
+/// Get the tables of the legs of the DM raid device of a mirrored data
+/// tier, None for a leg which is missing, or None if the data tier is not
+/// mirrored.
+fn leg_tables(data_tier: &DataTier) -> Option<[Option<LegTables>; 2]> {
+    data_tier.mirror_legs().map(|legs| {
+        legs.map(|leg| {
+            leg.map(|(data, meta)| LegTables {
+                data: data.map_to_dm(),
+                meta: meta.map_to_dm(),
+            })
+        })
+    })
+}
+
+/// Set up the origin sub-device of the cap device and, if the data tier is
+/// mirrored, the DM raid device and its legs, which the origin maps. If new
+/// is true, the DM raid device is set up for the first time.
+fn make_origin(
+    pool_uuid: PoolUuid,
+    data_tier: &DataTier,
+    new: bool,
+) -> StratisResult<(LinearDev, Option<MirrorDev>)> {
+    let mirror = match leg_tables(data_tier) {
+        Some(tables) => Some(MirrorDev::setup(pool_uuid, tables, new)?),
+        None => None,
+    };
+    let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::OriginSub);
+    let origin = LinearDev::setup(
+        get_dm(),
+        &dm_name,
+        Some(&dm_uuid),
+        origin_table(data_tier, mirror.as_ref()),
+    )?;
+    Ok((origin, mirror))
 }
 
 /// This structure can allocate additional space to the upper layer, but it
@@ -105,6 +531,9 @@ pub struct Backstore {
     data_tier: DataTier,
     /// A linear DM device.
     linear: Option<LinearDev>,
+    /// The DM raid device which the origin of the cap device maps, if the
+    /// data tier is mirrored.
+    mirror: Option<MirrorDev>,
     /// Index for managing allocation of cap device
     next: Sectors,
 }
@@ -141,17 +570,11 @@ impl Backstore {
     ) -> BDARecordResult<Backstore> {
         let block_mgr = BlockDevMgr::new(datadevs, Some(last_update_time));
         let data_tier = DataTier::setup(block_mgr, &backstore_save.data_tier)?;
-        let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::OriginSub);
-        let origin = match LinearDev::setup(
-            get_dm(),
-            &dm_name,
-            Some(&dm_uuid),
-            data_tier.segments.map_to_dm(),
-        ) {
-            Ok(origin) => origin,
+        let (origin, mirror) = match make_origin(pool_uuid, &data_tier, false) {
+            Ok(devs) => devs,
             Err(e) => {
                 return Err((
-                    StratisError::from(e),
+                    e,
                     data_tier
                         .block_mgr
                         .into_bdas()
@@ -207,13 +630,16 @@ impl Backstore {
             (None, None, Some(origin))
         };
 
-        Ok(Backstore {
+        let backstore = Backstore {
             data_tier,
             cache_tier,
             linear: origin,
+            mirror,
             cache,
             next: backstore_save.cap.allocs[0].1,
-        })
+        };
+        backstore.resume_volume_key_rotation(pool_uuid);
+        Ok(backstore)
     }
 
     /// Initialize a Backstore object, by initializing the specified devs.
@@ -224,32 +650,46 @@ impl Backstore {
     /// When the backstore is initialized it may be unencrypted, or it may
     /// be encrypted only with a kernel keyring and without Clevis information.
     ///
+    /// The data tier stores its data with the redundancy specified in
+    /// options. If integrity is true, each data device is protected by
+    /// dm-integrity. Encrypted devices are formatted according to
+    /// encryption_config. The thin pool settings in options are ignored.
+    ///
     /// WARNING: metadata changing event
     pub fn initialize(
         pool_name: Name,
         pool_uuid: PoolUuid,
         devices: UnownedDevices,
         mda_data_size: MDADataSize,
-        encryption_info: Option<&EncryptionInfo>,
+        options: PoolCreateOptions<'_>,
     ) -> StratisResult<Backstore> {
-        let data_tier = DataTier::new(BlockDevMgr::initialize(
-            pool_name,
-            pool_uuid,
-            devices,
-            mda_data_size,
-            encryption_info,
-        )?);
+        validate_redundancy(options.redundancy, devices.len())?;
+
+        let data_tier = DataTier::new(
+            BlockDevMgr::initialize(
+                pool_name,
+                pool_uuid,
+                devices,
+                mda_data_size,
+                options.integrity,
+                options.encryption_info,
+                options.encryption_config,
+            )?,
+            options.redundancy,
+        )?;
 
         Ok(Backstore {
             data_tier,
             cache_tier: None,
             linear: None,
+            mirror: None,
             cache: None,
             next: Sectors(0),
         })
     }
 
-    /// Initialize the cache tier and add cachedevs to the backstore.
+    /// Initialize the cache tier and add cachedevs to the backstore. The cache
+    /// device uses the mode and policy specified by config.
     ///
     /// Returns all `DevUuid`s of devices that were added to the cache on initialization.
     ///
@@ -262,6 +702,7 @@ impl Backstore {
         pool_name: Name,
         pool_uuid: PoolUuid,
         devices: UnownedDevices,
+        config: CacheConfig,
     ) -> StratisResult<Vec<DevUuid>> {
         match self.cache_tier {
             Some(_) => unreachable!("self.cache.is_none()"),
@@ -271,18 +712,42 @@ impl Backstore {
                 // If it is desired to change a cache dev to a data dev, it
                 // should be removed and then re-added in order to ensure
                 // that the MDA region is set to the correct size.
-                let bdm = BlockDevMgr::initialize(
+                // The cachedevs are encrypted with the same settings as the
+                // data devices; the defaults are used if those settings are
+                // inconsistent across the data devices.
+                let encryption_config = match self.data_tier_encryption_config() {
+                    Some(MaybeInconsistent::No(config)) => Some(config),
+                    _ => None,
+                };
+                // The cachedevs are bound to the same named passphrases as
+                // the data devices, so that the bindings remain consistent
+                // across the pool and the cachedevs can be unlocked with
+                // any of them.
+                let passphrase_bindings = match self.data_tier_passphrase_bindings() {
+                    Some(MaybeInconsistent::No(bindings)) => bindings,
+                    Some(MaybeInconsistent::Yes) => {
+                        return Err(StratisError::Msg(
+                            "The passphrase bindings of the data devices are inconsistent"
+                                .to_string(),
+                        ));
+                    }
+                    None => PassphraseBindings::default(),
+                };
+                let mut bdm = BlockDevMgr::initialize(
                     pool_name,
                     pool_uuid,
                     devices,
                     MDADataSize::default(),
+                    false,
                     self.data_tier_encryption_info()
                         .map(EncryptionInfo::try_from)
                         .transpose()?
                         .as_ref(),
+                    encryption_config.as_ref(),
                 )?;
+                bdm.bind_passphrases(&passphrase_bindings)?;
 
-                let cache_tier = CacheTier::new(bdm)?;
+                let cache_tier = CacheTier::new(bdm, config)?;
 
                 let linear = self.linear
                     .take()
@@ -334,18 +799,24 @@ impl Backstore {
                     cache_tier.add(pool_name, pool_uuid, devices)?;
 
                 if cache_change {
-                    let table = cache_tier.cache_segments.map_to_dm();
-                    cache_device.set_cache_table(get_dm(), table)?;
-                    cache_device.resume(get_dm())?;
+                    set_cache_sub_table(
+                        cache_device,
+                        CacheSubDev::Cache,
+                        cache_tier.cache_segments.map_to_dm(),
+                        &cache_tier.config,
+                    )?;
                 }
 
                 // NOTE: currently CacheTier::add() does not ever update the
                 // meta segments. That means that this code is dead. But,
                 // when CacheTier::add() is fixed, this code will become live.
                 if meta_change {
-                    let table = cache_tier.meta_segments.map_to_dm();
-                    cache_device.set_meta_table(get_dm(), table)?;
-                    cache_device.resume(get_dm())?;
+                    set_cache_sub_table(
+                        cache_device,
+                        CacheSubDev::Meta,
+                        cache_tier.meta_segments.map_to_dm(),
+                        &cache_tier.config,
+                    )?;
                 }
 
                 Ok(uuids)
@@ -354,6 +825,316 @@ impl Backstore {
         }
     }
 
+    /// Remove the specified cachedevs from the cache tier.
+    ///
+    /// The cache is cleaned and invalidated before the cachedevs are removed,
+    /// since the layout of the cache sub-device changes; the cache device
+    /// is then reloaded with its configured mode and policy.
+    ///
+    /// Returns the removed blockdevs, which have not been wiped, so that they
+    /// can be wiped once the metadata that no longer refers to them has been
+    /// written.
+    ///
+    /// Precondition: every device in uuids belongs to the cache tier.
+    // Precondition: self.cache.is_some() && self.linear.is_none()
+    pub fn remove_cachedevs(&mut self, uuids: &[DevUuid]) -> StratisResult<Vec<StratBlockDev>> {
+        match self.cache_tier {
+            Some(ref mut cache_tier) => {
+                let cache_device = self
+                    .cache
+                    .as_mut()
+                    .expect("cache_tier.is_some() <=> self.cache.is_some()");
+                invalidate_cache(cache_device, &cache_tier.config)?;
+
+                let cache_segments = cache_tier.cache_segments.inner.clone();
+                match cache_tier.remove(uuids) {
+                    Ok((true, removed)) => {
+                        if let Err(causal_error) = set_cache_sub_table(
+                            cache_device,
+                            CacheSubDev::Cache,
+                            cache_tier.cache_segments.map_to_dm(),
+                            &cache_tier.config,
+                        ) {
+                            cache_tier.revert_remove(removed, cache_segments);
+                            if let Err(rollback_error) = set_cache_sub_table(
+                                cache_device,
+                                CacheSubDev::Cache,
+                                cache_tier.cache_segments.map_to_dm(),
+                                &cache_tier.config,
+                            ) {
+                                return Err(StratisError::NoActionRollbackError {
+                                    causal_error: Box::new(causal_error),
+                                    rollback_error: Box::new(rollback_error),
+                                });
+                            }
+                            return Err(causal_error);
+                        }
+                        Ok(removed)
+                    }
+                    Ok((false, removed)) => {
+                        if let Err(e) = load_cache_table(
+                            cache_device,
+                            &cache_table(cache_device, &cache_tier.config),
+                        ) {
+                            cache_tier.revert_remove(removed, cache_segments);
+                            return Err(e);
+                        }
+                        Ok(removed)
+                    }
+                    Err(causal_error) => {
+                        if let Err(rollback_error) = load_cache_table(
+                            cache_device,
+                            &cache_table(cache_device, &cache_tier.config),
+                        ) {
+                            return Err(StratisError::NoActionRollbackError {
+                                causal_error: Box::new(causal_error),
+                                rollback_error: Box::new(rollback_error),
+                            });
+                        }
+                        Err(causal_error)
+                    }
+                }
+            }
+            None => unreachable!("self.cache.is_some()"),
+        }
+    }
+
+    /// Switch the cache to writethrough mode with the cleaner policy and
+    /// write back all its dirty blocks to the origin sub-device while the
+    /// pool remains in use.
+    ///
+    /// This is the first step of removing the cache; it makes the final
+    /// write back in remove_cache, which runs while the device which uses the
+    /// cap device is suspended, short. If the cache can not be cleaned, it is
+    /// switched back to its configured mode and policy.
+    // Precondition: self.cache.is_some() && self.linear.is_none()
+    pub fn write_back_cache(&mut self) -> StratisResult<()> {
+        let cache = self
+            .cache
+            .as_mut()
+            .expect("precondition: self.cache.is_some()");
+        let config = &self
+            .cache_tier
+            .as_ref()
+            .expect("self.cache.is_some() <=> self.cache_tier.is_some()")
+            .config;
+        clean_cache(cache, config)
+    }
+
+    /// Switch the cache back to its configured mode and policy, e.g., after
+    /// write_back_cache if the cache is not going to be removed after all.
+    // Precondition: self.cache.is_some() && self.linear.is_none()
+    pub fn restore_cache_config(&mut self) -> StratisResult<()> {
+        let cache = self
+            .cache
+            .as_mut()
+            .expect("precondition: self.cache.is_some()");
+        let config = &self
+            .cache_tier
+            .as_ref()
+            .expect("self.cache.is_some() <=> self.cache_tier.is_some()")
+            .config;
+        load_cache_table(cache, &cache_table(cache, config))
+    }
+
+    /// Remove the cache tier from the backstore.
+    ///
+    /// All dirty blocks in the cache are written back to the origin
+    /// sub-device and every block in the cache is invalidated, after which
+    /// the origin sub-device becomes the cap device again. set_device is
+    /// invoked with the new cap device and must switch the device which uses
+    /// the cap device over to it; the DM cache device and its metadata and
+    /// cache sub-devices are removed afterward.
+    ///
+    /// The device which uses the cap device must be suspended for the whole
+    /// call, so that no write can dirty a block of the cache after the last
+    /// dirty block has been written back and before the origin sub-device
+    /// replaces the cache device. Calling write_back_cache first, while the
+    /// pool is still in use, keeps that suspension short.
+    ///
+    /// Returns the cache tier, which has been detached from the backstore,
+    /// so that its blockdevs can be wiped once the metadata that no longer
+    /// refers to them has been written.
+    ///
+    /// If cleaning the cache or switching the device fails, the cache is
+    /// left in place.
+    // Precondition: self.cache.is_some() && self.linear.is_none()
+    // Postcondition: self.cache.is_none() && self.linear.is_some()
+    pub fn remove_cache<F>(
+        &mut self,
+        pool_uuid: PoolUuid,
+        set_device: F,
+    ) -> StratisResult<CacheTier>
+    where
+        F: FnOnce(Device) -> StratisResult<()>,
+    {
+        let cache = self
+            .cache
+            .as_mut()
+            .expect("precondition: self.cache.is_some()");
+        let config = &self
+            .cache_tier
+            .as_ref()
+            .expect("self.cache.is_some() <=> self.cache_tier.is_some()")
+            .config;
+        // Any block dirtied since write_back_cache was flushed to the cache
+        // device by the suspension of its user and is written back here.
+        // Every block of the cache is invalidated as well: once the origin
+        // sub-device replaces the cache device, writes no longer reach the
+        // cache, which must not serve stale blocks if it is set up again
+        // because its removal could not be recorded.
+        invalidate_cache(cache, config)?;
+
+        let restore = |cache: &mut CacheDev, causal_error: StratisError| match load_cache_table(
+            cache,
+            &cache_table(cache, config),
+        ) {
+            Ok(()) => causal_error,
+            Err(rollback_error) => StratisError::NoActionRollbackError {
+                causal_error: Box::new(causal_error),
+                rollback_error: Box::new(rollback_error),
+            },
+        };
+
+        // The origin sub-device of the cache is the linear device that was
+        // the cap device before the cache was initialized, so this only
+        // acquires a handle to the existing device.
+        let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::OriginSub);
+        let origin = match LinearDev::setup(
+            get_dm(),
+            &dm_name,
+            Some(&dm_uuid),
+            origin_table(&self.data_tier, self.mirror.as_ref()),
+        ) {
+            Ok(origin) => origin,
+            Err(causal_error) => return Err(restore(cache, StratisError::from(causal_error))),
+        };
+
+        if let Err(causal_error) = set_device(origin.device()) {
+            return Err(restore(cache, causal_error));
+        }
+
+        let cache = self
+            .cache
+            .take()
+            .expect("precondition: self.cache.is_some()");
+        let cache_tier = self
+            .cache_tier
+            .take()
+            .expect("self.cache.is_some() <=> self.cache_tier.is_some()");
+        self.linear = Some(origin);
+
+        // Nothing uses the DM cache device any longer, so a failure to remove
+        // it or its sub-devices does not affect the pool.
+        let teardown_res = get_dm()
+            .device_remove(&DevId::Name(cache.name()), DmOptions::default())
+            .map_err(StratisError::from)
+            .and_then(|_| {
+                for (role, segments) in [
+                    (CacheRole::MetaSub, &cache_tier.meta_segments),
+                    (CacheRole::CacheSub, &cache_tier.cache_segments),
+                ] {
+                    let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, role);
+                    LinearDev::setup(get_dm(), &dm_name, Some(&dm_uuid), segments.map_to_dm())?
+                        .teardown(get_dm())?;
+                }
+                Ok(())
+            });
+        if let Err(err) = teardown_res {
+            warn!(
+                "Failed to remove the devicemapper devices of the cache of pool with UUID {}: {}",
+                pool_uuid, err
+            );
+        }
+
+        Ok(cache_tier)
+    }
+
+    /// The mode and policy of the cache, if the backstore has a cache.
+    pub fn cache_config(&self) -> Option<&CacheConfig> {
+        self.cache_tier
+            .as_ref()
+            .map(|cache_tier| &cache_tier.config)
+    }
+
+    /// Switch the cache to the mode and policy specified by config.
+    ///
+    /// Before switching to passthrough mode all dirty blocks are written
+    /// back to the origin sub-device, since a cache in passthrough mode
+    /// must be clean. If the switch fails, the cache keeps its previous
+    /// mode and policy.
+    ///
+    /// Returns true if the mode or the policy of the cache changed.
+    /// WARNING: metadata changing event
+    // Precondition: self.cache.is_some() && self.linear.is_none()
+    pub fn set_cache_config(&mut self, config: CacheConfig) -> StratisResult<bool> {
+        match self.cache_tier {
+            Some(ref mut cache_tier) => {
+                if cache_tier.config == config {
+                    return Ok(false);
+                }
+
+                let cache_device = self
+                    .cache
+                    .as_mut()
+                    .expect("cache_tier.is_some() <=> self.cache.is_some()");
+
+                if config.mode == CacheMode::Passthrough {
+                    clean_cache(cache_device, &cache_tier.config)?;
+                }
+
+                if let Err(causal_error) =
+                    load_cache_table(cache_device, &cache_table(cache_device, &config))
+                {
+                    if let Err(rollback_error) = load_cache_table(
+                        cache_device,
+                        &cache_table(cache_device, &cache_tier.config),
+                    ) {
+                        return Err(StratisError::NoActionRollbackError {
+                            causal_error: Box::new(causal_error),
+                            rollback_error: Box::new(rollback_error),
+                        });
+                    }
+                    return Err(causal_error);
+                }
+
+                cache_tier.config = config;
+                Ok(true)
+            }
+            None => Err(StratisError::Msg(
+                "The pool has no cache, so the cache mode and policy can not be set".to_string(),
+            )),
+        }
+    }
+
+    /// The hit, miss, and dirty block counters of the DM cache device, if the
+    /// backstore has a cache.
+    pub fn cache_stats(&self) -> StratisResult<Option<CacheStats>> {
+        match self.cache {
+            Some(ref cache) => {
+                let performance = cache_status(cache)?.performance;
+                Ok(Some(CacheStats {
+                    read_hits: performance.read_hits,
+                    read_misses: performance.read_misses,
+                    write_hits: performance.write_hits,
+                    write_misses: performance.write_misses,
+                    dirty: performance.dirty,
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// The redundancy with which the data tier stores its data.
+    pub fn redundancy(&self) -> Redundancy {
+        self.data_tier.redundancy()
+    }
+
+    /// Whether the devices in the data tier are protected by dm-integrity.
+    pub fn has_integrity(&self) -> bool {
+        self.data_tier.has_integrity()
+    }
+
     /// Add datadevs to the backstore. The data tier always exists if the
     /// backstore exists at all, so there is no need to create it.
     pub fn add_datadevs(
@@ -365,40 +1146,269 @@ impl Backstore {
         self.data_tier.add(pool_name, pool_uuid, devices)
     }
 
-    /// Extend the cap device whether it is a cache or not. Create the DM
-    /// device if it does not already exist. Return an error if DM
-    /// operations fail. Use all segments currently allocated in the data tier.
-    fn extend_cap_device(&mut self, pool_uuid: PoolUuid) -> StratisResult<()> {
-        let create = match (self.cache.as_mut(), self.linear.as_mut()) {
-            (None, None) => true,
+    /// Start moving all data stored on the specified data devices to the
+    /// remaining devices in the data tier and wait until it is copied; see
+    /// move_data(). Returns None if the devices hold no data.
+    ///
+    /// The devices remain in the data tier, unused, until remove_datadevs()
+    /// is invoked.
+    ///
+    /// The caller must write the metadata, which now records the new
+    /// locations of the data, and then invoke finish_move() with the
+    /// returned migration.
+    /// WARNING: metadata changing event
+    pub fn evacuate_datadevs(
+        &mut self,
+        pool_uuid: PoolUuid,
+        uuids: &[DevUuid],
+    ) -> StratisResult<Option<Migration>> {
+        self.move_data(pool_uuid, uuids, None, |_, _| ())
+    }
+
+    /// Add the device specified by new to the data tier and move all data
+    /// stored on the data device old to it. The progress of the copy is
+    /// available through replace_progress() while the data is moved.
+    /// If moving the data fails, the new device is removed from the data
+    /// tier again.
+    ///
+    /// The old device remains in the data tier, unused, until
+    /// remove_datadevs() is invoked.
+    ///
+    /// Returns the UUID of the new device and the migration which moved the
+    /// data, if the old device held any. The caller must write the metadata,
+    /// which now records the new locations of the data, and then invoke
+    /// finish_move() with the migration, or abort_replace() if the metadata
+    /// can not be written.
+    ///
+    /// Precondition: new contains exactly one device.
+    /// WARNING: metadata changing event
+    pub fn replace_datadev(
+        &mut self,
+        pool_name: Name,
+        pool_uuid: PoolUuid,
+        old: DevUuid,
+        new: UnownedDevices,
+    ) -> StratisResult<(DevUuid, Option<Migration>)> {
+        let new_uuid = match self.add_datadevs(pool_name, pool_uuid, new)?.as_slice() {
+            [uuid] => *uuid,
+            _ => panic!("Precondition requires exactly one device"),
+        };
+
+        REPLACEMENTS_IN_PROGRESS
+            .lock()
+            .expect("Should not panic")
+            .insert(
+                pool_uuid,
+                ReplaceProgress {
+                    old,
+                    new: new_uuid,
+                    copied: Sectors(0),
+                    total: Sectors(0),
+                },
+            );
+        let res = self.move_data(pool_uuid, &[old], Some(&[new_uuid]), |copied, total| {
+            if let Some(progress) = REPLACEMENTS_IN_PROGRESS
+                .lock()
+                .expect("Should not panic")
+                .get_mut(&pool_uuid)
+            {
+                progress.copied = copied;
+                progress.total = total;
+            }
+        });
+        REPLACEMENTS_IN_PROGRESS
+            .lock()
+            .expect("Should not panic")
+            .remove(&pool_uuid);
+
+        match res {
+            Ok(migration) => Ok((new_uuid, migration)),
+            Err(causal_error) => {
+                if let Err(rollback_error) = self.data_tier.remove_added(&[new_uuid]) {
+                    return Err(StratisError::NoActionRollbackError {
+                        causal_error: Box::new(causal_error),
+                        rollback_error: Box::new(rollback_error),
+                    });
+                }
+                Err(causal_error)
+            }
+        }
+    }
+
+    /// Abandon a replacement made by replace_datadev(): abandon the
+    /// migration, if any, so that the data is mapped at its old locations
+    /// again, and remove the new device from the data tier again.
+    pub fn abort_replace(
+        &mut self,
+        new: DevUuid,
+        migration: Option<Migration>,
+    ) -> StratisResult<()> {
+        if let Some(migration) = migration {
+            self.abort_move(migration)?;
+        }
+        self.data_tier.remove_added(&[new])
+    }
+
+    /// Move the data stored on the data devices specified by uuids, restricting
+    /// the destination to the devices specified by targets if any, while the
+    /// pool remains in use.
+    ///
+    /// A DM mirror device is set up for each segment to move, and the origin
+    /// is reloaded so that it maps each moved segment onto its DM mirror
+    /// device; the origin is suspended only while its table is reloaded.
+    /// Once the kernel has copied the data to the new locations, from which
+    /// time every write goes to both locations, the data tier records the
+    /// new locations. Returns the migration, which finish_move() completes,
+    /// or None if there was no data to move.
+    ///
+    /// If the data can not be copied, the origin maps the old locations
+    /// again and no allocations are changed.
+    ///
+    /// Precondition: self.mirror.is_none()
+    fn move_data<F>(
+        &mut self,
+        pool_uuid: PoolUuid,
+        uuids: &[DevUuid],
+        targets: Option<&[DevUuid]>,
+        progress: F,
+    ) -> StratisResult<Option<Migration>>
+    where
+        F: FnMut(Sectors, Sectors),
+    {
+        let (transaction, moves) = match self.data_tier.request_evacuation(uuids, targets)? {
+            Some(evacuation) => evacuation,
+            None => return Ok(None),
+        };
+
+        let mut migration = Migration::setup(pool_uuid, moves)?;
+
+        let res = self
+            .set_origin_table(migration.origin_table(&self.data_tier.segments))
+            .and_then(|_| migration.wait_for_sync(progress))
+            .and_then(|_| {
+                self.data_tier
+                    .commit_evacuation(transaction, migration.moves())
+            })
+            .map(|previous| migration.previous = Some(previous));
+        if let Err(causal_error) = res {
+            let rollback_res = self
+                .set_origin_table(origin_table(&self.data_tier, self.mirror.as_ref()))
+                .and_then(|_| migration.teardown());
+            if let Err(rollback_error) = rollback_res {
+                return Err(StratisError::NoActionRollbackError {
+                    causal_error: Box::new(causal_error),
+                    rollback_error: Box::new(rollback_error),
+                });
+            }
+            return Err(causal_error);
+        }
+
+        Ok(Some(migration))
+    }
+
+    /// Complete a migration started by move_data(): reload the origin so that
+    /// it maps the new locations of the moved data directly, and remove the
+    /// DM mirror devices.
+    ///
+    /// Precondition: The metadata which records the new locations has been
+    /// written, since the old locations are no longer written to afterward.
+    pub fn finish_move(&mut self, mut migration: Migration) -> StratisResult<()> {
+        self.set_origin_table(origin_table(&self.data_tier, self.mirror.as_ref()))?;
+
+        // Nothing uses the DM mirror devices any longer, so a failure to
+        // remove them does not affect the pool.
+        if let Err(err) = migration.teardown() {
+            warn!(
+                "Failed to remove the devicemapper devices which moved data in the data tier: {}",
+                err
+            );
+        }
+        Ok(())
+    }
+
+    /// Abandon a migration started by move_data(), e.g., because the metadata
+    /// which records the new locations could not be written: the data tier
+    /// records the old locations again and releases the space allocated for
+    /// the new ones, the origin is reloaded so that it maps the old
+    /// locations, and the DM mirror devices are removed.
+    pub fn abort_move(&mut self, mut migration: Migration) -> StratisResult<()> {
+        // The old locations are recorded again even if releasing the space
+        // allocated for the new locations fails, so the origin is reloaded
+        // regardless.
+        let release_res = match migration.previous.take() {
+            Some(previous) => self
+                .data_tier
+                .revert_evacuation(previous, migration.moves()),
+            None => Ok(()),
+        };
+        self.set_origin_table(origin_table(&self.data_tier, self.mirror.as_ref()))?;
+        migration.teardown()?;
+        release_res
+    }
+
+    /// Remove the specified data devices from the data tier and return them.
+    /// They are not wiped, so that they can be wiped once the metadata that
+    /// no longer refers to them has been written.
+    ///
+    /// Precondition: The devices have been evacuated with
+    /// evacuate_datadevs() and the pool metadata no longer refers to any
+    /// segments allocated from them.
+    /// WARNING: metadata changing event
+    pub fn remove_datadevs(&mut self, uuids: &[DevUuid]) -> StratisResult<Vec<StratBlockDev>> {
+        self.check_no_volume_key_rotation()?;
+        self.data_tier.remove(uuids)
+    }
+
+    /// Reload the origin sub-device of the cache device, or the cap device
+    /// itself if there is no cache, with the given table.
+    ///
+    /// Precondition: The cap device exists.
+    fn set_origin_table(
+        &mut self,
+        table: Vec<TargetLine<LinearDevTargetParams>>,
+    ) -> StratisResult<()> {
+        match (self.cache.as_mut(), self.linear.as_mut()) {
             (Some(cache), None) => {
-                let table = self.data_tier.segments.map_to_dm();
-                cache.set_origin_table(get_dm(), table)?;
-                cache.resume(get_dm())?;
-                false
+                let config = &self
+                    .cache_tier
+                    .as_ref()
+                    .expect("self.cache.is_some() <=> self.cache_tier.is_some()")
+                    .config;
+                set_cache_sub_table(cache, CacheSubDev::Origin, table, config)
             }
             (None, Some(linear)) => {
-                let table = self.data_tier.segments.map_to_dm();
                 linear.set_table(get_dm(), table)?;
                 linear.resume(get_dm())?;
-                false
+                Ok(())
             }
+            (None, None) => panic!("precondition: the cap device exists"),
             _ => panic!("NOT (self.cache().is_some() AND self.linear.is_some())"),
-        };
+        }
+    }
+
+    /// Extend the cap device whether it is a cache or not. Create the DM
+    /// device if it does not already exist. Return an error if DM
+    /// operations fail. Use all segments currently allocated in the data tier.
+    /// If the data tier is mirrored, the DM raid device is extended first.
+    fn extend_cap_device(&mut self, pool_uuid: PoolUuid) -> StratisResult<()> {
+        if let (Some(mirror), Some(tables)) = (self.mirror.as_mut(), leg_tables(&self.data_tier)) {
+            mirror.set_tables(tables.map(|leg| leg.map(|tables| tables.data)))?;
+        }
 
-        if create {
-            let table = self.data_tier.segments.map_to_dm();
-            let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::OriginSub);
-            let origin = LinearDev::setup(get_dm(), &dm_name, Some(&dm_uuid), table)?;
+        if self.cache.is_none() && self.linear.is_none() {
+            let (origin, mirror) = make_origin(pool_uuid, &self.data_tier, true)?;
             self.linear = Some(origin);
+            self.mirror = mirror;
+        } else {
+            self.set_origin_table(origin_table(&self.data_tier, self.mirror.as_ref()))?;
         }
 
         Ok(())
     }
 
-    /// Satisfy a request for multiple segments. This request must
-    /// always be satisfied exactly, None is returned if this can not
-    /// be done.
+    /// Satisfy a request for multiple segments, each of a size and for a
+    /// purpose. This request must always be satisfied exactly, None is
+    /// returned if this can not be done.
     ///
     /// Precondition: self.next <= self.size()
     /// Postcondition: self.next <= self.size()
@@ -410,22 +1420,23 @@ impl Backstore {
     /// forall i, result_i.0 = result_(i - 1).0 + result_(i - 1).1
     pub fn request_alloc(
         &mut self,
-        sizes: &[Sectors],
+        requests: &[(Sectors, AllocationPurpose)],
     ) -> StratisResult<Option<RequestTransaction>> {
-        let mut transaction = match self.data_tier.alloc_request(sizes)? {
+        let sizes = requests.iter().map(|(size, _)| *size).collect::<Vec<_>>();
+        let mut transaction = match self.data_tier.alloc_request(requests)? {
             Some(t) => t,
             None => return Ok(None),
         };
 
         let mut next = self.next;
-        for size in sizes {
+        for size in sizes.iter() {
             transaction.add_seg_req((next, *size));
             next += *size
         }
 
         // Assert that the postcondition holds.
         assert_eq!(
-            sizes,
+            sizes.as_slice(),
             transaction
                 .get_backstore()
                 .iter()
@@ -565,6 +1576,9 @@ impl Backstore {
                 }
             }
         };
+        if let Some(ref mut mirror) = self.mirror {
+            mirror.teardown()?;
+        }
         self.data_tier.destroy()
     }
 
@@ -586,6 +1600,9 @@ impl Backstore {
                 Vec::new()
             }
         };
+        if let Some(ref mut mirror) = self.mirror {
+            mirror.teardown()?;
+        }
         devs.extend(self.data_tier.block_mgr.teardown()?);
         Ok(devs)
     }
@@ -679,6 +1696,40 @@ impl Backstore {
         )
     }
 
+    /// Set the allocation tags of the blockdev with the given UUID.
+    /// Return the UUID of the blockdev if its tags changed.
+    pub fn set_blockdev_tags(
+        &mut self,
+        uuid: DevUuid,
+        tags: &[String],
+    ) -> StratisResult<Option<DevUuid>> {
+        self.get_mut_blockdev_by_uuid(uuid).map_or_else(
+            || {
+                Err(StratisError::Msg(format!(
+                    "Blockdev with a UUID of {uuid} was not found"
+                )))
+            },
+            |(_, b)| {
+                if b.set_tags(tags) {
+                    Ok(Some(uuid))
+                } else {
+                    Ok(None)
+                }
+            },
+        )
+    }
+
+    /// The policy used when allocating space from the data tier.
+    pub fn allocation_config(&self) -> &AllocationConfig {
+        self.data_tier.allocation_config()
+    }
+
+    /// Set the policy used when allocating space from the data tier.
+    /// Return true if the configuration changed.
+    pub fn set_allocation_config(&mut self, config: AllocationConfig) -> bool {
+        self.data_tier.set_allocation_config(config)
+    }
+
     pub fn data_tier_is_encrypted(&self) -> bool {
         self.data_tier.block_mgr.is_encrypted()
     }
@@ -687,6 +1738,14 @@ impl Backstore {
         self.data_tier.block_mgr.encryption_info()
     }
 
+    pub fn data_tier_encryption_config(&self) -> Option<MaybeInconsistent<EncryptionConfig>> {
+        self.data_tier.block_mgr.encryption_config()
+    }
+
+    pub fn data_tier_passphrase_bindings(&self) -> Option<MaybeInconsistent<PassphraseBindings>> {
+        self.data_tier.block_mgr.passphrase_bindings()
+    }
+
     pub fn has_cache(&self) -> bool {
         self.cache_tier.is_some()
     }
@@ -700,6 +1759,18 @@ impl Backstore {
         )
     }
 
+    /// Gather the named passphrase bindings for all block devices in the
+    /// backstore.
+    pub fn passphrase_bindings(
+        &self,
+    ) -> StratisResult<Option<MaybeInconsistent<PassphraseBindings>>> {
+        let blockdevs = self.blockdevs();
+        gather_passphrase_bindings(
+            blockdevs.len(),
+            blockdevs.iter().map(|(_, _, bd)| bd.passphrase_bindings()),
+        )
+    }
+
     /// Bind all devices in the given backstore using the given clevis
     /// configuration.
     ///
@@ -717,6 +1788,7 @@ impl Backstore {
                 ));
             }
         };
+        self.check_no_volume_key_rotation()?;
 
         let mut parsed_config = clevis_info.clone();
         let yes = interpret_clevis_config(pin, &mut parsed_config)?;
@@ -774,6 +1846,7 @@ impl Backstore {
                 ));
             }
         };
+        self.check_no_volume_key_rotation()?;
 
         if encryption_info.clevis_info().is_some() {
             operation_loop(
@@ -803,6 +1876,7 @@ impl Backstore {
                 ));
             }
         };
+        self.check_no_volume_key_rotation()?;
 
         if let Some(kd) = encryption_info.key_description() {
             if kd == key_desc {
@@ -858,6 +1932,7 @@ impl Backstore {
                 ));
             }
         };
+        self.check_no_volume_key_rotation()?;
 
         if encryption_info.key_description().is_some() {
             operation_loop(
@@ -888,53 +1963,417 @@ impl Backstore {
                 ));
             }
         };
+        self.check_no_volume_key_rotation()?;
+
+        if encryption_info.key_description() == Some(key_desc) {
+            Ok(Some(false))
+        } else if encryption_info.key_description().is_some() {
+            // Keys are not the same but key description is present
+            operation_loop(
+                self.blockdevs_mut().into_iter().map(|(_, _, bd)| bd),
+                |blockdev| blockdev.rebind_keyring(key_desc),
+            )?;
+            Ok(Some(true))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Regenerate the Clevis bindings with the block devices in this pool using
+    /// the same configuration.
+    ///
+    /// The method for this rollback caches the initial Clevis metadata and
+    /// reverts all of the devices if there is a failure.
+    ///
+    /// This method returns StratisResult<()> because the Clevis regen command
+    /// will always change the metadata when successful. The command is not idempotent
+    /// so this method will either fail to regenerate the bindings or it will
+    /// result in a metadata change.
+    pub fn rebind_clevis(&mut self) -> StratisResult<()> {
+        let encryption_info = match pool_enc_to_enc!(self.encryption_info()?) {
+            Some(ei) => ei,
+            None => {
+                return Err(StratisError::Msg(
+                    "Requested pool does not appear to be encrypted".to_string(),
+                ));
+            }
+        };
+        self.check_no_volume_key_rotation()?;
+
+        if encryption_info.clevis_info().is_none() {
+            Err(StratisError::Msg(
+                "Requested pool is not already bound to Clevis".to_string(),
+            ))
+        } else {
+            operation_loop(
+                self.blockdevs_mut().into_iter().map(|(_, _, bd)| bd),
+                |blockdev| blockdev.rebind_clevis(),
+            )?;
+
+            Ok(())
+        }
+    }
+
+    /// Bind all devices in the given backstore to an additional passphrase
+    /// using the given key description under the given name.
+    ///
+    /// * Returns Ok(true) if the binding was performed.
+    /// * Returns Ok(false) if the binding had already been previously performed and
+    /// nothing was changed.
+    /// * Returns Err(_) if a binding with the same name but a different key
+    /// description exists, if an inconsistency was found in the metadata across
+    /// devices or binding failed.
+    pub fn add_passphrase_binding(
+        &mut self,
+        name: &str,
+        key_desc: &KeyDescription,
+    ) -> StratisResult<bool> {
+        let bindings = self.consistent_passphrase_bindings()?;
+        self.check_no_volume_key_rotation()?;
+
+        match bindings.get(name) {
+            Some(kd) if kd == key_desc => Ok(false),
+            Some(kd) => Err(StratisError::Msg(format!(
+                "Block devices have already been bound under name {} with key description {}; \
+                    requested key description {} can't be applied",
+                name,
+                kd.as_application_str(),
+                key_desc.as_application_str(),
+            ))),
+            None => {
+                operation_loop(
+                    self.blockdevs_mut().into_iter().map(|(_, _, bd)| bd),
+                    |blockdev| blockdev.add_passphrase_binding(name, key_desc),
+                )?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Unbind all devices in the given backstore from the passphrase bound
+    /// under the given name.
+    ///
+    /// * Returns Ok(true) if the unbinding was performed.
+    /// * Returns Ok(false) if the unbinding had already been previously performed and
+    /// nothing was changed.
+    /// * Returns Err(_) if an inconsistency was found in the metadata across
+    /// devices or unbinding failed.
+    pub fn remove_passphrase_binding(&mut self, name: &str) -> StratisResult<bool> {
+        let bindings = self.consistent_passphrase_bindings()?;
+        self.check_no_volume_key_rotation()?;
+
+        if bindings.contains_key(name) {
+            operation_loop(
+                self.blockdevs_mut().into_iter().map(|(_, _, bd)| bd),
+                |blockdev| blockdev.remove_passphrase_binding(name),
+            )?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Get the named passphrase bindings of an encrypted backstore, returning
+    /// an error if they differ between block devices.
+    fn consistent_passphrase_bindings(&self) -> StratisResult<PassphraseBindings> {
+        match self.passphrase_bindings()? {
+            Some(MaybeInconsistent::No(bindings)) => Ok(bindings),
+            Some(MaybeInconsistent::Yes) => Err(StratisError::Msg(
+                "The passphrase bindings of the block devices in the pool are inconsistent"
+                    .to_string(),
+            )),
+            None => Err(StratisError::Msg(
+                "Requested pool does not appear to be encrypted".to_string(),
+            )),
+        }
+    }
+
+    /// Begin encrypting all unencrypted devices in the backstore in place
+    /// using the given encryption configuration. The pool remains in use
+    /// while the data is encrypted by PoolEncryption::spawn().
+    ///
+    /// * Returns Ok(Some(_)) if the encryption of any device was begun.
+    /// * Returns Ok(None) if the backstore was already encrypted with the
+    /// given configuration.
+    /// * Returns Err(_) if the backstore was already encrypted with a different
+    /// configuration, if the backstore has a cache, a mirrored data tier, or
+    /// integrity protection, or if encryption failed.
+    ///
+    /// The last sectors of each device are taken up by the LUKS2 header, and
+    /// must not be allocated. The encryption of all devices is begun before
+    /// the data of any device is encrypted, so that the pool never consists
+    /// of a mixture of encrypted and unencrypted devices. If beginning the
+    /// encryption fails for any device, the encryption of the devices for
+    /// which it was begun is undone. Once it has been begun for all devices,
+    /// the LUKS2 headers are in place and the cap device uses the activated
+    /// crypt devices, so the metadata can be written before the data is
+    /// encrypted. If encrypting the data is interrupted, it is resumed when
+    /// the pool is next started.
+    ///
+    /// WARNING: metadata changing event
+    pub fn encrypt(
+        &mut self,
+        pool_name: &Name,
+        pool_uuid: PoolUuid,
+        encryption_info: &EncryptionInfo,
+    ) -> StratisResult<Option<PoolEncryption>> {
+        let mut unencrypted = Vec::new();
+        for (uuid, bd) in self.data_tier.blockdevs() {
+            match bd.encryption_info() {
+                None => unencrypted.push(uuid),
+                Some(ei) if ei == encryption_info => (),
+                Some(_) => {
+                    return Err(StratisError::Msg(format!(
+                        "Block device {} is already encrypted with a different configuration than the one requested",
+                        bd.devnode().display()
+                    )));
+                }
+            }
+        }
+
+        if unencrypted.is_empty() {
+            return Ok(None);
+        }
+
+        if self.has_cache() {
+            return Err(StratisError::Msg(
+                "Encrypting a pool with a cache is not supported".to_string(),
+            ));
+        }
+        if self.redundancy() != Redundancy::None {
+            return Err(StratisError::Msg(
+                "Encrypting a pool with a mirrored data tier is not supported".to_string(),
+            ));
+        }
+        if self.has_integrity() {
+            return Err(StratisError::Msg(
+                "Encrypting a pool with integrity protection is not supported".to_string(),
+            ));
+        }
+
+        // Check all devices before changing any so that the common failures
+        // do not leave the pool partially encrypted.
+        for uuid in unencrypted.iter() {
+            if let Some((_, bd)) = self.data_tier.get_blockdev_by_uuid(*uuid) {
+                bd.check_encryption()?;
+            }
+        }
+
+        let mut begun = Vec::new();
+        for uuid in unencrypted {
+            match self.begin_encryption(pool_name, pool_uuid, uuid, encryption_info) {
+                Ok(encryption) => begun.push((uuid, encryption)),
+                Err(causal_error) => {
+                    return Err(match self.abort_encryption(pool_uuid, begun) {
+                        Ok(()) => causal_error,
+                        Err(rollback_error) => StratisError::RollbackError {
+                            causal_error: Box::new(causal_error),
+                            rollback_error: Box::new(rollback_error),
+                            level: ActionAvailability::NoPoolChanges,
+                        },
+                    });
+                }
+            }
+        }
+
+        Ok(Some(PoolEncryption {
+            pool_uuid,
+            reencryptions: begun
+                .into_iter()
+                .map(|(_, encryption)| {
+                    let (handle, keyslot) = encryption.into_handle();
+                    Reencryption::Encryption(handle, keyslot)
+                })
+                .collect(),
+        }))
+    }
+
+    /// Begin the encryption of the data device with the given UUID and
+    /// reload the cap device to use the activated crypt device.
+    fn begin_encryption(
+        &mut self,
+        pool_name: &Name,
+        pool_uuid: PoolUuid,
+        uuid: DevUuid,
+        encryption_info: &EncryptionInfo,
+    ) -> StratisResult<BlockDevEncryption> {
+        // The cap device is suspended while the beginning of the device
+        // is moved and it is activated as a crypt device.
+        if let Some(linear) = self.linear.as_mut() {
+            linear.suspend(get_dm(), DmOptions::default())?;
+        }
+        // Reloading the cap device table resumes the cap device.
+        let res = self
+            .data_tier
+            .begin_encryption(uuid, pool_name.clone(), encryption_info)
+            .and_then(|encryption| {
+                if self.linear.is_none() {
+                    return Ok(encryption);
+                }
+                match self.extend_cap_device(pool_uuid) {
+                    Ok(_) => Ok(encryption),
+                    Err(causal_error) => {
+                        // The cap device table has not been reloaded, so the
+                        // crypt device is not in use.
+                        Err(
+                            match self
+                                .data_tier
+                                .abort_encryption(uuid, &encryption)
+                                .and_then(|_| encryption.deactivate())
+                            {
+                                Ok(()) => causal_error,
+                                Err(rollback_error) => StratisError::RollbackError {
+                                    causal_error: Box::new(causal_error),
+                                    rollback_error: Box::new(rollback_error),
+                                    level: ActionAvailability::NoPoolChanges,
+                                },
+                            },
+                        )
+                    }
+                }
+            });
+
+        if let Some(linear) = self.linear.as_mut() {
+            linear.resume(get_dm())?;
+        }
 
-        if encryption_info.key_description() == Some(key_desc) {
-            Ok(Some(false))
-        } else if encryption_info.key_description().is_some() {
-            // Keys are not the same but key description is present
-            operation_loop(
-                self.blockdevs_mut().into_iter().map(|(_, _, bd)| bd),
-                |blockdev| blockdev.rebind_keyring(key_desc),
-            )?;
-            Ok(Some(true))
-        } else {
-            Ok(None)
+        res
+    }
+
+    /// Undo the encryption of the data devices for which it was begun,
+    /// reload the cap device to use the unencrypted devices again, and
+    /// remove the crypt devices.
+    fn abort_encryption(
+        &mut self,
+        pool_uuid: PoolUuid,
+        begun: Vec<(DevUuid, BlockDevEncryption)>,
+    ) -> StratisResult<()> {
+        if begun.is_empty() {
+            return Ok(());
         }
+
+        if let Some(linear) = self.linear.as_mut() {
+            linear.suspend(get_dm(), DmOptions::default())?;
+        }
+        let res = begun
+            .iter()
+            .try_for_each(|(uuid, encryption)| self.data_tier.abort_encryption(*uuid, encryption))
+            .and_then(|_| {
+                if self.linear.is_some() {
+                    self.extend_cap_device(pool_uuid).map(|_| ())
+                } else {
+                    Ok(())
+                }
+            });
+        if let Some(linear) = self.linear.as_mut() {
+            linear.resume(get_dm())?;
+        }
+        res?;
+
+        begun
+            .into_iter()
+            .try_for_each(|(_, encryption)| encryption.deactivate())
     }
 
-    /// Regenerate the Clevis bindings with the block devices in this pool using
-    /// the same configuration.
-    ///
-    /// The method for this rollback caches the initial Clevis metadata and
-    /// reverts all of the devices if there is a failure.
+    /// Rotate the volume keys of all data devices in the backstore. The data
+    /// is reencrypted with the new volume keys in the background; the progress
+    /// is available through reencryption_progress() while the data is
+    /// reencrypted.
     ///
-    /// This method returns StratisResult<()> because the Clevis regen command
-    /// will always change the metadata when successful. The command is not idempotent
-    /// so this method will either fail to regenerate the bindings or it will
-    /// result in a metadata change.
-    pub fn rebind_clevis(&mut self) -> StratisResult<()> {
-        let encryption_info = match pool_enc_to_enc!(self.encryption_info()?) {
-            Some(ei) => ei,
-            None => {
-                return Err(StratisError::Msg(
-                    "Requested pool does not appear to be encrypted".to_string(),
-                ));
+    /// The rotation of devices for which a rotation was previously begun but
+    /// not completed is resumed rather than begun again. If beginning the
+    /// rotation fails for any device, the rotation of the devices for which
+    /// it was begun is completed the next time the rotation is requested or
+    /// the pool is started.
+    pub fn rotate_volume_key(&mut self, pool_uuid: PoolUuid) -> StratisResult<()> {
+        if pool_enc_to_enc!(self.encryption_info()?).is_none() {
+            return Err(StratisError::Msg(
+                "Requested pool does not appear to be encrypted".to_string(),
+            ));
+        }
+        if reencryption_progress(pool_uuid).is_some() {
+            return Err(StratisError::Msg(
+                "A volume key rotation is already in progress for the requested pool".to_string(),
+            ));
+        }
+
+        let mut handles = Vec::new();
+        for (_, bd) in self.data_tier.blockdevs() {
+            let handle = bd
+                .crypt_handle()
+                .expect("All data devices of an encrypted pool are encrypted");
+            if !handle.volume_key_rotation_in_progress()? {
+                handle.begin_volume_key_rotation()?;
             }
-        };
+            handles.push(handle.clone());
+        }
 
-        if encryption_info.clevis_info().is_none() {
-            Err(StratisError::Msg(
-                "Requested pool is not already bound to Clevis".to_string(),
-            ))
-        } else {
-            operation_loop(
-                self.blockdevs_mut().into_iter().map(|(_, _, bd)| bd),
-                |blockdev| blockdev.rebind_clevis(),
-            )?;
+        spawn_reencryption(
+            pool_uuid,
+            handles
+                .into_iter()
+                .map(Reencryption::VolumeKeyRotation)
+                .collect(),
+        )
+    }
 
-            Ok(())
+    /// Resume the volume key rotation of the data devices for which it was
+    /// interrupted, for example by a crash.
+    fn resume_volume_key_rotation(&self, pool_uuid: PoolUuid) {
+        let handles = self
+            .data_tier
+            .blockdevs()
+            .into_iter()
+            .filter_map(|(_, bd)| bd.crypt_handle())
+            .filter(|handle| match handle.volume_key_rotation_in_progress() {
+                Ok(in_progress) => in_progress,
+                Err(e) => {
+                    warn!(
+                        "Failed to determine whether the volume key rotation of device {} is in progress: {}",
+                        handle.luks2_device_path().display(),
+                        e
+                    );
+                    false
+                }
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if !handles.is_empty() {
+            info!(
+                "Resuming interrupted volume key rotation of pool with UUID {}",
+                pool_uuid
+            );
+            if let Err(e) = spawn_reencryption(
+                pool_uuid,
+                handles
+                    .into_iter()
+                    .map(Reencryption::VolumeKeyRotation)
+                    .collect(),
+            ) {
+                warn!(
+                    "Failed to resume the volume key rotation of pool with UUID {}: {}",
+                    pool_uuid, e
+                );
+            }
+        }
+    }
+
+    /// Return an error if the volume key rotation of any data device has not
+    /// completed. While the data is reencrypted each binding has a keyslot
+    /// for both the old and the new volume key, so the bindings must not be
+    /// changed.
+    fn check_no_volume_key_rotation(&self) -> StratisResult<()> {
+        for (_, bd) in self.data_tier.blockdevs() {
+            if let Some(handle) = bd.crypt_handle() {
+                if handle.volume_key_rotation_in_progress()? {
+                    return Err(StratisError::Msg(format!(
+                        "The volume key rotation of block device {} has not completed",
+                        bd.devnode().display()
+                    )));
+                }
+            }
         }
+        Ok(())
     }
 
     pub fn grow(&mut self, dev: DevUuid) -> StratisResult<bool> {
@@ -970,7 +2409,18 @@ impl Backstore {
             .expect("always exists");
         let cache_tier_bs_summary: Option<BlockSizeSummary> =
             self.block_size_summary(BlockDevTier::Cache);
-        if let Err(err) = data_tier_bs_summary.validate() {
+        let missing = self.data_tier.missing();
+        if !missing.is_empty() {
+            warn!(
+                "Disabling pool changes for this pool: data devices with UUIDs {} are missing",
+                missing
+                    .iter()
+                    .map(|u| u.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            ActionAvailability::NoPoolChanges
+        } else if let Err(err) = data_tier_bs_summary.validate() {
             warn!("Disabling pool changes for this pool: {}", err);
             ActionAvailability::NoPoolChanges
         } else if let Some(Err(err)) = cache_tier_bs_summary.map(|ct| ct.validate()) {
@@ -991,6 +2441,7 @@ impl Backstore {
 impl<'a> Into<Value> for &'a Backstore {
     fn into(self) -> Value {
         json!({
+            "redundancy": self.redundancy().to_string(),
             "blockdevs": {
                 "datadevs": Value::Array(
                     self.datadevs().into_iter().map(|(_, dev)| {
@@ -1087,12 +2538,21 @@ where
 
 #[cfg(test)]
 mod tests {
-    use std::{env, error::Error, fs::OpenOptions, path::Path};
+    use std::{
+        env,
+        error::Error,
+        fs::OpenOptions,
+        io::{Read, Write},
+        path::Path,
+    };
 
     use devicemapper::{CacheDevStatus, DataBlocks, DmOptions, IEC};
 
     use crate::engine::strat_engine::{
-        backstore::devices::{ProcessedPathInfos, UnownedDevices},
+        backstore::{
+            devices::{wipe_blockdevs, ProcessedPathInfos, UnownedDevices},
+            mirror::MIRROR_META_SIZE,
+        },
         cmd,
         metadata::device_identifiers,
         ns::{unshare_mount_namespace, MemoryFilesystem},
@@ -1125,6 +2585,10 @@ mod tests {
                 _ => panic!("impossible; see first assertion"),
             }
         );
+        assert_eq!(
+            backstore.mirror.is_some(),
+            backstore.data_tier.mirror.is_some() && backstore.data_tier.allocated() != Sectors(0)
+        );
         assert!(backstore.next <= backstore.size());
 
         backstore.data_tier.invariant();
@@ -1170,7 +2634,7 @@ mod tests {
             pool_uuid,
             initdatadevs,
             MDADataSize::default(),
-            None,
+            PoolCreateOptions::default(),
         )
         .unwrap();
 
@@ -1178,13 +2642,18 @@ mod tests {
 
         // Allocate space from the backstore so that the cap device is made.
         let transaction = backstore
-            .request_alloc(&[INITIAL_BACKSTORE_ALLOCATION])
+            .request_alloc(&[(INITIAL_BACKSTORE_ALLOCATION, AllocationPurpose::ThinData)])
             .unwrap()
             .unwrap();
         backstore.commit_alloc(pool_uuid, transaction).unwrap();
 
+        let writeback = CacheConfig {
+            mode: CacheMode::Writeback,
+            policy: "smq".to_string(),
+            policy_args: Vec::new(),
+        };
         let cache_uuids = backstore
-            .init_cache(pool_name.clone(), pool_uuid, initcachedevs)
+            .init_cache(pool_name.clone(), pool_uuid, initcachedevs, writeback)
             .unwrap();
 
         invariant(&backstore);
@@ -1215,6 +2684,14 @@ mod tests {
         invariant(&backstore);
         assert_eq!(data_uuids.len(), datadevpaths.len());
 
+        // Growing the origin sub-device reloads the cache device.
+        let transaction = backstore
+            .request_alloc(&[(INITIAL_BACKSTORE_ALLOCATION, AllocationPurpose::ThinData)])
+            .unwrap()
+            .unwrap();
+        backstore.commit_alloc(pool_uuid, transaction).unwrap();
+        invariant(&backstore);
+
         let cache_uuids = backstore
             .add_cachedevs(pool_name, pool_uuid, cachedevs)
             .unwrap();
@@ -1233,11 +2710,30 @@ mod tests {
                 assert_eq!(usage.used_cache, DataBlocks(0));
                 assert_eq!(usage.total_meta, meta_size.metablocks());
                 assert!(usage.total_cache > DataBlocks(0));
+                // The configured mode and policy survive every reload.
+                assert!(status.feature_args.contains(&"writeback".to_string()));
+                assert_eq!(status.policy, "smq");
             }
             CacheDevStatus::Error => panic!("cache status could not be obtained"),
             CacheDevStatus::Fail => panic!("cache is in a failed state"),
         }
 
+        // Setting up the live cache device again, as when stratisd is
+        // restarted, keeps the configured mode and policy.
+        let (origin, _) = make_origin(pool_uuid, &backstore.data_tier, false).unwrap();
+        let cache = make_cache(
+            pool_uuid,
+            backstore.cache_tier.as_ref().unwrap(),
+            origin,
+            false,
+        )
+        .unwrap();
+        let status = cache_status(&cache).unwrap();
+        assert!(status.feature_args.contains(&"writeback".to_string()));
+        assert_eq!(status.policy, "smq");
+        backstore.cache = Some(cache);
+        invariant(&backstore);
+
         backstore.destroy().unwrap();
     }
 
@@ -1276,7 +2772,7 @@ mod tests {
             pool_uuid,
             devices1,
             MDADataSize::default(),
-            None,
+            PoolCreateOptions::default(),
         )
         .unwrap();
 
@@ -1294,7 +2790,7 @@ mod tests {
 
         // Allocate space from the backstore so that the cap device is made.
         let transaction = backstore
-            .request_alloc(&[INITIAL_BACKSTORE_ALLOCATION])
+            .request_alloc(&[(INITIAL_BACKSTORE_ALLOCATION, AllocationPurpose::ThinData)])
             .unwrap()
             .unwrap();
         backstore.commit_alloc(pool_uuid, transaction).unwrap();
@@ -1302,7 +2798,7 @@ mod tests {
         let old_device = backstore.device();
 
         backstore
-            .init_cache(pool_name, pool_uuid, devices2)
+            .init_cache(pool_name, pool_uuid, devices2, CacheConfig::default())
             .unwrap();
 
         for path in paths2 {
@@ -1332,6 +2828,354 @@ mod tests {
         real::test_with_spec(&real::DeviceLimits::AtLeast(2, None, None), test_setup);
     }
 
+    /// Initialize a mirrored backstore, allocate from it, extend it with a
+    /// second pair of devices and allocate from the new devices. Verify that
+    /// the legs of the mirror and their metadata segments are recorded, that
+    /// the backstore can be set up again from the recorded metadata, and
+    /// that it can be set up degraded with the devices of one leg missing.
+    fn test_mirrored_setup(paths: &[&Path]) {
+        assert_eq!(paths.len(), 4);
+
+        let pool_uuid = PoolUuid::new_v4();
+        let pool_name = Name::new("pool_name".to_string());
+
+        assert!(Backstore::initialize(
+            pool_name.clone(),
+            pool_uuid,
+            get_devices(&paths[..3]).unwrap(),
+            MDADataSize::default(),
+            PoolCreateOptions {
+                redundancy: Redundancy::Raid1,
+                ..Default::default()
+            }
+        )
+        .is_err());
+
+        let mut backstore = Backstore::initialize(
+            pool_name.clone(),
+            pool_uuid,
+            get_devices(&paths[..2]).unwrap(),
+            MDADataSize::default(),
+            PoolCreateOptions {
+                redundancy: Redundancy::Raid1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(backstore.redundancy(), Redundancy::Raid1);
+        invariant(&backstore);
+
+        let transaction = backstore
+            .request_alloc(&[(INITIAL_BACKSTORE_ALLOCATION, AllocationPurpose::ThinData)])
+            .unwrap()
+            .unwrap();
+        backstore.commit_alloc(pool_uuid, transaction).unwrap();
+        invariant(&backstore);
+
+        backstore
+            .add_datadevs(
+                pool_name.clone(),
+                pool_uuid,
+                get_devices(&paths[2..]).unwrap(),
+            )
+            .unwrap();
+        let available = backstore.available_in_backstore();
+        let transaction = backstore
+            .request_alloc(&[(available, AllocationPurpose::ThinData)])
+            .unwrap()
+            .unwrap();
+        backstore.commit_alloc(pool_uuid, transaction).unwrap();
+        invariant(&backstore);
+
+        let save = backstore.record();
+        let legs = save.data_tier.blockdev.legs.as_ref().unwrap();
+        assert_eq!(legs[0].len(), 2);
+        assert_eq!(legs[1].len(), 2);
+        assert_eq!(save.data_tier.blockdev.allocs.len(), 4);
+        let leg_sizes = save
+            .data_tier
+            .blockdev
+            .allocs
+            .iter()
+            .map(|allocs| allocs.iter().map(|seg| seg.length).sum::<Sectors>())
+            .collect::<Vec<_>>();
+        assert_eq!(leg_sizes[0], leg_sizes[1]);
+        assert_eq!(leg_sizes[0], backstore.datatier_allocated_size());
+        assert_eq!(leg_sizes[2], MIRROR_META_SIZE);
+        assert_eq!(leg_sizes[3], MIRROR_META_SIZE);
+
+        let datadevs = backstore.teardown().unwrap();
+        let mut backstore =
+            Backstore::setup(pool_uuid, &save, datadevs, Vec::new(), Utc::now()).unwrap();
+        invariant(&backstore);
+        assert_eq!(backstore.redundancy(), Redundancy::Raid1);
+        assert!(backstore.device().is_some());
+        let size = backstore.datatier_allocated_size();
+
+        let (datadevs, mut missing): (Vec<_>, Vec<_>) = backstore
+            .teardown()
+            .unwrap()
+            .into_iter()
+            .partition(|bd| !legs[1].contains(&bd.uuid()));
+        let mut backstore =
+            Backstore::setup(pool_uuid, &save, datadevs, Vec::new(), Utc::now()).unwrap();
+        invariant(&backstore);
+        assert!(backstore.device().is_some());
+        assert_eq!(backstore.datatier_allocated_size(), size);
+        assert_eq!(
+            backstore.action_availability(),
+            ActionAvailability::NoPoolChanges
+        );
+        assert!(backstore
+            .request_alloc(&[(Sectors(1), AllocationPurpose::ThinData)])
+            .is_err());
+        let degraded_save = backstore.record();
+        assert_eq!(
+            degraded_save.data_tier.blockdev.allocs,
+            save.data_tier.blockdev.allocs
+        );
+        assert_eq!(
+            degraded_save.data_tier.blockdev.devs.len(),
+            save.data_tier.blockdev.devs.len()
+        );
+
+        backstore.destroy().unwrap();
+        wipe_blockdevs(&mut missing).unwrap();
+    }
+
+    #[test]
+    fn loop_test_mirrored_setup() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Exactly(4, None),
+            test_mirrored_setup,
+        );
+    }
+
+    #[test]
+    fn real_test_mirrored_setup() {
+        real::test_with_spec(
+            &real::DeviceLimits::Exactly(4, None, None),
+            test_mirrored_setup,
+        );
+    }
+
+    /// Initialize an encrypted backstore, bind it to a named passphrase and
+    /// add a cache. Verify that the cache devices are bound to the named
+    /// passphrase as well, so that named passphrases can still be bound and
+    /// unbound once the pool has a cache.
+    fn test_passphrase_bindings_with_cache(paths: &[&Path]) {
+        fn test_bindings(
+            paths: &[&Path],
+            key_desc: &KeyDescription,
+            named_key_desc: &KeyDescription,
+        ) -> Result<(), Box<dyn Error>> {
+            unshare_mount_namespace()?;
+            let _memfs = MemoryFilesystem::new()?;
+            let (cachepaths, datapaths) = paths.split_at(1);
+
+            let pool_uuid = PoolUuid::new_v4();
+            let pool_name = Name::new("pool_name".to_string());
+            let mut backstore = Backstore::initialize(
+                pool_name.clone(),
+                pool_uuid,
+                get_devices(datapaths)?,
+                MDADataSize::default(),
+                PoolCreateOptions {
+                    encryption_info: Some(&EncryptionInfo::KeyDesc(key_desc.clone())),
+                    ..Default::default()
+                },
+            )?;
+
+            if !backstore.add_passphrase_binding("recovery", named_key_desc)? {
+                return Err(Box::new(StratisError::Msg(
+                    "Passphrase binding test failed".to_string(),
+                )));
+            }
+
+            let transaction = backstore
+                .request_alloc(&[(INITIAL_BACKSTORE_ALLOCATION, AllocationPurpose::ThinData)])?
+                .ok_or_else(|| StratisError::Msg("Allocation failed".to_string()))?;
+            backstore.commit_alloc(pool_uuid, transaction)?;
+
+            backstore.init_cache(
+                pool_name,
+                pool_uuid,
+                get_devices(cachepaths)?,
+                CacheConfig::default(),
+            )?;
+            invariant(&backstore);
+
+            match backstore.passphrase_bindings()? {
+                Some(MaybeInconsistent::No(bindings))
+                    if bindings.get("recovery") == Some(named_key_desc) => {}
+                _ => {
+                    return Err(Box::new(StratisError::Msg(
+                        "Cache devices were not bound to the named passphrase".to_string(),
+                    )));
+                }
+            }
+
+            if !backstore.remove_passphrase_binding("recovery")? {
+                return Err(Box::new(StratisError::Msg(
+                    "Passphrase unbinding test failed".to_string(),
+                )));
+            }
+
+            if !backstore.add_passphrase_binding("operator", named_key_desc)? {
+                return Err(Box::new(StratisError::Msg(
+                    "Passphrase binding test with cache failed".to_string(),
+                )));
+            }
+
+            match backstore.passphrase_bindings()? {
+                Some(MaybeInconsistent::No(bindings))
+                    if bindings.keys().collect::<Vec<_>>() == vec!["operator"] => {}
+                _ => {
+                    return Err(Box::new(StratisError::Msg(
+                        "Passphrase bindings are inconsistent across the pool".to_string(),
+                    )));
+                }
+            }
+
+            backstore.destroy()?;
+            Ok(())
+        }
+
+        crypt::insert_and_cleanup_two_keys(paths, test_bindings);
+    }
+
+    #[test]
+    fn loop_test_passphrase_bindings_with_cache() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Exactly(2, None),
+            test_passphrase_bindings_with_cache,
+        );
+    }
+
+    #[test]
+    fn real_test_passphrase_bindings_with_cache() {
+        real::test_with_spec(
+            &real::DeviceLimits::Exactly(2, None, None),
+            test_passphrase_bindings_with_cache,
+        );
+    }
+
+    /// Initialize an unencrypted backstore and write data to its cap device.
+    /// Begin the encryption of its devices and undo it again, as is done if
+    /// beginning the encryption of any device fails. Verify that the devices
+    /// are no longer encrypted, that the data is unchanged, and that the
+    /// devices still belong to the pool.
+    fn test_abort_encryption(paths: &[&Path]) {
+        fn test_abort(paths: &[&Path], key_desc: &KeyDescription) -> Result<(), Box<dyn Error>> {
+            unshare_mount_namespace()?;
+            let _memfs = MemoryFilesystem::new()?;
+
+            let pool_uuid = PoolUuid::new_v4();
+            let pool_name = Name::new("pool_name".to_string());
+            let mut backstore = Backstore::initialize(
+                pool_name.clone(),
+                pool_uuid,
+                get_devices(paths)?,
+                MDADataSize::default(),
+                PoolCreateOptions::default(),
+            )?;
+
+            let transaction = backstore
+                .request_alloc(&[(INITIAL_BACKSTORE_ALLOCATION, AllocationPurpose::ThinData)])?
+                .ok_or_else(|| StratisError::Msg("Allocation failed".to_string()))?;
+            backstore.commit_alloc(pool_uuid, transaction)?;
+
+            let cap_path = backstore
+                .linear
+                .as_ref()
+                .expect("space has been allocated")
+                .devnode();
+            let data = vec![0xa5u8; 4096];
+            let mut f = OpenOptions::new().write(true).open(&cap_path)?;
+            f.write_all(&data)?;
+            f.sync_all()?;
+
+            let encryption_info = EncryptionInfo::KeyDesc(key_desc.clone());
+            let mut begun = Vec::new();
+            for uuid in backstore
+                .data_tier
+                .blockdevs()
+                .into_iter()
+                .map(|(uuid, _)| uuid)
+                .collect::<Vec<_>>()
+            {
+                let encryption =
+                    backstore.begin_encryption(&pool_name, pool_uuid, uuid, &encryption_info)?;
+                begun.push((uuid, encryption));
+            }
+            if backstore.encryption_info()?.is_none() {
+                return Err(Box::new(StratisError::Msg(
+                    "Devices were not encrypted".to_string(),
+                )));
+            }
+            invariant(&backstore);
+
+            backstore.abort_encryption(pool_uuid, begun)?;
+            invariant(&backstore);
+
+            if backstore.encryption_info()?.is_some() {
+                return Err(Box::new(StratisError::Msg(
+                    "Encryption of devices was not undone".to_string(),
+                )));
+            }
+
+            let mut read = vec![0u8; data.len()];
+            OpenOptions::new()
+                .read(true)
+                .open(&cap_path)?
+                .read_exact(&mut read)?;
+            if read != data {
+                return Err(Box::new(StratisError::Msg(
+                    "Data changed when the encryption was undone".to_string(),
+                )));
+            }
+
+            for path in paths {
+                if CryptHandle::setup(path)?.is_some() {
+                    return Err(Box::new(StratisError::Msg(format!(
+                        "Device {} still has a LUKS2 header",
+                        path.display()
+                    ))));
+                }
+                if device_identifiers(&mut OpenOptions::new().read(true).open(path)?)?
+                    .map(|ids| ids.pool_uuid)
+                    != Some(pool_uuid)
+                {
+                    return Err(Box::new(StratisError::Msg(format!(
+                        "Device {} no longer belongs to the pool",
+                        path.display()
+                    ))));
+                }
+            }
+
+            backstore.destroy()?;
+            Ok(())
+        }
+
+        crypt::insert_and_cleanup_key(paths, test_abort);
+    }
+
+    #[test]
+    fn loop_test_abort_encryption() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(1, 3, None),
+            test_abort_encryption,
+        );
+    }
+
+    #[test]
+    fn real_test_abort_encryption() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(1, None, None),
+            test_abort_encryption,
+        );
+    }
+
     fn test_clevis_initialize(paths: &[&Path]) {
         unshare_mount_namespace().unwrap();
         let pool_name = Name::new("pool_name".to_string());
@@ -1342,10 +3186,13 @@ mod tests {
             pool_uuid,
             get_devices(paths).unwrap(),
             MDADataSize::default(),
-            Some(&EncryptionInfo::ClevisInfo((
-                "tang".to_string(),
-                json!({"url": env::var("TANG_URL").unwrap(), "stratis:tang:trust_url": true}),
-            ))),
+            PoolCreateOptions {
+                encryption_info: Some(&EncryptionInfo::ClevisInfo((
+                    "tang".to_string(),
+                    json!({"url": env::var("TANG_URL").unwrap(), "stratis:tang:trust_url": true}),
+                ))),
+                ..Default::default()
+            },
         )
         .unwrap();
         cmd::udev_settle().unwrap();
@@ -1406,13 +3253,16 @@ mod tests {
                 pool_uuid,
                 get_devices(paths).unwrap(),
                 MDADataSize::default(),
-                Some(&EncryptionInfo::Both(
-                    key_desc.clone(),
-                    (
-                        "tang".to_string(),
-                        json!({"url": env::var("TANG_URL")?, "stratis:tang:trust_url": true}),
-                    ),
-                )),
+                PoolCreateOptions {
+                    encryption_info: Some(&EncryptionInfo::Both(
+                        key_desc.clone(),
+                        (
+                            "tang".to_string(),
+                            json!({"url": env::var("TANG_URL")?, "stratis:tang:trust_url": true}),
+                        ),
+                    )),
+                    ..Default::default()
+                },
             )?;
             cmd::udev_settle()?;
 
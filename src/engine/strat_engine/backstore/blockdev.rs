@@ -11,6 +11,7 @@ use std::{
 };
 
 use chrono::{DateTime, Utc};
+use libc::c_uint;
 use serde_json::Value;
 
 use devicemapper::{Device, Sectors};
@@ -21,8 +22,9 @@ use crate::{
         engine::{BlockDev, DumpState},
         strat_engine::{
             backstore::{
-                crypt::CryptHandle,
-                devices::BlockSizes,
+                crypt::{CryptHandle, CryptInitializer, PendingEncryption},
+                devices::{get_devno_from_path, BlockSizes},
+                integrity::IntegrityDev,
                 range_alloc::{PerDevSegments, RangeAllocator},
                 transaction::RequestTransaction,
             },
@@ -35,8 +37,8 @@ use crate::{
             types::BDAResult,
         },
         types::{
-            Compare, DevUuid, DevicePath, EncryptionInfo, KeyDescription, Name, PoolUuid,
-            StateDiff, StratBlockDevDiff,
+            Compare, DevUuid, DevicePath, EncryptionConfig, EncryptionInfo, KeyDescription, Name,
+            PassphraseBindings, PoolUuid, StateDiff, StratBlockDevDiff,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -78,6 +80,43 @@ impl UnderlyingDevice {
     }
 }
 
+/// The device which presents the data of a blockdev to upper layers: the
+/// device on which the Stratis metadata is written or, if the blockdev is
+/// protected by dm-integrity, the dm-integrity device on top of it.
+#[derive(Debug)]
+pub enum DataDevice {
+    Plain(Device),
+    Integrity(IntegrityDev),
+}
+
+/// The encryption in place of a block device, begun by
+/// StratBlockDev::begin_encryption(), whose data has not yet been encrypted.
+pub struct BlockDevEncryption {
+    pending: PendingEncryption,
+    // The path of the device before the encryption was begun.
+    physical_path: DevicePath,
+    // The size of the device before the encryption was begun.
+    orig_size: Sectors,
+}
+
+impl BlockDevEncryption {
+    /// Remove the activated crypt device of an encryption undone by
+    /// StratBlockDev::abort_encryption().
+    ///
+    /// Precondition: No device refers to the activated crypt device.
+    pub fn deactivate(self) -> StratisResult<()> {
+        self.pending.deactivate()
+    }
+
+    /// Give up the possibility of undoing the encryption, returning the
+    /// handle for the activated crypt device and the temporary keyslot, with
+    /// which the data is encrypted by CryptHandle::resume_encryption(). The
+    /// device may be in use while its data is encrypted.
+    pub fn into_handle(self) -> (CryptHandle, c_uint) {
+        self.pending.into_handle()
+    }
+}
+
 #[derive(Debug)]
 pub struct StratBlockDev {
     dev: Device,
@@ -85,7 +124,9 @@ pub struct StratBlockDev {
     used: RangeAllocator,
     user_info: Option<String>,
     hardware_info: Option<String>,
+    tags: Vec<String>,
     underlying_device: UnderlyingDevice,
+    integrity: Option<IntegrityDev>,
     new_size: Option<Sectors>,
     blksizes: BlockSizes,
 }
@@ -93,7 +134,8 @@ pub struct StratBlockDev {
 impl StratBlockDev {
     /// Make a new BlockDev from the parameters.
     /// Allocate space for the Stratis metadata on the device.
-    /// - dev: the device, identified by number
+    /// - data_device: the device, identified by number, or its dm-integrity
+    ///                protection
     /// - devnode: for encrypted devices, the logical and physical
     ///            paths; for unencrypted devices, the physical path
     /// - bda: the device's BDA
@@ -103,17 +145,18 @@ impl StratBlockDev {
     /// - key_description: optional argument enabling encryption using
     ///                    the specified key in the kernel keyring
     /// Returns an error if it is impossible to allocate all segments on the
-    /// device.
+    /// device; in that case the dm-integrity devices are torn down.
     /// NOTE: It is possible that the actual device size is greater than
     /// the recorded device size. In that case, the additional space available
     /// on the device is simply invisible to the blockdev. Consequently, it
     /// is invisible to the engine, and is not part of the total size value
-    /// reported on the D-Bus.
+    /// reported on the D-Bus. If the device is protected by dm-integrity,
+    /// the space taken up by the dm-integrity metadata is likewise invisible.
     ///
     /// Precondition: segments in other_segments do not overlap with Stratis
     /// metadata region.
     pub fn new(
-        dev: Device,
+        data_device: DataDevice,
         bda: BDA,
         other_segments: &[(Sectors, Sectors)],
         user_info: Option<String>,
@@ -124,9 +167,29 @@ impl StratBlockDev {
         let mut segments = vec![(Sectors(0), bda.extended_size().sectors())];
         segments.extend(other_segments);
 
-        let allocator = match RangeAllocator::new(bda.dev_size(), &segments) {
+        let (dev, size, integrity) = match data_device {
+            DataDevice::Plain(dev) => (dev, bda.dev_size(), None),
+            DataDevice::Integrity(integrity) => (
+                integrity.device(),
+                BlockdevSize::new(integrity.size()),
+                Some(integrity),
+            ),
+        };
+
+        let allocator = match RangeAllocator::new(size, &segments) {
             Ok(a) => a,
-            Err(e) => return Err((e, bda)),
+            Err(e) => {
+                if let Some(mut integrity) = integrity {
+                    if let Err(err) = integrity.teardown() {
+                        warn!(
+                            "Failed to deactivate dm-integrity devices of device with UUID {}: {}",
+                            bda.dev_uuid(),
+                            err
+                        );
+                    }
+                }
+                return Err((e, bda));
+            }
         };
 
         Ok(StratBlockDev {
@@ -135,7 +198,9 @@ impl StratBlockDev {
             used: allocator,
             user_info,
             hardware_info,
+            tags: Vec::new(),
             underlying_device,
+            integrity,
             new_size: None,
             blksizes,
         })
@@ -143,7 +208,8 @@ impl StratBlockDev {
 
     /// Returns the blockdev's Device. For unencrypted devices, this is the physical,
     /// unencrypted device. For encrypted devices, this is the logical, unlocked
-    /// device on top of LUKS2.
+    /// device on top of LUKS2. For devices protected by dm-integrity, this is
+    /// the device which presents the dm-integrity device to upper layers.
     ///
     /// Practically, this is the device number that should be used when constructing
     /// the cap device.
@@ -182,6 +248,10 @@ impl StratBlockDev {
     ///               self.devnode.physical_path() has been encrypted with
     ///               aes-xts-plain64 encryption.
     pub fn disown(&mut self) -> StratisResult<()> {
+        if let Some(ref mut integrity) = self.integrity {
+            integrity.teardown()?;
+            self.integrity = None;
+        }
         if let Some(ref mut handle) = self.underlying_device.crypt_handle_mut() {
             handle.wipe()?;
         } else {
@@ -228,6 +298,12 @@ impl StratBlockDev {
         self.used.commit(segs);
     }
 
+    /// Release space committed by commit_space(), so that it can be
+    /// allocated again.
+    pub fn release_space(&mut self, ranges: &[(Sectors, Sectors)]) -> StratisResult<()> {
+        self.used.release(ranges)
+    }
+
     // ALL SIZE METHODS (except size(), which is in BlockDev impl.)
     /// The number of Sectors on this device used by Stratis for metadata
     pub fn metadata_size(&self) -> BDAExtendedSize {
@@ -245,6 +321,13 @@ impl StratBlockDev {
         self.bda.dev_size()
     }
 
+    /// The number of Sectors on this device that can be allocated, including
+    /// those used by Stratis for metadata. This is less than the total size
+    /// if the device is protected by dm-integrity.
+    pub fn usable_size(&self) -> BlockdevSize {
+        self.used.size()
+    }
+
     /// The maximum size of variable length metadata that can be accommodated.
     /// self.max_metadata_size() < self.metadata_size()
     pub fn max_metadata_size(&self) -> MDADataSize {
@@ -264,6 +347,17 @@ impl StratBlockDev {
         set_blockdev_user_info!(self; user_info)
     }
 
+    /// Set the tags on this blockdev.
+    /// Returns true if the tags were changed, otherwise false.
+    pub fn set_tags(&mut self, tags: &[String]) -> bool {
+        set_blockdev_tags!(self; tags)
+    }
+
+    /// Whether this blockdev carries the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
     /// Get the physical path for a block device.
     pub fn devnode(&self) -> &Path {
         self.underlying_device.physical_path()
@@ -276,6 +370,20 @@ impl StratBlockDev {
             .map(|ch| ch.encryption_info())
     }
 
+    /// Get the settings of the encryption of the block device, if encrypted.
+    pub fn encryption_config(&self) -> Option<&EncryptionConfig> {
+        self.underlying_device
+            .crypt_handle()
+            .map(|ch| ch.encryption_config())
+    }
+
+    /// Get the named passphrase bindings of the block device, if encrypted.
+    pub fn passphrase_bindings(&self) -> Option<&PassphraseBindings> {
+        self.underlying_device
+            .crypt_handle()
+            .map(|ch| ch.passphrase_bindings())
+    }
+
     /// Get the pool name for the given block device.
     ///
     /// Returns:
@@ -296,6 +404,16 @@ impl StratBlockDev {
         self.blksizes
     }
 
+    /// Whether the device is protected by dm-integrity.
+    pub fn has_integrity(&self) -> bool {
+        self.integrity.is_some()
+    }
+
+    /// The handle for the encrypted device, if the device is encrypted.
+    pub fn crypt_handle(&self) -> Option<&CryptHandle> {
+        self.underlying_device.crypt_handle()
+    }
+
     /// Bind encrypted device using the given clevis configuration.
     pub fn bind_clevis(&mut self, pin: &str, clevis_info: &Value) -> StratisResult<()> {
         let crypt_handle = self.underlying_device.crypt_handle_mut().ok_or_else(|| {
@@ -339,6 +457,27 @@ impl StratBlockDev {
         crypt_handle.rebind_keyring(key_desc)
     }
 
+    /// Bind a block device to an additional passphrase, represented by a key
+    /// description in the kernel keyring, under the given name.
+    pub fn add_passphrase_binding(
+        &mut self,
+        name: &str,
+        key_desc: &KeyDescription,
+    ) -> StratisResult<()> {
+        let crypt_handle = self.underlying_device.crypt_handle_mut().ok_or_else(|| {
+            StratisError::Msg("This device does not appear to be encrypted".to_string())
+        })?;
+        crypt_handle.add_passphrase_binding(name, key_desc)
+    }
+
+    /// Unbind a block device from the passphrase bound under the given name.
+    pub fn remove_passphrase_binding(&mut self, name: &str) -> StratisResult<()> {
+        let crypt_handle = self.underlying_device.crypt_handle_mut().ok_or_else(|| {
+            StratisError::Msg("This device does not appear to be encrypted".to_string())
+        })?;
+        crypt_handle.remove_passphrase_binding(name)
+    }
+
     /// Regenerate the Clevis bindings for a block device.
     pub fn rebind_clevis(&mut self) -> StratisResult<()> {
         let crypt_handle = self.underlying_device.crypt_handle_mut().ok_or_else(|| {
@@ -444,6 +583,12 @@ impl StratBlockDev {
             }
         }
 
+        if self.integrity.is_some() {
+            return Err(StratisError::Msg(
+                "Growing a device that is protected by dm-integrity is not supported".to_string(),
+            ));
+        }
+
         let size = BlockdevSize::new(Self::scan_blkdev_size(
             self.physical_path(),
             self.underlying_device.crypt_handle().is_some(),
@@ -478,6 +623,155 @@ impl StratBlockDev {
         }
     }
 
+    /// Check that the device can be encrypted in place and return the size
+    /// of the device once it has been encrypted. The size is reduced by
+    /// twice the size of the crypt metadata; the sectors that are cut off
+    /// must not be allocated. This is more than is required by the LUKS2
+    /// header of an encrypted device, so the device can be grown afterwards.
+    pub fn check_encryption(&self) -> StratisResult<Sectors> {
+        if self.underlying_device.crypt_handle().is_some() {
+            return Err(StratisError::Msg(format!(
+                "Device {} is already encrypted",
+                self.devnode().display()
+            )));
+        }
+        if self.integrity.is_some() {
+            return Err(StratisError::Msg(
+                "Encrypting a device that is protected by dm-integrity is not supported"
+                    .to_string(),
+            ));
+        }
+
+        let physical_size = blkdev_size(&File::open(self.physical_path())?)?.sectors();
+        let reserved = (2u64 * crypt_metadata_size()).sectors();
+        if physical_size < reserved + self.metadata_size().sectors() {
+            return Err(StratisError::Msg(format!(
+                "Device {} is too small to be encrypted in place",
+                self.devnode().display()
+            )));
+        }
+        let new_size = physical_size - reserved;
+        if self.used.allocated_end() > new_size {
+            return Err(StratisError::Msg(format!(
+                "The last {} of device {} must not be allocated to encrypt it in place",
+                reserved,
+                self.devnode().display()
+            )));
+        }
+        Ok(new_size)
+    }
+
+    /// Begin encrypting the unencrypted device in place. Once this method
+    /// returns, the device number of the blockdev is that of the activated
+    /// crypt device, through which the contents of the device are visible
+    /// unchanged, and the reduced size of the device is recorded in its
+    /// static header. The data is encrypted once
+    /// BlockDevEncryption::into_handle() has been called; until then, the
+    /// encryption can be undone by abort_encryption().
+    ///
+    /// Precondition: No IO is issued to the device until this method returns.
+    pub fn begin_encryption(
+        &mut self,
+        pool_name: Name,
+        encryption_info: &EncryptionInfo,
+    ) -> StratisResult<BlockDevEncryption> {
+        let new_size = self.check_encryption()?;
+        // If the device has grown, the space beyond the recorded size is
+        // not allocated and need not be excluded.
+        let orig_size = self.used.size().sectors();
+        if new_size < orig_size {
+            self.used.decrease_size(new_size)?;
+        }
+
+        let physical_path = DevicePath::new(self.physical_path())?;
+        let pending =
+            match CryptInitializer::new(physical_path.clone(), self.pool_uuid(), self.uuid())
+                .initialize_in_place(
+                    pool_name,
+                    encryption_info.key_description(),
+                    encryption_info.clevis_info(),
+                ) {
+                Ok(pending) => pending,
+                Err(e) => {
+                    if new_size < orig_size {
+                        self.used.increase_size(orig_size);
+                    }
+                    return Err(e);
+                }
+            };
+
+        self.underlying_device = UnderlyingDevice::Encrypted(pending.handle().clone());
+        let encryption = BlockDevEncryption {
+            pending,
+            physical_path,
+            orig_size,
+        };
+
+        let res = get_devno_from_path(self.metadata_path()).and_then(|dev| {
+            self.dev = dev;
+            if new_size < orig_size {
+                self.write_blkdev_size(BlockdevSize::new(new_size))
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(causal_error) = res {
+            return Err(
+                match self
+                    .abort_encryption(&encryption)
+                    .and_then(|_| encryption.deactivate())
+                {
+                    Ok(()) => causal_error,
+                    Err(rollback_error) => StratisError::NoActionRollbackError {
+                        causal_error: Box::new(causal_error),
+                        rollback_error: Box::new(rollback_error),
+                    },
+                },
+            );
+        }
+
+        Ok(encryption)
+    }
+
+    /// Undo the encryption of a device whose encryption was begun by
+    /// begin_encryption(), restoring the unencrypted device and its size.
+    /// The activated crypt device must be removed with
+    /// BlockDevEncryption::deactivate() once it is no longer in use.
+    ///
+    /// Precondition: No IO is issued to the device until this method returns.
+    pub fn abort_encryption(&mut self, encryption: &BlockDevEncryption) -> StratisResult<()> {
+        // The static header is part of the first segment of the device,
+        // which is copied back from the activated device.
+        if self.bda.dev_size().sectors() != encryption.orig_size {
+            self.write_blkdev_size(BlockdevSize::new(encryption.orig_size))?;
+        }
+        encryption.pending.restore_first_segment()?;
+
+        self.dev = get_devno_from_path(&encryption.physical_path)?;
+        self.underlying_device = UnderlyingDevice::Unencrypted(encryption.physical_path.clone());
+        if self.used.size().sectors() < encryption.orig_size {
+            self.used.increase_size(encryption.orig_size);
+        }
+        Ok(())
+    }
+
+    /// Record the given size of the device in its static header.
+    fn write_blkdev_size(&mut self, size: BlockdevSize) -> StratisResult<()> {
+        let mut f = OpenOptions::new()
+            .write(true)
+            .read(true)
+            .open(self.metadata_path())?;
+        let mut h = static_header(&mut f)?.ok_or_else(|| {
+            StratisError::Msg(format!(
+                "No static header found on device {}",
+                self.metadata_path().display()
+            ))
+        })?;
+        h.blkdev_size = size;
+        self.bda.header = StaticHeader::write_header(&mut f, h, MetadataLocation::Both)?;
+        Ok(())
+    }
+
     /// Rename pool in metadata if it is encrypted.
     pub fn rename_pool(&mut self, pool_name: Name) -> StratisResult<()> {
         match self.underlying_device.crypt_handle_mut() {
@@ -488,12 +782,24 @@ impl StratBlockDev {
 
     #[cfg(test)]
     pub fn invariant(&self) {
-        assert!(self.total_size() == self.used.size());
+        if self.integrity.is_some() {
+            assert!(self.total_size() >= self.used.size());
+        } else {
+            assert!(self.total_size() == self.used.size());
+        }
     }
 
-    /// If a pool is encrypted, tear down the cryptsetup devicemapper devices on the
-    /// physical device.
+    /// Tear down the dm-integrity devicemapper devices on the device if it is
+    /// protected by dm-integrity. If a pool is encrypted, tear down the
+    /// cryptsetup devicemapper devices on the physical device.
     pub fn teardown(&mut self) -> StratisResult<()> {
+        if let Some(ref mut integrity) = self.integrity {
+            debug!(
+                "Deactivating dm-integrity devices of device with UUID {}",
+                self.bda.dev_uuid()
+            );
+            integrity.teardown()?;
+        }
         if let Some(ch) = self.underlying_device.crypt_handle() {
             debug!(
                 "Deactivating unlocked encrypted device with UUID {}",
@@ -534,6 +840,8 @@ impl<'a> Into<Value> for &'a StratBlockDev {
             Value::from(self.blksizes.to_string()),
         );
         map.insert("in_use".to_string(), Value::from(self.in_use()));
+        map.insert("integrity".to_string(), Value::from(self.has_integrity()));
+        map.insert("tags".to_string(), Value::from(self.tags.clone()));
         json
     }
 }
@@ -570,6 +878,17 @@ impl BlockDev for StratBlockDev {
     fn new_size(&self) -> Option<Sectors> {
         self.new_size
     }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn integrity_mismatches(&self) -> StratisResult<Option<u64>> {
+        self.integrity
+            .as_ref()
+            .map(|integrity| integrity.mismatches())
+            .transpose()
+    }
 }
 
 impl Recordable<BaseBlockDevSave> for StratBlockDev {
@@ -578,6 +897,8 @@ impl Recordable<BaseBlockDevSave> for StratBlockDev {
             uuid: self.uuid(),
             user_info: self.user_info.clone(),
             hardware_info: self.hardware_info.clone(),
+            integrity: self.integrity.as_ref().map(|_| true),
+            tags: self.tags.clone(),
         }
     }
 }
@@ -4,9 +4,9 @@
 
 // Code to handle a collection of block devices.
 
-use std::collections::HashMap;
 #[cfg(test)]
 use std::collections::HashSet;
+use std::{cmp::min, collections::HashMap};
 
 use chrono::{DateTime, Duration, Utc};
 use rand::{seq::IteratorRandom, thread_rng};
@@ -15,7 +15,7 @@ use devicemapper::{Bytes, Device, Sectors};
 
 use crate::{
     engine::{
-        shared::gather_encryption_info,
+        shared::{gather_encryption_config, gather_encryption_info, gather_passphrase_bindings},
         strat_engine::{
             backstore::{
                 blockdev::StratBlockDev,
@@ -28,13 +28,35 @@ use crate::{
             serde_structs::{BaseBlockDevSave, Recordable},
             shared::{bds_to_bdas, can_unlock},
         },
-        types::{DevUuid, EncryptionInfo, Name, PoolEncryptionInfo, PoolUuid},
+        types::{
+            AllocationPolicy, DevUuid, EncryptionConfig, EncryptionInfo, MaybeInconsistent, Name,
+            PassphraseBindings, PoolEncryptionInfo, PoolUuid,
+        },
     },
     stratis::{StratisError, StratisResult},
 };
 
 const MAX_NUM_TO_WRITE: usize = 10;
 
+/// Bind each of the newly initialized block devices to the given named
+/// passphrases. If binding fails, wipe the block devices.
+fn bind_passphrases(bds: &mut [StratBlockDev], bindings: &PassphraseBindings) -> StratisResult<()> {
+    if let Err(causal_error) = bds.iter_mut().try_for_each(|bd| {
+        bindings
+            .iter()
+            .try_for_each(|(name, key_desc)| bd.add_passphrase_binding(name, key_desc))
+    }) {
+        if let Err(rollback_error) = wipe_blockdevs(bds) {
+            return Err(StratisError::NoActionRollbackError {
+                causal_error: Box::new(causal_error),
+                rollback_error: Box::new(rollback_error),
+            });
+        }
+        return Err(causal_error);
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct BlockDevMgr {
     /// All the block devices that belong to this block dev manager.
@@ -57,12 +79,16 @@ impl BlockDevMgr {
     }
 
     /// Initialize a new StratBlockDevMgr with specified pool and devices.
+    /// If integrity is true, each device is protected by dm-integrity.
+    /// Encrypted devices are formatted according to encryption_config.
     pub fn initialize(
         pool_name: Name,
         pool_uuid: PoolUuid,
         devices: UnownedDevices,
         mda_data_size: MDADataSize,
+        integrity: bool,
         encryption_info: Option<&EncryptionInfo>,
+        encryption_config: Option<&EncryptionConfig>,
     ) -> StratisResult<BlockDevMgr> {
         Ok(BlockDevMgr::new(
             initialize_devices(
@@ -70,12 +96,21 @@ impl BlockDevMgr {
                 pool_name,
                 pool_uuid,
                 mda_data_size,
+                integrity,
                 encryption_info,
+                encryption_config,
             )?,
             None,
         ))
     }
 
+    /// Bind all the block devices, which must have just been initialized, to
+    /// the given named passphrases. If binding fails, the block devices are
+    /// wiped.
+    pub fn bind_passphrases(&mut self, bindings: &PassphraseBindings) -> StratisResult<()> {
+        bind_passphrases(&mut self.block_devs, bindings)
+    }
+
     /// Convert the BlockDevMgr into a collection of BDAs.
     pub fn into_bdas(self) -> HashMap<DevUuid, BDA> {
         bds_to_bdas(self.block_devs)
@@ -123,17 +158,41 @@ impl BlockDevMgr {
             }
         }
 
+        // New devices are protected by dm-integrity only if the devices
+        // already being managed are.
+        let integrity = self.block_devs.iter().any(|bd| bd.has_integrity());
+
+        // New devices are encrypted with the same settings as the devices
+        // already being managed.
+        let encryption_config = self
+            .block_devs
+            .get(0)
+            .and_then(|bd| bd.encryption_config())
+            .cloned();
+
+        // New devices are bound to the same named passphrases as the devices
+        // already being managed.
+        let passphrase_bindings = self
+            .block_devs
+            .get(0)
+            .and_then(|bd| bd.passphrase_bindings())
+            .cloned()
+            .unwrap_or_default();
+
         // FIXME: This is a bug. If new devices are added to a pool, and the
         // variable length metadata requires more than the minimum allocated,
         // then the necessary amount must be provided or the data can not be
         // saved.
-        let bds = initialize_devices(
+        let mut bds = initialize_devices(
             devices,
             pool_name,
             pool_uuid,
             MDADataSize::default(),
+            integrity,
             encryption_info.as_ref(),
+            encryption_config.as_ref(),
         )?;
+        bind_passphrases(&mut bds, &passphrase_bindings)?;
         let bdev_uuids = bds.iter().map(|bd| bd.uuid()).collect();
         self.block_devs.extend(bds);
         Ok(bdev_uuids)
@@ -152,31 +211,42 @@ impl BlockDevMgr {
     /// If a specified blockdev is not found, returns an error and does nothing.
     ///
     /// NOTE: This method traverses the block_devs Vec from the rear to the
-    /// front, looking for blockdevs to remove. This is algorithmically
-    /// inefficient, unless it is assumed that the blockdevs specified are very
-    /// near the end of the Vec, which is expected to be the case. In that case,
-    /// the algorithm is O(n).
+    /// front, looking for blockdevs to remove, since blockdevs being removed
+    /// on a failed addition are at the end of the Vec.
     pub(super) fn remove_blockdevs(&mut self, uuids: &[DevUuid]) -> StratisResult<()> {
+        let mut removed = self.detach_blockdevs(uuids)?;
+        wipe_blockdevs(&mut removed)
+    }
+
+    /// Remove the blockdevs specified by uuids from the manager without
+    /// wiping them and return them, so that they can be wiped once nothing
+    /// refers to them any more.
+    pub(super) fn detach_blockdevs(
+        &mut self,
+        uuids: &[DevUuid],
+    ) -> StratisResult<Vec<StratBlockDev>> {
+        if let Some(uuid) = uuids
+            .iter()
+            .find(|uuid| !self.block_devs.iter().any(|bd| bd.uuid() == **uuid))
+        {
+            return Err(StratisError::Msg(format!(
+                "Blockdev corresponding to UUID: {uuid} not found."
+            )));
+        }
+
         let mut removed = Vec::new();
         for uuid in uuids {
-            let mut found = false;
-            let blockdevs_last_index = self.block_devs.len() - 1;
-            for i in 0..blockdevs_last_index {
-                let index = blockdevs_last_index - i;
-                if self.block_devs[index].uuid() == *uuid {
-                    removed.push(self.block_devs.swap_remove(index));
-                    found = true;
-                    break;
-                }
-            }
-            if !found {
-                return Err(StratisError::Msg(format!(
-                    "Blockdev corresponding to UUID: {uuid} not found."
-                )));
+            if let Some(index) = self.block_devs.iter().rposition(|bd| bd.uuid() == *uuid) {
+                removed.push(self.block_devs.swap_remove(index));
             }
         }
-        wipe_blockdevs(&mut removed)?;
-        Ok(())
+        Ok(removed)
+    }
+
+    /// Return blockdevs which were removed by detach_blockdevs to the
+    /// manager, e.g., because the operation which removed them failed.
+    pub(super) fn reattach_blockdevs(&mut self, blockdevs: Vec<StratBlockDev>) {
+        self.block_devs.extend(blockdevs);
     }
 
     /// Allocate space according to sizes vector request.
@@ -185,14 +255,172 @@ impl BlockDevMgr {
     /// This method is atomic, it either allocates all requested or allocates
     /// nothing.
     pub fn request_space(&self, sizes: &[Sectors]) -> StratisResult<Option<RequestTransaction>> {
+        self.request_space_excluding(sizes, &[])
+    }
+
+    /// Allocate space for each request, a size and the tag of the block
+    /// devices from which the space should preferably be allocated, with
+    /// the given policy.
+    /// Return the segments allocated for each request, or None if it was
+    /// not possible to satisfy the request.
+    /// This method is atomic, it either allocates all requested or allocates
+    /// nothing.
+    pub fn request_space_by_policy(
+        &self,
+        requests: &[(Sectors, Option<&str>)],
+        policy: AllocationPolicy,
+    ) -> StratisResult<Option<RequestTransaction>> {
         let mut transaction = RequestTransaction::default();
 
-        let total_needed: Sectors = sizes.iter().cloned().sum();
-        if self.avail_space() < total_needed {
-            return Ok(None);
+        let candidates = self.block_devs.iter().collect::<Vec<_>>();
+
+        if Self::request_space_from(&candidates, requests, policy, 0, &mut transaction)? {
+            Ok(Some(transaction))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Allocate space according to sizes vector request, considering only
+    /// the block devices whose UUIDs are not in exclude.
+    /// Return the segments allocated for each request, or None if it was
+    /// not possible to satisfy the request from the remaining devices.
+    /// This method is atomic, it either allocates all requested or allocates
+    /// nothing.
+    pub fn request_space_excluding(
+        &self,
+        sizes: &[Sectors],
+        exclude: &[DevUuid],
+    ) -> StratisResult<Option<RequestTransaction>> {
+        let mut transaction = RequestTransaction::default();
+
+        let candidates = self
+            .block_devs
+            .iter()
+            .filter(|bd| !exclude.contains(&bd.uuid()))
+            .collect::<Vec<_>>();
+        let requests = sizes.iter().map(|size| (*size, None)).collect::<Vec<_>>();
+
+        if Self::request_space_from(
+            &candidates,
+            &requests,
+            AllocationPolicy::FillFirst,
+            0,
+            &mut transaction,
+        )? {
+            Ok(Some(transaction))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Allocate space for each request twice, once from the block devices
+    /// of each leg of a mirror, with the given policy. The segments for
+    /// request i allocated from the first leg are found at index i of the
+    /// transaction, those allocated from the second leg at index
+    /// requests.len() + i.
+    /// Return None if it was not possible to satisfy the request from the
+    /// devices of both legs.
+    /// This method is atomic, it either allocates all requested or allocates
+    /// nothing.
+    pub fn request_space_mirrored(
+        &self,
+        requests: &[(Sectors, Option<&str>)],
+        policy: AllocationPolicy,
+        legs: &[Vec<DevUuid>; 2],
+    ) -> StratisResult<Option<RequestTransaction>> {
+        let mut transaction = RequestTransaction::default();
+
+        for (leg_idx, leg) in legs.iter().enumerate() {
+            let candidates = self
+                .block_devs
+                .iter()
+                .filter(|bd| leg.contains(&bd.uuid()))
+                .collect::<Vec<_>>();
+
+            if !Self::request_space_from(
+                &candidates,
+                requests,
+                policy,
+                leg_idx * requests.len(),
+                &mut transaction,
+            )? {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(transaction))
+    }
+
+    /// The number of sectors on the block device which are neither allocated
+    /// nor already requested in the transaction.
+    fn available_in_transaction(bd: &StratBlockDev, transaction: &RequestTransaction) -> Sectors {
+        bd.available()
+            - transaction
+                .get_blockdevmgr()
+                .iter()
+                .filter(|seg| seg.uuid == bd.uuid())
+                .map(|seg| seg.segment.length)
+                .sum::<Sectors>()
+    }
+
+    /// Add segments from the candidate block devices satisfying the
+    /// requests to the transaction. The segments for request i are
+    /// recorded at index first_idx + i of the transaction.
+    ///
+    /// If a request specifies a tag, the candidates which carry the tag are
+    /// considered before the others. With the fill-first and prefer-tagged
+    /// policies, space is taken from each candidate in turn until the
+    /// request is satisfied. With the spread policy, the request is divided
+    /// as evenly as possible among all the candidates with space available.
+    ///
+    /// Return false, leaving the transaction unchanged, if the candidates do
+    /// not have enough space available.
+    fn request_space_from(
+        candidates: &[&StratBlockDev],
+        requests: &[(Sectors, Option<&str>)],
+        policy: AllocationPolicy,
+        first_idx: usize,
+        transaction: &mut RequestTransaction,
+    ) -> StratisResult<bool> {
+        let total_needed: Sectors = requests.iter().map(|(size, _)| *size).sum();
+        if candidates.iter().map(|bd| bd.available()).sum::<Sectors>() < total_needed {
+            return Ok(false);
         }
 
-        for (idx, &needed) in sizes.iter().enumerate() {
+        for (idx, &(needed, tag)) in requests.iter().enumerate() {
+            let mut ordered = candidates.to_vec();
+            if let Some(tag) = tag {
+                ordered.sort_by_key(|bd| !bd.has_tag(tag));
+            }
+
+            // The maximum amount to take from each candidate, in the order
+            // in which the candidates are to be considered.
+            let plan = match policy {
+                AllocationPolicy::Spread => {
+                    let mut available = ordered
+                        .iter()
+                        .map(|bd| (*bd, Self::available_in_transaction(bd, transaction)))
+                        .filter(|(_, avail)| *avail > Sectors(0))
+                        .collect::<Vec<_>>();
+                    available.sort_by_key(|(_, avail)| *avail);
+
+                    let num_devs = available.len();
+                    let mut remaining = needed;
+                    let mut plan = Vec::new();
+                    for (i, (bd, avail)) in available.into_iter().enumerate() {
+                        let ways = (num_devs - i) as u64;
+                        let share = min(avail, Sectors((*remaining + ways - 1) / ways));
+                        remaining -= share;
+                        plan.push((bd, share));
+                    }
+                    plan
+                }
+                AllocationPolicy::FillFirst | AllocationPolicy::PreferTagged => {
+                    ordered.into_iter().map(|bd| (bd, needed)).collect()
+                }
+            };
+
             let mut alloc = Sectors(0);
             // TODO: Consider greater efficiency for allocation generally.
             // Over time, the blockdevs at the start will be exhausted. It
@@ -201,15 +429,15 @@ impl BlockDevMgr {
             // In the context of this major inefficiency that ensues over time
             // the obvious but more minor inefficiency of this inner loop is
             // not worth worrying about.
-            for bd in &self.block_devs {
+            for (bd, limit) in plan {
                 if alloc == needed {
                     break;
                 }
 
-                let r_segs = bd.request_space(needed - alloc, &transaction)?;
+                let r_segs = bd.request_space(min(limit, needed - alloc), transaction)?;
                 for (&start, &length) in r_segs.iter() {
                     transaction.add_bd_seg_req(
-                        idx,
+                        first_idx + idx,
                         BlkDevSegment::new(bd.uuid(), Segment::new(*bd.device(), start, length)),
                     );
                 }
@@ -218,7 +446,7 @@ impl BlockDevMgr {
             assert_eq!(alloc, needed);
         }
 
-        Ok(Some(transaction))
+        Ok(true)
     }
 
     /// Commit the allocations calculated by the request_space() method.
@@ -240,7 +468,7 @@ impl BlockDevMgr {
                             .expect(
                                 "Block dev was determined to be present during allocation request",
                             )
-                            .total_size()
+                            .usable_size()
                             .sectors(),
                     );
                     segs.insert(&(seg.segment.start, seg.segment.length))?;
@@ -260,6 +488,30 @@ impl BlockDevMgr {
         Ok(())
     }
 
+    /// Release the space occupied by the specified segments, which was
+    /// committed by commit_space(), so that it can be allocated again.
+    pub fn release_space(&mut self, segments: &[BlkDevSegment]) -> StratisResult<()> {
+        let mut ranges = HashMap::<DevUuid, Vec<(Sectors, Sectors)>>::new();
+        for seg in segments {
+            ranges
+                .entry(seg.uuid)
+                .or_default()
+                .push((seg.segment.start, seg.segment.length));
+        }
+
+        for (uuid, ranges) in ranges {
+            self.get_mut_blockdev_by_uuid(uuid)
+                .ok_or_else(|| {
+                    StratisError::Msg(format!(
+                        "Block device with UUID {uuid} from which space is to be released was not found"
+                    ))
+                })?
+                .release_space(&ranges)?;
+        }
+
+        Ok(())
+    }
+
     /// Write the given data to all blockdevs marking with current time.
     /// Return an error if data was not written to any blockdev.
     /// Omit blockdevs which do not have sufficient space in BDA to accommodate
@@ -335,7 +587,7 @@ impl BlockDevMgr {
     pub fn size(&self) -> Sectors {
         self.block_devs
             .iter()
-            .map(|b| b.total_size().sectors())
+            .map(|b| b.usable_size().sectors())
             .sum()
     }
 
@@ -357,6 +609,22 @@ impl BlockDevMgr {
         .expect("Cannot create a pool out of both encrypted and unencrypted devices")
     }
 
+    pub fn encryption_config(&self) -> Option<MaybeInconsistent<EncryptionConfig>> {
+        gather_encryption_config(
+            self.block_devs.len(),
+            self.block_devs.iter().map(|bd| bd.encryption_config()),
+        )
+        .expect("Cannot create a pool out of both encrypted and unencrypted devices")
+    }
+
+    pub fn passphrase_bindings(&self) -> Option<MaybeInconsistent<PassphraseBindings>> {
+        gather_passphrase_bindings(
+            self.block_devs.len(),
+            self.block_devs.iter().map(|bd| bd.passphrase_bindings()),
+        )
+        .expect("Cannot create a pool out of both encrypted and unencrypted devices")
+    }
+
     pub fn is_encrypted(&self) -> bool {
         self.encryption_info().is_some()
     }
@@ -461,9 +729,16 @@ mod tests {
         let pool_uuid = PoolUuid::new_v4();
         let pool_name = Name::new("pool_name".to_string());
         let devices = get_devices(paths).unwrap();
-        let mut mgr =
-            BlockDevMgr::initialize(pool_name, pool_uuid, devices, MDADataSize::default(), None)
-                .unwrap();
+        let mut mgr = BlockDevMgr::initialize(
+            pool_name,
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            false,
+            None,
+            None,
+        )
+        .unwrap();
         assert_eq!(mgr.avail_space() + mgr.metadata_size(), mgr.size());
 
         let allocated = Sectors(2);
@@ -491,6 +766,75 @@ mod tests {
         );
     }
 
+    /// Verify that with the prefer-tagged policy space is allocated from the
+    /// tagged device even though it is not the first device, and that with
+    /// the spread policy a request is divided evenly between the devices.
+    fn test_blockdevmgr_policy(paths: &[&Path]) {
+        let pool_uuid = PoolUuid::new_v4();
+        let pool_name = Name::new("pool_name".to_string());
+        let devices = get_devices(paths).unwrap();
+        let mut mgr = BlockDevMgr::initialize(
+            pool_name,
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            false,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let tagged = {
+            let (uuid, bd) = mgr.blockdevs_mut().pop().unwrap();
+            assert!(bd.set_tags(&["fast".to_string()]));
+            uuid
+        };
+
+        let transaction = mgr
+            .request_space_by_policy(
+                &[(Sectors(2), Some("fast"))],
+                AllocationPolicy::PreferTagged,
+            )
+            .unwrap()
+            .unwrap();
+        assert!(transaction
+            .get_blockdevmgr()
+            .iter()
+            .all(|seg| seg.uuid == tagged));
+
+        let transaction = mgr
+            .request_space_by_policy(&[(Sectors(20), None)], AllocationPolicy::Spread)
+            .unwrap()
+            .unwrap();
+        for (uuid, _) in mgr.blockdevs() {
+            assert_eq!(
+                transaction
+                    .get_blockdevmgr()
+                    .iter()
+                    .filter(|seg| seg.uuid == uuid)
+                    .map(|seg| seg.segment.length)
+                    .sum::<Sectors>(),
+                Sectors(20 / paths.len() as u64)
+            );
+        }
+    }
+
+    #[test]
+    fn loop_test_blockdevmgr_policy() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Exactly(2, None),
+            test_blockdevmgr_policy,
+        );
+    }
+
+    #[test]
+    fn real_test_blockdevmgr_policy() {
+        real::test_with_spec(
+            &real::DeviceLimits::Exactly(2, None, None),
+            test_blockdevmgr_policy,
+        );
+    }
+
     /// Test that the `BlockDevMgr` will add devices if the same key
     /// is used to encrypted the existing devices and the added devices.
     fn test_blockdevmgr_same_key(paths: &[&Path]) {
@@ -506,7 +850,9 @@ mod tests {
                 pool_uuid,
                 devices1,
                 MDADataSize::default(),
+                false,
                 Some(&EncryptionInfo::KeyDesc(key_desc.clone())),
+                None,
             )?;
 
             if bdm.add(pool_name, pool_uuid, devices2).is_err() {
@@ -554,7 +900,9 @@ mod tests {
                 pool_uuid,
                 devices1,
                 MDADataSize::default(),
+                false,
                 Some(&EncryptionInfo::KeyDesc(key_desc.clone())),
+                None,
             )?;
 
             crypt::change_key(key_desc)?;
@@ -606,6 +954,8 @@ mod tests {
             uuid,
             get_devices(paths1).unwrap(),
             MDADataSize::default(),
+            false,
+            None,
             None,
         )
         .unwrap();
@@ -634,6 +984,8 @@ mod tests {
             uuid,
             get_devices(paths2).unwrap(),
             MDADataSize::default(),
+            false,
+            None,
             None,
         )
         .unwrap();
@@ -21,7 +21,7 @@ use crate::{
             serde_structs::{BaseDevSave, BlockDevSave, CacheTierSave, Recordable},
             types::BDARecordResult,
         },
-        types::{BlockDevTier, DevUuid, Name, PoolUuid},
+        types::{BlockDevTier, CacheConfig, DevUuid, Name, PoolUuid},
     },
     stratis::{StratisError, StratisResult},
 };
@@ -45,6 +45,8 @@ pub struct CacheTier {
     /// The list of segments granted by block_mgr and used by the metadata
     /// device.
     pub(super) meta_segments: AllocatedAbove,
+    /// The mode and policy of the cache device.
+    pub(super) config: CacheConfig,
 }
 
 impl CacheTier {
@@ -89,6 +91,7 @@ impl CacheTier {
             block_mgr,
             cache_segments,
             meta_segments,
+            config: cache_tier_save.config.clone().unwrap_or_default(),
         })
     }
 
@@ -141,13 +144,77 @@ impl CacheTier {
         Ok((uuids, (true, false)))
     }
 
-    /// Setup a new CacheTier struct from the block_mgr.
+    /// Remove the blockdevs specified by uuids from the tier. The segments
+    /// allocated from the blockdevs are dropped from the cache sub-device.
+    /// Returns true if the cache sub-device's segments were changed, together
+    /// with the removed blockdevs, which are not wiped; the caller must wipe
+    /// them once the metadata no longer refers to them.
+    /// WARNING: metadata changing event
+    ///
+    /// Return an error if any of the blockdevs holds part of the meta
+    /// sub-device or if removing the blockdevs would leave no space for the
+    /// cache sub-device.
+    ///
+    /// Precondition: The cache device does not depend on any data stored on
+    /// the cache sub-device, i.e., it has been cleaned and invalidated.
+    pub fn remove(&mut self, uuids: &[DevUuid]) -> StratisResult<(bool, Vec<StratBlockDev>)> {
+        if let Some(seg) = self
+            .meta_segments
+            .inner
+            .iter()
+            .find(|seg| uuids.contains(&seg.uuid))
+        {
+            return Err(StratisError::Msg(format!(
+                "Device with UUID {} holds the cache metadata and can not be removed from the cache tier",
+                seg.uuid
+            )));
+        }
+
+        let cache_segments = self
+            .cache_segments
+            .inner
+            .iter()
+            .filter(|seg| !uuids.contains(&seg.uuid))
+            .cloned()
+            .collect::<Vec<_>>();
+        if cache_segments.is_empty() {
+            return Err(StratisError::Msg(
+                "Removing the requested devices would leave no space for the cache sub-device"
+                    .to_string(),
+            ));
+        }
+
+        let removed = self.block_mgr.detach_blockdevs(uuids)?;
+
+        let changed = cache_segments.len() != self.cache_segments.inner.len();
+        self.cache_segments = AllocatedAbove {
+            inner: cache_segments,
+        };
+        Ok((changed, removed))
+    }
+
+    /// Undo remove by returning the removed blockdevs to the tier and
+    /// restoring the segments of the cache sub-device which were in use
+    /// before.
+    pub fn revert_remove(
+        &mut self,
+        removed: Vec<StratBlockDev>,
+        cache_segments: Vec<BlkDevSegment>,
+    ) {
+        self.block_mgr.reattach_blockdevs(removed);
+        self.cache_segments = AllocatedAbove {
+            inner: cache_segments,
+        };
+    }
+
+    /// Setup a new CacheTier struct from the block_mgr. The cache device will
+    /// use the mode and policy specified by config.
     ///
     /// Returns an error if the block devices passed would make the cache
     /// sub-device too big.
     ///
     /// WARNING: metadata changing event
-    pub fn new(mut block_mgr: BlockDevMgr) -> StratisResult<CacheTier> {
+    pub fn new(mut block_mgr: BlockDevMgr, config: CacheConfig) -> StratisResult<CacheTier> {
         let avail_space = block_mgr.avail_space();
 
         // FIXME: Come up with a better way to choose metadata device size
@@ -187,6 +254,7 @@ impl CacheTier {
             block_mgr,
             cache_segments,
             meta_segments,
+            config,
         })
     }
 
@@ -263,7 +331,9 @@ impl Recordable<CacheTierSave> for CacheTier {
             blockdev: BlockDevSave {
                 allocs: vec![self.cache_segments.record(), self.meta_segments.record()],
                 devs: self.block_mgr.record(),
+                legs: None,
             },
+            config: Some(self.config.clone()),
         }
     }
 }
@@ -274,7 +344,7 @@ mod tests {
     use std::path::Path;
 
     use crate::engine::strat_engine::{
-        backstore::devices::{ProcessedPathInfos, UnownedDevices},
+        backstore::devices::{wipe_blockdevs, ProcessedPathInfos, UnownedDevices},
         metadata::MDADataSize,
         tests::{loopbacked, real},
     };
@@ -309,11 +379,13 @@ mod tests {
             pool_uuid,
             devices1,
             MDADataSize::default(),
+            false,
+            None,
             None,
         )
         .unwrap();
 
-        let mut cache_tier = CacheTier::new(mgr).unwrap();
+        let mut cache_tier = CacheTier::new(mgr, CacheConfig::default()).unwrap();
         cache_tier.invariant();
 
         // A cache tier w/ some devices and everything promptly allocated to
@@ -354,4 +426,73 @@ mod tests {
     fn real_cache_test_add() {
         real::test_with_spec(&real::DeviceLimits::AtLeast(2, None, None), cache_test_add);
     }
+
+    /// Make a new cache, then remove the devices which do not hold any part
+    /// of the meta sub-device and verify that the cache sub-device shrinks
+    /// accordingly. Verify that removing the device which holds the meta
+    /// sub-device is an error.
+    fn cache_test_remove(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let pool_uuid = PoolUuid::new_v4();
+        let pool_name = Name::new("pool_name".to_string());
+
+        let devices = get_devices(paths).unwrap();
+
+        let mgr = BlockDevMgr::initialize(
+            pool_name,
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            false,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let mut cache_tier = CacheTier::new(mgr, CacheConfig::default()).unwrap();
+        cache_tier.invariant();
+
+        let meta_uuids = cache_tier.meta_segments.uuids();
+        let (meta_devs, other_devs): (Vec<_>, Vec<_>) = cache_tier
+            .blockdevs()
+            .iter()
+            .map(|(uuid, _)| *uuid)
+            .partition(|uuid| meta_uuids.contains(uuid));
+
+        assert!(cache_tier.remove(&meta_devs).is_err());
+        cache_tier.invariant();
+
+        let cache_size = cache_tier.cache_segments.size();
+        let (changed, mut removed) = cache_tier.remove(&other_devs).unwrap();
+        assert!(changed);
+        assert_eq!(removed.len(), other_devs.len());
+        cache_tier.invariant();
+        wipe_blockdevs(&mut removed).unwrap();
+
+        assert_eq!(cache_tier.blockdevs().len(), meta_devs.len());
+        assert!(cache_tier.cache_segments.size() < cache_size);
+        assert_eq!(
+            cache_tier.block_mgr.size() - cache_tier.block_mgr.metadata_size(),
+            cache_tier.cache_segments.size() + cache_tier.meta_segments.size()
+        );
+
+        cache_tier.destroy().unwrap();
+    }
+
+    #[test]
+    fn loop_cache_test_remove() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            cache_test_remove,
+        );
+    }
+
+    #[test]
+    fn real_cache_test_remove() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, None, None),
+            cache_test_remove,
+        );
+    }
 }
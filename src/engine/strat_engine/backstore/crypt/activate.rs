@@ -19,7 +19,7 @@ use crate::{
             },
             cmd::clevis_decrypt,
         },
-        types::UnlockMethod,
+        types::{SizedKeyMemory, UnlockMethod},
     },
     stratis::{StratisError, StratisResult},
 };
@@ -105,12 +105,18 @@ impl CryptActivationHandle {
     /// * is a LUKS2 device
     /// * has a valid Stratis LUKS2 token
     /// * has a token of the proper type for LUKS2 keyring unlocking
+    ///
+    /// If a passphrase is given with the keyring unlock method, it is used
+    /// to activate the device directly and the kernel keyring is not consulted.
     pub fn setup(
         physical_path: &Path,
         unlock_method: UnlockMethod,
+        passphrase: Option<&SizedKeyMemory>,
     ) -> StratisResult<Option<CryptHandle>> {
         match setup_crypt_device(physical_path)? {
-            Some(ref mut device) => setup_crypt_handle(device, physical_path, Some(unlock_method)),
+            Some(ref mut device) => {
+                setup_crypt_handle(device, physical_path, Some(unlock_method), passphrase)
+            }
             None => Ok(None),
         }
     }
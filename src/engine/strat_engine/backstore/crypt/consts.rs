@@ -2,7 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use libc::c_uint;
+use libc::{c_int, c_uint};
 
 use devicemapper::{Bytes, IEC};
 
@@ -13,11 +13,22 @@ pub const STRATIS_TOKEN_DEVNAME_KEY: &str = "activation_name";
 pub const STRATIS_TOKEN_POOL_UUID_KEY: &str = "pool_uuid";
 pub const STRATIS_TOKEN_DEV_UUID_KEY: &str = "device_uuid";
 pub const STRATIS_TOKEN_POOLNAME_KEY: &str = "pool_name";
+pub const TOKEN_KEY_DESCRIPTION_KEY: &str = "key_description";
+pub const TOKEN_BINDING_NAME_KEY: &str = "stratis_binding_name";
 
 pub const STRATIS_TOKEN_ID: c_uint = 0;
 pub const LUKS2_TOKEN_ID: c_uint = 1;
 pub const CLEVIS_LUKS_TOKEN_ID: c_uint = 2;
 
+/// Token slots from this one up to LUKS2_TOKENS_MAX hold the LUKS2 keyring
+/// tokens of the named passphrase bindings of a device.
+pub const FIRST_NAMED_TOKEN_ID: c_uint = 3;
+/// Number of token slots in the LUKS2 header.
+pub const LUKS2_TOKENS_MAX: c_uint = 32;
+
+/// Value of a keyslot argument to libcryptsetup that matches any keyslot.
+pub const CRYPT_ANY_SLOT: c_int = -1;
+
 pub const LUKS2_TOKEN_TYPE: &str = "luks2-keyring";
 pub const STRATIS_TOKEN_TYPE: &str = "stratis";
 
@@ -25,6 +36,10 @@ pub const STRATIS_TOKEN_TYPE: &str = "stratis";
 /// each block device.
 pub const STRATIS_MEK_SIZE: usize = 512 / 8;
 
+/// The cipher and mode used if the encryption configuration of a pool does
+/// not specify any.
+pub const DEFAULT_CIPHER: (&str, &str) = ("aes", "xts-plain64");
+
 /// Sector size as defined in the LUKS2 specification documentation.
 pub const LUKS2_SECTOR_SIZE: Bytes = Bytes(4096);
 
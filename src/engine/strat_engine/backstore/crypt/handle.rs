@@ -3,7 +3,10 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::{
+    cell::RefCell,
     fmt::Debug,
+    fs::File,
+    io::Read,
     iter::once,
     path::{Path, PathBuf},
 };
@@ -11,11 +14,18 @@ use std::{
 use either::Either;
 use serde_json::Value;
 
-use devicemapper::{Device, Sectors};
+use devicemapper::{Bytes, Device, Sectors};
 use libcryptsetup_rs::{
-    c_uint,
-    consts::{flags::CryptActivate, vals::EncryptionFormat},
-    CryptDevice, TokenInput,
+    c_int, c_uint,
+    consts::{
+        flags::{CryptActivate, CryptReencrypt, CryptVolumeKey},
+        vals::{
+            CryptReencryptDirectionInfo, CryptReencryptInfo, CryptReencryptModeInfo,
+            EncryptionFormat, KeyslotInfo,
+        },
+    },
+    get_sector_size, CryptDevice, CryptKeyslotHandle, CryptParamsLuks2, CryptParamsReencrypt,
+    SafeMemHandle, TokenInput,
 };
 
 use crate::{
@@ -23,12 +33,16 @@ use crate::{
         strat_engine::{
             backstore::{
                 crypt::{
-                    consts::{CLEVIS_LUKS_TOKEN_ID, LUKS2_TOKEN_ID},
+                    consts::{CLEVIS_LUKS_TOKEN_ID, CRYPT_ANY_SLOT, LUKS2_TOKEN_ID},
+                    initialize::in_place_params,
                     metadata_handle::CryptMetadataHandle,
                     shared::{
-                        acquire_crypt_device, add_keyring_keyslot, clevis_info_from_metadata,
+                        acquire_crypt_device, add_keyring_keyslot, add_named_keyring_keyslot,
+                        clevis_info_from_metadata, encryption_config_from_metadata,
                         ensure_inactive, ensure_wiped, get_keyslot_number, interpret_clevis_config,
-                        read_key, replace_pool_name, setup_crypt_device, setup_crypt_handle,
+                        key_desc_to_passphrase, named_keyring_tokens, read_key,
+                        remove_named_keyring_keyslot, replace_pool_name, set_pbkdf,
+                        setup_crypt_device, setup_crypt_handle,
                     },
                 },
                 devices::get_devno_from_path,
@@ -38,11 +52,57 @@ use crate::{
             keys::MemoryPrivateFilesystem,
             metadata::StratisIdentifiers,
         },
-        types::{DevicePath, EncryptionInfo, KeyDescription, Name, SizedKeyMemory},
+        types::{
+            DevicePath, EncryptionConfig, EncryptionInfo, KeyDescription, Name, PassphraseBindings,
+            SizedKeyMemory,
+        },
     },
     stratis::{StratisError, StratisResult},
 };
 
+thread_local! {
+    // libcryptsetup does not pass a user pointer to the progress callback
+    // of crypt_reencrypt(), so the closure that records the progress of the
+    // reencryption running on this thread is kept here.
+    static REENCRYPT_PROGRESS: RefCell<Option<Box<dyn FnMut(Bytes, Bytes)>>> = RefCell::new(None);
+}
+
+fn reencrypt_progress(size: u64, offset: u64, _: Option<&mut ()>) -> c_int {
+    REENCRYPT_PROGRESS.with(|progress| {
+        if let Some(ref mut f) = *progress.borrow_mut() {
+            f(Bytes(u128::from(offset)), Bytes(u128::from(size)));
+        }
+    });
+    0
+}
+
+c_progress_callback!(c_reencrypt_progress, (), reencrypt_progress);
+
+/// Parameters for reencrypting the data of a device to a new volume key
+/// while the device is active.
+fn rotation_params(flags: CryptReencrypt, sector_size: u32) -> CryptParamsReencrypt {
+    CryptParamsReencrypt {
+        mode: CryptReencryptModeInfo::Reencrypt,
+        direction: CryptReencryptDirectionInfo::Forward,
+        resilience: "checksum".to_string(),
+        hash: "sha256".to_string(),
+        data_shift: 0,
+        max_hotzone_size: 0,
+        device_size: 0,
+        luks2: CryptParamsLuks2 {
+            pbkdf: None,
+            integrity: None,
+            integrity_params: None,
+            data_alignment: 0,
+            data_device: None,
+            sector_size,
+            label: None,
+            subsystem: None,
+        },
+        flags,
+    }
+}
+
 /// Handle for performing operations on an encrypted device.
 ///
 /// This device assumes that its logical, unlocked device path has been activated and
@@ -64,14 +124,18 @@ impl CryptHandle {
         physical_path: DevicePath,
         identifiers: StratisIdentifiers,
         encryption_info: EncryptionInfo,
+        encryption_config: EncryptionConfig,
         activation_name: String,
         pool_name: Option<Name>,
     ) -> StratisResult<CryptHandle> {
         let device = get_devno_from_path(&physical_path)?;
+        // A newly formatted device has no named passphrase bindings.
         CryptHandle::new_with_metadata_handle(CryptMetadataHandle::new(
             physical_path,
             identifiers,
             encryption_info,
+            encryption_config,
+            PassphraseBindings::new(),
             activation_name,
             pool_name,
             device,
@@ -114,7 +178,7 @@ impl CryptHandle {
     /// * has a token of the proper type for LUKS2 keyring unlocking
     pub fn setup(physical_path: &Path) -> StratisResult<Option<CryptHandle>> {
         match setup_crypt_device(physical_path)? {
-            Some(ref mut device) => setup_crypt_handle(device, physical_path, None),
+            Some(ref mut device) => setup_crypt_handle(device, physical_path, None, None),
             None => Ok(None),
         }
     }
@@ -124,6 +188,16 @@ impl CryptHandle {
         self.metadata_handle.encryption_info()
     }
 
+    /// Get the settings of the encryption of this encrypted device.
+    pub fn encryption_config(&self) -> &EncryptionConfig {
+        self.metadata_handle.encryption_config()
+    }
+
+    /// Get the named passphrase bindings of this encrypted device.
+    pub fn passphrase_bindings(&self) -> &PassphraseBindings {
+        self.metadata_handle.passphrase_bindings()
+    }
+
     /// Return the path to the device node of the underlying storage device
     /// for the encrypted device.
     pub fn luks2_device_path(&self) -> &Path {
@@ -299,6 +373,7 @@ impl CryptHandle {
             )
         })?;
 
+        set_pbkdf(&mut device, self.encryption_config())?;
         add_keyring_keyslot(&mut device, key_desc, Some(Either::Left(key)))?;
 
         self.metadata_handle.encryption_info = self
@@ -306,6 +381,7 @@ impl CryptHandle {
             .encryption_info
             .clone()
             .set_key_desc(key_desc.clone());
+        self.metadata_handle.encryption_config = encryption_config_from_metadata(&mut device)?;
         Ok(())
     }
 
@@ -338,6 +414,7 @@ impl CryptHandle {
             .encryption_info
             .clone()
             .unset_key_desc();
+        self.metadata_handle.encryption_config = encryption_config_from_metadata(&mut device)?;
 
         Ok(())
     }
@@ -351,6 +428,7 @@ impl CryptHandle {
             .ok_or_else(|| {
                 StratisError::Msg("Cannot change passphrase because this device is not bound to a passphrase in the kernel keyring".to_string())
             })?;
+        set_pbkdf(&mut device, self.encryption_config())?;
         add_keyring_keyslot(
             &mut device,
             new_key_desc,
@@ -364,12 +442,280 @@ impl CryptHandle {
         Ok(())
     }
 
+    /// Add a named passphrase binding for the passphrase with the given key
+    /// description to the underlying LUKS2 volume.
+    pub fn add_passphrase_binding(
+        &mut self,
+        name: &str,
+        key_desc: &KeyDescription,
+    ) -> StratisResult<()> {
+        let mut device = self.acquire_crypt_device()?;
+        let pass = self.existing_passphrase(&mut device)?;
+
+        set_pbkdf(&mut device, self.encryption_config())?;
+        add_named_keyring_keyslot(&mut device, name, key_desc, &pass)?;
+
+        self.metadata_handle
+            .passphrase_bindings
+            .insert(name.to_string(), key_desc.clone());
+        Ok(())
+    }
+
+    /// Remove the named passphrase binding from the underlying LUKS2 volume.
+    pub fn remove_passphrase_binding(&mut self, name: &str) -> StratisResult<()> {
+        let mut device = self.acquire_crypt_device()?;
+        if !remove_named_keyring_keyslot(&mut device, name)? {
+            return Err(StratisError::Msg(format!(
+                "No passphrase binding named {} was found on device {}",
+                name,
+                self.luks2_device_path().display()
+            )));
+        }
+
+        self.metadata_handle.passphrase_bindings.remove(name);
+        Ok(())
+    }
+
     /// Rename the pool in the LUKS2 token.
     pub fn rename_pool_in_metadata(&mut self, pool_name: Name) -> StratisResult<()> {
         let mut device = self.acquire_crypt_device()?;
         replace_pool_name(&mut device, pool_name)
     }
 
+    /// Begin the rotation of the volume key of the device.
+    ///
+    /// A new volume key is generated and a keyslot for the new volume key is
+    /// added for every binding of the device, after which the reencryption of
+    /// the data is recorded in the LUKS2 metadata. The data itself is
+    /// reencrypted by resume_volume_key_rotation().
+    ///
+    /// The passphrases of all bindings must be available, as the keyslots
+    /// for the old volume key are removed once the data has been reencrypted.
+    pub fn begin_volume_key_rotation(&self) -> StratisResult<()> {
+        let mut device = self.acquire_crypt_device()?;
+
+        let mut passphrases = Vec::new();
+        if let Some(kd) = self.encryption_info().key_description() {
+            passphrases.push((LUKS2_TOKEN_ID, key_desc_to_passphrase(kd)?));
+        }
+        if self.encryption_info().clevis_info().is_some() {
+            let key = Self::clevis_decrypt(&mut device)?.ok_or_else(|| {
+                StratisError::Msg(
+                    "The Clevis token appears to have been wiped outside of Stratis; \
+                    cannot rotate the volume key without the Clevis passphrase"
+                        .to_string(),
+                )
+            })?;
+            passphrases.push((CLEVIS_LUKS_TOKEN_ID, key));
+        }
+        for (token_id, name, _) in named_keyring_tokens(&mut device) {
+            if let Some(kd) = self.passphrase_bindings().get(&name) {
+                passphrases.push((token_id, key_desc_to_passphrase(kd)?));
+            }
+        }
+
+        let cipher = device.status_handle().get_cipher()?;
+        let cipher_mode = device.status_handle().get_cipher_mode()?;
+        let key_size = convert_int!(device.status_handle().get_volume_key_size(), c_int, usize)?;
+        let sector_size = convert_int!(get_sector_size(Some(&mut device)), c_int, u32)?;
+
+        let mut volume_key = SafeMemHandle::alloc(key_size)?;
+        File::open("/dev/urandom")?.read_exact(volume_key.as_mut())?;
+
+        // The keyslots for the new volume key use the same PBKDF as the
+        // keyslots they replace.
+        set_pbkdf(&mut device, self.encryption_config())?;
+
+        let mut new_keyslots = Vec::new();
+        let res = passphrases
+            .iter()
+            .try_for_each(|(token_id, pass)| {
+                let keyslot = device.keyslot_handle().add_by_key(
+                    None,
+                    Some(volume_key.as_ref()),
+                    pass.as_ref(),
+                    CryptVolumeKey::NO_SEGMENT | CryptVolumeKey::DIGEST_REUSE,
+                )?;
+                new_keyslots.push(keyslot);
+                device
+                    .token_handle()
+                    .assign_keyslot(*token_id, Some(keyslot))
+            })
+            .map_err(StratisError::from)
+            .and_then(|_| {
+                let (_, pass) = passphrases.first().expect("device is encrypted");
+                device.reencrypt_handle().reencrypt_init_by_passphrase(
+                    None,
+                    pass.as_ref(),
+                    CRYPT_ANY_SLOT,
+                    convert_int!(new_keyslots[0], c_uint, c_int)?,
+                    (cipher.as_str(), cipher_mode.as_str()),
+                    rotation_params(CryptReencrypt::INITIALIZE_ONLY, sector_size),
+                )?;
+                Ok(())
+            });
+
+        if let Err(causal_error) = res {
+            // Destroying the new keyslots also removes them from the tokens.
+            if let Err(rollback_error) = new_keyslots
+                .into_iter()
+                .try_for_each(|keyslot| device.keyslot_handle().destroy(keyslot))
+            {
+                return Err(StratisError::NoActionRollbackError {
+                    causal_error: Box::new(causal_error),
+                    rollback_error: Box::new(StratisError::from(rollback_error)),
+                });
+            }
+            return Err(causal_error);
+        }
+
+        Ok(())
+    }
+
+    /// Check whether the LUKS2 metadata records a reencryption of the data
+    /// that has not yet completed.
+    pub fn volume_key_rotation_in_progress(&self) -> StratisResult<bool> {
+        match self
+            .acquire_crypt_device()?
+            .reencrypt_handle()
+            .status(rotation_params(CryptReencrypt::empty(), 0))?
+        {
+            CryptReencryptInfo::None => Ok(false),
+            CryptReencryptInfo::Clean | CryptReencryptInfo::Crash => Ok(true),
+            CryptReencryptInfo::Invalid => Err(StratisError::Msg(format!(
+                "The reencryption metadata of device {} is invalid",
+                self.luks2_device_path().display()
+            ))),
+        }
+    }
+
+    /// Reencrypt the data of the device to the new volume key, resuming the
+    /// rotation begun by begin_volume_key_rotation(), recovering from a
+    /// crash if necessary. This method blocks until all the data has been
+    /// reencrypted, calling progress with the amount of data reencrypted
+    /// and the total amount of data to reencrypt.
+    ///
+    /// When the reencryption completes, the keyslots for the old volume key
+    /// are removed.
+    pub fn resume_volume_key_rotation<F>(&self, progress: F) -> StratisResult<()>
+    where
+        F: FnMut(Bytes, Bytes) + 'static,
+    {
+        let mut device = self.acquire_crypt_device()?;
+        self.reencrypt(&mut device, rotation_params, progress)?;
+
+        // libcryptsetup removes the keyslots for the old volume key when the
+        // reencryption completes; remove any that remain unbound.
+        for keyslot in 0..CryptKeyslotHandle::max_keyslots(EncryptionFormat::Luks2)? {
+            if let Ok(KeyslotInfo::Unbound) = device.keyslot_handle().status(keyslot) {
+                if let Err(e) = device.keyslot_handle().destroy(keyslot) {
+                    warn!(
+                        "Failed to remove keyslot {} for the old volume key of device {}: {}",
+                        keyslot,
+                        self.luks2_device_path().display(),
+                        e
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Encrypt the data of a device whose encryption in place was begun by
+    /// CryptInitializer::initialize_in_place(), through the keyslots of the
+    /// bindings of the device. This method blocks until all the data has
+    /// been encrypted, calling progress with the amount of data encrypted
+    /// and the total amount of data to encrypt.
+    ///
+    /// When the encryption completes, the temporary keyslot with which it
+    /// was initialized is removed.
+    pub fn resume_encryption<F>(&self, keyslot: c_uint, progress: F) -> StratisResult<()>
+    where
+        F: FnMut(Bytes, Bytes) + 'static,
+    {
+        let mut device = self.acquire_crypt_device()?;
+        self.reencrypt(&mut device, in_place_params, progress)?;
+        log_on_failure!(
+            device.keyslot_handle().destroy(keyslot),
+            "Failed to destroy the temporary keyslot used to encrypt device {}",
+            self.luks2_device_path().display()
+        );
+        Ok(())
+    }
+
+    /// Resume the reencryption recorded in the LUKS2 metadata of the device,
+    /// recovering from a crash if necessary, with the parameters returned by
+    /// params, and reencrypt all the data, calling progress as it goes.
+    fn reencrypt<F>(
+        &self,
+        device: &mut CryptDevice,
+        params: fn(CryptReencrypt, u32) -> CryptParamsReencrypt,
+        progress: F,
+    ) -> StratisResult<()>
+    where
+        F: FnMut(Bytes, Bytes) + 'static,
+    {
+        let flags = match device
+            .reencrypt_handle()
+            .status(params(CryptReencrypt::empty(), 0))?
+        {
+            CryptReencryptInfo::Crash => CryptReencrypt::RESUME_ONLY | CryptReencrypt::RECOVERY,
+            _ => CryptReencrypt::RESUME_ONLY,
+        };
+        let passphrase = self.existing_passphrase(device)?;
+        let cipher = device.status_handle().get_cipher()?;
+        let cipher_mode = device.status_handle().get_cipher_mode()?;
+        let sector_size = convert_int!(get_sector_size(Some(&mut *device)), c_int, u32)?;
+
+        log_on_failure!(
+            device.reencrypt_handle().reencrypt_init_by_passphrase(
+                Some(self.activation_name()),
+                passphrase.as_ref(),
+                CRYPT_ANY_SLOT,
+                CRYPT_ANY_SLOT,
+                (cipher.as_str(), cipher_mode.as_str()),
+                params(flags, sector_size),
+            ),
+            "Failed to resume the reencryption of device {}",
+            self.luks2_device_path().display()
+        );
+
+        REENCRYPT_PROGRESS.with(|p| *p.borrow_mut() = Some(Box::new(progress)));
+        let res = device
+            .reencrypt_handle()
+            .reencrypt(Some(c_reencrypt_progress));
+        REENCRYPT_PROGRESS.with(|p| *p.borrow_mut() = None);
+        log_on_failure!(
+            res,
+            "Failed to reencrypt device {}",
+            self.luks2_device_path().display()
+        );
+        Ok(())
+    }
+
+    /// Get a passphrase that opens the device: the key of the first keyring
+    /// binding whose key is in the kernel keyring or, failing that, the
+    /// Clevis passphrase.
+    fn existing_passphrase(&self, device: &mut CryptDevice) -> StratisResult<SizedKeyMemory> {
+        for kd in self
+            .encryption_info()
+            .key_description()
+            .into_iter()
+            .chain(self.passphrase_bindings().values())
+        {
+            if let Some(key) = read_key(kd)? {
+                return Ok(key);
+            }
+        }
+        Self::clevis_decrypt(device)?.ok_or_else(|| {
+            StratisError::Msg(format!(
+                "Neither a key in the kernel keyring nor the Clevis binding is available to open device {}",
+                self.luks2_device_path().display()
+            ))
+        })
+    }
+
     /// Decrypt a Clevis passphrase and return it securely.
     fn clevis_decrypt(device: &mut CryptDevice) -> StratisResult<Option<SizedKeyMemory>> {
         let mut token = match device.token_handle().json_get(CLEVIS_LUKS_TOKEN_ID).ok() {
@@ -432,15 +778,7 @@ impl CryptHandle {
             None => 0,
         };
         let mut crypt = self.acquire_crypt_device()?;
-        let passphrase = if let Some(kd) = self.encryption_info().key_description() {
-            read_key(kd)?.ok_or_else(|| {
-                StratisError::Msg("Failed to find key with key description".to_string())
-            })?
-        } else if self.encryption_info().clevis_info().is_some() {
-            Self::clevis_decrypt(&mut crypt)?.expect("Already checked token exists")
-        } else {
-            unreachable!("Must be encrypted")
-        };
+        let passphrase = self.existing_passphrase(&mut crypt)?;
         crypt.activate_handle().activate_by_passphrase(
             None,
             None,
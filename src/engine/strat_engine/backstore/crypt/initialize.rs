@@ -2,17 +2,28 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::path::Path;
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    path::Path,
+};
 
 use either::Either;
 use serde_json::Value;
 
+use devicemapper::{DevId, DmNameBuf, DmOptions};
+
 use libcryptsetup_rs::{
+    c_int, c_uint,
     consts::{
-        flags::CryptVolumeKey,
-        vals::{EncryptionFormat, KeyslotsSize, MetadataSize},
+        flags::{CryptActivate, CryptReencrypt, CryptVolumeKey},
+        vals::{
+            CryptReencryptDirectionInfo, CryptReencryptModeInfo, EncryptionFormat, KeyslotsSize,
+            MetadataSize,
+        },
     },
-    CryptDevice, CryptInit, TokenInput,
+    get_sector_size, CryptDevice, CryptInit, CryptParamsLuks2, CryptParamsLuks2Ref,
+    CryptParamsReencrypt, TokenInput,
 };
 
 use crate::{
@@ -20,21 +31,27 @@ use crate::{
         strat_engine::{
             backstore::crypt::{
                 consts::{
-                    CLEVIS_LUKS_TOKEN_ID, DEFAULT_CRYPT_KEYSLOTS_SIZE, DEFAULT_CRYPT_METADATA_SIZE,
+                    CLEVIS_LUKS_TOKEN_ID, CRYPT_ANY_SLOT, DEFAULT_CIPHER,
+                    DEFAULT_CRYPT_KEYSLOTS_SIZE, DEFAULT_CRYPT_METADATA_SIZE, LUKS2_SECTOR_SIZE,
                     STRATIS_MEK_SIZE, STRATIS_TOKEN_ID,
                 },
                 handle::CryptHandle,
                 shared::{
-                    activate, add_keyring_keyslot, clevis_info_from_metadata, ensure_wiped,
-                    interpret_clevis_config, wipe_fallback, StratisLuks2Token,
+                    activate, add_keyring_keyslot, clevis_info_from_metadata, crypt_metadata_size,
+                    encryption_config_from_metadata, ensure_wiped, interpret_clevis_config,
+                    set_pbkdf, wipe_fallback, StratisLuks2Token,
                 },
             },
             cmd::clevis_luks_bind,
+            dm::get_dm,
             keys::MemoryPrivateFilesystem,
             metadata::StratisIdentifiers,
             names::format_crypt_name,
         },
-        types::{ClevisInfo, DevUuid, DevicePath, EncryptionInfo, KeyDescription, Name, PoolUuid},
+        types::{
+            ClevisInfo, DevUuid, DevicePath, EncryptionConfig, EncryptionInfo, KeyDescription,
+            Name, PoolUuid,
+        },
     },
     stratis::{StratisError, StratisResult},
 };
@@ -60,12 +77,18 @@ impl CryptInitializer {
     }
 
     /// Initialize a device with the provided key description and Clevis info.
+    /// The LUKS2 header is formatted according to the encryption
+    /// configuration; defaults are used for any settings not specified.
     pub fn initialize(
         self,
         pool_name: Name,
         key_description: Option<&KeyDescription>,
         clevis_info: Option<&ClevisInfo>,
+        encryption_config: Option<&EncryptionConfig>,
     ) -> StratisResult<CryptHandle> {
+        let default_config = EncryptionConfig::default();
+        let encryption_config = encryption_config.unwrap_or(&default_config);
+
         let mut clevis_info_owned =
             clevis_info.map(|(pin, config)| (pin.to_owned(), config.clone()));
         let clevis_parsed = match clevis_info_owned {
@@ -87,7 +110,13 @@ impl CryptInitializer {
             KeyslotsSize::try_from(convert_int!(*DEFAULT_CRYPT_KEYSLOTS_SIZE, u128, u64)?)?,
         )?;
         self
-            .initialize_with_err(&mut device, &pool_name, key_description, clevis_parsed)
+            .initialize_with_err(
+                &mut device,
+                &pool_name,
+                key_description,
+                clevis_parsed,
+                encryption_config,
+            )
             .and_then(|path| clevis_info_from_metadata(&mut device).map(|ci| (path, ci)))
             .and_then(|(_, clevis_info)| {
                 let encryption_info =
@@ -97,6 +126,7 @@ impl CryptInitializer {
                     self.physical_path.clone(),
                     self.identifiers,
                     encryption_info,
+                    encryption_config_from_metadata(&mut device)?,
                     self.activation_name.clone(),
                     Some(pool_name),
                 )
@@ -212,18 +242,40 @@ impl CryptInitializer {
         pool_name: &Name,
         key_description: Option<&KeyDescription>,
         clevis_info: Option<(&str, &Value, bool)>,
+        encryption_config: &EncryptionConfig,
     ) -> StratisResult<()> {
+        let cipher_and_mode = encryption_config
+            .cipher_and_mode()?
+            .unwrap_or(DEFAULT_CIPHER);
+        let key_size = match encryption_config.key_size {
+            Some(bits) => convert_int!(bits / 8, u32, usize)?,
+            None => STRATIS_MEK_SIZE,
+        };
+        // A sector size of 0 leaves the choice to libcryptsetup.
+        let params = CryptParamsLuks2 {
+            pbkdf: None,
+            integrity: None,
+            integrity_params: None,
+            data_alignment: 0,
+            data_device: None,
+            sector_size: encryption_config.sector_size.unwrap_or(0),
+            label: None,
+            subsystem: None,
+        };
+        let mut params_ref: CryptParamsLuks2Ref<'_> = (&params).try_into()?;
         log_on_failure!(
-            device.context_handle().format::<()>(
+            device.context_handle().format(
                 EncryptionFormat::Luks2,
-                ("aes", "xts-plain64"),
-                None,
-                libcryptsetup_rs::Either::Right(STRATIS_MEK_SIZE),
+                cipher_and_mode,
                 None,
+                libcryptsetup_rs::Either::Right(key_size),
+                Some(&mut params_ref.inner),
             ),
             "Failed to format device {} with LUKS2 header",
             self.physical_path.display()
         );
+        // The PBKDF is set after formatting, which resets it to the default.
+        set_pbkdf(device, encryption_config)?;
 
         match (key_description, clevis_info) {
             (Some(kd), Some(ci)) => self.initialize_with_both(device, kd, ci)?,
@@ -248,7 +300,7 @@ impl CryptInitializer {
 
         activate(
             if let Some(kd) = key_description {
-                Either::Left((device, kd))
+                Either::Left((device, vec![kd]))
             } else {
                 Either::Right(&self.physical_path)
             },
@@ -263,4 +315,273 @@ impl CryptInitializer {
     ) -> StratisResult<()> {
         ensure_wiped(device, physical_path, name)
     }
+
+    /// Initialize the encryption in place of a device that already holds
+    /// Stratis data with the provided key description and Clevis info.
+    ///
+    /// The LUKS2 header is formatted in a temporary file with a data offset
+    /// of crypt_metadata_size(). Initializing the reencryption moves the
+    /// first segment of the device, which holds the Stratis BDA, towards the
+    /// end of the device, after which the header is written to the start of
+    /// the device and the device is activated. The contents of the device,
+    /// including the BDA, are visible unchanged through the activated device.
+    /// The last 2 * crypt_metadata_size() of the device are overwritten. If
+    /// the device can not be activated once the first segment has been
+    /// moved, the temporary header file is not removed, and its path is
+    /// reported in the error.
+    ///
+    /// Precondition: No IO is issued to the device until this method returns
+    /// and the last 2 * crypt_metadata_size() of the device hold no data.
+    pub fn initialize_in_place(
+        self,
+        pool_name: Name,
+        key_description: Option<&KeyDescription>,
+        clevis_info: Option<&ClevisInfo>,
+    ) -> StratisResult<PendingEncryption> {
+        let clevis_parsed = match clevis_info {
+            Some((pin, config)) => {
+                let mut config = config.clone();
+                let yes = interpret_clevis_config(pin, &mut config)?;
+                Some((pin.to_owned(), config, yes))
+            }
+            None => None,
+        };
+
+        let memfs = MemoryPrivateFilesystem::new()?;
+        let keyfile = memfs.rand_key()?;
+
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis-encrypt")
+            .tempdir()?;
+        let header_path = tmp_dir.path().join("luks2-header");
+        File::create(&header_path)?.set_len(convert_int!(*crypt_metadata_size(), u128, u64)?)?;
+
+        let mut device = log_on_failure!(
+            CryptInit::init_with_data_device(libcryptsetup_rs::Either::Right((
+                header_path.as_path(),
+                &*self.physical_path
+            ))),
+            "Failed to acquire context for device {} while initializing encryption in place; \
+            nothing to clean up",
+            self.physical_path.display()
+        );
+        device.settings_handle().set_metadata_size(
+            MetadataSize::try_from(convert_int!(*DEFAULT_CRYPT_METADATA_SIZE, u128, u64)?)?,
+            KeyslotsSize::try_from(convert_int!(*DEFAULT_CRYPT_KEYSLOTS_SIZE, u128, u64)?)?,
+        )?;
+        // The data offset is given in units of 4096 bytes.
+        device.set_data_offset(convert_int!(
+            *crypt_metadata_size() / *LUKS2_SECTOR_SIZE,
+            u128,
+            u64
+        )?)?;
+        log_on_failure!(
+            device.context_handle().format::<()>(
+                EncryptionFormat::Luks2,
+                DEFAULT_CIPHER,
+                None,
+                libcryptsetup_rs::Either::Right(STRATIS_MEK_SIZE),
+                None,
+            ),
+            "Failed to format LUKS2 header for device {}",
+            self.physical_path.display()
+        );
+        let sector_size = convert_int!(get_sector_size(Some(&mut device)), c_int, u32)?;
+
+        // The reencryption is initialized through a temporary keyslot, which
+        // is destroyed once all the data on the device has been encrypted.
+        let keyslot = log_on_failure!(
+            device.keyslot_handle().add_by_key(
+                None,
+                None,
+                keyfile.as_ref(),
+                CryptVolumeKey::empty(),
+            ),
+            "Failed to initialize temporary keyslot for encryption"
+        );
+        // The keyslots of the keyring passphrase and the Clevis binding must
+        // exist before the reencryption is initialized; the key of the
+        // temporary keyslot is discarded when this method returns, and the
+        // data is encrypted through the other keyslots.
+        if let Some(kd) = key_description {
+            Self::initialize_with_keyring(&mut device, kd)?;
+        }
+        if let Some((ref pin, ref config, yes)) = clevis_parsed {
+            clevis_luks_bind(
+                &header_path,
+                keyfile.keyfile_path(),
+                CLEVIS_LUKS_TOKEN_ID,
+                pin,
+                config,
+                yes,
+            )?;
+            // Need to reload device here to refresh the state of the device
+            // after being modified by Clevis.
+            device
+                .context_handle()
+                .load::<()>(Some(EncryptionFormat::Luks2), None)?;
+        }
+        log_on_failure!(
+            device.token_handle().json_set(TokenInput::ReplaceToken(
+                STRATIS_TOKEN_ID,
+                &StratisLuks2Token {
+                    devname: self.activation_name.clone(),
+                    identifiers: self.identifiers,
+                    pool_name: Some(pool_name.clone()),
+                }
+                .into(),
+            )),
+            "Failed to create the Stratis token"
+        );
+
+        log_on_failure!(
+            device.reencrypt_handle().reencrypt_init_by_passphrase(
+                None,
+                keyfile.as_ref(),
+                CRYPT_ANY_SLOT,
+                convert_int!(keyslot, c_uint, c_int)?,
+                DEFAULT_CIPHER,
+                in_place_params(
+                    CryptReencrypt::INITIALIZE_ONLY | CryptReencrypt::MOVE_FIRST_SEGMENT,
+                    sector_size
+                ),
+            ),
+            "Failed to initialize encryption of device {}",
+            self.physical_path.display()
+        );
+
+        // From here on the first segment of the device has been moved, so
+        // the LUKS2 header must be written to the device to make the moved
+        // data accessible again; the initialization can only be undone
+        // through the activated device with
+        // PendingEncryption::restore_first_segment().
+        let res = CryptInit::init(&self.physical_path)
+            .map_err(StratisError::from)
+            .and_then(|mut device| {
+                device
+                    .backup_handle()
+                    .header_restore(Some(EncryptionFormat::Luks2), &header_path)?;
+                device
+                    .context_handle()
+                    .load::<()>(Some(EncryptionFormat::Luks2), None)?;
+                device.activate_handle().activate_by_passphrase(
+                    Some(&self.activation_name),
+                    Some(keyslot),
+                    keyfile.as_ref(),
+                    CryptActivate::empty(),
+                )?;
+                encryption_config_from_metadata(&mut device)
+            })
+            .and_then(|encryption_config| {
+                CryptHandle::new(
+                    self.physical_path.clone(),
+                    self.identifiers,
+                    EncryptionInfo::from_options((key_description.cloned(), clevis_info.cloned()))
+                        .expect("Encrypted device must be provided encryption parameters"),
+                    encryption_config,
+                    self.activation_name.clone(),
+                    Some(pool_name),
+                )
+            });
+
+        match res {
+            Ok(handle) => Ok(PendingEncryption { handle, keyslot }),
+            Err(e) => {
+                // The LUKS2 header may not have been written to the device,
+                // in which case the header file is the only record of where
+                // the moved first segment is, so it is kept.
+                let _ = tmp_dir.into_path();
+                Err(StratisError::Chained(
+                    format!(
+                        "Encryption of device {} was initialized but the device could not be \
+                        activated; the encryption can be completed with cryptsetup reencrypt \
+                        --resume-only --header {}",
+                        self.physical_path.display(),
+                        header_path.display()
+                    ),
+                    Box::new(e),
+                ))
+            }
+        }
+    }
+}
+
+/// Parameters of the reencryption that encrypts a device in place. The data
+/// is shifted by twice the size of the LUKS2 metadata, half of which is
+/// occupied by the moved first segment of the device.
+pub fn in_place_params(flags: CryptReencrypt, sector_size: u32) -> CryptParamsReencrypt {
+    CryptParamsReencrypt {
+        mode: CryptReencryptModeInfo::Encrypt,
+        direction: CryptReencryptDirectionInfo::Backward,
+        resilience: "datashift".to_string(),
+        hash: "sha256".to_string(),
+        data_shift: *(2u64 * crypt_metadata_size()).sectors(),
+        max_hotzone_size: 0,
+        device_size: 0,
+        luks2: CryptParamsLuks2 {
+            pbkdf: None,
+            integrity: None,
+            integrity_params: None,
+            data_alignment: 0,
+            data_device: None,
+            sector_size,
+            label: None,
+            subsystem: None,
+        },
+        flags,
+    }
+}
+
+/// An encryption in place which has been initialized, but during which the
+/// data on the device has not yet been encrypted. The device is active, and
+/// upper layers may use the activated device while the data is encrypted.
+pub struct PendingEncryption {
+    handle: CryptHandle,
+    // The temporary keyslot with which the encryption was initialized.
+    keyslot: c_uint,
+}
+
+impl PendingEncryption {
+    /// The handle for the activated device.
+    pub fn handle(&self) -> &CryptHandle {
+        &self.handle
+    }
+
+    /// Give up the possibility of undoing the encryption, returning the
+    /// handle for the activated device and the temporary keyslot, with
+    /// which the data is encrypted by CryptHandle::resume_encryption().
+    pub fn into_handle(self) -> (CryptHandle, c_uint) {
+        (self.handle, self.keyslot)
+    }
+
+    /// Undo the initialization of the encryption. This is possible only as
+    /// long as into_handle() has not been called: the data has not yet been
+    /// encrypted, so the first segment of the device, which was moved
+    /// towards the end of the device, can be read through the activated
+    /// device and written back to the start of the device, overwriting the
+    /// LUKS2 header. The activated device, which does not map the LUKS2
+    /// header, is left unchanged; it is removed by deactivate() once no
+    /// device refers to it any longer.
+    ///
+    /// Precondition: No IO is issued to the activated device.
+    pub fn restore_first_segment(&self) -> StratisResult<()> {
+        let mut first_segment = vec![0u8; convert_int!(*crypt_metadata_size(), u128, usize)?];
+        File::open(self.handle.activated_device_path())?.read_exact(&mut first_segment)?;
+
+        let mut f = OpenOptions::new()
+            .write(true)
+            .open(self.handle.luks2_device_path())?;
+        f.write_all(&first_segment)?;
+        f.sync_all()?;
+        Ok(())
+    }
+
+    /// Remove the activated device after restore_first_segment(). The
+    /// device is removed through devicemapper, as there is no longer a LUKS2
+    /// header on the device.
+    pub fn deactivate(self) -> StratisResult<()> {
+        let name = DmNameBuf::new(self.handle.activation_name().to_string())?;
+        get_dm().device_remove(&DevId::Name(&name), DmOptions::default())?;
+        Ok(())
+    }
 }
@@ -12,7 +12,7 @@ use crate::{
             backstore::crypt::shared::{setup_crypt_device, setup_crypt_metadata_handle},
             metadata::StratisIdentifiers,
         },
-        types::{DevicePath, EncryptionInfo, Name},
+        types::{DevicePath, EncryptionConfig, EncryptionInfo, Name, PassphraseBindings},
     },
     stratis::StratisResult,
 };
@@ -23,6 +23,8 @@ pub struct CryptMetadataHandle {
     pub(super) physical_path: DevicePath,
     pub(super) identifiers: StratisIdentifiers,
     pub(super) encryption_info: EncryptionInfo,
+    pub(super) encryption_config: EncryptionConfig,
+    pub(super) passphrase_bindings: PassphraseBindings,
     pub(super) activation_name: String,
     pub(super) pool_name: Option<Name>,
     pub(super) device: Device,
@@ -33,6 +35,8 @@ impl CryptMetadataHandle {
         physical_path: DevicePath,
         identifiers: StratisIdentifiers,
         encryption_info: EncryptionInfo,
+        encryption_config: EncryptionConfig,
+        passphrase_bindings: PassphraseBindings,
         activation_name: String,
         pool_name: Option<Name>,
         device: Device,
@@ -41,6 +45,8 @@ impl CryptMetadataHandle {
             physical_path,
             identifiers,
             encryption_info,
+            encryption_config,
+            passphrase_bindings,
             activation_name,
             pool_name,
             device,
@@ -60,6 +66,16 @@ impl CryptMetadataHandle {
         &self.encryption_info
     }
 
+    /// Get the settings of the encryption of this encrypted device.
+    pub fn encryption_config(&self) -> &EncryptionConfig {
+        &self.encryption_config
+    }
+
+    /// Get the named passphrase bindings of this encrypted device.
+    pub fn passphrase_bindings(&self) -> &PassphraseBindings {
+        &self.passphrase_bindings
+    }
+
     /// Return the path to the device node of the underlying storage device
     /// for the encrypted device.
     pub fn luks2_device_path(&self) -> &Path {
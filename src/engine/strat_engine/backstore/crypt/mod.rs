@@ -16,11 +16,11 @@ pub use self::{
     activate::CryptActivationHandle,
     consts::CLEVIS_TANG_TRUST_URL,
     handle::CryptHandle,
-    initialize::CryptInitializer,
+    initialize::{CryptInitializer, PendingEncryption},
     metadata_handle::CryptMetadataHandle,
     shared::{
-        back_up_luks_header, crypt_metadata_size, interpret_clevis_config, restore_luks_header,
-        set_up_crypt_logging,
+        back_up_luks_header, check_encryption_config_support, crypt_metadata_size,
+        interpret_clevis_config, restore_luks_header, set_up_crypt_logging,
     },
 };
 
@@ -299,8 +299,8 @@ mod tests {
 
             handle.deactivate()?;
 
-            let handle =
-                CryptActivationHandle::setup(path, UnlockMethod::Keyring)?.ok_or_else(|| {
+            let handle = CryptActivationHandle::setup(path, UnlockMethod::Keyring, None)?
+                .ok_or_else(|| {
                     Box::new(io::Error::new(
                         io::ErrorKind::Other,
                         format!(
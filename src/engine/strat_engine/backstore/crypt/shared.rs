@@ -5,11 +5,18 @@
 use std::{
     fs::OpenOptions,
     io::Write,
+    os::unix::io::RawFd,
     path::{Path, PathBuf},
 };
 
 use data_encoding::BASE64URL_NOPAD;
 use either::Either;
+use nix::{
+    sys::socket::{
+        bind, setsockopt, socket, sockopt::AlgSetKey, AddressFamily, AlgAddr, SockFlag, SockType,
+    },
+    unistd::close,
+};
 use retry::{delay::Fixed, retry_with_index, Error};
 use serde_json::{Map, Value};
 use sha2::{Digest, Sha256};
@@ -17,14 +24,16 @@ use tempfile::TempDir;
 
 use devicemapper::Bytes;
 use libcryptsetup_rs::{
-    c_uint,
+    c_int, c_uint,
     consts::{
-        flags::{CryptActivate, CryptDeactivate, CryptVolumeKey, CryptWipe},
+        flags::{CryptActivate, CryptDeactivate, CryptPbkdf, CryptVolumeKey, CryptWipe},
         vals::{
-            CryptDebugLevel, CryptLogLevel, CryptStatusInfo, CryptWipePattern, EncryptionFormat,
+            CryptDebugLevel, CryptKdf, CryptLogLevel, CryptStatusInfo, CryptWipePattern,
+            EncryptionFormat, LuksType,
         },
     },
-    set_debug_level, set_log_callback, CryptDevice, CryptInit, LibcryptErr, TokenInput,
+    get_sector_size, set_debug_level, set_log_callback, CryptDevice, CryptInit,
+    CryptSettingsHandle, LibcryptErr, TokenInput,
 };
 
 use crate::{
@@ -33,11 +42,13 @@ use crate::{
             backstore::{
                 crypt::{
                     consts::{
-                        CLEVIS_LUKS_TOKEN_ID, CLEVIS_TANG_TRUST_URL, DEFAULT_CRYPT_KEYSLOTS_SIZE,
-                        DEFAULT_CRYPT_METADATA_SIZE, LUKS2_SECTOR_SIZE, LUKS2_TOKEN_ID,
-                        LUKS2_TOKEN_TYPE, STRATIS_TOKEN_DEVNAME_KEY, STRATIS_TOKEN_DEV_UUID_KEY,
-                        STRATIS_TOKEN_ID, STRATIS_TOKEN_POOLNAME_KEY, STRATIS_TOKEN_POOL_UUID_KEY,
-                        STRATIS_TOKEN_TYPE, TOKEN_KEYSLOTS_KEY, TOKEN_TYPE_KEY,
+                        CLEVIS_LUKS_TOKEN_ID, CLEVIS_TANG_TRUST_URL, DEFAULT_CIPHER,
+                        DEFAULT_CRYPT_KEYSLOTS_SIZE, DEFAULT_CRYPT_METADATA_SIZE,
+                        FIRST_NAMED_TOKEN_ID, LUKS2_SECTOR_SIZE, LUKS2_TOKENS_MAX, LUKS2_TOKEN_ID,
+                        LUKS2_TOKEN_TYPE, STRATIS_MEK_SIZE, STRATIS_TOKEN_DEVNAME_KEY,
+                        STRATIS_TOKEN_DEV_UUID_KEY, STRATIS_TOKEN_ID, STRATIS_TOKEN_POOLNAME_KEY,
+                        STRATIS_TOKEN_POOL_UUID_KEY, STRATIS_TOKEN_TYPE, TOKEN_BINDING_NAME_KEY,
+                        TOKEN_KEYSLOTS_KEY, TOKEN_KEY_DESCRIPTION_KEY, TOKEN_TYPE_KEY,
                     },
                     handle::CryptHandle,
                     metadata_handle::CryptMetadataHandle,
@@ -49,8 +60,8 @@ use crate::{
             metadata::StratisIdentifiers,
         },
         types::{
-            DevUuid, DevicePath, EncryptionInfo, KeyDescription, Name, PoolUuid, SizedKeyMemory,
-            UnlockMethod,
+            DevUuid, DevicePath, EncryptionConfig, EncryptionInfo, KeyDescription, Name,
+            PassphraseBindings, Pbkdf, PoolUuid, SizedKeyMemory, UnlockMethod,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -168,7 +179,7 @@ pub fn acquire_crypt_device(physical_path: &Path) -> StratisResult<CryptDevice>
 }
 
 /// Get the passphrase associated with a given key description.
-fn key_desc_to_passphrase(key_description: &KeyDescription) -> StratisResult<SizedKeyMemory> {
+pub fn key_desc_to_passphrase(key_description: &KeyDescription) -> StratisResult<SizedKeyMemory> {
     let key_option = log_on_failure!(
         read_key(key_description),
         "Failed to read key with key description {} from keyring",
@@ -246,6 +257,269 @@ pub fn add_keyring_keyslot(
     Ok(())
 }
 
+/// Add a keyslot for the passphrase with the given key description, opened
+/// with the existing passphrase pass, and a LUKS2 keyring token recording
+/// the named passphrase binding.
+///
+/// The token is placed in a free slot above the slots reserved for the
+/// keyring and Clevis bindings in the EncryptionInfo.
+pub fn add_named_keyring_keyslot(
+    device: &mut CryptDevice,
+    name: &str,
+    key_description: &KeyDescription,
+    pass: &SizedKeyMemory,
+) -> StratisResult<()> {
+    let token_id = (FIRST_NAMED_TOKEN_ID..LUKS2_TOKENS_MAX)
+        .find(|token_id| device.token_handle().json_get(*token_id).is_err())
+        .ok_or_else(|| {
+            StratisError::Msg(
+                "No free LUKS2 token slot remains for another passphrase binding".to_string(),
+            )
+        })?;
+
+    let key = key_desc_to_passphrase(key_description)?;
+    let keyslot = log_on_failure!(
+        device
+            .keyslot_handle()
+            .add_by_passphrase(None, pass.as_ref(), key.as_ref()),
+        "Failed to initialize keyslot for passphrase binding {}",
+        name
+    );
+
+    let token = json!({
+        TOKEN_TYPE_KEY: LUKS2_TOKEN_TYPE,
+        TOKEN_KEYSLOTS_KEY: [keyslot.to_string()],
+        TOKEN_KEY_DESCRIPTION_KEY: key_description.to_system_string(),
+        TOKEN_BINDING_NAME_KEY: name,
+    });
+    if let Err(e) = device
+        .token_handle()
+        .json_set(TokenInput::ReplaceToken(token_id, &token))
+    {
+        let causal_error = StratisError::from(e);
+        if let Err(rollback_error) = device.keyslot_handle().destroy(keyslot) {
+            return Err(StratisError::NoActionRollbackError {
+                causal_error: Box::new(causal_error),
+                rollback_error: Box::new(StratisError::from(rollback_error)),
+            });
+        }
+        return Err(causal_error);
+    }
+
+    Ok(())
+}
+
+/// Remove the keyslots and the LUKS2 keyring token of the named passphrase
+/// binding.
+///
+/// Returns Ok(false) if the device has no passphrase binding with this name.
+pub fn remove_named_keyring_keyslot(device: &mut CryptDevice, name: &str) -> StratisResult<bool> {
+    let token_id = match named_keyring_tokens(device)
+        .into_iter()
+        .find(|(_, token_name, _)| token_name == name)
+    {
+        Some((token_id, _, _)) => token_id,
+        None => return Ok(false),
+    };
+
+    for keyslot in get_keyslot_number(device, token_id)?.unwrap_or_default() {
+        log_on_failure!(
+            device.keyslot_handle().destroy(keyslot),
+            "Failed partway through removing passphrase binding {} which \
+            cannot be rolled back; manual intervention may be required",
+            name
+        )
+    }
+    device
+        .token_handle()
+        .json_set(TokenInput::RemoveToken(token_id))?;
+
+    Ok(true)
+}
+
+/// Get the token ID, the name, and the system key description of each named
+/// passphrase binding recorded in the LUKS2 metadata.
+pub fn named_keyring_tokens(device: &mut CryptDevice) -> Vec<(c_uint, String, String)> {
+    (FIRST_NAMED_TOKEN_ID..LUKS2_TOKENS_MAX)
+        .filter_map(|token_id| {
+            let json = device.token_handle().json_get(token_id).ok()?;
+            if !luks2_token_type_is_valid(&json) {
+                return None;
+            }
+            let name = json.get(TOKEN_BINDING_NAME_KEY)?.as_str()?.to_string();
+            let key_desc = json.get(TOKEN_KEY_DESCRIPTION_KEY)?.as_str()?.to_string();
+            Some((token_id, name, key_desc))
+        })
+        .collect()
+}
+
+/// Get the token IDs of all LUKS2 keyring tokens of the device, starting with
+/// the token for the keyring binding in the EncryptionInfo if it is present.
+pub fn keyring_token_ids(device: &mut CryptDevice) -> Vec<c_uint> {
+    let primary = device
+        .token_handle()
+        .json_get(LUKS2_TOKEN_ID)
+        .ok()
+        .filter(luks2_token_type_is_valid)
+        .map(|_| LUKS2_TOKEN_ID);
+    primary
+        .into_iter()
+        .chain(
+            named_keyring_tokens(device)
+                .into_iter()
+                .map(|(token_id, _, _)| token_id),
+        )
+        .collect()
+}
+
+fn pbkdf_to_kdf(pbkdf: Pbkdf) -> CryptKdf {
+    match pbkdf {
+        Pbkdf::Pbkdf2 => CryptKdf::Pbkdf2,
+        Pbkdf::Argon2i => CryptKdf::Argon2I,
+        Pbkdf::Argon2id => CryptKdf::Argon2Id,
+    }
+}
+
+fn kdf_to_pbkdf(kdf: &CryptKdf) -> Pbkdf {
+    match kdf {
+        CryptKdf::Pbkdf2 => Pbkdf::Pbkdf2,
+        CryptKdf::Argon2I => Pbkdf::Argon2i,
+        CryptKdf::Argon2Id => Pbkdf::Argon2id,
+    }
+}
+
+/// Set the PBKDF of the keyslots subsequently added to the device according to
+/// the PBKDF settings of the encryption configuration. If no iteration cost is
+/// specified, it is benchmarked to take the default unlocking time, in which
+/// case the memory cost is an upper bound. Does nothing if no PBKDF settings
+/// are specified.
+pub fn set_pbkdf(device: &mut CryptDevice, config: &EncryptionConfig) -> StratisResult<()> {
+    if !config.has_pbkdf_settings() {
+        return Ok(());
+    }
+
+    let mut pbkdf = match config.pbkdf {
+        Some(p) => CryptSettingsHandle::get_pbkdf_type_params(&pbkdf_to_kdf(p))?,
+        None => CryptSettingsHandle::get_pbkdf_default(&LuksType::Luks2)?,
+    };
+    if let Some(memory) = config.pbkdf_memory {
+        pbkdf.max_memory_kb = memory;
+    }
+    if let Some(iterations) = config.pbkdf_iterations {
+        pbkdf.iterations = iterations;
+        pbkdf.time_ms = 0;
+        pbkdf.flags |= CryptPbkdf::NO_BENCHMARK;
+    }
+
+    log_on_failure!(
+        device.settings_handle().set_pbkdf_type(&pbkdf),
+        "Failed to set the PBKDF {} for the keyslots of the encrypted device",
+        kdf_to_pbkdf(&pbkdf.type_)
+    );
+    Ok(())
+}
+
+/// Read the settings of the encryption of a device from its LUKS2 metadata.
+/// The PBKDF settings are those of the keyslot for the passphrase in the
+/// kernel keyring; they are None if the device is not bound to one.
+pub fn encryption_config_from_metadata(
+    device: &mut CryptDevice,
+) -> StratisResult<EncryptionConfig> {
+    let cipher = device.status_handle().get_cipher()?;
+    let cipher_mode = device.status_handle().get_cipher_mode()?;
+    let key_size = convert_int!(device.status_handle().get_volume_key_size(), c_int, u32)?;
+    let sector_size = convert_int!(get_sector_size(Some(&mut *device)), c_int, u32)?;
+
+    let pbkdf = match get_keyslot_number(device, LUKS2_TOKEN_ID)?
+        .and_then(|keyslots| keyslots.first().copied())
+    {
+        Some(keyslot) => Some(device.keyslot_handle().get_pbkdf(keyslot)?),
+        None => None,
+    };
+
+    Ok(EncryptionConfig {
+        cipher: Some(format!("{cipher}-{cipher_mode}")),
+        key_size: Some(key_size * 8),
+        pbkdf: pbkdf.as_ref().map(|p| kdf_to_pbkdf(&p.type_)),
+        pbkdf_memory: pbkdf
+            .as_ref()
+            .filter(|p| p.type_ != CryptKdf::Pbkdf2)
+            .map(|p| p.max_memory_kb),
+        pbkdf_iterations: pbkdf.as_ref().map(|p| p.iterations),
+        sector_size: Some(sector_size),
+    })
+}
+
+/// Check whether the kernel crypto API supports the algorithm with the given
+/// name, e.g. xts(aes), with a key of the given size in bytes. As in
+/// libcryptsetup, binding an AF_ALG socket to the algorithm loads the kernel
+/// modules which implement it, if necessary, and setting the key verifies the
+/// key size.
+fn check_kernel_cipher(alg_name: &str, key_size: usize) -> StratisResult<()> {
+    // The algorithm name, including its terminating NUL, must fit in the
+    // 64 bytes of the name field of the socket address.
+    if alg_name.len() >= 64 {
+        return Err(StratisError::Msg(format!(
+            "Cipher name {alg_name} is too long"
+        )));
+    }
+
+    let fd: RawFd = socket(
+        AddressFamily::Alg,
+        SockType::SeqPacket,
+        SockFlag::SOCK_CLOEXEC,
+        None,
+    )?;
+    let res = bind(fd, &AlgAddr::new("skcipher", alg_name))
+        .and_then(|_| setsockopt(fd, AlgSetKey::default(), &vec![0u8; key_size]));
+    if let Err(e) = close(fd) {
+        warn!("Failed to close AF_ALG socket: {}", e);
+    }
+    Ok(res?)
+}
+
+/// Check that the kernel supports the encryption configuration: that the
+/// cipher can be used with the key size, and that dm-crypt can use the
+/// sector size on devices with the given logical sector size. The default
+/// cipher and key size are checked if not specified.
+pub fn check_encryption_config_support(
+    config: &EncryptionConfig,
+    logical_sector_size: Bytes,
+) -> StratisResult<()> {
+    let (cipher, mode) = config.cipher_and_mode()?.unwrap_or(DEFAULT_CIPHER);
+    let key_size = match config.key_size {
+        Some(bits) => convert_int!(bits / 8, u32, usize)?,
+        None => STRATIS_MEK_SIZE,
+    };
+
+    // The kernel name of the cipher combines the chaining mode and the
+    // cipher, e.g. xts(aes) for aes-xts-plain64; the IV generator, plain64,
+    // is implemented by dm-crypt itself.
+    let chain_mode = mode
+        .split('-')
+        .next()
+        .expect("split yields at least one item");
+    check_kernel_cipher(&format!("{chain_mode}({cipher})"), key_size).map_err(|e| {
+        StratisError::Chained(
+            format!(
+                "The kernel does not support cipher {cipher}-{mode} with a key size of {} bits",
+                key_size * 8
+            ),
+            Box::new(e),
+        )
+    })?;
+
+    if let Some(sector_size) = config.sector_size {
+        if Bytes::from(sector_size) < logical_sector_size {
+            return Err(StratisError::Msg(format!(
+                "Encryption sector size {sector_size} is smaller than the logical sector size {logical_sector_size} of the devices"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 /// Set up a libcryptsetup device handle on a device that may or may not be a LUKS2
 /// device.
 pub fn setup_crypt_device(physical_path: &Path) -> StratisResult<Option<CryptDevice>> {
@@ -293,6 +567,7 @@ pub fn setup_crypt_metadata_handle(
         None => None,
     };
     let clevis_info = clevis_info_from_metadata(device)?;
+    let passphrase_bindings = passphrase_bindings_from_metadata(device, physical_path)?;
 
     let encryption_info = match (key_description, clevis_info) {
         (Some(kd), Some(ci)) => EncryptionInfo::Both(kd, ci),
@@ -300,11 +575,14 @@ pub fn setup_crypt_metadata_handle(
         (_, Some(ci)) => EncryptionInfo::ClevisInfo(ci),
         (None, None) => return Ok(None),
     };
+    let encryption_config = encryption_config_from_metadata(device)?;
 
     Ok(Some(CryptMetadataHandle::new(
         DevicePath::new(physical_path)?,
         identifiers,
         encryption_info,
+        encryption_config,
+        passphrase_bindings,
         activation_name,
         pool_name,
         devno,
@@ -317,6 +595,7 @@ pub fn setup_crypt_handle(
     device: &mut CryptDevice,
     physical_path: &Path,
     unlock_method: Option<UnlockMethod>,
+    passphrase: Option<&SizedKeyMemory>,
 ) -> StratisResult<Option<CryptHandle>> {
     let metadata_handle = match setup_crypt_metadata_handle(device, physical_path)? {
         Some(handle) => handle,
@@ -325,26 +604,34 @@ pub fn setup_crypt_handle(
 
     let name = activation_name_from_metadata(device)?;
 
-    match unlock_method {
-        Some(UnlockMethod::Keyring) => {
-            activate(Either::Left((
-                device,
-                metadata_handle.encryption_info().key_description()
-                    .ok_or_else(|| {
-                        StratisError::Msg(
-                            "Unlock action was specified to be keyring but not key description is present in the metadata".to_string(),
-                        )
-                    })?,
-            )), &name)?
+    match (unlock_method, passphrase) {
+        (Some(UnlockMethod::Keyring), Some(passphrase)) => {
+            activate_with_passphrase(device, passphrase, &name)?
         }
-        Some(UnlockMethod::Clevis) => activate(Either::Right(physical_path), &name)?,
-        None => {
-            if let Err(_) | Ok(CryptStatusInfo::Inactive | CryptStatusInfo::Invalid) = libcryptsetup_rs::status(Some(device), &name) {
+        (Some(UnlockMethod::Keyring), None) => {
+            let key_descs = metadata_handle
+                .encryption_info()
+                .key_description()
+                .into_iter()
+                .chain(metadata_handle.passphrase_bindings().values())
+                .collect::<Vec<_>>();
+            if key_descs.is_empty() {
+                return Err(StratisError::Msg(
+                    "Unlock action was specified to be keyring but not key description is present in the metadata".to_string(),
+                ));
+            }
+            activate(Either::Left((device, key_descs)), &name)?
+        }
+        (Some(UnlockMethod::Clevis), _) => activate(Either::Right(physical_path), &name)?,
+        (None, _) => {
+            if let Err(_) | Ok(CryptStatusInfo::Inactive | CryptStatusInfo::Invalid) =
+                libcryptsetup_rs::status(Some(device), &name)
+            {
                 return Err(StratisError::Msg(
                     "Found a crypt device but it is not activated and no unlock method was provided".to_string(),
                 ));
             }
-        },
+        }
     };
 
     match CryptHandle::new_with_metadata_handle(metadata_handle) {
@@ -645,49 +932,115 @@ fn device_is_active(device: Option<&mut CryptDevice>, device_name: &str) -> Stra
     }
 }
 
-/// Activate device by LUKS2 keyring token.
+/// Activate device by LUKS2 keyring token, trying the token of each keyring
+/// binding in turn until one succeeds.
 ///
-/// Precondition: The key description has been verified to be present in the keyring
+/// Precondition: The key of at least one of the keyring bindings has been
+/// verified to be present in the keyring
 /// if matches!(unlock_method, UnlockMethod::Keyring).
 fn activate_with_keyring(crypt_device: &mut CryptDevice, name: &str) -> StratisResult<()> {
-    // Activate by token
-    log_on_failure!(
-        crypt_device.token_handle().activate_by_token::<()>(
-            Some(name),
-            Some(LUKS2_TOKEN_ID),
-            None,
-            CryptActivate::empty(),
-        ),
-        "Failed to activate device with name {}",
-        name
-    );
+    let mut result = Err(StratisError::Msg(
+        "No LUKS2 keyring token was found".to_string(),
+    ));
+    for token_id in keyring_token_ids(crypt_device) {
+        // Activate by token
+        result = crypt_device
+            .token_handle()
+            .activate_by_token::<()>(Some(name), Some(token_id), None, CryptActivate::empty())
+            .map(|_| ())
+            .map_err(StratisError::from);
+        if result.is_ok() {
+            break;
+        }
+    }
+    log_on_failure!(result, "Failed to activate device with name {}", name);
     Ok(())
 }
 
+/// Activate encrypted Stratis device with a passphrase that was supplied
+/// directly instead of through the kernel keyring. Only the keyslots
+/// associated with the LUKS2 keyring tokens are tried.
+fn activate_with_passphrase(
+    device: &mut CryptDevice,
+    passphrase: &SizedKeyMemory,
+    name: &str,
+) -> StratisResult<()> {
+    let keyslots = keyring_token_ids(device)
+        .into_iter()
+        .map(|token_id| get_keyslot_number(device, token_id))
+        .collect::<StratisResult<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .flatten()
+        .collect::<Vec<_>>();
+    if keyslots.is_empty() {
+        return Err(StratisError::Msg(
+            "The encrypted device is not bound to a passphrase; it cannot be unlocked with one"
+                .to_string(),
+        ));
+    }
+
+    if !keyslots.into_iter().any(|keyslot| {
+        device
+            .activate_handle()
+            .activate_by_passphrase(
+                Some(name),
+                Some(keyslot),
+                passphrase.as_ref(),
+                CryptActivate::empty(),
+            )
+            .is_ok()
+    }) {
+        warn!(
+            "The passphrase provided did not unlock any keyslot of device {}",
+            name
+        );
+        return Err(StratisError::Msg(format!(
+            "Failed to activate device with name {name}; the passphrase provided is incorrect"
+        )));
+    }
+
+    device_is_active(Some(device), name)
+}
+
 /// Activate encrypted Stratis device using the name stored in the
 /// Stratis token.
+///
+/// When unlocking with the keyring, the key of at least one of the given
+/// key descriptions must be present in the keyring.
 pub fn activate(
-    unlock_param: Either<(&mut CryptDevice, &KeyDescription), &Path>,
+    unlock_param: Either<(&mut CryptDevice, Vec<&KeyDescription>), &Path>,
     name: &str,
 ) -> StratisResult<()> {
     let crypt_device = match unlock_param {
-        Either::Left((device, kd)) => {
-            let key_description_missing = keys::search_key_persistent(kd)
-                .map_err(|_| {
-                    StratisError::Msg(format!(
-                        "Searching the persistent keyring for the key description {} failed.",
-                        kd.as_application_str(),
-                    ))
-                })?
-                .is_none();
+        Either::Left((device, key_descs)) => {
+            let mut key_description_missing = true;
+            for kd in key_descs.iter() {
+                if keys::search_key_persistent(kd)
+                    .map_err(|_| {
+                        StratisError::Msg(format!(
+                            "Searching the persistent keyring for the key description {} failed.",
+                            kd.as_application_str(),
+                        ))
+                    })?
+                    .is_some()
+                {
+                    key_description_missing = false;
+                } else {
+                    warn!(
+                        "Key description {} was not found in the keyring",
+                        kd.as_application_str()
+                    );
+                }
+            }
             if key_description_missing {
-                warn!(
-                    "Key description {} was not found in the keyring",
-                    kd.as_application_str()
-                );
                 return Err(StratisError::Msg(format!(
-                    "The key description \"{}\" is not currently set.",
-                    kd.as_application_str(),
+                    "None of the key descriptions {} is currently set.",
+                    key_descs
+                        .iter()
+                        .map(|kd| format!("\"{}\"", kd.as_application_str()))
+                        .collect::<Vec<_>>()
+                        .join(", "),
                 )));
             }
             activate_with_keyring(device, name)?;
@@ -995,6 +1348,33 @@ pub fn key_desc_from_metadata(device: &mut CryptDevice) -> Option<String> {
     device.token_handle().luks2_keyring_get(LUKS2_TOKEN_ID).ok()
 }
 
+/// Query the Stratis metadata for the named passphrase bindings of the
+/// physical device.
+pub fn passphrase_bindings_from_metadata(
+    device: &mut CryptDevice,
+    physical_path: &Path,
+) -> StratisResult<PassphraseBindings> {
+    named_keyring_tokens(device)
+        .into_iter()
+        .filter_map(
+            |(_, name, key_desc)| match KeyDescription::from_system_key_desc(&key_desc) {
+                Some(Ok(kd)) => Some(Ok((name, kd))),
+                Some(Err(e)) => Some(Err(StratisError::Msg(format!(
+                    "key description {} of passphrase binding {} found on devnode {} is not a valid Stratis key description: {}",
+                    key_desc,
+                    name,
+                    physical_path.display(),
+                    e,
+                )))),
+                None => {
+                    warn!("Key description of passphrase binding {} stored on device {} does not appear to be a Stratis key description; ignoring", name, physical_path.display());
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
 /// Query the Stratis metadata for the pool name.
 pub fn pool_name_from_metadata(device: &mut CryptDevice) -> StratisResult<Option<Name>> {
     Ok(StratisLuks2Token::try_from(&device.token_handle().json_get(STRATIS_TOKEN_ID)?)?.pool_name)
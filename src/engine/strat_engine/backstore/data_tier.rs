@@ -4,78 +4,278 @@
 
 // Code to handle the backing store of a pool.
 
+use std::collections::HashMap;
 #[cfg(test)]
 use std::collections::HashSet;
 
-use devicemapper::Sectors;
+use devicemapper::{Device, Sectors};
 
 use crate::{
     engine::{
         strat_engine::{
             backstore::{
-                blockdev::StratBlockDev,
+                blockdev::{BlockDevEncryption, StratBlockDev},
                 blockdevmgr::BlockDevMgr,
                 devices::UnownedDevices,
+                migration::SegmentMove,
+                mirror::MIRROR_META_SIZE,
                 shared::{metadata_to_segment, AllocatedAbove, BlkDevSegment, BlockDevPartition},
                 transaction::RequestTransaction,
             },
-            serde_structs::{BaseDevSave, BlockDevSave, DataTierSave, Recordable},
+            serde_structs::{
+                BaseBlockDevSave, BaseDevSave, BlockDevSave, DataTierSave, Recordable,
+            },
             types::BDARecordResult,
         },
-        types::{BlockDevTier, DevUuid, Name, PoolUuid},
+        types::{
+            AllocationConfig, AllocationPolicy, AllocationPurpose, BlockDevTier, DevUuid,
+            EncryptionInfo, Name, PoolUuid, Redundancy,
+        },
     },
-    stratis::StratisResult,
+    stratis::{StratisError, StratisResult},
 };
 
+/// The two legs of a mirrored data tier. Every segment allocated to the
+/// first leg has a counterpart of the same size allocated to the second leg.
+#[derive(Debug)]
+pub struct MirrorLegs {
+    /// The UUIDs of the block devices that make up each leg
+    pub(super) devs: [Vec<DevUuid>; 2],
+    /// The list of segments granted by block_mgr to the second leg
+    pub(super) segments: AllocatedAbove,
+    /// The segments granted by block_mgr to hold the metadata of each leg
+    pub(super) meta: [AllocatedAbove; 2],
+    /// The leg which was missing when the tier was set up, if any. No
+    /// segments are held for it in segments or meta.
+    pub(super) missing_leg: Option<MissingLeg>,
+}
+
+/// A leg of a mirrored data tier of which some block devices holding
+/// segments were missing when the tier was set up. Its recorded segments
+/// are retained so that they remain in the metadata.
+#[derive(Debug)]
+pub struct MissingLeg {
+    /// The index of the leg, either 0 or 1
+    pub(super) index: usize,
+    /// The recorded data segments of the leg
+    pub(super) segments: Vec<BaseDevSave>,
+    /// The recorded metadata segments of the leg
+    pub(super) meta: Vec<BaseDevSave>,
+}
+
+/// Set up the legs of a mirrored data tier from the recorded allocations:
+/// the data segments of the first and second leg followed by the metadata
+/// segments of the first and second leg. A leg is missing if any of its
+/// segments is allocated from a block device not in uuid_to_devno. Return
+/// the data segments of the first leg and the legs.
+fn setup_mirror(
+    devs: &[Vec<DevUuid>; 2],
+    allocs: &[Vec<BaseDevSave>],
+    uuid_to_devno: &HashMap<DevUuid, Device>,
+) -> StratisResult<(AllocatedAbove, MirrorLegs)> {
+    if allocs.len() != 4 {
+        return Err(StratisError::Msg(format!(
+            "The data tier is mirrored, so the data and metadata segments of both legs must be recorded, but {} lists of segments are recorded",
+            allocs.len()
+        )));
+    }
+
+    let is_present = |leg: usize| {
+        allocs[leg]
+            .iter()
+            .chain(allocs[leg + 2].iter())
+            .all(|ld| uuid_to_devno.contains_key(&ld.parent))
+    };
+    let missing_leg = match (is_present(0), is_present(1)) {
+        (true, true) => None,
+        (false, true) => Some(0),
+        (true, false) => Some(1),
+        (false, false) => {
+            return Err(StratisError::Msg(
+                "Block devices holding segments of both legs of the mirrored data tier are missing"
+                    .to_string(),
+            ));
+        }
+    };
+
+    let map_segments = |idx: usize| -> StratisResult<AllocatedAbove> {
+        if missing_leg == Some(idx % 2) {
+            return Ok(AllocatedAbove { inner: vec![] });
+        }
+        allocs[idx]
+            .iter()
+            .map(|ld| metadata_to_segment(uuid_to_devno, ld))
+            .collect::<StratisResult<Vec<_>>>()
+            .map(|inner| AllocatedAbove { inner })
+    };
+
+    Ok((
+        map_segments(0)?,
+        MirrorLegs {
+            devs: devs.clone(),
+            segments: map_segments(1)?,
+            meta: [map_segments(2)?, map_segments(3)?],
+            missing_leg: missing_leg.map(|index| MissingLeg {
+                index,
+                segments: allocs[index].clone(),
+                meta: allocs[index + 2].clone(),
+            }),
+        },
+    ))
+}
+
+/// Allocate a metadata segment of MIRROR_META_SIZE from each of the given
+/// legs.
+fn alloc_mirror_meta(
+    block_mgr: &mut BlockDevMgr,
+    policy: AllocationPolicy,
+    legs: &[Vec<DevUuid>; 2],
+) -> StratisResult<[AllocatedAbove; 2]> {
+    let transaction = block_mgr
+        .request_space_mirrored(&[(MIRROR_META_SIZE, None)], policy, legs)?
+        .ok_or_else(|| {
+            StratisError::Msg(format!(
+                "Each leg of the mirror requires {MIRROR_META_SIZE} of space for its metadata"
+            ))
+        })?;
+    let meta = [0, 1].map(|leg| AllocatedAbove {
+        inner: transaction
+            .get_segs_for_req(leg)
+            .expect("one request was made for each leg"),
+    });
+    block_mgr.commit_space(transaction)?;
+    Ok(meta)
+}
+
 /// Handles the lowest level, base layer of this tier.
 #[derive(Debug)]
 pub struct DataTier {
     /// Manages the individual block devices
     pub(super) block_mgr: BlockDevMgr,
-    /// The list of segments granted by block_mgr and used by dm_device
+    /// The list of segments granted by block_mgr and used by dm_device. If
+    /// the tier is mirrored, these are the segments of the first leg.
     pub(super) segments: AllocatedAbove,
+    /// The legs of the mirror, if the tier is mirrored
+    pub(super) mirror: Option<MirrorLegs>,
+    /// The saved records of block devices which belong to the data tier but
+    /// were missing when it was set up. None of them have any segments
+    /// allocated. They are retained so that they remain in the metadata.
+    pub(super) missing: Vec<BaseBlockDevSave>,
+    /// The policy by which space is allocated from the block devices
+    pub(super) allocation: AllocationConfig,
 }
 
 impl DataTier {
     /// Setup a previously existing data layer from the block_mgr and
-    /// previously allocated segments.
+    /// previously allocated segments. Any block devices recorded in
+    /// data_tier_save which are not managed by block_mgr are treated as
+    /// missing. If the tier is mirrored, a leg with segments allocated on
+    /// missing block devices is treated as missing.
+    ///
+    /// Precondition: No segments are allocated on any missing block devices,
+    /// other than those of one leg of a mirrored tier.
     pub fn setup(
         block_mgr: BlockDevMgr,
         data_tier_save: &DataTierSave,
     ) -> BDARecordResult<DataTier> {
         let uuid_to_devno = block_mgr.uuid_to_devno();
-        let mapper = |ld: &BaseDevSave| -> StratisResult<BlkDevSegment> {
-            metadata_to_segment(&uuid_to_devno, ld)
+        let allocs = &data_tier_save.blockdev.allocs;
+        let segments = match data_tier_save.blockdev.legs {
+            Some(ref devs) => setup_mirror(devs, allocs, &uuid_to_devno)
+                .map(|(segments, mirror)| (segments, Some(mirror))),
+            None => allocs
+                .first()
+                .map(|allocs| allocs.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .map(|ld| metadata_to_segment(&uuid_to_devno, ld))
+                .collect::<StratisResult<Vec<_>>>()
+                .map(|inner| (AllocatedAbove { inner }, None)),
         };
-        let segments = match data_tier_save.blockdev.allocs[0]
-            .iter()
-            .map(&mapper)
-            .collect::<StratisResult<Vec<_>>>()
-        {
-            Ok(s) => AllocatedAbove { inner: s },
+        let (leg_segments, mirror) = match segments {
+            Ok(s) => s,
             Err(e) => return Err((e, block_mgr.into_bdas())),
         };
 
+        let missing = data_tier_save
+            .blockdev
+            .devs
+            .iter()
+            .filter(|bd_save| !uuid_to_devno.contains_key(&bd_save.uuid))
+            .cloned()
+            .collect();
+
         Ok(DataTier {
             block_mgr,
-            segments,
+            segments: leg_segments,
+            mirror,
+            missing,
+            allocation: data_tier_save.allocation.clone().unwrap_or_default(),
         })
     }
 
-    /// Setup a new DataTier struct from the block_mgr.
+    /// Setup a new DataTier struct from the block_mgr. If the data tier is
+    /// mirrored, consecutive pairs of block devices are split between the
+    /// two legs of the mirror, and a metadata segment is allocated from each
+    /// leg.
+    ///
+    /// Initially 0 data segments are allocated.
     ///
-    /// Initially 0 segments are allocated.
+    /// Precondition: redundancy == Redundancy::Raid1 -> the number of block
+    /// devices is even.
     ///
     /// WARNING: metadata changing event
-    pub fn new(block_mgr: BlockDevMgr) -> DataTier {
-        DataTier {
+    pub fn new(mut block_mgr: BlockDevMgr, redundancy: Redundancy) -> StratisResult<DataTier> {
+        let allocation = AllocationConfig::default();
+        let mirror = match redundancy {
+            Redundancy::None => None,
+            Redundancy::Raid1 => {
+                let mut devs = [Vec::new(), Vec::new()];
+                for (idx, (uuid, _)) in block_mgr.blockdevs().into_iter().enumerate() {
+                    devs[idx % 2].push(uuid);
+                }
+                assert_eq!(devs[0].len(), devs[1].len());
+
+                // The block devices are wiped if the metadata segments can
+                // not be allocated, since the tier is never set up.
+                let meta = match alloc_mirror_meta(&mut block_mgr, allocation.policy, &devs) {
+                    Ok(meta) => meta,
+                    Err(causal_error) => {
+                        if let Err(rollback_error) = block_mgr.destroy_all() {
+                            return Err(StratisError::NoActionRollbackError {
+                                causal_error: Box::new(causal_error),
+                                rollback_error: Box::new(rollback_error),
+                            });
+                        }
+                        return Err(causal_error);
+                    }
+                };
+
+                Some(MirrorLegs {
+                    devs,
+                    segments: AllocatedAbove { inner: vec![] },
+                    meta,
+                    missing_leg: None,
+                })
+            }
+        };
+
+        Ok(DataTier {
             block_mgr,
             segments: AllocatedAbove { inner: vec![] },
-        }
+            mirror,
+            missing: Vec::new(),
+            allocation,
+        })
     }
 
     /// Add the given paths to self. Return UUIDs of the new blockdevs
-    /// corresponding to the specified paths.
+    /// corresponding to the specified paths. If the data tier is mirrored,
+    /// consecutive pairs of the new block devices are split between the two
+    /// legs of the mirror.
+    ///
+    /// Precondition: self.redundancy() == Redundancy::Raid1 -> the number
+    /// of devices is even.
     /// WARNING: metadata changing event
     pub fn add(
         &mut self,
@@ -83,14 +283,99 @@ impl DataTier {
         pool_uuid: PoolUuid,
         devices: UnownedDevices,
     ) -> StratisResult<Vec<DevUuid>> {
-        self.block_mgr.add(pool_name, pool_uuid, devices)
+        let uuids = self.block_mgr.add(pool_name, pool_uuid, devices)?;
+        if let Some(ref mut mirror) = self.mirror {
+            assert_eq!(uuids.len() % 2, 0);
+            for (idx, uuid) in uuids.iter().enumerate() {
+                mirror.devs[idx % 2].push(*uuid);
+            }
+        }
+        Ok(uuids)
+    }
+
+    /// Whether the devices in this tier are protected by dm-integrity.
+    pub fn has_integrity(&self) -> bool {
+        self.block_mgr
+            .blockdevs()
+            .iter()
+            .any(|(_, bd)| bd.has_integrity())
+    }
+
+    /// The redundancy with which this tier stores its data.
+    pub fn redundancy(&self) -> Redundancy {
+        if self.mirror.is_some() {
+            Redundancy::Raid1
+        } else {
+            Redundancy::None
+        }
+    }
+
+    /// The UUIDs of the block devices which were missing when the tier was
+    /// set up.
+    pub fn missing(&self) -> Vec<DevUuid> {
+        self.missing.iter().map(|bd_save| bd_save.uuid).collect()
+    }
+
+    /// The data and metadata segments of each leg of the mirror, or None
+    /// for a leg which is missing. None if the tier is not mirrored.
+    pub fn mirror_legs(&self) -> Option<[Option<(&AllocatedAbove, &AllocatedAbove)>; 2]> {
+        self.mirror.as_ref().map(|mirror| {
+            let missing = mirror.missing_leg.as_ref().map(|leg| leg.index);
+            [
+                (missing != Some(0)).then_some((&self.segments, &mirror.meta[0])),
+                (missing != Some(1)).then_some((&mirror.segments, &mirror.meta[1])),
+            ]
+        })
     }
 
     /// Allocate a region for all sector size requests from unallocated segments in
-    /// block devices belonging to the data tier. Return Some(_) if requested
-    /// amount or more was allocated, otherwise, None.
-    pub fn alloc_request(&self, requests: &[Sectors]) -> StratisResult<Option<RequestTransaction>> {
-        self.block_mgr.request_space(requests)
+    /// block devices belonging to the data tier, according to the allocation
+    /// policy of the tier and the purpose of each request. Return Some(_) if
+    /// requested amount or more was allocated, otherwise, None. If the data
+    /// tier is mirrored, each request is allocated once from each leg.
+    pub fn alloc_request(
+        &self,
+        requests: &[(Sectors, AllocationPurpose)],
+    ) -> StratisResult<Option<RequestTransaction>> {
+        let requests = requests
+            .iter()
+            .map(|(size, purpose)| (*size, self.allocation.preferred_tag(*purpose)))
+            .collect::<Vec<_>>();
+        match self.mirror {
+            Some(MirrorLegs {
+                missing_leg: Some(ref leg),
+                ..
+            }) => Err(StratisError::Msg(format!(
+                "No space can be allocated from the mirrored data tier while leg {} of the mirror is missing",
+                leg.index
+            ))),
+            Some(ref mirror) => self.block_mgr.request_space_mirrored(
+                &requests,
+                self.allocation.policy,
+                &mirror.devs,
+            ),
+            None => self
+                .block_mgr
+                .request_space_by_policy(&requests, self.allocation.policy),
+        }
+    }
+
+    /// The policy by which space is allocated from the block devices of
+    /// this tier.
+    pub fn allocation_config(&self) -> &AllocationConfig {
+        &self.allocation
+    }
+
+    /// Change the policy by which space is allocated from the block devices
+    /// of this tier. Space which is already allocated is not moved.
+    /// Returns true if the policy changed.
+    pub fn set_allocation_config(&mut self, config: AllocationConfig) -> bool {
+        if self.allocation == config {
+            false
+        } else {
+            self.allocation = config;
+            true
+        }
     }
 
     /// Commit an allocation that was determined to be valid by alloc_request()
@@ -98,15 +383,179 @@ impl DataTier {
     pub fn alloc_commit(&mut self, transaction: RequestTransaction) -> StratisResult<()> {
         let segments = transaction.get_blockdevmgr();
         self.block_mgr.commit_space(transaction)?;
-        self.segments.coalesce_blkdevsegs(&segments);
+        match self.mirror {
+            Some(ref mut mirror) => {
+                let (first, second): (Vec<_>, Vec<_>) = segments
+                    .into_iter()
+                    .partition(|seg| mirror.devs[0].contains(&seg.uuid));
+                self.segments.coalesce_blkdevsegs(&first);
+                mirror.segments.coalesce_blkdevsegs(&second);
+            }
+            None => self.segments.coalesce_blkdevsegs(&segments),
+        }
 
         Ok(())
     }
 
+    /// Allocate new segments for all segments allocated from the block devices
+    /// specified by uuids from the remaining block devices in this tier. If
+    /// targets is specified, the new segments are allocated only from the
+    /// block devices it lists.
+    /// Return the uncommitted allocation together with the segments to move,
+    /// or None if no segments are allocated from the specified block devices.
+    ///
+    /// Precondition: self.mirror.is_none()
+    pub fn request_evacuation(
+        &self,
+        uuids: &[DevUuid],
+        targets: Option<&[DevUuid]>,
+    ) -> StratisResult<Option<(RequestTransaction, Vec<SegmentMove>)>> {
+        let to_move = self
+            .segments
+            .inner
+            .iter()
+            .enumerate()
+            .filter(|(_, seg)| uuids.contains(&seg.uuid))
+            .map(|(idx, seg)| (idx, seg.clone()))
+            .collect::<Vec<_>>();
+
+        if to_move.is_empty() {
+            return Ok(None);
+        }
+
+        let sizes = to_move
+            .iter()
+            .map(|(_, seg)| seg.segment.length)
+            .collect::<Vec<_>>();
+        let total = sizes.iter().cloned().sum::<Sectors>();
+        let exclude = match targets {
+            Some(targets) => self
+                .block_mgr
+                .blockdevs()
+                .into_iter()
+                .map(|(uuid, _)| uuid)
+                .filter(|uuid| uuids.contains(uuid) || !targets.contains(uuid))
+                .collect::<Vec<_>>(),
+            None => uuids.to_vec(),
+        };
+        let transaction = self
+            .block_mgr
+            .request_space_excluding(&sizes, &exclude)?
+            .ok_or_else(|| {
+                StratisError::Msg(format!(
+                    "The devices available in the data tier do not have the {total} of unallocated space required to hold the data moved off devices with UUIDs {}",
+                    uuids
+                        .iter()
+                        .map(|u| u.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            })?;
+
+        let moves = to_move
+            .into_iter()
+            .enumerate()
+            .map(|(req_idx, (index, old))| SegmentMove {
+                index,
+                old,
+                new: transaction
+                    .get_segs_for_req(req_idx)
+                    .expect("one request was made for each segment to move"),
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Some((transaction, moves)))
+    }
+
+    /// Commit an allocation made by request_evacuation() and replace each
+    /// moved segment with the segments it was moved to. The order of the
+    /// segments mapped to the upper device is preserved.
+    /// Return the segments mapped to the upper device before the change, for
+    /// revert_evacuation().
+    ///
+    /// Precondition: The data in each moved segment has been copied to its
+    /// new segments and any further writes go to both.
+    /// Postcondition: None of the block devices from which the moved segments
+    /// were allocated are in use by the upper device.
+    /// WARNING: metadata changing event
+    pub fn commit_evacuation(
+        &mut self,
+        transaction: RequestTransaction,
+        moves: &[SegmentMove],
+    ) -> StratisResult<Vec<BlkDevSegment>> {
+        self.block_mgr.commit_space(transaction)?;
+        let previous = self.segments.inner.clone();
+
+        let mut replacements = moves
+            .iter()
+            .map(|mv| (mv.index, mv.new.clone()))
+            .collect::<HashMap<_, _>>();
+        let segments = self
+            .segments
+            .inner
+            .drain(..)
+            .enumerate()
+            .flat_map(|(idx, seg)| replacements.remove(&idx).unwrap_or_else(|| vec![seg]))
+            .collect::<Vec<_>>();
+        self.segments.coalesce_blkdevsegs(&segments);
+
+        Ok(previous)
+    }
+
+    /// Map the segments returned by commit_evacuation() to the upper device
+    /// again and release the space that commit_evacuation() committed for
+    /// the new segments of the moves.
+    ///
+    /// Precondition: The data in each moved segment is still written to both
+    /// its old and its new segments.
+    pub fn revert_evacuation(
+        &mut self,
+        previous: Vec<BlkDevSegment>,
+        moves: &[SegmentMove],
+    ) -> StratisResult<()> {
+        self.segments = AllocatedAbove { inner: previous };
+        self.block_mgr.release_space(
+            &moves
+                .iter()
+                .flat_map(|mv| mv.new.iter().cloned())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Remove the block devices specified by uuids from this tier without
+    /// wiping them and return them, so that they can be wiped once the
+    /// metadata no longer refers to them.
+    ///
+    /// Precondition: No segments allocated from the specified block devices
+    /// are mapped to the upper device; see commit_evacuation().
+    /// WARNING: metadata changing event
+    pub fn remove(&mut self, uuids: &[DevUuid]) -> StratisResult<Vec<StratBlockDev>> {
+        assert!(!self
+            .segments
+            .inner
+            .iter()
+            .any(|seg| uuids.contains(&seg.uuid)));
+        self.block_mgr.detach_blockdevs(uuids)
+    }
+
+    /// Remove the block devices specified by uuids, which were just added to
+    /// this tier and have no segments allocated from them, and wipe them.
+    pub fn remove_added(&mut self, uuids: &[DevUuid]) -> StratisResult<()> {
+        self.block_mgr.remove_blockdevs(uuids)
+    }
+
     /// The sum of the lengths of all the sectors that have been mapped to an
-    /// upper device.
+    /// upper device. If the data tier is mirrored, each sector is counted
+    /// once, although it is allocated from both legs.
     pub fn allocated(&self) -> Sectors {
-        self.segments.size()
+        match self.mirror {
+            Some(MirrorLegs {
+                missing_leg: Some(MissingLeg { index: 0, .. }),
+                ref segments,
+                ..
+            }) => segments.size(),
+            _ => self.segments.size(),
+        }
     }
 
     /// The total size of all the blockdevs combined
@@ -119,9 +568,27 @@ impl DataTier {
         self.block_mgr.metadata_size()
     }
 
-    /// The total usable size of all the blockdevs combined
+    /// The total usable size of all the blockdevs combined. If the data tier
+    /// is mirrored, this is the usable size of the smaller leg which is
+    /// present, less the size of its metadata segment.
     pub fn usable_size(&self) -> Sectors {
-        self.size() - self.metadata_size()
+        match self.mirror {
+            Some(ref mirror) => mirror
+                .devs
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| mirror.missing_leg.as_ref().map(|leg| leg.index) != Some(*idx))
+                .map(|(_, devs)| {
+                    devs.iter()
+                        .filter_map(|uuid| self.block_mgr.get_blockdev_by_uuid(*uuid))
+                        .map(|bd| bd.usable_size().sectors() - bd.metadata_size().sectors())
+                        .sum::<Sectors>()
+                        - MIRROR_META_SIZE
+                })
+                .min()
+                .expect("at least one leg of a mirror is present"),
+            None => self.size() - self.metadata_size(),
+        }
     }
 
     /// Destroy the store. Wipe its blockdevs.
@@ -165,6 +632,49 @@ impl DataTier {
         self.block_mgr.grow(dev)
     }
 
+    /// Begin encrypting the block device with the given UUID in place and
+    /// update the segments allocated from it to refer to the activated
+    /// crypt device.
+    ///
+    /// Precondition: No IO is issued to the data tier until the segments
+    /// have been reloaded.
+    pub fn begin_encryption(
+        &mut self,
+        uuid: DevUuid,
+        pool_name: Name,
+        encryption_info: &EncryptionInfo,
+    ) -> StratisResult<BlockDevEncryption> {
+        let bd = self
+            .block_mgr
+            .get_mut_blockdev_by_uuid(uuid)
+            .ok_or_else(|| StratisError::Msg(format!("No block device found for UUID {uuid}")))?;
+        let encryption = bd.begin_encryption(pool_name, encryption_info)?;
+        let device = *bd.device();
+        self.segments.set_device(uuid, device);
+        Ok(encryption)
+    }
+
+    /// Undo the encryption of the block device with the given UUID and
+    /// update the segments allocated from it to refer to the unencrypted
+    /// device again.
+    ///
+    /// Precondition: No IO is issued to the data tier until the segments
+    /// have been reloaded.
+    pub fn abort_encryption(
+        &mut self,
+        uuid: DevUuid,
+        encryption: &BlockDevEncryption,
+    ) -> StratisResult<()> {
+        let bd = self
+            .block_mgr
+            .get_mut_blockdev_by_uuid(uuid)
+            .ok_or_else(|| StratisError::Msg(format!("No block device found for UUID {uuid}")))?;
+        bd.abort_encryption(encryption)?;
+        let device = *bd.device();
+        self.segments.set_device(uuid, device);
+        Ok(())
+    }
+
     /// Return the partition of the block devs that are in use and those
     /// that are not.
     pub fn partition_by_use(&self) -> BlockDevPartition<'_> {
@@ -175,7 +685,27 @@ impl DataTier {
 
     #[cfg(test)]
     pub fn invariant(&self) {
-        let allocated_uuids = self.segments.uuids();
+        let mut allocated_uuids = self.segments.uuids();
+        if let Some(ref mirror) = self.mirror {
+            if mirror.missing_leg.is_none() {
+                assert_eq!(self.segments.size(), mirror.segments.size());
+                assert!(mirror
+                    .meta
+                    .iter()
+                    .all(|meta| meta.size() == MIRROR_META_SIZE));
+            }
+            let leg_uuids = mirror.devs[0].iter().cloned().collect::<HashSet<_>>();
+            assert!(self.segments.uuids().iter().all(|u| leg_uuids.contains(u)));
+            assert!(!mirror
+                .segments
+                .uuids()
+                .iter()
+                .any(|u| leg_uuids.contains(u)));
+            allocated_uuids.extend(mirror.segments.uuids());
+            for meta in mirror.meta.iter() {
+                allocated_uuids.extend(meta.uuids());
+            }
+        }
         let in_use_uuids = self
             .block_mgr
             .blockdevs()
@@ -184,6 +714,10 @@ impl DataTier {
             .map(|(u, _)| *u)
             .collect::<HashSet<_>>();
         assert_eq!(allocated_uuids, in_use_uuids);
+        assert!(!self
+            .missing
+            .iter()
+            .any(|bd_save| self.block_mgr.get_blockdev_by_uuid(bd_save.uuid).is_some()));
     }
 }
 
@@ -191,9 +725,31 @@ impl Recordable<DataTierSave> for DataTier {
     fn record(&self) -> DataTierSave {
         DataTierSave {
             blockdev: BlockDevSave {
-                allocs: vec![self.segments.record()],
-                devs: self.block_mgr.record(),
+                allocs: match self.mirror {
+                    Some(ref mirror) => {
+                        let mut allocs = vec![
+                            self.segments.record(),
+                            mirror.segments.record(),
+                            mirror.meta[0].record(),
+                            mirror.meta[1].record(),
+                        ];
+                        if let Some(ref leg) = mirror.missing_leg {
+                            allocs[leg.index] = leg.segments.clone();
+                            allocs[leg.index + 2] = leg.meta.clone();
+                        }
+                        allocs
+                    }
+                    None => vec![self.segments.record()],
+                },
+                devs: self
+                    .block_mgr
+                    .record()
+                    .into_iter()
+                    .chain(self.missing.iter().cloned())
+                    .collect(),
+                legs: self.mirror.as_ref().map(|mirror| mirror.devs.clone()),
             },
+            allocation: Some(self.allocation.clone()),
         }
     }
 }
@@ -239,11 +795,13 @@ mod tests {
             pool_uuid,
             devices1,
             MDADataSize::default(),
+            false,
+            None,
             None,
         )
         .unwrap();
 
-        let mut data_tier = DataTier::new(mgr);
+        let mut data_tier = DataTier::new(mgr, Redundancy::None).unwrap();
         data_tier.invariant();
 
         // A data_tier w/ some devices but nothing allocated
@@ -257,7 +815,10 @@ mod tests {
         let request_amount = data_tier.block_mgr.avail_space() / 2usize;
         assert!(request_amount != Sectors(0));
 
-        let transaction = data_tier.alloc_request(&[request_amount]).unwrap().unwrap();
+        let transaction = data_tier
+            .alloc_request(&[(request_amount, AllocationPurpose::ThinData)])
+            .unwrap()
+            .unwrap();
         data_tier.alloc_commit(transaction).unwrap();
         data_tier.invariant();
 
@@ -277,7 +838,7 @@ mod tests {
 
         // Allocate enough to get into the newly added block devices
         let transaction = data_tier
-            .alloc_request(&[last_request_amount])
+            .alloc_request(&[(last_request_amount, AllocationPurpose::ThinData)])
             .unwrap()
             .unwrap();
         data_tier.alloc_commit(transaction).unwrap();
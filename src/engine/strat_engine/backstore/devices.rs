@@ -23,8 +23,9 @@ use crate::{
     engine::{
         strat_engine::{
             backstore::{
-                blockdev::{StratBlockDev, UnderlyingDevice},
+                blockdev::{DataDevice, StratBlockDev, UnderlyingDevice},
                 crypt::{CryptHandle, CryptInitializer},
+                integrity::IntegrityDev,
             },
             device::{blkdev_logical_sector_size, blkdev_physical_sector_size, blkdev_size},
             metadata::{
@@ -37,7 +38,9 @@ use crate::{
                 STRATIS_FS_TYPE,
             },
         },
-        types::{ClevisInfo, DevUuid, DevicePath, EncryptionInfo, Name, PoolUuid},
+        types::{
+            ClevisInfo, DevUuid, DevicePath, EncryptionConfig, EncryptionInfo, Name, PoolUuid,
+        },
     },
     stratis::{StratisError, StratisResult},
 };
@@ -472,6 +475,10 @@ impl UnownedDevices {
         self.inner.is_empty()
     }
 
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
     pub fn unpack(self) -> Vec<DeviceInfo> {
         self.inner
     }
@@ -504,7 +511,9 @@ pub fn initialize_devices(
     pool_name: Name,
     pool_uuid: PoolUuid,
     mda_data_size: MDADataSize,
+    integrity: bool,
     encryption_info: Option<&EncryptionInfo>,
+    encryption_config: Option<&EncryptionConfig>,
 ) -> StratisResult<Vec<StratBlockDev>> {
     /// Initialize an encrypted device on the given physical device
     /// using the pool and device UUIDs of the new Stratis block device
@@ -520,9 +529,10 @@ pub fn initialize_devices(
         dev_uuid: DevUuid,
         key_description: Option<&KeyDescription>,
         enable_clevis: Option<&ClevisInfo>,
+        encryption_config: Option<&EncryptionConfig>,
     ) -> StratisResult<(CryptHandle, Device, Sectors)> {
         let handle = CryptInitializer::new(DevicePath::new(physical_path)?, pool_uuid, dev_uuid)
-            .initialize(pool_name, key_description, enable_clevis)?;
+            .initialize(pool_name, key_description, enable_clevis, encryption_config)?;
 
         let device_size = match handle.logical_device_size() {
             Ok(size) => size,
@@ -545,10 +555,10 @@ pub fn initialize_devices(
     fn initialize_stratis_metadata(
         underlying_device: UnderlyingDevice,
         devno: Device,
-        pool_uuid: PoolUuid,
-        dev_uuid: DevUuid,
+        identifiers: StratisIdentifiers,
         sizes: (MDADataSize, BlockdevSize),
         id_wwn: &Option<StratisResult<String>>,
+        integrity: bool,
         blksizes: BlockSizes,
     ) -> StratisResult<StratBlockDev> {
         let (mda_data_size, data_size) = sizes;
@@ -565,23 +575,37 @@ pub fn initialize_devices(
             (_, Some(Err(_))) => {
                 warn!("Value for ID_WWN for device {} obtained from the udev database could not be decoded; inserting device into pool with UUID {} anyway",
                       underlying_device.physical_path().display(),
-                      pool_uuid);
+                      identifiers.pool_uuid);
                 None
             }
             (_, None) => None,
         };
 
-        let bda = BDA::new(
-            StratisIdentifiers::new(pool_uuid, dev_uuid),
-            mda_data_size,
-            data_size,
-            Utc::now(),
-        );
+        let bda = BDA::new(identifiers, mda_data_size, data_size, Utc::now());
 
         bda.initialize(&mut f)?;
 
-        StratBlockDev::new(devno, bda, &[], None, hw_id, underlying_device, blksizes)
-            .map_err(|(e, _)| e)
+        let data_device = if integrity {
+            DataDevice::Integrity(IntegrityDev::initialize(
+                identifiers.device_uuid,
+                underlying_device.metadata_path(),
+                devno,
+                bda.extended_size().sectors(),
+            )?)
+        } else {
+            DataDevice::Plain(devno)
+        };
+
+        StratBlockDev::new(
+            data_device,
+            bda,
+            &[],
+            None,
+            hw_id,
+            underlying_device,
+            blksizes,
+        )
+        .map_err(|(e, _)| e)
     }
 
     /// Clean up an encrypted device after initialization failure.
@@ -645,7 +669,9 @@ pub fn initialize_devices(
         pool_name: Name,
         pool_uuid: PoolUuid,
         mda_data_size: MDADataSize,
+        integrity: bool,
         encryption_info: Option<&EncryptionInfo>,
+        encryption_config: Option<&EncryptionConfig>,
     ) -> StratisResult<StratBlockDev> {
         let dev_uuid = DevUuid::new_v4();
         let (handle, devno, blockdev_size) = if let Some(ei) = encryption_info {
@@ -656,6 +682,7 @@ pub fn initialize_devices(
                 dev_uuid,
                 ei.key_description(),
                 ei.clevis_info(),
+                encryption_config,
             )
             .map(|(handle, devno, devsize)| {
                 debug!(
@@ -685,10 +712,10 @@ pub fn initialize_devices(
                 let blockdev = initialize_stratis_metadata(
                     UnderlyingDevice::Encrypted(handle),
                     devno,
-                    pool_uuid,
-                    dev_uuid,
+                    StratisIdentifiers::new(pool_uuid, dev_uuid),
                     (mda_data_size, BlockdevSize::new(blockdev_size)),
                     &dev_info.id_wwn,
+                    integrity,
                     dev_info.blksizes,
                 );
                 if let Err(err) = blockdev {
@@ -701,10 +728,10 @@ pub fn initialize_devices(
                 let blockdev = initialize_stratis_metadata(
                     UnderlyingDevice::Unencrypted(DevicePath::new(physical_path)?),
                     devno,
-                    pool_uuid,
-                    dev_uuid,
+                    StratisIdentifiers::new(pool_uuid, dev_uuid),
                     (mda_data_size, BlockdevSize::new(blockdev_size)),
                     &dev_info.id_wwn,
+                    integrity,
                     dev_info.blksizes,
                 );
                 if let Err(err) = blockdev {
@@ -722,7 +749,9 @@ pub fn initialize_devices(
         pool_name: Name,
         pool_uuid: PoolUuid,
         mda_data_size: MDADataSize,
+        integrity: bool,
         encryption_info: Option<&EncryptionInfo>,
+        encryption_config: Option<&EncryptionConfig>,
     ) -> StratisResult<Vec<StratBlockDev>> {
         let mut initialized_blockdevs: Vec<StratBlockDev> = Vec::new();
         for dev_info in devices.inner {
@@ -731,7 +760,9 @@ pub fn initialize_devices(
                 pool_name.clone(),
                 pool_uuid,
                 mda_data_size,
+                integrity,
                 encryption_info,
+                encryption_config,
             ) {
                 Ok(blockdev) => initialized_blockdevs.push(blockdev),
                 Err(err) => {
@@ -766,7 +797,9 @@ pub fn initialize_devices(
         pool_name,
         pool_uuid,
         mda_data_size,
+        integrity,
         encryption_info,
+        encryption_config,
     );
 
     {
@@ -841,9 +874,11 @@ mod tests {
             pool_name,
             pool_uuid,
             MDADataSize::default(),
+            false,
             key_description
                 .map(|kd| EncryptionInfo::KeyDesc(kd.clone()))
                 .as_ref(),
+            None,
         )?;
 
         if blockdevs.len() != paths.len() {
@@ -1098,9 +1133,11 @@ mod tests {
             pool_name,
             pool_uuid,
             MDADataSize::default(),
+            false,
             key_desc
                 .map(|kd| EncryptionInfo::KeyDesc(kd.clone()))
                 .as_ref(),
+            None,
         )
         .is_ok()
         {
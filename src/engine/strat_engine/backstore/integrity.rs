@@ -0,0 +1,273 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Code to handle the dm-integrity protection of a single block device.
+
+use std::{
+    fs::OpenOptions,
+    io::{Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use devicemapper::{DevId, Device, DmFlags, DmName, DmOptions, DmUuid, Sectors};
+
+use crate::{
+    engine::{
+        strat_engine::{
+            dm::get_dm,
+            names::{format_integrity_ids, IntegrityRole},
+        },
+        types::DevUuid,
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// The size in bytes of the dm-integrity superblock. If this region is zeroed when
+/// the DM integrity device is first activated, the kernel formats the device.
+const INTEGRITY_SUPERBLOCK_SIZE: usize = 4096;
+
+/// The size in bytes of the integrity tag computed for each sector.
+const INTEGRITY_TAG_SIZE: u64 = 4;
+
+/// The hash algorithm used to compute the integrity tags.
+const INTEGRITY_HASH: &str = "crc32c";
+
+/// A DM integrity device in journaled mode which covers the data region of
+/// a block device, together with a device which presents it to upper
+/// layers. The Stratis metadata region of the block device is not covered
+/// by the DM integrity device; instead, the DM integrity device reserves
+/// it, and places its own superblock, journal, and tags after it.
+///
+/// The upper layer device maps a zero target over the Stratis metadata
+/// region and the DM integrity device after it, so that the offsets of the
+/// data region are the same as those on the block device itself.
+#[derive(Debug)]
+pub struct IntegrityDev {
+    /// The device number of the device which presents the DM integrity
+    /// device to upper layers
+    device: Device,
+    /// The size of the Stratis metadata region of the block device
+    metadata_size: Sectors,
+    /// The number of data sectors provided by the DM integrity device
+    data_size: Sectors,
+    /// The UUID of the block device protected by the DM integrity device
+    dev_uuid: DevUuid,
+}
+
+impl IntegrityDev {
+    /// Prepare the block device at path for dm-integrity protection by
+    /// wiping the region where the DM integrity superblock will be placed,
+    /// and then set up the devices.
+    ///
+    /// Precondition: path and devno refer to the same block device and the
+    /// Stratis metadata region of the device occupies metadata_size sectors.
+    pub fn initialize(
+        dev_uuid: DevUuid,
+        path: &Path,
+        devno: Device,
+        metadata_size: Sectors,
+    ) -> StratisResult<IntegrityDev> {
+        let mut f = OpenOptions::new().write(true).open(path)?;
+        f.seek(SeekFrom::Start(convert_int!(
+            *metadata_size.bytes(),
+            u128,
+            u64
+        )?))?;
+        f.write_all(&[0u8; INTEGRITY_SUPERBLOCK_SIZE])?;
+        f.sync_all()?;
+
+        IntegrityDev::setup(dev_uuid, devno, metadata_size)
+    }
+
+    /// Set up the DM integrity device on the block device with the given
+    /// device number, and the device which presents it to upper layers. If
+    /// the devices already exist, they are reloaded only if their tables
+    /// differ from the tables specified. If the setup fails, the DM integrity
+    /// device is removed only if it was created by this call.
+    pub fn setup(
+        dev_uuid: DevUuid,
+        devno: Device,
+        metadata_size: Sectors,
+    ) -> StratisResult<IntegrityDev> {
+        let (dm_name, dm_uuid) = format_integrity_ids(dev_uuid, IntegrityRole::Integrity);
+        let integrity_table = |size: Sectors| {
+            vec![(
+                0,
+                *size,
+                "integrity".to_string(),
+                format!(
+                    "{devno} {} {INTEGRITY_TAG_SIZE} J 2 internal_hash:{INTEGRITY_HASH} recalculate",
+                    *metadata_size
+                ),
+            )]
+        };
+
+        // The number of data sectors that the DM integrity device provides
+        // is only known once the kernel has read or written its superblock,
+        // so a new device is first activated with a table of minimal size.
+        let created = get_dm().device_info(&DevId::Name(&dm_name)).is_err();
+        if created {
+            setup_dm_device(&dm_name, &dm_uuid, &integrity_table(Sectors(1)))?;
+        }
+
+        let rollback = |err: StratisError| -> StratisError {
+            if !created {
+                return err;
+            }
+            match get_dm().device_remove(&DevId::Name(&dm_name), DmOptions::default()) {
+                Ok(_) => err,
+                Err(rollback_err) => StratisError::NoActionRollbackError {
+                    causal_error: Box::new(err),
+                    rollback_error: Box::new(StratisError::from(rollback_err)),
+                },
+            }
+        };
+
+        let res = integrity_status(&dm_name).and_then(|(_, data_size)| {
+            setup_dm_device(&dm_name, &dm_uuid, &integrity_table(data_size))
+                .map(|device| (device, data_size))
+        });
+        let (integrity_device, data_size) = match res {
+            Ok(vals) => vals,
+            Err(err) => return Err(rollback(err)),
+        };
+
+        let (sub_name, sub_uuid) = format_integrity_ids(dev_uuid, IntegrityRole::IntegritySub);
+        let device = match setup_dm_device(
+            &sub_name,
+            &sub_uuid,
+            &[
+                (0, *metadata_size, "zero".to_string(), String::new()),
+                (
+                    *metadata_size,
+                    *data_size,
+                    "linear".to_string(),
+                    format!("{integrity_device} 0"),
+                ),
+            ],
+        ) {
+            Ok(device) => device,
+            Err(err) => return Err(rollback(err)),
+        };
+
+        Ok(IntegrityDev {
+            device,
+            metadata_size,
+            data_size,
+            dev_uuid,
+        })
+    }
+
+    /// The device number of the device which upper layers should allocate
+    /// from in place of the block device.
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// The number of sectors that can be allocated from the protected block
+    /// device, including its Stratis metadata region.
+    pub fn size(&self) -> Sectors {
+        self.metadata_size + self.data_size
+    }
+
+    /// The number of integrity mismatches that the DM integrity device has
+    /// detected since it was activated.
+    pub fn mismatches(&self) -> StratisResult<u64> {
+        let (dm_name, _) = format_integrity_ids(self.dev_uuid, IntegrityRole::Integrity);
+        integrity_status(&dm_name).map(|(mismatches, _)| mismatches)
+    }
+
+    /// Remove the device which presents the DM integrity device and then
+    /// the DM integrity device itself.
+    pub fn teardown(&mut self) -> StratisResult<()> {
+        for role in [IntegrityRole::IntegritySub, IntegrityRole::Integrity] {
+            let (dm_name, _) = format_integrity_ids(self.dev_uuid, role);
+            get_dm().device_remove(&DevId::Name(&dm_name), DmOptions::default())?;
+        }
+        Ok(())
+    }
+}
+
+/// Create the DM device with the given name and UUID and load the given
+/// table, or, if the device already exists, reload it if its table differs.
+/// Return the device number of the device.
+fn setup_dm_device(
+    dm_name: &DmName,
+    dm_uuid: &DmUuid,
+    table: &[(u64, u64, String, String)],
+) -> StratisResult<Device> {
+    let id = DevId::Name(dm_name);
+    match get_dm().device_info(&id) {
+        Ok(_) => {
+            let (info, current) = get_dm().table_status(
+                &id,
+                DmOptions::default().set_flags(DmFlags::DM_STATUS_TABLE),
+            )?;
+            // The kernel reports target parameters in a normalized form, so
+            // only the extents and types of the targets are compared.
+            let differs = current.len() != table.len()
+                || current
+                    .iter()
+                    .zip(table.iter())
+                    .any(|(cur, new)| (cur.0, cur.1, &cur.2) != (new.0, new.1, &new.2));
+            if differs {
+                get_dm().table_load(&id, table, DmOptions::default())?;
+                get_dm().device_suspend(&id, DmOptions::default())?;
+            }
+            Ok(info.device())
+        }
+        Err(_) => {
+            get_dm().device_create(dm_name, Some(dm_uuid), DmOptions::default())?;
+            match get_dm()
+                .table_load(&id, table, DmOptions::default())
+                .and_then(|_| get_dm().device_suspend(&id, DmOptions::default()))
+            {
+                Ok(info) => Ok(info.device()),
+                Err(causal_error) => {
+                    if let Err(rollback_error) = get_dm().device_remove(&id, DmOptions::default()) {
+                        return Err(StratisError::NoActionRollbackError {
+                            causal_error: Box::new(StratisError::from(causal_error)),
+                            rollback_error: Box::new(StratisError::from(rollback_error)),
+                        });
+                    }
+                    Err(StratisError::from(causal_error))
+                }
+            }
+        }
+    }
+}
+
+/// Get the number of integrity mismatches and the number of data sectors
+/// provided from the status of the DM integrity device with the given name.
+fn integrity_status(dm_name: &DmName) -> StratisResult<(u64, Sectors)> {
+    let (_, status) = get_dm().table_status(&DevId::Name(dm_name), DmOptions::default())?;
+    let params = status
+        .first()
+        .map(|(_, _, _, params)| params.clone())
+        .ok_or_else(|| {
+            StratisError::Msg(format!(
+                "No status was found for DM integrity device {dm_name}"
+            ))
+        })?;
+    let mut fields = params.split_whitespace();
+    let mut next_field = || -> StratisResult<u64> {
+        fields
+            .next()
+            .ok_or_else(|| {
+                StratisError::Msg(format!(
+                    "Status \"{params}\" of DM integrity device {dm_name} has too few fields"
+                ))
+            })
+            .and_then(|field| {
+                field.parse::<u64>().map_err(|_| {
+                    StratisError::Msg(format!(
+                        "Unexpected value {field} in status of DM integrity device {dm_name}"
+                    ))
+                })
+            })
+    };
+    let mismatches = next_field()?;
+    let data_size = Sectors(next_field()?);
+    Ok((mismatches, data_size))
+}
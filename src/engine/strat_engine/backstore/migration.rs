@@ -0,0 +1,278 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Code to move segments of the data tier while the pool remains in use.
+
+use std::{cmp::min, thread, time::Duration};
+
+use devicemapper::{
+    DevId, Device, DmDevice, DmName, DmNameBuf, DmOptions, LinearDev, LinearDevTargetParams,
+    LinearTargetParams, Sectors, TargetLine,
+};
+
+use crate::{
+    engine::{
+        strat_engine::{
+            backstore::shared::{AllocatedAbove, BlkDevSegment},
+            dm::get_dm,
+            names::{format_backstore_ids, CacheRole},
+        },
+        types::PoolUuid,
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// The largest region size of a DM mirror device. The kernel copies, and
+/// tracks as in sync, each region of a DM mirror device as a unit.
+const MAX_REGION_SIZE: Sectors = Sectors(1024); // 512 KiB
+
+/// A segment of the data tier and the newly allocated segments it is moved
+/// to, which have the same total length.
+#[derive(Debug)]
+pub struct SegmentMove {
+    /// The index of the segment in the segments of the data tier
+    pub(super) index: usize,
+    /// The segment that is moved
+    pub(super) old: BlkDevSegment,
+    /// The segments that the segment is moved to
+    pub(super) new: Vec<BlkDevSegment>,
+}
+
+/// The DM devices which move segments of the data tier, one DM mirror device
+/// in core log mode for each segment. The first leg of each DM mirror device
+/// is the segment itself, the second a linear device which maps the segments
+/// it is moved to.
+///
+/// While the origin maps the moved segments onto the DM mirror devices, the
+/// kernel copies the data to the second legs and every write goes to both
+/// legs, so the pool remains in use while the data is moved, and both the
+/// old and the new locations of the data are valid once the legs are in
+/// sync.
+#[derive(Debug)]
+pub struct Migration {
+    /// The segments that are moved
+    moves: Vec<SegmentMove>,
+    /// The DM mirror devices, in the order of moves
+    mirrors: Vec<(DmNameBuf, Device)>,
+    /// The second legs of the DM mirror devices, in the order of moves
+    dests: Vec<LinearDev>,
+    /// The segments of the data tier before the moves were committed to it
+    pub(super) previous: Option<Vec<BlkDevSegment>>,
+}
+
+impl Migration {
+    /// Set up a DM mirror device for each move. The kernel starts copying
+    /// the data to the new segments immediately.
+    ///
+    /// A DM mirror device left over from a move that was interrupted is not
+    /// in use, since the origin is set up from the metadata, which records
+    /// only the old locations; it is replaced.
+    pub fn setup(pool_uuid: PoolUuid, moves: Vec<SegmentMove>) -> StratisResult<Migration> {
+        let mut migration = Migration {
+            moves: Vec::new(),
+            mirrors: Vec::new(),
+            dests: Vec::new(),
+            previous: None,
+        };
+
+        for (index, mv) in moves.iter().enumerate() {
+            if let Err(causal_error) = migration.setup_mirror(pool_uuid, index, mv) {
+                if let Err(rollback_error) = migration.teardown() {
+                    return Err(StratisError::NoActionRollbackError {
+                        causal_error: Box::new(causal_error),
+                        rollback_error: Box::new(rollback_error),
+                    });
+                }
+                return Err(causal_error);
+            }
+        }
+
+        migration.moves = moves;
+        Ok(migration)
+    }
+
+    /// Set up the linear device which maps the new segments of mv and the DM
+    /// mirror device on top of it and the old segment.
+    fn setup_mirror(
+        &mut self,
+        pool_uuid: PoolUuid,
+        index: usize,
+        mv: &SegmentMove,
+    ) -> StratisResult<()> {
+        let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::MoveDestSub(index));
+        let table = AllocatedAbove {
+            inner: mv.new.clone(),
+        }
+        .map_to_dm();
+        let dest = LinearDev::setup(get_dm(), &dm_name, Some(&dm_uuid), table)?;
+        let dest_device = dest.device();
+        self.dests.push(dest);
+
+        let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::MoveSub(index));
+        let id = DevId::Name(&dm_name);
+        if get_dm().device_info(&id).is_ok() {
+            get_dm().device_remove(&id, DmOptions::default())?;
+        }
+        get_dm().device_create(&dm_name, Some(&dm_uuid), DmOptions::default())?;
+        let info = match get_dm()
+            .table_load(
+                &id,
+                &mirror_table(&mv.old, dest_device),
+                DmOptions::default(),
+            )
+            .and_then(|_| get_dm().device_suspend(&id, DmOptions::default()))
+        {
+            Ok(info) => info,
+            Err(causal_error) => {
+                if let Err(rollback_error) = get_dm().device_remove(&id, DmOptions::default()) {
+                    return Err(StratisError::NoActionRollbackError {
+                        causal_error: Box::new(StratisError::from(causal_error)),
+                        rollback_error: Box::new(StratisError::from(rollback_error)),
+                    });
+                }
+                return Err(StratisError::from(causal_error));
+            }
+        };
+        self.mirrors.push((dm_name, info.device()));
+        Ok(())
+    }
+
+    /// The segments that are moved.
+    pub fn moves(&self) -> &[SegmentMove] {
+        &self.moves
+    }
+
+    /// The table of the origin which maps the given segments of the data
+    /// tier, each moved segment onto its DM mirror device.
+    pub fn origin_table(
+        &self,
+        segments: &AllocatedAbove,
+    ) -> Vec<TargetLine<LinearDevTargetParams>> {
+        let mut logical_start_offset = Sectors(0);
+        segments
+            .inner
+            .iter()
+            .enumerate()
+            .map(|(idx, seg)| {
+                let segment = seg.to_segment();
+                let params = match self.moves.iter().position(|mv| mv.index == idx) {
+                    Some(pos) => LinearTargetParams::new(self.mirrors[pos].1, Sectors(0)),
+                    None => LinearTargetParams::new(segment.device, segment.start),
+                };
+                let line = TargetLine::new(
+                    logical_start_offset,
+                    segment.length,
+                    LinearDevTargetParams::Linear(params),
+                );
+                logical_start_offset += segment.length;
+                line
+            })
+            .collect()
+    }
+
+    /// Wait until the second leg of every DM mirror device is in sync with
+    /// the first. progress is invoked with the number of sectors copied so
+    /// far and the total number of sectors to copy.
+    ///
+    /// Return an error if a leg of any DM mirror device has failed.
+    pub fn wait_for_sync<F>(&self, mut progress: F) -> StratisResult<()>
+    where
+        F: FnMut(Sectors, Sectors),
+    {
+        let total = self
+            .moves
+            .iter()
+            .map(|mv| mv.old.segment.length)
+            .sum::<Sectors>();
+        loop {
+            let mut copied = Sectors(0);
+            for ((dm_name, _), mv) in self.mirrors.iter().zip(self.moves.iter()) {
+                let (in_sync, regions) = mirror_sync_status(dm_name)?;
+                let length = mv.old.segment.length;
+                copied += if in_sync == regions {
+                    length
+                } else {
+                    min(length, region_size(length) * in_sync)
+                };
+            }
+            progress(copied, total);
+            if copied == total {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+    }
+
+    /// Remove the DM mirror devices and then their second legs.
+    ///
+    /// Precondition: The origin no longer maps any segment onto a DM mirror
+    /// device.
+    pub fn teardown(&mut self) -> StratisResult<()> {
+        for (dm_name, _) in self.mirrors.drain(..) {
+            get_dm().device_remove(&DevId::Name(&dm_name), DmOptions::default())?;
+        }
+        for mut dest in self.dests.drain(..) {
+            dest.teardown(get_dm())?;
+        }
+        Ok(())
+    }
+}
+
+/// The region size of a DM mirror device of the given length, the largest
+/// power of two that is no larger than MAX_REGION_SIZE or the length.
+fn region_size(length: Sectors) -> Sectors {
+    let limit = *min(MAX_REGION_SIZE, length);
+    Sectors(1 << (u64::BITS - 1 - limit.leading_zeros()))
+}
+
+/// The raw table of a DM mirror device in core log mode which copies the
+/// segment old to the device dest.
+fn mirror_table(old: &BlkDevSegment, dest: Device) -> Vec<(u64, u64, String, String)> {
+    let length = old.segment.length;
+    vec![(
+        0,
+        *length,
+        "mirror".to_string(),
+        format!(
+            "core 1 {} 2 {} {} {} 0",
+            *region_size(length),
+            old.segment.device,
+            *old.segment.start,
+            dest
+        ),
+    )]
+}
+
+/// The number of regions of the DM mirror device which are in sync and the
+/// total number of regions. Return an error if any leg has failed.
+///
+/// The status line has the format:
+/// <#legs> <leg>... <in sync regions>/<total regions> 1 <leg health> <log status>
+/// where the leg health has one character per leg, 'A' if the leg is alive.
+fn mirror_sync_status(dm_name: &DmName) -> StratisResult<(u64, u64)> {
+    let (_, status) = get_dm().table_status(&DevId::Name(dm_name), DmOptions::default())?;
+    let params = status
+        .first()
+        .map(|(_, _, _, params)| params.split_whitespace().collect::<Vec<_>>())
+        .ok_or_else(|| StratisError::Msg(format!("Device {dm_name} has no status")))?;
+
+    let parse_err = || StratisError::Msg(format!("Failed to parse status of device {dm_name}"));
+    let legs = params
+        .first()
+        .and_then(|legs| legs.parse::<usize>().ok())
+        .ok_or_else(parse_err)?;
+    let (in_sync, regions) = params
+        .get(legs + 1)
+        .and_then(|ratio| ratio.split_once('/'))
+        .and_then(|(in_sync, regions)| Some((in_sync.parse().ok()?, regions.parse().ok()?)))
+        .ok_or_else(parse_err)?;
+    let health = params.get(legs + 3).ok_or_else(parse_err)?;
+    if health.chars().any(|c| c != 'A') {
+        return Err(StratisError::Msg(format!(
+            "A leg of device {dm_name} has failed; leg health: {health}"
+        )));
+    }
+
+    Ok((in_sync, regions))
+}
@@ -0,0 +1,251 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Code to handle the DM raid device of a mirrored data tier.
+
+use std::cmp::min;
+
+use devicemapper::{
+    DevId, Device, DmDevice, DmFlags, DmNameBuf, DmOptions, LinearDev, LinearDevTargetParams,
+    LinearTargetParams, Sectors, TargetLine,
+};
+
+use crate::{
+    engine::{
+        strat_engine::{
+            dm::get_dm,
+            names::{format_backstore_ids, CacheRole},
+            writing::wipe_sectors,
+        },
+        types::PoolUuid,
+    },
+    stratis::{StratisError, StratisResult},
+};
+
+/// The size of the metadata sub-device of each leg. It holds the DM raid
+/// superblock and the bitmap which records the regions of the legs which
+/// are out of sync.
+pub const MIRROR_META_SIZE: Sectors = Sectors(8192); // 4 MiB
+
+/// The tables of the sub-devices of a leg of the DM raid device.
+#[derive(Debug)]
+pub struct LegTables {
+    /// The table of the sub-device which holds the data of the leg
+    pub data: Vec<TargetLine<LinearDevTargetParams>>,
+    /// The table of the metadata sub-device of the leg
+    pub meta: Vec<TargetLine<LinearDevTargetParams>>,
+}
+
+/// The sub-devices of a leg of the DM raid device.
+#[derive(Debug)]
+struct Leg {
+    /// The linear DM device which maps the data of the leg
+    data: LinearDev,
+    /// The linear DM device which maps the metadata of the leg
+    meta: LinearDev,
+}
+
+/// A DM raid device in raid1 mode which mirrors its data across two legs.
+/// Each leg consists of a linear DM device which maps the segments allocated
+/// to it from the data tier and a linear DM device which maps its metadata
+/// segment.
+///
+/// The kernel records in the metadata of each leg which regions are in sync,
+/// so that only the regions which are out of sync are resynchronized when
+/// the device is activated, and a leg which is stale or has failed is
+/// recovered when it becomes available again.
+///
+/// If a leg is missing, the DM raid device is set up degraded, on the
+/// remaining leg only.
+#[derive(Debug)]
+pub struct MirrorDev {
+    /// The name of the DM raid device
+    name: DmNameBuf,
+    /// The device number of the DM raid device
+    device: Device,
+    /// The size of the DM raid device, equal to the size of each leg
+    size: Sectors,
+    /// The sub-devices of each leg, None if the leg is missing
+    legs: [Option<Leg>; 2],
+}
+
+impl MirrorDev {
+    /// Set up the legs from the given tables and the DM raid device on top
+    /// of them. A leg for which no tables are given is missing. If any of
+    /// the devices already exist, they are reloaded only if their tables
+    /// differ from the tables specified.
+    ///
+    /// If new is true, the metadata sub-devices are wiped, so that the
+    /// kernel initializes the metadata and synchronizes the legs in full.
+    ///
+    /// Precondition: At least one leg is present.
+    /// Precondition: The data tables of all present legs map the same number
+    /// of sectors.
+    pub fn setup(
+        pool_uuid: PoolUuid,
+        leg_tables: [Option<LegTables>; 2],
+        new: bool,
+    ) -> StratisResult<MirrorDev> {
+        let setup_leg = |leg: usize, tables: LegTables| -> StratisResult<Leg> {
+            let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::LegMetaSub(leg));
+            let meta = LinearDev::setup(get_dm(), &dm_name, Some(&dm_uuid), tables.meta)?;
+            if new {
+                // Wipe the first 4 KiB, which holds the DM raid superblock.
+                wipe_sectors(meta.devnode(), Sectors(0), min(Sectors(8), meta.size()))?;
+            }
+
+            let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::LegSub(leg));
+            let data = LinearDev::setup(get_dm(), &dm_name, Some(&dm_uuid), tables.data)?;
+            Ok(Leg { data, meta })
+        };
+
+        let [first, second] = leg_tables;
+        let legs = [
+            first.map(|tables| setup_leg(0, tables)).transpose()?,
+            second.map(|tables| setup_leg(1, tables)).transpose()?,
+        ];
+        let size = leg_size(&legs);
+
+        let (dm_name, dm_uuid) = format_backstore_ids(pool_uuid, CacheRole::MirrorSub);
+        let id = DevId::Name(&dm_name);
+        let table = raid_table(&legs, size);
+        let device = match get_dm().device_info(&id) {
+            Ok(_) => {
+                let (info, current) = get_dm().table_status(
+                    &id,
+                    DmOptions::default().set_flags(DmFlags::DM_STATUS_TABLE),
+                )?;
+                if current != table {
+                    get_dm().table_load(&id, &table, DmOptions::default())?;
+                    get_dm().device_suspend(&id, DmOptions::default())?;
+                }
+                info.device()
+            }
+            Err(_) => {
+                get_dm().device_create(&dm_name, Some(&dm_uuid), DmOptions::default())?;
+                let info = match get_dm()
+                    .table_load(&id, &table, DmOptions::default())
+                    .and_then(|_| get_dm().device_suspend(&id, DmOptions::default()))
+                {
+                    Ok(info) => info,
+                    Err(causal_error) => {
+                        if let Err(rollback_error) =
+                            get_dm().device_remove(&id, DmOptions::default())
+                        {
+                            return Err(StratisError::NoActionRollbackError {
+                                causal_error: Box::new(StratisError::from(causal_error)),
+                                rollback_error: Box::new(StratisError::from(rollback_error)),
+                            });
+                        }
+                        return Err(StratisError::from(causal_error));
+                    }
+                };
+                info.device()
+            }
+        };
+
+        Ok(MirrorDev {
+            name: dm_name,
+            device,
+            size,
+            legs,
+        })
+    }
+
+    /// Reload the data sub-devices of the legs with the given tables and
+    /// grow the DM raid device to the new size of the legs. The DM raid
+    /// device is suspended while the legs are reloaded and resumed
+    /// afterward, even if reloading fails. The metadata sub-devices are
+    /// unchanged.
+    ///
+    /// Precondition: Tables are given for exactly the legs which are present.
+    /// Precondition: The tables map the same number of sectors, which is no
+    /// less than the current size of the device.
+    pub fn set_tables(
+        &mut self,
+        data_tables: [Option<Vec<TargetLine<LinearDevTargetParams>>>; 2],
+    ) -> StratisResult<()> {
+        get_dm().device_suspend(
+            &DevId::Name(&self.name),
+            DmOptions::default().set_flags(DmFlags::DM_SUSPEND),
+        )?;
+        let res = self.reload(data_tables);
+        get_dm().device_suspend(&DevId::Name(&self.name), DmOptions::default())?;
+        res
+    }
+
+    /// Reload the data sub-devices of the legs and load the table of the DM
+    /// raid device with the size of the reloaded legs. The new table of the
+    /// DM raid device becomes active when the device is resumed.
+    fn reload(
+        &mut self,
+        data_tables: [Option<Vec<TargetLine<LinearDevTargetParams>>>; 2],
+    ) -> StratisResult<()> {
+        for (leg, table) in self.legs.iter_mut().zip(data_tables) {
+            match (leg, table) {
+                (Some(leg), Some(table)) => {
+                    leg.data.set_table(get_dm(), table)?;
+                    leg.data.resume(get_dm())?;
+                }
+                (None, None) => {}
+                _ => panic!("tables are given for exactly the legs which are present"),
+            }
+        }
+
+        let size = leg_size(&self.legs);
+        get_dm().table_load(
+            &DevId::Name(&self.name),
+            &raid_table(&self.legs, size),
+            DmOptions::default(),
+        )?;
+        self.size = size;
+        Ok(())
+    }
+
+    /// The table of a linear device which maps the whole of the DM raid
+    /// device.
+    pub fn linear_table(&self) -> Vec<TargetLine<LinearDevTargetParams>> {
+        vec![TargetLine::new(
+            Sectors(0),
+            self.size,
+            LinearDevTargetParams::Linear(LinearTargetParams::new(self.device, Sectors(0))),
+        )]
+    }
+
+    /// Remove the DM raid device and then the sub-devices of its legs.
+    pub fn teardown(&mut self) -> StratisResult<()> {
+        get_dm().device_remove(&DevId::Name(&self.name), DmOptions::default())?;
+        for leg in self.legs.iter_mut().flatten() {
+            leg.data.teardown(get_dm())?;
+            leg.meta.teardown(get_dm())?;
+        }
+        Ok(())
+    }
+}
+
+/// The size of the data sub-devices of the legs which are present.
+///
+/// Precondition: At least one leg is present and the data sub-devices of
+/// all present legs have the same size.
+fn leg_size(legs: &[Option<Leg>; 2]) -> Sectors {
+    let mut sizes = legs.iter().flatten().map(|leg| leg.data.size());
+    let size = sizes.next().expect("at least one leg is present");
+    assert!(sizes.all(|s| s == size));
+    size
+}
+
+/// The raw table of a DM raid device in raid1 mode of the given size across
+/// the given legs. A missing leg is specified by "-" in place of both of its
+/// sub-devices.
+fn raid_table(legs: &[Option<Leg>; 2], size: Sectors) -> Vec<(u64, u64, String, String)> {
+    let devs = legs
+        .iter()
+        .map(|leg| match leg {
+            Some(leg) => format!("{} {}", leg.meta.device(), leg.data.device()),
+            None => "- -".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    vec![(0, *size, "raid".to_string(), format!("raid1 1 0 2 {devs}"))]
+}
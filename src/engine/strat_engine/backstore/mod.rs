@@ -10,19 +10,23 @@ mod cache_tier;
 mod crypt;
 mod data_tier;
 mod devices;
+mod integrity;
+mod migration;
+mod mirror;
 mod range_alloc;
 mod shared;
 mod transaction;
 
 pub use self::{
-    backstore::Backstore,
-    blockdev::{StratBlockDev, UnderlyingDevice},
+    backstore::{reencryption_progress, replace_progress, Backstore},
+    blockdev::{DataDevice, StratBlockDev, UnderlyingDevice},
     crypt::{
-        crypt_metadata_size, set_up_crypt_logging, CryptActivationHandle, CryptHandle,
-        CryptMetadataHandle, CLEVIS_TANG_TRUST_URL,
+        check_encryption_config_support, crypt_metadata_size, set_up_crypt_logging,
+        CryptActivationHandle, CryptHandle, CryptMetadataHandle, CLEVIS_TANG_TRUST_URL,
     },
     devices::{
-        find_stratis_devs_by_uuid, initialize_devices, BlockSizes, ProcessedPathInfos,
-        UnownedDevices,
+        find_stratis_devs_by_uuid, initialize_devices, wipe_blockdevs, BlockSizes,
+        ProcessedPathInfos, UnownedDevices,
     },
+    integrity::IntegrityDev,
 };
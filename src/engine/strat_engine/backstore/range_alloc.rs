@@ -225,6 +225,41 @@ impl PerDevSegments {
         Ok(())
     }
 
+    /// Remove specified range from self. Return an error if the range is not
+    /// wholly contained in a single existing range. If the range lies inside
+    /// an existing range, the remainder of that range is kept.
+    /// Removing a 0 length range has no effect.
+    pub fn remove(&mut self, range: &(Sectors, Sectors)) -> StratisResult<()> {
+        let &(start, len) = range;
+
+        if len == Sectors(0) {
+            return Ok(());
+        }
+
+        let (prev_start, prev_len) = self
+            .used
+            .range(..=start)
+            .next_back()
+            .map(|(&prev_start, &prev_len)| (prev_start, prev_len))
+            .filter(|&(prev_start, prev_len)| start + len <= prev_start + prev_len)
+            .ok_or_else(|| {
+                StratisError::Msg(format!(
+                    "range starting at {start} with length {len} is not wholly allocated"
+                ))
+            })?;
+
+        self.used.remove(&prev_start);
+        if start > prev_start {
+            self.used.insert(prev_start, start - prev_start);
+        }
+        let (end, prev_end) = (start + len, prev_start + prev_len);
+        if prev_end > end {
+            self.used.insert(end, prev_end - end);
+        }
+
+        Ok(())
+    }
+
     /// Take the union of two PerDevSegments. Require that both PerDevSegments
     /// objects have the same limit, for simplicity.
     pub fn union(&self, other: &PerDevSegments) -> StratisResult<PerDevSegments> {
@@ -336,7 +371,6 @@ impl RangeAllocator {
     }
 
     /// The maximum allocation from this manager
-    #[cfg(test)]
     pub fn size(&self) -> BlockdevSize {
         BlockdevSize::new(self.segments.limit())
     }
@@ -408,6 +442,18 @@ impl RangeAllocator {
             .expect("all segments verified to be in available ranges");
     }
 
+    /// Release an allocation previously committed, making the specified
+    /// ranges available again. Return an error if any of the ranges is not
+    /// allocated; in that case no ranges are released.
+    pub fn release(&mut self, ranges: &[(Sectors, Sectors)]) -> StratisResult<()> {
+        let mut segments = self.segments.clone();
+        for range in ranges.iter() {
+            segments.remove(range)?;
+        }
+        self.segments = segments;
+        Ok(())
+    }
+
     /// Increase the available size of the RangeAlloc data structure.
     ///
     /// Precondition: new_size > self.limit
@@ -415,6 +461,31 @@ impl RangeAllocator {
         assert!(new_size > self.segments.limit);
         self.segments.limit = new_size;
     }
+
+    /// The end of the last allocated range, or 0 if nothing is allocated.
+    pub fn allocated_end(&self) -> Sectors {
+        self.segments
+            .iter()
+            .last()
+            .map(|(&start, &len)| start + len)
+            .unwrap_or(Sectors(0))
+    }
+
+    /// Decrease the available size of the RangeAlloc data structure.
+    /// Returns an error if any allocated range extends beyond the new size.
+    ///
+    /// Precondition: new_size < self.limit
+    pub fn decrease_size(&mut self, new_size: Sectors) -> StratisResult<()> {
+        assert!(new_size < self.segments.limit);
+        let end = self.allocated_end();
+        if end > new_size {
+            return Err(StratisError::Msg(format!(
+                "Sectors up to {end} are allocated; cannot reduce the size of the device to {new_size}"
+            )));
+        }
+        self.segments.limit = new_size;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -641,6 +712,52 @@ mod tests {
         allocator.invariant();
     }
 
+    #[test]
+    /// Verify that the size of an allocator can be decreased only if no
+    /// allocated range extends beyond the new size.
+    fn test_allocator_decrease_size() {
+        let mut allocator = RangeAllocator::new(
+            BlockdevSize::new(Sectors(128)),
+            &[(Sectors(0), Sectors(64))],
+        )
+        .unwrap();
+
+        assert_eq!(allocator.allocated_end(), Sectors(64));
+        assert_matches!(allocator.decrease_size(Sectors(32)), Err(_));
+        assert_eq!(allocator.size(), BlockdevSize::new(Sectors(128)));
+
+        allocator.decrease_size(Sectors(64)).unwrap();
+        assert_eq!(allocator.size(), BlockdevSize::new(Sectors(64)));
+        assert_eq!(allocator.available(), Sectors(0));
+    }
+
+    #[test]
+    /// Verify that release() splits allocated ranges as needed and that it
+    /// releases nothing if any of the ranges is not allocated.
+    fn test_allocator_release() {
+        let mut allocator = RangeAllocator::new(
+            BlockdevSize::new(Sectors(128)),
+            &[(Sectors(10), Sectors(100))],
+        )
+        .unwrap();
+
+        assert_matches!(
+            allocator.release(&[(Sectors(20), Sectors(10)), (Sectors(100), Sectors(20))]),
+            Err(_)
+        );
+        assert_eq!(allocator.used(), Sectors(100));
+
+        allocator
+            .release(&[(Sectors(20), Sectors(10)), (Sectors(100), Sectors(10))])
+            .unwrap();
+        assert_eq!(allocator.used(), Sectors(80));
+        assert_eq!(
+            allocator.segments.iter().collect::<Vec<_>>(),
+            vec![(&Sectors(10), &Sectors(10)), (&Sectors(30), &Sectors(70))]
+        );
+        allocator.segments.invariant();
+    }
+
     #[test]
     /// Verify that an insertion at the end with 0 length has no effect,
     /// but with 1 length returns an error.
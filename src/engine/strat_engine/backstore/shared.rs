@@ -135,6 +135,14 @@ impl AllocatedAbove {
         );
     }
 
+    /// Set the device number of every segment allocated from the block
+    /// device with the given UUID.
+    pub fn set_device(&mut self, uuid: DevUuid, device: Device) {
+        for bseg in self.inner.iter_mut().filter(|bseg| bseg.uuid == uuid) {
+            bseg.segment.device = device;
+        }
+    }
+
     /// A set of UUIDs of every device that is allocated from.
     #[cfg(test)]
     pub fn uuids(&self) -> HashSet<DevUuid> {
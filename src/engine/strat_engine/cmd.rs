@@ -26,12 +26,12 @@ use libc::c_uint;
 use libcryptsetup_rs::SafeMemHandle;
 use serde_json::Value;
 
-use devicemapper::{MetaBlocks, Sectors};
+use devicemapper::{DataBlocks, MetaBlocks, Sectors, ThinDevId};
 
 use crate::{
     engine::{
         engine::MAX_STRATIS_PASS_SIZE,
-        types::{FilesystemUuid, SizedKeyMemory, StratisUuid},
+        types::{FilesystemType, FilesystemUuid, MkfsOptions, SizedKeyMemory, StratisUuid},
     },
     stratis::{StratisError, StratisResult},
 };
@@ -63,6 +63,9 @@ fn find_executable(name: &str) -> Option<PathBuf> {
 const MKFS_XFS: &str = "mkfs.xfs";
 const THIN_CHECK: &str = "thin_check";
 const THIN_REPAIR: &str = "thin_repair";
+const THIN_LS: &str = "thin_ls";
+const THIN_DUMP: &str = "thin_dump";
+const THIN_DELTA: &str = "thin_delta";
 #[cfg(test)]
 const UDEVADM: &str = "udevadm";
 const THIN_METADATA_SIZE: &str = "thin_metadata_size";
@@ -87,6 +90,9 @@ const TPM2_CREATEPRIMARY: &str = "tpm2_createprimary";
 const TPM2_UNSEAL: &str = "tpm2_unseal";
 const TPM2_LOAD: &str = "tpm2_load";
 const MKTEMP: &str = "mktemp";
+const MKFS_EXT4: &str = "mkfs.ext4";
+const RESIZE2FS: &str = "resize2fs";
+const TUNE2FS: &str = "tune2fs";
 
 // This list of executables required for Clevis to function properly is based
 // off of the Clevis dracut module and the Stratis dracut module for supporting
@@ -115,11 +121,19 @@ const CLEVIS_EXEC_NAMES: &[&str] = &[
     MKTEMP,
 ];
 
+// The executables required only to manage ext4 filesystems. These are not
+// checked for on engine initialization, so that a system which uses only XFS
+// filesystems does not need to have them installed.
+const EXT4_EXEC_NAMES: &[&str] = &[MKFS_EXT4, RESIZE2FS, TUNE2FS];
+
 lazy_static! {
     static ref EXECUTABLES: HashMap<String, Option<PathBuf>> = [
         (MKFS_XFS.to_string(), find_executable(MKFS_XFS)),
         (THIN_CHECK.to_string(), find_executable(THIN_CHECK)),
         (THIN_REPAIR.to_string(), find_executable(THIN_REPAIR)),
+        (THIN_LS.to_string(), find_executable(THIN_LS)),
+        (THIN_DUMP.to_string(), find_executable(THIN_DUMP)),
+        (THIN_DELTA.to_string(), find_executable(THIN_DELTA)),
         #[cfg(test)]
         (UDEVADM.to_string(), find_executable(UDEVADM)),
         (XFS_DB.to_string(), find_executable(XFS_DB)),
@@ -159,6 +173,28 @@ pub fn verify_executables() -> StratisResult<()> {
     }
 }
 
+/// Verify that all executables required to manage filesystems of the given
+/// type are available at some path. The executables for XFS are verified by
+/// verify_executables() on engine initialization.
+pub fn verify_fs_executables(fs_type: FilesystemType) -> StratisResult<()> {
+    match fs_type {
+        FilesystemType::Xfs => Ok(()),
+        FilesystemType::Ext4 => {
+            let not_found = EXT4_EXEC_NAMES
+                .iter()
+                .filter(|name| find_executable(name).is_none())
+                .collect::<Vec<_>>();
+            if not_found.is_empty() {
+                Ok(())
+            } else {
+                Err(StratisError::Msg(format!(
+                    "ext4 filesystems are not supported because some of the required executables were not found on this system. Required executables that were not found are: {not_found:?}"
+                )))
+            }
+        }
+    }
+}
+
 /// Invoke the specified command. Return an error if invoking the command
 /// fails or if the command itself fails.
 fn execute_cmd(cmd: &mut Command) -> StratisResult<()> {
@@ -197,6 +233,14 @@ fn get_executable(name: &str) -> &Path {
         .expect("verify_executables() was previously called and returned no error")
 }
 
+/// Get an absolute path for an executable required to manage ext4 filesystems
+/// or return an error if it can not be found.
+fn get_ext4_executable(name: &str) -> StratisResult<PathBuf> {
+    verify_fs_executables(FilesystemType::Ext4)?;
+    find_executable(name)
+        .ok_or_else(|| StratisError::Msg(format!("Unable to find executable \"{name}\"")))
+}
+
 /// Get an absolute path for a Clevis-related executable or return an error if Clevis
 /// support is disabled.
 fn get_clevis_executable(name: &str) -> StratisResult<PathBuf> {
@@ -226,47 +270,120 @@ fn get_clevis_executable(name: &str) -> StratisResult<PathBuf> {
     }
 }
 
-/// Create a filesystem on devnode. If uuid specified, set the UUID of the
-/// filesystem on creation. If 'noalign', set the noalign option.
+/// Create a filesystem of type fs_type on devnode. If uuid specified, set the
+/// UUID of the filesystem on creation. If 'noalign', set the noalign option;
+/// this option applies only to XFS. Any options set in mkfs_options, which
+/// must already have been validated for fs_type, are passed to mkfs.
 /// NOTE: It used to be desirable to set the noalign option when creating the
 /// MDV, because it was very small; since then the size of the MDV has been
 /// increased, and it is no longer necessary to pass the noalign option when
 /// creating the MDV.
-pub fn create_fs(devnode: &Path, uuid: Option<StratisUuid>, noalign: bool) -> StratisResult<()> {
-    let mut command = Command::new(get_executable(MKFS_XFS).as_os_str());
-    command.arg("-f");
-    command.arg("-q");
-    command.arg(devnode);
-
-    if let Some(uuid) = uuid {
-        command.arg("-m");
-        command.arg(format!("uuid={uuid}"));
-    }
-    if noalign {
-        command.arg("-d");
-        command.arg("noalign");
-    }
+pub fn create_fs(
+    devnode: &Path,
+    fs_type: FilesystemType,
+    uuid: Option<StratisUuid>,
+    noalign: bool,
+    mkfs_options: &MkfsOptions,
+) -> StratisResult<()> {
+    let mut command = match fs_type {
+        FilesystemType::Xfs => {
+            let mut command = Command::new(get_executable(MKFS_XFS).as_os_str());
+            command.arg("-f");
+            command.arg("-q");
+            command.arg(devnode);
+
+            let meta_options = uuid
+                .map(|uuid| format!("uuid={uuid}"))
+                .into_iter()
+                .chain(
+                    mkfs_options
+                        .reflink
+                        .map(|reflink| format!("reflink={}", u8::from(reflink))),
+                )
+                .chain(
+                    mkfs_options
+                        .bigtime
+                        .map(|bigtime| format!("bigtime={}", u8::from(bigtime))),
+                )
+                .collect::<Vec<_>>();
+            if !meta_options.is_empty() {
+                command.arg("-m");
+                command.arg(meta_options.join(","));
+            }
+            if noalign {
+                command.arg("-d");
+                command.arg("noalign");
+            }
+            if let Some(inode_size) = mkfs_options.inode_size {
+                command.arg("-i");
+                command.arg(format!("size={inode_size}"));
+            }
+            if let Some(ref label) = mkfs_options.label {
+                command.arg("-L");
+                command.arg(label);
+            }
+            command
+        }
+        FilesystemType::Ext4 => {
+            let mut command = Command::new(get_ext4_executable(MKFS_EXT4)?.as_os_str());
+            command.arg("-F");
+            command.arg("-q");
+
+            if let Some(uuid) = uuid {
+                command.arg("-U");
+                command.arg(uuid.to_string());
+            }
+            if let Some(inode_size) = mkfs_options.inode_size {
+                command.arg("-I");
+                command.arg(inode_size.to_string());
+            }
+            if let Some(ref label) = mkfs_options.label {
+                command.arg("-L");
+                command.arg(label);
+            }
+            command.arg(devnode);
+            command
+        }
+    };
     execute_cmd(&mut command)
 }
 
-/// Use the xfs_growfs command to expand a filesystem mounted at the given
-/// mount point.
-pub fn xfs_growfs(mount_point: &Path) -> StratisResult<()> {
-    execute_cmd(
-        Command::new(get_executable(XFS_GROWFS).as_os_str())
-            .arg(mount_point)
-            .arg("-d"),
-    )
+/// Expand the filesystem of type fs_type on devnode, which is mounted at the
+/// given mount point, to the size of the device.
+pub fn grow_fs(devnode: &Path, mount_point: &Path, fs_type: FilesystemType) -> StratisResult<()> {
+    match fs_type {
+        FilesystemType::Xfs => execute_cmd(
+            Command::new(get_executable(XFS_GROWFS).as_os_str())
+                .arg(mount_point)
+                .arg("-d"),
+        ),
+        FilesystemType::Ext4 => {
+            execute_cmd(Command::new(get_ext4_executable(RESIZE2FS)?.as_os_str()).arg(devnode))
+        }
+    }
 }
 
-/// Set a new UUID for filesystem on the devnode.
-pub fn set_uuid(devnode: &Path, uuid: FilesystemUuid) -> StratisResult<()> {
-    execute_cmd(
-        Command::new(get_executable(XFS_DB).as_os_str())
-            .arg("-x")
-            .arg(format!("-c uuid {uuid}"))
-            .arg(devnode),
-    )
+/// Set a new UUID for the filesystem of type fs_type on the devnode.
+pub fn set_uuid(
+    devnode: &Path,
+    fs_type: FilesystemType,
+    uuid: FilesystemUuid,
+) -> StratisResult<()> {
+    match fs_type {
+        FilesystemType::Xfs => execute_cmd(
+            Command::new(get_executable(XFS_DB).as_os_str())
+                .arg("-x")
+                .arg(format!("-c uuid {uuid}"))
+                .arg(devnode),
+        ),
+        FilesystemType::Ext4 => execute_cmd(
+            Command::new(get_ext4_executable(TUNE2FS)?.as_os_str())
+                .arg("-f")
+                .arg("-U")
+                .arg(uuid.to_string())
+                .arg(devnode),
+        ),
+    }
 }
 
 /// Call thin_check on a thinpool
@@ -289,6 +406,154 @@ pub fn thin_repair(meta_dev: &Path, new_meta_dev: &Path) -> StratisResult<()> {
     )
 }
 
+/// Call thin_ls on the metadata snapshot of the thin pool whose metadata
+/// device is meta_dev. Return, for each thin device, the number of data
+/// blocks it maps, and how many of these are exclusive to it and how many
+/// shared with other thin devices.
+/// Precondition: A metadata snapshot has been reserved for the thin pool.
+pub fn thin_ls(
+    meta_dev: &Path,
+) -> StratisResult<HashMap<ThinDevId, (DataBlocks, DataBlocks, DataBlocks)>> {
+    let mut cmd = Command::new(get_executable(THIN_LS).as_os_str());
+    cmd.arg("--metadata-snap")
+        .arg("--no-headers")
+        .arg("--format")
+        .arg("DEV,MAPPED_BLOCKS,EXCLUSIVE_BLOCKS,SHARED_BLOCKS")
+        .arg(meta_dev);
+    parse_thin_ls(&execute_thin_tool(&mut cmd)?)
+}
+
+/// Invoke a thin provisioning tool and return its standard output. Return
+/// an error if invoking the command fails or if the command itself fails.
+fn execute_thin_tool(cmd: &mut Command) -> StratisResult<String> {
+    let output = cmd.output().map_err(|err| {
+        StratisError::Msg(format!("Failed to execute command {cmd:?}, err: {err:?}"))
+    })?;
+    if !output.status.success() {
+        return Err(StratisError::Msg(format!(
+            "Command {cmd:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parse the output of thin_ls, one line per thin device consisting of the
+/// device ID and the numbers of mapped, exclusive, and shared blocks.
+fn parse_thin_ls(
+    output: &str,
+) -> StratisResult<HashMap<ThinDevId, (DataBlocks, DataBlocks, DataBlocks)>> {
+    let parse_err =
+        |line: &str| StratisError::Msg(format!("Unable to parse thin_ls output line \"{line}\""));
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let values = line
+                .split_whitespace()
+                .map(|v| v.parse::<u64>().map_err(|_| parse_err(line)))
+                .collect::<StratisResult<Vec<_>>>()?;
+            match values.as_slice() {
+                [id, mapped, exclusive, shared] => Ok((
+                    ThinDevId::new_u64(*id).map_err(|_| parse_err(line))?,
+                    (
+                        DataBlocks(*mapped),
+                        DataBlocks(*exclusive),
+                        DataBlocks(*shared),
+                    ),
+                )),
+                _ => Err(parse_err(line)),
+            }
+        })
+        .collect()
+}
+
+/// Call thin_dump on the metadata snapshot of the thin pool whose metadata
+/// device is meta_dev and return the ranges of the thin device with the
+/// given ID that are mapped, as pairs of the first block and the number of
+/// blocks of each range.
+/// Precondition: A metadata snapshot has been reserved for the thin pool.
+pub fn thin_dump_mappings(
+    meta_dev: &Path,
+    id: ThinDevId,
+) -> StratisResult<Vec<(DataBlocks, DataBlocks)>> {
+    let output = execute_thin_tool(
+        Command::new(get_executable(THIN_DUMP).as_os_str())
+            .arg("--metadata-snap")
+            .arg("--dev-id")
+            .arg(id.to_string())
+            .arg(meta_dev),
+    )?;
+    output
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.starts_with("<range_mapping") {
+                Some(
+                    xml_attr(line, "origin_begin")
+                        .and_then(|begin| Ok((begin, xml_attr(line, "length")?))),
+                )
+            } else if line.starts_with("<single_mapping") {
+                Some(xml_attr(line, "origin_block").map(|block| (block, DataBlocks(1))))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Call thin_delta on the metadata snapshot of the thin pool whose metadata
+/// device is meta_dev to compare the thin device with ID right to the thin
+/// device with ID left. Return the ranges whose mappings differ or are
+/// mapped only on the right and the ranges that are mapped only on the left,
+/// as pairs of the first block and the number of blocks of each range.
+/// Precondition: A metadata snapshot has been reserved for the thin pool.
+#[allow(clippy::type_complexity)]
+pub fn thin_delta(
+    meta_dev: &Path,
+    left: ThinDevId,
+    right: ThinDevId,
+) -> StratisResult<(Vec<(DataBlocks, DataBlocks)>, Vec<(DataBlocks, DataBlocks)>)> {
+    let output = execute_thin_tool(
+        Command::new(get_executable(THIN_DELTA).as_os_str())
+            .arg("--metadata-snap")
+            .arg("--snap1")
+            .arg(left.to_string())
+            .arg("--snap2")
+            .arg(right.to_string())
+            .arg(meta_dev),
+    )?;
+    let mut changed = Vec::new();
+    let mut removed = Vec::new();
+    for line in output.lines().map(|line| line.trim()) {
+        let ranges = if line.starts_with("<different") || line.starts_with("<right_only") {
+            &mut changed
+        } else if line.starts_with("<left_only") {
+            &mut removed
+        } else {
+            continue;
+        };
+        ranges.push((xml_attr(line, "begin")?, xml_attr(line, "length")?));
+    }
+    Ok((changed, removed))
+}
+
+/// Get the numeric value of the attribute with the given name from a line
+/// of the XML output of thin_dump or thin_delta.
+fn xml_attr(line: &str, name: &str) -> StratisResult<DataBlocks> {
+    let prefix = format!("{name}=\"");
+    line.split_whitespace()
+        .find_map(|attr| attr.strip_prefix(prefix.as_str()))
+        .and_then(|value| value.split('"').next())
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(DataBlocks)
+        .ok_or_else(|| {
+            StratisError::Msg(format!(
+                "Unable to find a value for attribute {name} in \"{line}\""
+            ))
+        })
+}
+
 /// Call udevadm settle
 #[cfg(test)]
 pub fn udev_settle() -> StratisResult<()> {
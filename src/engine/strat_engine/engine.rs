@@ -4,11 +4,13 @@
 
 use std::{
     collections::{HashMap, HashSet},
+    os::unix::io::RawFd,
     path::Path,
     sync::Arc,
 };
 
 use async_trait::async_trait;
+use chrono::Utc;
 use futures::{executor::block_on, future::join_all};
 use serde_json::Value;
 use tokio::{
@@ -16,28 +18,36 @@ use tokio::{
     task::{spawn_blocking, JoinHandle},
 };
 
-use devicemapper::DmNameBuf;
+use devicemapper::{Bytes, DmNameBuf};
 
 use crate::{
     engine::{
-        engine::HandleEvents,
-        shared::{create_pool_idempotent_or_err, validate_name, validate_paths},
+        engine::{Filesystem, HandleEvents},
+        shared::{
+            create_pool_idempotent_or_err, read_unlock_passphrase, space_usage_report,
+            validate_encryption_config, validate_name, validate_paths, validate_redundancy,
+        },
         strat_engine::{
-            backstore::ProcessedPathInfos,
+            backstore::{
+                check_encryption_config_support, reencryption_progress, replace_progress,
+                ProcessedPathInfos,
+            },
             cmd::verify_executables,
             dm::get_dm,
             keys::StratKeyActions,
             liminal::{find_all, LiminalDevices},
             ns::MemoryFilesystem,
             pool::StratPool,
+            thinpool::fs_trim,
         },
         structures::{
             AllLockReadGuard, AllLockWriteGuard, AllOrSomeLock, ExclusiveGuard, Lockable,
             SharedGuard, SomeLockReadGuard, SomeLockWriteGuard, Table,
         },
         types::{
-            CreateAction, DeleteAction, DevUuid, EncryptionInfo, FilesystemUuid, LockedPoolsInfo,
-            PoolDiff, PoolIdentifier, RenameAction, ReportType, SetUnlockAction, StartAction,
+            CreateAction, DeleteAction, DevUuid, FilesystemUuid, LockedPoolsInfo,
+            PoolCreateOptions, PoolDiff, PoolIdentifier, ReencryptionProgress, RenameAction,
+            ReplaceProgress, ReportType, ScheduledSnapshots, SetUnlockAction, StartAction,
             StopAction, StoppedPoolsInfo, StratFilesystemDiff, UdevEngineEvent, UnlockMethod,
         },
         Engine, Name, Pool, PoolUuid, Report,
@@ -293,6 +303,7 @@ impl Report for StratEngine {
     fn get_report(&self, report_type: ReportType) -> Value {
         match report_type {
             ReportType::StoppedPools => (&*self.liminal_devices.blocking_read()).into(),
+            ReportType::SpaceUsage => space_usage_report(block_on(self.pools.read_all()).iter()),
         }
     }
 }
@@ -368,13 +379,31 @@ impl Engine for StratEngine {
         &self,
         name: &str,
         blockdev_paths: &[&Path],
-        encryption_info: Option<&EncryptionInfo>,
+        options: PoolCreateOptions<'_>,
     ) -> StratisResult<CreateAction<PoolUuid>> {
+        let PoolCreateOptions {
+            redundancy,
+            integrity,
+            encryption_info,
+            encryption_config,
+            thin_pool_config,
+        } = options;
+
         validate_name(name)?;
         let name = Name::new(name.to_owned());
 
         validate_paths(blockdev_paths)?;
 
+        if let Some(config) = encryption_config {
+            if encryption_info.is_none() {
+                return Err(StratisError::Msg(
+                    "Encryption settings were specified for a pool that is not encrypted"
+                        .to_string(),
+                ));
+            }
+            validate_encryption_config(config)?;
+        }
+
         let cloned_paths = blockdev_paths
             .iter()
             .map(|p| p.to_path_buf())
@@ -407,6 +436,8 @@ impl Engine for StratEngine {
                             .map(|info| info.devnode.as_path()),
                     )
                     .collect::<Vec<_>>(),
+                redundancy,
+                integrity,
             )
         } else {
             stratis_devices.error_on_not_empty()?;
@@ -417,19 +448,42 @@ impl Engine for StratEngine {
                 ));
             }
 
+            validate_redundancy(redundancy, unowned_devices.len())?;
+
             let block_size_summary = unowned_devices.blocksizes();
             if block_size_summary.len() > 1 {
                 let err_str = "The devices specified for initializing the pool do do not all have the same physical sector size or do not all have the same logical sector size.".into();
                 return Err(StratisError::Msg(err_str));
             }
 
+            if let Some(config) = encryption_config {
+                let logical_sector_size = block_size_summary
+                    .keys()
+                    .next()
+                    .expect("unowned_devices is not empty")
+                    .logical_sector_size;
+                check_encryption_config_support(config, logical_sector_size)?;
+            }
+
             let cloned_name = name.clone();
             let cloned_enc_info = encryption_info.cloned();
+            let cloned_enc_config = encryption_config.cloned();
+            let cloned_thin_pool_config = thin_pool_config.copied();
 
             let pool_uuid = {
                 let mut pools = self.pools.write_all().await;
                 let (pool_uuid, pool) = spawn_blocking!({
-                    StratPool::initialize(&cloned_name, unowned_devices, cloned_enc_info.as_ref())
+                    StratPool::initialize(
+                        &cloned_name,
+                        unowned_devices,
+                        PoolCreateOptions {
+                            redundancy,
+                            integrity,
+                            encryption_info: cloned_enc_info.as_ref(),
+                            encryption_config: cloned_enc_config.as_ref(),
+                            thin_pool_config: cloned_thin_pool_config.as_ref(),
+                        },
+                    )
                 })??;
                 pools.insert(Name::new(name.to_string()), pool_uuid, pool);
                 pool_uuid
@@ -447,6 +501,11 @@ impl Engine for StratEngine {
         } else {
             return Ok(DeleteAction::Identity);
         }
+        if reencryption_progress(uuid).is_some() {
+            return Err(StratisError::Msg(
+                "The volume key of the pool is being rotated; the pool can be destroyed once the rotation has completed".into(),
+            ));
+        }
 
         let mut guard = self.pools.write_all().await;
         let (pool_name, mut pool) = guard
@@ -500,11 +559,18 @@ impl Engine for StratEngine {
         &self,
         pool_uuid: PoolUuid,
         unlock_method: UnlockMethod,
+        passphrase_fd: Option<RawFd>,
     ) -> StratisResult<SetUnlockAction<DevUuid>> {
+        let passphrase = read_unlock_passphrase(Some(unlock_method), passphrase_fd)?;
+
         let pools_read_all = self.pools.read_all().await;
         let mut ld_guard = self.liminal_devices.write().await;
-        let unlocked =
-            spawn_blocking!(ld_guard.unlock_pool(&pools_read_all, pool_uuid, unlock_method,))??;
+        let unlocked = spawn_blocking!(ld_guard.unlock_pool(
+            &pools_read_all,
+            pool_uuid,
+            unlock_method,
+            passphrase.as_ref(),
+        ))??;
         Ok(SetUnlockAction::new(
             unlocked
                 .into_iter()
@@ -598,6 +664,119 @@ impl Engine for StratEngine {
         }
     }
 
+    async fn trim_filesystems(&self) -> HashMap<FilesystemUuid, StratisResult<Bytes>> {
+        // Find the mount points while holding the lock, but trim the
+        // filesystems after releasing it, as trimming may take a long time.
+        let mut mount_points = Vec::new();
+        for (_, _, pool) in self.pools.read_all().await.iter() {
+            for (_, fs_uuid, fs) in pool.filesystems() {
+                match fs.mount_points() {
+                    Ok(mps) => {
+                        if let Some(mp) = mps.into_iter().next() {
+                            mount_points.push((fs_uuid, mp));
+                        }
+                    }
+                    Err(e) => warn!(
+                        "Failed to find the mount points of filesystem with UUID {}: {}",
+                        fs_uuid, e
+                    ),
+                }
+            }
+        }
+
+        match spawn_blocking!(mount_points
+            .into_iter()
+            .map(|(fs_uuid, mp)| (fs_uuid, fs_trim(&mp)))
+            .collect::<HashMap<_, _>>())
+        {
+            Ok(trimmed) => trimmed,
+            Err(e) => {
+                warn!("Failed to join the filesystem trim thread: {}", e);
+                HashMap::default()
+            }
+        }
+    }
+
+    async fn update_space_usage(&self) -> HashMap<FilesystemUuid, StratFilesystemDiff> {
+        // Calculate the space usage while holding only read locks, as
+        // reading the thin pool metadata may take a long time, and lock each
+        // pool for writing only to record the result.
+        let mut joins = Vec::new();
+        let guards: Vec<SomeLockReadGuard<PoolUuid, StratPool>> =
+            self.pools.read_all().await.into();
+        for guard in guards {
+            joins.push(spawn_blocking(move || {
+                let (_, uuid, pool) = guard.as_tuple();
+                (uuid, pool.space_usage())
+            }));
+        }
+
+        let mut updated = HashMap::default();
+        for res in join_all(joins).await {
+            match res {
+                Ok((uuid, Ok(usage))) => {
+                    if let Some(mut guard) = self.pools.write(PoolIdentifier::Uuid(uuid)).await {
+                        let (_, uuid, pool) = guard.as_mut_tuple();
+                        match pool.set_space_usage(uuid, &usage) {
+                            Ok(diffs) => updated.extend(diffs),
+                            Err(StratisError::ActionDisabled(_)) => (),
+                            Err(e) => warn!("Failed to update space usage: {}", e),
+                        }
+                    }
+                }
+                Ok((_, Err(e))) => warn!("Failed to calculate space usage: {}", e),
+                Err(e) => warn!(
+                    "Failed to get status for thread calculating space usage: {}",
+                    e
+                ),
+            }
+        }
+        updated
+    }
+
+    async fn run_snapshot_policies(&self) -> HashMap<PoolUuid, StratisResult<ScheduledSnapshots>> {
+        let now = Utc::now();
+        // Only lock the pools that have filesystems with snapshot policies.
+        let pool_uuids = self
+            .pools
+            .read_all()
+            .await
+            .iter()
+            .filter(|(_, _, pool)| {
+                pool.filesystems()
+                    .iter()
+                    .any(|(_, _, fs)| !fs.snapshot_policies().is_empty())
+            })
+            .map(|(_, uuid, _)| *uuid)
+            .collect::<Vec<_>>();
+
+        let mut joins = Vec::new();
+        for uuid in pool_uuids {
+            if let Some(mut guard) = self.pools.write(PoolIdentifier::Uuid(uuid)).await {
+                joins.push(spawn_blocking(move || {
+                    let (name, uuid, pool) = guard.as_mut_tuple();
+                    (uuid, pool.run_snapshot_policies(&name, uuid, now))
+                }));
+            }
+        }
+
+        join_all(joins)
+            .await
+            .into_iter()
+            .filter_map(|res| match res {
+                Ok((_, Err(StratisError::ActionDisabled(_)))) => None,
+                Ok(tup) => Some(tup),
+                Err(e) => {
+                    warn!(
+                        "Failed to get status for thread applying snapshot policies: {}",
+                        e
+                    );
+                    None
+                }
+            })
+            .collect::<HashMap<_, _>>()
+    }
+
     async fn get_key_handler(&self) -> SharedGuard<OwnedRwLockReadGuard<Self::KeyActions>> {
         self.key_handler.read().await
     }
@@ -610,7 +789,11 @@ impl Engine for StratEngine {
         &self,
         id: PoolIdentifier<PoolUuid>,
         unlock_method: Option<UnlockMethod>,
+        passphrase_fd: Option<RawFd>,
+        allow_missing: bool,
     ) -> StratisResult<StartAction<PoolUuid>> {
+        let passphrase = read_unlock_passphrase(unlock_method, passphrase_fd)?;
+
         if let Some(lock) = self.pools.read(id.clone()).await {
             let (_, pool_uuid, pool) = lock.as_tuple();
             if pool.is_encrypted() && unlock_method.is_none() {
@@ -626,17 +809,25 @@ impl Engine for StratEngine {
             }
         } else {
             let mut pools = self.pools.write_all().await;
-            let (name, pool_uuid, pool) =
-                self.liminal_devices
-                    .write()
-                    .await
-                    .start_pool(&pools, id, unlock_method)?;
+            let (name, pool_uuid, pool) = self.liminal_devices.write().await.start_pool(
+                &pools,
+                id,
+                unlock_method,
+                passphrase.as_ref(),
+                allow_missing,
+            )?;
             pools.insert(name, pool_uuid, pool);
             Ok(StartAction::Started(pool_uuid))
         }
     }
 
     async fn stop_pool(&self, pool_uuid: PoolUuid) -> StratisResult<StopAction<PoolUuid>> {
+        if reencryption_progress(pool_uuid).is_some() {
+            return Err(StratisError::Msg(
+                "The volume key of the pool is being rotated; the pool can be stopped once the rotation has completed".into(),
+            ));
+        }
+
         let mut pools = self.pools.write_all().await;
         if let Some((name, mut pool)) = pools.remove_by_uuid(pool_uuid) {
             if let Err(e) = self
@@ -683,6 +874,14 @@ impl Engine for StratEngine {
         Ok(())
     }
 
+    fn replace_progress(&self, pool_uuid: PoolUuid) -> Option<ReplaceProgress> {
+        replace_progress(pool_uuid)
+    }
+
+    fn reencryption_progress(&self, pool_uuid: PoolUuid) -> Option<ReencryptionProgress> {
+        reencryption_progress(pool_uuid)
+    }
+
     fn is_sim(&self) -> bool {
         false
     }
@@ -690,19 +889,23 @@ impl Engine for StratEngine {
 
 #[cfg(test)]
 mod test {
-    use std::{env, error::Error, path::Path};
+    use std::{env, error::Error, fs::OpenOptions, path::Path};
 
     use devicemapper::Sectors;
 
     use crate::engine::{
-        engine::Pool,
+        engine::{BlockDev, Pool},
         strat_engine::{
             backstore::crypt_metadata_size,
             cmd,
+            metadata::disown_device,
             ns::unshare_mount_namespace,
             tests::{crypt, loopbacked, real, FailDevice},
         },
-        types::{ActionAvailability, EngineAction, KeyDescription},
+        types::{
+            ActionAvailability, BlockDevTier, CacheConfig, EncryptionInfo, EngineAction,
+            FilesystemSpec, KeyDescription,
+        },
     };
 
     use super::*;
@@ -713,7 +916,7 @@ mod test {
         let engine = StratEngine::initialize().unwrap();
 
         let name1 = "name1";
-        let uuid1 = test_async!(engine.create_pool(name1, paths, None))
+        let uuid1 = test_async!(engine.create_pool(name1, paths, PoolCreateOptions::default()))
             .unwrap()
             .changed()
             .unwrap();
@@ -726,12 +929,12 @@ mod test {
         let (fs_uuid1, fs_uuid2) = {
             let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid1))).unwrap();
             let fs_uuid1 = pool
-                .create_filesystems(name1, uuid1, &[(fs_name1, None)])
+                .create_filesystems(name1, uuid1, &[FilesystemSpec::new(fs_name1)])
                 .unwrap()
                 .changed()
                 .unwrap();
             let fs_uuid2 = pool
-                .create_filesystems(name1, uuid1, &[(fs_name2, None)])
+                .create_filesystems(name1, uuid1, &[FilesystemSpec::new(fs_name2)])
                 .unwrap()
                 .changed()
                 .unwrap();
@@ -820,13 +1023,13 @@ mod test {
         let engine = StratEngine::initialize().unwrap();
 
         let name1 = "name1";
-        let uuid1 = test_async!(engine.create_pool(name1, paths1, None))
+        let uuid1 = test_async!(engine.create_pool(name1, paths1, PoolCreateOptions::default()))
             .unwrap()
             .changed()
             .unwrap();
 
         let name2 = "name2";
-        let uuid2 = test_async!(engine.create_pool(name2, paths2, None))
+        let uuid2 = test_async!(engine.create_pool(name2, paths2, PoolCreateOptions::default()))
             .unwrap()
             .changed()
             .unwrap();
@@ -904,18 +1107,25 @@ mod test {
 
         unshare_mount_namespace()?;
         let engine = StratEngine::initialize()?;
-        let uuid = test_async!(engine.create_pool(name, data_paths, Some(encryption_info)))?
-            .changed()
-            .ok_or_else(|| {
-                Box::new(StratisError::Msg(
-                    "Pool should be newly created".to_string(),
-                ))
-            })?;
+        let uuid = test_async!(engine.create_pool(
+            name,
+            data_paths,
+            PoolCreateOptions {
+                encryption_info: Some(encryption_info),
+                ..Default::default()
+            }
+        ))?
+        .changed()
+        .ok_or_else(|| {
+            Box::new(StratisError::Msg(
+                "Pool should be newly created".to_string(),
+            ))
+        })?;
         let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid)))
             .ok_or_else(|| Box::new(StratisError::Msg("Pool must be present".to_string())))?;
 
         let init_res = if let Some(cds) = cache_paths {
-            pool.init_cache(uuid, name, cds, true)
+            pool.init_cache(uuid, name, cds, &CacheConfig::default(), true)
                 .map(|_| ())
                 .map_err(|e| Box::new(e) as Box<dyn Error>)
         } else {
@@ -928,7 +1138,12 @@ mod test {
         test_async!(engine.stop_pool(uuid))?;
         res?;
 
-        test_async!(engine.start_pool(PoolIdentifier::Uuid(uuid), Some(unlock_method)))?;
+        test_async!(engine.start_pool(
+            PoolIdentifier::Uuid(uuid),
+            Some(unlock_method),
+            None,
+            false
+        ))?;
         test_async!(engine.destroy_pool(uuid))?;
         engine.teardown()?;
 
@@ -1338,7 +1553,7 @@ mod test {
     fn test_start_stop(paths: &[&Path]) {
         let engine = StratEngine::initialize().unwrap();
         let name = "pool_name";
-        let uuid = test_async!(engine.create_pool(name, paths, None))
+        let uuid = test_async!(engine.create_pool(name, paths, PoolCreateOptions::default()))
             .unwrap()
             .changed()
             .unwrap();
@@ -1353,7 +1568,7 @@ mod test {
         assert_eq!(test_async!(engine.pools()).len(), 0);
 
         assert!(
-            test_async!(engine.start_pool(PoolIdentifier::Uuid(uuid), None))
+            test_async!(engine.start_pool(PoolIdentifier::Uuid(uuid), None, None, false))
                 .unwrap()
                 .is_changed()
         );
@@ -1373,4 +1588,139 @@ mod test {
     fn real_test_start_stop() {
         real::test_with_spec(&real::DeviceLimits::AtLeast(2, None, None), test_start_stop);
     }
+
+    /// Test that a pool with a missing data device on which no data is
+    /// allocated can be started only if missing devices are explicitly
+    /// allowed, and that the pool is then prevented from making changes
+    /// while retaining the missing device in its metadata.
+    fn test_start_missing(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let (first, rest) = paths.split_at(1);
+
+        let engine = StratEngine::initialize().unwrap();
+        let name = "pool_name";
+        let uuid = test_async!(engine.create_pool(name, first, PoolCreateOptions::default()))
+            .unwrap()
+            .changed()
+            .unwrap();
+        let missing_uuid = {
+            let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+            let missing_uuid = pool
+                .add_blockdevs(uuid, name, &rest[..1], BlockDevTier::Data)
+                .unwrap()
+                .changed()
+                .unwrap()[0];
+            assert!(!pool.get_blockdev(missing_uuid).unwrap().1.in_use());
+            missing_uuid
+        };
+        assert!(test_async!(engine.stop_pool(uuid)).unwrap().is_changed());
+        engine.teardown().unwrap();
+
+        disown_device(&mut OpenOptions::new().write(true).open(rest[0]).unwrap()).unwrap();
+
+        let engine = StratEngine::initialize().unwrap();
+        assert!(
+            test_async!(engine.start_pool(PoolIdentifier::Uuid(uuid), None, None, false)).is_err()
+        );
+        assert_eq!(test_async!(engine.stopped_pools()).stopped.len(), 1);
+
+        assert!(
+            test_async!(engine.start_pool(PoolIdentifier::Uuid(uuid), None, None, true))
+                .unwrap()
+                .is_changed()
+        );
+        let pool = test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        assert_eq!(pool.avail_actions(), ActionAvailability::NoPoolChanges);
+        assert_eq!(pool.blockdevs().len(), 1);
+        assert!(pool
+            .record(name)
+            .backstore
+            .data_tier
+            .blockdev
+            .devs
+            .iter()
+            .any(|bd_save| bd_save.uuid == missing_uuid));
+    }
+
+    #[test]
+    fn loop_test_start_missing() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Exactly(2, None),
+            test_start_missing,
+        );
+    }
+
+    #[test]
+    fn real_test_start_missing() {
+        real::test_with_spec(
+            &real::DeviceLimits::Exactly(2, None, None),
+            test_start_missing,
+        );
+    }
+
+    /// Test that a pool created with dm-integrity protection protects every
+    /// data device, including those added later, and that the protection is
+    /// restored when the pool is started again.
+    fn test_integrity(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let (first, rest) = paths.split_at(1);
+
+        let engine = StratEngine::initialize().unwrap();
+        let name = "pool_name";
+        let uuid = test_async!(engine.create_pool(
+            name,
+            first,
+            PoolCreateOptions {
+                integrity: true,
+                ..Default::default()
+            }
+        ))
+        .unwrap()
+        .changed()
+        .unwrap();
+        assert!(
+            test_async!(engine.create_pool(name, first, PoolCreateOptions::default())).is_err()
+        );
+        {
+            let mut pool = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+            pool.add_blockdevs(uuid, name, rest, BlockDevTier::Data)
+                .unwrap();
+            assert!(pool.has_integrity());
+            assert!(pool
+                .blockdevs()
+                .iter()
+                .all(|(_, _, bd)| bd.integrity_mismatches().unwrap() == Some(0)));
+        }
+        assert!(test_async!(engine.stop_pool(uuid)).unwrap().is_changed());
+        engine.teardown().unwrap();
+
+        let engine = StratEngine::initialize().unwrap();
+        assert!(
+            test_async!(engine.start_pool(PoolIdentifier::Uuid(uuid), None, None, false))
+                .unwrap()
+                .is_changed()
+        );
+        let pool = test_async!(engine.get_pool(PoolIdentifier::Uuid(uuid))).unwrap();
+        assert!(pool.has_integrity());
+        assert_eq!(pool.blockdevs().len(), paths.len());
+        assert!(pool
+            .blockdevs()
+            .iter()
+            .all(|(_, _, bd)| bd.integrity_mismatches().unwrap() == Some(0)));
+        drop(pool);
+        test_async!(engine.destroy_pool(uuid)).unwrap();
+        engine.teardown().unwrap();
+    }
+
+    #[test]
+    fn loop_test_integrity() {
+        loopbacked::test_with_spec(&loopbacked::DeviceLimits::Range(2, 3, None), test_integrity);
+    }
+
+    #[test]
+    fn real_test_integrity() {
+        real::test_with_spec(&real::DeviceLimits::AtLeast(2, None, None), test_integrity);
+    }
 }
@@ -494,7 +494,7 @@ mod tests {
                 tests::{crypt, loopbacked, real},
                 udev::block_device_apply,
             },
-            types::{DevicePath, EncryptionInfo, KeyDescription},
+            types::{DevicePath, EncryptionInfo, FilesystemType, KeyDescription, MkfsOptions},
         },
         stratis::{StratisError, StratisResult},
     };
@@ -532,7 +532,9 @@ mod tests {
                 pool_name,
                 pool_uuid,
                 MDADataSize::default(),
+                false,
                 Some(&EncryptionInfo::KeyDesc(key_description.clone())),
+                None,
             )?;
 
             for dev in devices {
@@ -650,6 +652,8 @@ mod tests {
             pool_name,
             pool_uuid,
             MDADataSize::default(),
+            false,
+            None,
             None,
         )
         .unwrap();
@@ -716,7 +720,14 @@ mod tests {
         }
 
         for path in paths {
-            create_fs(path, None, false).unwrap();
+            create_fs(
+                path,
+                FilesystemType::Xfs,
+                None,
+                false,
+                &MkfsOptions::default(),
+            )
+            .unwrap();
             let device_path = DevicePath::new(path).expect("our test path");
             assert_eq!(
                 block_device_apply(&device_path, process_stratis_device)
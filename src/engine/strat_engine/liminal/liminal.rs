@@ -40,8 +40,8 @@ use crate::{
         structures::Table,
         types::{
             DevUuid, LockedPoolsInfo, MaybeInconsistent, Name, PoolEncryptionInfo, PoolIdentifier,
-            PoolUuid, StoppedPoolsInfo, StratBlockDevDiff, UdevEngineEvent, UnlockMethod,
-            UuidOrConflict,
+            PoolUuid, SizedKeyMemory, StoppedPoolsInfo, StratBlockDevDiff, UdevEngineEvent,
+            UnlockMethod, UuidOrConflict,
         },
         BlockDevTier,
     },
@@ -82,19 +82,26 @@ impl LiminalDevices {
     }
 
     /// Unlock the liminal encrypted devices that correspond to the given pool UUID.
+    ///
+    /// If a passphrase is given, it is used to unlock the devices instead of
+    /// the key in the kernel keyring.
     pub fn unlock_pool(
         &mut self,
         pools: &Table<PoolUuid, StratPool>,
         pool_uuid: PoolUuid,
         unlock_method: UnlockMethod,
+        passphrase: Option<&SizedKeyMemory>,
     ) -> StratisResult<Vec<(DevUuid, CryptHandle)>> {
         fn handle_luks(
             luks_info: &LLuksInfo,
             unlock_method: UnlockMethod,
+            passphrase: Option<&SizedKeyMemory>,
         ) -> StratisResult<CryptHandle> {
-            if let Some(h) =
-                CryptActivationHandle::setup(&luks_info.dev_info.devnode, unlock_method)?
-            {
+            if let Some(h) = CryptActivationHandle::setup(
+                &luks_info.dev_info.devnode,
+                unlock_method,
+                passphrase,
+            )? {
                 Ok(h)
             } else {
                 Err(StratisError::Msg(format!(
@@ -127,18 +134,20 @@ impl LiminalDevices {
                 for (dev_uuid, info) in map.iter() {
                     match info {
                         LInfo::Stratis(_) => (),
-                        LInfo::Luks(ref luks_info) => match handle_luks(luks_info, unlock_method) {
-                            Ok(handle) => unlocked.push((*dev_uuid, handle)),
-                            Err(e) => {
-                                return Err(handle_unlock_rollback(
-                                    e,
-                                    unlocked
-                                        .into_iter()
-                                        .map(|(_, handle)| handle)
-                                        .collect::<Vec<_>>(),
-                                ));
+                        LInfo::Luks(ref luks_info) => {
+                            match handle_luks(luks_info, unlock_method, passphrase) {
+                                Ok(handle) => unlocked.push((*dev_uuid, handle)),
+                                Err(e) => {
+                                    return Err(handle_unlock_rollback(
+                                        e,
+                                        unlocked
+                                            .into_iter()
+                                            .map(|(_, handle)| handle)
+                                            .collect::<Vec<_>>(),
+                                    ));
+                                }
                             }
-                        },
+                        }
                     }
                 }
                 unlocked
@@ -166,11 +175,18 @@ impl LiminalDevices {
 
     /// Start a pool, create the devicemapper devices, and return the fully constructed
     /// pool.
+    ///
+    /// If allow_missing is true, the pool may be started even if some of its
+    /// data devices are missing, so long as the metadata shows that no data is
+    /// allocated on them, or, if the data tier is mirrored, that they belong
+    /// to only one leg of the mirror.
     pub fn start_pool(
         &mut self,
         pools: &Table<PoolUuid, StratPool>,
         id: PoolIdentifier<PoolUuid>,
         unlock_method: Option<UnlockMethod>,
+        passphrase: Option<&SizedKeyMemory>,
+        allow_missing: bool,
     ) -> StratisResult<(Name, PoolUuid, StratPool)> {
         let pool_uuid = match id {
             PoolIdentifier::Uuid(u) => u,
@@ -196,7 +212,9 @@ impl LiminalDevices {
                 )));
             }
             (Ok(None), None) => Vec::new(),
-            (Ok(Some(_)), Some(method)) => self.unlock_pool(pools, pool_uuid, method)?,
+            (Ok(Some(_)), Some(method)) => {
+                self.unlock_pool(pools, pool_uuid, method, passphrase)?
+            }
             (Ok(None), Some(_)) => {
                 return Err(StratisError::Msg(format!(
                     "Pool with UUID {pool_uuid} is not encrypted but an unlock method was provided"
@@ -245,7 +263,7 @@ impl LiminalDevices {
             }
         };
 
-        match self.try_setup_pool(pools, pool_uuid, stopped_pool) {
+        match self.try_setup_pool(pools, pool_uuid, stopped_pool, allow_missing) {
             Ok((name, pool)) => Ok((name, pool_uuid, pool)),
             Err(e) => Err(handle_unlock_rollback(e, handles)),
         }
@@ -498,6 +516,7 @@ impl LiminalDevices {
         pools: &Table<PoolUuid, StratPool>,
         pool_uuid: PoolUuid,
         device_set: DeviceSet,
+        allow_missing: bool,
     ) -> StratisResult<(Name, StratPool)> {
         fn try_setup_pool_failure(
             pools: &Table<PoolUuid, StratPool>,
@@ -506,14 +525,21 @@ impl LiminalDevices {
             infos: &HashMap<DevUuid, LStratisDevInfo>,
             bdas: HashMap<DevUuid, BDA>,
             meta_res: StratisResult<(DateTime<Utc>, PoolSave)>,
+            allow_missing: bool,
         ) -> BDARecordResult<(Name, StratPool)> {
-            let (timestamp, metadata) = match meta_res {
+            let meta = match meta_res {
                 Ok(o) => o,
                 Err(e) => return Err((e, bdas)),
             };
 
             setup_pool(
-                pools, pool_uuid, luks_info, infos, bdas, timestamp, metadata,
+                pools,
+                pool_uuid,
+                luks_info,
+                infos,
+                bdas,
+                meta,
+                allow_missing,
             )
         }
 
@@ -539,7 +565,15 @@ impl LiminalDevices {
 
         let res = load_stratis_metadata(pool_uuid, stratis_infos_ref(&infos));
         let (infos, bdas) = split_stratis_infos(infos);
-        match try_setup_pool_failure(pools, pool_uuid, luks_info, &infos, bdas, res) {
+        match try_setup_pool_failure(
+            pools,
+            pool_uuid,
+            luks_info,
+            &infos,
+            bdas,
+            res,
+            allow_missing,
+        ) {
             Ok((name, pool)) => {
                 self.uuid_lookup = self
                     .uuid_lookup
@@ -599,7 +633,13 @@ impl LiminalDevices {
             };
             if let Some(true) | None = metadata.started {
                 setup_pool(
-                    pools, pool_uuid, luks_info, infos, bdas, timestamp, metadata,
+                    pools,
+                    pool_uuid,
+                    luks_info,
+                    infos,
+                    bdas,
+                    (timestamp, metadata),
+                    false,
                 )
                 .map(Either::Left)
             } else {
@@ -930,14 +970,22 @@ fn load_stratis_metadata(
 ///
 /// If there is a name conflict between the set of devices in devices
 /// and some existing pool, return an error.
+///
+/// If allow_missing is true, set up the pool even if some data devices
+/// that have no data allocated on them are missing, or, if the data tier is
+/// mirrored, the data devices of one leg are missing. The DM raid device of
+/// a mirrored data tier is then started degraded.
+///
+/// The pool is set up from metadata, the pool-level metadata of the devices
+/// together with the time at which it was written.
 fn setup_pool(
     pools: &Table<PoolUuid, StratPool>,
     pool_uuid: PoolUuid,
     luks_info: StratisResult<(Option<PoolEncryptionInfo>, MaybeInconsistent<Option<Name>>)>,
     infos: &HashMap<DevUuid, LStratisDevInfo>,
     bdas: HashMap<DevUuid, BDA>,
-    timestamp: DateTime<Utc>,
-    metadata: PoolSave,
+    (timestamp, metadata): (DateTime<Utc>, PoolSave),
+    allow_missing: bool,
 ) -> BDARecordResult<(Name, StratPool)> {
     if let Some((uuid, _)) = pools.get_by_name(&metadata.name) {
         return Err((
@@ -949,7 +997,7 @@ fn setup_pool(
             )), bdas));
     }
 
-    let (datadevs, cachedevs) = match get_blockdevs(&metadata.backstore, infos, bdas) {
+    let (datadevs, cachedevs) = match get_blockdevs(&metadata.backstore, infos, bdas, allow_missing) {
         Err((err, bdas)) => return Err(
             (StratisError::Chained(
                 format!(
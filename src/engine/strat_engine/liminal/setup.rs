@@ -18,7 +18,9 @@ use devicemapper::Sectors;
 use crate::{
     engine::{
         strat_engine::{
-            backstore::{BlockSizes, CryptHandle, StratBlockDev, UnderlyingDevice},
+            backstore::{
+                BlockSizes, CryptHandle, DataDevice, IntegrityDev, StratBlockDev, UnderlyingDevice,
+            },
             device::blkdev_size,
             liminal::device_info::{LStratisDevInfo, LStratisInfo},
             metadata::BDA,
@@ -128,6 +130,9 @@ pub fn get_name(infos: HashMap<DevUuid, &LStratisInfo>) -> StratisResult<Option<
 /// the given devices. Sort the blockdevs in the order in which they were
 /// recorded in the metadata.
 /// Returns an error if the blockdevs obtained do not match the metadata.
+/// If allow_missing is true, data devices that have no segments allocated
+/// according to the metadata may be missing from the devices obtained, as
+/// may the devices of one leg of a mirrored data tier.
 /// Returns a tuple, of which the first are the data devs, and the second
 /// are the devs that support the cache tier.
 /// Precondition: Every device in infos has already been determined to
@@ -137,6 +142,7 @@ pub fn get_blockdevs(
     backstore_save: &BackstoreSave,
     infos: &HashMap<DevUuid, LStratisDevInfo>,
     mut bdas: HashMap<DevUuid, BDA>,
+    allow_missing: bool,
 ) -> BDARecordResult<(Vec<StratBlockDev>, Vec<StratBlockDev>)> {
     let recorded_data_map: HashMap<DevUuid, (usize, &BaseBlockDevSave)> = backstore_save
         .data_tier
@@ -160,7 +166,13 @@ pub fn get_blockdevs(
         };
 
     let mut segment_table: HashMap<DevUuid, Vec<(Sectors, Sectors)>> = HashMap::new();
-    for seg in &backstore_save.data_tier.blockdev.allocs[0] {
+    for seg in backstore_save
+        .data_tier
+        .blockdev
+        .allocs
+        .iter()
+        .flat_map(|i| i.iter())
+    {
         segment_table
             .entry(seg.parent)
             .or_insert_with(Vec::default)
@@ -258,17 +270,33 @@ pub fn get_blockdevs(
                 Err(e) => return Err((e, bda)),
             }),
         };
+        let data_device = if bd_save.integrity == Some(true) {
+            match IntegrityDev::setup(
+                dev_uuid,
+                info.dev_info.device_number,
+                bda.extended_size().sectors(),
+            ) {
+                Ok(integrity) => DataDevice::Integrity(integrity),
+                Err(e) => return Err((e, bda)),
+            }
+        } else {
+            DataDevice::Plain(info.dev_info.device_number)
+        };
         Ok((
             tier,
             StratBlockDev::new(
-                info.dev_info.device_number,
+                data_device,
                 bda,
                 segments.unwrap_or(&vec![]),
                 bd_save.user_info.clone(),
                 bd_save.hardware_info.clone(),
                 underlying_device,
                 blksizes,
-            )?,
+            )
+            .map(|mut bd| {
+                bd.set_tags(&bd_save.tags);
+                bd
+            })?,
         ))
     }
 
@@ -294,9 +322,11 @@ pub fn get_blockdevs(
     // Verify that devices located are consistent with the metadata recorded
     // and generally consistent with expectations. If all seems correct,
     // sort the devices according to their order in the metadata.
+    // Devices in missing_ok may be absent from the devices located.
     fn check_and_sort_devs(
         mut devs: Vec<StratBlockDev>,
         dev_map: &HashMap<DevUuid, (usize, &BaseBlockDevSave)>,
+        missing_ok: &HashSet<DevUuid>,
     ) -> BDARecordResult<Vec<StratBlockDev>> {
         let mut uuids = HashSet::new();
         let mut duplicate_uuids = Vec::new();
@@ -316,7 +346,11 @@ pub fn get_blockdevs(
         }
 
         let recorded_uuids: HashSet<_> = dev_map.keys().cloned().collect();
-        if uuids != recorded_uuids {
+        if !uuids.is_subset(&recorded_uuids)
+            || !recorded_uuids
+                .difference(&uuids)
+                .all(|uuid| missing_ok.contains(uuid))
+        {
             let err_msg = format!(
                 "UUIDs of devices found ({}) did not correspond with UUIDs specified in the metadata for this group of devices ({})",
                 uuids.iter().map(|u| u.to_string()).collect::<Vec<_>>().join(", "),
@@ -333,7 +367,32 @@ pub fn get_blockdevs(
         Ok(devs)
     }
 
-    let datadevs = match check_and_sort_devs(datadevs, &recorded_data_map) {
+    // Data devices which hold no allocated segments according to the
+    // metadata may be missing if a degraded setup has been requested. If the
+    // data tier is mirrored, the devices of one leg may be missing, so long
+    // as all the devices of the other leg have been found, since the other
+    // leg holds a complete copy of the data.
+    // Cache devices are always fully allocated and so may never be missing.
+    let missing_ok = if allow_missing {
+        let mut missing_ok = recorded_data_map
+            .keys()
+            .filter(|uuid| !segment_table.contains_key(uuid))
+            .cloned()
+            .collect::<HashSet<_>>();
+        if let Some(ref legs) = backstore_save.data_tier.blockdev.legs {
+            let found = datadevs.iter().map(|bd| bd.uuid()).collect::<HashSet<_>>();
+            for (idx, leg) in legs.iter().enumerate() {
+                if legs[1 - idx].iter().all(|uuid| found.contains(uuid)) {
+                    missing_ok.extend(leg.iter().cloned());
+                }
+            }
+        }
+        missing_ok
+    } else {
+        HashSet::new()
+    };
+
+    let datadevs = match check_and_sort_devs(datadevs, &recorded_data_map, &missing_ok) {
         Ok(dd) => dd,
         Err((err, mut bdas)) => {
             bdas.extend(bds_to_bdas(cachedevs));
@@ -346,7 +405,7 @@ pub fn get_blockdevs(
         }
     };
 
-    let cachedevs = match check_and_sort_devs(cachedevs, &recorded_cache_map) {
+    let cachedevs = match check_and_sort_devs(cachedevs, &recorded_cache_map, &HashSet::new()) {
         Ok(cd) => cd,
         Err((err, mut bdas)) => {
             bdas.extend(bds_to_bdas(datadevs));
@@ -28,7 +28,7 @@ pub use self::{
     keys::StratKeyActions,
     metadata::{StaticHeader, StaticHeaderResult, BDA},
     ns::unshare_mount_namespace,
-    thinpool::ThinPoolSizeParams,
+    thinpool::{ThinPoolSizeParams, DATA_BLOCK_SIZE, DEFAULT_EXTEND_POLICY},
 };
 
 #[cfg(test)]
@@ -114,7 +114,7 @@ impl Display for ThinPoolRole {
     }
 }
 
-/// The various roles taken on by DM devices in the cache tier.
+/// The various roles taken on by DM devices in the backstore.
 #[derive(Clone, Copy)]
 pub enum CacheRole {
     /// The DM cache device, contains the other three devices.
@@ -125,6 +125,20 @@ pub enum CacheRole {
     MetaSub,
     /// The origin sub-device of the DM cache device, holds the actual data.
     OriginSub,
+    /// The DM raid device which mirrors the data of a mirrored data tier,
+    /// the origin sub-device maps onto it.
+    MirrorSub,
+    /// A leg of the DM raid device, either 0 or 1.
+    LegSub(usize),
+    /// The metadata sub-device of a leg of the DM raid device, either 0 or 1.
+    LegMetaSub(usize),
+    /// A DM mirror device which copies a segment of the data tier that is
+    /// being moved to its new location; the origin sub-device maps the
+    /// segment onto it while the segment is moved.
+    MoveSub(usize),
+    /// The second leg of a DM mirror device which copies a segment of the
+    /// data tier, maps the new location of the segment.
+    MoveDestSub(usize),
 }
 
 impl Display for CacheRole {
@@ -134,6 +148,32 @@ impl Display for CacheRole {
             CacheRole::CacheSub => write!(f, "cachesub"),
             CacheRole::MetaSub => write!(f, "metasub"),
             CacheRole::OriginSub => write!(f, "originsub"),
+            CacheRole::MirrorSub => write!(f, "mirrorsub"),
+            CacheRole::LegSub(leg) => write!(f, "leg{leg}sub"),
+            CacheRole::LegMetaSub(leg) => write!(f, "leg{leg}metasub"),
+            CacheRole::MoveSub(index) => write!(f, "mv{index}sub"),
+            CacheRole::MoveDestSub(index) => write!(f, "mvd{index}sub"),
+        }
+    }
+}
+
+/// The roles taken on by the DM devices which protect a single block device
+/// with dm-integrity.
+#[derive(Clone, Copy)]
+pub enum IntegrityRole {
+    /// The DM integrity device, which covers the data region of the block
+    /// device.
+    Integrity,
+    /// The device which presents the DM integrity device at the offsets of
+    /// the data region of the block device, allocated from by upper layers.
+    IntegritySub,
+}
+
+impl Display for IntegrityRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            IntegrityRole::Integrity => write!(f, "integrity"),
+            IntegrityRole::IntegritySub => write!(f, "integritysub"),
         }
     }
 }
@@ -237,6 +277,30 @@ pub fn format_backstore_ids(pool_uuid: PoolUuid, role: CacheRole) -> (DmNameBuf,
     )
 }
 
+/// Format a name & uuid for the dm-integrity devices of a block device.
+///
+/// Prerequisite: len(format!("{}", FORMAT_VERSION)
+///             + len("stratis")                         7
+///             + len("private")                         7
+///             + num_dashes                             3
+///             + len(dev uuid)                          32
+///             + max(len(IntegrityRole))                12
+///             < 128 (129 for UUID)
+///
+/// which is equivalent to len(format!("{}", FORMAT_VERSION) < 67 (68 for UUID)
+pub fn format_integrity_ids(dev_uuid: DevUuid, role: IntegrityRole) -> (DmNameBuf, DmUuidBuf) {
+    let value = format!(
+        "stratis-{}-private-{}-{}",
+        FORMAT_VERSION,
+        uuid_to_string!(dev_uuid),
+        role
+    );
+    (
+        DmNameBuf::new(value.clone()).expect("FORMAT_VERSION display_length < 67"),
+        DmUuidBuf::new(value).expect("FORMAT_VERSION display_length < 68"),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
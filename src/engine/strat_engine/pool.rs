@@ -2,35 +2,53 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::{cmp::max, collections::HashMap, path::Path, vec::Vec};
+use std::{
+    cmp::max,
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+    vec::Vec,
+};
 
 use chrono::{DateTime, Utc};
 use serde_json::{Map, Value};
 
-use devicemapper::{Bytes, DmNameBuf, Sectors};
+use devicemapper::{Bytes, DmNameBuf, Sectors, ThinDevId};
 use stratisd_proc_macros::strat_pool_impl_gen;
 
 use crate::{
     engine::{
-        engine::{DumpState, Pool, StateDiff},
+        engine::{DumpState, Filesystem, Pool, StateDiff},
         shared::{
-            init_cache_idempotent_or_err, validate_filesystem_size_specs, validate_name,
-            validate_paths,
+            init_cache_idempotent_or_err, thin_pool_settings, validate_allocation_config,
+            validate_blockdev_tags, validate_filesystem_size_specs, validate_fs_extend_config,
+            validate_fs_size_limit, validate_name, validate_paths, validate_redundancy,
+            validate_snapshot_policies, validate_thin_pool_extend_policy,
         },
         strat_engine::{
-            backstore::{Backstore, ProcessedPathInfos, StratBlockDev, UnownedDevices},
+            backstore::{
+                wipe_blockdevs, Backstore, ProcessedPathInfos, StratBlockDev, UnownedDevices,
+            },
+            cmd::verify_fs_executables,
             liminal::{DeviceInfo, DeviceSet, LInfo},
             metadata::MDADataSize,
             serde_structs::{FlexDevsSave, PoolSave, Recordable},
             shared::tiers_to_bdas,
-            thinpool::{StratFilesystem, ThinPool, ThinPoolSizeParams, DATA_BLOCK_SIZE},
+            thinpool::{
+                StratFilesystem, StreamHeader, ThinPool, ThinPoolSizeParams, DATA_BLOCK_SIZE,
+                DEFAULT_EXTEND_POLICY,
+            },
             types::BDARecordResult,
         },
         types::{
-            ActionAvailability, BlockDevTier, Clevis, Compare, CreateAction, DeleteAction, DevUuid,
-            EncryptionInfo, FilesystemUuid, GrowAction, Key, KeyDescription, Name, PoolDiff,
-            PoolEncryptionInfo, PoolUuid, RegenAction, RenameAction, SetCreateAction,
-            SetDeleteAction, StratFilesystemDiff, StratPoolDiff,
+            ActionAvailability, AllocationConfig, BlockDevTier, CacheConfig, CacheStats, Clevis,
+            Compare, CreateAction, DeleteAction, DevUuid, Encryption, EncryptionConfig,
+            EncryptionInfo, FilesystemSpec, FilesystemUuid, FsExtendConfig, GrowAction, Key,
+            KeyDescription, MaybeInconsistent, Name, PassphraseBindings, PoolCreateOptions,
+            PoolDiff, PoolEncryptionInfo, PoolUuid, Redundancy, RegenAction, RenameAction,
+            ScheduledSnapshots, SetCreateAction, SetDeleteAction, SnapshotPolicy, SpaceUsage,
+            StratFilesystemDiff, StratPoolDiff, ThinPoolExtendPolicy,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -167,8 +185,14 @@ impl StratPool {
     pub fn initialize(
         name: &str,
         devices: UnownedDevices,
-        encryption_info: Option<&EncryptionInfo>,
+        options: PoolCreateOptions<'_>,
     ) -> StratisResult<(PoolUuid, StratPool)> {
+        let (data_block_size, extend_policy) = thin_pool_settings(
+            options.thin_pool_config,
+            DATA_BLOCK_SIZE,
+            DEFAULT_EXTEND_POLICY,
+        )?;
+
         let pool_uuid = PoolUuid::new_v4();
 
         // FIXME: Initializing with the minimum MDA size is not necessarily
@@ -179,12 +203,16 @@ impl StratPool {
             pool_uuid,
             devices,
             MDADataSize::default(),
-            encryption_info,
+            options,
         )?;
 
         let thinpool = ThinPool::new(
             pool_uuid,
-            match ThinPoolSizeParams::new(backstore.datatier_usable_size()) {
+            match ThinPoolSizeParams::new(
+                backstore.datatier_usable_size(),
+                data_block_size,
+                extend_policy.extend_size,
+            ) {
                 Ok(ref params) => params,
                 Err(causal_error) => {
                     if let Err(cleanup_err) = backstore.destroy() {
@@ -197,7 +225,8 @@ impl StratPool {
                     return Err(causal_error);
                 }
             },
-            DATA_BLOCK_SIZE,
+            data_block_size,
+            extend_policy,
             &mut backstore,
         );
 
@@ -315,6 +344,18 @@ impl StratPool {
         self.backstore.save_state(data.as_bytes())
     }
 
+    /// Write current metadata to pool members, then call wipe to wipe the
+    /// devices that the metadata no longer refers to. The devices are wiped
+    /// only after the metadata has been written so that the pool can always
+    /// be set up again.
+    fn write_metadata_then_wipe<F>(&mut self, name: &str, wipe: F) -> StratisResult<()>
+    where
+        F: FnOnce() -> StratisResult<()>,
+    {
+        self.write_metadata(name)?;
+        wipe()
+    }
+
     /// Teardown a pool.
     #[cfg(test)]
     pub fn teardown(&mut self) -> StratisResult<()> {
@@ -355,6 +396,154 @@ impl StratPool {
         self.thin_pool.check_fs(pool_uuid, &self.backstore)
     }
 
+    /// Calculate the mapped, exclusive, and shared space of all filesystems
+    /// in this pool. The calculation only reads the pool.
+    pub fn space_usage(&self) -> StratisResult<HashMap<ThinDevId, SpaceUsage>> {
+        self.thin_pool.space_usage()
+    }
+
+    /// Set the space usage calculated by space_usage() on the filesystems in
+    /// this pool and return which changed.
+    #[pool_mutating_action("NoPoolChanges")]
+    pub fn set_space_usage(
+        &mut self,
+        _pool_uuid: PoolUuid,
+        usage: &HashMap<ThinDevId, SpaceUsage>,
+    ) -> StratisResult<HashMap<FilesystemUuid, StratFilesystemDiff>> {
+        Ok(self.thin_pool.set_space_usage(usage))
+    }
+
+    /// Take the snapshots that are due according to the snapshot policies of
+    /// the filesystems in this pool and destroy the scheduled snapshots that
+    /// the policies no longer keep. A failure to create or destroy a single
+    /// snapshot is logged and does not prevent applying the other policies.
+    #[pool_mutating_action("NoRequests")]
+    pub fn run_snapshot_policies(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        now: DateTime<Utc>,
+    ) -> StratisResult<ScheduledSnapshots> {
+        let mut scheduled = ScheduledSnapshots::default();
+        // Snapshots are not taken while the pool is out of space, but
+        // destroying the snapshots the policies no longer keep frees space.
+        let out_of_alloc_space = self.thin_pool.out_of_alloc_space();
+
+        let age_of = |created: DateTime<Utc>| u64::try_from((now - created).num_seconds()).ok();
+
+        let policies = self
+            .thin_pool
+            .filesystems()
+            .into_iter()
+            .flat_map(|(fs_name, fs_uuid, fs)| {
+                fs.snapshot_policies().iter().map(move |policy| {
+                    (fs_name.clone(), fs_uuid, fs.thindev_size(), policy.clone())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for (fs_name, fs_uuid, fs_size, policy) in policies {
+            // Scheduled snapshots of the filesystem by this policy, newest
+            // first. They are found by the recorded owner of the policy
+            // rather than by their origin, which may be unset.
+            let mut snapshots = self
+                .thin_pool
+                .filesystems()
+                .into_iter()
+                .filter(|(_, _, fs)| {
+                    fs.scheduled_for() == Some(fs_uuid)
+                        && fs.scheduled_by() == Some(policy.name.as_str())
+                })
+                .map(|(_, uuid, fs)| (uuid, fs.created()))
+                .collect::<Vec<_>>();
+            snapshots.sort_by(|(_, c1), (_, c2)| c2.cmp(c1));
+
+            let due = !out_of_alloc_space
+                && match snapshots.first() {
+                    Some((_, newest)) => age_of(*newest)
+                        .map(|age| age >= policy.interval)
+                        .unwrap_or(false),
+                    None => true,
+                };
+            if due {
+                let snapshot_name = format!(
+                    "{}-{}-{}",
+                    fs_name,
+                    policy.name,
+                    now.format("%Y%m%dT%H%M%SZ")
+                );
+                let result = self
+                    .check_fs_limit(1)
+                    .and_then(|_| validate_name(&snapshot_name))
+                    .and_then(|_| self.check_overprov(fs_size))
+                    .and_then(|_| {
+                        if self
+                            .thin_pool
+                            .get_filesystem_by_name(&snapshot_name)
+                            .is_some()
+                        {
+                            Err(StratisError::Msg(format!(
+                                "A filesystem with name {snapshot_name} already exists"
+                            )))
+                        } else {
+                            self.thin_pool
+                                .snapshot_filesystem(
+                                    pool_name,
+                                    pool_uuid,
+                                    fs_uuid,
+                                    &snapshot_name,
+                                    Some(&policy.name),
+                                )
+                                .map(|(uuid, fs)| (uuid, fs.created()))
+                        }
+                    });
+                match result {
+                    Ok((uuid, created)) => {
+                        snapshots.insert(0, (uuid, created));
+                        scheduled.created.push(uuid);
+                    }
+                    Err(e) => warn!(
+                        "Failed to take snapshot of filesystem with UUID {} according to policy {}: {}",
+                        fs_uuid, policy.name, e
+                    ),
+                }
+            }
+
+            let keep_count = policy
+                .keep_count
+                .and_then(|count| usize::try_from(count).ok())
+                .unwrap_or(usize::MAX);
+            for (index, (uuid, created)) in snapshots.into_iter().enumerate() {
+                let expired = policy
+                    .keep_age
+                    .map(|keep_age| age_of(created).map(|age| age > keep_age).unwrap_or(false))
+                    .unwrap_or(false);
+                if index >= keep_count || expired {
+                    let orphans = self
+                        .thin_pool
+                        .filesystems()
+                        .into_iter()
+                        .filter(|(_, _, fs)| fs.origin() == Some(uuid))
+                        .map(|(_, orphan, _)| orphan)
+                        .collect::<Vec<_>>();
+                    match self.thin_pool.destroy_filesystem(pool_name, uuid) {
+                        Ok(Some(uuid)) => {
+                            scheduled.destroyed.push(uuid);
+                            scheduled.origins_unset.extend(orphans);
+                        }
+                        Ok(None) => (),
+                        Err(e) => warn!(
+                            "Failed to destroy snapshot with UUID {} no longer kept by policy {}: {}",
+                            uuid, policy.name, e
+                        ),
+                    }
+                }
+            }
+        }
+
+        Ok(scheduled)
+    }
+
     pub fn record(&self, name: &str) -> PoolSave {
         PoolSave {
             name: name.to_owned(),
@@ -515,6 +704,7 @@ impl Pool for StratPool {
         pool_uuid: PoolUuid,
         pool_name: &str,
         blockdevs: &[&Path],
+        cache_config: &CacheConfig,
         supports_encrypted: bool,
     ) -> StratisResult<SetCreateAction<DevUuid>> {
         validate_paths(blockdevs)?;
@@ -604,7 +794,12 @@ impl Pool for StratPool {
             self.thin_pool.suspend()?;
             let devices_result = self
                 .backstore
-                .init_cache(Name::new(pool_name.to_string()), pool_uuid, unowned_devices)
+                .init_cache(
+                    Name::new(pool_name.to_string()),
+                    pool_uuid,
+                    unowned_devices,
+                    cache_config.clone(),
+                )
                 .and_then(|bdi| {
                     self.thin_pool
                         .set_device(self.backstore.device().expect(
@@ -643,6 +838,64 @@ impl Pool for StratPool {
         }
     }
 
+    #[pool_mutating_action("NoRequests")]
+    fn remove_cache(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+    ) -> StratisResult<SetDeleteAction<DevUuid>> {
+        if !self.has_cache() {
+            return Ok(SetDeleteAction::empty());
+        }
+
+        // Write back the bulk of the dirty blocks while the pool is in use,
+        // then suspend the thin pool so that no further writes can reach the
+        // cache while the last dirty blocks are written back and the thin
+        // pool is switched over to the origin sub-device.
+        self.backstore.write_back_cache()?;
+        if let Err(causal_error) = self.thin_pool.suspend() {
+            if let Err(rollback_error) = self.backstore.restore_cache_config() {
+                return Err(StratisError::NoActionRollbackError {
+                    causal_error: Box::new(causal_error),
+                    rollback_error: Box::new(rollback_error),
+                });
+            }
+            return Err(causal_error);
+        }
+        let thin_pool = &mut self.thin_pool;
+        let remove_res = self
+            .backstore
+            .remove_cache(pool_uuid, |device| thin_pool.set_device(device).map(|_| ()));
+        self.thin_pool.resume()?;
+        let mut cache_tier = remove_res?;
+
+        let uuids = cache_tier
+            .blockdevs()
+            .into_iter()
+            .map(|(uuid, _)| uuid)
+            .collect::<Vec<_>>();
+        self.write_metadata_then_wipe(pool_name, || cache_tier.destroy())?;
+
+        Ok(SetDeleteAction::new(uuids))
+    }
+
+    fn cache_config(&self) -> Option<CacheConfig> {
+        self.backstore.cache_config().cloned()
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn set_cache_config(&mut self, pool_name: &str, config: CacheConfig) -> StratisResult<bool> {
+        let changed = self.backstore.set_cache_config(config)?;
+        if changed {
+            self.write_metadata(pool_name)?;
+        }
+        Ok(changed)
+    }
+
+    fn cache_stats(&self) -> StratisResult<Option<CacheStats>> {
+        self.backstore.cache_stats()
+    }
+
     #[pool_mutating_action("NoRequests")]
     #[pool_rollback]
     fn bind_clevis(
@@ -713,33 +966,99 @@ impl Pool for StratPool {
         self.backstore.rebind_clevis().map(|_| RegenAction)
     }
 
+    #[pool_mutating_action("NoRequests")]
+    #[pool_rollback]
+    fn add_passphrase_binding(
+        &mut self,
+        name: &str,
+        key_desc: &KeyDescription,
+    ) -> StratisResult<CreateAction<Key>> {
+        validate_name(name)?;
+        let changed = self.backstore.add_passphrase_binding(name, key_desc)?;
+        if changed {
+            Ok(CreateAction::Created(Key))
+        } else {
+            Ok(CreateAction::Identity)
+        }
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    #[pool_rollback]
+    fn remove_passphrase_binding(&mut self, name: &str) -> StratisResult<DeleteAction<Key>> {
+        let changed = self.backstore.remove_passphrase_binding(name)?;
+        if changed {
+            Ok(DeleteAction::Deleted(Key))
+        } else {
+            Ok(DeleteAction::Identity)
+        }
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn encrypt_pool(
+        &mut self,
+        name: &Name,
+        pool_uuid: PoolUuid,
+        encryption_info: &EncryptionInfo,
+    ) -> StratisResult<CreateAction<Encryption>> {
+        match self.backstore.encrypt(name, pool_uuid, encryption_info)? {
+            Some(encryption) => {
+                self.write_metadata(name)?;
+                // The data is encrypted without holding the pool lock. If
+                // encrypting it can not be begun now, it is resumed when the
+                // pool is next started.
+                if let Err(e) = encryption.spawn() {
+                    warn!(
+                        "Failed to begin encrypting the data of pool {}: {}",
+                        name, e
+                    );
+                }
+                Ok(CreateAction::Created(Encryption))
+            }
+            None => Ok(CreateAction::Identity),
+        }
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn rotate_volume_key(&mut self, pool_uuid: PoolUuid) -> StratisResult<RegenAction> {
+        self.backstore
+            .rotate_volume_key(pool_uuid)
+            .map(|_| RegenAction)
+    }
+
     #[pool_mutating_action("NoRequests")]
     fn create_filesystems<'a>(
         &mut self,
         pool_name: &str,
         pool_uuid: PoolUuid,
-        specs: &[(&'a str, Option<Bytes>)],
+        specs: &[FilesystemSpec<'a>],
     ) -> StratisResult<SetCreateAction<(&'a str, FilesystemUuid, Sectors)>> {
         self.check_fs_limit(specs.len())?;
 
         let spec_map = validate_filesystem_size_specs(specs)?;
 
-        let increase = spec_map.values().copied().sum::<Sectors>();
+        let increase = spec_map.values().map(|params| params.size).sum::<Sectors>();
         self.check_overprov(increase)?;
 
-        spec_map.iter().fold(Ok(()), |res, (name, size)| {
+        spec_map.iter().fold(Ok(()), |res, (name, params)| {
             res.and_then(|()| validate_name(name))
                 .and_then(|()| {
                     if let Some((_, fs)) = self.thin_pool.get_filesystem_by_name(name) {
-                        if fs.thindev_size() == *size {
-                            Ok(())
-                        } else {
+                        if fs.thindev_size() != params.size {
                             Err(StratisError::Msg(format!(
                                 "Size {} of filesystem {} to be created conflicts with size {} for existing filesystem",
-                                size,
+                                params.size,
                                 name,
                                 fs.thindev_size()
                             )))
+                        } else if fs.fs_type() != params.fs_type {
+                            Err(StratisError::Msg(format!(
+                                "Type {} of filesystem {} to be created conflicts with type {} for existing filesystem",
+                                params.fs_type,
+                                name,
+                                fs.fs_type()
+                            )))
+                        } else {
+                            Ok(())
                         }
                     } else {
                         Ok(())
@@ -747,14 +1066,27 @@ impl Pool for StratPool {
                 })
         })?;
 
+        spec_map
+            .values()
+            .map(|params| params.fs_type)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .try_for_each(verify_fs_executables)?;
+
         // TODO: Roll back on filesystem initialization failure.
         let mut result = Vec::new();
-        for (name, size) in spec_map {
+        for (name, params) in spec_map {
             if self.thin_pool.get_mut_filesystem_by_name(name).is_none() {
-                let fs_uuid = self
-                    .thin_pool
-                    .create_filesystem(pool_name, pool_uuid, name, size)?;
-                result.push((name, fs_uuid, size));
+                let fs_uuid = self.thin_pool.create_filesystem(
+                    pool_name,
+                    pool_uuid,
+                    name,
+                    params.size,
+                    params.size_limit,
+                    params.fs_type,
+                    &params.options,
+                )?;
+                result.push((name, fs_uuid, params.size));
             }
         }
 
@@ -879,6 +1211,8 @@ impl Pool for StratPool {
                     return Ok(SetCreateAction::new(vec![]));
                 }
 
+                validate_redundancy(self.backstore.redundancy(), unowned_devices.len())?;
+
                 let block_size_summary = unowned_devices.blocksizes();
                 if block_size_summary.len() > 1 {
                     let err_str = "The devices specified to be added to the data tier do not have uniform physcal and logical sector sizes.".into();
@@ -917,6 +1251,169 @@ impl Pool for StratPool {
         bdev_info
     }
 
+    #[pool_mutating_action("NoRequests")]
+    fn remove_blockdevs(
+        &mut self,
+        pool_uuid: PoolUuid,
+        pool_name: &str,
+        devices: &[DevUuid],
+        tier: BlockDevTier,
+    ) -> StratisResult<SetDeleteAction<DevUuid>> {
+        let mut to_remove = Vec::new();
+        for uuid in devices {
+            match self.backstore.get_blockdev_by_uuid(*uuid) {
+                Some((dev_tier, _)) if dev_tier == tier => {
+                    if !to_remove.contains(uuid) {
+                        to_remove.push(*uuid);
+                    }
+                }
+                Some((BlockDevTier::Cache, _)) => {
+                    return Err(StratisError::Msg(format!(
+                        "Device with UUID {uuid} belongs to the cache tier of pool with UUID {pool_uuid}, not the data tier"
+                    )));
+                }
+                Some((BlockDevTier::Data, _)) => {
+                    return Err(StratisError::Msg(format!(
+                        "Device with UUID {uuid} belongs to the data tier of pool with UUID {pool_uuid}, not the cache tier"
+                    )));
+                }
+                None => (),
+            }
+        }
+
+        if to_remove.is_empty() {
+            return Ok(SetDeleteAction::empty());
+        }
+
+        match tier {
+            BlockDevTier::Data => {
+                if self.backstore.redundancy() != Redundancy::None {
+                    return Err(StratisError::Msg(format!(
+                        "Removing devices is not supported for pools with a mirrored data tier; the data tier of pool with UUID {pool_uuid} has redundancy {}",
+                        self.backstore.redundancy()
+                    )));
+                }
+
+                if to_remove.len() == self.backstore.datadevs().len() {
+                    return Err(StratisError::Msg(format!(
+                        "Removing the requested devices would leave no devices in the data tier of pool with UUID {pool_uuid}"
+                    )));
+                }
+
+                // The data is moved while the pool remains in use. Until
+                // finish_move() every write goes to both the old and the new
+                // locations, so the new locations must be recorded before
+                // then; either set of locations is valid in the meantime.
+                if let Some(migration) = self.backstore.evacuate_datadevs(pool_uuid, &to_remove)? {
+                    if let Err(causal_error) = self.write_metadata(pool_name) {
+                        if let Err(rollback_error) = self.backstore.abort_move(migration) {
+                            return Err(StratisError::NoActionRollbackError {
+                                causal_error: Box::new(causal_error),
+                                rollback_error: Box::new(rollback_error),
+                            });
+                        }
+                        return Err(causal_error);
+                    }
+                    self.backstore.finish_move(migration)?;
+                }
+
+                let mut removed = self.backstore.remove_datadevs(&to_remove)?;
+                self.write_metadata_then_wipe(pool_name, || wipe_blockdevs(&mut removed))?;
+            }
+            BlockDevTier::Cache => {
+                if to_remove.len() == self.backstore.cachedevs().len() {
+                    return Err(StratisError::Msg(format!(
+                        "Removing the requested devices would leave no devices in the cache tier of pool with UUID {pool_uuid}; remove the cache instead"
+                    )));
+                }
+
+                let mut removed = self.backstore.remove_cachedevs(&to_remove)?;
+                self.write_metadata_then_wipe(pool_name, || wipe_blockdevs(&mut removed))?;
+            }
+        }
+
+        Ok(SetDeleteAction::new(to_remove))
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn replace_blockdev(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        old: DevUuid,
+        new: &Path,
+    ) -> StratisResult<CreateAction<DevUuid>> {
+        validate_paths(&[new])?;
+
+        match self.backstore.get_blockdev_by_uuid(old) {
+            Some((BlockDevTier::Data, _)) => (),
+            Some((BlockDevTier::Cache, _)) => {
+                return Err(StratisError::Msg(
+                    "Replacing devices in the cache tier is not supported".to_string(),
+                ));
+            }
+            None => {
+                return Err(StratisError::Msg(format!(
+                    "No device with UUID {old} found in pool with UUID {pool_uuid}"
+                )));
+            }
+        }
+
+        if self.backstore.redundancy() != Redundancy::None {
+            return Err(StratisError::Msg(format!(
+                "Replacing devices is not supported for pools with a mirrored data tier; the data tier of pool with UUID {pool_uuid} has redundancy {}",
+                self.backstore.redundancy()
+            )));
+        }
+
+        let devices = ProcessedPathInfos::try_from(&[new][..])?;
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty()?;
+
+        let block_size_summary = unowned_devices.blocksizes();
+        let current_sector_sizes = self
+            .backstore
+            .block_size_summary(BlockDevTier::Data)
+            .expect("always exists")
+            .validate()
+            .expect("All operations prevented if validate() function on data tier block size summary returns an error");
+        let new_sector_sizes = block_size_summary
+            .keys()
+            .next()
+            .expect("new was processed and is not owned by Stratis");
+        if new_sector_sizes != &current_sector_sizes {
+            let err_str = format!("The sector sizes of the replacement device, {new_sector_sizes}, do not match the effective sector sizes of the existing data devices, {current_sector_sizes}");
+            return Err(StratisError::Msg(err_str));
+        }
+
+        // The data is moved while the pool remains in use, as for the
+        // removal of data devices; the new locations are recorded before
+        // finish_move() stops the writes to the old locations.
+        let (new_uuid, migration) = self.backstore.replace_datadev(
+            Name::new(pool_name.to_string()),
+            pool_uuid,
+            old,
+            unowned_devices,
+        )?;
+        if let Err(causal_error) = self.write_metadata(pool_name) {
+            if let Err(rollback_error) = self.backstore.abort_replace(new_uuid, migration) {
+                return Err(StratisError::NoActionRollbackError {
+                    causal_error: Box::new(causal_error),
+                    rollback_error: Box::new(rollback_error),
+                });
+            }
+            return Err(causal_error);
+        }
+        if let Some(migration) = migration {
+            self.backstore.finish_move(migration)?;
+        }
+
+        let mut removed = self.backstore.remove_datadevs(&[old])?;
+        self.write_metadata_then_wipe(pool_name, || wipe_blockdevs(&mut removed))?;
+
+        Ok(CreateAction::Created(new_uuid))
+    }
+
     #[pool_mutating_action("NoRequests")]
     fn destroy_filesystems(
         &mut self,
@@ -951,6 +1448,56 @@ impl Pool for StratPool {
         }
     }
 
+    #[pool_mutating_action("NoRequests")]
+    fn set_fs_size_limit(
+        &mut self,
+        _pool_name: &str,
+        uuid: FilesystemUuid,
+        size_limit: Option<Bytes>,
+    ) -> StratisResult<RenameAction<FilesystemUuid>> {
+        let size_limit = match self.thin_pool.get_filesystem_by_uuid(uuid) {
+            Some((name, fs)) => validate_fs_size_limit(&name, fs.thindev_size(), size_limit)?,
+            None => return Ok(RenameAction::NoSource),
+        };
+        match self.thin_pool.set_fs_size_limit(uuid, size_limit)? {
+            Some(true) => Ok(RenameAction::Renamed(uuid)),
+            Some(false) => Ok(RenameAction::Identity),
+            None => Ok(RenameAction::NoSource),
+        }
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn set_fs_extend_config(
+        &mut self,
+        _pool_name: &str,
+        uuid: FilesystemUuid,
+        config: Option<FsExtendConfig>,
+    ) -> StratisResult<RenameAction<FilesystemUuid>> {
+        if let Some(ref config) = config {
+            validate_fs_extend_config(config)?;
+        }
+        match self.thin_pool.set_fs_extend_config(uuid, config)? {
+            Some(true) => Ok(RenameAction::Renamed(uuid)),
+            Some(false) => Ok(RenameAction::Identity),
+            None => Ok(RenameAction::NoSource),
+        }
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn set_snapshot_policies(
+        &mut self,
+        _pool_name: &str,
+        uuid: FilesystemUuid,
+        policies: &[SnapshotPolicy],
+    ) -> StratisResult<RenameAction<FilesystemUuid>> {
+        validate_snapshot_policies(policies)?;
+        match self.thin_pool.set_fs_snapshot_policies(uuid, policies)? {
+            Some(true) => Ok(RenameAction::Renamed(uuid)),
+            Some(false) => Ok(RenameAction::Identity),
+            None => Ok(RenameAction::NoSource),
+        }
+    }
+
     #[pool_mutating_action("NoRequests")]
     fn snapshot_filesystem<'a>(
         &'a mut self,
@@ -983,10 +1530,93 @@ impl Pool for StratPool {
         }
 
         self.thin_pool
-            .snapshot_filesystem(pool_name, pool_uuid, origin_uuid, snapshot_name)
+            .snapshot_filesystem(pool_name, pool_uuid, origin_uuid, snapshot_name, None)
             .map(CreateAction::Created)
     }
 
+    #[pool_mutating_action("NoRequests")]
+    fn revert_filesystem(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        origin: FilesystemUuid,
+        snapshot: FilesystemUuid,
+    ) -> StratisResult<DeleteAction<FilesystemUuid>> {
+        self.thin_pool
+            .revert_filesystem(pool_name, pool_uuid, origin, snapshot)?
+            .map(DeleteAction::Deleted)
+            .ok_or_else(|| {
+                StratisError::Msg(format!(
+                    "Filesystems with UUIDs {origin} and {snapshot} could not both be found"
+                ))
+            })
+    }
+
+    fn send_filesystem(
+        &self,
+        fs_uuid: FilesystemUuid,
+        parent: Option<FilesystemUuid>,
+        stream: File,
+    ) -> StratisResult<Bytes> {
+        let mut stream = BufWriter::new(stream);
+        self.thin_pool.send_filesystem(fs_uuid, parent, &mut stream)
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn receive_filesystem(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        fs_name: &str,
+        stream: File,
+    ) -> StratisResult<FilesystemUuid> {
+        let mut stream = BufReader::new(stream);
+
+        validate_name(fs_name)?;
+        if self.thin_pool.get_filesystem_by_name(fs_name).is_some() {
+            return Err(StratisError::Msg(format!(
+                "Filesystem {fs_name} already exists"
+            )));
+        }
+        self.check_fs_limit(1)?;
+
+        let header = StreamHeader::read(&mut stream)?;
+        self.check_overprov(header.size)?;
+        verify_fs_executables(header.fs_type)?;
+        self.thin_pool
+            .receive_filesystem(pool_name, pool_uuid, fs_name, &header, &mut stream)
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn receive_filesystem_delta(
+        &mut self,
+        pool_name: &str,
+        fs_uuid: FilesystemUuid,
+        stream: File,
+    ) -> StratisResult<Bytes> {
+        let mut stream = BufReader::new(stream);
+
+        let header = StreamHeader::read(&mut stream)?;
+        let (_, fs) = self
+            .thin_pool
+            .get_filesystem_by_uuid(fs_uuid)
+            .ok_or_else(|| {
+                StratisError::Msg(format!("Filesystem with UUID {fs_uuid} not found"))
+            })?;
+        if let Some(size_limit) = fs.size_limit() {
+            if header.size > size_limit {
+                return Err(StratisError::Msg(format!(
+                    "Stream of a filesystem of size {} exceeds the size limit {} of filesystem with UUID {fs_uuid}",
+                    header.size, size_limit
+                )));
+            }
+        }
+        self.check_overprov(Sectors(header.size.saturating_sub(*fs.thindev_size())))?;
+        verify_fs_executables(header.fs_type)?;
+        self.thin_pool
+            .receive_filesystem_delta(pool_name, fs_uuid, &header, &mut stream)
+    }
+
     fn total_physical_size(&self) -> Sectors {
         self.backstore.datatier_size()
     }
@@ -1064,18 +1694,72 @@ impl Pool for StratPool {
         }
     }
 
-    fn has_cache(&self) -> bool {
-        self.backstore.has_cache()
-    }
-
-    fn is_encrypted(&self) -> bool {
-        self.datadevs_encrypted()
+    #[pool_mutating_action("NoRequests")]
+    fn set_blockdev_tags(
+        &mut self,
+        pool_name: &str,
+        uuid: DevUuid,
+        tags: &[String],
+    ) -> StratisResult<RenameAction<DevUuid>> {
+        validate_blockdev_tags(tags)?;
+        let result = self.backstore.set_blockdev_tags(uuid, tags);
+        match result {
+            Ok(Some(uuid)) => {
+                self.write_metadata(pool_name)?;
+                Ok(RenameAction::Renamed(uuid))
+            }
+            Ok(None) => Ok(RenameAction::Identity),
+            Err(_) => Ok(RenameAction::NoSource),
+        }
+    }
+
+    fn allocation_config(&self) -> AllocationConfig {
+        self.backstore.allocation_config().clone()
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn set_allocation_config(
+        &mut self,
+        pool_name: &str,
+        config: AllocationConfig,
+    ) -> StratisResult<bool> {
+        validate_allocation_config(&config)?;
+        if self.backstore.set_allocation_config(config) {
+            self.write_metadata(pool_name)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn has_cache(&self) -> bool {
+        self.backstore.has_cache()
+    }
+
+    fn redundancy(&self) -> Redundancy {
+        self.backstore.redundancy()
+    }
+
+    fn has_integrity(&self) -> bool {
+        self.backstore.has_integrity()
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.datadevs_encrypted()
     }
 
     fn encryption_info(&self) -> Option<PoolEncryptionInfo> {
         self.backstore.data_tier_encryption_info()
     }
 
+    fn encryption_config(&self) -> Option<MaybeInconsistent<EncryptionConfig>> {
+        self.backstore.data_tier_encryption_config()
+    }
+
+    fn passphrase_bindings(&self) -> Option<MaybeInconsistent<PassphraseBindings>> {
+        self.backstore.data_tier_passphrase_bindings()
+    }
+
     fn avail_actions(&self) -> ActionAvailability {
         self.action_avail.clone()
     }
@@ -1117,6 +1801,61 @@ impl Pool for StratPool {
         self.thin_pool.out_of_alloc_space()
     }
 
+    fn discard_passdown(&self) -> bool {
+        self.thin_pool.discard_passdown()
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn set_discard_passdown(&mut self, pool_name: &str, enabled: bool) -> StratisResult<bool> {
+        let changed = self.thin_pool.set_discard_passdown(enabled)?;
+        if changed {
+            self.write_metadata(pool_name)?;
+        }
+        Ok(changed)
+    }
+
+    fn default_fs_extend_config(&self) -> FsExtendConfig {
+        self.thin_pool.default_fs_extend_config()
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn set_default_fs_extend_config(
+        &mut self,
+        pool_name: &str,
+        config: FsExtendConfig,
+    ) -> StratisResult<bool> {
+        validate_fs_extend_config(&config)?;
+        if self.thin_pool.set_default_fs_extend_config(config) {
+            self.write_metadata(pool_name)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn data_block_size(&self) -> Sectors {
+        self.thin_pool.data_block_size()
+    }
+
+    fn thin_pool_extend_policy(&self) -> ThinPoolExtendPolicy {
+        self.thin_pool.extend_policy()
+    }
+
+    #[pool_mutating_action("NoRequests")]
+    fn set_thin_pool_extend_policy(
+        &mut self,
+        pool_name: &str,
+        policy: ThinPoolExtendPolicy,
+    ) -> StratisResult<bool> {
+        validate_thin_pool_extend_policy(&policy, self.thin_pool.data_block_size())?;
+        if self.thin_pool.set_extend_policy(policy)? {
+            self.write_metadata(pool_name)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
     #[pool_mutating_action("NoRequests")]
     fn grow_physical(
         &mut self,
@@ -1176,9 +1915,10 @@ impl<'a> DumpState<'a> for StratPool {
 mod tests {
     use std::{
         fs::OpenOptions,
-        io::{BufWriter, Read, Write},
+        io::{BufWriter, Read, Seek, Write},
     };
 
+    use chrono::Duration;
     use nix::mount::{mount, umount, MsFlags};
 
     use devicemapper::{Bytes, IEC, SECTOR_SIZE};
@@ -1186,11 +1926,16 @@ mod tests {
     use crate::engine::{
         engine::{BlockDev, Filesystem},
         strat_engine::{
+            backstore::replace_progress,
             cmd::udev_settle,
             tests::{loopbacked, real},
-            thinpool::ThinPoolStatusDigest,
+            thinpool::{fs_trim, ThinPoolStatusDigest},
+            udev::{block_device_apply, get_udev_property},
+        },
+        types::{
+            CacheMode, DevicePath, EngineAction, FilesystemType, FsCreateOptions,
+            FsExtendIncrement, MkfsOptions, PoolIdentifier, ThinPoolConfig,
         },
-        types::{EngineAction, PoolIdentifier},
         Engine, StratEngine,
     };
 
@@ -1233,14 +1978,15 @@ mod tests {
         stratis_devices.error_on_not_empty().unwrap();
 
         let name = "stratis-test-pool";
-        let (uuid, mut pool) = StratPool::initialize(name, unowned_devices2, None).unwrap();
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices2, PoolCreateOptions::default()).unwrap();
         invariant(&pool, name);
 
         let metadata1 = pool.record(name);
         assert_matches!(metadata1.backstore.cache_tier, None);
 
         let (_, fs_uuid, _) = pool
-            .create_filesystems(name, uuid, &[("stratis-filesystem", None)])
+            .create_filesystems(name, uuid, &[FilesystemSpec::new("stratis-filesystem")])
             .unwrap()
             .changed()
             .and_then(|mut fs| fs.pop())
@@ -1272,7 +2018,8 @@ mod tests {
                 .unwrap();
         }
 
-        pool.init_cache(uuid, name, paths1, true).unwrap();
+        pool.init_cache(uuid, name, paths1, &CacheConfig::default(), true)
+            .unwrap();
         invariant(&pool, name);
 
         let metadata2 = pool.record(name);
@@ -1320,10 +2067,12 @@ mod tests {
         stratis_devices.error_on_not_empty().unwrap();
 
         let name = "stratis-test-pool";
-        let (uuid, mut pool) = StratPool::initialize(name, unowned_devices, None).unwrap();
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, PoolCreateOptions::default()).unwrap();
         invariant(&pool, name);
 
-        pool.init_cache(uuid, name, cache_path, true).unwrap();
+        pool.init_cache(uuid, name, cache_path, &CacheConfig::default(), true)
+            .unwrap();
         invariant(&pool, name);
 
         pool.add_blockdevs(uuid, name, data_paths, BlockDevTier::Data)
@@ -1348,205 +2097,215 @@ mod tests {
         );
     }
 
-    /// Verify that adding additional blockdevs will cause a pool that is
-    /// out of space to be extended.
-    fn test_add_datadevs(paths: &[&Path]) {
+    /// Verify that removing the cache causes metadata to be updated and the
+    /// cache devices to be released. Verify that data written while the cache
+    /// was in use can be read after the cache is removed.
+    fn test_remove_cache(paths: &[&Path]) {
         assert!(paths.len() > 1);
 
-        let (paths1, paths2) = paths.split_at(1);
+        let (cache_paths, data_paths) = paths.split_at(1);
 
-        let devices1 = ProcessedPathInfos::try_from(paths1).unwrap();
-        let (stratis_devices, unowned_devices1) = devices1.unpack();
+        let devices = ProcessedPathInfos::try_from(data_paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
         stratis_devices.error_on_not_empty().unwrap();
 
         let name = "stratis-test-pool";
-        let (pool_uuid, mut pool) = StratPool::initialize(name, unowned_devices1, None).unwrap();
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, PoolCreateOptions::default()).unwrap();
+        invariant(&pool, name);
+
+        assert_matches!(
+            pool.remove_cache(uuid, name).map(|a| a.is_changed()),
+            Ok(false)
+        );
+
+        let cache_uuids = pool
+            .init_cache(uuid, name, cache_paths, &CacheConfig::default(), true)
+            .unwrap()
+            .changed()
+            .unwrap();
         invariant(&pool, name);
 
-        let fs_name = "stratis_test_filesystem";
         let (_, fs_uuid, _) = pool
-            .create_filesystems(name, pool_uuid, &[(fs_name, None)])
+            .create_filesystems(name, uuid, &[FilesystemSpec::new("stratis-filesystem")])
             .unwrap()
             .changed()
             .and_then(|mut fs| fs.pop())
-            .expect("just created one");
-
-        let devnode = pool.get_filesystem(fs_uuid).unwrap().1.devnode();
+            .unwrap();
+        invariant(&pool, name);
 
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        let new_file = tmp_dir.path().join("stratis_test.txt");
+        let bytestring = b"some bytes";
         {
-            let buffer_length = IEC::Mi;
-            let mut f = BufWriter::with_capacity(
-                convert_test!(buffer_length, u64, usize),
-                OpenOptions::new().write(true).open(devnode).unwrap(),
-            );
+            let (_, fs) = pool.get_filesystem(fs_uuid).unwrap();
+            mount(
+                Some(&fs.devnode()),
+                tmp_dir.path(),
+                Some("xfs"),
+                MsFlags::empty(),
+                None as Option<&str>,
+            )
+            .unwrap();
+            let mut f = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(&new_file)
+                .unwrap();
+            f.write_all(bytestring).unwrap();
+            f.sync_all().unwrap();
+        }
 
-            let buf = &[1u8; SECTOR_SIZE];
+        assert_eq!(
+            pool.remove_cache(uuid, name).unwrap().changed(),
+            Some(cache_uuids)
+        );
+        invariant(&pool, name);
 
-            let mut amount_written = Sectors(0);
-            let buffer_length = Bytes::from(buffer_length).sectors();
-            while matches!(pool.thin_pool.state(), Some(ThinPoolStatusDigest::Good)) {
-                f.write_all(buf).unwrap();
-                amount_written += Sectors(1);
-                // Run check roughly every time the buffer is cleared.
-                // Running it more often is pointless as the pool is guaranteed
-                // not to see any effects unless the buffer is cleared.
-                if amount_written % buffer_length == Sectors(1) {
-                    pool.event_on(pool_uuid, &Name::new(name.to_string()))
-                        .unwrap();
-                }
-            }
+        assert!(!pool.has_cache());
+        let metadata = pool.record(name);
+        assert_matches!(metadata.backstore.cache_tier, None);
 
-            pool.add_blockdevs(pool_uuid, name, paths2, BlockDevTier::Data)
-                .unwrap();
+        udev_settle().unwrap();
+        let devices = ProcessedPathInfos::try_from(cache_paths).unwrap();
+        let (stratis_devices, _) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
 
-            let pool_diff = pool
-                .event_on(pool_uuid, &Name::new(name.to_string()))
+        let mut buf = [0u8; 10];
+        {
+            OpenOptions::new()
+                .read(true)
+                .open(&new_file)
+                .unwrap()
+                .read_exact(&mut buf)
                 .unwrap();
-
-            assert!(pool_diff.thin_pool.allocated_size.is_changed());
-
-            match pool.thin_pool.state() {
-                Some(ThinPoolStatusDigest::Good) => (),
-                _ => panic!("thin pool status should be back to working"),
-            }
         }
+        assert_eq!(&buf, bytestring);
+        umount(tmp_dir.path()).unwrap();
+        pool.teardown().unwrap();
     }
 
     #[test]
-    fn loop_test_add_datadevs() {
+    fn loop_test_remove_cache() {
         loopbacked::test_with_spec(
-            &loopbacked::DeviceLimits::Range(2, 3, Some(Bytes::from(IEC::Gi * 4).sectors())),
-            test_add_datadevs,
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_remove_cache,
         );
     }
 
     #[test]
-    fn real_test_add_datadevs() {
+    fn real_test_remove_cache() {
         real::test_with_spec(
-            &real::DeviceLimits::AtLeast(
-                2,
-                Some(Bytes::from(IEC::Gi * 2).sectors()),
-                Some(Bytes::from(IEC::Gi * 4).sectors()),
-            ),
-            test_add_datadevs,
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_remove_cache,
         );
     }
 
-    /// Test that rollback errors are properly detected an maintenance mode
-    /// is set accordingly.
-    fn test_maintenance_mode(paths: &[&Path]) {
+    /// Verify that the cache mode and policy chosen when the cache is
+    /// initialized are recorded in the metadata, and that changing them
+    /// updates the metadata and the active cache device.
+    fn test_cache_config(paths: &[&Path]) {
         assert!(paths.len() > 1);
 
-        let name = "stratis-test-pool";
+        let (cache_paths, data_paths) = paths.split_at(1);
 
-        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let devices = ProcessedPathInfos::try_from(data_paths).unwrap();
         let (stratis_devices, unowned_devices) = devices.unpack();
         stratis_devices.error_on_not_empty().unwrap();
 
-        let (_, mut pool) = StratPool::initialize(name, unowned_devices, None).unwrap();
+        let name = "stratis-test-pool";
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, PoolCreateOptions::default()).unwrap();
         invariant(&pool, name);
 
-        assert_eq!(pool.action_avail, ActionAvailability::Full);
-        assert!(pool.return_rollback_failure().is_err());
-        assert_eq!(pool.action_avail, ActionAvailability::NoRequests);
+        assert_eq!(pool.cache_config(), None);
+        assert_matches!(pool.cache_stats(), Ok(None));
 
-        pool.destroy().unwrap();
-        udev_settle().unwrap();
+        let writeback = CacheConfig {
+            mode: CacheMode::Writeback,
+            policy: "smq".to_string(),
+            policy_args: vec![("migration_threshold".to_string(), "4096".to_string())],
+        };
+        pool.init_cache(uuid, name, cache_paths, &writeback, true)
+            .unwrap();
+        invariant(&pool, name);
 
-        let name = "stratis-test-pool";
+        assert_eq!(pool.cache_config(), Some(writeback.clone()));
+        assert_eq!(
+            pool.record(name)
+                .backstore
+                .cache_tier
+                .and_then(|cache_tier| cache_tier.config),
+            Some(writeback.clone())
+        );
+        assert_matches!(pool.cache_stats(), Ok(Some(_)));
 
-        let devices = ProcessedPathInfos::try_from(paths).unwrap();
-        let (stratis_devices, unowned_devices) = devices.unpack();
-        stratis_devices.error_on_not_empty().unwrap();
+        assert_matches!(pool.set_cache_config(name, writeback), Ok(false));
 
-        let (_, mut pool) = StratPool::initialize(name, unowned_devices, None).unwrap();
+        let passthrough = CacheConfig {
+            mode: CacheMode::Passthrough,
+            ..CacheConfig::default()
+        };
+        assert_matches!(pool.set_cache_config(name, passthrough.clone()), Ok(true));
         invariant(&pool, name);
+        assert_eq!(pool.cache_config(), Some(passthrough.clone()));
+        assert_eq!(
+            pool.record(name)
+                .backstore
+                .cache_tier
+                .and_then(|cache_tier| cache_tier.config),
+            Some(passthrough)
+        );
+        assert_matches!(pool.cache_stats(), Ok(Some(stats)) if stats.dirty == 0);
 
-        assert_eq!(pool.action_avail, ActionAvailability::Full);
-        assert!(pool.return_rollback_failure_chain().is_err());
-        assert_eq!(pool.action_avail, ActionAvailability::NoRequests);
+        pool.teardown().unwrap();
     }
 
     #[test]
-    fn loop_test_maintenance_mode() {
+    fn loop_test_cache_config() {
         loopbacked::test_with_spec(
-            &loopbacked::DeviceLimits::Range(2, 3, Some(Bytes::from(IEC::Gi * 4).sectors())),
-            test_maintenance_mode,
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_cache_config,
         );
     }
 
     #[test]
-    fn real_test_mainenance_mode() {
+    fn real_test_cache_config() {
         real::test_with_spec(
-            &real::DeviceLimits::AtLeast(
-                2,
-                Some(Bytes::from(IEC::Gi * 2).sectors()),
-                Some(Bytes::from(IEC::Gi * 4).sectors()),
-            ),
-            test_maintenance_mode,
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_cache_config,
         );
     }
 
-    /// Test overprovisioning mode disabled and enabled assuring that the appropriate
-    /// checks and behavior are in place.
-    fn test_overprov(paths: &[&Path]) {
-        assert!(paths.len() == 1);
+    /// Verify that removing a cache device shrinks the cache and releases the
+    /// device, and that data written while the cache was in use can be read
+    /// afterwards.
+    fn test_remove_cachedevs(paths: &[&Path]) {
+        assert!(paths.len() > 2);
 
-        let pool_name = "pool";
+        let (cache_paths, data_paths) = paths.split_at(2);
 
-        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let devices = ProcessedPathInfos::try_from(data_paths).unwrap();
         let (stratis_devices, unowned_devices) = devices.unpack();
         stratis_devices.error_on_not_empty().unwrap();
 
-        let (pool_uuid, mut pool) =
-            StratPool::initialize(pool_name, unowned_devices, None).unwrap();
-
-        let (_, fs_uuid, _) = pool
-            .create_filesystems(
-                pool_name,
-                pool_uuid,
-                &[(
-                    "stratis_test_filesystem",
-                    Some(pool.backstore.datatier_usable_size().bytes() * 2u64),
-                )],
-            )
-            .unwrap()
-            .changed()
-            .unwrap()
-            .pop()
-            .unwrap();
-        udev_settle().unwrap();
-        assert!(pool
-            .set_overprov_mode(&Name::new(pool_name.to_string()), false)
-            .is_err());
-        pool.destroy_filesystems(pool_name, &[fs_uuid]).unwrap();
+        let name = "stratis-test-pool";
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, PoolCreateOptions::default()).unwrap();
+        invariant(&pool, name);
 
-        pool.set_overprov_mode(&Name::new(pool_name.to_string()), false)
+        pool.init_cache(uuid, name, cache_paths, &CacheConfig::default(), true)
             .unwrap();
-        assert!(pool
-            .create_filesystems(
-                pool_name,
-                pool_uuid,
-                &[(
-                    "stratis_test_filesystem",
-                    Some(pool.backstore.datatier_usable_size().bytes() * 2u64)
-                )],
-            )
-            .is_err());
+        invariant(&pool, name);
 
-        let mut initial_fs_size = pool.backstore.datatier_usable_size().bytes() * 2u64 / 3u64;
-        initial_fs_size = initial_fs_size.sectors().bytes();
-        let half_init_size = initial_fs_size / 2u64 + Bytes(1);
         let (_, fs_uuid, _) = pool
-            .create_filesystems(
-                pool_name,
-                pool_uuid,
-                &[("stratis_test_filesystem", Some(initial_fs_size))],
-            )
+            .create_filesystems(name, uuid, &[FilesystemSpec::new("stratis-filesystem")])
             .unwrap()
             .changed()
-            .unwrap()
-            .pop()
+            .and_then(|mut fs| fs.pop())
             .unwrap();
 
         let tmp_dir = tempfile::Builder::new()
@@ -1554,8 +2313,7 @@ mod tests {
             .tempdir()
             .unwrap();
         let new_file = tmp_dir.path().join("stratis_test.txt");
-        let sector = &[0; 512];
-
+        let bytestring = b"some bytes";
         {
             let (_, fs) = pool.get_filesystem(fs_uuid).unwrap();
             mount(
@@ -1566,38 +2324,1343 @@ mod tests {
                 None as Option<&str>,
             )
             .unwrap();
-        }
+            let mut f = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(&new_file)
+                .unwrap();
+            f.write_all(bytestring).unwrap();
+            f.sync_all().unwrap();
+        }
+
+        let cache_uuids = pool
+            .backstore
+            .cachedevs()
+            .into_iter()
+            .map(|(uuid, _)| uuid)
+            .collect::<Vec<_>>();
+        assert!(pool
+            .remove_blockdevs(uuid, name, &cache_uuids, BlockDevTier::Cache)
+            .is_err());
+
+        // The meta sub-device is allocated from the first cache device.
+        let to_remove = cache_uuids[cache_uuids.len() - 1];
+        assert_eq!(
+            pool.remove_blockdevs(uuid, name, &[to_remove], BlockDevTier::Cache)
+                .unwrap()
+                .changed(),
+            Some(vec![to_remove])
+        );
+        invariant(&pool, name);
+
+        assert!(pool.get_blockdev(to_remove).is_none());
+        assert_eq!(pool.backstore.cachedevs().len(), cache_uuids.len() - 1);
+
+        let mut buf = [0u8; 10];
+        {
+            OpenOptions::new()
+                .read(true)
+                .open(&new_file)
+                .unwrap()
+                .read_exact(&mut buf)
+                .unwrap();
+        }
+        assert_eq!(&buf, bytestring);
+        umount(tmp_dir.path()).unwrap();
+        pool.teardown().unwrap();
+    }
+
+    #[test]
+    fn loop_test_remove_cachedevs() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(3, 4, None),
+            test_remove_cachedevs,
+        );
+    }
+
+    #[test]
+    fn real_test_remove_cachedevs() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(3, None, None),
+            test_remove_cachedevs,
+        );
+    }
+
+    /// Verify that adding additional blockdevs will cause a pool that is
+    /// out of space to be extended.
+    fn test_add_datadevs(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let (paths1, paths2) = paths.split_at(1);
+
+        let devices1 = ProcessedPathInfos::try_from(paths1).unwrap();
+        let (stratis_devices, unowned_devices1) = devices1.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let name = "stratis-test-pool";
+        let (pool_uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices1, PoolCreateOptions::default()).unwrap();
+        invariant(&pool, name);
+
+        let fs_name = "stratis_test_filesystem";
+        let (_, fs_uuid, _) = pool
+            .create_filesystems(name, pool_uuid, &[FilesystemSpec::new(fs_name)])
+            .unwrap()
+            .changed()
+            .and_then(|mut fs| fs.pop())
+            .expect("just created one");
+
+        let devnode = pool.get_filesystem(fs_uuid).unwrap().1.devnode();
+
+        {
+            let buffer_length = IEC::Mi;
+            let mut f = BufWriter::with_capacity(
+                convert_test!(buffer_length, u64, usize),
+                OpenOptions::new().write(true).open(devnode).unwrap(),
+            );
+
+            let buf = &[1u8; SECTOR_SIZE];
+
+            let mut amount_written = Sectors(0);
+            let buffer_length = Bytes::from(buffer_length).sectors();
+            while matches!(pool.thin_pool.state(), Some(ThinPoolStatusDigest::Good)) {
+                f.write_all(buf).unwrap();
+                amount_written += Sectors(1);
+                // Run check roughly every time the buffer is cleared.
+                // Running it more often is pointless as the pool is guaranteed
+                // not to see any effects unless the buffer is cleared.
+                if amount_written % buffer_length == Sectors(1) {
+                    pool.event_on(pool_uuid, &Name::new(name.to_string()))
+                        .unwrap();
+                }
+            }
+
+            pool.add_blockdevs(pool_uuid, name, paths2, BlockDevTier::Data)
+                .unwrap();
+
+            let pool_diff = pool
+                .event_on(pool_uuid, &Name::new(name.to_string()))
+                .unwrap();
+
+            assert!(pool_diff.thin_pool.allocated_size.is_changed());
+
+            match pool.thin_pool.state() {
+                Some(ThinPoolStatusDigest::Good) => (),
+                _ => panic!("thin pool status should be back to working"),
+            }
+        }
+    }
+
+    #[test]
+    fn loop_test_add_datadevs() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, Some(Bytes::from(IEC::Gi * 4).sectors())),
+            test_add_datadevs,
+        );
+    }
+
+    #[test]
+    fn real_test_add_datadevs() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(
+                2,
+                Some(Bytes::from(IEC::Gi * 2).sectors()),
+                Some(Bytes::from(IEC::Gi * 4).sectors()),
+            ),
+            test_add_datadevs,
+        );
+    }
+
+    /// Verify that removing a data device moves the data stored on it to the
+    /// remaining devices and that data written before the device was removed
+    /// can be read afterwards.
+    fn test_remove_datadevs(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let (paths1, paths2) = paths.split_at(1);
+
+        let devices1 = ProcessedPathInfos::try_from(paths1).unwrap();
+        let (stratis_devices, unowned_devices1) = devices1.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let name = "stratis-test-pool";
+        let (pool_uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices1, PoolCreateOptions::default()).unwrap();
+        invariant(&pool, name);
+
+        let (dev_uuid, _) = pool.backstore.datadevs()[0];
+
+        let (_, fs_uuid, _) = pool
+            .create_filesystems(
+                name,
+                pool_uuid,
+                &[FilesystemSpec::new("stratis-filesystem")],
+            )
+            .unwrap()
+            .changed()
+            .and_then(|mut fs| fs.pop())
+            .unwrap();
+
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        let new_file = tmp_dir.path().join("stratis_test.txt");
+        let bytestring = b"some bytes";
+        let devnode = pool.get_filesystem(fs_uuid).unwrap().1.devnode();
+        mount(
+            Some(&devnode),
+            tmp_dir.path(),
+            Some("xfs"),
+            MsFlags::empty(),
+            None as Option<&str>,
+        )
+        .unwrap();
+        {
+            let mut f = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(&new_file)
+                .unwrap();
+            f.write_all(bytestring).unwrap();
+            f.sync_all().unwrap();
+        }
+
+        pool.add_blockdevs(pool_uuid, name, paths2, BlockDevTier::Data)
+            .unwrap();
+        invariant(&pool, name);
+
+        let removed = pool
+            .remove_blockdevs(pool_uuid, name, &[dev_uuid], BlockDevTier::Data)
+            .unwrap()
+            .changed()
+            .unwrap();
+        assert_eq!(removed, vec![dev_uuid]);
+        invariant(&pool, name);
+
+        assert!(pool.get_blockdev(dev_uuid).is_none());
+        assert!(!pool
+            .record(&Name::new(name.into()))
+            .backstore
+            .data_tier
+            .blockdev
+            .allocs[0]
+            .iter()
+            .any(|seg| seg.parent == dev_uuid));
+
+        umount(tmp_dir.path()).unwrap();
+        mount(
+            Some(&devnode),
+            tmp_dir.path(),
+            Some("xfs"),
+            MsFlags::empty(),
+            None as Option<&str>,
+        )
+        .unwrap();
+
+        let mut buf = [0u8; 10];
+        OpenOptions::new()
+            .read(true)
+            .open(&new_file)
+            .unwrap()
+            .read_exact(&mut buf)
+            .unwrap();
+        assert_eq!(&buf, bytestring);
+
+        umount(tmp_dir.path()).unwrap();
+        pool.teardown().unwrap();
+    }
+
+    #[test]
+    fn loop_test_remove_datadevs() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_remove_datadevs,
+        );
+    }
+
+    #[test]
+    fn real_test_remove_datadevs() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_remove_datadevs,
+        );
+    }
+
+    /// Verify that replacing a data device moves the data stored on it to the
+    /// new device and that data written before the device was replaced can
+    /// be read afterwards.
+    fn test_replace_blockdev(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let (paths1, paths2) = paths.split_at(1);
+
+        let devices1 = ProcessedPathInfos::try_from(paths1).unwrap();
+        let (stratis_devices, unowned_devices1) = devices1.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let name = "stratis-test-pool";
+        let (pool_uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices1, PoolCreateOptions::default()).unwrap();
+        invariant(&pool, name);
+
+        let (old_uuid, _) = pool.backstore.datadevs()[0];
+
+        let (_, fs_uuid, _) = pool
+            .create_filesystems(
+                name,
+                pool_uuid,
+                &[FilesystemSpec::new("stratis-filesystem")],
+            )
+            .unwrap()
+            .changed()
+            .and_then(|mut fs| fs.pop())
+            .unwrap();
+
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        let new_file = tmp_dir.path().join("stratis_test.txt");
+        let bytestring = b"some bytes";
+        let devnode = pool.get_filesystem(fs_uuid).unwrap().1.devnode();
+        mount(
+            Some(&devnode),
+            tmp_dir.path(),
+            Some("xfs"),
+            MsFlags::empty(),
+            None as Option<&str>,
+        )
+        .unwrap();
+        {
+            let mut f = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(&new_file)
+                .unwrap();
+            f.write_all(bytestring).unwrap();
+            f.sync_all().unwrap();
+        }
+
+        let new_uuid = pool
+            .replace_blockdev(name, pool_uuid, old_uuid, paths2[0])
+            .unwrap()
+            .changed()
+            .unwrap();
+        invariant(&pool, name);
+
+        assert!(pool.get_blockdev(old_uuid).is_none());
+        assert_eq!(
+            pool.blockdevs()
+                .into_iter()
+                .map(|(uuid, _, _)| uuid)
+                .collect::<Vec<_>>(),
+            vec![new_uuid]
+        );
+        assert!(replace_progress(pool_uuid).is_none());
+
+        umount(tmp_dir.path()).unwrap();
+        mount(
+            Some(&devnode),
+            tmp_dir.path(),
+            Some("xfs"),
+            MsFlags::empty(),
+            None as Option<&str>,
+        )
+        .unwrap();
+
+        let mut buf = [0u8; 10];
+        OpenOptions::new()
+            .read(true)
+            .open(&new_file)
+            .unwrap()
+            .read_exact(&mut buf)
+            .unwrap();
+        assert_eq!(&buf, bytestring);
+
+        umount(tmp_dir.path()).unwrap();
+        pool.teardown().unwrap();
+    }
+
+    #[test]
+    fn loop_test_replace_blockdev() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Exactly(2, None),
+            test_replace_blockdev,
+        );
+    }
+
+    #[test]
+    fn real_test_replace_blockdev() {
+        real::test_with_spec(
+            &real::DeviceLimits::Exactly(2, None, None),
+            test_replace_blockdev,
+        );
+    }
+
+    /// Test that rollback errors are properly detected an maintenance mode
+    /// is set accordingly.
+    fn test_maintenance_mode(paths: &[&Path]) {
+        assert!(paths.len() > 1);
+
+        let name = "stratis-test-pool";
+
+        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let (_, mut pool) =
+            StratPool::initialize(name, unowned_devices, PoolCreateOptions::default()).unwrap();
+        invariant(&pool, name);
+
+        assert_eq!(pool.action_avail, ActionAvailability::Full);
+        assert!(pool.return_rollback_failure().is_err());
+        assert_eq!(pool.action_avail, ActionAvailability::NoRequests);
+
+        pool.destroy().unwrap();
+        udev_settle().unwrap();
+
+        let name = "stratis-test-pool";
+
+        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let (_, mut pool) =
+            StratPool::initialize(name, unowned_devices, PoolCreateOptions::default()).unwrap();
+        invariant(&pool, name);
+
+        assert_eq!(pool.action_avail, ActionAvailability::Full);
+        assert!(pool.return_rollback_failure_chain().is_err());
+        assert_eq!(pool.action_avail, ActionAvailability::NoRequests);
+    }
+
+    #[test]
+    fn loop_test_maintenance_mode() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, Some(Bytes::from(IEC::Gi * 4).sectors())),
+            test_maintenance_mode,
+        );
+    }
+
+    #[test]
+    fn real_test_mainenance_mode() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(
+                2,
+                Some(Bytes::from(IEC::Gi * 2).sectors()),
+                Some(Bytes::from(IEC::Gi * 4).sectors()),
+            ),
+            test_maintenance_mode,
+        );
+    }
+
+    /// Test overprovisioning mode disabled and enabled assuring that the appropriate
+    /// checks and behavior are in place.
+    fn test_overprov(paths: &[&Path]) {
+        assert!(paths.len() == 1);
+
+        let pool_name = "pool";
+
+        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let (pool_uuid, mut pool) =
+            StratPool::initialize(pool_name, unowned_devices, PoolCreateOptions::default())
+                .unwrap();
+
+        let (_, fs_uuid, _) = pool
+            .create_filesystems(
+                pool_name,
+                pool_uuid,
+                &[FilesystemSpec {
+                    name: "stratis_test_filesystem",
+                    size: Some(pool.backstore.datatier_usable_size().bytes() * 2u64),
+                    ..Default::default()
+                }],
+            )
+            .unwrap()
+            .changed()
+            .unwrap()
+            .pop()
+            .unwrap();
+        udev_settle().unwrap();
+        assert!(pool
+            .set_overprov_mode(&Name::new(pool_name.to_string()), false)
+            .is_err());
+        pool.destroy_filesystems(pool_name, &[fs_uuid]).unwrap();
+
+        pool.set_overprov_mode(&Name::new(pool_name.to_string()), false)
+            .unwrap();
+        assert!(pool
+            .create_filesystems(
+                pool_name,
+                pool_uuid,
+                &[FilesystemSpec {
+                    name: "stratis_test_filesystem",
+                    size: Some(pool.backstore.datatier_usable_size().bytes() * 2u64),
+                    ..Default::default()
+                }],
+            )
+            .is_err());
+
+        let mut initial_fs_size = pool.backstore.datatier_usable_size().bytes() * 2u64 / 3u64;
+        initial_fs_size = initial_fs_size.sectors().bytes();
+        let half_init_size = initial_fs_size / 2u64 + Bytes(1);
+        let (_, fs_uuid, _) = pool
+            .create_filesystems(
+                pool_name,
+                pool_uuid,
+                &[FilesystemSpec {
+                    name: "stratis_test_filesystem",
+                    size: Some(initial_fs_size),
+                    ..Default::default()
+                }],
+            )
+            .unwrap()
+            .changed()
+            .unwrap()
+            .pop()
+            .unwrap();
+
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        let new_file = tmp_dir.path().join("stratis_test.txt");
+        let sector = &[0; 512];
+
+        {
+            let (_, fs) = pool.get_filesystem(fs_uuid).unwrap();
+            mount(
+                Some(&fs.devnode()),
+                tmp_dir.path(),
+                Some("xfs"),
+                MsFlags::empty(),
+                None as Option<&str>,
+            )
+            .unwrap();
+        }
+
+        let mut f = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(new_file)
+            .unwrap();
+        let mut written = Sectors(0);
+        while written.bytes() < half_init_size {
+            f.write_all(sector).unwrap();
+            written += Sectors(1);
+        }
+        let diffs = pool.fs_event_on(pool_uuid).unwrap();
+        assert!(diffs.get(&fs_uuid).unwrap().size.is_changed());
+
+        let (_, fs) = pool.get_filesystem(fs_uuid).unwrap();
+        assert!(fs.thindev_size() < initial_fs_size.sectors() * 2u64);
+    }
+
+    #[test]
+    fn loop_test_overprov() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Exactly(1, Some(Sectors(10 * IEC::Mi))),
+            test_overprov,
+        );
+    }
+
+    #[test]
+    fn real_test_overprov() {
+        real::test_with_spec(
+            &real::DeviceLimits::Exactly(1, Some(Sectors(10 * IEC::Mi)), None),
+            test_overprov,
+        );
+    }
+
+    /// Verify that the discard passdown setting is recorded in the thin pool
+    /// feature arguments and that a mounted filesystem can be trimmed with
+    /// discard passdown enabled.
+    fn test_discard_passdown(paths: &[&Path]) {
+        let name = "stratis-test-pool";
+
+        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, PoolCreateOptions::default()).unwrap();
+        invariant(&pool, name);
+
+        assert!(!pool.discard_passdown());
+        assert!(pool.set_discard_passdown(name, true).unwrap());
+        assert!(!pool.set_discard_passdown(name, true).unwrap());
+        assert!(pool.discard_passdown());
+        assert!(!pool
+            .record(name)
+            .thinpool_dev
+            .feature_args
+            .unwrap()
+            .contains("no_discard_passdown"));
+        invariant(&pool, name);
+
+        let (_, fs_uuid, _) = pool
+            .create_filesystems(name, uuid, &[FilesystemSpec::new("stratis-filesystem")])
+            .unwrap()
+            .changed()
+            .and_then(|mut fs| fs.pop())
+            .unwrap();
+
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        {
+            let (_, fs) = pool.get_filesystem(fs_uuid).unwrap();
+            mount(
+                Some(&fs.devnode()),
+                tmp_dir.path(),
+                Some("xfs"),
+                MsFlags::empty(),
+                None as Option<&str>,
+            )
+            .unwrap();
+            assert_eq!(fs.mount_points().unwrap(), vec![tmp_dir.path().to_owned()]);
+        }
+        fs_trim(tmp_dir.path()).unwrap();
+        umount(tmp_dir.path()).unwrap();
+
+        assert!(pool.set_discard_passdown(name, false).unwrap());
+        assert!(pool
+            .record(name)
+            .thinpool_dev
+            .feature_args
+            .unwrap()
+            .contains("no_discard_passdown"));
+        invariant(&pool, name);
+    }
+
+    #[test]
+    fn loop_test_discard_passdown() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(1, 3, None),
+            test_discard_passdown,
+        );
+    }
+
+    #[test]
+    fn real_test_discard_passdown() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(1, None, None),
+            test_discard_passdown,
+        );
+    }
+
+    /// Verify that a filesystem is extended according to the default extension
+    /// configuration of its pool unless it has a configuration of its own, and
+    /// that both configurations are recorded in the metadata.
+    fn test_fs_extend_config(paths: &[&Path]) {
+        let name = "stratis-test-pool";
+
+        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, PoolCreateOptions::default()).unwrap();
+        invariant(&pool, name);
+
+        assert_eq!(pool.default_fs_extend_config(), FsExtendConfig::default());
+        let increment = Bytes::from(256 * IEC::Mi).sectors();
+        let config = FsExtendConfig {
+            enabled: true,
+            threshold: 10,
+            increment: FsExtendIncrement::Absolute(increment),
+        };
+        assert!(pool
+            .set_default_fs_extend_config(
+                name,
+                FsExtendConfig {
+                    threshold: 100,
+                    ..config
+                }
+            )
+            .is_err());
+        assert!(pool.set_default_fs_extend_config(name, config).unwrap());
+        assert!(!pool.set_default_fs_extend_config(name, config).unwrap());
+        assert_eq!(
+            pool.record(name).thinpool_dev.fs_extend_config,
+            Some(config)
+        );
+        invariant(&pool, name);
+
+        let fs_size = Bytes::from(IEC::Gi).sectors();
+        let (_, fs_uuid, _) = pool
+            .create_filesystems(
+                name,
+                uuid,
+                &[FilesystemSpec {
+                    name: "stratis-filesystem",
+                    size: Some(fs_size.bytes()),
+                    ..Default::default()
+                }],
+            )
+            .unwrap()
+            .changed()
+            .and_then(|mut fs| fs.pop())
+            .unwrap();
+
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        {
+            let (_, fs) = pool.get_filesystem(fs_uuid).unwrap();
+            mount(
+                Some(&fs.devnode()),
+                tmp_dir.path(),
+                Some("xfs"),
+                MsFlags::empty(),
+                None as Option<&str>,
+            )
+            .unwrap();
+        }
+        {
+            let mut f = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(tmp_dir.path().join("stratis_test.txt"))
+                .unwrap();
+            f.write_all(&vec![1u8; convert_test!(200 * IEC::Mi, u64, usize)])
+                .unwrap();
+            f.sync_all().unwrap();
+        }
+
+        pool.fs_event_on(uuid).unwrap();
+        assert_eq!(
+            pool.get_filesystem(fs_uuid).unwrap().1.size(),
+            (fs_size + increment).bytes()
+        );
+
+        let disabled = FsExtendConfig {
+            enabled: false,
+            ..config
+        };
+        assert_matches!(
+            pool.set_fs_extend_config(name, fs_uuid, Some(disabled)),
+            Ok(RenameAction::Renamed(_))
+        );
+        assert_eq!(
+            pool.get_filesystem(fs_uuid).unwrap().1.extend_config(),
+            Some(disabled)
+        );
+        pool.fs_event_on(uuid).unwrap();
+        assert_eq!(
+            pool.get_filesystem(fs_uuid).unwrap().1.size(),
+            (fs_size + increment).bytes()
+        );
+
+        umount(tmp_dir.path()).unwrap();
+        invariant(&pool, name);
+    }
+
+    #[test]
+    fn loop_test_fs_extend_config() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(1, 3, None),
+            test_fs_extend_config,
+        );
+    }
+
+    #[test]
+    fn real_test_fs_extend_config() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(1, None, None),
+            test_fs_extend_config,
+        );
+    }
+
+    /// Verify that a pool is created with the data block size and thin pool
+    /// extension policy requested, that a change to the policy is recorded
+    /// in the metadata, and that an invalid policy is rejected.
+    fn test_thin_pool_extend_policy(paths: &[&Path]) {
+        let name = "stratis-test-pool";
+
+        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let data_block_size = Bytes::from(512 * IEC::Ki).sectors();
+        let extend_size = Bytes::from(512 * IEC::Mi).sectors();
+        let config = ThinPoolConfig {
+            data_block_size: Some(data_block_size),
+            low_water: None,
+            extend_size: Some(extend_size),
+        };
+        let (_, mut pool) = StratPool::initialize(
+            name,
+            unowned_devices,
+            PoolCreateOptions {
+                thin_pool_config: Some(&config),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        invariant(&pool, name);
+
+        assert_eq!(pool.data_block_size(), data_block_size);
+        let policy = pool.thin_pool_extend_policy();
+        assert_eq!(policy.low_water, DEFAULT_EXTEND_POLICY.low_water);
+        assert_eq!(policy.extend_size, extend_size);
+
+        let record = pool.record(name);
+        assert_eq!(record.thinpool_dev.data_block_size, data_block_size);
+        assert_eq!(record.thinpool_dev.extend_policy, Some(policy));
+        assert_eq!(
+            record
+                .flex_devs
+                .thin_data_dev
+                .iter()
+                .map(|(_, len)| *len)
+                .sum::<Sectors>(),
+            extend_size
+        );
+
+        let new_policy = ThinPoolExtendPolicy {
+            low_water: Bytes::from(128 * IEC::Mi).sectors(),
+            extend_size: Bytes::from(256 * IEC::Mi).sectors(),
+        };
+        assert!(pool
+            .set_thin_pool_extend_policy(
+                name,
+                ThinPoolExtendPolicy {
+                    low_water: Bytes::from(256 * IEC::Ki).sectors(),
+                    ..new_policy
+                }
+            )
+            .is_err());
+        assert!(pool.set_thin_pool_extend_policy(name, new_policy).unwrap());
+        assert!(!pool.set_thin_pool_extend_policy(name, new_policy).unwrap());
+        assert_eq!(pool.thin_pool_extend_policy(), new_policy);
+        assert_eq!(
+            pool.record(name).thinpool_dev.extend_policy,
+            Some(new_policy)
+        );
+        invariant(&pool, name);
+    }
+
+    #[test]
+    fn loop_test_thin_pool_extend_policy() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(1, 3, None),
+            test_thin_pool_extend_policy,
+        );
+    }
+
+    #[test]
+    fn real_test_thin_pool_extend_policy() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(1, None, None),
+            test_thin_pool_extend_policy,
+        );
+    }
+
+    /// Verify that snapshot policies take snapshots when they are due, record
+    /// the policy that took them and the filesystem it belongs to, and
+    /// destroy the snapshots beyond the count to keep.
+    fn test_snapshot_policies(paths: &[&Path]) {
+        let name = "stratis-test-pool";
+
+        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
 
-        let mut f = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(new_file)
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, PoolCreateOptions::default()).unwrap();
+        invariant(&pool, name);
+
+        let (_, fs_uuid, _) = pool
+            .create_filesystems(name, uuid, &[FilesystemSpec::new("stratis-filesystem")])
+            .unwrap()
+            .changed()
+            .and_then(|mut fs| fs.pop())
             .unwrap();
-        let mut written = Sectors(0);
-        while written.bytes() < half_init_size {
-            f.write_all(sector).unwrap();
-            written += Sectors(1);
+
+        let now = Utc::now();
+        assert!(pool
+            .run_snapshot_policies(name, uuid, now)
+            .unwrap()
+            .is_empty());
+
+        let policy = SnapshotPolicy {
+            name: "hourly".to_string(),
+            interval: 3600,
+            keep_count: Some(2),
+            keep_age: None,
+        };
+        assert_matches!(
+            pool.set_snapshot_policies(name, fs_uuid, &[policy]),
+            Ok(RenameAction::Renamed(_))
+        );
+
+        let first = pool.run_snapshot_policies(name, uuid, now).unwrap();
+        assert_eq!(first.created.len(), 1);
+        assert!(first.destroyed.is_empty());
+        let (_, snapshot) = pool.get_filesystem(first.created[0]).unwrap();
+        assert_eq!(snapshot.origin(), Some(fs_uuid));
+        assert_eq!(snapshot.scheduled_by(), Some("hourly"));
+        assert_eq!(snapshot.scheduled_for(), Some(fs_uuid));
+        assert!(snapshot.snapshot_policies().is_empty());
+
+        assert!(pool
+            .run_snapshot_policies(name, uuid, now + Duration::minutes(30))
+            .unwrap()
+            .is_empty());
+
+        let second = pool
+            .run_snapshot_policies(name, uuid, now + Duration::minutes(61))
+            .unwrap();
+        assert_eq!(second.created.len(), 1);
+        assert!(second.destroyed.is_empty());
+
+        let third = pool
+            .run_snapshot_policies(name, uuid, now + Duration::minutes(122))
+            .unwrap();
+        assert_eq!(third.created.len(), 1);
+        assert_eq!(third.destroyed, first.created);
+        assert!(pool.get_filesystem(first.created[0]).is_none());
+        assert_eq!(pool.filesystems().len(), 3);
+        invariant(&pool, name);
+    }
+
+    #[test]
+    fn loop_test_snapshot_policies() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(1, 3, None),
+            test_snapshot_policies,
+        );
+    }
+
+    #[test]
+    fn real_test_snapshot_policies() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(1, None, None),
+            test_snapshot_policies,
+        );
+    }
+
+    /// Verify that an ext4 filesystem can be created, mounted, and
+    /// snapshotted, and that its type is recorded in the metadata and
+    /// inherited by its snapshot.
+    fn test_ext4_filesystem(paths: &[&Path]) {
+        let name = "stratis-test-pool";
+
+        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, PoolCreateOptions::default()).unwrap();
+        invariant(&pool, name);
+
+        let (fs_name, fs_uuid, _) = pool
+            .create_filesystems(
+                name,
+                uuid,
+                &[FilesystemSpec {
+                    name: "stratis-filesystem",
+                    fs_type: Some(FilesystemType::Ext4),
+                    ..Default::default()
+                }],
+            )
+            .unwrap()
+            .changed()
+            .and_then(|mut fs| fs.pop())
+            .unwrap();
+        invariant(&pool, name);
+
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        let new_file = tmp_dir.path().join("stratis_test.txt");
+        {
+            let (_, fs) = pool.get_filesystem(fs_uuid).unwrap();
+            assert_eq!(fs.fs_type(), FilesystemType::Ext4);
+            assert_eq!(
+                fs.record(&Name::new(fs_name.to_string()), fs_uuid).fs_type,
+                Some(FilesystemType::Ext4)
+            );
+            mount(
+                Some(&fs.devnode()),
+                tmp_dir.path(),
+                Some("ext4"),
+                MsFlags::empty(),
+                None as Option<&str>,
+            )
+            .unwrap();
+            OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .open(&new_file)
+                .unwrap()
+                .write_all(b"data")
+                .unwrap();
         }
-        let diffs = pool.fs_event_on(pool_uuid).unwrap();
-        assert!(diffs.get(&fs_uuid).unwrap().size.is_changed());
+        umount(tmp_dir.path()).unwrap();
 
-        let (_, fs) = pool.get_filesystem(fs_uuid).unwrap();
-        assert!(fs.thindev_size() < initial_fs_size.sectors() * 2u64);
+        let snapshot_uuid = match pool
+            .snapshot_filesystem(name, uuid, fs_uuid, "stratis-snapshot")
+            .unwrap()
+        {
+            CreateAction::Created((snapshot_uuid, _)) => snapshot_uuid,
+            CreateAction::Identity => panic!("snapshot should have been created"),
+        };
+        {
+            let (_, snapshot) = pool.get_filesystem(snapshot_uuid).unwrap();
+            assert_eq!(snapshot.fs_type(), FilesystemType::Ext4);
+            mount(
+                Some(&snapshot.devnode()),
+                tmp_dir.path(),
+                Some("ext4"),
+                MsFlags::empty(),
+                None as Option<&str>,
+            )
+            .unwrap();
+            assert!(new_file.exists());
+        }
+        umount(tmp_dir.path()).unwrap();
+        invariant(&pool, name);
     }
 
     #[test]
-    fn loop_test_overprov() {
+    fn loop_test_ext4_filesystem() {
         loopbacked::test_with_spec(
-            &loopbacked::DeviceLimits::Exactly(1, Some(Sectors(10 * IEC::Mi))),
-            test_overprov,
+            &loopbacked::DeviceLimits::Range(1, 3, None),
+            test_ext4_filesystem,
         );
     }
 
     #[test]
-    fn real_test_overprov() {
+    fn real_test_ext4_filesystem() {
         real::test_with_spec(
-            &real::DeviceLimits::Exactly(1, Some(Sectors(10 * IEC::Mi)), None),
-            test_overprov,
+            &real::DeviceLimits::AtLeast(1, None, None),
+            test_ext4_filesystem,
+        );
+    }
+
+    /// Verify that a filesystem created with mkfs options has the requested
+    /// label, and that its recommended mount options are recorded in the
+    /// metadata and inherited by its snapshot.
+    fn test_fs_create_options(paths: &[&Path]) {
+        let name = "stratis-test-pool";
+
+        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, PoolCreateOptions::default()).unwrap();
+        invariant(&pool, name);
+
+        let options = FsCreateOptions {
+            mkfs: MkfsOptions {
+                reflink: Some(true),
+                bigtime: Some(true),
+                inode_size: Some(512),
+                label: Some("database".to_string()),
+            },
+            mount_options: Some("noatime,logbsize=256k".to_string()),
+        };
+        let (fs_name, fs_uuid, _) = pool
+            .create_filesystems(
+                name,
+                uuid,
+                &[FilesystemSpec {
+                    name: "stratis-filesystem",
+                    options: Some(options),
+                    ..Default::default()
+                }],
+            )
+            .unwrap()
+            .changed()
+            .and_then(|mut fs| fs.pop())
+            .unwrap();
+        invariant(&pool, name);
+
+        udev_settle().unwrap();
+        {
+            let (_, fs) = pool.get_filesystem(fs_uuid).unwrap();
+            assert_eq!(fs.mount_options(), Some("noatime,logbsize=256k"));
+            assert_eq!(
+                fs.record(&Name::new(fs_name.to_string()), fs_uuid)
+                    .mount_options,
+                Some("noatime,logbsize=256k".to_string())
+            );
+            assert_eq!(
+                block_device_apply(&DevicePath::new(&fs.devnode()).unwrap(), |dev| {
+                    get_udev_property(dev, "ID_FS_LABEL").map(|l| l.unwrap())
+                })
+                .unwrap()
+                .flatten(),
+                Some("database".to_string())
+            );
+        }
+
+        let snapshot_uuid = match pool
+            .snapshot_filesystem(name, uuid, fs_uuid, "stratis-snapshot")
+            .unwrap()
+        {
+            CreateAction::Created((snapshot_uuid, _)) => snapshot_uuid,
+            CreateAction::Identity => panic!("snapshot should have been created"),
+        };
+        let (_, snapshot) = pool.get_filesystem(snapshot_uuid).unwrap();
+        assert_eq!(snapshot.mount_options(), Some("noatime,logbsize=256k"));
+        invariant(&pool, name);
+    }
+
+    #[test]
+    fn loop_test_fs_create_options() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(1, 3, None),
+            test_fs_create_options,
+        );
+    }
+
+    #[test]
+    fn real_test_fs_create_options() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(1, None, None),
+            test_fs_create_options,
+        );
+    }
+
+    /// Mount the XFS filesystem at devnode, write a file of the given size
+    /// to it and unmount it.
+    fn write_file(devnode: &Path, file_name: &str, size: u64) {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        mount(
+            Some(devnode),
+            tmp_dir.path(),
+            Some("xfs"),
+            MsFlags::empty(),
+            None as Option<&str>,
+        )
+        .unwrap();
+        {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .open(tmp_dir.path().join(file_name))
+                .unwrap();
+            file.write_all(&vec![1u8; size as usize]).unwrap();
+            file.sync_all().unwrap();
+        }
+        umount(tmp_dir.path()).unwrap();
+    }
+
+    /// Mount the XFS filesystem at devnode and return the names of the files
+    /// in its root directory.
+    fn list_files(devnode: &Path) -> Vec<String> {
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        mount(
+            Some(devnode),
+            tmp_dir.path(),
+            Some("xfs"),
+            MsFlags::empty(),
+            None as Option<&str>,
+        )
+        .unwrap();
+        let mut files = std::fs::read_dir(tmp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        files.sort();
+        umount(tmp_dir.path()).unwrap();
+        files
+    }
+
+    /// Verify that after a snapshot is taken and more data is written to
+    /// its origin, the data written before the snapshot is reported as
+    /// shared by both filesystems and the data written afterwards as
+    /// exclusive to the origin.
+    fn test_space_usage(paths: &[&Path]) {
+        let name = "stratis-test-pool";
+
+        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, PoolCreateOptions::default()).unwrap();
+        invariant(&pool, name);
+
+        let (_, fs_uuid, _) = pool
+            .create_filesystems(name, uuid, &[FilesystemSpec::new("stratis-filesystem")])
+            .unwrap()
+            .changed()
+            .and_then(|mut fs| fs.pop())
+            .unwrap();
+        let written = 16 * IEC::Mi;
+        write_file(
+            &pool.get_filesystem(fs_uuid).unwrap().1.devnode(),
+            "before",
+            written,
+        );
+
+        let snapshot_uuid = match pool
+            .snapshot_filesystem(name, uuid, fs_uuid, "stratis-snapshot")
+            .unwrap()
+        {
+            CreateAction::Created((snapshot_uuid, _)) => snapshot_uuid,
+            CreateAction::Identity => panic!("snapshot should have been created"),
+        };
+        write_file(
+            &pool.get_filesystem(fs_uuid).unwrap().1.devnode(),
+            "after",
+            written,
+        );
+
+        let usage = pool.space_usage().unwrap();
+        let diffs = pool.set_space_usage(uuid, &usage).unwrap();
+        assert!(diffs.contains_key(&fs_uuid));
+        assert!(diffs.contains_key(&snapshot_uuid));
+
+        let fs_usage = pool
+            .get_filesystem(fs_uuid)
+            .unwrap()
+            .1
+            .space_usage()
+            .unwrap();
+        let snapshot_usage = pool
+            .get_filesystem(snapshot_uuid)
+            .unwrap()
+            .1
+            .space_usage()
+            .unwrap();
+        for usage in [fs_usage, snapshot_usage] {
+            assert_eq!(usage.mapped, usage.exclusive + usage.shared);
+        }
+        assert!(fs_usage.exclusive >= Bytes::from(written));
+        assert!(snapshot_usage.shared >= Bytes::from(written));
+        assert!(fs_usage.exclusive > snapshot_usage.exclusive);
+
+        let usage = pool.space_usage().unwrap();
+        assert!(pool.set_space_usage(uuid, &usage).unwrap().is_empty());
+        invariant(&pool, name);
+    }
+
+    #[test]
+    fn loop_test_space_usage() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(1, 3, None),
+            test_space_usage,
+        );
+    }
+
+    #[test]
+    fn real_test_space_usage() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(1, None, None),
+            test_space_usage,
+        );
+    }
+
+    /// Verify that a snapshot sent as a complete stream can be received as a
+    /// new filesystem, and that the changes up to a later snapshot sent as
+    /// an incremental stream can be applied to the received filesystem.
+    fn test_send_receive(paths: &[&Path]) {
+        let name = "stratis-test-pool";
+
+        let devices = ProcessedPathInfos::try_from(paths).unwrap();
+        let (stratis_devices, unowned_devices) = devices.unpack();
+        stratis_devices.error_on_not_empty().unwrap();
+
+        let (uuid, mut pool) =
+            StratPool::initialize(name, unowned_devices, PoolCreateOptions::default()).unwrap();
+        invariant(&pool, name);
+
+        let (_, fs_uuid, _) = pool
+            .create_filesystems(name, uuid, &[FilesystemSpec::new("stratis-filesystem")])
+            .unwrap()
+            .changed()
+            .and_then(|mut fs| fs.pop())
+            .unwrap();
+        let fs_devnode = pool.get_filesystem(fs_uuid).unwrap().1.devnode();
+        write_file(&fs_devnode, "first", IEC::Mi);
+
+        let snapshot = |pool: &mut StratPool, snapshot_name: &str| match pool
+            .snapshot_filesystem(name, uuid, fs_uuid, snapshot_name)
+            .unwrap()
+        {
+            CreateAction::Created((snapshot_uuid, _)) => snapshot_uuid,
+            CreateAction::Identity => panic!("snapshot should have been created"),
+        };
+
+        let first_uuid = snapshot(&mut pool, "first-snapshot");
+        let mut stream = tempfile::tempfile().unwrap();
+        assert!(
+            pool.send_filesystem(first_uuid, None, stream.try_clone().unwrap())
+                .unwrap()
+                >= Bytes::from(IEC::Mi)
+        );
+        stream.rewind().unwrap();
+        let received_uuid = pool
+            .receive_filesystem(name, uuid, "received", stream.try_clone().unwrap())
+            .unwrap();
+        invariant(&pool, name);
+        let received_devnode = pool.get_filesystem(received_uuid).unwrap().1.devnode();
+        assert_eq!(list_files(&received_devnode), vec!["first".to_string()]);
+
+        write_file(&fs_devnode, "second", IEC::Mi);
+        let second_uuid = snapshot(&mut pool, "second-snapshot");
+        let mut stream = tempfile::tempfile().unwrap();
+        pool.send_filesystem(second_uuid, Some(first_uuid), stream.try_clone().unwrap())
+            .unwrap();
+        stream.rewind().unwrap();
+        assert_matches!(
+            pool.receive_filesystem(name, uuid, "received-delta", stream.try_clone().unwrap(),),
+            Err(_)
+        );
+        stream.rewind().unwrap();
+        assert_matches!(
+            pool.receive_filesystem_delta(name, fs_uuid, stream.try_clone().unwrap()),
+            Err(_)
+        );
+        stream.rewind().unwrap();
+        pool.receive_filesystem_delta(name, received_uuid, stream.try_clone().unwrap())
+            .unwrap();
+        invariant(&pool, name);
+        assert_eq!(
+            list_files(&received_devnode),
+            vec!["first".to_string(), "second".to_string()]
+        );
+        stream.rewind().unwrap();
+        assert_matches!(
+            pool.receive_filesystem_delta(name, received_uuid, stream.try_clone().unwrap()),
+            Err(_)
+        );
+    }
+
+    #[test]
+    fn loop_test_send_receive() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(1, 3, None),
+            test_send_receive,
+        );
+    }
+
+    #[test]
+    fn real_test_send_receive() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(1, None, None),
+            test_send_receive,
         );
     }
 
@@ -1605,15 +3668,20 @@ mod tests {
     fn test_grow_physical_pre_grow(paths: &[&Path]) {
         let pool_name = Name::new("pool".to_string());
         let engine = StratEngine::initialize().unwrap();
-        let pool_uuid = test_async!(engine.create_pool(&pool_name, paths, None))
-            .unwrap()
-            .changed()
-            .unwrap();
+        let pool_uuid =
+            test_async!(engine.create_pool(&pool_name, paths, PoolCreateOptions::default()))
+                .unwrap()
+                .changed()
+                .unwrap();
         let mut guard = test_async!(engine.get_mut_pool(PoolIdentifier::Uuid(pool_uuid))).unwrap();
         let (_, _, pool) = guard.as_mut_tuple();
 
         let (_, fs_uuid, _) = pool
-            .create_filesystems(&pool_name, pool_uuid, &[("stratis_test_filesystem", None)])
+            .create_filesystems(
+                &pool_name,
+                pool_uuid,
+                &[FilesystemSpec::new("stratis_test_filesystem")],
+            )
             .unwrap()
             .changed()
             .unwrap()
@@ -18,7 +18,10 @@ use serde::Serialize;
 
 use devicemapper::{Sectors, ThinDevId};
 
-use crate::engine::types::{DevUuid, FilesystemUuid};
+use crate::engine::types::{
+    AllocationConfig, CacheConfig, DevUuid, FilesystemType, FilesystemUuid, FsExtendConfig,
+    SnapshotPolicy, ThinPoolExtendPolicy,
+};
 
 /// Implements saving struct data to a serializable form. The form should be
 /// sufficient, in conjunction with the environment, to reconstruct the
@@ -53,28 +56,40 @@ pub struct BackstoreSave {
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct DataTierSave {
     pub blockdev: BlockDevSave,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allocation: Option<AllocationConfig>,
 }
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct BlockDevSave {
     pub allocs: Vec<Vec<BaseDevSave>>,
     pub devs: Vec<BaseBlockDevSave>,
+    /// The UUIDs of the devices that make up each leg of a mirrored tier.
+    /// The segments allocated to the second leg are recorded in allocs[1],
+    /// the metadata segments of the first and second leg in allocs[2] and
+    /// allocs[3].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub legs: Option<[Vec<DevUuid>; 2]>,
 }
 
-#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct BaseDevSave {
     pub parent: DevUuid,
     pub start: Sectors,
     pub length: Sectors,
 }
 
-#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct BaseBlockDevSave {
     pub uuid: DevUuid,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user_info: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub hardware_info: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -85,6 +100,9 @@ pub struct CapSave {
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct CacheTierSave {
     pub blockdev: BlockDevSave,
+    // TODO: This data type should no longer be optional in Stratis 4.0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config: Option<CacheConfig>,
 }
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -107,6 +125,10 @@ pub struct ThinPoolDevSave {
     // TODO: This data type should no longer be optional in Stratis 4.0
     #[serde(skip_serializing_if = "Option::is_none")]
     pub enable_overprov: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fs_extend_config: Option<FsExtendConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extend_policy: Option<ThinPoolExtendPolicy>,
 }
 
 // Struct representing filesystem metadata. This metadata is not held in the
@@ -118,5 +140,24 @@ pub struct FilesystemSave {
     pub uuid: FilesystemUuid,
     pub thin_id: ThinDevId,
     pub size: Sectors,
+    // TODO: This data type should no longer be optional in Stratis 4.0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fs_type: Option<FilesystemType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mount_options: Option<String>,
     pub created: u64, // Unix timestamp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_limit: Option<Sectors>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extend_config: Option<FsExtendConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<FilesystemUuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot_policies: Option<Vec<SnapshotPolicy>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheduled_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheduled_for: Option<FilesystemUuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub received_uuid: Option<FilesystemUuid>,
 }
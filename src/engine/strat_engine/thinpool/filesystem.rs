@@ -6,6 +6,7 @@ use std::{
     cmp::min,
     fs::{File, OpenOptions},
     io::{Read, Write},
+    os::unix::io::AsRawFd,
     path::{Path, PathBuf},
 };
 
@@ -29,15 +30,17 @@ use crate::{
         engine::{DumpState, Filesystem, StateDiff},
         shared::unsigned_to_timestamp,
         strat_engine::{
-            cmd::{create_fs, set_uuid, xfs_growfs},
+            cmd::{create_fs, grow_fs, set_uuid},
             devlinks,
             dm::get_dm,
             names::{format_thin_ids, ThinRole},
             serde_structs::FilesystemSave,
+            thinpool::stream::{receive_ranges, send_ranges, StreamHeader},
         },
         types::{
-            ActionAvailability, Compare, FilesystemUuid, Name, PoolUuid, StratFilesystemDiff,
-            StratisUuid,
+            ActionAvailability, Compare, FilesystemType, FilesystemUuid, FsCreateOptions,
+            FsExtendConfig, FsExtendIncrement, Name, PoolUuid, SnapshotPolicy, SpaceUsage,
+            StratFilesystemDiff, StratisUuid,
         },
     },
     stratis::{StratisError, StratisResult},
@@ -48,8 +51,20 @@ const TEMP_MNT_POINT_PREFIX: &str = "stratis_mp_";
 #[derive(Debug)]
 pub struct StratFilesystem {
     thin_dev: ThinDev,
+    fs_type: FilesystemType,
+    mount_options: Option<String>,
     created: DateTime<Utc>,
     used: Option<Bytes>,
+    size_limit: Option<Sectors>,
+    extend_config: Option<FsExtendConfig>,
+    origin: Option<FilesystemUuid>,
+    snapshot_policies: Vec<SnapshotPolicy>,
+    scheduled_by: Option<String>,
+    scheduled_for: Option<FilesystemUuid>,
+    /// The UUID of the sent filesystem whose contents this filesystem was
+    /// given by the last stream it received, if any.
+    received_uuid: Option<FilesystemUuid>,
+    space_usage: Option<SpaceUsage>,
 }
 
 fn init_used(thin_dev: &ThinDev) -> Option<Bytes> {
@@ -66,11 +81,15 @@ fn init_used(thin_dev: &ThinDev) -> Option<Bytes> {
 }
 
 impl StratFilesystem {
-    /// Create a StratFilesystem on top of the given ThinDev.
+    /// Create a StratFilesystem of type fs_type on top of the given ThinDev,
+    /// applying the given creation options.
     pub fn initialize(
         pool_uuid: PoolUuid,
         thinpool_dev: &ThinPoolDev,
         size: Sectors,
+        size_limit: Option<Sectors>,
+        fs_type: FilesystemType,
+        options: &FsCreateOptions,
         id: ThinDevId,
     ) -> StratisResult<(FilesystemUuid, StratFilesystem)> {
         let fs_uuid = FilesystemUuid::new_v4();
@@ -78,7 +97,13 @@ impl StratFilesystem {
         let mut thin_dev =
             ThinDev::new(get_dm(), &dm_name, Some(&dm_uuid), size, thinpool_dev, id)?;
 
-        if let Err(err) = create_fs(&thin_dev.devnode(), Some(StratisUuid::Fs(fs_uuid)), false) {
+        if let Err(err) = create_fs(
+            &thin_dev.devnode(),
+            fs_type,
+            Some(StratisUuid::Fs(fs_uuid)),
+            false,
+            &options.mkfs,
+        ) {
             if let Err(err2) = retry_with_index(Fixed::from_millis(100).take(4), |i| {
                 trace!(
                     "Cleanup new thin device after failed create_fs() attempt {}",
@@ -102,7 +127,77 @@ impl StratFilesystem {
             StratFilesystem {
                 used: init_used(&thin_dev),
                 thin_dev,
+                fs_type,
+                mount_options: options.mount_options.clone(),
                 created: Utc::now(),
+                size_limit,
+                extend_config: None,
+                origin: None,
+                snapshot_policies: Vec::new(),
+                scheduled_by: None,
+                scheduled_for: None,
+                received_uuid: None,
+                space_usage: None,
+            },
+        ))
+    }
+
+    /// Create a StratFilesystem on a new thin device with the size given by
+    /// the header of a complete stream and write the contents read from the
+    /// stream to it.
+    pub fn receive<R: Read>(
+        pool_uuid: PoolUuid,
+        thinpool_dev: &ThinPoolDev,
+        header: &StreamHeader,
+        stream: &mut R,
+        id: ThinDevId,
+    ) -> StratisResult<(FilesystemUuid, StratFilesystem)> {
+        let fs_uuid = FilesystemUuid::new_v4();
+        let (dm_name, dm_uuid) = format_thin_ids(pool_uuid, ThinRole::Filesystem(fs_uuid));
+        let mut thin_dev = ThinDev::new(
+            get_dm(),
+            &dm_name,
+            Some(&dm_uuid),
+            header.size,
+            thinpool_dev,
+            id,
+        )?;
+
+        if let Err(err) = OpenOptions::new()
+            .write(true)
+            .open(thin_dev.devnode())
+            .map_err(StratisError::from)
+            .and_then(|mut f| receive_ranges(stream, &mut f, header.size))
+            .and_then(|_| set_uuid(&thin_dev.devnode(), header.fs_type, fs_uuid))
+        {
+            if let Err(err2) = retry_with_index(Fixed::from_millis(100).take(4), |i| {
+                trace!("Cleanup new thin device after failed receive attempt {}", i);
+                thin_dev.destroy(get_dm(), thinpool_dev)
+            }) {
+                error!(
+                    "While handling receive error, thin_dev.destroy() failed: {}",
+                    err2
+                );
+            }
+            return Err(err);
+        }
+
+        Ok((
+            fs_uuid,
+            StratFilesystem {
+                used: init_used(&thin_dev),
+                thin_dev,
+                fs_type: header.fs_type,
+                mount_options: None,
+                created: Utc::now(),
+                size_limit: None,
+                extend_config: None,
+                origin: None,
+                snapshot_policies: Vec::new(),
+                scheduled_by: None,
+                scheduled_for: None,
+                received_uuid: Some(header.uuid),
+                space_usage: None,
             },
         ))
     }
@@ -127,18 +222,30 @@ impl StratFilesystem {
         Ok(StratFilesystem {
             used: init_used(&thin_dev),
             thin_dev,
+            fs_type: fssave.fs_type.unwrap_or_default(),
+            mount_options: fssave.mount_options.clone(),
             created,
+            size_limit: fssave.size_limit,
+            extend_config: fssave.extend_config,
+            origin: fssave.origin,
+            snapshot_policies: fssave.snapshot_policies.clone().unwrap_or_default(),
+            scheduled_by: fssave.scheduled_by.clone(),
+            scheduled_for: fssave.scheduled_for,
+            received_uuid: fssave.received_uuid,
+            space_usage: None,
         })
     }
 
     /// Send a synthetic udev change event to the devicemapper device representing
-    /// the filesystem.
+    /// the filesystem. The event carries the recommended mount options of the
+    /// filesystem, if any, so that udev can export them.
     pub fn udev_fs_change(&self, pool_name: &str, fs_uuid: FilesystemUuid, fs_name: &str) {
         fn udev_change_event(
             thin_dev: &ThinDev,
             pool_name: &str,
             fs_uuid: FilesystemUuid,
             fs_name: &str,
+            mount_options: Option<&str>,
         ) -> StratisResult<()> {
             let device = thin_dev.device();
             let uevent_file = [
@@ -153,18 +260,30 @@ impl StratFilesystem {
                 .open(uevent_file)?
                 .write_all(
                     format!(
-                        "{} {} STRATISPOOLNAME={} STRATISFSNAME={}",
+                        "{} {} STRATISPOOLNAME={} STRATISFSNAME={}{}",
                         devlinks::UEVENT_CHANGE_EVENT,
                         fs_uuid,
                         BASE32_NOPAD.encode(pool_name.as_bytes()),
                         BASE32_NOPAD.encode(fs_name.as_bytes()),
+                        mount_options
+                            .map(|o| format!(
+                                " STRATISMOUNTOPTIONS={}",
+                                BASE32_NOPAD.encode(o.as_bytes())
+                            ))
+                            .unwrap_or_default(),
                     )
                     .as_bytes(),
                 )?;
             Ok(())
         }
 
-        if let Err(e) = udev_change_event(&self.thin_dev, pool_name, fs_uuid, fs_name) {
+        if let Err(e) = udev_change_event(
+            &self.thin_dev,
+            pool_name,
+            fs_uuid,
+            fs_name,
+            self.mount_options.as_deref(),
+        ) {
             warn!("Failed to notify udev to perform symlink operation: {}", e);
         }
     }
@@ -174,11 +293,12 @@ impl StratFilesystem {
     /// snapshot_dmname for the new name of the ThinDev allocated for the snapshot.
     /// Mounting a filesystem with a duplicate UUID would require special handling,
     /// so snapshot_fs_uuid is used to update the new snapshot filesystem so it has
-    /// a unique UUID.
+    /// a unique UUID. The snapshot records origin_uuid as its origin.
     #[allow(clippy::too_many_arguments)]
     pub fn snapshot(
         &self,
         thin_pool: &ThinPoolDev,
+        origin_uuid: FilesystemUuid,
         snapshot_name: &str,
         snapshot_dm_name: &DmName,
         snapshot_dm_uuid: Option<&DmUuid>,
@@ -200,21 +320,26 @@ impl StratFilesystem {
                 // state. xfs_admin won't allow a filesystem UUID
                 // to be updated when the log is dirty.  To clear the log
                 // we mount/unmount the filesystem before updating the UUID.
+                // Similarly, tune2fs won't update the UUID of an ext4
+                // filesystem whose journal needs to be recovered.
                 //
-                // If the source is unmounted the XFS log will be clean so
+                // If the source is unmounted the log will be clean so
                 // we can skip the mount/unmount.
                 if !self.mount_points()?.is_empty() {
                     let tmp_dir = tempfile::Builder::new()
                         .prefix(TEMP_MNT_POINT_PREFIX)
                         .tempdir()?;
-                    // Mount the snapshot with the "nouuid" option. mount
+                    // Mount an XFS snapshot with the "nouuid" option. mount
                     // will fail due to duplicate UUID otherwise.
                     mount(
                         Some(&thin_dev.devnode()),
                         tmp_dir.path(),
-                        Some("xfs"),
+                        Some(self.fs_type.to_string().as_str()),
                         MsFlags::empty(),
-                        Some("nouuid"),
+                        match self.fs_type {
+                            FilesystemType::Xfs => Some("nouuid"),
+                            FilesystemType::Ext4 => None,
+                        },
                     )?;
                     if let Err(e) = retry_with_index(Fixed::from_millis(100).take(2), |i| {
                         trace!("Unmount temporary snapshot mount attempt {}", i);
@@ -224,11 +349,21 @@ impl StratFilesystem {
                     }
                 }
 
-                set_uuid(&thin_dev.devnode(), snapshot_fs_uuid)?;
+                set_uuid(&thin_dev.devnode(), self.fs_type, snapshot_fs_uuid)?;
                 Ok(StratFilesystem {
                     used: init_used(&thin_dev),
                     thin_dev,
+                    fs_type: self.fs_type,
+                    mount_options: self.mount_options.clone(),
                     created: Utc::now(),
+                    size_limit: self.size_limit,
+                    extend_config: self.extend_config,
+                    origin: Some(origin_uuid),
+                    snapshot_policies: Vec::new(),
+                    scheduled_by: None,
+                    scheduled_for: None,
+                    received_uuid: None,
+                    space_usage: None,
                 })
             }
             Err(e) => Err(StratisError::Msg(format!(
@@ -237,18 +372,235 @@ impl StratFilesystem {
         }
     }
 
-    /// Check the filesystem usage and determine whether it should extend.
+    /// Exchange the thin devices backing this filesystem and other. Afterwards
+    /// each filesystem's devicemapper name and filesystem UUID refer to the
+    /// data that previously belonged to the other filesystem, and each has the
+    /// filesystem type of that data. Both filesystems must be
+    /// unmounted. Applying the operation a second time restores the original
+    /// arrangement.
+    pub fn swap_thin_devs(
+        &mut self,
+        pool_uuid: PoolUuid,
+        thin_pool: &ThinPoolDev,
+        fs_uuid: FilesystemUuid,
+        other: &mut StratFilesystem,
+        other_uuid: FilesystemUuid,
+    ) -> StratisResult<()> {
+        fn activate(
+            thin_pool: &ThinPoolDev,
+            dm_name: &DmName,
+            dm_uuid: &DmUuid,
+            size: Sectors,
+            id: ThinDevId,
+            fs_type: FilesystemType,
+            fs_uuid: FilesystemUuid,
+        ) -> StratisResult<ThinDev> {
+            let mut thin_dev =
+                ThinDev::setup(get_dm(), dm_name, Some(dm_uuid), size, thin_pool, id)?;
+            if let Err(err) = set_uuid(&thin_dev.devnode(), fs_type, fs_uuid) {
+                if let Err(e) = thin_dev.teardown(get_dm()) {
+                    warn!("Failed to tear down thin device {}: {}", dm_name, e);
+                }
+                return Err(err);
+            }
+            Ok(thin_dev)
+        }
+
+        if !self.mount_points()?.is_empty() || !other.mount_points()?.is_empty() {
+            return Err(StratisError::Msg(format!(
+                "Filesystems with UUIDs {fs_uuid} and {other_uuid} must both be unmounted"
+            )));
+        }
+
+        let (dm_name, dm_uuid) = format_thin_ids(pool_uuid, ThinRole::Filesystem(fs_uuid));
+        let (other_dm_name, other_dm_uuid) =
+            format_thin_ids(pool_uuid, ThinRole::Filesystem(other_uuid));
+        let (id, size, fs_type) = (self.thin_dev.id(), self.thin_dev.size(), self.fs_type);
+        let (other_id, other_size, other_fs_type) =
+            (other.thin_dev.id(), other.thin_dev.size(), other.fs_type);
+
+        // Tear down any devices set up with the exchanged thin ids and set
+        // up the original devices again.
+        let restore = |this: &mut StratFilesystem,
+                       other: &mut StratFilesystem,
+                       new_devs: Vec<ThinDev>,
+                       causal: StratisError|
+         -> StratisError {
+            let res = new_devs
+                .into_iter()
+                .try_for_each(|mut thin_dev| thin_dev.teardown(get_dm()))
+                .map_err(StratisError::from)
+                .and_then(|_| {
+                    this.thin_dev =
+                        activate(thin_pool, &dm_name, &dm_uuid, size, id, fs_type, fs_uuid)?;
+                    other.thin_dev = activate(
+                        thin_pool,
+                        &other_dm_name,
+                        &other_dm_uuid,
+                        other_size,
+                        other_id,
+                        other_fs_type,
+                        other_uuid,
+                    )?;
+                    Ok(())
+                });
+            match res {
+                Ok(_) => causal,
+                Err(rollback) => StratisError::RollbackError {
+                    causal_error: Box::new(causal),
+                    rollback_error: Box::new(rollback),
+                    level: ActionAvailability::NoPoolChanges,
+                },
+            }
+        };
+
+        self.thin_dev.teardown(get_dm())?;
+        if let Err(causal) = other.thin_dev.teardown(get_dm()) {
+            return Err(
+                match ThinDev::setup(get_dm(), &dm_name, Some(&dm_uuid), size, thin_pool, id) {
+                    Ok(thin_dev) => {
+                        self.thin_dev = thin_dev;
+                        StratisError::from(causal)
+                    }
+                    Err(rollback) => StratisError::RollbackError {
+                        causal_error: Box::new(StratisError::from(causal)),
+                        rollback_error: Box::new(StratisError::from(rollback)),
+                        level: ActionAvailability::NoPoolChanges,
+                    },
+                },
+            );
+        }
+
+        let new_thin_dev = match activate(
+            thin_pool,
+            &dm_name,
+            &dm_uuid,
+            other_size,
+            other_id,
+            other_fs_type,
+            fs_uuid,
+        ) {
+            Ok(thin_dev) => thin_dev,
+            Err(causal) => return Err(restore(self, other, Vec::new(), causal)),
+        };
+        let new_other_thin_dev = match activate(
+            thin_pool,
+            &other_dm_name,
+            &other_dm_uuid,
+            size,
+            id,
+            fs_type,
+            other_uuid,
+        ) {
+            Ok(thin_dev) => thin_dev,
+            Err(causal) => return Err(restore(self, other, vec![new_thin_dev], causal)),
+        };
+
+        self.thin_dev = new_thin_dev;
+        self.fs_type = other_fs_type;
+        self.used = init_used(&self.thin_dev);
+        other.thin_dev = new_other_thin_dev;
+        other.fs_type = fs_type;
+        other.used = init_used(&other.thin_dev);
+        std::mem::swap(&mut self.received_uuid, &mut other.received_uuid);
+        std::mem::swap(&mut self.space_usage, &mut other.space_usage);
+        Ok(())
+    }
+
+    /// Write a stream with the contents of the given ranges of the
+    /// filesystem and the given unmapped ranges to w. If the stream is
+    /// incremental, parent is the UUID of the filesystem it was generated
+    /// against. The filesystem must be unmounted, so that its contents do not
+    /// change while they are written.
+    pub fn send<W: Write>(
+        &self,
+        fs_uuid: FilesystemUuid,
+        parent: Option<FilesystemUuid>,
+        data: &[(Sectors, Sectors)],
+        discards: &[(Sectors, Sectors)],
+        w: &mut W,
+    ) -> StratisResult<Bytes> {
+        if !self.mount_points()?.is_empty() {
+            return Err(StratisError::Msg(format!(
+                "Filesystem with UUID {fs_uuid} must be unmounted to be sent"
+            )));
+        }
+
+        // The last block mapped may extend beyond the end of the thin device
+        // if its size is not a multiple of the data block size.
+        let size = self.thin_dev.size();
+        let clip = |ranges: &[(Sectors, Sectors)]| {
+            ranges
+                .iter()
+                .filter(|(offset, _)| *offset < size)
+                .map(|(offset, length)| (*offset, min(*length, size - *offset)))
+                .collect::<Vec<_>>()
+        };
+
+        StreamHeader {
+            fs_type: self.fs_type,
+            size,
+            uuid: fs_uuid,
+            parent,
+        }
+        .write(w)?;
+        let mut dev = File::open(self.thin_dev.devnode())?;
+        send_ranges(&mut dev, &clip(data), &clip(discards), w)
+    }
+
+    /// Apply the contents read from an incremental stream to this filesystem.
+    /// The thin device is extended if the filesystem that was sent is larger.
+    /// The filesystem must be unmounted and must have the contents of the
+    /// parent from which the stream was generated; if applying the stream
+    /// fails, its contents are inconsistent and the thin device keeps any
+    /// size it was extended to.
+    pub fn receive_delta<R: Read>(
+        &mut self,
+        fs_uuid: FilesystemUuid,
+        header: &StreamHeader,
+        stream: &mut R,
+    ) -> StratisResult<Bytes> {
+        if !self.mount_points()?.is_empty() {
+            return Err(StratisError::Msg(format!(
+                "Filesystem with UUID {fs_uuid} must be unmounted to receive a stream"
+            )));
+        }
+
+        if header.size > self.thin_dev.size() {
+            let mut new_table = self.thin_dev.table().table.clone();
+            new_table.length = header.size;
+            self.thin_dev.set_table(get_dm(), new_table)?;
+        }
+
+        let mut dev = OpenOptions::new()
+            .write(true)
+            .open(self.thin_dev.devnode())?;
+        let received = receive_ranges(stream, &mut dev, self.thin_dev.size())?;
+        set_uuid(&self.thin_dev.devnode(), self.fs_type, fs_uuid)?;
+        self.used = init_used(&self.thin_dev);
+        self.received_uuid = Some(header.uuid);
+        Ok(received)
+    }
+
+    /// Check the filesystem usage and determine whether it should extend,
+    /// i.e., whether more than threshold percent of it is in use.
     ///
     /// Returns:
     /// * Some(mount_point) if the filesystem should be extended
-    /// * None if the filesystem does not need to be extended
-    pub fn should_extend(&self) -> Option<PathBuf> {
-        fn should_extend_fail(fs: &StratFilesystem) -> StratisResult<Option<PathBuf>> {
+    /// * None if the filesystem does not need to be extended or has already
+    /// reached its size limit
+    pub fn should_extend(&self, threshold: u8) -> Option<PathBuf> {
+        fn should_extend_fail(
+            fs: &StratFilesystem,
+            threshold: u8,
+        ) -> StratisResult<Option<PathBuf>> {
             match fs.thin_dev.status(get_dm(), DmOptions::default())? {
                 ThinStatus::Working(_) => {
                     if let Some(mount_point) = fs.mount_points()?.first() {
                         let (fs_total_bytes, fs_total_used_bytes) = fs_usage(mount_point)?;
-                        if 2u64 * fs_total_used_bytes > fs_total_bytes {
+                        if 100u128 * u128::from(fs_total_used_bytes)
+                            > u128::from(threshold) * u128::from(fs_total_bytes)
+                        {
                             return Ok(Some(mount_point.clone()));
                         }
                     }
@@ -265,7 +617,13 @@ impl StratFilesystem {
             }
         }
 
-        match should_extend_fail(self) {
+        if let Some(size_limit) = self.size_limit {
+            if self.thindev_size() >= size_limit {
+                return None;
+            }
+        }
+
+        match should_extend_fail(self, threshold) {
             Ok(mt_pt) => mt_pt,
             Err(e) => {
                 warn!(
@@ -292,7 +650,7 @@ impl StratFilesystem {
         let mut new_table = old_table.clone();
         new_table.length = original_state.size.sectors() + extend_size;
         self.thin_dev.set_table(get_dm(), new_table)?;
-        if let Err(causal) = xfs_growfs(mount_point) {
+        if let Err(causal) = grow_fs(&self.thin_dev.devnode(), mount_point, self.fs_type) {
             if let Err(rollback) = self.thin_dev.set_table(get_dm(), old_table) {
                 return Err(StratisError::RollbackError {
                     causal_error: Box::new(causal),
@@ -308,16 +666,28 @@ impl StratFilesystem {
     }
 
     /// Return an extend size for the thindev under the filesystem
-    pub fn extend_size(current_size: Sectors, remaining_size: Option<&mut Sectors>) -> Sectors {
+    pub fn extend_size(
+        current_size: Sectors,
+        remaining_size: Option<&mut Sectors>,
+        size_limit: Option<Sectors>,
+        increment: FsExtendIncrement,
+    ) -> Sectors {
+        // Extend the filesystem by the configured increment, but never extend
+        // the filesystem beyond its size limit.
+        let increment = increment.extend_size(current_size);
+        let max_extend_size = match size_limit {
+            Some(limit) => min(Sectors(limit.saturating_sub(*current_size)), increment),
+            None => increment,
+        };
         if let Some(rem_size) = remaining_size {
             // Extend either by the remaining amount left before the data device
-            // overprovisioning limit is reached if it is less than the size of the
-            // filesystem or double the filesystem size.
-            let extend_size = min(*rem_size, current_size);
+            // overprovisioning limit is reached if it is less than the maximum
+            // extension or by the maximum extension.
+            let extend_size = min(*rem_size, max_extend_size);
             *rem_size -= extend_size;
             extend_size
         } else {
-            current_size
+            max_extend_size
         }
     }
 
@@ -339,12 +709,88 @@ impl StratFilesystem {
             uuid,
             thin_id: self.thin_dev.id(),
             size: self.thin_dev.size(),
+            fs_type: Some(self.fs_type),
+            mount_options: self.mount_options.clone(),
             created: self.created.timestamp() as u64,
+            size_limit: self.size_limit,
+            extend_config: self.extend_config,
+            origin: self.origin,
+            snapshot_policies: if self.snapshot_policies.is_empty() {
+                None
+            } else {
+                Some(self.snapshot_policies.clone())
+            },
+            scheduled_by: self.scheduled_by.clone(),
+            scheduled_for: self.scheduled_for,
+            received_uuid: self.received_uuid,
         }
     }
 
+    /// Set the size limit of this filesystem. Returns true if the limit
+    /// changed.
+    pub fn set_size_limit(&mut self, size_limit: Option<Sectors>) -> bool {
+        if self.size_limit == size_limit {
+            false
+        } else {
+            self.size_limit = size_limit;
+            true
+        }
+    }
+
+    /// Set the configuration of the automatic extension of this filesystem.
+    /// Returns true if the configuration changed.
+    pub fn set_extend_config(&mut self, config: Option<FsExtendConfig>) -> bool {
+        if self.extend_config == config {
+            false
+        } else {
+            self.extend_config = config;
+            true
+        }
+    }
+
+    /// Set the snapshot policies of this filesystem. Returns true if the
+    /// policies changed.
+    pub fn set_snapshot_policies(&mut self, policies: &[SnapshotPolicy]) -> bool {
+        if self.snapshot_policies == policies {
+            false
+        } else {
+            self.snapshot_policies = policies.to_vec();
+            true
+        }
+    }
+
+    /// Record that this filesystem was created by the given snapshot policy
+    /// of the filesystem with the given UUID.
+    pub fn set_scheduled_by(&mut self, owner: FilesystemUuid, policy_name: &str) {
+        self.scheduled_by = Some(policy_name.to_owned());
+        self.scheduled_for = Some(owner);
+    }
+
+    /// The UUID of the filesystem whose snapshot policy created this
+    /// filesystem, if it is a scheduled snapshot. Unlike the origin, it is
+    /// kept when the filesystem it refers to is destroyed or reverted.
+    /// Scheduled snapshots recorded before the owner was recorded report
+    /// their origin.
+    pub fn scheduled_for(&self) -> Option<FilesystemUuid> {
+        self.scheduled_by
+            .as_ref()
+            .and_then(|_| self.scheduled_for.or(self.origin))
+    }
+
+    /// The UUID of the filesystem whose stream this filesystem last
+    /// received, if any.
+    pub fn received_uuid(&self) -> Option<FilesystemUuid> {
+        self.received_uuid
+    }
+
+    /// Forget the origin of this filesystem, e.g., because the origin has
+    /// been destroyed. Returns true if the filesystem had an origin.
+    pub fn unset_origin(&mut self) -> bool {
+        self.origin.take().is_some()
+    }
+
     /// Find places where this filesystem is mounted.
-    fn mount_points(&self) -> StratisResult<Vec<PathBuf>> {
+    pub fn mount_points(&self) -> StratisResult<Vec<PathBuf>> {
         // Use major:minor values to find mounts for this filesystem
         let major = u64::from(self.thin_dev.device().major);
         let minor = u64::from(self.thin_dev.device().minor);
@@ -376,6 +822,17 @@ impl StratFilesystem {
     pub fn thindev_size(&self) -> Sectors {
         self.thin_dev.size()
     }
+
+    /// The ID of the thin device backing this filesystem within its thin pool.
+    pub fn thin_id(&self) -> ThinDevId {
+        self.thin_dev.id()
+    }
+
+    /// Record the space usage of this filesystem as most recently calculated
+    /// from the thin pool metadata.
+    pub fn set_space_usage(&mut self, space_usage: Option<SpaceUsage>) {
+        self.space_usage = space_usage;
+    }
 }
 
 impl Filesystem for StratFilesystem {
@@ -411,12 +868,45 @@ impl Filesystem for StratFilesystem {
     fn size(&self) -> Bytes {
         self.thin_dev.size().bytes()
     }
+
+    fn size_limit(&self) -> Option<Sectors> {
+        self.size_limit
+    }
+
+    fn extend_config(&self) -> Option<FsExtendConfig> {
+        self.extend_config
+    }
+
+    fn fs_type(&self) -> FilesystemType {
+        self.fs_type
+    }
+
+    fn mount_options(&self) -> Option<&str> {
+        self.mount_options.as_deref()
+    }
+
+    fn origin(&self) -> Option<FilesystemUuid> {
+        self.origin
+    }
+
+    fn snapshot_policies(&self) -> &[SnapshotPolicy] {
+        &self.snapshot_policies
+    }
+
+    fn scheduled_by(&self) -> Option<&str> {
+        self.scheduled_by.as_deref()
+    }
+
+    fn space_usage(&self) -> Option<SpaceUsage> {
+        self.space_usage
+    }
 }
 
 /// Represents the state of the Stratis filesystem at a given moment in time.
 pub struct StratFilesystemState {
     size: Bytes,
     used: Option<Bytes>,
+    space_usage: Option<SpaceUsage>,
 }
 
 impl StateDiff for StratFilesystemState {
@@ -426,6 +916,7 @@ impl StateDiff for StratFilesystemState {
         StratFilesystemDiff {
             size: self.size.compare(&new_state.size),
             used: self.used.compare(&new_state.used),
+            space_usage: self.space_usage.compare(&new_state.space_usage),
         }
     }
 }
@@ -437,6 +928,7 @@ impl<'a> DumpState<'a> for StratFilesystem {
         StratFilesystemState {
             size: self.size(),
             used: self.used,
+            space_usage: self.space_usage,
         }
     }
 
@@ -445,6 +937,7 @@ impl<'a> DumpState<'a> for StratFilesystem {
         StratFilesystemState {
             used: self.used,
             size: self.size(),
+            space_usage: self.space_usage,
         }
     }
 }
@@ -466,6 +959,35 @@ pub fn fs_usage(mount_point: &Path) -> StratisResult<(Bytes, Bytes)> {
     ))
 }
 
+/// The argument of the FITRIM ioctl, struct fstrim_range in linux/fs.h.
+#[repr(C)]
+pub struct FstrimRange {
+    start: u64,
+    len: u64,
+    minlen: u64,
+}
+
+ioctl_readwrite!(fitrim, b'X', 121, FstrimRange);
+
+/// Discard all the unused blocks of the filesystem mounted at mount_point,
+/// so that the thin pool can reclaim them. Return the number of bytes
+/// discarded, as reported by the filesystem.
+pub fn fs_trim(mount_point: &Path) -> StratisResult<Bytes> {
+    let file = File::open(mount_point)?;
+    let mut range = FstrimRange {
+        start: 0,
+        len: u64::MAX,
+        minlen: 0,
+    };
+    unsafe { fitrim(file.as_raw_fd(), &mut range) }.map_err(|e| {
+        StratisError::Msg(format!(
+            "Error discarding unused blocks of filesystem mounted at {} (FITRIM): {e}",
+            mount_point.display()
+        ))
+    })?;
+    Ok(Bytes::from(range.len))
+}
+
 impl<'a> Into<Value> for &'a StratFilesystem {
     fn into(self) -> Value {
         let mut json = Map::new();
@@ -473,6 +995,13 @@ impl<'a> Into<Value> for &'a StratFilesystem {
             "size".to_string(),
             Value::from(self.thindev_size().to_string()),
         );
+        json.insert("fs_type".to_string(), Value::from(self.fs_type.to_string()));
+        if let Some(ref mount_options) = self.mount_options {
+            json.insert(
+                "mount_options".to_string(),
+                Value::from(mount_options.to_string()),
+            );
+        }
         json.insert(
             "used".to_string(),
             Value::from(
@@ -481,6 +1010,35 @@ impl<'a> Into<Value> for &'a StratFilesystem {
                     .unwrap_or_else(|_| "Unavailable".to_string()),
             ),
         );
+        if let Some(space_usage) = self.space_usage {
+            json.insert(
+                "mapped".to_string(),
+                Value::from(space_usage.mapped.to_string()),
+            );
+            json.insert(
+                "exclusive".to_string(),
+                Value::from(space_usage.exclusive.to_string()),
+            );
+            json.insert(
+                "shared".to_string(),
+                Value::from(space_usage.shared.to_string()),
+            );
+        }
+        if let Some(size_limit) = self.size_limit {
+            json.insert(
+                "size_limit".to_string(),
+                Value::from(size_limit.to_string()),
+            );
+        }
+        if let Some(extend_config) = self.extend_config {
+            json.insert(
+                "extend_config".to_string(),
+                Value::from(extend_config.to_string()),
+            );
+        }
+        if let Some(origin) = self.origin {
+            json.insert("origin".to_string(), Value::from(origin.to_string()));
+        }
         Value::from(json)
     }
 }
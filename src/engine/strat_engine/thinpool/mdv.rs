@@ -30,7 +30,7 @@ use crate::{
             serde_structs::FilesystemSave,
             thinpool::filesystem::{fs_usage, StratFilesystem},
         },
-        types::{FilesystemUuid, Name, PoolUuid, StratisUuid},
+        types::{FilesystemType, FilesystemUuid, MkfsOptions, Name, PoolUuid, StratisUuid},
     },
     stratis::{StratisError, StratisResult},
 };
@@ -50,7 +50,13 @@ impl MetadataVol {
 
     /// Initialize a new Metadata Volume.
     pub fn initialize(pool_uuid: PoolUuid, dev: LinearDev) -> StratisResult<MetadataVol> {
-        create_fs(&dev.devnode(), Some(StratisUuid::Pool(pool_uuid)), false)?;
+        create_fs(
+            &dev.devnode(),
+            FilesystemType::Xfs,
+            Some(StratisUuid::Pool(pool_uuid)),
+            false,
+            &MkfsOptions::default(),
+        )?;
         MetadataVol::setup(pool_uuid, dev)
     }
 
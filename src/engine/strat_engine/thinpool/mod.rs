@@ -4,6 +4,7 @@
 
 mod filesystem;
 mod mdv;
+mod stream;
 mod thinids;
 #[allow(clippy::module_inception)]
 mod thinpool;
@@ -11,6 +12,9 @@ mod thinpool;
 #[cfg(test)]
 pub use self::thinpool::ThinPoolStatusDigest;
 pub use self::{
-    filesystem::StratFilesystem,
-    thinpool::{ThinPool, ThinPoolSizeParams, ThinPoolState, DATA_BLOCK_SIZE},
+    filesystem::{fs_trim, StratFilesystem},
+    stream::StreamHeader,
+    thinpool::{
+        ThinPool, ThinPoolSizeParams, ThinPoolState, DATA_BLOCK_SIZE, DEFAULT_EXTEND_POLICY,
+    },
 };
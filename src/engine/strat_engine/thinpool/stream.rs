@@ -0,0 +1,344 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Code to handle streams of filesystem contents sent from and received by a
+// thin pool.
+//
+// A stream consists of a header followed by a sequence of records, the last
+// of which is an end record. A data record is followed by the contents of
+// the range of sectors it describes. A discard record indicates that the
+// range of sectors it describes is no longer mapped. All integers are
+// encoded little-endian.
+
+use std::{
+    cmp::min,
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    os::unix::io::AsRawFd,
+};
+
+use uuid::Uuid;
+
+use devicemapper::{Bytes, Sectors, IEC};
+
+use crate::{
+    engine::types::{FilesystemType, FilesystemUuid},
+    stratis::{StratisError, StratisResult},
+};
+
+const STREAM_MAGIC: &[u8; 8] = b"STRATSND";
+const STREAM_VERSION: u8 = 2;
+
+const FS_TYPE_XFS: u8 = 0;
+const FS_TYPE_EXT4: u8 = 1;
+
+const RECORD_END: u8 = 0;
+const RECORD_DATA: u8 = 1;
+const RECORD_DISCARD: u8 = 2;
+
+// BLKDISCARD is _IO(0x12, 119) but takes a pointer to the range to discard.
+ioctl_write_ptr_bad!(blkdiscard, request_code_none!(0x12, 119), [u64; 2]);
+
+/// The header of a stream of filesystem contents.
+#[derive(Debug, Eq, PartialEq)]
+pub struct StreamHeader {
+    pub fs_type: FilesystemType,
+    /// The size of the thin device of the filesystem that was sent.
+    pub size: Sectors,
+    /// The UUID of the filesystem that was sent.
+    pub uuid: FilesystemUuid,
+    /// The UUID of the parent filesystem if the stream contains only the
+    /// differences between the filesystem and the parent.
+    pub parent: Option<FilesystemUuid>,
+}
+
+impl StreamHeader {
+    pub fn write<W: Write>(&self, w: &mut W) -> StratisResult<()> {
+        let fs_type = match self.fs_type {
+            FilesystemType::Xfs => FS_TYPE_XFS,
+            FilesystemType::Ext4 => FS_TYPE_EXT4,
+        };
+        w.write_all(STREAM_MAGIC)?;
+        w.write_all(&[STREAM_VERSION, fs_type, u8::from(self.parent.is_some())])?;
+        w.write_all(&self.size.to_le_bytes())?;
+        w.write_all(self.uuid.as_bytes())?;
+        if let Some(parent) = self.parent {
+            w.write_all(parent.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Whether the stream contains only the differences between the
+    /// filesystem and a parent filesystem.
+    pub fn incremental(&self) -> bool {
+        self.parent.is_some()
+    }
+
+    pub fn read<R: Read>(r: &mut R) -> StratisResult<StreamHeader> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != STREAM_MAGIC {
+            return Err(StratisError::Msg(
+                "Input is not a Stratis filesystem stream".to_string(),
+            ));
+        }
+        let mut flags = [0u8; 3];
+        r.read_exact(&mut flags)?;
+        let [version, fs_type, incremental] = flags;
+        if version != STREAM_VERSION {
+            return Err(StratisError::Msg(format!(
+                "Filesystem stream has version {version}, but only version {STREAM_VERSION} is supported"
+            )));
+        }
+        let fs_type = match fs_type {
+            FS_TYPE_XFS => FilesystemType::Xfs,
+            FS_TYPE_EXT4 => FilesystemType::Ext4,
+            _ => {
+                return Err(StratisError::Msg(format!(
+                    "Filesystem stream has unknown filesystem type {fs_type}"
+                )))
+            }
+        };
+        let size = Sectors(read_u64(r)?);
+        let uuid = read_uuid(r)?;
+        let parent = if incremental != 0 {
+            Some(read_uuid(r)?)
+        } else {
+            None
+        };
+        Ok(StreamHeader {
+            fs_type,
+            size,
+            uuid,
+            parent,
+        })
+    }
+}
+
+/// A record in a stream of filesystem contents.
+#[derive(Debug, Eq, PartialEq)]
+pub enum StreamRecord {
+    /// The range of the given length at the given offset, which is followed
+    /// by its contents.
+    Data(Sectors, Sectors),
+    /// The range of the given length at the given offset is not mapped.
+    Discard(Sectors, Sectors),
+    End,
+}
+
+impl StreamRecord {
+    pub fn write<W: Write>(&self, w: &mut W) -> StratisResult<()> {
+        match self {
+            StreamRecord::Data(offset, length) => {
+                w.write_all(&[RECORD_DATA])?;
+                w.write_all(&offset.to_le_bytes())?;
+                w.write_all(&length.to_le_bytes())?;
+            }
+            StreamRecord::Discard(offset, length) => {
+                w.write_all(&[RECORD_DISCARD])?;
+                w.write_all(&offset.to_le_bytes())?;
+                w.write_all(&length.to_le_bytes())?;
+            }
+            StreamRecord::End => w.write_all(&[RECORD_END])?,
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(r: &mut R) -> StratisResult<StreamRecord> {
+        let mut kind = [0u8; 1];
+        r.read_exact(&mut kind)?;
+        match kind[0] {
+            RECORD_END => Ok(StreamRecord::End),
+            RECORD_DATA => Ok(StreamRecord::Data(
+                Sectors(read_u64(r)?),
+                Sectors(read_u64(r)?),
+            )),
+            RECORD_DISCARD => Ok(StreamRecord::Discard(
+                Sectors(read_u64(r)?),
+                Sectors(read_u64(r)?),
+            )),
+            kind => Err(StratisError::Msg(format!(
+                "Filesystem stream contains a record of unknown kind {kind}"
+            ))),
+        }
+    }
+}
+
+fn read_u64<R: Read>(r: &mut R) -> StratisResult<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_uuid<R: Read>(r: &mut R) -> StratisResult<FilesystemUuid> {
+    let mut buf = [0u8; 16];
+    r.read_exact(&mut buf)?;
+    Ok(FilesystemUuid(Uuid::from_bytes(buf)))
+}
+
+/// Copy length sectors between r and w in chunks of no more than 1 MiB.
+fn copy_data<R: Read, W: Write>(r: &mut R, w: &mut W, length: Sectors) -> StratisResult<()> {
+    let chunk_size = Bytes::from(IEC::Mi).sectors();
+    let mut buf = vec![0u8; convert_const!(*chunk_size.bytes(), u128, usize)];
+    let mut remaining = length;
+    while remaining > Sectors(0) {
+        let to_copy = min(remaining, chunk_size);
+        let len = convert_int!(*to_copy.bytes(), u128, usize)?;
+        r.read_exact(&mut buf[..len])?;
+        w.write_all(&buf[..len])?;
+        remaining -= to_copy;
+    }
+    Ok(())
+}
+
+/// Write a data record with the contents of each of the given ranges of the
+/// device, a discard record for each of the given unmapped ranges, and an end
+/// record to w. Return the number of bytes of data written.
+pub fn send_ranges<W: Write>(
+    dev: &mut File,
+    data: &[(Sectors, Sectors)],
+    discards: &[(Sectors, Sectors)],
+    w: &mut W,
+) -> StratisResult<Bytes> {
+    let mut sent = Sectors(0);
+    for (offset, length) in data {
+        StreamRecord::Data(*offset, *length).write(w)?;
+        dev.seek(SeekFrom::Start(convert_int!(*offset.bytes(), u128, u64)?))?;
+        copy_data(dev, w, *length)?;
+        sent += *length;
+    }
+    for (offset, length) in discards {
+        StreamRecord::Discard(*offset, *length).write(w)?;
+    }
+    StreamRecord::End.write(w)?;
+    w.flush()?;
+    Ok(sent.bytes())
+}
+
+/// Apply the records read from r to the device, whose size is dev_size,
+/// until the end record is read. Return the number of bytes of data written.
+pub fn receive_ranges<R: Read>(
+    r: &mut R,
+    dev: &mut File,
+    dev_size: Sectors,
+) -> StratisResult<Bytes> {
+    let check_range = |offset: Sectors, length: Sectors| {
+        match offset.checked_add(*length) {
+            Some(end) if Sectors(end) <= dev_size => Ok(()),
+            _ => Err(StratisError::Msg(format!(
+                "Filesystem stream contains a range of {length} at offset {offset}, which exceeds the size of the filesystem, {dev_size}"
+            ))),
+        }
+    };
+
+    let mut received = Sectors(0);
+    loop {
+        match StreamRecord::read(r)? {
+            StreamRecord::Data(offset, length) => {
+                check_range(offset, length)?;
+                dev.seek(SeekFrom::Start(convert_int!(*offset.bytes(), u128, u64)?))?;
+                copy_data(r, dev, length)?;
+                received += length;
+            }
+            StreamRecord::Discard(offset, length) => {
+                check_range(offset, length)?;
+                let range = [
+                    convert_int!(*offset.bytes(), u128, u64)?,
+                    convert_int!(*length.bytes(), u128, u64)?,
+                ];
+                unsafe { blkdiscard(dev.as_raw_fd(), &range) }.map_err(|e| {
+                    StratisError::Msg(format!(
+                        "Failed to discard {length} at offset {offset}: {e}"
+                    ))
+                })?;
+            }
+            StreamRecord::End => break,
+        }
+    }
+    dev.sync_all()?;
+    Ok(received.bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_header_round_trip() {
+        for parent in [None, Some(FilesystemUuid::new_v4())] {
+            let header = StreamHeader {
+                fs_type: FilesystemType::Ext4,
+                size: Sectors(2 * IEC::Gi),
+                uuid: FilesystemUuid::new_v4(),
+                parent,
+            };
+            let mut buf = Vec::new();
+            header.write(&mut buf).unwrap();
+            assert_eq!(StreamHeader::read(&mut Cursor::new(buf)).unwrap(), header);
+        }
+
+        assert!(StreamHeader::read(&mut Cursor::new(b"STRATIS0\x01\x00\x00")).is_err());
+    }
+
+    #[test]
+    fn test_send_receive_ranges() {
+        let size = Bytes::from(4 * IEC::Mi).sectors();
+        let mut src = tempfile::tempfile().unwrap();
+        src.write_all(
+            &(0..*size.bytes())
+                .map(|i| (i % 251) as u8)
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        let mut dst = tempfile::tempfile().unwrap();
+        dst.set_len(convert_test!(*size.bytes(), u128, u64))
+            .unwrap();
+
+        let ranges = [(Sectors(0), Sectors(8)), (Sectors(2048), Sectors(4096))];
+        let mut stream = Vec::new();
+        assert_eq!(
+            send_ranges(&mut src, &ranges, &[], &mut stream).unwrap(),
+            Sectors(4104).bytes()
+        );
+        assert_eq!(
+            receive_ranges(&mut Cursor::new(stream), &mut dst, size).unwrap(),
+            Sectors(4104).bytes()
+        );
+
+        let mut src_data = Vec::new();
+        let mut dst_data = Vec::new();
+        src.seek(SeekFrom::Start(0)).unwrap();
+        src.read_to_end(&mut src_data).unwrap();
+        dst.seek(SeekFrom::Start(0)).unwrap();
+        dst.read_to_end(&mut dst_data).unwrap();
+        for (offset, length) in ranges {
+            let range = convert_test!(*offset.bytes(), u128, usize)
+                ..convert_test!(*(offset + length).bytes(), u128, usize);
+            assert_eq!(src_data[range.clone()], dst_data[range]);
+        }
+        assert!(dst_data[4096..1024 * 1024].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_receive_out_of_range() {
+        let mut stream = Vec::new();
+        StreamRecord::Data(Sectors(8), Sectors(8))
+            .write(&mut stream)
+            .unwrap();
+        stream.extend(vec![0u8; 8 * 512]);
+        StreamRecord::End.write(&mut stream).unwrap();
+        let mut dst = tempfile::tempfile().unwrap();
+        dst.set_len(8 * 512).unwrap();
+        assert!(receive_ranges(&mut Cursor::new(stream), &mut dst, Sectors(8)).is_err());
+
+        let mut stream = Vec::new();
+        StreamRecord::Discard(Sectors(u64::MAX), Sectors(16))
+            .write(&mut stream)
+            .unwrap();
+        StreamRecord::End.write(&mut stream).unwrap();
+        assert!(receive_ranges(&mut Cursor::new(stream), &mut dst, Sectors(8)).is_err());
+    }
+}
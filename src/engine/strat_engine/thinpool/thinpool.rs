@@ -8,6 +8,9 @@ use std::{
     cmp::{max, min, Ordering},
     collections::HashMap,
     fmt,
+    io::{Read, Write},
+    path::Path,
+    sync::Mutex,
     thread::scope,
 };
 
@@ -15,28 +18,38 @@ use retry::{delay::Fixed, retry_with_index};
 use serde_json::{Map, Value};
 
 use devicemapper::{
-    device_exists, Bytes, DataBlocks, Device, DmDevice, DmName, DmNameBuf, DmOptions,
+    device_exists, Bytes, DataBlocks, DevId, Device, DmDevice, DmName, DmNameBuf, DmOptions,
     FlakeyTargetParams, LinearDev, LinearDevTargetParams, LinearTargetParams, MetaBlocks, Sectors,
     TargetLine, ThinDevId, ThinPoolDev, ThinPoolStatus, ThinPoolStatusSummary, ThinPoolUsage, IEC,
 };
 
 use crate::{
     engine::{
-        engine::{DumpState, StateDiff},
+        engine::{DumpState, Filesystem, StateDiff},
         strat_engine::{
             backstore::Backstore,
-            cmd::{thin_check, thin_metadata_size, thin_repair},
+            cmd::{
+                thin_check, thin_delta, thin_dump_mappings, thin_ls, thin_metadata_size,
+                thin_repair,
+            },
             dm::get_dm,
             names::{
                 format_flex_ids, format_thin_ids, format_thinpool_ids, FlexRole, ThinPoolRole,
                 ThinRole,
             },
             serde_structs::{FlexDevsSave, Recordable, ThinPoolDevSave},
-            thinpool::{filesystem::StratFilesystem, mdv::MetadataVol, thinids::ThinDevIdPool},
+            thinpool::{
+                filesystem::StratFilesystem, mdv::MetadataVol, stream::StreamHeader,
+                thinids::ThinDevIdPool,
+            },
             writing::wipe_sectors,
         },
         structures::Table,
-        types::{Compare, FilesystemUuid, Name, PoolUuid, StratFilesystemDiff, ThinPoolDiff},
+        types::{
+            ActionAvailability, AllocationPurpose, Compare, FilesystemType, FilesystemUuid,
+            FsCreateOptions, FsExtendConfig, Name, PoolUuid, SnapshotPolicy, SpaceUsage,
+            StratFilesystemDiff, ThinPoolDiff, ThinPoolExtendPolicy,
+        },
     },
     stratis::{StratisError, StratisResult},
 };
@@ -55,29 +68,36 @@ const INITIAL_MDV_SIZE: Sectors = Sectors(IEC::Mi);
 use self::consts::{DATA_ALLOC_SIZE, DATA_LOWATER};
 #[cfg(not(test))]
 mod consts {
-    use super::{DataBlocks, IEC};
+    use super::{Sectors, IEC};
 
     // 50 GiB
-    pub const DATA_ALLOC_SIZE: DataBlocks = DataBlocks(50 * IEC::Ki);
+    pub const DATA_ALLOC_SIZE: Sectors = Sectors(100 * IEC::Mi);
     // 15 GiB
-    pub const DATA_LOWATER: DataBlocks = DataBlocks(15 * IEC::Ki);
+    pub const DATA_LOWATER: Sectors = Sectors(30 * IEC::Mi);
 }
 #[cfg(test)]
 mod consts {
-    use super::{DataBlocks, IEC};
+    use super::{Sectors, IEC};
 
     // 5 GiB
-    pub const DATA_ALLOC_SIZE: DataBlocks = DataBlocks(5 * IEC::Ki);
+    pub const DATA_ALLOC_SIZE: Sectors = Sectors(10 * IEC::Mi);
     // 4 GiB
-    pub const DATA_LOWATER: DataBlocks = DataBlocks(4 * IEC::Ki);
+    pub const DATA_LOWATER: Sectors = Sectors(8 * IEC::Mi);
 }
 
-fn sectors_to_datablocks(sectors: Sectors) -> DataBlocks {
-    DataBlocks(sectors / DATA_BLOCK_SIZE)
+/// The policy used to extend the thin pool data device if the user has not
+/// chosen another one.
+pub const DEFAULT_EXTEND_POLICY: ThinPoolExtendPolicy = ThinPoolExtendPolicy {
+    low_water: DATA_LOWATER,
+    extend_size: DATA_ALLOC_SIZE,
+};
+
+fn sectors_to_datablocks(sectors: Sectors, data_block_size: Sectors) -> DataBlocks {
+    DataBlocks(sectors / data_block_size)
 }
 
-fn datablocks_to_sectors(data_blocks: DataBlocks) -> Sectors {
-    *data_blocks * DATA_BLOCK_SIZE
+fn datablocks_to_sectors(data_blocks: DataBlocks, data_block_size: Sectors) -> Sectors {
+    *data_blocks * data_block_size
 }
 
 // Return all the useful identifying information for a particular thinpool
@@ -215,20 +235,24 @@ fn room_for_data(usable_size: Sectors, meta_size: Sectors) -> Sectors {
 pub struct ThinPoolSizeParams {
     meta_size: MetaBlocks,
     data_size: DataBlocks,
+    data_block_size: Sectors,
     mdv_size: Sectors,
 }
 
 impl ThinPoolSizeParams {
-    /// Create a new set of initial sizes for all flex devices.
-    pub fn new(total_usable: Sectors) -> StratisResult<Self> {
-        let meta_size = thin_metadata_size(DATA_BLOCK_SIZE, total_usable, DEFAULT_FS_LIMIT)?;
-        let data_size = min(
-            room_for_data(total_usable, meta_size),
-            datablocks_to_sectors(DATA_ALLOC_SIZE),
-        );
+    /// Create a new set of initial sizes for all flex devices. The data
+    /// device is made no larger than extend_size.
+    pub fn new(
+        total_usable: Sectors,
+        data_block_size: Sectors,
+        extend_size: Sectors,
+    ) -> StratisResult<Self> {
+        let meta_size = thin_metadata_size(data_block_size, total_usable, DEFAULT_FS_LIMIT)?;
+        let data_size = min(room_for_data(total_usable, meta_size), extend_size);
 
         Ok(ThinPoolSizeParams {
-            data_size: sectors_to_datablocks(data_size),
+            data_size: sectors_to_datablocks(data_size, data_block_size),
+            data_block_size,
             meta_size: meta_size.metablocks(),
             mdv_size: INITIAL_MDV_SIZE,
         })
@@ -240,7 +264,7 @@ impl ThinPoolSizeParams {
     }
     /// The number of Sectors in the DataBlocks.
     pub fn data_size(&self) -> Sectors {
-        datablocks_to_sectors(self.data_size)
+        datablocks_to_sectors(self.data_size, self.data_block_size)
     }
     /// MDV size
     pub fn mdv_size(&self) -> Sectors {
@@ -303,7 +327,12 @@ pub struct ThinPool {
     allocated_size: Sectors,
     fs_limit: u64,
     enable_overprov: bool,
+    fs_extend_config: FsExtendConfig,
+    extend_policy: ThinPoolExtendPolicy,
     out_of_meta_space: bool,
+    /// The thin pool supports a single metadata snapshot at a time, but
+    /// the metadata is read through it while the pool is only read locked.
+    metadata_snap: Mutex<()>,
 }
 
 impl ThinPool {
@@ -312,13 +341,14 @@ impl ThinPool {
         pool_uuid: PoolUuid,
         thin_pool_size: &ThinPoolSizeParams,
         data_block_size: Sectors,
+        extend_policy: ThinPoolExtendPolicy,
         backstore: &mut Backstore,
     ) -> StratisResult<ThinPool> {
         let mut segments_list = match backstore.request_alloc(&[
-            thin_pool_size.meta_size(),
-            thin_pool_size.meta_size(),
-            thin_pool_size.data_size(),
-            thin_pool_size.mdv_size(),
+            (thin_pool_size.meta_size(), AllocationPurpose::ThinMeta),
+            (thin_pool_size.meta_size(), AllocationPurpose::ThinMeta),
+            (thin_pool_size.data_size(), AllocationPurpose::ThinData),
+            (thin_pool_size.mdv_size(), AllocationPurpose::Mdv),
         ])? {
             Some(trans) => {
                 let segs = trans.get_backstore();
@@ -392,12 +422,12 @@ impl ThinPool {
             meta_dev,
             data_dev,
             data_block_size,
-            // Either set the low water mark to the standard low water mark if
-            // the device is larger than DATA_LOWATER or otherwise to half of the
+            // Either set the low water mark to the policy's low water mark if
+            // the device is larger than that or otherwise to half of the
             // capacity of the data device.
             min(
-                DATA_LOWATER,
-                DataBlocks((data_dev_size / DATA_BLOCK_SIZE) / 2),
+                sectors_to_datablocks(extend_policy.low_water, data_block_size),
+                DataBlocks((data_dev_size / data_block_size) / 2),
             ),
             vec![
                 "no_discard_passdown".to_string(),
@@ -423,7 +453,10 @@ impl ThinPool {
             allocated_size: backstore.datatier_allocated_size(),
             fs_limit: DEFAULT_FS_LIMIT,
             enable_overprov: true,
+            fs_extend_config: FsExtendConfig::default(),
+            extend_policy,
             out_of_meta_space: false,
+            metadata_snap: Mutex::new(()),
         })
     }
 
@@ -479,7 +512,7 @@ impl ThinPool {
             // This is a larger amount of free space than the actual amount of free
             // space currently which will cause the value to be updated when the
             // thinpool's check method is invoked.
-            sectors_to_datablocks(data_dev_size),
+            sectors_to_datablocks(data_dev_size, thin_pool_save.data_block_size),
             thin_pool_save
                 .feature_args
                 .as_ref()
@@ -562,7 +595,12 @@ impl ThinPool {
             allocated_size: backstore.datatier_allocated_size(),
             fs_limit,
             enable_overprov: thin_pool_save.enable_overprov.unwrap_or(true),
+            fs_extend_config: thin_pool_save.fs_extend_config.unwrap_or_default(),
+            extend_policy: thin_pool_save
+                .extend_policy
+                .unwrap_or(DEFAULT_EXTEND_POLICY),
             out_of_meta_space: false,
+            metadata_snap: Mutex::new(()),
         })
     }
 
@@ -575,8 +613,12 @@ impl ThinPool {
     /// Get the last cached value for the total amount of space used on the
     /// thin pool in the data and metadata devices.
     fn used(&self) -> Option<(Sectors, MetaBlocks)> {
-        status_to_usage(self.thin_pool_status.as_ref())
-            .map(|u| (datablocks_to_sectors(u.used_data), u.used_meta))
+        status_to_usage(self.thin_pool_status.as_ref()).map(|u| {
+            (
+                datablocks_to_sectors(u.used_data, self.thin_pool.data_block_size()),
+                u.used_meta,
+            )
+        })
     }
 
     /// Run status checks and take actions on the thinpool and its components.
@@ -624,7 +666,7 @@ impl ThinPool {
         }
 
         if let Some((data_usage, _)) = self.used() {
-            if self.thin_pool.data_dev().size() - data_usage < datablocks_to_sectors(DATA_LOWATER)
+            if self.thin_pool.data_dev().size() - data_usage < self.extend_policy.low_water
                 && !self.out_of_alloc_space()
             {
                 let amount_allocated = match self.extend_thin_data_device(pool_uuid, backstore) {
@@ -640,7 +682,13 @@ impl ThinPool {
                 };
                 should_save |= amount_allocated != Sectors(0);
 
-                self.thin_pool.set_low_water_mark(get_dm(), DATA_LOWATER)?;
+                self.thin_pool.set_low_water_mark(
+                    get_dm(),
+                    sectors_to_datablocks(
+                        self.extend_policy.low_water,
+                        self.thin_pool.data_block_size(),
+                    ),
+                )?;
                 self.resume()?;
             }
         }
@@ -658,6 +706,82 @@ impl ThinPool {
             .sum()
     }
 
+    /// Invoke f on the metadata device of the thin pool while a snapshot of
+    /// the thin pool metadata is reserved. The thin provisioning tools may
+    /// only read the metadata of a live thin pool through such a snapshot.
+    /// Only one snapshot may be reserved at a time, so concurrent callers
+    /// are serialized.
+    fn with_metadata_snap<F, T>(&self, f: F) -> StratisResult<T>
+    where
+        F: FnOnce(&Path) -> StratisResult<T>,
+    {
+        let _snap = self
+            .metadata_snap
+            .lock()
+            .expect("f does not panic while the lock is held");
+        get_dm().target_msg(
+            &DevId::Name(self.thin_pool.name()),
+            None,
+            "reserve_metadata_snap",
+        )?;
+        let res = f(&self.thin_pool.meta_dev().devnode());
+        let release = get_dm().target_msg(
+            &DevId::Name(self.thin_pool.name()),
+            None,
+            "release_metadata_snap",
+        );
+        match (res, release) {
+            (Ok(t), Ok(_)) => Ok(t),
+            (Err(e), Ok(_)) => Err(e),
+            (Ok(_), Err(e)) => Err(StratisError::from(e)),
+            (Err(e), Err(release_err)) => Err(StratisError::BestEffortError(
+                "Reading the thin pool metadata and releasing the thin pool metadata snapshot both failed".to_string(),
+                vec![e, StratisError::from(release_err)],
+            )),
+        }
+    }
+
+    /// Calculate the mapped, exclusive, and shared space of every thin device
+    /// on this thin pool from a snapshot of the thin pool metadata. This may
+    /// take a long time, so it only requires read access to the thin pool.
+    pub fn space_usage(&self) -> StratisResult<HashMap<ThinDevId, SpaceUsage>> {
+        let blocks = self.with_metadata_snap(thin_ls)?;
+
+        let data_block_size = self.thin_pool.data_block_size();
+        let to_bytes = |blocks: DataBlocks| datablocks_to_sectors(blocks, data_block_size).bytes();
+        Ok(blocks
+            .into_iter()
+            .map(|(thin_id, (mapped, exclusive, shared))| {
+                (
+                    thin_id,
+                    SpaceUsage {
+                        mapped: to_bytes(mapped),
+                        exclusive: to_bytes(exclusive),
+                        shared: to_bytes(shared),
+                    },
+                )
+            })
+            .collect())
+    }
+
+    /// Set the space usage calculated by space_usage() on the filesystems of
+    /// this thin pool and return the filesystems whose space usage changed.
+    pub fn set_space_usage(
+        &mut self,
+        usage: &HashMap<ThinDevId, SpaceUsage>,
+    ) -> HashMap<FilesystemUuid, StratFilesystemDiff> {
+        let mut updated = HashMap::default();
+        for (_, uuid, fs) in self.filesystems.iter_mut() {
+            let orig = fs.cached();
+            fs.set_space_usage(usage.get(&fs.thin_id()).copied());
+            let diff = orig.diff(&fs.dump(()));
+            if diff.space_usage.is_changed() {
+                updated.insert(*uuid, diff);
+            }
+        }
+        updated
+    }
+
     /// Check all filesystems on this thin pool and return which had their sizes
     /// extended, if any. This method should not need to handle thin pool status
     /// because it never alters the thin pool itself.
@@ -683,6 +807,8 @@ impl ThinPool {
             return Ok(HashMap::default());
         };
 
+        let fs_extend_config = self.fs_extend_config;
+
         scope(|s| {
             // This collect is needed to ensure all threads are spawned in
             // parallel, not each thread being spawned and immediately joined
@@ -693,10 +819,16 @@ impl ThinPool {
                 .filesystems
                 .iter_mut()
                 .filter_map(|(name, uuid, fs)| {
-                    if let Some(mt_pt) = fs.should_extend() {
+                    let config = fs.extend_config().unwrap_or(fs_extend_config);
+                    if !config.enabled {
+                        return None;
+                    }
+                    if let Some(mt_pt) = fs.should_extend(config.threshold) {
                         let extend_size = StratFilesystem::extend_size(
                             fs.thindev_size(),
                             remaining_space.as_mut(),
+                            fs.size_limit(),
+                            config.increment,
                         );
                         if extend_size == Sectors(0) {
                             None
@@ -872,6 +1004,36 @@ impl ThinPool {
             .contains("error_if_no_space")
     }
 
+    /// Returns true if discards issued to the thin devices are passed down
+    /// to the devices beneath the pool.
+    pub fn discard_passdown(&self) -> bool {
+        !self
+            .thin_pool
+            .table()
+            .table
+            .params
+            .feature_args
+            .contains("no_discard_passdown")
+    }
+
+    /// Enable or disable passing discards issued to the thin devices down to
+    /// the devices beneath the pool. The setting is recorded in the feature
+    /// arguments of the thin pool table and is therefore persisted with it.
+    ///
+    /// Returns true if the setting was changed.
+    pub fn set_discard_passdown(&mut self, enabled: bool) -> StratisResult<bool> {
+        if self.discard_passdown() == enabled {
+            return Ok(false);
+        }
+
+        if enabled {
+            self.thin_pool.discard_passdown(get_dm())?;
+        } else {
+            self.thin_pool.no_discard_passdown(get_dm())?;
+        }
+        Ok(true)
+    }
+
     /// Extend thinpool's data dev.
     ///
     /// This method returns the extension size as Ok(data_extension).
@@ -896,7 +1058,7 @@ impl ThinPool {
                 .device()
                 .expect("If request succeeded, backstore must have cap device.");
 
-            let requests = vec![data_extend_size];
+            let requests = vec![(data_extend_size, AllocationPurpose::ThinData)];
             let data_index = 0;
             match backstore.request_alloc(&requests) {
                 Ok(Some(transaction)) => {
@@ -941,12 +1103,14 @@ impl ThinPool {
         }
 
         let available_size = backstore.available_in_backstore();
-        let data_ext = min(sectors_to_datablocks(available_size), DATA_ALLOC_SIZE);
+        let data_block_size = self.thin_pool.data_block_size();
+        let extend_size = self.extend_policy.extend_size;
+        let data_ext = sectors_to_datablocks(min(available_size, extend_size), data_block_size);
         if data_ext == DataBlocks(0) {
             return (
                 self.set_error_mode(),
                 Err(StratisError::OutOfSpaceError(format!(
-                    "{DATA_ALLOC_SIZE} requested but no space is available"
+                    "{extend_size} requested but no space is available"
                 ))),
             );
         }
@@ -956,7 +1120,7 @@ impl ThinPool {
             backstore,
             pool_uuid,
             &mut self.segments.data_segments,
-            datablocks_to_sectors(data_ext),
+            datablocks_to_sectors(data_ext, data_block_size),
         );
 
         match res {
@@ -994,7 +1158,10 @@ impl ThinPool {
                 .device()
                 .expect("If request succeeded, backstore must have cap device.");
 
-            let requests = vec![meta_extend_size, meta_extend_size];
+            let requests = vec![
+                (meta_extend_size, AllocationPurpose::ThinMeta),
+                (meta_extend_size, AllocationPurpose::ThinMeta),
+            ];
             let meta_index = 0;
             let spare_index = 1;
             match backstore.request_alloc(&requests) {
@@ -1061,7 +1228,7 @@ impl ThinPool {
             )
         } else {
             match thin_metadata_size(
-                DATA_BLOCK_SIZE,
+                self.thin_pool.data_block_size(),
                 backstore.datatier_usable_size(),
                 new_thin_limit.unwrap_or(self.fs_limit),
             ) {
@@ -1165,9 +1332,162 @@ impl ThinPool {
         pool_uuid: PoolUuid,
         name: &str,
         size: Sectors,
+        size_limit: Option<Sectors>,
+        fs_type: FilesystemType,
+        options: &FsCreateOptions,
+    ) -> StratisResult<FilesystemUuid> {
+        let (fs_uuid, new_filesystem) = StratFilesystem::initialize(
+            pool_uuid,
+            &self.thin_pool,
+            size,
+            size_limit,
+            fs_type,
+            options,
+            self.id_gen.new_id()?,
+        )?;
+        self.add_filesystem(pool_name, name, fs_uuid, new_filesystem)?;
+        Ok(fs_uuid)
+    }
+
+    /// Create a filesystem with the given name from the contents of a
+    /// complete stream. The header of the stream has already been read from
+    /// stream.
+    pub fn receive_filesystem<R: Read>(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        name: &str,
+        header: &StreamHeader,
+        stream: &mut R,
     ) -> StratisResult<FilesystemUuid> {
-        let (fs_uuid, mut new_filesystem) =
-            StratFilesystem::initialize(pool_uuid, &self.thin_pool, size, self.id_gen.new_id()?)?;
+        if header.incremental() {
+            return Err(StratisError::Msg(
+                "An incremental stream can only be applied to an existing filesystem".to_string(),
+            ));
+        }
+        let (fs_uuid, new_filesystem) = StratFilesystem::receive(
+            pool_uuid,
+            &self.thin_pool,
+            header,
+            stream,
+            self.id_gen.new_id()?,
+        )?;
+        self.add_filesystem(pool_name, name, fs_uuid, new_filesystem)?;
+        Ok(fs_uuid)
+    }
+
+    /// Apply the contents of an incremental stream to the filesystem with
+    /// the given UUID. The header of the stream has already been read from
+    /// stream.
+    pub fn receive_filesystem_delta<R: Read>(
+        &mut self,
+        pool_name: &str,
+        fs_uuid: FilesystemUuid,
+        header: &StreamHeader,
+        stream: &mut R,
+    ) -> StratisResult<Bytes> {
+        if !header.incremental() {
+            return Err(StratisError::Msg(
+                "A complete stream can only be received as a new filesystem".to_string(),
+            ));
+        }
+        let (name, fs) = self.filesystems.get_mut_by_uuid(fs_uuid).ok_or_else(|| {
+            StratisError::Msg(format!("Filesystem with UUID {fs_uuid} not found"))
+        })?;
+        if fs.fs_type() != header.fs_type {
+            return Err(StratisError::Msg(format!(
+                "Stream of a filesystem of type {} can not be applied to filesystem with UUID {fs_uuid} of type {}",
+                header.fs_type,
+                fs.fs_type()
+            )));
+        }
+        // The stream can only be applied to the filesystem it was generated
+        // against or to the filesystem that last received that filesystem's
+        // contents.
+        if let Some(parent) = header
+            .parent
+            .filter(|parent| *parent != fs_uuid && Some(*parent) != fs.received_uuid())
+        {
+            return Err(StratisError::Msg(format!(
+                "Stream generated against filesystem with UUID {parent} can not be applied to filesystem with UUID {fs_uuid}"
+            )));
+        }
+        let received = match fs.receive_delta(fs_uuid, header, stream) {
+            Ok(received) => received,
+            Err(e) => {
+                // The thin device may have been extended before the stream
+                // could not be applied; its size must be recorded regardless.
+                if let Err(save_err) = self.mdv.save_fs(&name, fs_uuid, fs) {
+                    warn!(
+                        "Failed to save the metadata of filesystem with UUID {} after receiving a stream failed: {}",
+                        fs_uuid, save_err
+                    );
+                }
+                return Err(e);
+            }
+        };
+        self.mdv.save_fs(&name, fs_uuid, fs)?;
+        fs.udev_fs_change(pool_name, fs_uuid, &name);
+        Ok(received)
+    }
+
+    /// Write the contents of the filesystem with the given UUID to w as a
+    /// stream. If parent_uuid is specified, only the ranges in which the
+    /// filesystem differs from the parent are written. Return the number of
+    /// bytes of filesystem data written.
+    pub fn send_filesystem<W: Write>(
+        &self,
+        fs_uuid: FilesystemUuid,
+        parent_uuid: Option<FilesystemUuid>,
+        w: &mut W,
+    ) -> StratisResult<Bytes> {
+        let not_found = |uuid| StratisError::Msg(format!("Filesystem with UUID {uuid} not found"));
+        let (_, fs) = self
+            .get_filesystem_by_uuid(fs_uuid)
+            .ok_or_else(|| not_found(fs_uuid))?;
+
+        let data_block_size = self.thin_pool.data_block_size();
+        let to_sectors = |ranges: Vec<(DataBlocks, DataBlocks)>| {
+            ranges
+                .into_iter()
+                .map(|(begin, length)| {
+                    (
+                        datablocks_to_sectors(begin, data_block_size),
+                        datablocks_to_sectors(length, data_block_size),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let (data, discards) = match parent_uuid {
+            Some(parent_uuid) => {
+                let (_, parent) = self
+                    .get_filesystem_by_uuid(parent_uuid)
+                    .ok_or_else(|| not_found(parent_uuid))?;
+                let (changed, removed) = self.with_metadata_snap(|meta_dev| {
+                    thin_delta(meta_dev, parent.thin_id(), fs.thin_id())
+                })?;
+                (to_sectors(changed), to_sectors(removed))
+            }
+            None => (
+                to_sectors(
+                    self.with_metadata_snap(|meta_dev| thin_dump_mappings(meta_dev, fs.thin_id()))?,
+                ),
+                Vec::new(),
+            ),
+        };
+        fs.send(fs_uuid, parent_uuid, &data, &discards, w)
+    }
+
+    /// Record a newly created filesystem in the metadata and add it to the
+    /// filesystems of this thin pool. The filesystem is destroyed if it can
+    /// not be recorded.
+    fn add_filesystem(
+        &mut self,
+        pool_name: &str,
+        name: &str,
+        fs_uuid: FilesystemUuid,
+        mut new_filesystem: StratFilesystem,
+    ) -> StratisResult<()> {
         let name = Name::new(name.to_owned());
         if let Err(err) = self.mdv.save_fs(&name, fs_uuid, &new_filesystem) {
             if let Err(err2) = retry_with_index(Fixed::from_millis(100).take(4), |i| {
@@ -1191,7 +1511,7 @@ impl ThinPool {
             .expect("Inserted above");
         fs.udev_fs_change(pool_name, fs_uuid, &name);
 
-        Ok(fs_uuid)
+        Ok(())
     }
 
     /// Create a filesystem snapshot of the origin.  Given origin_uuid
@@ -1202,14 +1522,16 @@ impl ThinPool {
         pool_uuid: PoolUuid,
         origin_uuid: FilesystemUuid,
         snapshot_name: &str,
+        scheduled_by: Option<&str>,
     ) -> StratisResult<(FilesystemUuid, &mut StratFilesystem)> {
         let snapshot_fs_uuid = FilesystemUuid::new_v4();
         let (snapshot_dm_name, snapshot_dm_uuid) =
             format_thin_ids(pool_uuid, ThinRole::Filesystem(snapshot_fs_uuid));
         let snapshot_id = self.id_gen.new_id()?;
-        let new_filesystem = match self.get_filesystem_by_uuid(origin_uuid) {
+        let mut new_filesystem = match self.get_filesystem_by_uuid(origin_uuid) {
             Some((fs_name, filesystem)) => filesystem.snapshot(
                 &self.thin_pool,
+                origin_uuid,
                 snapshot_name,
                 &snapshot_dm_name,
                 Some(&snapshot_dm_uuid),
@@ -1223,6 +1545,9 @@ impl ThinPool {
                 ));
             }
         };
+        if let Some(policy_name) = scheduled_by {
+            new_filesystem.set_scheduled_by(origin_uuid, policy_name);
+        }
         let new_fs_name = Name::new(snapshot_name.to_owned());
         self.mdv
             .save_fs(&new_fs_name, snapshot_fs_uuid, &new_filesystem)?;
@@ -1265,6 +1590,7 @@ impl ThinPool {
                                pool_name,
                                err);
                     }
+                    self.unset_origins(uuid);
                     Ok(Some(uuid))
                 }
                 Err(err) => {
@@ -1276,6 +1602,129 @@ impl ThinPool {
         }
     }
 
+    /// Forget the origin of every filesystem that is a snapshot of the
+    /// filesystem with the given UUID and update the metadata of those
+    /// filesystems.
+    fn unset_origins(&mut self, origin_uuid: FilesystemUuid) {
+        for (name, uuid, fs) in self.filesystems.iter_mut() {
+            if fs.origin() == Some(origin_uuid) && fs.unset_origin() {
+                if let Err(err) = self.mdv.save_fs(name, *uuid, fs) {
+                    warn!(
+                        "Could not update metadata for fs with UUID {} and name {}, reason: {:?}",
+                        uuid, name, err
+                    );
+                }
+            }
+        }
+    }
+
+    /// Revert a filesystem to one of its snapshots. The origin filesystem
+    /// keeps its UUID and name but takes over the snapshot's data. The
+    /// origin's previous data is destroyed along with the snapshot.
+    ///
+    /// * Ok(Some(snapshot_uuid)) is returned if the revert succeeded
+    /// * Ok(None) is returned if either filesystem did not exist
+    /// * Err(_) is returned if the revert could not be completed
+    pub fn revert_filesystem(
+        &mut self,
+        pool_name: &str,
+        pool_uuid: PoolUuid,
+        origin_uuid: FilesystemUuid,
+        snapshot_uuid: FilesystemUuid,
+    ) -> StratisResult<Option<FilesystemUuid>> {
+        if origin_uuid == snapshot_uuid {
+            return Err(StratisError::Msg(
+                "A filesystem can not be reverted to itself".into(),
+            ));
+        }
+
+        let (origin_name, mut origin) = match self.filesystems.remove_by_uuid(origin_uuid) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let (snapshot_name, mut snapshot) = match self.filesystems.remove_by_uuid(snapshot_uuid) {
+            Some(entry) => entry,
+            None => {
+                self.filesystems.insert(origin_name, origin_uuid, origin);
+                return Ok(None);
+            }
+        };
+
+        if let Err(err) = origin.swap_thin_devs(
+            pool_uuid,
+            &self.thin_pool,
+            origin_uuid,
+            &mut snapshot,
+            snapshot_uuid,
+        ) {
+            self.filesystems.insert(origin_name, origin_uuid, origin);
+            self.filesystems
+                .insert(snapshot_name, snapshot_uuid, snapshot);
+            return Err(err);
+        }
+
+        // The snapshot's record is removed before the origin's record is
+        // updated, as the records of both filesystems would otherwise refer
+        // to the same thin device until both were updated.
+        let res = self
+            .mdv
+            .rm_fs(snapshot_uuid)
+            .and_then(|_| self.mdv.save_fs(&origin_name, origin_uuid, &origin));
+        if let Err(err) = res {
+            let err = match origin
+                .swap_thin_devs(
+                    pool_uuid,
+                    &self.thin_pool,
+                    origin_uuid,
+                    &mut snapshot,
+                    snapshot_uuid,
+                )
+                .and_then(|_| self.mdv.save_fs(&snapshot_name, snapshot_uuid, &snapshot))
+            {
+                Ok(_) => err,
+                Err(rollback) => StratisError::RollbackError {
+                    causal_error: Box::new(err),
+                    rollback_error: Box::new(rollback),
+                    level: ActionAvailability::NoPoolChanges,
+                },
+            };
+            self.filesystems.insert(origin_name, origin_uuid, origin);
+            self.filesystems
+                .insert(snapshot_name, snapshot_uuid, snapshot);
+            return Err(err);
+        }
+        self.filesystems.insert(origin_name, origin_uuid, origin);
+
+        // The snapshot's thin device now holds the origin's previous data.
+        match snapshot.destroy(&self.thin_pool) {
+            Ok(_) => {
+                self.clear_out_of_meta_flag();
+                self.unset_origins(snapshot_uuid);
+            }
+            Err(err) => {
+                // Keep the snapshot, which now holds the origin's previous
+                // data, so that its thin device is not leaked.
+                if let Err(e) = self.mdv.save_fs(&snapshot_name, snapshot_uuid, &snapshot) {
+                    warn!(
+                        "Could not update metadata for fs with UUID {} and name {} belonging to pool {}, reason: {:?}",
+                        snapshot_uuid, snapshot_name, pool_name, e
+                    );
+                }
+                self.filesystems
+                    .insert(snapshot_name, snapshot_uuid, snapshot);
+                return Err(err);
+            }
+        }
+
+        let (origin_name, origin) = self
+            .filesystems
+            .get_by_uuid(origin_uuid)
+            .expect("Inserted above");
+        origin.udev_fs_change(pool_name, origin_uuid, &origin_name);
+
+        Ok(Some(snapshot_uuid))
+    }
+
     #[cfg(test)]
     pub fn state(&self) -> Option<ThinPoolStatusDigest> {
         self.thin_pool_status.as_ref().map(|s| s.into())
@@ -1313,6 +1762,85 @@ impl ThinPool {
         }
     }
 
+    /// Set the size limit of a filesystem within the thin pool.
+    ///
+    /// * Ok(Some(true)) is returned if the limit was changed.
+    /// * Ok(Some(false)) is returned if the limit was already set.
+    /// * Ok(None) is returned if the filesystem does not exist.
+    pub fn set_fs_size_limit(
+        &mut self,
+        uuid: FilesystemUuid,
+        size_limit: Option<Sectors>,
+    ) -> StratisResult<Option<bool>> {
+        let (name, fs) = match self.filesystems.get_mut_by_uuid(uuid) {
+            Some(tup) => tup,
+            None => return Ok(None),
+        };
+        let old_limit = fs.size_limit();
+        if !fs.set_size_limit(size_limit) {
+            return Ok(Some(false));
+        }
+        if let Err(err) = self.mdv.save_fs(&name, uuid, fs) {
+            fs.set_size_limit(old_limit);
+            Err(err)
+        } else {
+            Ok(Some(true))
+        }
+    }
+
+    /// Set the configuration of the automatic extension of a filesystem
+    /// within the thin pool.
+    ///
+    /// * Ok(Some(true)) is returned if the configuration was changed.
+    /// * Ok(Some(false)) is returned if the configuration was already set.
+    /// * Ok(None) is returned if the filesystem does not exist.
+    pub fn set_fs_extend_config(
+        &mut self,
+        uuid: FilesystemUuid,
+        config: Option<FsExtendConfig>,
+    ) -> StratisResult<Option<bool>> {
+        let (name, fs) = match self.filesystems.get_mut_by_uuid(uuid) {
+            Some(tup) => tup,
+            None => return Ok(None),
+        };
+        let old_config = fs.extend_config();
+        if !fs.set_extend_config(config) {
+            return Ok(Some(false));
+        }
+        if let Err(err) = self.mdv.save_fs(&name, uuid, fs) {
+            fs.set_extend_config(old_config);
+            Err(err)
+        } else {
+            Ok(Some(true))
+        }
+    }
+
+    /// Set the snapshot policies of a filesystem within the thin pool.
+    ///
+    /// * Ok(Some(true)) is returned if the policies were changed.
+    /// * Ok(Some(false)) is returned if the policies were already set.
+    /// * Ok(None) is returned if the filesystem does not exist.
+    pub fn set_fs_snapshot_policies(
+        &mut self,
+        uuid: FilesystemUuid,
+        policies: &[SnapshotPolicy],
+    ) -> StratisResult<Option<bool>> {
+        let (name, fs) = match self.filesystems.get_mut_by_uuid(uuid) {
+            Some(tup) => tup,
+            None => return Ok(None),
+        };
+        let old_policies = fs.snapshot_policies().to_vec();
+        if !fs.set_snapshot_policies(policies) {
+            return Ok(Some(false));
+        }
+        if let Err(err) = self.mdv.save_fs(&name, uuid, fs) {
+            fs.set_snapshot_policies(&old_policies);
+            Err(err)
+        } else {
+            Ok(Some(true))
+        }
+    }
+
     /// The names of DM devices belonging to this pool that may generate events
     pub fn get_eventing_dev_names(&self, pool_uuid: PoolUuid) -> Vec<DmNameBuf> {
         let mut eventing = vec![
@@ -1508,6 +2036,50 @@ impl ThinPool {
         }
     }
 
+    /// The size of the blocks in which the thin pool allocates data.
+    pub fn data_block_size(&self) -> Sectors {
+        self.thin_pool.data_block_size()
+    }
+
+    /// The policy according to which the thin pool data device is extended.
+    pub fn extend_policy(&self) -> ThinPoolExtendPolicy {
+        self.extend_policy
+    }
+
+    /// Set the policy according to which the thin pool data device is
+    /// extended and update the low water mark of the thin pool device
+    /// accordingly. Returns true if the policy changed.
+    pub fn set_extend_policy(&mut self, policy: ThinPoolExtendPolicy) -> StratisResult<bool> {
+        if self.extend_policy == policy {
+            return Ok(false);
+        }
+
+        self.thin_pool.set_low_water_mark(
+            get_dm(),
+            sectors_to_datablocks(policy.low_water, self.thin_pool.data_block_size()),
+        )?;
+        self.resume()?;
+        self.extend_policy = policy;
+        Ok(true)
+    }
+
+    /// The configuration of the automatic extension of the filesystems that do
+    /// not have a configuration of their own.
+    pub fn default_fs_extend_config(&self) -> FsExtendConfig {
+        self.fs_extend_config
+    }
+
+    /// Set the default configuration of the automatic extension of the
+    /// filesystems. Returns true if the configuration changed.
+    pub fn set_default_fs_extend_config(&mut self, config: FsExtendConfig) -> bool {
+        if self.fs_extend_config == config {
+            false
+        } else {
+            self.fs_extend_config = config;
+            true
+        }
+    }
+
     /// Indicate to the pool that it may now have more room for metadata growth.
     pub fn clear_out_of_meta_flag(&mut self) {
         self.out_of_meta_space = false;
@@ -1599,6 +2171,8 @@ impl Recordable<ThinPoolDevSave> for ThinPool {
             feature_args: Some(self.thin_pool.table().table.params.feature_args.clone()),
             fs_limit: Some(self.fs_limit),
             enable_overprov: Some(self.enable_overprov),
+            fs_extend_config: Some(self.fs_extend_config),
+            extend_policy: Some(self.extend_policy),
         }
     }
 }
@@ -1673,7 +2247,7 @@ mod tests {
         path::Path,
     };
 
-    use nix::mount::{mount, MsFlags};
+    use nix::mount::{mount, umount, MsFlags};
 
     use devicemapper::{Bytes, SECTOR_SIZE};
 
@@ -1687,6 +2261,7 @@ mod tests {
             tests::{loopbacked, real},
             writing::SyncAll,
         },
+        types::{CacheConfig, PoolCreateOptions},
     };
 
     use super::*;
@@ -1720,11 +2295,28 @@ mod tests {
 
         let devices = get_devices(paths).unwrap();
 
-        let mut backstore =
-            Backstore::initialize(pool_name, pool_uuid, devices, MDADataSize::default(), None)
-                .unwrap();
-        let size = ThinPoolSizeParams::new(backstore.datatier_usable_size()).unwrap();
-        let mut pool = ThinPool::new(pool_uuid, &size, DATA_BLOCK_SIZE, &mut backstore).unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_name,
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            PoolCreateOptions::default(),
+        )
+        .unwrap();
+        let size = ThinPoolSizeParams::new(
+            backstore.datatier_usable_size(),
+            DATA_BLOCK_SIZE,
+            DATA_ALLOC_SIZE,
+        )
+        .unwrap();
+        let mut pool = ThinPool::new(
+            pool_uuid,
+            &size,
+            DATA_BLOCK_SIZE,
+            DEFAULT_EXTEND_POLICY,
+            &mut backstore,
+        )
+        .unwrap();
 
         let init_data_size = size.data_size();
         let init_meta_size = size.meta_size();
@@ -1747,6 +2339,9 @@ mod tests {
                 pool_uuid,
                 format!("testfs{i}").as_str(),
                 Sectors(2 * IEC::Gi),
+                None,
+                FilesystemType::Xfs,
+                &FsCreateOptions::default(),
             )
             .unwrap();
             i += 1;
@@ -1754,7 +2349,7 @@ mod tests {
             let init_used = pool.used().unwrap().0;
             let init_size = pool.thin_pool.data_dev().size();
             let (changed, diff) = pool.check(pool_uuid, &mut backstore).unwrap();
-            if init_size - init_used < datablocks_to_sectors(DATA_LOWATER) {
+            if init_size - init_used < DATA_LOWATER {
                 assert!(changed);
                 assert!(diff.allocated_size.is_changed());
                 break;
@@ -1763,10 +2358,13 @@ mod tests {
 
         assert_eq!(
             init_data_size
-                + datablocks_to_sectors(min(
-                    DATA_ALLOC_SIZE,
-                    sectors_to_datablocks(available_on_start),
-                )),
+                + datablocks_to_sectors(
+                    sectors_to_datablocks(
+                        min(DATA_ALLOC_SIZE, available_on_start),
+                        DATA_BLOCK_SIZE
+                    ),
+                    DATA_BLOCK_SIZE,
+                ),
             pool.thin_pool.data_dev().size(),
         );
         assert_eq!(
@@ -1816,13 +2414,19 @@ mod tests {
             pool_uuid,
             first_devices,
             MDADataSize::default(),
-            None,
+            PoolCreateOptions::default(),
         )
         .unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
-            &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
+            &ThinPoolSizeParams::new(
+                backstore.available_in_backstore(),
+                DATA_BLOCK_SIZE,
+                DATA_ALLOC_SIZE,
+            )
+            .unwrap(),
             DATA_BLOCK_SIZE,
+            DEFAULT_EXTEND_POLICY,
             &mut backstore,
         )
         .unwrap();
@@ -1833,6 +2437,9 @@ mod tests {
                 pool_uuid,
                 "stratis_test_filesystem",
                 DEFAULT_THIN_DEV_SIZE,
+                None,
+                FilesystemType::Xfs,
+                &FsCreateOptions::default(),
             )
             .unwrap();
 
@@ -1954,20 +2561,34 @@ mod tests {
             pool_uuid,
             devices,
             MDADataSize::default(),
-            None,
+            PoolCreateOptions::default(),
         )
         .unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
-            &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
+            &ThinPoolSizeParams::new(
+                backstore.available_in_backstore(),
+                DATA_BLOCK_SIZE,
+                DATA_ALLOC_SIZE,
+            )
+            .unwrap(),
             DATA_BLOCK_SIZE,
+            DEFAULT_EXTEND_POLICY,
             &mut backstore,
         )
         .unwrap();
 
         let filesystem_name = "stratis_test_filesystem";
         let fs_uuid = pool
-            .create_filesystem(pool_name, pool_uuid, filesystem_name, DEFAULT_THIN_DEV_SIZE)
+            .create_filesystem(
+                pool_name,
+                pool_uuid,
+                filesystem_name,
+                DEFAULT_THIN_DEV_SIZE,
+                None,
+                FilesystemType::Xfs,
+                &FsCreateOptions::default(),
+            )
             .unwrap();
 
         cmd::udev_settle().unwrap();
@@ -2009,8 +2630,9 @@ mod tests {
 
         let snapshot_name = "test_snapshot";
         let (_, snapshot_filesystem) = pool
-            .snapshot_filesystem(pool_name, pool_uuid, fs_uuid, snapshot_name)
+            .snapshot_filesystem(pool_name, pool_uuid, fs_uuid, snapshot_name, None)
             .unwrap();
+        assert_eq!(snapshot_filesystem.origin(), Some(fs_uuid));
 
         cmd::udev_settle().unwrap();
 
@@ -2057,6 +2679,153 @@ mod tests {
         );
     }
 
+    /// Verify that reverting a filesystem to a snapshot restores the contents
+    /// of the snapshot under the origin's name and UUID and removes the
+    /// snapshot.
+    fn test_filesystem_revert(paths: &[&Path]) {
+        let pool_name = "pool";
+        let pool_uuid = PoolUuid::new_v4();
+
+        let devices = get_devices(paths).unwrap();
+
+        let mut backstore = Backstore::initialize(
+            Name::new(pool_name.to_string()),
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            PoolCreateOptions::default(),
+        )
+        .unwrap();
+        let mut pool = ThinPool::new(
+            pool_uuid,
+            &ThinPoolSizeParams::new(
+                backstore.available_in_backstore(),
+                DATA_BLOCK_SIZE,
+                DATA_ALLOC_SIZE,
+            )
+            .unwrap(),
+            DATA_BLOCK_SIZE,
+            DEFAULT_EXTEND_POLICY,
+            &mut backstore,
+        )
+        .unwrap();
+
+        let filesystem_name = "stratis_test_filesystem";
+        let fs_uuid = pool
+            .create_filesystem(
+                pool_name,
+                pool_uuid,
+                filesystem_name,
+                DEFAULT_THIN_DEV_SIZE,
+                None,
+                FilesystemType::Xfs,
+                &FsCreateOptions::default(),
+            )
+            .unwrap();
+
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("stratis_testing")
+            .tempdir()
+            .unwrap();
+        let file_path = tmp_dir.path().join("stratis_test.txt");
+        let write_file = |pool: &ThinPool, contents: &[u8]| {
+            let (_, filesystem) = pool.get_filesystem_by_uuid(fs_uuid).unwrap();
+            mount(
+                Some(&filesystem.devnode()),
+                tmp_dir.path(),
+                Some("xfs"),
+                MsFlags::empty(),
+                None as Option<&str>,
+            )
+            .unwrap();
+            let mut f = OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .open(&file_path)
+                .unwrap();
+            f.write_all(contents).unwrap();
+            f.sync_all().unwrap();
+            umount(tmp_dir.path()).unwrap();
+        };
+
+        let original = &[1u8; SECTOR_SIZE];
+        write_file(&pool, original);
+
+        let snapshot_name = "test_snapshot";
+        let (snapshot_uuid, _) = pool
+            .snapshot_filesystem(pool_name, pool_uuid, fs_uuid, snapshot_name, None)
+            .unwrap();
+
+        write_file(&pool, &[2u8; SECTOR_SIZE]);
+
+        assert_eq!(
+            pool.revert_filesystem(pool_name, pool_uuid, fs_uuid, snapshot_uuid)
+                .unwrap(),
+            Some(snapshot_uuid)
+        );
+        assert!(pool.get_filesystem_by_uuid(snapshot_uuid).is_none());
+        assert_eq!(
+            pool.revert_filesystem(pool_name, pool_uuid, fs_uuid, snapshot_uuid)
+                .unwrap(),
+            None
+        );
+
+        cmd::udev_settle().unwrap();
+        assert!(Path::new(&format!("/dev/stratis/{pool_name}/{filesystem_name}")).exists());
+        assert!(!Path::new(&format!("/dev/stratis/{pool_name}/{snapshot_name}")).exists());
+
+        let (name, filesystem) = pool.get_filesystem_by_uuid(fs_uuid).unwrap();
+        assert_eq!(&*name, filesystem_name);
+        mount(
+            Some(&filesystem.devnode()),
+            tmp_dir.path(),
+            Some("xfs"),
+            MsFlags::empty(),
+            None as Option<&str>,
+        )
+        .unwrap();
+        let mut read_buf = [0u8; SECTOR_SIZE];
+        OpenOptions::new()
+            .read(true)
+            .open(&file_path)
+            .unwrap()
+            .read_exact(&mut read_buf)
+            .unwrap();
+        assert_eq!(read_buf, *original);
+        umount(tmp_dir.path()).unwrap();
+
+        let flexdevs: FlexDevsSave = pool.record();
+        let thinpoolsave: ThinPoolDevSave = pool.record();
+        retry_operation!(pool.teardown());
+
+        let pool =
+            ThinPool::setup(pool_name, pool_uuid, &thinpoolsave, &flexdevs, &backstore).unwrap();
+        assert_eq!(
+            pool.filesystems()
+                .iter()
+                .map(|(name, uuid, _)| (name.to_string(), *uuid))
+                .collect::<Vec<_>>(),
+            vec![(filesystem_name.to_string(), fs_uuid)]
+        );
+    }
+
+    #[test]
+    fn loop_test_filesystem_revert() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_filesystem_revert,
+        );
+    }
+
+    #[test]
+    fn real_test_filesystem_revert() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(2, None, None),
+            test_filesystem_revert,
+        );
+    }
+
     /// Verify that a filesystem rename causes the filesystem metadata to be
     /// updated.
     fn test_filesystem_rename(paths: &[&Path]) {
@@ -2068,20 +2837,39 @@ mod tests {
 
         let devices = get_devices(paths).unwrap();
 
-        let mut backstore =
-            Backstore::initialize(pool_name, pool_uuid, devices, MDADataSize::default(), None)
-                .unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_name,
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            PoolCreateOptions::default(),
+        )
+        .unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
-            &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
+            &ThinPoolSizeParams::new(
+                backstore.available_in_backstore(),
+                DATA_BLOCK_SIZE,
+                DATA_ALLOC_SIZE,
+            )
+            .unwrap(),
             DATA_BLOCK_SIZE,
+            DEFAULT_EXTEND_POLICY,
             &mut backstore,
         )
         .unwrap();
 
         let pool_name = "stratis_test_pool";
         let fs_uuid = pool
-            .create_filesystem(pool_name, pool_uuid, name1, DEFAULT_THIN_DEV_SIZE)
+            .create_filesystem(
+                pool_name,
+                pool_uuid,
+                name1,
+                DEFAULT_THIN_DEV_SIZE,
+                None,
+                FilesystemType::Xfs,
+                &FsCreateOptions::default(),
+            )
             .unwrap();
 
         cmd::udev_settle().unwrap();
@@ -2138,19 +2926,33 @@ mod tests {
             pool_uuid,
             devices,
             MDADataSize::default(),
-            None,
+            PoolCreateOptions::default(),
         )
         .unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
-            &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
+            &ThinPoolSizeParams::new(
+                backstore.available_in_backstore(),
+                DATA_BLOCK_SIZE,
+                DATA_ALLOC_SIZE,
+            )
+            .unwrap(),
             DATA_BLOCK_SIZE,
+            DEFAULT_EXTEND_POLICY,
             &mut backstore,
         )
         .unwrap();
 
         let fs_uuid = pool
-            .create_filesystem(pool_name, pool_uuid, "fsname", DEFAULT_THIN_DEV_SIZE)
+            .create_filesystem(
+                pool_name,
+                pool_uuid,
+                "fsname",
+                DEFAULT_THIN_DEV_SIZE,
+                None,
+                FilesystemType::Xfs,
+                &FsCreateOptions::default(),
+            )
             .unwrap();
 
         let tmp_dir = tempfile::Builder::new()
@@ -2213,20 +3015,39 @@ mod tests {
 
         let devices = get_devices(paths).unwrap();
 
-        let mut backstore =
-            Backstore::initialize(pool_name, pool_uuid, devices, MDADataSize::default(), None)
-                .unwrap();
+        let mut backstore = Backstore::initialize(
+            pool_name,
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            PoolCreateOptions::default(),
+        )
+        .unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
-            &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
+            &ThinPoolSizeParams::new(
+                backstore.available_in_backstore(),
+                DATA_BLOCK_SIZE,
+                DATA_ALLOC_SIZE,
+            )
+            .unwrap(),
             DATA_BLOCK_SIZE,
+            DEFAULT_EXTEND_POLICY,
             &mut backstore,
         )
         .unwrap();
         let pool_name = "stratis_test_pool";
         let fs_name = "stratis_test_filesystem";
         let fs_uuid = pool
-            .create_filesystem(pool_name, pool_uuid, fs_name, DEFAULT_THIN_DEV_SIZE)
+            .create_filesystem(
+                pool_name,
+                pool_uuid,
+                fs_name,
+                DEFAULT_THIN_DEV_SIZE,
+                None,
+                FilesystemType::Xfs,
+                &FsCreateOptions::default(),
+            )
             .unwrap();
 
         retry_operation!(pool.destroy_filesystem(pool_name, fs_uuid));
@@ -2266,6 +3087,120 @@ mod tests {
         );
     }
 
+    /// Verify that the origin of a snapshot is saved in the pool metadata and
+    /// that it is cleared when the origin is destroyed.
+    fn test_snapshot_origin(paths: &[&Path]) {
+        let pool_uuid = PoolUuid::new_v4();
+        let pool_name = "stratis_test_pool";
+
+        let devices = get_devices(paths).unwrap();
+
+        let mut backstore = Backstore::initialize(
+            Name::new(pool_name.to_string()),
+            pool_uuid,
+            devices,
+            MDADataSize::default(),
+            PoolCreateOptions::default(),
+        )
+        .unwrap();
+        let mut pool = ThinPool::new(
+            pool_uuid,
+            &ThinPoolSizeParams::new(
+                backstore.available_in_backstore(),
+                DATA_BLOCK_SIZE,
+                DATA_ALLOC_SIZE,
+            )
+            .unwrap(),
+            DATA_BLOCK_SIZE,
+            DEFAULT_EXTEND_POLICY,
+            &mut backstore,
+        )
+        .unwrap();
+        let fs_uuid = pool
+            .create_filesystem(
+                pool_name,
+                pool_uuid,
+                "stratis_test_filesystem",
+                DEFAULT_THIN_DEV_SIZE,
+                None,
+                FilesystemType::Xfs,
+                &FsCreateOptions::default(),
+            )
+            .unwrap();
+        let (snapshot_uuid, _) = pool
+            .snapshot_filesystem(pool_name, pool_uuid, fs_uuid, "stratis_test_snapshot", None)
+            .unwrap();
+
+        let flexdevs: FlexDevsSave = pool.record();
+        let thinpooldevsave: ThinPoolDevSave = pool.record();
+        retry_operation!(pool.teardown());
+
+        let mut pool = ThinPool::setup(
+            pool_name,
+            pool_uuid,
+            &thinpooldevsave,
+            &flexdevs,
+            &backstore,
+        )
+        .unwrap();
+        assert_eq!(
+            pool.get_filesystem_by_uuid(fs_uuid).unwrap().1.origin(),
+            None
+        );
+        assert_eq!(
+            pool.get_filesystem_by_uuid(snapshot_uuid)
+                .unwrap()
+                .1
+                .origin(),
+            Some(fs_uuid)
+        );
+
+        retry_operation!(pool.destroy_filesystem(pool_name, fs_uuid));
+        assert_eq!(
+            pool.get_filesystem_by_uuid(snapshot_uuid)
+                .unwrap()
+                .1
+                .origin(),
+            None
+        );
+
+        let flexdevs: FlexDevsSave = pool.record();
+        let thinpooldevsave: ThinPoolDevSave = pool.record();
+        retry_operation!(pool.teardown());
+
+        let pool = ThinPool::setup(
+            pool_name,
+            pool_uuid,
+            &thinpooldevsave,
+            &flexdevs,
+            &backstore,
+        )
+        .unwrap();
+        assert_eq!(
+            pool.get_filesystem_by_uuid(snapshot_uuid)
+                .unwrap()
+                .1
+                .origin(),
+            None
+        );
+    }
+
+    #[test]
+    fn loop_test_snapshot_origin() {
+        loopbacked::test_with_spec(
+            &loopbacked::DeviceLimits::Range(2, 3, None),
+            test_snapshot_origin,
+        );
+    }
+
+    #[test]
+    fn real_test_snapshot_origin() {
+        real::test_with_spec(
+            &real::DeviceLimits::AtLeast(1, None, None),
+            test_snapshot_origin,
+        );
+    }
+
     /// Just suspend and resume the device and make sure it doesn't crash.
     /// Suspend twice in succession and then resume twice in succession
     /// to check idempotency.
@@ -2280,13 +3215,19 @@ mod tests {
             pool_uuid,
             devices,
             MDADataSize::default(),
-            None,
+            PoolCreateOptions::default(),
         )
         .unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
-            &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
+            &ThinPoolSizeParams::new(
+                backstore.available_in_backstore(),
+                DATA_BLOCK_SIZE,
+                DATA_ALLOC_SIZE,
+            )
+            .unwrap(),
             DATA_BLOCK_SIZE,
+            DEFAULT_EXTEND_POLICY,
             &mut backstore,
         )
         .unwrap();
@@ -2296,6 +3237,9 @@ mod tests {
             pool_uuid,
             "stratis_test_filesystem",
             DEFAULT_THIN_DEV_SIZE,
+            None,
+            FilesystemType::Xfs,
+            &FsCreateOptions::default(),
         )
         .unwrap();
 
@@ -2341,13 +3285,19 @@ mod tests {
             pool_uuid,
             devices,
             MDADataSize::default(),
-            None,
+            PoolCreateOptions::default(),
         )
         .unwrap();
         let mut pool = ThinPool::new(
             pool_uuid,
-            &ThinPoolSizeParams::new(backstore.available_in_backstore()).unwrap(),
+            &ThinPoolSizeParams::new(
+                backstore.available_in_backstore(),
+                DATA_BLOCK_SIZE,
+                DATA_ALLOC_SIZE,
+            )
+            .unwrap(),
             DATA_BLOCK_SIZE,
+            DEFAULT_EXTEND_POLICY,
             &mut backstore,
         )
         .unwrap();
@@ -2358,6 +3308,9 @@ mod tests {
                 pool_uuid,
                 "stratis_test_filesystem",
                 DEFAULT_THIN_DEV_SIZE,
+                None,
+                FilesystemType::Xfs,
+                &FsCreateOptions::default(),
             )
             .unwrap();
 
@@ -2400,7 +3353,12 @@ mod tests {
             .device()
             .expect("Space already allocated from backstore, backstore must have device");
         backstore
-            .init_cache(Name::new(pool_name.to_string()), pool_uuid, devices1)
+            .init_cache(
+                Name::new(pool_name.to_string()),
+                pool_uuid,
+                devices1,
+                CacheConfig::default(),
+            )
             .unwrap();
         let new_device = backstore
             .device()
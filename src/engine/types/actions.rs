@@ -23,6 +23,9 @@ pub struct Key;
 /// Return value indicating clevis operation
 pub struct Clevis;
 
+/// Return value indicating encryption operation
+pub struct Encryption;
+
 /// A trait for a generic kind of action. Defines the type of the thing to
 /// be changed, and also a method to indicate what changed.
 pub trait EngineAction {
@@ -94,6 +97,22 @@ impl Display for CreateAction<Clevis> {
     }
 }
 
+impl Display for CreateAction<Encryption> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CreateAction::Created(Encryption) => {
+                write!(f, "Pool successfully encrypted")
+            }
+            CreateAction::Identity => {
+                write!(
+                    f,
+                    "The pool requested for encryption is already encrypted; no action taken"
+                )
+            }
+        }
+    }
+}
+
 impl Display for CreateAction<Key> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -604,6 +623,27 @@ impl Display for SetDeleteAction<FilesystemUuid> {
     }
 }
 
+impl Display for SetDeleteAction<DevUuid> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changed.is_empty() {
+            write!(
+                f,
+                "The requested devices are already absent from the pool; no action taken"
+            )
+        } else {
+            write!(
+                f,
+                "The following devices with UUIDs {} were successfully removed from a pool",
+                self.changed
+                    .iter()
+                    .map(|u| u.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+    }
+}
+
 /// Action indicating a Clevis binding regeneration
 pub struct RegenAction;
 
@@ -753,6 +793,29 @@ where
     }
 }
 
+impl<T> ToDisplay for PropChangeAction<Vec<T>>
+where
+    T: Display,
+{
+    type Display = PropChangeAction<String>;
+
+    fn to_display(&self) -> PropChangeAction<String> {
+        match self {
+            PropChangeAction::Identity => PropChangeAction::Identity,
+            PropChangeAction::NewValue(v) if v.is_empty() => {
+                PropChangeAction::NewValue("an empty list".to_string())
+            }
+            PropChangeAction::NewValue(v) => PropChangeAction::NewValue(format!(
+                "a list of {}",
+                v.iter()
+                    .map(|t| t.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
 impl<T> Display for PropChangeAction<T>
 where
     T: Display,
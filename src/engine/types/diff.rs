@@ -6,6 +6,8 @@ use std::ops::{Deref, DerefMut};
 
 use devicemapper::{Bytes, Sectors};
 
+use crate::engine::types::SpaceUsage;
+
 /// This interface defines a generic way to compare whether two values of
 /// the same type have changed or remained the same.
 pub trait Compare {
@@ -94,6 +96,7 @@ pub struct StratPoolDiff {
 pub struct StratFilesystemDiff {
     pub size: Diff<Bytes>,
     pub used: Diff<Option<Bytes>>,
+    pub space_usage: Diff<Option<SpaceUsage>>,
 }
 
 /// Represents the difference between two dumped states for a pool.
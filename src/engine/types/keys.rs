@@ -3,6 +3,7 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::{
+    collections::BTreeMap,
     fmt::{self, Debug},
     hash::{Hash, Hasher},
 };
@@ -341,6 +342,102 @@ where
     }
 }
 
+/// The password-based key derivation function which derives the keys of the
+/// keyslots of an encrypted device from their passphrases.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pbkdf {
+    Pbkdf2,
+    Argon2i,
+    Argon2id,
+}
+
+impl fmt::Display for Pbkdf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pbkdf::Pbkdf2 => write!(f, "pbkdf2"),
+            Pbkdf::Argon2i => write!(f, "argon2i"),
+            Pbkdf::Argon2id => write!(f, "argon2id"),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Pbkdf {
+    type Error = StratisError;
+
+    fn try_from(s: &str) -> StratisResult<Pbkdf> {
+        match s {
+            "pbkdf2" => Ok(Pbkdf::Pbkdf2),
+            "argon2i" => Ok(Pbkdf::Argon2i),
+            "argon2id" => Ok(Pbkdf::Argon2id),
+            _ => Err(StratisError::Msg(format!("{s} is an invalid PBKDF"))),
+        }
+    }
+}
+
+/// Settings of the LUKS2 encryption of the devices of a pool that are chosen
+/// when the pool is created. Defaults are used for the settings that are not
+/// specified. The PBKDF settings apply to the keyslots of the passphrase in
+/// the kernel keyring; Clevis chooses the PBKDF of its own keyslots.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct EncryptionConfig {
+    /// The cipher specification, e.g. "aes-xts-plain64".
+    pub cipher: Option<String>,
+    /// The size of the volume key in bits.
+    pub key_size: Option<u32>,
+    pub pbkdf: Option<Pbkdf>,
+    /// The memory cost of the PBKDF in KiB; Argon2 only.
+    pub pbkdf_memory: Option<u32>,
+    /// The iteration cost of the PBKDF; the number of passes for Argon2.
+    pub pbkdf_iterations: Option<u32>,
+    /// The size of an encryption sector in bytes.
+    pub sector_size: Option<u32>,
+}
+
+impl EncryptionConfig {
+    /// Split the cipher specification into the cipher and the mode, e.g.
+    /// "aes-xts-plain64" into "aes" and "xts-plain64".
+    pub fn cipher_and_mode(&self) -> StratisResult<Option<(&str, &str)>> {
+        self.cipher
+            .as_ref()
+            .map(|spec| match spec.split_once('-') {
+                Some((cipher, mode)) if !cipher.is_empty() && !mode.is_empty() => {
+                    Ok((cipher, mode))
+                }
+                _ => Err(StratisError::Msg(format!(
+                    "Cipher specification {spec} is not of the form <cipher>-<mode>"
+                ))),
+            })
+            .transpose()
+    }
+
+    /// True if any of the PBKDF settings are specified.
+    pub fn has_pbkdf_settings(&self) -> bool {
+        self.pbkdf.is_some() || self.pbkdf_memory.is_some() || self.pbkdf_iterations.is_some()
+    }
+}
+
+impl fmt::Display for EncryptionConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn or_default<T: fmt::Display>(val: &Option<T>) -> String {
+            val.as_ref()
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| "default".to_string())
+        }
+
+        write!(
+            f,
+            "cipher: {}, key size: {}, PBKDF: {}, PBKDF memory: {}, PBKDF iterations: {}, sector size: {}",
+            or_default(&self.cipher),
+            or_default(&self.key_size),
+            or_default(&self.pbkdf),
+            or_default(&self.pbkdf_memory),
+            or_default(&self.pbkdf_iterations),
+            or_default(&self.sector_size),
+        )
+    }
+}
+
 /// A data type representing a key description for the kernel keyring
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct KeyDescription(String);
@@ -377,3 +474,8 @@ impl<'a> TryFrom<&'a String> for KeyDescription {
         KeyDescription::try_from(s.to_owned())
     }
 }
+
+/// The named passphrase bindings of an encrypted device or pool, mapping the
+/// name of each binding to the key description of its passphrase. These are
+/// in addition to the keyring binding recorded in the EncryptionInfo.
+pub type PassphraseBindings = BTreeMap<String, KeyDescription>;
@@ -18,20 +18,25 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use uuid::Uuid;
 
+use devicemapper::{Bytes, Sectors};
+
 pub use crate::engine::{
     engine::{Engine, StateDiff},
     structures::Lockable,
     types::{
         actions::{
-            Clevis, CreateAction, DeleteAction, EngineAction, GrowAction, Key, MappingCreateAction,
-            MappingDeleteAction, PropChangeAction, RegenAction, RenameAction, SetCreateAction,
-            SetDeleteAction, SetUnlockAction, StartAction, StopAction, ToDisplay,
+            Clevis, CreateAction, DeleteAction, Encryption, EngineAction, GrowAction, Key,
+            MappingCreateAction, MappingDeleteAction, PropChangeAction, RegenAction, RenameAction,
+            SetCreateAction, SetDeleteAction, SetUnlockAction, StartAction, StopAction, ToDisplay,
         },
         diff::{
             Compare, Diff, PoolDiff, StratBlockDevDiff, StratFilesystemDiff, StratPoolDiff,
             ThinPoolDiff,
         },
-        keys::{EncryptionInfo, KeyDescription, PoolEncryptionInfo, SizedKeyMemory},
+        keys::{
+            EncryptionConfig, EncryptionInfo, KeyDescription, PassphraseBindings, Pbkdf,
+            PoolEncryptionInfo, SizedKeyMemory,
+        },
     },
 };
 use crate::stratis::{StratisError, StratisResult};
@@ -158,6 +163,470 @@ pub enum BlockDevTier {
     Cache = 1,
 }
 
+/// The mode in which a cache handles writes.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheMode {
+    /// Writes go to both the cache and the data tier before they complete.
+    Writethrough,
+    /// Writes complete once they are stored in the cache and are written
+    /// back to the data tier later.
+    Writeback,
+    /// Reads and writes bypass the cache.
+    Passthrough,
+}
+
+impl Display for CacheMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheMode::Writethrough => write!(f, "writethrough"),
+            CacheMode::Writeback => write!(f, "writeback"),
+            CacheMode::Passthrough => write!(f, "passthrough"),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for CacheMode {
+    type Error = StratisError;
+
+    fn try_from(s: &str) -> StratisResult<CacheMode> {
+        match s {
+            "writethrough" => Ok(CacheMode::Writethrough),
+            "writeback" => Ok(CacheMode::Writeback),
+            "passthrough" => Ok(CacheMode::Passthrough),
+            _ => Err(StratisError::Msg(format!("{s} is an invalid cache mode"))),
+        }
+    }
+}
+
+/// The configuration of a cache: the mode in which it handles writes and the
+/// policy, with its tunables, which decides which blocks are cached.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CacheConfig {
+    pub mode: CacheMode,
+    pub policy: String,
+    pub policy_args: Vec<(String, String)>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            mode: CacheMode::Writethrough,
+            policy: "smq".to_string(),
+            policy_args: Vec::new(),
+        }
+    }
+}
+
+impl Display for CacheConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mode {}, policy {}", self.mode, self.policy)?;
+        for (key, value) in self.policy_args.iter() {
+            write!(f, " {key}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The redundancy with which the data tier of a pool stores its data.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Redundancy {
+    /// The data tier is a linear concatenation of its block devices.
+    #[default]
+    None,
+    /// Every block of the data tier is stored on two block devices.
+    Raid1,
+}
+
+impl Display for Redundancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Redundancy::None => write!(f, "none"),
+            Redundancy::Raid1 => write!(f, "raid1"),
+        }
+    }
+}
+
+impl TryFrom<u16> for Redundancy {
+    type Error = StratisError;
+
+    fn try_from(code: u16) -> StratisResult<Redundancy> {
+        match code {
+            0 => Ok(Redundancy::None),
+            1 => Ok(Redundancy::Raid1),
+            _ => Err(StratisError::Msg(format!(
+                "code {code} does not correspond to any redundancy"
+            ))),
+        }
+    }
+}
+
+/// The policy by which space is allocated from the block devices of the
+/// data tier of a pool.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AllocationPolicy {
+    /// Each allocation is made from the first block device with available
+    /// space, moving on to the next only when it is exhausted.
+    #[default]
+    FillFirst,
+    /// Each allocation is spread as evenly as possible across all the block
+    /// devices with available space.
+    Spread,
+    /// Each allocation is made first from the block devices which carry the
+    /// tag configured for its purpose, and then as for fill-first.
+    PreferTagged,
+}
+
+impl Display for AllocationPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllocationPolicy::FillFirst => write!(f, "fill-first"),
+            AllocationPolicy::Spread => write!(f, "spread"),
+            AllocationPolicy::PreferTagged => write!(f, "prefer-tagged"),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for AllocationPolicy {
+    type Error = StratisError;
+
+    fn try_from(s: &str) -> StratisResult<AllocationPolicy> {
+        match s {
+            "fill-first" => Ok(AllocationPolicy::FillFirst),
+            "spread" => Ok(AllocationPolicy::Spread),
+            "prefer-tagged" => Ok(AllocationPolicy::PreferTagged),
+            _ => Err(StratisError::Msg(format!(
+                "{s} is an invalid allocation policy"
+            ))),
+        }
+    }
+}
+
+/// The purpose for which space is allocated from the data tier of a pool.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AllocationPurpose {
+    /// The data sub-device of the thin pool
+    ThinData,
+    /// The metadata sub-device of the thin pool and its spare
+    ThinMeta,
+    /// The metadata volume which holds the filesystem metadata
+    Mdv,
+}
+
+impl Display for AllocationPurpose {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllocationPurpose::ThinData => write!(f, "thin-data"),
+            AllocationPurpose::ThinMeta => write!(f, "thin-meta"),
+            AllocationPurpose::Mdv => write!(f, "mdv"),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for AllocationPurpose {
+    type Error = StratisError;
+
+    fn try_from(s: &str) -> StratisResult<AllocationPurpose> {
+        match s {
+            "thin-data" => Ok(AllocationPurpose::ThinData),
+            "thin-meta" => Ok(AllocationPurpose::ThinMeta),
+            "mdv" => Ok(AllocationPurpose::Mdv),
+            _ => Err(StratisError::Msg(format!(
+                "{s} is an invalid allocation purpose"
+            ))),
+        }
+    }
+}
+
+/// The configuration of allocation from the data tier of a pool: the policy
+/// and, for the prefer-tagged policy, the tag of the block devices preferred
+/// for each purpose.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AllocationConfig {
+    pub policy: AllocationPolicy,
+    pub tags: Vec<(AllocationPurpose, String)>,
+}
+
+impl AllocationConfig {
+    /// The tag of the block devices from which space for purpose should
+    /// preferably be allocated, if any.
+    pub fn preferred_tag(&self, purpose: AllocationPurpose) -> Option<&str> {
+        if self.policy == AllocationPolicy::PreferTagged {
+            self.tags
+                .iter()
+                .find(|(p, _)| *p == purpose)
+                .map(|(_, tag)| tag.as_str())
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for AllocationConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "policy {}", self.policy)?;
+        for (purpose, tag) in self.tags.iter() {
+            write!(f, " {purpose}={tag}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A policy for taking snapshots of a filesystem automatically. A snapshot
+/// is taken every interval seconds. Scheduled snapshots beyond the most recent
+/// keep_count or older than keep_age seconds are destroyed.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SnapshotPolicy {
+    /// Identifies the policy, e.g., "hourly"; it is part of the names of the
+    /// snapshots the policy creates.
+    pub name: String,
+    pub interval: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_age: Option<u64>,
+}
+
+impl Display for SnapshotPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} every {}s", self.name, self.interval)?;
+        if let Some(count) = self.keep_count {
+            write!(f, " keeping {count}")?;
+        }
+        if let Some(age) = self.keep_age {
+            write!(f, " for at most {age}s")?;
+        }
+        Ok(())
+    }
+}
+
+/// The amount by which a filesystem is extended when it is extended
+/// automatically.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FsExtendIncrement {
+    /// A fixed amount
+    Absolute(Sectors),
+    /// A percentage of the current size of the filesystem
+    Percent(u64),
+}
+
+impl FsExtendIncrement {
+    /// The amount by which a filesystem of the given size should be extended.
+    pub fn extend_size(&self, current_size: Sectors) -> Sectors {
+        match self {
+            FsExtendIncrement::Absolute(amount) => *amount,
+            FsExtendIncrement::Percent(percent) => {
+                Sectors(current_size.saturating_mul(*percent) / 100)
+            }
+        }
+    }
+}
+
+impl Display for FsExtendIncrement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FsExtendIncrement::Absolute(amount) => write!(f, "{amount}"),
+            FsExtendIncrement::Percent(percent) => write!(f, "{percent}%"),
+        }
+    }
+}
+
+/// The configuration of the automatic extension of filesystems: whether
+/// filesystems are extended at all, the percentage of a filesystem that must
+/// be in use before it is extended, and the amount by which it is extended.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct FsExtendConfig {
+    pub enabled: bool,
+    pub threshold: u8,
+    pub increment: FsExtendIncrement,
+}
+
+impl Default for FsExtendConfig {
+    /// Double the size of a filesystem once more than half of it is used.
+    fn default() -> Self {
+        FsExtendConfig {
+            enabled: true,
+            threshold: 50,
+            increment: FsExtendIncrement::Percent(100),
+        }
+    }
+}
+
+impl Display for FsExtendConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.enabled {
+            write!(
+                f,
+                "extend by {} when more than {}% is used",
+                self.increment, self.threshold
+            )
+        } else {
+            write!(f, "disabled")
+        }
+    }
+}
+
+/// The type of the filesystem created on a thin device.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilesystemType {
+    #[default]
+    Xfs,
+    Ext4,
+}
+
+impl Display for FilesystemType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilesystemType::Xfs => write!(f, "xfs"),
+            FilesystemType::Ext4 => write!(f, "ext4"),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for FilesystemType {
+    type Error = StratisError;
+
+    fn try_from(s: &str) -> StratisResult<FilesystemType> {
+        match s {
+            "xfs" => Ok(FilesystemType::Xfs),
+            "ext4" => Ok(FilesystemType::Ext4),
+            _ => Err(StratisError::Msg(format!(
+                "{s} is an invalid filesystem type"
+            ))),
+        }
+    }
+}
+
+/// Options passed to mkfs when creating a filesystem. Only options in this
+/// whitelist may be set; each is left at the mkfs default if not specified.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MkfsOptions {
+    /// Whether to enable reflink support. XFS only.
+    pub reflink: Option<bool>,
+    /// Whether to enable timestamps beyond the year 2038. XFS only.
+    pub bigtime: Option<bool>,
+    /// The size of an inode in bytes.
+    pub inode_size: Option<u64>,
+    /// The label of the filesystem.
+    pub label: Option<String>,
+}
+
+/// Options which apply when creating a filesystem: the options passed to mkfs
+/// and the mount options recommended for the filesystem.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FsCreateOptions {
+    pub mkfs: MkfsOptions,
+    pub mount_options: Option<String>,
+}
+
+/// The physical space in the thin pool used by a thin device: the space it
+/// maps, and how much of that is mapped by this device alone and how much is
+/// shared with other thin devices, such as its snapshots or origin. Deleting
+/// the device frees only its exclusive space.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SpaceUsage {
+    pub mapped: Bytes,
+    pub exclusive: Bytes,
+    pub shared: Bytes,
+}
+
+/// The specification of a filesystem to be created. Each unspecified value
+/// takes its default.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FilesystemSpec<'a> {
+    pub name: &'a str,
+    pub size: Option<Bytes>,
+    pub size_limit: Option<Bytes>,
+    pub fs_type: Option<FilesystemType>,
+    pub options: Option<FsCreateOptions>,
+}
+
+impl<'a> FilesystemSpec<'a> {
+    /// The specification of a filesystem with the given name and default
+    /// values for everything else.
+    pub fn new(name: &'a str) -> Self {
+        FilesystemSpec {
+            name,
+            ..Default::default()
+        }
+    }
+}
+
+/// The policy according to which the data device of the thin pool of a pool
+/// is extended: once less than low_water of it is free, it is extended by
+/// extend_size.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ThinPoolExtendPolicy {
+    pub low_water: Sectors,
+    pub extend_size: Sectors,
+}
+
+impl Display for ThinPoolExtendPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "extend by {} when less than {} is free",
+            self.extend_size, self.low_water
+        )
+    }
+}
+
+/// Settings of the thin pool of a pool that are chosen when the pool is
+/// created. Defaults are used for the settings that are not specified. The
+/// data block size can not be changed once the pool has been created.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ThinPoolConfig {
+    pub data_block_size: Option<Sectors>,
+    pub low_water: Option<Sectors>,
+    pub extend_size: Option<Sectors>,
+}
+
+/// Settings chosen when a pool is created: the redundancy of its data tier,
+/// whether its devices are protected by dm-integrity, its encryption and the
+/// settings of its LUKS2 headers, and the settings of its thin pool.
+/// Defaults are used for the settings that are not specified.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PoolCreateOptions<'a> {
+    pub redundancy: Redundancy,
+    pub integrity: bool,
+    pub encryption_info: Option<&'a EncryptionInfo>,
+    pub encryption_config: Option<&'a EncryptionConfig>,
+    pub thin_pool_config: Option<&'a ThinPoolConfig>,
+}
+
+/// The filesystems created and destroyed when applying the snapshot policies
+/// of the filesystems in a pool, and the filesystems that lost their origin
+/// because it was destroyed.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct ScheduledSnapshots {
+    pub created: Vec<FilesystemUuid>,
+    pub destroyed: Vec<FilesystemUuid>,
+    pub origins_unset: Vec<FilesystemUuid>,
+}
+
+impl ScheduledSnapshots {
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.destroyed.is_empty()
+    }
+}
+
+/// Counters reported by an active cache.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CacheStats {
+    pub read_hits: u64,
+    pub read_misses: u64,
+    pub write_hits: u64,
+    pub write_misses: u64,
+    /// The number of blocks in the cache which have not yet been written
+    /// back to the data tier.
+    pub dirty: u64,
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
 pub struct Name(String);
 
@@ -204,8 +673,12 @@ impl fmt::Display for Name {
 ///
 /// * `ErroredPoolDevices` returns the state of devices that caused an error while
 /// attempting to reconstruct a pool.
+///
+/// * `SpaceUsage` returns the physical space used by each filesystem in each
+/// pool, listing first the filesystems whose removal would free the most space.
 pub enum ReportType {
     StoppedPools,
+    SpaceUsage,
 }
 
 impl<'a> TryFrom<&'a str> for ReportType {
@@ -214,6 +687,7 @@ impl<'a> TryFrom<&'a str> for ReportType {
     fn try_from(name: &str) -> StratisResult<ReportType> {
         match name {
             "stopped_pools" => Ok(ReportType::StoppedPools),
+            "space_usage" => Ok(ReportType::SpaceUsage),
             _ => Err(StratisError::Msg(format!(
                 "Report name {name} not understood"
             ))),
@@ -253,6 +727,28 @@ pub struct StoppedPoolsInfo {
     pub uuid_to_name: HashMap<PoolUuid, Name>,
 }
 
+/// The progress of a block device replacement that is currently in progress.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReplaceProgress {
+    /// The UUID of the block device being replaced
+    pub old: DevUuid,
+    /// The UUID of the block device replacing it
+    pub new: DevUuid,
+    /// The amount of data copied to the new block device so far
+    pub copied: Sectors,
+    /// The total amount of data to copy to the new block device
+    pub total: Sectors,
+}
+
+/// The progress of a volume key rotation that is currently in progress.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReencryptionProgress {
+    /// The amount of data reencrypted with the new volume keys so far
+    pub reencrypted: Sectors,
+    /// The total amount of data to reencrypt
+    pub total: Sectors,
+}
+
 /// A sendable event with all of the necessary information for the engine
 /// to process a received udev event.
 pub struct UdevEngineEvent {
@@ -2,6 +2,12 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::{
+    fs::{File, OpenOptions},
+    io::{stdin, stdout},
+    os::unix::io::AsRawFd,
+};
+
 use crate::{jsonrpc::client::utils::to_suffix_repr, stratis::StratisResult};
 
 // stratis-min filesystem create
@@ -11,7 +17,7 @@ pub fn filesystem_create(pool_name: String, filesystem_name: String) -> StratisR
 
 // stratis-min filesystem [list]
 pub fn filesystem_list() -> StratisResult<()> {
-    let (pool_names, fs_names, used, created, paths, uuids) = do_request!(FsList);
+    let (pool_names, fs_names, used, created, paths, uuids, origins) = do_request!(FsList);
     let used_formatted: Vec<_> = used
         .into_iter()
         .map(|u_opt| {
@@ -22,13 +28,22 @@ pub fn filesystem_list() -> StratisResult<()> {
         .collect();
     let devices_formatted: Vec<_> = paths.into_iter().map(|p| p.display().to_string()).collect();
     let uuids_formatted: Vec<_> = uuids.into_iter().map(|u| u.to_string()).collect();
+    let origins_formatted: Vec<_> = origins
+        .into_iter()
+        .map(|o_opt| {
+            o_opt
+                .map(|o| o.to_string())
+                .unwrap_or_else(|| " ".to_string())
+        })
+        .collect();
     print_table!(
         "Pool Name", pool_names, "<";
         "Name", fs_names, "<";
         "Used", used_formatted, "<";
         "Created", created, "<";
         "Device", devices_formatted, "<";
-        "UUID", uuids_formatted, "<"
+        "UUID", uuids_formatted, "<";
+        "Origin", origins_formatted, "<"
     );
     Ok(())
 }
@@ -46,3 +61,52 @@ pub fn filesystem_rename(
 ) -> StratisResult<()> {
     do_request_standard!(FsRename, pool_name, filesystem_name, new_filesystem_name)
 }
+
+// stratis-min filesystem revert
+pub fn filesystem_revert(
+    pool_name: String,
+    filesystem_name: String,
+    snapshot_name: String,
+) -> StratisResult<()> {
+    do_request_standard!(FsRevert, pool_name, filesystem_name, snapshot_name)
+}
+
+// stratis-min filesystem send
+pub fn filesystem_send(
+    pool_name: String,
+    filesystem_name: String,
+    parent_name: Option<String>,
+    file_path: Option<&str>,
+) -> StratisResult<()> {
+    match file_path {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)?;
+            do_request_standard!(FsSend, pool_name, filesystem_name, parent_name; file.as_raw_fd())
+        }
+        None => {
+            do_request_standard!(FsSend, pool_name, filesystem_name, parent_name; stdout().as_raw_fd())
+        }
+    }
+}
+
+// stratis-min filesystem receive
+pub fn filesystem_receive(
+    pool_name: String,
+    filesystem_name: String,
+    incremental: bool,
+    file_path: Option<&str>,
+) -> StratisResult<()> {
+    match file_path {
+        Some(path) => {
+            let file = File::open(path)?;
+            do_request_standard!(FsReceive, pool_name, filesystem_name, incremental; file.as_raw_fd())
+        }
+        None => {
+            do_request_standard!(FsReceive, pool_name, filesystem_name, incremental; stdin().as_raw_fd())
+        }
+    }
+}
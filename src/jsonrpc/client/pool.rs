@@ -2,12 +2,12 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::path::PathBuf;
+use std::{fs::File, os::unix::io::AsRawFd, path::PathBuf};
 
 use nix::unistd::{pipe, write};
 
 use crate::{
-    engine::{EncryptionInfo, PoolIdentifier, PoolUuid, UnlockMethod},
+    engine::{EncryptionConfig, EncryptionInfo, PoolIdentifier, PoolUuid, UnlockMethod},
     jsonrpc::client::utils::to_suffix_repr,
     print_table,
     stratis::{StratisError, StratisResult},
@@ -18,28 +18,34 @@ pub fn pool_create(
     name: String,
     blockdevs: Vec<PathBuf>,
     enc_info: Option<EncryptionInfo>,
+    enc_config: Option<EncryptionConfig>,
 ) -> StratisResult<()> {
-    do_request_standard!(PoolCreate, name, blockdevs, enc_info)
+    do_request_standard!(PoolCreate, name, blockdevs, enc_info, enc_config)
 }
 
 // stratis-min pool start
 pub fn pool_start(
     id: PoolIdentifier<PoolUuid>,
     unlock_method: Option<UnlockMethod>,
+    allow_missing: bool,
     prompt: bool,
+    keyfile_path: Option<&str>,
 ) -> StratisResult<()> {
-    if prompt {
+    if let Some(kp) = keyfile_path {
+        let file = File::open(kp)?;
+        do_request_standard!(PoolStart, id, unlock_method, allow_missing; file.as_raw_fd())
+    } else if prompt {
         let password = rpassword::prompt_password_stdout("Enter passphrase followed by return: ")?;
         if password.is_empty() {
             return Ok(());
         }
-        do_request_standard!(PoolStart, id, unlock_method; {
+        do_request_standard!(PoolStart, id, unlock_method, allow_missing; {
             let (read_end, write_end) = pipe()?;
             write(write_end, password.as_bytes())?;
             read_end
         })
     } else {
-        do_request_standard!(PoolStart, id, unlock_method)
+        do_request_standard!(PoolStart, id, unlock_method, allow_missing)
     }
 }
 
@@ -68,6 +74,58 @@ pub fn pool_add_cache(name: String, paths: Vec<PathBuf>) -> StratisResult<()> {
     do_request_standard!(PoolAddCache, name, paths)
 }
 
+// stratis-min pool encrypt
+pub fn pool_encrypt(name: String, enc_info: EncryptionInfo) -> StratisResult<()> {
+    do_request_standard!(PoolEncrypt, name, enc_info)
+}
+
+// stratis-min pool rotate-volume-key
+pub fn pool_rotate_volume_key(name: String) -> StratisResult<()> {
+    do_request_standard!(PoolRotateVolumeKey, name)
+}
+
+// stratis-min pool reencryption-progress
+pub fn pool_reencryption_progress(uuid: PoolUuid) -> StratisResult<String> {
+    let (progress, rc, rs) = do_request!(PoolReencryptionProgress, uuid);
+    if rc != 0 {
+        Err(StratisError::Msg(rs))
+    } else {
+        Ok(progress
+            .map(|(reencrypted, total)| {
+                format!(
+                    "{} / {}",
+                    to_suffix_repr(reencrypted),
+                    to_suffix_repr(total)
+                )
+            })
+            .unwrap_or_else(|| "None".to_string()))
+    }
+}
+
+// stratis-min pool remove-cache
+pub fn pool_remove_cache(name: String) -> StratisResult<()> {
+    do_request_standard!(PoolRemoveCache, name)
+}
+
+// stratis-min pool replace-blockdev
+pub fn pool_replace_blockdev(name: String, old: PathBuf, new: PathBuf) -> StratisResult<()> {
+    do_request_standard!(PoolReplaceBlockdev, name, old, new)
+}
+
+// stratis-min pool replace-progress
+pub fn pool_replace_progress(uuid: PoolUuid) -> StratisResult<String> {
+    let (progress, rc, rs) = do_request!(PoolReplaceProgress, uuid);
+    if rc != 0 {
+        Err(StratisError::Msg(rs))
+    } else {
+        Ok(progress
+            .map(|(copied, total)| {
+                format!("{} / {}", to_suffix_repr(copied), to_suffix_repr(total))
+            })
+            .unwrap_or_else(|| "None".to_string()))
+    }
+}
+
 // stratis-min pool destroy
 pub fn pool_destroy(name: String) -> StratisResult<()> {
     do_request_standard!(PoolDestroy, name)
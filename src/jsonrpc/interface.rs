@@ -9,7 +9,8 @@ use std::{os::unix::io::RawFd, path::PathBuf};
 use serde_json::Value;
 
 use crate::engine::{
-    EncryptionInfo, FilesystemUuid, KeyDescription, PoolIdentifier, PoolUuid, UnlockMethod,
+    EncryptionConfig, EncryptionInfo, FilesystemUuid, KeyDescription, PoolIdentifier, PoolUuid,
+    UnlockMethod,
 };
 
 pub type PoolListType = (
@@ -28,6 +29,7 @@ pub type FsListType = (
     Vec<String>,
     Vec<PathBuf>,
     Vec<FilesystemUuid>,
+    Vec<Option<FilesystemUuid>>,
 );
 
 #[derive(Serialize, Deserialize)]
@@ -35,13 +37,24 @@ pub enum StratisParamType {
     KeySet(KeyDescription),
     KeyUnset(KeyDescription),
     KeyList,
-    PoolCreate(String, Vec<PathBuf>, Option<EncryptionInfo>),
+    PoolCreate(
+        String,
+        Vec<PathBuf>,
+        Option<EncryptionInfo>,
+        Option<EncryptionConfig>,
+    ),
     PoolRename(String, String),
     PoolAddData(String, Vec<PathBuf>),
     PoolInitCache(String, Vec<PathBuf>),
     PoolAddCache(String, Vec<PathBuf>),
+    PoolRemoveCache(String),
+    PoolEncrypt(String, EncryptionInfo),
+    PoolRotateVolumeKey(String),
+    PoolReencryptionProgress(PoolUuid),
+    PoolReplaceBlockdev(String, PathBuf, PathBuf),
+    PoolReplaceProgress(PoolUuid),
     PoolDestroy(String),
-    PoolStart(PoolIdentifier<PoolUuid>, Option<UnlockMethod>),
+    PoolStart(PoolIdentifier<PoolUuid>, Option<UnlockMethod>, bool),
     PoolStop(PoolIdentifier<PoolUuid>),
     PoolList,
     PoolIsEncrypted(PoolIdentifier<PoolUuid>),
@@ -52,6 +65,9 @@ pub enum StratisParamType {
     FsCreate(String, String),
     FsDestroy(String, String),
     FsRename(String, String, String),
+    FsRevert(String, String, String),
+    FsSend(String, String, Option<String>),
+    FsReceive(String, String, bool),
     FsList,
     Report,
 }
@@ -73,6 +89,12 @@ pub enum StratisRet {
     PoolAddData((bool, u16, String)),
     PoolInitCache((bool, u16, String)),
     PoolAddCache((bool, u16, String)),
+    PoolRemoveCache((bool, u16, String)),
+    PoolEncrypt((bool, u16, String)),
+    PoolRotateVolumeKey((bool, u16, String)),
+    PoolReencryptionProgress((Option<(u128, u128)>, u16, String)),
+    PoolReplaceBlockdev((bool, u16, String)),
+    PoolReplaceProgress((Option<(u128, u128)>, u16, String)),
     PoolDestroy((bool, u16, String)),
     PoolStart((bool, u16, String)),
     PoolStop((bool, u16, String)),
@@ -86,5 +108,8 @@ pub enum StratisRet {
     FsList(FsListType),
     FsDestroy((bool, u16, String)),
     FsRename((bool, u16, String)),
+    FsRevert((bool, u16, String)),
+    FsSend((bool, u16, String)),
+    FsReceive((bool, u16, String)),
     Report(Value),
 }
@@ -2,13 +2,13 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::sync::Arc;
+use std::{fs::File, sync::Arc};
 
 use chrono::SecondsFormat;
 use tokio::task::block_in_place;
 
 use crate::{
-    engine::{Engine, EngineAction, Filesystem, Name, Pool, PoolIdentifier},
+    engine::{Engine, EngineAction, Filesystem, FilesystemSpec, Name, Pool, PoolIdentifier},
     jsonrpc::interface::FsListType,
     stratis::{StratisError, StratisResult},
 };
@@ -29,7 +29,7 @@ where
     let (_, pool_uuid, pool) = guard.as_mut_tuple();
     block_in_place(|| {
         Ok(pool
-            .create_filesystems(pool_name, pool_uuid, &[(name, None)])?
+            .create_filesystems(pool_name, pool_uuid, &[FilesystemSpec::new(name)])?
             .is_changed())
     })
 }
@@ -48,6 +48,7 @@ where
             Vec::new(),
             Vec::new(),
             Vec::new(),
+            Vec::new(),
         ),
         |mut acc, (name, _, pool)| {
             for (fs_name, uuid, fs) in pool.filesystems() {
@@ -58,6 +59,7 @@ where
                     .push(fs.created().to_rfc3339_opts(SecondsFormat::Secs, true));
                 acc.4.push(fs.devnode());
                 acc.5.push(uuid);
+                acc.6.push(fs.origin());
             }
             acc
         },
@@ -106,3 +108,94 @@ where
             .is_changed())
     })
 }
+
+// stratis-min filesystem revert
+pub async fn filesystem_revert<E>(
+    engine: Arc<E>,
+    pool_name: &str,
+    fs_name: &str,
+    snapshot_name: &str,
+) -> StratisResult<bool>
+where
+    E: Engine,
+{
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(pool_name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {pool_name} found")))?;
+    let (_, pool_uuid, pool) = guard.as_mut_tuple();
+    let (origin, _) = pool
+        .get_filesystem_by_name(&Name::new(fs_name.to_string()))
+        .ok_or_else(|| StratisError::Msg(format!("No filesystem named {fs_name} found")))?;
+    let (snapshot, _) = pool
+        .get_filesystem_by_name(&Name::new(snapshot_name.to_string()))
+        .ok_or_else(|| StratisError::Msg(format!("No filesystem named {snapshot_name} found")))?;
+    block_in_place(|| {
+        Ok(pool
+            .revert_filesystem(pool_name, pool_uuid, origin, snapshot)?
+            .is_changed())
+    })
+}
+
+// stratis-min filesystem send
+pub async fn filesystem_send<E>(
+    engine: Arc<E>,
+    pool_name: &str,
+    fs_name: &str,
+    parent_name: Option<&str>,
+    file: File,
+) -> StratisResult<bool>
+where
+    E: Engine,
+{
+    let pool = engine
+        .get_pool(PoolIdentifier::Name(Name::new(pool_name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {pool_name} found")))?;
+    let (uuid, _) = pool
+        .get_filesystem_by_name(&Name::new(fs_name.to_string()))
+        .ok_or_else(|| StratisError::Msg(format!("No filesystem named {fs_name} found")))?;
+    let parent = parent_name
+        .map(|name| {
+            pool.get_filesystem_by_name(&Name::new(name.to_string()))
+                .map(|(parent_uuid, _)| parent_uuid)
+                .ok_or_else(|| StratisError::Msg(format!("No filesystem named {name} found")))
+        })
+        .transpose()?;
+    block_in_place(|| {
+        pool.send_filesystem(uuid, parent, file)?;
+        Ok(true)
+    })
+}
+
+// stratis-min filesystem receive
+pub async fn filesystem_receive<E>(
+    engine: Arc<E>,
+    pool_name: &str,
+    fs_name: &str,
+    incremental: bool,
+    file: File,
+) -> StratisResult<bool>
+where
+    E: Engine,
+{
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(pool_name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {pool_name} found")))?;
+    let (_, pool_uuid, pool) = guard.as_mut_tuple();
+    if incremental {
+        let (uuid, _) = pool
+            .get_filesystem_by_name(&Name::new(fs_name.to_string()))
+            .ok_or_else(|| StratisError::Msg(format!("No filesystem named {fs_name} found")))?;
+        block_in_place(|| {
+            pool.receive_filesystem_delta(pool_name, uuid, file)?;
+            Ok(true)
+        })
+    } else {
+        block_in_place(|| {
+            pool.receive_filesystem(pool_name, pool_uuid, fs_name, file)?;
+            Ok(true)
+        })
+    }
+}
@@ -5,11 +5,8 @@
 use std::{os::unix::io::RawFd, sync::Arc};
 
 use crate::{
-    engine::{
-        Engine, KeyActions, KeyDescription, MappingCreateAction, MappingDeleteAction, Pool,
-        PoolIdentifier, PoolUuid,
-    },
-    stratis::{StratisError, StratisResult},
+    engine::{Engine, KeyActions, KeyDescription, MappingCreateAction, MappingDeleteAction},
+    stratis::StratisResult,
 };
 
 // stratis-min key set
@@ -53,30 +50,3 @@ where
         .into_iter()
         .collect())
 }
-
-pub async fn key_get_desc<E>(
-    engine: Arc<E>,
-    id: PoolIdentifier<PoolUuid>,
-) -> StratisResult<Option<KeyDescription>>
-where
-    E: Engine,
-{
-    let locked = engine.locked_pools().await;
-    let guard = engine.get_pool(id.clone()).await;
-    if let Some((_, _, pool)) = guard.as_ref().map(|guard| guard.as_tuple()) {
-        match pool.encryption_info() {
-            Some(ei) => ei.key_description().map(|opt| opt.cloned()),
-            None => Ok(None),
-        }
-    } else if let Some(info) = locked.locked.get(match id {
-        PoolIdentifier::Uuid(ref u) => u,
-        PoolIdentifier::Name(ref n) => locked
-            .name_to_uuid
-            .get(n)
-            .ok_or_else(|| StratisError::Msg(format!("Pool with name {n} not found")))?,
-    }) {
-        info.info.key_description().map(|opt| opt.cloned())
-    } else {
-        Err(StratisError::Msg(format!("Pool with {id} not found")))
-    }
-}
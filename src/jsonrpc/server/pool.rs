@@ -8,13 +8,11 @@ use tokio::task::block_in_place;
 
 use crate::{
     engine::{
-        BlockDevTier, CreateAction, EncryptionInfo, Engine, EngineAction, Name, Pool,
-        PoolIdentifier, PoolUuid, RenameAction, UnlockMethod,
-    },
-    jsonrpc::{
-        interface::PoolListType,
-        server::key::{key_get_desc, key_set},
+        BlockDev, BlockDevTier, CacheConfig, CreateAction, EncryptionConfig, EncryptionInfo,
+        Engine, EngineAction, Name, Pool, PoolCreateOptions, PoolIdentifier, PoolUuid,
+        RenameAction, UnlockMethod,
     },
+    jsonrpc::interface::PoolListType,
     stratis::{StratisError, StratisResult},
 };
 
@@ -23,16 +21,16 @@ pub async fn pool_start<E>(
     engine: Arc<E>,
     id: PoolIdentifier<PoolUuid>,
     unlock_method: Option<UnlockMethod>,
-    prompt: Option<RawFd>,
+    allow_missing: bool,
+    passphrase_fd: Option<RawFd>,
 ) -> StratisResult<bool>
 where
     E: Engine,
 {
-    if let (Some(fd), Some(kd)) = (prompt, key_get_desc(engine.clone(), id.clone()).await?) {
-        key_set(engine.clone(), &kd, fd).await?;
-    }
-
-    Ok(engine.start_pool(id, unlock_method).await?.is_changed())
+    Ok(engine
+        .start_pool(id, unlock_method, passphrase_fd, allow_missing)
+        .await?
+        .is_changed())
 }
 
 // stratis-min pool stop
@@ -60,18 +58,132 @@ pub async fn pool_create<E>(
     name: &str,
     blockdev_paths: &[&Path],
     enc_info: Option<&EncryptionInfo>,
+    enc_config: Option<&EncryptionConfig>,
 ) -> StratisResult<bool>
 where
     E: Engine,
 {
     Ok(
-        match engine.create_pool(name, blockdev_paths, enc_info).await? {
+        match engine
+            .create_pool(
+                name,
+                blockdev_paths,
+                PoolCreateOptions {
+                    encryption_info: enc_info,
+                    encryption_config: enc_config,
+                    ..Default::default()
+                },
+            )
+            .await?
+        {
             CreateAction::Created(_) => true,
             CreateAction::Identity => false,
         },
     )
 }
 
+// stratis-min pool remove-cache
+pub async fn pool_remove_cache<E>(engine: Arc<E>, name: &str) -> StratisResult<bool>
+where
+    E: Engine,
+{
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (_, uuid, pool) = guard.as_mut_tuple();
+    block_in_place(|| Ok(pool.remove_cache(uuid, name)?.is_changed()))
+}
+
+// stratis-min pool encrypt
+pub async fn pool_encrypt<E>(
+    engine: Arc<E>,
+    name: &str,
+    enc_info: &EncryptionInfo,
+) -> StratisResult<bool>
+where
+    E: Engine,
+{
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (pool_name, uuid, pool) = guard.as_mut_tuple();
+    block_in_place(|| Ok(pool.encrypt_pool(&pool_name, uuid, enc_info)?.is_changed()))
+}
+
+// stratis-min pool rotate-volume-key
+pub async fn pool_rotate_volume_key<E>(engine: Arc<E>, name: &str) -> StratisResult<bool>
+where
+    E: Engine,
+{
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (_, uuid, pool) = guard.as_mut_tuple();
+    block_in_place(|| pool.rotate_volume_key(uuid).map(|_| true))
+}
+
+// stratis-min pool reencryption-progress
+pub async fn pool_reencryption_progress<E>(
+    engine: Arc<E>,
+    uuid: PoolUuid,
+) -> StratisResult<Option<(u128, u128)>>
+where
+    E: Engine,
+{
+    Ok(engine
+        .reencryption_progress(uuid)
+        .map(|progress| (*progress.reencrypted.bytes(), *progress.total.bytes())))
+}
+
+// stratis-min pool replace-blockdev
+pub async fn pool_replace_blockdev<E>(
+    engine: Arc<E>,
+    name: &str,
+    old: &Path,
+    new: &Path,
+) -> StratisResult<bool>
+where
+    E: Engine,
+{
+    let mut guard = engine
+        .get_mut_pool(PoolIdentifier::Name(Name::new(name.to_owned())))
+        .await
+        .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
+    let (_, uuid, pool) = guard.as_mut_tuple();
+    let old_uuid = pool
+        .blockdevs()
+        .into_iter()
+        .find(|(_, _, bd)| bd.devnode() == old)
+        .map(|(dev_uuid, _, _)| dev_uuid)
+        .ok_or_else(|| {
+            StratisError::Msg(format!(
+                "No device {} found in pool named {name}",
+                old.display()
+            ))
+        })?;
+    block_in_place(|| {
+        Ok(pool
+            .replace_blockdev(name, uuid, old_uuid, new)?
+            .is_changed())
+    })
+}
+
+// stratis-min pool replace-progress
+pub async fn pool_replace_progress<E>(
+    engine: Arc<E>,
+    uuid: PoolUuid,
+) -> StratisResult<Option<(u128, u128)>>
+where
+    E: Engine,
+{
+    Ok(engine
+        .replace_progress(uuid)
+        .map(|progress| (*progress.copied.bytes(), *progress.total.bytes())))
+}
+
 // stratis-min pool destroy
 pub async fn pool_destroy<E>(engine: Arc<E>, name: &str) -> StratisResult<bool>
 where
@@ -95,7 +207,11 @@ where
         .await
         .ok_or_else(|| StratisError::Msg(format!("No pool named {name} found")))?;
     let (_, uuid, pool) = guard.as_mut_tuple();
-    block_in_place(|| Ok(pool.init_cache(uuid, name, paths, true)?.is_changed()))
+    block_in_place(|| {
+        Ok(pool
+            .init_cache(uuid, name, paths, &CacheConfig::default(), true)?
+            .is_changed())
+    })
 }
 
 // stratis-min pool rename
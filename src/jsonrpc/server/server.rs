@@ -5,10 +5,10 @@
 #[cfg(feature = "systemd_compat")]
 use std::collections::HashMap;
 use std::{
-    fs::{create_dir_all, remove_file},
+    fs::{create_dir_all, remove_file, File},
     future::Future,
     io::{IoSlice, IoSliceMut},
-    os::unix::io::{AsRawFd, RawFd},
+    os::unix::io::{AsRawFd, FromRawFd, RawFd},
     path::Path,
     pin::Pin,
     sync::Arc,
@@ -69,7 +69,7 @@ impl StratisParams {
                     Vec::new(),
                 )))
             }
-            StratisParamType::PoolCreate(name, paths, encryption_info) => {
+            StratisParamType::PoolCreate(name, paths, encryption_info, encryption_config) => {
                 expects_fd!(self.fd_opt, false);
                 let path_ref: Vec<_> = paths.iter().map(|p| p.as_path()).collect();
                 Ok(StratisRet::PoolCreate(stratis_result_to_return(
@@ -78,6 +78,7 @@ impl StratisParams {
                         name.as_str(),
                         path_ref.as_slice(),
                         encryption_info.as_ref(),
+                        encryption_config.as_ref(),
                     )
                     .await,
                     false,
@@ -114,6 +115,50 @@ impl StratisParams {
                     false,
                 )))
             }
+            StratisParamType::PoolRemoveCache(name) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolRemoveCache(stratis_result_to_return(
+                    pool::pool_remove_cache(engine, name.as_str()).await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolEncrypt(name, enc_info) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolEncrypt(stratis_result_to_return(
+                    pool::pool_encrypt(engine, name.as_str(), &enc_info).await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolRotateVolumeKey(name) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolRotateVolumeKey(stratis_result_to_return(
+                    pool::pool_rotate_volume_key(engine, name.as_str()).await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolReencryptionProgress(uuid) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolReencryptionProgress(
+                    stratis_result_to_return(
+                        pool::pool_reencryption_progress(engine, uuid).await,
+                        None,
+                    ),
+                ))
+            }
+            StratisParamType::PoolReplaceBlockdev(name, old, new) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolReplaceBlockdev(stratis_result_to_return(
+                    pool::pool_replace_blockdev(engine, name.as_str(), &old, &new).await,
+                    false,
+                )))
+            }
+            StratisParamType::PoolReplaceProgress(uuid) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::PoolReplaceProgress(stratis_result_to_return(
+                    pool::pool_replace_progress(engine, uuid).await,
+                    None,
+                )))
+            }
             StratisParamType::PoolDestroy(name) => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::PoolDestroy(stratis_result_to_return(
@@ -121,9 +166,9 @@ impl StratisParams {
                     false,
                 )))
             }
-            StratisParamType::PoolStart(id, unlock_method) => {
+            StratisParamType::PoolStart(id, unlock_method, allow_missing) => {
                 Ok(StratisRet::PoolStart(stratis_result_to_return(
-                    pool::pool_start(engine, id, unlock_method, self.fd_opt).await,
+                    pool::pool_start(engine, id, unlock_method, allow_missing, self.fd_opt).await,
                     false,
                 )))
             }
@@ -200,6 +245,38 @@ impl StratisParams {
                     false,
                 )))
             }
+            StratisParamType::FsRevert(pool_name, fs_name, snapshot_name) => {
+                expects_fd!(self.fd_opt, false);
+                Ok(StratisRet::FsRevert(stratis_result_to_return(
+                    filesystem::filesystem_revert(engine, &pool_name, &fs_name, &snapshot_name)
+                        .await,
+                    false,
+                )))
+            }
+            StratisParamType::FsSend(pool_name, fs_name, parent_name) => {
+                // The file descriptor was received with the request and is
+                // owned by it, so that it is closed when the file is dropped.
+                let file = unsafe { File::from_raw_fd(expects_fd!(self.fd_opt, true)) };
+                Ok(StratisRet::FsSend(stratis_result_to_return(
+                    filesystem::filesystem_send(
+                        engine,
+                        &pool_name,
+                        &fs_name,
+                        parent_name.as_deref(),
+                        file,
+                    )
+                    .await,
+                    false,
+                )))
+            }
+            StratisParamType::FsReceive(pool_name, fs_name, incremental) => {
+                let file = unsafe { File::from_raw_fd(expects_fd!(self.fd_opt, true)) };
+                Ok(StratisRet::FsReceive(stratis_result_to_return(
+                    filesystem::filesystem_receive(engine, &pool_name, &fs_name, incremental, file)
+                        .await,
+                    false,
+                )))
+            }
             StratisParamType::Report => {
                 expects_fd!(self.fd_opt, false);
                 Ok(StratisRet::Report(report::report(engine).await))
@@ -6,6 +6,7 @@ pub use self::{
     errors::{StratisError, StratisResult},
     run::run,
     stratis::VERSION,
+    timer::parse_fstrim_interval,
 };
 
 mod dm;
@@ -4,9 +4,12 @@
 
 //! Main loop
 
-use std::sync::{
-    atomic::{AtomicUsize, Ordering},
-    Arc,
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
 use tokio::{
@@ -48,8 +51,10 @@ async fn signal_thread() -> StratisResult<()> {
 /// Initialize the engine and keep it running until a signal is received
 /// or a fatal error is encountered.
 /// If sim is true, start the sim engine rather than the real engine.
+/// If fstrim_interval is set, discard the unused blocks of all mounted
+/// filesystems at that interval.
 /// Always check for devicemapper context.
-pub fn run(sim: bool) -> StratisResult<()> {
+pub fn run(sim: bool, fstrim_interval: Option<Duration>) -> StratisResult<()> {
     if !sim {
         // If stratisd is running as PID 1, then it is definitely in a container and the
         // unshare_mount_namespace() command method will fail silently, since the mount namespace will
@@ -88,7 +93,7 @@ pub fn run(sim: bool) -> StratisResult<()> {
         })
         .build()?;
     runtime.block_on(async move {
-        async fn start_threads<E>(engine: Arc<E>, sim: bool) -> StratisResult<()> where E: 'static + Engine {
+        async fn start_threads<E>(engine: Arc<E>, sim: bool, fstrim_interval: Option<Duration>) -> StratisResult<()> where E: 'static + Engine {
             let (trigger, should_exit) = channel(1);
             let (udev_sender, udev_receiver) = unbounded_channel::<UdevEngineEvent>();
             #[cfg(feature = "dbus_enabled")]
@@ -115,6 +120,7 @@ pub fn run(sim: bool) -> StratisResult<()> {
             );
             let join_timer = run_timers(
                 engine,
+                fstrim_interval,
                 #[cfg(feature = "dbus_enabled")]
                 dbus_sender,
             );
@@ -162,7 +168,7 @@ pub fn run(sim: bool) -> StratisResult<()> {
         info!("stratis daemon version {} started", VERSION);
         if sim {
             info!("Using SimEngine");
-            start_threads(Arc::new(SimEngine::default()), sim).await
+            start_threads(Arc::new(SimEngine::default()), sim, fstrim_interval).await
         } else {
             info!("Using StratEngine");
             start_threads(
@@ -173,7 +179,8 @@ pub fn run(sim: bool) -> StratisResult<()> {
                         return Err(e);
                     }
                 }),
-                sim
+                sim,
+                fstrim_interval,
             ).await
         }
     })?;
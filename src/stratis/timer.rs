@@ -6,7 +6,7 @@ use std::{sync::Arc, time::Duration};
 
 #[cfg(feature = "dbus_enabled")]
 use tokio::sync::mpsc::UnboundedSender;
-use tokio::{task::spawn, time::sleep};
+use tokio::{task::spawn, time::sleep, try_join};
 
 #[cfg(feature = "dbus_enabled")]
 use crate::dbus_api::DbusAction;
@@ -72,21 +72,162 @@ async fn check_pool_and_fs<E>(
     }
 }
 
+/// Discards the unused blocks of all mounted filesystems once every interval,
+/// so that space freed by the filesystems is returned to their pools and, if
+/// a pool passes discards down, to the devices beneath it.
+async fn trim_filesystems<E>(engine: Arc<E>, interval: Duration)
+where
+    E: Engine,
+{
+    loop {
+        sleep(interval).await;
+        trace!("Starting timed filesystem trim");
+        for (uuid, res) in engine.trim_filesystems().await {
+            match res {
+                Ok(trimmed) => debug!(
+                    "Discarded {} of unused blocks of filesystem with UUID {}",
+                    trimmed, uuid
+                ),
+                Err(e) => warn!("Failed to trim filesystem with UUID {}: {}", uuid, e),
+            }
+        }
+        trace!("Timed filesystem trim finished");
+    }
+}
+
+/// Recalculates the exclusive and shared space usage of all filesystems once
+/// a minute. This is done on its own timer because reading the thin pool
+/// metadata is too expensive to do as often as the usage checks.
+async fn update_space_usage<E>(
+    engine: Arc<E>,
+    #[cfg(feature = "dbus_enabled")] sender: UnboundedSender<DbusAction<E>>,
+) where
+    E: Engine,
+{
+    loop {
+        trace!("Starting filesystem space usage update");
+        #[cfg(feature = "min")]
+        {
+            let _ = engine.update_space_usage().await;
+        }
+        #[cfg(feature = "dbus_enabled")]
+        {
+            let fs_diffs = engine.update_space_usage().await;
+            for action in DbusAction::from_fs_diffs(fs_diffs) {
+                if let Err(e) = sender.send(action) {
+                    warn!(
+                        "Failed to update D-Bus API with information on changed properties: {}",
+                        e
+                    );
+                }
+            }
+        }
+        trace!("Filesystem space usage update finished");
+        sleep(Duration::from_secs(60)).await;
+    }
+}
+
+/// Applies the snapshot policies of all filesystems once a minute, taking
+/// the snapshots that are due and destroying those that are no longer kept.
+async fn run_snapshot_policies<E>(
+    engine: Arc<E>,
+    #[cfg(feature = "dbus_enabled")] sender: UnboundedSender<DbusAction<E>>,
+) where
+    E: Engine,
+{
+    loop {
+        sleep(Duration::from_secs(60)).await;
+        trace!("Starting scheduled snapshots");
+        for (uuid, res) in engine.run_snapshot_policies().await {
+            match res {
+                Ok(scheduled) => {
+                    debug!(
+                        "Created {} and destroyed {} scheduled snapshots in pool with UUID {}",
+                        scheduled.created.len(),
+                        scheduled.destroyed.len(),
+                        uuid
+                    );
+                    #[cfg(feature = "dbus_enabled")]
+                    {
+                        if !scheduled.is_empty() {
+                            if let Err(e) =
+                                sender.send(DbusAction::ScheduledSnapshots(uuid, scheduled))
+                            {
+                                warn!(
+                                    "Failed to update D-Bus API with information on scheduled snapshots: {}",
+                                    e
+                                );
+                            }
+                        }
+                    }
+                }
+                Err(e) => warn!(
+                    "Failed to apply snapshot policies in pool with UUID {}: {}",
+                    uuid, e
+                ),
+            }
+        }
+        trace!("Scheduled snapshots finished");
+    }
+}
+
+/// Parse the interval between runs of the trim of all mounted filesystems,
+/// a positive number of hours, as given on the command line.
+pub fn parse_fstrim_interval(hours: &str) -> Result<Duration, String> {
+    hours
+        .parse::<u64>()
+        .ok()
+        .filter(|hours| *hours > 0)
+        .and_then(|hours| hours.checked_mul(60 * 60))
+        .map(Duration::from_secs)
+        .ok_or_else(|| {
+            format!(
+                "must be a positive number of hours no greater than {}",
+                u64::MAX / (60 * 60)
+            )
+        })
+}
+
 /// Run all timed background tasks.
 ///
-/// Currently runs a timer to check thin pool and filesystem usage.
+/// Runs a timer to check thin pool and filesystem usage, a timer to update
+/// the space usage of filesystems, a timer to apply snapshot policies and,
+/// if fstrim_interval is set, a timer to trim all mounted filesystems.
 pub async fn run_timers<E>(
     engine: Arc<E>,
+    fstrim_interval: Option<Duration>,
     #[cfg(feature = "dbus_enabled")] sender: UnboundedSender<DbusAction<E>>,
 ) -> StratisResult<()>
 where
     E: 'static + Engine,
 {
-    spawn(check_pool_and_fs(
-        engine,
+    let join_check = spawn(check_pool_and_fs(
+        Arc::clone(&engine),
+        #[cfg(feature = "dbus_enabled")]
+        sender.clone(),
+    ));
+    let join_space_usage = spawn(update_space_usage(
+        Arc::clone(&engine),
+        #[cfg(feature = "dbus_enabled")]
+        sender.clone(),
+    ));
+    let join_snapshots = spawn(run_snapshot_policies(
+        Arc::clone(&engine),
         #[cfg(feature = "dbus_enabled")]
         sender,
-    ))
-    .await?;
+    ));
+    match fstrim_interval {
+        Some(interval) => {
+            try_join!(
+                join_check,
+                join_space_usage,
+                join_snapshots,
+                spawn(trim_filesystems(engine, interval))
+            )?;
+        }
+        None => {
+            try_join!(join_check, join_space_usage, join_snapshots)?;
+        }
+    }
     Ok(())
 }